@@ -1,38 +1,69 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
 use bonsaidb_core::api;
 use bonsaidb_core::api::ApiName;
-use bonsaidb_core::networking::CURRENT_PROTOCOL_VERSION;
+#[cfg(feature = "token-authentication")]
+use bonsaidb_core::connection::SensitiveString;
+#[cfg(not(target_arch = "wasm32"))]
+use bonsaidb_core::keyvalue::KeyValue;
+use bonsaidb_core::networking::{Capability, RequestSigningKey, SUPPORTED_PROTOCOL_VERSIONS};
 #[cfg(not(target_arch = "wasm32"))]
 use fabruic::Certificate;
 #[cfg(not(target_arch = "wasm32"))]
 use tokio::runtime::Handle;
 use url::Url;
 
-use crate::client::{AnyApiCallback, ApiCallback};
+#[cfg(feature = "token-authentication")]
+use crate::client::ConnectionToken;
+use crate::client::{AnyApiCallback, ApiCallback, DEFAULT_MAX_IN_FLIGHT_REQUESTS};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::outbox::{ConflictCallback, Outbox, OutboxConflict};
 #[cfg(not(target_arch = "wasm32"))]
 use crate::BlockingClient;
+#[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+use crate::ProxyConfig;
 use crate::{AsyncClient, Error};
 
 pub struct Async;
 #[cfg(not(target_arch = "wasm32"))]
 pub struct Blocking;
 
+#[cfg(not(target_arch = "wasm32"))]
+enum OutboxLocation {
+    File(PathBuf),
+    KeyValue(Box<dyn FnOnce(Option<ConflictCallback>) -> Result<Outbox, Error> + Send>),
+}
+
 /// Builder for a [`BlockingClient`] or an [`AsyncClient`].
 #[must_use]
 pub struct Builder<AsyncMode> {
     url: Url,
-    protocol_version: &'static str,
+    protocol_versions: &'static [&'static str],
     custom_apis: HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>,
     connect_timeout: Option<Duration>,
     request_timeout: Option<Duration>,
+    request_signing_key: Option<RequestSigningKey>,
+    required_capabilities: Vec<Capability>,
+    max_in_flight_requests: usize,
+    #[cfg(feature = "token-authentication")]
+    token: Option<ConnectionToken>,
+    #[cfg(not(target_arch = "wasm32"))]
+    outbox: Option<OutboxLocation>,
+    #[cfg(not(target_arch = "wasm32"))]
+    outbox_conflict_callback: Option<ConflictCallback>,
+    #[cfg(not(target_arch = "wasm32"))]
+    write_retries: usize,
     #[cfg(not(target_arch = "wasm32"))]
     certificate: Option<fabruic::Certificate>,
     #[cfg(not(target_arch = "wasm32"))]
     tokio: Option<Handle>,
+    #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+    proxy: Option<ProxyConfig>,
     mode: PhantomData<AsyncMode>,
 }
 
@@ -41,14 +72,27 @@ impl<AsyncMode> Builder<AsyncMode> {
     pub(crate) fn new(url: Url) -> Self {
         Self {
             url,
-            protocol_version: CURRENT_PROTOCOL_VERSION,
+            protocol_versions: SUPPORTED_PROTOCOL_VERSIONS,
             custom_apis: HashMap::new(),
             request_timeout: None,
             connect_timeout: None,
+            request_signing_key: None,
+            required_capabilities: Vec::new(),
+            max_in_flight_requests: DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+            #[cfg(feature = "token-authentication")]
+            token: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            outbox: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            outbox_conflict_callback: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            write_retries: 0,
             #[cfg(not(target_arch = "wasm32"))]
             certificate: None,
             #[cfg(not(target_arch = "wasm32"))]
             tokio: None,
+            #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+            proxy: None,
             mode: PhantomData,
         }
     }
@@ -87,11 +131,12 @@ impl<AsyncMode> Builder<AsyncMode> {
         self
     }
 
-    /// Overrides the protocol version. Only for testing purposes.
+    /// Overrides the protocol versions offered to the server, ordered from
+    /// newest to oldest. Only for testing purposes.
     #[cfg(feature = "test-util")]
     #[allow(clippy::missing_const_for_fn)]
-    pub fn with_protocol_version(mut self, version: &'static str) -> Self {
-        self.protocol_version = version;
+    pub fn with_protocol_versions(mut self, versions: &'static [&'static str]) -> Self {
+        self.protocol_versions = versions;
         self
     }
 
@@ -112,17 +157,186 @@ impl<AsyncMode> Builder<AsyncMode> {
         self
     }
 
+    /// Signs all requests sent by this client using `key`. The server must be
+    /// configured with the same key.
+    ///
+    /// This requires [`Capability::REQUEST_SIGNING`]: if the server doesn't
+    /// verify signatures, the connection is closed and the pending request
+    /// returns [`Error::MissingCapabilities`].
+    pub fn with_request_signing_key(mut self, key: RequestSigningKey) -> Self {
+        self.request_signing_key = Some(key);
+        self.with_required_capability(Capability::REQUEST_SIGNING)
+    }
+
+    /// Requires that servers this client connects to support `capability`.
+    ///
+    /// Each time a connection is established, the client asks the server for
+    /// its capabilities. If the server doesn't support every required
+    /// capability, the connection is closed and the pending request returns
+    /// [`Error::MissingCapabilities`]. This prevents an app from silently
+    /// using a server that lacks a feature it depends on, such as when
+    /// servers of different versions are behind a load balancer.
+    pub fn with_required_capability(mut self, capability: Capability) -> Self {
+        if !self.required_capabilities.contains(&capability) {
+            self.required_capabilities.push(capability);
+        }
+        self
+    }
+
+    /// Authenticates each connection using the
+    /// [`AuthenticationToken`](bonsaidb_core::admin::AuthenticationToken) with
+    /// `id` while the connection is being established.
+    ///
+    /// Requests sent by clients that haven't authenticated are performed
+    /// using the token's identity, removing the need to call
+    /// [`authenticate_with_token()`](bonsaidb_core::connection::AsyncStorageConnection::authenticate_with_token).
+    /// If the server rejects the token, the connection is closed and the
+    /// pending request returns an error.
+    ///
+    /// The token can also be provided in the url as
+    /// `bonsaidb://<id>:<token>@host`, with the token percent-encoded. If both
+    /// are provided, this token is used.
+    #[cfg(feature = "token-authentication")]
+    pub fn with_token(mut self, id: u64, token: SensitiveString) -> Self {
+        self.token = Some(ConnectionToken { id, token });
+        self
+    }
+
+    /// Journals requests that modify data to the file at `path` before they
+    /// are sent. If the file doesn't exist, it will be created.
+    ///
+    /// Document pushes, updates, and deletes are journaled, as are key-value
+    /// operations other than gets. Read requests are never journaled. Each
+    /// operation is marked complete once the server responds to it, and
+    /// operations the server did not acknowledge -- including those queued
+    /// before the process was restarted -- are replayed in order when the
+    /// client is created and each time it reconnects. They can also be
+    /// replayed using [`AsyncClient::flush_outbox()`].
+    ///
+    /// Records are written to the file without being synced to disk. They
+    /// survive the process exiting, but may be lost if the operating system
+    /// crashes.
+    ///
+    /// Each operation is assigned a random id when it is journaled. The server
+    /// remembers the ids of operations that succeeded within its idempotency
+    /// window, ensuring that an operation is not applied twice if the original
+    /// request was executed but its response was lost.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_outbox(mut self, path: impl AsRef<Path>) -> Self {
+        self.outbox = Some(OutboxLocation::File(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Journals requests that modify data to `store` before they are sent,
+    /// such as a local `bonsaidb-local` database. The operations are stored
+    /// in the [`OUTBOX_NAMESPACE`](crate::OUTBOX_NAMESPACE) Key-Value
+    /// namespace. Otherwise, this behaves the same as
+    /// [`with_outbox()`](Self::with_outbox).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_outbox_store<KV: KeyValue + 'static>(mut self, store: KV) -> Self {
+        self.outbox = Some(OutboxLocation::KeyValue(Box::new(move |on_conflict| {
+            Outbox::open_key_value(store, on_conflict)
+        })));
+        self
+    }
+
+    /// Invokes `callback` when an operation replayed from this client's
+    /// outbox is rejected by the server, such as when a document was modified
+    /// by another client while the operation was queued.
+    ///
+    /// If not specified, rejected operations are logged.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_outbox_conflict_callback<F: Fn(OutboxConflict) + Send + Sync + 'static>(
+        mut self,
+        callback: F,
+    ) -> Self {
+        self.outbox_conflict_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Retries requests that modify data up to `retries` times if the
+    /// connection to the server is lost or the request times out before a
+    /// response is received.
+    ///
+    /// Each write is sent as an
+    /// [`IdempotentRequest`](bonsaidb_core::networking::IdempotentRequest)
+    /// with a random operation id, and every retry reuses the same id. If the
+    /// original request was executed but its response was lost, the server
+    /// responds to the retry with the original result rather than executing
+    /// the write again. Servers remember the results of operations for their
+    /// `idempotency_window`, which defaults to 24 hours.
+    ///
+    /// Retries are delayed using an exponential backoff with jitter, starting
+    /// at 50 milliseconds and doubling with each retry up to 5 seconds.
+    ///
+    /// The same requests are retried as are journaled by
+    /// [`with_outbox()`](Self::with_outbox). Writes journaled to an outbox
+    /// are not retried, as they are replayed when the client reconnects. The
+    /// default is 0, which disables retrying.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_write_retries(mut self, retries: usize) -> Self {
+        self.write_retries = retries;
+        self
+    }
+
+    /// Limits the number of requests this client can have waiting for a
+    /// response to `limit`. Once the limit is reached, sending another request
+    /// waits until an earlier request receives its response or times out,
+    /// applying backpressure to the code issuing requests rather than queueing
+    /// them without bound. The time spent waiting does not count towards the
+    /// request timeout.
+    ///
+    /// The limit is shared by all clones of the client. A `limit` of 0 is
+    /// treated as 1. The default is 1,024.
+    pub fn with_max_in_flight_requests(mut self, limit: usize) -> Self {
+        self.max_in_flight_requests = limit;
+        self
+    }
+
+    /// Establishes WebSocket connections through `proxy`. The proxy is
+    /// asked to connect to the server's host and port, and the TLS and
+    /// WebSocket handshakes are performed over the resulting tunnel.
+    ///
+    /// BonsaiDb protocol-based (`bonsaidb://`) connections use QUIC, which
+    /// can't be tunneled through HTTP or SOCKS5 proxies. They ignore this
+    /// setting and always connect directly.
+    #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
     fn finish_internal(self) -> Result<AsyncClient, Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let outbox = match self.outbox {
+            Some(OutboxLocation::File(path)) => {
+                Some(Outbox::open(&path, self.outbox_conflict_callback)?)
+            }
+            Some(OutboxLocation::KeyValue(open)) => Some(open(self.outbox_conflict_callback)?),
+            None => None,
+        };
         AsyncClient::new_from_parts(
             self.url,
-            self.protocol_version,
+            self.protocol_versions,
             self.custom_apis,
             self.connect_timeout,
             self.request_timeout,
+            self.request_signing_key,
+            self.required_capabilities,
+            self.max_in_flight_requests,
+            #[cfg(feature = "token-authentication")]
+            self.token,
+            #[cfg(not(target_arch = "wasm32"))]
+            outbox,
+            #[cfg(not(target_arch = "wasm32"))]
+            self.write_retries,
             #[cfg(not(target_arch = "wasm32"))]
             self.certificate,
             #[cfg(not(target_arch = "wasm32"))]
             self.tokio.or_else(|| Handle::try_current().ok()),
+            #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+            self.proxy,
         )
     }
 }
@@ -7,19 +7,30 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
-use bonsaidb_core::admin::{Admin, ADMIN_DATABASE_NAME};
+#[cfg(feature = "token-authentication")]
+use bonsaidb_core::admin::AuthenticationToken;
+use bonsaidb_core::admin::{Admin, DatabaseQuota, ADMIN_DATABASE_NAME};
 use bonsaidb_core::api::{self, Api, ApiName};
 use bonsaidb_core::arc_bytes::serde::Bytes;
 use bonsaidb_core::arc_bytes::OwnedBytes;
 use bonsaidb_core::connection::{
-    AsyncStorageConnection, Database, HasSession, IdentityReference, Session,
+    AsyncStorageConnection, Database, HasSession, IdentityReference, Session, SessionId,
 };
+#[cfg(feature = "token-authentication")]
+use bonsaidb_core::connection::{
+    Authentication, SensitiveString, SessionAuthentication, TokenChallengeAlgorithm,
+};
+#[cfg(feature = "token-authentication")]
+use bonsaidb_core::networking::AuthenticateConnection;
 use bonsaidb_core::networking::{
-    AlterUserPermissionGroupMembership, AlterUserRoleMembership, AssumeIdentity, CreateDatabase,
-    CreateUser, DeleteDatabase, DeleteUser, ListAvailableSchemas, ListDatabases, LogOutSession,
-    MessageReceived, Payload, UnregisterSubscriber, CURRENT_PROTOCOL_VERSION,
+    AlterUserPermissionGroupMembership, AlterUserRoleMembership, AssumeCappedIdentity,
+    AssumeIdentity, Capability, CreateDatabase, CreatePermissionGroup, CreateRole, CreateUser,
+    DatabaseRequest, DeleteDatabase, DeleteRole, DeleteUser, IdempotentRequest,
+    ListAvailableSchemas, ListCapabilities, ListDatabases, LogOutSession, MessageReceived, Payload,
+    RequestSigningKey, SetDatabaseQuota, UnregisterSubscriber, UpdatePermissionGroupStatements,
+    SUPPORTED_PROTOCOL_VERSIONS,
 };
-use bonsaidb_core::permissions::Permissions;
+use bonsaidb_core::permissions::{Permissions, Statement};
 use bonsaidb_core::schema::{Nameable, Schema, SchemaName, SchemaSummary, Schematic};
 use bonsaidb_utils::fast_async_lock;
 use flume::Sender;
@@ -35,6 +46,8 @@ pub use self::remote_database::{AsyncRemoteDatabase, AsyncRemoteSubscriber};
 pub use self::sync::{BlockingClient, BlockingRemoteDatabase, BlockingRemoteSubscriber};
 use crate::builder::Async;
 use crate::error::Error;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::outbox::{self, Outbox};
 use crate::{ApiError, Builder};
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -251,11 +264,21 @@ impl PartialEq for AsyncClient {
 #[derive(Debug)]
 pub struct Data {
     request_sender: Sender<PendingRequest>,
+    in_flight: InFlightLimit,
     effective_permissions: Mutex<Option<Permissions>>,
     schemas: Mutex<HashMap<TypeId, Arc<Schematic>>>,
+    /// The newest transaction applied or observed in each database using
+    /// this connection.
+    last_seen_transaction_ids: Mutex<HashMap<String, u64>>,
     connection_counter: Arc<AtomicU32>,
     request_id: AtomicU32,
     subscribers: SubscriberMap,
+    request_signing_key: Option<RequestSigningKey>,
+    handshake: Handshake,
+    #[cfg(not(target_arch = "wasm32"))]
+    outbox: Option<Outbox>,
+    #[cfg(not(target_arch = "wasm32"))]
+    write_retries: usize,
 }
 
 impl AsyncClient {
@@ -282,14 +305,25 @@ impl AsyncClient {
     pub fn new(url: Url) -> Result<Self, Error> {
         Self::new_from_parts(
             url,
-            CURRENT_PROTOCOL_VERSION,
+            SUPPORTED_PROTOCOL_VERSIONS,
             HashMap::default(),
             None,
             None,
+            None,
+            Vec::new(),
+            DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+            #[cfg(feature = "token-authentication")]
+            None,
+            #[cfg(not(target_arch = "wasm32"))]
+            None,
+            #[cfg(not(target_arch = "wasm32"))]
+            0,
             #[cfg(not(target_arch = "wasm32"))]
             None,
             #[cfg(not(target_arch = "wasm32"))]
             Handle::try_current().ok(),
+            #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+            None,
         )
     }
 
@@ -309,14 +343,25 @@ impl AsyncClient {
     /// "retry-to-recover" design, or "abort-and-fail" depending on how critical
     /// the database is to operation.
     pub(crate) fn new_from_parts(
-        url: Url,
-        protocol_version: &'static str,
+        #[cfg_attr(not(feature = "token-authentication"), allow(unused_mut))] mut url: Url,
+        protocol_versions: &'static [&'static str],
         mut custom_apis: HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>,
         connect_timeout: Option<Duration>,
         request_timeout: Option<Duration>,
+        request_signing_key: Option<RequestSigningKey>,
+        required_capabilities: Vec<Capability>,
+        max_in_flight_requests: usize,
+        #[cfg(feature = "token-authentication")] token: Option<ConnectionToken>,
+        #[cfg(not(target_arch = "wasm32"))] outbox: Option<Outbox>,
+        #[cfg(not(target_arch = "wasm32"))] write_retries: usize,
         #[cfg(not(target_arch = "wasm32"))] certificate: Option<fabruic::Certificate>,
         #[cfg(not(target_arch = "wasm32"))] tokio: Option<Handle>,
+        #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))] proxy: Option<
+            crate::ProxyConfig,
+        >,
     ) -> Result<Self, Error> {
+        #[cfg(feature = "token-authentication")]
+        let token = token.or(ConnectionToken::take_from_url(&mut url)?);
         let subscribers = SubscriberMap::default();
         let callback_subscribers = subscribers.clone();
         custom_apis.insert(
@@ -341,51 +386,127 @@ impl AsyncClient {
                 },
             ))),
         );
+        // Each time a connection is established, operations remaining in the
+        // outbox are replayed.
+        #[cfg(not(target_arch = "wasm32"))]
+        let (connected_sender, connected_receiver) = if outbox.is_some() {
+            let (sender, receiver) = flume::bounded(1);
+            (Some(sender), Some(receiver))
+        } else {
+            (None, None)
+        };
+        #[cfg(target_arch = "wasm32")]
+        let connected_sender: Option<flume::Sender<()>> = None;
         // Default timeouts to 1 minute.
         let connection = ConnectionInfo {
             url,
             subscribers,
             connect_timeout: connect_timeout.unwrap_or(Duration::from_secs(60)),
             request_timeout: request_timeout.unwrap_or(Duration::from_secs(60)),
+            handshake: Handshake::new(
+                required_capabilities,
+                request_signing_key.clone(),
+                connected_sender,
+                #[cfg(feature = "token-authentication")]
+                token,
+            ),
+            max_in_flight_requests,
+            #[cfg(not(target_arch = "wasm32"))]
+            write_retries,
+            #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+            proxy,
         };
-        match connection.url.scheme() {
+        #[cfg(not(target_arch = "wasm32"))]
+        let replay_tokio = tokio.clone();
+        let client = match connection.url.scheme() {
             #[cfg(not(target_arch = "wasm32"))]
-            "bonsaidb" => Ok(Self::new_bonsai_client(
+            "bonsaidb" => Self::new_bonsai_client(
                 connection,
-                protocol_version,
+                protocol_versions,
                 certificate,
                 custom_apis,
+                request_signing_key,
+                outbox,
                 tokio,
-            )),
+            ),
             #[cfg(feature = "websockets")]
-            "wss" | "ws" => Ok(Self::new_websocket_client(
+            "wss" | "ws" => Self::new_websocket_client(
                 connection,
-                protocol_version,
+                protocol_versions,
                 custom_apis,
+                request_signing_key,
+                #[cfg(not(target_arch = "wasm32"))]
+                outbox,
                 #[cfg(not(target_arch = "wasm32"))]
                 tokio,
-            )),
-            other => Err(Error::InvalidUrl(format!("unsupported scheme {other}"))),
+            ),
+            other => return Err(Error::InvalidUrl(format!("unsupported scheme {other}"))),
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(connected) = connected_receiver {
+            client.spawn_outbox_replay(connected, replay_tokio);
         }
+        Ok(client)
+    }
+
+    /// Replays the outbox when this client is created and each time a
+    /// connection is established, until the client is dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn_outbox_replay(&self, connected: flume::Receiver<()>, tokio: Option<Handle>) {
+        let data = Arc::downgrade(&self.data);
+        let request_timeout = self.request_timeout;
+        // Operations left in the outbox by a previous process are replayed
+        // immediately, which also establishes the connection.
+        let mut replay = self.data.outbox.as_ref().map_or(false, Outbox::has_pending);
+        sync::spawn_client(
+            async move {
+                loop {
+                    if replay {
+                        let Some(data) = data.upgrade() else {
+                            break;
+                        };
+                        let client = Self {
+                            data,
+                            session: ClientSession::default(),
+                            request_timeout,
+                        };
+                        if let Err(err) = client.flush_outbox().await {
+                            log::debug!("[client] outbox replay interrupted: {err}");
+                        }
+                    }
+                    if connected.recv_async().await.is_err() {
+                        break;
+                    }
+                    replay = true;
+                }
+                Ok(())
+            },
+            tokio,
+        );
     }
 
     #[cfg(not(target_arch = "wasm32"))]
     fn new_bonsai_client(
         server: ConnectionInfo,
-        protocol_version: &'static str,
+        protocol_versions: &'static [&'static str],
         certificate: Option<fabruic::Certificate>,
         custom_apis: HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>,
+        request_signing_key: Option<RequestSigningKey>,
+        outbox: Option<Outbox>,
         tokio: Option<Handle>,
     ) -> Self {
         let (request_sender, request_receiver) = flume::unbounded();
+        let in_flight = InFlightLimit::new(server.max_in_flight_requests);
         let connection_counter = Arc::new(AtomicU32::default());
         let request_timeout = server.request_timeout;
         let subscribers = server.subscribers.clone();
+        let handshake = server.handshake.clone();
+        let write_retries = server.write_retries;
 
         sync::spawn_client(
             quic_worker::reconnecting_client_loop(
                 server,
-                protocol_version,
+                protocol_versions,
                 certificate,
                 request_receiver,
                 Arc::new(custom_apis),
@@ -397,11 +518,17 @@ impl AsyncClient {
         Self {
             data: Arc::new(Data {
                 request_sender,
+                in_flight,
                 schemas: Mutex::default(),
+                last_seen_transaction_ids: Mutex::default(),
                 connection_counter,
                 request_id: AtomicU32::default(),
                 effective_permissions: Mutex::default(),
                 subscribers,
+                request_signing_key,
+                handshake,
+                outbox,
+                write_retries,
             }),
             session: ClientSession::default(),
             request_timeout,
@@ -411,19 +538,24 @@ impl AsyncClient {
     #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
     fn new_websocket_client(
         server: ConnectionInfo,
-        protocol_version: &'static str,
+        protocol_versions: &'static [&'static str],
         custom_apis: HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>,
+        request_signing_key: Option<RequestSigningKey>,
+        outbox: Option<Outbox>,
         tokio: Option<Handle>,
     ) -> Self {
         let (request_sender, request_receiver) = flume::unbounded();
+        let in_flight = InFlightLimit::new(server.max_in_flight_requests);
         let connection_counter = Arc::new(AtomicU32::default());
         let request_timeout = server.request_timeout;
         let subscribers = server.subscribers.clone();
+        let handshake = server.handshake.clone();
+        let write_retries = server.write_retries;
 
         sync::spawn_client(
             tungstenite_worker::reconnecting_client_loop(
                 server,
-                protocol_version,
+                protocol_versions,
                 request_receiver,
                 Arc::new(custom_apis),
                 connection_counter.clone(),
@@ -434,11 +566,17 @@ impl AsyncClient {
         Self {
             data: Arc::new(Data {
                 request_sender,
+                in_flight,
                 schemas: Mutex::default(),
+                last_seen_transaction_ids: Mutex::default(),
                 request_id: AtomicU32::default(),
                 connection_counter,
                 effective_permissions: Mutex::default(),
                 subscribers,
+                request_signing_key,
+                handshake,
+                outbox,
+                write_retries,
             }),
             session: ClientSession::default(),
             request_timeout,
@@ -448,18 +586,21 @@ impl AsyncClient {
     #[cfg(all(feature = "websockets", target_arch = "wasm32"))]
     fn new_websocket_client(
         server: ConnectionInfo,
-        protocol_version: &'static str,
+        protocol_versions: &'static [&'static str],
         custom_apis: HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>,
+        request_signing_key: Option<RequestSigningKey>,
     ) -> Self {
         let (request_sender, request_receiver) = flume::unbounded();
+        let in_flight = InFlightLimit::new(server.max_in_flight_requests);
         let connection_counter = Arc::new(AtomicU32::default());
 
         wasm_websocket_worker::spawn_client(
             Arc::new(server.url),
-            protocol_version,
+            protocol_versions,
             request_receiver,
             Arc::new(custom_apis),
             server.subscribers.clone(),
+            server.handshake.clone(),
             connection_counter.clone(),
             None,
             server.connect_timeout,
@@ -471,11 +612,15 @@ impl AsyncClient {
         Self {
             data: Arc::new(Data {
                 request_sender,
+                in_flight,
                 schemas: Mutex::default(),
+                last_seen_transaction_ids: Mutex::default(),
                 request_id: AtomicU32::default(),
                 connection_counter,
                 effective_permissions: Mutex::default(),
                 subscribers: server.subscribers,
+                request_signing_key,
+                handshake: server.handshake,
                 #[cfg(feature = "test-util")]
                 background_task_running,
             }),
@@ -491,13 +636,17 @@ impl AsyncClient {
     ) -> Result<flume::Receiver<Result<Bytes, Error>>, Error> {
         let (result_sender, result_receiver) = flume::bounded(1);
         let id = self.data.request_id.fetch_add(1, Ordering::SeqCst);
+        let mut request = Payload {
+            session_id: self.session.session.id,
+            id: Some(id),
+            name,
+            value: Ok(bytes),
+        };
+        if let Some(key) = &self.data.request_signing_key {
+            request = key.sign(request)?;
+        }
         self.data.request_sender.send(PendingRequest {
-            request: Payload {
-                session_id: self.session.session.id,
-                id: Some(id),
-                name,
-                value: Ok(bytes),
-            },
+            request,
             responder: result_sender,
         })?;
 
@@ -505,6 +654,42 @@ impl AsyncClient {
     }
 
     async fn send_request_async(&self, name: ApiName, bytes: Bytes) -> Result<Bytes, Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(outbox) = &self.data.outbox {
+            if let Some(operation) = outbox.queue(&name, &bytes)? {
+                let result = self
+                    .send_unqueued_request_async(IdempotentRequest::name(), operation.request()?)
+                    .await;
+                outbox.finished(&operation, &result);
+                return result;
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.data.write_retries > 0 && outbox::is_write(&name, &bytes) {
+            let request = outbox::idempotent_request(rand::random(), name, bytes)?;
+            let mut retries = 0;
+            loop {
+                let result = self
+                    .send_unqueued_request_async(IdempotentRequest::name(), request.clone())
+                    .await;
+                if retries == self.data.write_retries || outbox::is_acknowledged(&result) {
+                    return result;
+                }
+                retries += 1;
+                tokio::time::sleep(outbox::retry_delay(retries)).await;
+            }
+        }
+
+        self.send_unqueued_request_async(name, bytes).await
+    }
+
+    async fn send_unqueued_request_async(
+        &self,
+        name: ApiName,
+        bytes: Bytes,
+    ) -> Result<Bytes, Error> {
+        let _permit = self.data.in_flight.acquire_async().await;
         let result_receiver = self.send_request_without_confirmation(name, bytes)?;
 
         #[cfg(target_arch = "wasm32")]
@@ -543,11 +728,74 @@ impl AsyncClient {
 
     #[cfg(not(target_arch = "wasm32"))]
     fn send_request(&self, name: ApiName, bytes: Bytes) -> Result<Bytes, Error> {
+        if let Some(outbox) = &self.data.outbox {
+            if let Some(operation) = outbox.queue(&name, &bytes)? {
+                let result =
+                    self.send_unqueued_request(IdempotentRequest::name(), operation.request()?);
+                outbox.finished(&operation, &result);
+                return result;
+            }
+        }
+
+        if self.data.write_retries > 0 && outbox::is_write(&name, &bytes) {
+            let request = outbox::idempotent_request(rand::random(), name, bytes)?;
+            let mut retries = 0;
+            loop {
+                let result = self.send_unqueued_request(IdempotentRequest::name(), request.clone());
+                if retries == self.data.write_retries || outbox::is_acknowledged(&result) {
+                    return result;
+                }
+                retries += 1;
+                std::thread::sleep(outbox::retry_delay(retries));
+            }
+        }
+
+        self.send_unqueued_request(name, bytes)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn send_unqueued_request(&self, name: ApiName, bytes: Bytes) -> Result<Bytes, Error> {
+        let _permit = self.data.in_flight.acquire();
         let result_receiver = self.send_request_without_confirmation(name, bytes)?;
 
         result_receiver.recv_timeout(self.request_timeout)?
     }
 
+    /// Replays the operations in this client's outbox that the server has not
+    /// acknowledged, in the order they were queued. This includes operations
+    /// queued before the process was restarted. Operations are replayed using
+    /// this client's current session.
+    ///
+    /// Returns the number of operations replayed. If the server rejects an
+    /// operation, the error is reported to the conflict callback and the
+    /// remaining operations continue to be replayed. If the server cannot be
+    /// reached, the error is returned and the remaining operations stay
+    /// queued.
+    ///
+    /// Clients with an outbox replay it automatically when they are created
+    /// and each time a connection is established, using a session without
+    /// any authentication beyond the connection's token. Calling this function
+    /// replays the outbox using this client's session instead. Writes
+    /// performed while the outbox is being replayed may be executed before
+    /// the replayed operations. If this client has no outbox, this function
+    /// does nothing. See
+    /// [`Builder::with_outbox()`](crate::Builder::with_outbox) for more
+    /// information.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn flush_outbox(&self) -> Result<usize, Error> {
+        let mut replayed = 0;
+        if let Some(outbox) = &self.data.outbox {
+            for operation in outbox.pending() {
+                let result = self
+                    .send_unqueued_request_async(IdempotentRequest::name(), operation.request()?)
+                    .await;
+                outbox.replayed(&operation, result)?;
+                replayed += 1;
+            }
+        }
+        Ok(replayed)
+    }
+
     /// Sends an api `request`.
     pub async fn send_api_request<Api: api::Api>(
         &self,
@@ -560,6 +808,38 @@ impl AsyncClient {
         response.map_err(ApiError::Api)
     }
 
+    /// Sends an api `request` scoped to the database named `database`. The
+    /// server opens the database before dispatching the request, making it
+    /// available to the handler through the `database` field of its
+    /// `HandlerSession`.
+    pub async fn send_api_request_to<Api: api::Api>(
+        &self,
+        database: &str,
+        request: &Api,
+    ) -> Result<Api::Response, ApiError<Api::Error>> {
+        let request = database_request(database, request)?;
+        let response = self
+            .send_request_async(DatabaseRequest::name(), request)
+            .await?;
+        let response =
+            pot::from_slice::<Result<Api::Response, Api::Error>>(&response).map_err(Error::from)?;
+        response.map_err(ApiError::Api)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn send_blocking_api_request_to<Api: api::Api>(
+        &self,
+        database: &str,
+        request: &Api,
+    ) -> Result<Api::Response, ApiError<Api::Error>> {
+        let request = database_request(database, request)?;
+        let response = self.send_request(DatabaseRequest::name(), request)?;
+
+        let response =
+            pot::from_slice::<Result<Api::Response, Api::Error>>(&response).map_err(Error::from)?;
+        response.map_err(ApiError::Api)
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     fn send_blocking_api_request<Api: api::Api>(
         &self,
@@ -587,6 +867,35 @@ impl AsyncClient {
         effective_permissions.clone()
     }
 
+    /// Returns the number of requests sent by this client, or any of its
+    /// clones, that are waiting for a response. This never exceeds the limit
+    /// configured using
+    /// [`Builder::with_max_in_flight_requests()`](crate::Builder::with_max_in_flight_requests).
+    #[must_use]
+    pub fn in_flight_requests(&self) -> usize {
+        self.data.in_flight.len()
+    }
+
+    /// Returns the capabilities the server reported when the current
+    /// connection was established. Returns None if the client is not
+    /// connected.
+    #[must_use]
+    pub fn capabilities(&self) -> Option<Vec<Capability>> {
+        self.data.handshake.negotiated()
+    }
+
+    /// Returns the session the current connection was authenticated with
+    /// while it was being established, using the token provided to
+    /// [`Builder::with_token()`] or in the url. Requests sent by clients that
+    /// haven't authenticated are performed using this session.
+    ///
+    /// Returns None if no token was provided or the client is not connected.
+    #[cfg(feature = "token-authentication")]
+    #[must_use]
+    pub fn connection_session(&self) -> Option<Session> {
+        self.data.handshake.session()
+    }
+
     pub(crate) fn register_subscriber(&self, id: u64, sender: flume::Sender<Message>) {
         let mut subscribers = self.data.subscribers.lock();
         subscribers.insert(id, sender);
@@ -614,6 +923,29 @@ impl AsyncClient {
         subscribers.remove(&id);
     }
 
+    /// Records that `transaction_id` has been applied or observed in
+    /// `database` using this connection.
+    pub(crate) fn saw_transaction(&self, database: &str, transaction_id: Option<u64>) {
+        if let Some(transaction_id) = transaction_id {
+            let mut last_seen = self.data.last_seen_transaction_ids.lock();
+            if let Some(last_seen) = last_seen.get_mut(database) {
+                *last_seen = (*last_seen).max(transaction_id);
+            } else {
+                last_seen.insert(database.to_string(), transaction_id);
+            }
+        }
+    }
+
+    /// Returns the newest transaction applied or observed in `database` using
+    /// this connection.
+    pub(crate) fn last_seen_transaction_id(&self, database: &str) -> Option<u64> {
+        self.data
+            .last_seen_transaction_ids
+            .lock()
+            .get(database)
+            .copied()
+    }
+
     fn remote_database<DB: bonsaidb_core::schema::Schema>(
         &self,
         name: &str,
@@ -670,10 +1002,7 @@ impl AsyncStorageConnection for AsyncClient {
         only_if_needed: bool,
     ) -> Result<(), bonsaidb_core::Error> {
         self.send_api_request(&CreateDatabase {
-            database: Database {
-                name: name.to_string(),
-                schema,
-            },
+            database: Database::new(name, schema),
             only_if_needed,
         })
         .await?;
@@ -695,6 +1024,19 @@ impl AsyncStorageConnection for AsyncClient {
         Ok(())
     }
 
+    async fn set_database_quota(
+        &self,
+        name: &str,
+        quota: DatabaseQuota,
+    ) -> Result<(), bonsaidb_core::Error> {
+        self.send_api_request(&SetDatabaseQuota {
+            name: name.to_string(),
+            quota,
+        })
+        .await?;
+        Ok(())
+    }
+
     async fn list_databases(&self) -> Result<Vec<Database>, bonsaidb_core::Error> {
         Ok(self.send_api_request(&ListDatabases).await?)
     }
@@ -771,6 +1113,23 @@ impl AsyncStorageConnection for AsyncClient {
         })
     }
 
+    async fn assume_identity_capped(
+        &self,
+        identity: IdentityReference<'_>,
+    ) -> Result<Self::Authenticated, bonsaidb_core::Error> {
+        let session = self
+            .send_api_request(&AssumeCappedIdentity(identity.into_owned()))
+            .await?;
+        Ok(Self {
+            data: self.data.clone(),
+            session: ClientSession {
+                session: Arc::new(session),
+                connection_id: self.data.connection_counter.load(Ordering::SeqCst),
+            },
+            request_timeout: self.request_timeout,
+        })
+    }
+
     async fn add_permission_group_to_user<
         'user,
         'group,
@@ -846,6 +1205,52 @@ impl AsyncStorageConnection for AsyncClient {
         .await?;
         Ok(())
     }
+
+    async fn create_permission_group(
+        &self,
+        name: &str,
+        statements: Vec<Statement>,
+    ) -> Result<u64, bonsaidb_core::Error> {
+        Ok(self
+            .send_api_request(&CreatePermissionGroup {
+                name: name.to_string(),
+                statements,
+            })
+            .await?)
+    }
+
+    async fn update_permission_group_statements<'group, G: Nameable<'group, u64> + Send + Sync>(
+        &self,
+        permission_group: G,
+        statements: Vec<Statement>,
+    ) -> Result<(), bonsaidb_core::Error> {
+        Ok(self
+            .send_api_request(&UpdatePermissionGroupStatements {
+                group: permission_group.name()?.into_owned(),
+                statements,
+            })
+            .await?)
+    }
+
+    async fn create_role(&self, name: &str, groups: Vec<u64>) -> Result<u64, bonsaidb_core::Error> {
+        Ok(self
+            .send_api_request(&CreateRole {
+                name: name.to_string(),
+                groups,
+            })
+            .await?)
+    }
+
+    async fn delete_role<'role, R: Nameable<'role, u64> + Send + Sync>(
+        &self,
+        role: R,
+    ) -> Result<(), bonsaidb_core::Error> {
+        Ok(self
+            .send_api_request(&DeleteRole {
+                role: role.name()?.into_owned(),
+            })
+            .await?)
+    }
 }
 
 type OutstandingRequestMap = HashMap<u32, PendingRequest>;
@@ -987,10 +1392,311 @@ async fn disconnect_pending_requests(
     }
 }
 
+/// Serializes `request` wrapped in a [`DatabaseRequest`] for `database`.
+fn database_request<Api: api::Api>(database: &str, request: &Api) -> Result<Bytes, Error> {
+    Ok(Bytes::from(pot::to_vec(&DatabaseRequest {
+        database: database.to_owned(),
+        name: Api::name(),
+        value: Bytes::from(pot::to_vec(request)?),
+    })?))
+}
+
+/// The default limit of [`Builder::with_max_in_flight_requests()`].
+pub(crate) const DEFAULT_MAX_IN_FLIGHT_REQUESTS: usize = 1_024;
+
+/// Limits the number of requests awaiting a response. Each request reserves a
+/// slot in a bounded channel before it is sent, and frees it once the request
+/// finishes.
+#[derive(Debug)]
+struct InFlightLimit {
+    reserve: flume::Sender<()>,
+    release: flume::Receiver<()>,
+}
+
+impl InFlightLimit {
+    fn new(limit: usize) -> Self {
+        let (reserve, release) = flume::bounded(limit.max(1));
+        Self { reserve, release }
+    }
+
+    fn len(&self) -> usize {
+        self.reserve.len()
+    }
+
+    async fn acquire_async(&self) -> InFlightPermit<'_> {
+        // Both ends of the channel are owned by self, so sending cannot fail.
+        drop(self.reserve.send_async(()).await);
+        InFlightPermit(&self.release)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn acquire(&self) -> InFlightPermit<'_> {
+        drop(self.reserve.send(()));
+        InFlightPermit(&self.release)
+    }
+}
+
+/// A slot reserved in an [`InFlightLimit`], released when dropped.
+struct InFlightPermit<'a>(&'a flume::Receiver<()>);
+
+impl Drop for InFlightPermit<'_> {
+    fn drop(&mut self) {
+        drop(self.0.try_recv());
+    }
+}
+
 struct ConnectionInfo {
     pub url: Url,
     pub subscribers: SubscriberMap,
     #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
     pub connect_timeout: Duration,
     pub request_timeout: Duration,
+    pub handshake: Handshake,
+    pub max_in_flight_requests: usize,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub write_retries: usize,
+    #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+    pub proxy: Option<crate::ProxyConfig>,
+}
+
+/// The id used for the requests sent when a connection is established. No
+/// other requests are outstanding until the handshake is complete.
+const HANDSHAKE_REQUEST_ID: u32 = u32::MAX;
+
+/// Performs the requests sent on each connection when it is established:
+/// querying the server's capabilities, and authenticating the connection if a
+/// token was provided.
+#[derive(Debug, Clone)]
+pub(crate) struct Handshake {
+    required: Arc<Vec<Capability>>,
+    negotiated: Arc<Mutex<Option<Vec<Capability>>>>,
+    request_signing_key: Option<RequestSigningKey>,
+    connected: Option<flume::Sender<()>>,
+    #[cfg(feature = "token-authentication")]
+    token: Option<Arc<ConnectionToken>>,
+    #[cfg(feature = "token-authentication")]
+    session: Arc<Mutex<Option<Session>>>,
+    #[cfg(feature = "token-authentication")]
+    step: Arc<Mutex<HandshakeStep>>,
+}
+
+#[cfg(feature = "token-authentication")]
+#[derive(Debug, Clone, Copy)]
+enum HandshakeStep {
+    Capabilities,
+    Token,
+    TokenChallenge,
+}
+
+impl Handshake {
+    fn new(
+        required: Vec<Capability>,
+        request_signing_key: Option<RequestSigningKey>,
+        connected: Option<flume::Sender<()>>,
+        #[cfg(feature = "token-authentication")] token: Option<ConnectionToken>,
+    ) -> Self {
+        Self {
+            required: Arc::new(required),
+            negotiated: Arc::default(),
+            request_signing_key,
+            connected,
+            #[cfg(feature = "token-authentication")]
+            token: token.map(Arc::new),
+            #[cfg(feature = "token-authentication")]
+            session: Arc::default(),
+            #[cfg(feature = "token-authentication")]
+            step: Arc::new(Mutex::new(HandshakeStep::Capabilities)),
+        }
+    }
+
+    fn negotiated(&self) -> Option<Vec<Capability>> {
+        self.negotiated.lock().clone()
+    }
+
+    #[cfg(feature = "token-authentication")]
+    fn session(&self) -> Option<Session> {
+        self.session.lock().clone()
+    }
+
+    /// Returns the first request to send before any other request on a new
+    /// connection.
+    pub fn request(&self) -> Result<Payload, Error> {
+        *self.negotiated.lock() = None;
+        #[cfg(feature = "token-authentication")]
+        {
+            *self.session.lock() = None;
+            *self.step.lock() = HandshakeStep::Capabilities;
+        }
+        // Capabilities are requested without a signature so that servers can
+        // report whether they require signed requests.
+        Self::unsigned_payload(None, &ListCapabilities)
+    }
+
+    /// Processes the server's `response` to the previous handshake request,
+    /// returning the next request to send, if any.
+    ///
+    /// Returns [`Error::MissingCapabilities`] if any required capability is
+    /// missing, or [`Error::RequestSigningRequired`] if the server requires
+    /// signed requests and no key was provided.
+    pub fn finish(&self, response: Result<Bytes, Error>) -> Result<Option<Payload>, Error> {
+        let next = self.advance(response)?;
+        if next.is_none() {
+            if let Some(connected) = &self.connected {
+                // A pending notification hasn't been handled yet, so there's
+                // no need for another.
+                drop(connected.try_send(()));
+            }
+        }
+        Ok(next)
+    }
+
+    fn advance(&self, response: Result<Bytes, Error>) -> Result<Option<Payload>, Error> {
+        #[cfg(feature = "token-authentication")]
+        {
+            let step = *self.step.lock();
+            match step {
+                HandshakeStep::Capabilities => {}
+                HandshakeStep::Token => return self.answer_token_challenge(response).map(Some),
+                HandshakeStep::TokenChallenge => {
+                    let session =
+                        pot::from_slice::<Result<Session, bonsaidb_core::Error>>(&response?)??;
+                    *self.session.lock() = Some(session);
+                    return Ok(None);
+                }
+            }
+        }
+
+        let capabilities = match response {
+            Ok(response) => {
+                pot::from_slice::<Result<Vec<Capability>, bonsaidb_core::Error>>(&response)??
+            }
+            // Servers that predate capabilities don't support any.
+            Err(Error::Core(bonsaidb_core::Error::ApiNotFound(_))) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let missing = self
+            .required
+            .iter()
+            .filter(|capability| !capabilities.contains(capability))
+            .cloned()
+            .collect::<Vec<_>>();
+        let signing_required = self.request_signing_key.is_none()
+            && capabilities.contains(&Capability::REQUEST_SIGNING);
+        *self.negotiated.lock() = Some(capabilities);
+        if !missing.is_empty() {
+            return Err(Error::MissingCapabilities(missing));
+        }
+        if signing_required {
+            return Err(Error::RequestSigningRequired);
+        }
+
+        #[cfg(feature = "token-authentication")]
+        if let Some(token) = &self.token {
+            *self.step.lock() = HandshakeStep::Token;
+            return self
+                .payload(
+                    None,
+                    &AuthenticateConnection {
+                        authentication: Authentication::token(token.id, &token.token)?,
+                    },
+                )
+                .map(Some);
+        }
+
+        Ok(None)
+    }
+
+    #[cfg(feature = "token-authentication")]
+    fn answer_token_challenge(&self, response: Result<Bytes, Error>) -> Result<Payload, Error> {
+        let challenge = pot::from_slice::<Result<Session, bonsaidb_core::Error>>(&response?)??;
+        let token = self
+            .token
+            .as_ref()
+            .expect("token challenge without a token");
+        match &challenge.authentication {
+            SessionAuthentication::TokenChallenge {
+                algorithm: TokenChallengeAlgorithm::Blake3,
+                nonce,
+                server_timestamp,
+                ..
+            } => {
+                let response = AuthenticationToken::compute_challenge_response_blake3(
+                    &token.token,
+                    nonce,
+                    *server_timestamp,
+                );
+                *self.step.lock() = HandshakeStep::TokenChallenge;
+                self.payload(
+                    challenge.id,
+                    &AuthenticateConnection {
+                        authentication: Authentication::TokenChallengeResponse(Bytes::from(
+                            response.as_bytes().to_vec(),
+                        )),
+                    },
+                )
+            }
+            _ => Err(Error::Core(bonsaidb_core::Error::InvalidCredentials)),
+        }
+    }
+
+    fn unsigned_payload<Api: api::Api>(
+        session_id: Option<SessionId>,
+        request: &Api,
+    ) -> Result<Payload, Error> {
+        Ok(Payload {
+            session_id,
+            id: Some(HANDSHAKE_REQUEST_ID),
+            name: Api::name(),
+            value: Ok(Bytes::from(pot::to_vec(request)?)),
+        })
+    }
+
+    fn payload<Api: api::Api>(
+        &self,
+        session_id: Option<SessionId>,
+        request: &Api,
+    ) -> Result<Payload, Error> {
+        let mut request = Self::unsigned_payload(session_id, request)?;
+        if let Some(key) = &self.request_signing_key {
+            request = key.sign(request)?;
+        }
+        Ok(request)
+    }
+}
+
+/// A token used to authenticate each connection as it is established.
+#[cfg(feature = "token-authentication")]
+#[derive(Debug, Clone)]
+pub(crate) struct ConnectionToken {
+    pub id: u64,
+    pub token: SensitiveString,
+}
+
+#[cfg(feature = "token-authentication")]
+impl ConnectionToken {
+    /// Removes the credentials from `url`, returning them as a token if
+    /// present. The username is the token's id, and the password is the
+    /// percent-encoded token.
+    fn take_from_url(url: &mut Url) -> Result<Option<Self>, Error> {
+        if url.username().is_empty() {
+            return Ok(None);
+        }
+
+        let id = url
+            .username()
+            .parse()
+            .map_err(|_| Error::InvalidUrl(String::from("the username must be a token id")))?;
+        let token = percent_encoding::percent_decode_str(url.password().unwrap_or_default())
+            .decode_utf8()
+            .map_err(|_| Error::InvalidUrl(String::from("the password must be a token")))?
+            .into_owned();
+        // The credentials are sent during the handshake rather than as part
+        // of the url.
+        let _: Result<_, _> = url.set_username("");
+        let _: Result<_, _> = url.set_password(None);
+        Ok(Some(Self {
+            id,
+            token: SensitiveString(token),
+        }))
+    }
 }
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
 
 use bonsaidb_core::api::ApiName;
 use bonsaidb_core::networking::Payload;
@@ -13,7 +12,8 @@ use url::Url;
 
 use super::PendingRequest;
 use crate::client::{
-    disconnect_pending_requests, AnyApiCallback, ConnectionInfo, OutstandingRequestMapHandle,
+    disconnect_pending_requests, AnyApiCallback, ConnectionInfo, Handshake,
+    OutstandingRequestMapHandle,
 };
 use crate::Error;
 
@@ -22,7 +22,7 @@ use crate::Error;
 /// error replayed to them.
 pub(super) async fn reconnecting_client_loop(
     mut server: ConnectionInfo,
-    protocol_version: &'static str,
+    protocol_versions: &'static [&'static str],
     certificate: Option<Certificate>,
     request_receiver: Receiver<PendingRequest>,
     custom_apis: Arc<HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>>,
@@ -41,13 +41,12 @@ pub(super) async fn reconnecting_client_loop(
         }
         connection_counter.fetch_add(1, Ordering::SeqCst);
         if let Err((failed_request, Some(err))) = connect_and_process(
-            &server.url,
-            protocol_version,
+            &server,
+            protocol_versions,
             certificate.as_ref(),
             request,
             &request_receiver,
             custom_apis.clone(),
-            server.connect_timeout,
         )
         .await
         {
@@ -63,22 +62,33 @@ pub(super) async fn reconnecting_client_loop(
 }
 
 async fn connect_and_process(
-    url: &Url,
-    protocol_version: &str,
+    server: &ConnectionInfo,
+    protocol_versions: &[&str],
     certificate: Option<&Certificate>,
     initial_request: PendingRequest,
     request_receiver: &Receiver<PendingRequest>,
     custom_apis: Arc<HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>>,
-    connect_timeout: Duration,
 ) -> Result<(), (Option<PendingRequest>, Option<Error>)> {
-    let (_connection, payload_sender, payload_receiver) =
-        match tokio::time::timeout(connect_timeout, connect(url, certificate, protocol_version))
-            .await
-        {
-            Ok(Ok(result)) => result,
-            Ok(Err(err)) => return Err((Some(initial_request), Some(err))),
-            Err(_) => return Err((Some(initial_request), Some(Error::connect_timeout()))),
-        };
+    let (_connection, payload_sender, mut payload_receiver) = match tokio::time::timeout(
+        server.connect_timeout,
+        connect(&server.url, certificate, protocol_versions),
+    )
+    .await
+    {
+        Ok(Ok(result)) => result,
+        Ok(Err(err)) => return Err((Some(initial_request), Some(err))),
+        Err(_) => return Err((Some(initial_request), Some(Error::connect_timeout()))),
+    };
+
+    let negotiation = tokio::time::timeout(
+        server.connect_timeout,
+        perform_handshake(&server.handshake, &payload_sender, &mut payload_receiver),
+    )
+    .await
+    .unwrap_or_else(|_| Err(Error::connect_timeout()));
+    if let Err(err) = negotiation {
+        return Err((Some(initial_request), Some(err)));
+    }
 
     let outstanding_requests = OutstandingRequestMapHandle::default();
     let request_processor = tokio::spawn(process(
@@ -119,6 +129,27 @@ async fn connect_and_process(
     Ok(())
 }
 
+/// Performs the [`Handshake`] of a newly established connection before any
+/// other requests are sent.
+async fn perform_handshake(
+    handshake: &Handshake,
+    payload_sender: &fabruic::Sender<Payload>,
+    payload_receiver: &mut fabruic::Receiver<Payload>,
+) -> Result<(), Error> {
+    let mut request = handshake.request()?;
+    loop {
+        payload_sender.send(&request)?;
+        let payload = payload_receiver
+            .next()
+            .await
+            .ok_or_else(Error::disconnected)??;
+        match handshake.finish(payload.value.map_err(Error::from))? {
+            Some(next) => request = next,
+            None => return Ok(()),
+        }
+    }
+}
+
 async fn process_requests(
     outstanding_requests: OutstandingRequestMapHandle,
     request_receiver: &Receiver<PendingRequest>,
@@ -155,7 +186,7 @@ pub async fn process(
 async fn connect(
     url: &Url,
     certificate: Option<&Certificate>,
-    protocol_version: &str,
+    protocol_versions: &[&str],
 ) -> Result<
     (
         fabruic::Connection<()>,
@@ -168,7 +199,12 @@ async fn connect(
     endpoint
         .set_max_idle_timeout(None)
         .map_err(|err| Error::Core(bonsaidb_core::Error::other("quic", err)))?;
-    endpoint.set_protocols([protocol_version.as_bytes().to_vec()]);
+    endpoint.set_protocols(
+        protocol_versions
+            .iter()
+            .map(|version| version.as_bytes().to_vec())
+            .collect::<Vec<_>>(),
+    );
     let endpoint = endpoint
         .build()
         .map_err(|err| Error::Core(bonsaidb_core::Error::other("quic", err)))?;
@@ -180,7 +216,7 @@ async fn connect(
 
     let connection = connecting.accept::<()>().await.map_err(|err| {
         if matches!(err, fabruic::error::Connecting::ProtocolMismatch) {
-            Error::ProtocolVersionMismatch
+            Error::IncompatibleProtocol
         } else {
             Error::from(err)
         }
@@ -3,12 +3,13 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use bonsaidb_core::connection::{
-    AccessPolicy, AsyncConnection, AsyncLowLevelConnection, HasSchema, HasSession, Range,
-    SerializedQueryKey, Session, Sort,
+    AccessPolicy, AsyncConnection, AsyncLowLevelConnection, DeletedDocuments, HasSchema,
+    HasSession, Range, SerializedQueryKey, Session, Sort,
 };
 use bonsaidb_core::document::{DocumentId, Header, OwnedDocument};
 use bonsaidb_core::networking::{
-    ApplyTransaction, Compact, CompactCollection, CompactKeyValueStore, Count, DeleteDocs, Get,
+    ApplyTransaction, ApplyTransactionReturningId, AwaitKeyValueDurability, Compact,
+    CompactCollection, CompactKeyValueStore, Count, DeleteDocs, DeleteDocsReturningHeaders, Get,
     GetMultiple, LastTransactionId, List, ListExecutedTransactions, ListHeaders, Query,
     QueryWithDocs, Reduce, ReduceGrouped,
 };
@@ -36,6 +37,30 @@ impl AsyncRemoteDatabase {
     pub fn name(&self) -> &str {
         self.name.as_ref()
     }
+
+    /// Waits until every key-value change made to this database before this
+    /// call has been persisted by the server.
+    ///
+    /// Unlike [`AsyncKeyValue::persist()`], this does not force the changes to
+    /// be written. The server responds once its configured key-value
+    /// persistence has written them, which allows writes to be made using
+    /// [`DurabilityMode::Deferred`] while still learning when they are durable.
+    /// This request is subject to the client's request timeout, and the server
+    /// responds with a timeout if the changes aren't persisted within 30
+    /// seconds. Requires the
+    /// [`KeyValueAction::Persist`](bonsaidb_core::permissions::bonsai::KeyValueAction::Persist)
+    /// permission.
+    ///
+    /// [`AsyncKeyValue::persist()`]: bonsaidb_core::keyvalue::AsyncKeyValue::persist
+    /// [`DurabilityMode::Deferred`]: bonsaidb_core::keyvalue::DurabilityMode::Deferred
+    pub async fn await_key_value_durability(&self) -> Result<(), bonsaidb_core::Error> {
+        Ok(self
+            .client
+            .send_api_request(&AwaitKeyValueDurability {
+                database: self.name.to_string(),
+            })
+            .await?)
+    }
 }
 
 impl Deref for AsyncRemoteDatabase {
@@ -86,12 +111,18 @@ impl AsyncConnection for AsyncRemoteDatabase {
     }
 
     async fn last_transaction_id(&self) -> Result<Option<u64>, bonsaidb_core::Error> {
-        Ok(self
+        let transaction_id = self
             .client
             .send_api_request(&LastTransactionId {
                 database: self.name.to_string(),
             })
-            .await?)
+            .await?;
+        self.client.saw_transaction(&self.name, transaction_id);
+        Ok(transaction_id)
+    }
+
+    fn last_seen_transaction_id(&self) -> Option<u64> {
+        self.client.last_seen_transaction_id(&self.name)
     }
 
     async fn compact(&self) -> Result<(), bonsaidb_core::Error> {
@@ -117,13 +148,16 @@ impl AsyncLowLevelConnection for AsyncRemoteDatabase {
         &self,
         transaction: Transaction,
     ) -> Result<Vec<OperationResult>, bonsaidb_core::Error> {
-        Ok(self
+        let applied = self
             .client
-            .send_api_request(&ApplyTransaction {
+            .send_api_request(&ApplyTransactionReturningId(ApplyTransaction {
                 database: self.name.to_string(),
                 transaction,
-            })
-            .await?)
+            }))
+            .await?;
+        self.client
+            .saw_transaction(&self.name, Some(applied.transaction_id));
+        Ok(applied.results)
     }
 
     async fn get_from_collection(
@@ -303,16 +337,29 @@ impl AsyncLowLevelConnection for AsyncRemoteDatabase {
         view: &ViewName,
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, bonsaidb_core::Error> {
-        Ok(self
-            .client
-            .send_api_request(&DeleteDocs {
-                database: self.name.to_string(),
-                view: view.clone(),
-                key,
-                access_policy,
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, bonsaidb_core::Error> {
+        let request = DeleteDocs {
+            database: self.name.to_string(),
+            view: view.clone(),
+            key,
+            access_policy,
+        };
+        if include_headers {
+            let deleted = self
+                .client
+                .send_api_request(&DeleteDocsReturningHeaders(request))
+                .await?;
+            self.client
+                .saw_transaction(&self.name, deleted.transaction_id);
+            Ok(deleted)
+        } else {
+            let count = self.client.send_api_request(&request).await?;
+            Ok(DeletedDocuments {
+                count,
+                ..DeletedDocuments::default()
             })
-            .await?)
+        }
     }
 }
 
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use bonsaidb_core::keyvalue::AsyncKeyValue;
-use bonsaidb_core::networking::ExecuteKeyOperation;
+use bonsaidb_core::networking::{ExecuteKeyOperation, PersistKeyValueStore};
 
 #[async_trait]
 impl AsyncKeyValue for super::AsyncRemoteDatabase {
@@ -17,4 +17,13 @@ impl AsyncKeyValue for super::AsyncRemoteDatabase {
             })
             .await?)
     }
+
+    async fn persist(&self) -> Result<(), bonsaidb_core::Error> {
+        Ok(self
+            .client
+            .send_api_request(&PersistKeyValueStore {
+                database: self.name.to_string(),
+            })
+            .await?)
+    }
 }
@@ -4,21 +4,24 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use bonsaidb_core::admin::{Admin, ADMIN_DATABASE_NAME};
-use bonsaidb_core::api;
+use bonsaidb_core::api::{self, Api};
 use bonsaidb_core::arc_bytes::serde::Bytes;
 use bonsaidb_core::connection::{
-    AccessPolicy, Connection, Database, HasSchema, HasSession, IdentityReference,
+    AccessPolicy, Connection, Database, DeletedDocuments, HasSchema, HasSession, IdentityReference,
     LowLevelConnection, Range, SerializedQueryKey, Sort, StorageConnection,
 };
 use bonsaidb_core::document::{DocumentId, Header, OwnedDocument};
 use bonsaidb_core::keyvalue::KeyValue;
 use bonsaidb_core::networking::{
-    AlterUserPermissionGroupMembership, AlterUserRoleMembership, ApplyTransaction, AssumeIdentity,
-    Compact, CompactCollection, CompactKeyValueStore, Count, CreateDatabase, CreateSubscriber,
-    CreateUser, DeleteDatabase, DeleteDocs, DeleteUser, ExecuteKeyOperation, Get, GetMultiple,
-    LastTransactionId, List, ListAvailableSchemas, ListDatabases, ListExecutedTransactions,
-    ListHeaders, Publish, PublishToAll, Query, QueryWithDocs, Reduce, ReduceGrouped, SubscribeTo,
-    UnsubscribeFrom, CURRENT_PROTOCOL_VERSION,
+    AlterUserPermissionGroupMembership, AlterUserRoleMembership, ApplyTransaction,
+    ApplyTransactionReturningId, AssumeCappedIdentity, AssumeIdentity, AwaitKeyValueDurability,
+    Capability, Compact, CompactCollection, CompactKeyValueStore, Count, CreateDatabase,
+    CreatePermissionGroup, CreateRole, CreateSubscriber, CreateUser, DeleteDatabase, DeleteDocs,
+    DeleteDocsReturningHeaders, DeleteRole, DeleteUser, ExecuteKeyOperation, Get, GetMultiple,
+    IdempotentRequest, LastTransactionId, List, ListAvailableSchemas, ListDatabases,
+    ListExecutedTransactions, ListHeaders, PersistKeyValueStore, Publish, PublishToAll, Query,
+    QueryWithDocs, Reduce, ReduceGrouped, SetDatabaseQuota, SubscribeTo, UnsubscribeFrom,
+    UpdatePermissionGroupStatements, SUPPORTED_PROTOCOL_VERSIONS,
 };
 use bonsaidb_core::pubsub::{AsyncSubscriber, PubSub, Receiver, Subscriber};
 use bonsaidb_core::schema::view::map;
@@ -30,7 +33,7 @@ use tokio::task::JoinHandle;
 use url::Url;
 
 use crate::builder::Blocking;
-use crate::client::ClientSession;
+use crate::client::{ClientSession, DEFAULT_MAX_IN_FLIGHT_REQUESTS};
 use crate::{ApiError, AsyncClient, AsyncRemoteDatabase, AsyncRemoteSubscriber, Builder, Error};
 
 /// A BonsaiDb client that blocks the current thread when performing requests.
@@ -61,14 +64,25 @@ impl BlockingClient {
     pub fn new(url: Url) -> Result<Self, Error> {
         AsyncClient::new_from_parts(
             url,
-            CURRENT_PROTOCOL_VERSION,
+            SUPPORTED_PROTOCOL_VERSIONS,
             HashMap::default(),
             None,
             None,
+            None,
+            Vec::new(),
+            DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+            #[cfg(feature = "token-authentication")]
+            None,
+            #[cfg(not(target_arch = "wasm32"))]
+            None,
+            #[cfg(not(target_arch = "wasm32"))]
+            0,
             #[cfg(not(target_arch = "wasm32"))]
             None,
             #[cfg(not(target_arch = "wasm32"))]
             Handle::try_current().ok(),
+            #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+            None,
         )
         .map(Self)
     }
@@ -81,6 +95,33 @@ impl BlockingClient {
         self.0.send_blocking_api_request(request)
     }
 
+    /// Sends an api `request` scoped to the database named `database`. See
+    /// [`AsyncClient::send_api_request_to()`] for more information.
+    pub fn send_api_request_to<Api: api::Api>(
+        &self,
+        database: &str,
+        request: &Api,
+    ) -> Result<Api::Response, ApiError<Api::Error>> {
+        self.0.send_blocking_api_request_to(database, request)
+    }
+
+    /// Replays the operations in this client's outbox that the server has not
+    /// acknowledged, in the order they were queued. See
+    /// [`AsyncClient::flush_outbox()`] for more information.
+    pub fn flush_outbox(&self) -> Result<usize, Error> {
+        let mut replayed = 0;
+        if let Some(outbox) = &self.0.data.outbox {
+            for operation in outbox.pending() {
+                let result = self
+                    .0
+                    .send_unqueued_request(IdempotentRequest::name(), operation.request()?);
+                outbox.replayed(&operation, result)?;
+                replayed += 1;
+            }
+        }
+        Ok(replayed)
+    }
+
     /// Sends an api `request` without waiting for a result. The response from
     /// the server will be ignored.
     pub fn invoke_api_request<Api: api::Api>(&self, request: &Api) -> Result<(), Error> {
@@ -96,6 +137,31 @@ impl BlockingClient {
         &self.0
     }
 
+    /// Returns the number of requests sent by this client, or any of its
+    /// clones, that are waiting for a response. See
+    /// [`AsyncClient::in_flight_requests()`] for more information.
+    #[must_use]
+    pub fn in_flight_requests(&self) -> usize {
+        self.0.in_flight_requests()
+    }
+
+    /// Returns the capabilities the server reported when the current
+    /// connection was established. Returns None if the client is not
+    /// connected.
+    #[must_use]
+    pub fn capabilities(&self) -> Option<Vec<Capability>> {
+        self.0.capabilities()
+    }
+
+    /// Returns the session the current connection was authenticated with
+    /// while it was being established. See
+    /// [`AsyncClient::connection_session()`] for more information.
+    #[cfg(feature = "token-authentication")]
+    #[must_use]
+    pub fn connection_session(&self) -> Option<bonsaidb_core::connection::Session> {
+        self.0.connection_session()
+    }
+
     /// Sets this instance's request timeout.
     ///
     /// Each client has its own timeout. When cloning a client, this timeout
@@ -145,10 +211,7 @@ impl StorageConnection for BlockingClient {
         only_if_needed: bool,
     ) -> Result<(), bonsaidb_core::Error> {
         self.send_api_request(&CreateDatabase {
-            database: Database {
-                name: name.to_string(),
-                schema,
-            },
+            database: Database::new(name, schema),
             only_if_needed,
         })?;
         Ok(())
@@ -161,6 +224,18 @@ impl StorageConnection for BlockingClient {
         Ok(())
     }
 
+    fn set_database_quota(
+        &self,
+        name: &str,
+        quota: bonsaidb_core::admin::DatabaseQuota,
+    ) -> Result<(), bonsaidb_core::Error> {
+        self.send_api_request(&SetDatabaseQuota {
+            name: name.to_string(),
+            quota,
+        })?;
+        Ok(())
+    }
+
     fn list_databases(
         &self,
     ) -> Result<Vec<bonsaidb_core::connection::Database>, bonsaidb_core::Error> {
@@ -234,6 +309,21 @@ impl StorageConnection for BlockingClient {
         }))
     }
 
+    fn assume_identity_capped(
+        &self,
+        identity: IdentityReference<'_>,
+    ) -> Result<Self::Authenticated, bonsaidb_core::Error> {
+        let session = self.send_api_request(&AssumeCappedIdentity(identity.into_owned()))?;
+        Ok(Self(AsyncClient {
+            data: self.0.data.clone(),
+            session: ClientSession {
+                session: Arc::new(session),
+                connection_id: self.0.data.connection_counter.load(Ordering::SeqCst),
+            },
+            request_timeout: self.0.request_timeout,
+        }))
+    }
+
     fn add_permission_group_to_user<
         'user,
         'group,
@@ -305,6 +395,47 @@ impl StorageConnection for BlockingClient {
         })?;
         Ok(())
     }
+
+    fn create_permission_group(
+        &self,
+        name: &str,
+        statements: Vec<bonsaidb_core::permissions::Statement>,
+    ) -> Result<u64, bonsaidb_core::Error> {
+        Ok(self.send_api_request(&CreatePermissionGroup {
+            name: name.to_string(),
+            statements,
+        })?)
+    }
+
+    fn update_permission_group_statements<
+        'group,
+        G: bonsaidb_core::schema::Nameable<'group, u64> + Send + Sync,
+    >(
+        &self,
+        permission_group: G,
+        statements: Vec<bonsaidb_core::permissions::Statement>,
+    ) -> Result<(), bonsaidb_core::Error> {
+        Ok(self.send_api_request(&UpdatePermissionGroupStatements {
+            group: permission_group.name()?.into_owned(),
+            statements,
+        })?)
+    }
+
+    fn create_role(&self, name: &str, groups: Vec<u64>) -> Result<u64, bonsaidb_core::Error> {
+        Ok(self.send_api_request(&CreateRole {
+            name: name.to_string(),
+            groups,
+        })?)
+    }
+
+    fn delete_role<'role, R: bonsaidb_core::schema::Nameable<'role, u64> + Send + Sync>(
+        &self,
+        role: R,
+    ) -> Result<(), bonsaidb_core::Error> {
+        Ok(self.send_api_request(&DeleteRole {
+            role: role.name()?.into_owned(),
+        })?)
+    }
 }
 
 impl HasSession for BlockingClient {
@@ -318,6 +449,21 @@ impl HasSession for BlockingClient {
 #[derive(Debug, Clone)]
 pub struct BlockingRemoteDatabase(AsyncRemoteDatabase);
 
+impl BlockingRemoteDatabase {
+    /// Blocks until every key-value change made to this database before this
+    /// call has been persisted by the server. See
+    /// [`AsyncRemoteDatabase::await_key_value_durability()`] for more
+    /// information.
+    pub fn await_key_value_durability(&self) -> Result<(), bonsaidb_core::Error> {
+        Ok(self
+            .0
+            .client
+            .send_blocking_api_request(&AwaitKeyValueDurability {
+                database: self.0.name.to_string(),
+            })?)
+    }
+}
+
 impl Connection for BlockingRemoteDatabase {
     type Storage = BlockingClient;
 
@@ -341,12 +487,18 @@ impl Connection for BlockingRemoteDatabase {
     }
 
     fn last_transaction_id(&self) -> Result<Option<u64>, bonsaidb_core::Error> {
-        Ok(self
+        let transaction_id = self
             .0
             .client
             .send_blocking_api_request(&LastTransactionId {
                 database: self.0.name.to_string(),
-            })?)
+            })?;
+        self.0.client.saw_transaction(&self.0.name, transaction_id);
+        Ok(transaction_id)
+    }
+
+    fn last_seen_transaction_id(&self) -> Option<u64> {
+        self.0.client.last_seen_transaction_id(&self.0.name)
     }
 
     fn compact(&self) -> Result<(), bonsaidb_core::Error> {
@@ -369,10 +521,17 @@ impl LowLevelConnection for BlockingRemoteDatabase {
         &self,
         transaction: bonsaidb_core::transaction::Transaction,
     ) -> Result<Vec<bonsaidb_core::transaction::OperationResult>, bonsaidb_core::Error> {
-        Ok(self.0.client.send_blocking_api_request(&ApplyTransaction {
-            database: self.0.name.to_string(),
-            transaction,
-        })?)
+        let applied = self
+            .0
+            .client
+            .send_blocking_api_request(&ApplyTransactionReturningId(ApplyTransaction {
+                database: self.0.name.to_string(),
+                transaction,
+            }))?;
+        self.0
+            .client
+            .saw_transaction(&self.0.name, Some(applied.transaction_id));
+        Ok(applied.results)
     }
 
     fn get_from_collection(
@@ -535,13 +694,30 @@ impl LowLevelConnection for BlockingRemoteDatabase {
         view: &bonsaidb_core::schema::ViewName,
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, bonsaidb_core::Error> {
-        Ok(self.0.client.send_blocking_api_request(&DeleteDocs {
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, bonsaidb_core::Error> {
+        let request = DeleteDocs {
             database: self.0.name.to_string(),
             view: view.clone(),
             key,
             access_policy,
-        })?)
+        };
+        if include_headers {
+            let deleted = self
+                .0
+                .client
+                .send_blocking_api_request(&DeleteDocsReturningHeaders(request))?;
+            self.0
+                .client
+                .saw_transaction(&self.0.name, deleted.transaction_id);
+            Ok(deleted)
+        } else {
+            let count = self.0.client.send_blocking_api_request(&request)?;
+            Ok(DeletedDocuments {
+                count,
+                ..DeletedDocuments::default()
+            })
+        }
     }
 }
 
@@ -643,6 +819,15 @@ impl KeyValue for BlockingRemoteDatabase {
                 op,
             })?)
     }
+
+    fn persist(&self) -> Result<(), bonsaidb_core::Error> {
+        Ok(self
+            .0
+            .client
+            .send_blocking_api_request(&PersistKeyValueStore {
+                database: self.0.name.to_string(),
+            })?)
+    }
 }
 
 pub enum Tokio {
@@ -9,7 +9,7 @@ use flume::Receiver;
 use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
 use tokio::net::TcpStream;
-use tokio_tungstenite::tungstenite::handshake::client::generate_key;
+use tokio_tungstenite::tungstenite::handshake::client::{generate_key, Request, Response};
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
@@ -21,7 +21,7 @@ use crate::Error;
 
 pub(super) async fn reconnecting_client_loop(
     server: ConnectionInfo,
-    protocol_version: &str,
+    protocol_versions: &[&str],
     request_receiver: Receiver<PendingRequest>,
     custom_apis: Arc<HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>>,
     connection_counter: Arc<AtomicU32>,
@@ -37,36 +37,33 @@ pub(super) async fn reconnecting_client_loop(
         }
 
         connection_counter.fetch_add(1, Ordering::SeqCst);
-        let (stream, _) = match tokio::time::timeout(
+        let (stream, _) =
+            match tokio::time::timeout(server.connect_timeout, connect(&server, protocol_versions))
+                .await
+            {
+                Ok(Ok(result)) => result,
+                Ok(Err(err)) => {
+                    drop(request.responder.send(Err(err)));
+                    continue;
+                }
+                Err(_) => {
+                    drop(request.responder.send(Err(Error::connect_timeout())));
+                    continue;
+                }
+            };
+
+        let (mut sender, mut receiver) = stream.split();
+
+        let negotiation = tokio::time::timeout(
             server.connect_timeout,
-            tokio_tungstenite::connect_async(
-                tokio_tungstenite::tungstenite::handshake::client::Request::get(
-                    server.url.as_str(),
-                )
-                .header("Sec-WebSocket-Protocol", protocol_version)
-                .header("Sec-WebSocket-Version", "13")
-                .header("Sec-WebSocket-Key", generate_key())
-                .header("Host", server.url.host_str().expect("no host"))
-                .header("Connection", "Upgrade")
-                .header("Upgrade", "websocket")
-                .body(())
-                .unwrap(),
-            ),
+            perform_handshake(&server, &mut sender, &mut receiver),
         )
         .await
-        {
-            Ok(Ok(result)) => result,
-            Ok(Err(err)) => {
-                drop(request.responder.send(Err(Error::from(err))));
-                continue;
-            }
-            Err(_) => {
-                drop(request.responder.send(Err(Error::connect_timeout())));
-                continue;
-            }
-        };
-
-        let (mut sender, receiver) = stream.split();
+        .unwrap_or_else(|_| Err(Error::connect_timeout()));
+        if let Err(err) = negotiation {
+            drop(request.responder.send(Err(err)));
+            continue;
+        }
 
         let outstanding_requests = OutstandingRequestMapHandle::default();
         {
@@ -98,6 +95,62 @@ pub(super) async fn reconnecting_client_loop(
     Ok(())
 }
 
+/// Opens the WebSocket connection to `server`, tunneling it through the
+/// configured proxy if there is one.
+async fn connect(
+    server: &ConnectionInfo,
+    protocol_versions: &[&str],
+) -> Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, Response), Error> {
+    let request = Request::get(server.url.as_str())
+        .header("Sec-WebSocket-Protocol", protocol_versions.join(", "))
+        .header("Sec-WebSocket-Version", "13")
+        .header("Sec-WebSocket-Key", generate_key())
+        .header("Host", server.url.host_str().expect("no host"))
+        .header("Connection", "Upgrade")
+        .header("Upgrade", "websocket")
+        .body(())
+        .unwrap();
+    match &server.proxy {
+        Some(proxy) => {
+            let stream = proxy.connect(&server.url).await?;
+            Ok(tokio_tungstenite::client_async_tls(request, stream).await?)
+        }
+        None => Ok(tokio_tungstenite::connect_async(request).await?),
+    }
+}
+
+/// Performs the [`Handshake`](crate::client::Handshake) of a newly
+/// established connection before any other requests are sent.
+async fn perform_handshake(
+    server: &ConnectionInfo,
+    sender: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+    receiver: &mut SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+) -> Result<(), Error> {
+    let mut request = server.handshake.request()?;
+    loop {
+        sender
+            .send(Message::Binary(bincode::serialize(&request)?))
+            .await?;
+        let payload = loop {
+            match receiver.next().await {
+                Some(message) => {
+                    if let Message::Binary(response) = message? {
+                        break bincode::deserialize::<Payload>(&response)?;
+                    }
+                }
+                None => return Err(Error::disconnected()),
+            }
+        };
+        match server
+            .handshake
+            .finish(payload.value.map_err(Error::from))?
+        {
+            Some(next) => request = next,
+            None => return Ok(()),
+        }
+    }
+}
+
 async fn request_sender(
     request_receiver: &Receiver<PendingRequest>,
     mut sender: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
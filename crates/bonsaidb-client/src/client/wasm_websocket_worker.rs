@@ -13,28 +13,30 @@ use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{CloseEvent, ErrorEvent, MessageEvent, WebSocket};
 
 use crate::client::{
-    disconnect_pending_requests, AnyApiCallback, OutstandingRequestMapHandle, PendingRequest,
-    SubscriberMap,
+    disconnect_pending_requests, AnyApiCallback, Handshake, OutstandingRequestMapHandle,
+    PendingRequest, SubscriberMap,
 };
 use crate::Error;
 
 #[allow(clippy::too_many_arguments)]
 pub fn spawn_client(
     url: Arc<Url>,
-    protocol_version: &'static str,
+    protocol_versions: &'static [&'static str],
     request_receiver: Receiver<PendingRequest>,
     custom_apis: Arc<HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>>,
     subscribers: SubscriberMap,
+    handshake: Handshake,
     connection_counter: Arc<AtomicU32>,
     pending_error: Option<Error>,
     connect_timeout: Duration,
 ) {
     wasm_bindgen_futures::spawn_local(create_websocket(
         url,
-        protocol_version,
+        protocol_versions,
         request_receiver,
         custom_apis,
         subscribers,
+        handshake,
         connection_counter,
         pending_error,
         connect_timeout,
@@ -44,10 +46,11 @@ pub fn spawn_client(
 #[allow(clippy::too_many_arguments)]
 async fn create_websocket(
     url: Arc<Url>,
-    protocol_version: &'static str,
+    protocol_versions: &'static [&'static str],
     request_receiver: Receiver<PendingRequest>,
     custom_apis: Arc<HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>>,
     subscribers: SubscriberMap,
+    handshake: Handshake,
     connection_counter: Arc<AtomicU32>,
     pending_error: Option<Error>,
     connect_timeout: Duration,
@@ -70,7 +73,11 @@ async fn create_websocket(
     // In wasm we're not going to have a real loop. We're going create a
     // websocket and store it in JS. This will allow us to get around Send/Sync
     // issues since each access of the websocket can pull it from js.
-    let ws = match WebSocket::new_with_str(&url.to_string(), protocol_version) {
+    let protocols = protocol_versions
+        .iter()
+        .map(|version| JsValue::from_str(version))
+        .collect::<js_sys::Array>();
+    let ws = match WebSocket::new_with_str_sequence(&url.to_string(), &protocols) {
         Ok(ws) => ws,
         Err(err) => {
             drop(
@@ -80,10 +87,11 @@ async fn create_websocket(
             );
             spawn_client(
                 url,
-                protocol_version,
+                protocol_versions,
                 request_receiver,
                 custom_apis.clone(),
                 subscribers,
+                handshake,
                 connection_counter,
                 None,
                 connect_timeout,
@@ -109,6 +117,7 @@ async fn create_websocket(
         initial_request.clone(),
         outstanding_requests.clone(),
         ws.clone(),
+        handshake.clone(),
     );
     ws.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
 
@@ -130,7 +139,7 @@ async fn create_websocket(
 
     let onclose_callback = on_close_callback(
         url.clone(),
-        protocol_version,
+        protocol_versions,
         request_receiver.clone(),
         shutdown_sender,
         ws.clone(),
@@ -138,6 +147,7 @@ async fn create_websocket(
         outstanding_requests,
         custom_apis.clone(),
         subscribers.clone(),
+        handshake,
         connection_counter.clone(),
         connect_timeout,
     );
@@ -174,16 +184,22 @@ fn on_open_callback(
     initial_request: Arc<Mutex<Option<PendingRequest>>>,
     requests: OutstandingRequestMapHandle,
     ws: WebSocket,
+    handshake: Handshake,
 ) -> JsValue {
     Closure::once_into_js(move || {
         wasm_bindgen_futures::spawn_local(async move {
             if let Some(initial_request) = take_initial_request(&initial_request) {
-                if send_request(&ws, initial_request, &requests).await {
-                    while let Ok(pending) = request_receiver.recv_async().await {
-                        if !send_request(&ws, pending, &requests).await {
-                            break;
+                match perform_handshake(&ws, &handshake, &requests).await {
+                    Ok(()) => {
+                        if send_request(&ws, initial_request, &requests).await {
+                            while let Ok(pending) = request_receiver.recv_async().await {
+                                if !send_request(&ws, pending, &requests).await {
+                                    break;
+                                }
+                            }
                         }
                     }
+                    Err(err) => drop(initial_request.responder.send(Err(err))),
                 }
             }
 
@@ -193,6 +209,27 @@ fn on_open_callback(
     })
 }
 
+/// Performs the [`Handshake`] of a newly established connection before any
+/// other requests are sent.
+#[allow(clippy::future_not_send)]
+async fn perform_handshake(
+    ws: &WebSocket,
+    handshake: &Handshake,
+    requests: &OutstandingRequestMapHandle,
+) -> Result<(), Error> {
+    let mut request = handshake.request()?;
+    loop {
+        let (responder, response) = flume::bounded(1);
+        let pending = PendingRequest { request, responder };
+        // If sending fails, the error is sent to the responder.
+        send_request(ws, pending, requests).await;
+        match handshake.finish(response.recv_async().await?)? {
+            Some(next) => request = next,
+            None => return Ok(()),
+        }
+    }
+}
+
 #[allow(clippy::future_not_send)]
 async fn send_request(
     ws: &WebSocket,
@@ -312,7 +349,7 @@ fn take_initial_request(initial_request: &Mutex<Option<PendingRequest>>) -> Opti
 #[allow(clippy::too_many_arguments)]
 fn on_close_callback(
     url: Arc<Url>,
-    protocol_version: &'static str,
+    protocol_versions: &'static [&'static str],
     request_receiver: Receiver<PendingRequest>,
     shutdown: flume::Sender<()>,
     ws: WebSocket,
@@ -320,6 +357,7 @@ fn on_close_callback(
     outstanding_requests: OutstandingRequestMapHandle,
     custom_apis: Arc<HashMap<ApiName, Option<Arc<dyn AnyApiCallback>>>>,
     subscribers: SubscriberMap,
+    handshake: Handshake,
     connection_counter: Arc<AtomicU32>,
     connect_timeout: Duration,
 ) -> JsValue {
@@ -346,10 +384,11 @@ fn on_close_callback(
 
             spawn_client(
                 url,
-                protocol_version,
+                protocol_versions,
                 request_receiver,
                 custom_apis.clone(),
                 subscribers,
+                handshake,
                 connection_counter,
                 pending_error,
                 connect_timeout,
@@ -1,5 +1,5 @@
 use bonsaidb_core::arc_bytes::serde::Bytes;
-use bonsaidb_core::networking;
+use bonsaidb_core::networking::{self, Capability};
 use bonsaidb_core::schema::Name;
 
 /// Errors related to working with the BonsaiDb client.
@@ -32,9 +32,41 @@ pub enum Error {
         error: Bytes,
     },
 
+    /// The server does not support any of the protocol versions offered by
+    /// this client.
+    #[error("no protocol version is supported by both the client and the server")]
+    IncompatibleProtocol,
+
     /// The server is incompatible with this version of the client.
+    #[deprecated = "no longer returned, use `IncompatibleProtocol` instead"]
     #[error("server incompatible with client protocol version")]
     ProtocolVersionMismatch,
+
+    /// The server does not support capabilities that were required using
+    /// [`Builder::with_required_capability()`](crate::Builder::with_required_capability).
+    #[error("the server does not support the required capabilities: {}", display_capabilities(.0))]
+    MissingCapabilities(Vec<Capability>),
+
+    /// The server requires requests to be signed, but this client was not
+    /// given a key using
+    /// [`Builder::with_request_signing_key()`](crate::Builder::with_request_signing_key).
+    #[error("the server requires requests to be signed")]
+    RequestSigningRequired,
+
+    /// The proxy configured using
+    /// [`Builder::with_proxy()`](crate::Builder::with_proxy) could not
+    /// establish a connection to the server.
+    #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+    #[error("proxy error: {0}")]
+    Proxy(String),
+}
+
+fn display_capabilities(capabilities: &[Capability]) -> String {
+    capabilities
+        .iter()
+        .map(Capability::name)
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 impl Error {
@@ -119,7 +151,7 @@ impl From<crate::client::WebSocketError> for Error {
     fn from(err: crate::client::WebSocketError) -> Self {
         if let crate::client::WebSocketError::Http(response) = &err {
             if response.status() == 406 {
-                return Self::ProtocolVersionMismatch;
+                return Self::IncompatibleProtocol;
             }
         }
 
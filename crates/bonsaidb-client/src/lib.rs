@@ -20,6 +20,10 @@ pub use url;
 mod builder;
 mod client;
 mod error;
+#[cfg(not(target_arch = "wasm32"))]
+mod outbox;
+#[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+mod proxy;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub use fabruic;
@@ -29,3 +33,7 @@ pub use self::client::{ApiCallback, AsyncClient, AsyncRemoteDatabase, AsyncRemot
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::client::{BlockingClient, BlockingRemoteDatabase, BlockingRemoteSubscriber};
 pub use self::error::{ApiError, Error};
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::outbox::{OutboxConflict, OUTBOX_NAMESPACE};
+#[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+pub use self::proxy::{ProxyConfig, ProxyProtocol};
@@ -0,0 +1,446 @@
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bonsaidb_core::api::{Api, ApiName};
+use bonsaidb_core::arc_bytes::serde::Bytes;
+use bonsaidb_core::keyvalue::{
+    Command, DurabilityMode, KeyOperation, KeyValue, Output, ScanCommand, SetCommand, Value,
+};
+use bonsaidb_core::networking::{
+    ApplyTransaction, ApplyTransactionReturningId, DeleteDocs, DeleteDocsReturningHeaders,
+    ExecuteKeyOperation, IdempotentRequest,
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// An operation replayed from a client's outbox that the server rejected.
+///
+/// See [`Builder::with_outbox()`](crate::Builder::with_outbox) for more
+/// information.
+#[derive(Debug, Clone)]
+pub struct OutboxConflict {
+    /// The client-generated id of the operation.
+    pub operation_id: u64,
+    /// The name of the operation's api.
+    pub api: ApiName,
+    /// The serialized request.
+    pub request: Bytes,
+    /// The error the server responded with.
+    pub error: bonsaidb_core::Error,
+}
+
+pub type ConflictCallback = Arc<dyn Fn(OutboxConflict) + Send + Sync>;
+
+type KeyOperator = Box<dyn Fn(KeyOperation) -> Result<Output, bonsaidb_core::Error> + Send + Sync>;
+
+/// The Key-Value namespace an outbox's operations are stored in when using
+/// [`Builder::with_outbox_store()`](crate::Builder::with_outbox_store).
+pub const OUTBOX_NAMESPACE: &str = "bonsaidb-outbox";
+
+/// A journal of mutating requests that have not been acknowledged by the
+/// server.
+///
+/// The journal is either an append-only file of length-prefixed records, or a
+/// Key-Value store containing one entry per pending operation. A file journal
+/// is compacted each time it is opened, and truncated whenever it no longer
+/// contains any pending operations.
+///
+/// Records are written to the journal without being synced, which allows them
+/// to survive the process exiting but not the operating system crashing. A
+/// Key-Value journal is persisted according to its database's
+/// configuration.
+pub struct Outbox {
+    journal: Mutex<Journal>,
+    on_conflict: Option<ConflictCallback>,
+}
+
+impl Debug for Outbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Outbox")
+            .field("pending", &self.journal.lock().pending.len())
+            .finish_non_exhaustive()
+    }
+}
+
+struct Journal {
+    storage: JournalStorage,
+    pending: Vec<QueuedOperation>,
+}
+
+enum JournalStorage {
+    File(File),
+    KeyValue {
+        execute: KeyOperator,
+        next_sequence: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Record {
+    Queued(QueuedOperation),
+    Completed(u64),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueuedOperation {
+    operation_id: u64,
+    name: ApiName,
+    value: Bytes,
+    /// The position of this operation within a Key-Value journal.
+    #[serde(default)]
+    sequence: u64,
+}
+
+impl QueuedOperation {
+    /// Returns the serialized [`IdempotentRequest`] for this operation.
+    pub fn request(&self) -> Result<Bytes, Error> {
+        idempotent_request(self.operation_id, self.name.clone(), self.value.clone())
+    }
+}
+
+impl Outbox {
+    /// Opens the outbox stored at `path`, creating it if it doesn't exist.
+    pub fn open(path: &Path, on_conflict: Option<ConflictCallback>) -> Result<Self, Error> {
+        let contents = match std::fs::read(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(io_error(err)),
+        };
+
+        let mut pending = Vec::<QueuedOperation>::new();
+        let mut remaining = &contents[..];
+        while let Some(record) = read_record(&mut remaining) {
+            match record {
+                Record::Queued(operation) => pending.push(operation),
+                Record::Completed(operation_id) => {
+                    pending.retain(|operation| operation.operation_id != operation_id);
+                }
+            }
+        }
+
+        // Rewriting the journal discards completed operations and any record
+        // that was only partially written.
+        let mut compacted = Vec::new();
+        for operation in &pending {
+            write_record(&Record::Queued(operation.clone()), &mut compacted)?;
+        }
+        let temporary = path.with_extension("compacting");
+        let mut file = File::create(&temporary).map_err(io_error)?;
+        file.write_all(&compacted).map_err(io_error)?;
+        file.sync_all().map_err(io_error)?;
+        drop(file);
+        std::fs::rename(&temporary, path).map_err(io_error)?;
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(io_error)?;
+        Ok(Self {
+            journal: Mutex::new(Journal {
+                storage: JournalStorage::File(file),
+                pending,
+            }),
+            on_conflict,
+        })
+    }
+
+    /// Opens the outbox stored in [`OUTBOX_NAMESPACE`] of `store`.
+    pub fn open_key_value<KV: KeyValue + 'static>(
+        store: KV,
+        on_conflict: Option<ConflictCallback>,
+    ) -> Result<Self, Error> {
+        let execute: KeyOperator = Box::new(move |op| store.execute_key_operation(op));
+        let mut pending = Vec::<QueuedOperation>::new();
+        let mut start_after = None;
+        loop {
+            let Output::Entries(entries) = execute(KeyOperation {
+                namespace: Some(OUTBOX_NAMESPACE.to_string()),
+                key: String::new(),
+                command: Command::Scan(ScanCommand {
+                    start_after: start_after.take(),
+                    limit: Some(KEY_VALUE_SCAN_LIMIT),
+                    filter: None,
+                }),
+            })?
+            else {
+                return Err(Error::Core(bonsaidb_core::Error::other(
+                    "outbox",
+                    "unexpected output from scan",
+                )));
+            };
+            let finished = entries.len() < KEY_VALUE_SCAN_LIMIT as usize;
+            for entry in entries {
+                if let Value::Bytes(bytes) = &entry.value {
+                    pending.push(pot::from_slice(bytes)?);
+                }
+                start_after = Some(entry.key);
+            }
+            if finished {
+                break;
+            }
+        }
+
+        let next_sequence = pending.last().map_or(0, |operation| operation.sequence + 1);
+        Ok(Self {
+            journal: Mutex::new(Journal {
+                storage: JournalStorage::KeyValue {
+                    execute,
+                    next_sequence,
+                },
+                pending,
+            }),
+            on_conflict,
+        })
+    }
+
+    /// Journals the request if it is an operation that modifies data,
+    /// returning the queued operation. Returns `None` if the request is not
+    /// journaled.
+    pub fn queue(&self, name: &ApiName, value: &Bytes) -> Result<Option<QueuedOperation>, Error> {
+        if !is_write(name, value) {
+            return Ok(None);
+        }
+
+        let mut operation = QueuedOperation {
+            operation_id: rand::random(),
+            name: name.clone(),
+            value: value.clone(),
+            sequence: 0,
+        };
+        let mut journal = self.journal.lock();
+        journal.queue(&mut operation)?;
+        journal.pending.push(operation.clone());
+        Ok(Some(operation))
+    }
+
+    /// Returns the operations that have not been acknowledged, in the order
+    /// they were queued.
+    pub fn pending(&self) -> Vec<QueuedOperation> {
+        self.journal.lock().pending.clone()
+    }
+
+    /// Returns true if any operations have not been acknowledged.
+    pub fn has_pending(&self) -> bool {
+        !self.journal.lock().pending.is_empty()
+    }
+
+    /// Marks `operation` complete if `result` was received from the server.
+    /// Returns false if the server may not have received the operation, which
+    /// leaves it queued to be replayed.
+    pub fn finished(&self, operation: &QueuedOperation, result: &Result<Bytes, Error>) -> bool {
+        if !is_acknowledged(result) {
+            return false;
+        }
+
+        let mut journal = self.journal.lock();
+        journal
+            .pending
+            .retain(|pending| pending.operation_id != operation.operation_id);
+        if let Err(err) = journal.complete(operation) {
+            log::warn!("[client] error recording completed outbox operation: {err}");
+        }
+        true
+    }
+
+    /// Records the `result` of replaying `operation`, reporting the error to
+    /// the conflict callback if the server rejected it. Returns an error if
+    /// the server did not acknowledge the operation.
+    pub fn replayed(
+        &self,
+        operation: &QueuedOperation,
+        result: Result<Bytes, Error>,
+    ) -> Result<(), Error> {
+        if !self.finished(operation, &result) {
+            return result.map(|_| ());
+        }
+
+        let error = match result {
+            Ok(response) => {
+                pot::from_slice::<Result<pot::Value<'_>, bonsaidb_core::Error>>(&response)
+                    .ok()
+                    .and_then(Result::err)
+            }
+            Err(err) => Some(bonsaidb_core::Error::from(err)),
+        };
+        if let Some(error) = error {
+            let conflict = OutboxConflict {
+                operation_id: operation.operation_id,
+                api: operation.name.clone(),
+                request: operation.value.clone(),
+                error,
+            };
+            match &self.on_conflict {
+                Some(on_conflict) => on_conflict(conflict),
+                None => log::warn!(
+                    "[client] replayed outbox operation {} was rejected: {}",
+                    conflict.api,
+                    conflict.error
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Journal {
+    fn queue(&mut self, operation: &mut QueuedOperation) -> Result<(), Error> {
+        match &mut self.storage {
+            JournalStorage::File(file) => append(file, &Record::Queued(operation.clone())),
+            JournalStorage::KeyValue {
+                execute,
+                next_sequence,
+            } => {
+                operation.sequence = *next_sequence;
+                execute(KeyOperation {
+                    namespace: Some(OUTBOX_NAMESPACE.to_string()),
+                    key: sequence_key(operation.sequence),
+                    command: Command::Set(SetCommand {
+                        value: Value::Bytes(Bytes::from(pot::to_vec(&*operation)?)),
+                        expiration: None,
+                        keep_existing_expiration: false,
+                        check: None,
+                        return_previous_value: false,
+                        durability: DurabilityMode::Deferred,
+                    }),
+                })?;
+                *next_sequence += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn complete(&mut self, operation: &QueuedOperation) -> Result<(), Error> {
+        match &mut self.storage {
+            JournalStorage::File(file) => {
+                if self.pending.is_empty() {
+                    file.set_len(0).map_err(io_error)
+                } else {
+                    append(file, &Record::Completed(operation.operation_id))
+                }
+            }
+            JournalStorage::KeyValue { execute, .. } => {
+                execute(KeyOperation {
+                    namespace: Some(OUTBOX_NAMESPACE.to_string()),
+                    key: sequence_key(operation.sequence),
+                    command: Command::Delete,
+                })?;
+                Ok(())
+            }
+        }
+    }
+}
+
+const KEY_VALUE_SCAN_LIMIT: u32 = 100;
+
+/// Returns the key of the operation at `sequence`. The keys are zero-padded
+/// so that scanning returns operations in the order they were queued.
+fn sequence_key(sequence: u64) -> String {
+    format!("{sequence:020}")
+}
+
+fn append(file: &mut File, record: &Record) -> Result<(), Error> {
+    let mut bytes = Vec::new();
+    write_record(record, &mut bytes)?;
+    file.write_all(&bytes).map_err(io_error)
+}
+
+fn write_record(record: &Record, bytes: &mut Vec<u8>) -> Result<(), Error> {
+    let record = pot::to_vec(record)?;
+    let length = u32::try_from(record.len())
+        .map_err(|_| Error::Core(bonsaidb_core::Error::other("outbox", "record too large")))?;
+    bytes.extend_from_slice(&length.to_be_bytes());
+    bytes.extend_from_slice(&record);
+    Ok(())
+}
+
+fn read_record(remaining: &mut &[u8]) -> Option<Record> {
+    let length = remaining.get(..4)?;
+    let length = usize::try_from(u32::from_be_bytes(length.try_into().ok()?)).ok()?;
+    let record = remaining.get(4..4 + length)?;
+    let record = pot::from_slice(record).ok()?;
+    *remaining = &remaining[4 + length..];
+    Some(record)
+}
+
+fn io_error(err: io::Error) -> Error {
+    Error::Core(bonsaidb_core::Error::other("outbox", err))
+}
+
+/// Returns the serialized [`IdempotentRequest`] executing `value` as the
+/// operation `operation_id`.
+pub fn idempotent_request(operation_id: u64, name: ApiName, value: Bytes) -> Result<Bytes, Error> {
+    Ok(Bytes::from(pot::to_vec(&IdempotentRequest {
+        operation_id,
+        name,
+        value,
+    })?))
+}
+
+/// The delay before the first retry of a write.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(50);
+/// The longest delay between retries of a write.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Returns how long to wait before sending retry number `retry` of a write,
+/// starting at 1. The delay doubles with each retry up to
+/// [`MAX_RETRY_DELAY`], and is randomly shortened by up to half to keep
+/// clients that were disconnected at the same time from retrying in unison.
+pub fn retry_delay(retry: usize) -> Duration {
+    let doublings = u32::try_from(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+    let delay = 2_u32
+        .checked_pow(doublings)
+        .and_then(|factor| INITIAL_RETRY_DELAY.checked_mul(factor))
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY));
+    delay.mul_f64(rand::random::<f64>().mul_add(0.5, 0.5))
+}
+
+/// Returns true if the request modifies data. Read requests are never
+/// journaled or retried.
+pub fn is_write(name: &ApiName, request: &[u8]) -> bool {
+    if *name == ApplyTransaction::name()
+        || *name == ApplyTransactionReturningId::name()
+        || *name == DeleteDocs::name()
+        || *name == DeleteDocsReturningHeaders::name()
+    {
+        true
+    } else if *name == ExecuteKeyOperation::name() {
+        pot::from_slice::<ExecuteKeyOperation>(request).map_or(false, |request| {
+            !matches!(request.op.command, Command::Get { delete: false })
+        })
+    } else {
+        false
+    }
+}
+
+/// Returns true if `result` was received from the server, as opposed to being
+/// caused by the client being unable to communicate with the server.
+#[allow(deprecated)]
+pub fn is_acknowledged(result: &Result<Bytes, Error>) -> bool {
+    match result {
+        Ok(_) | Err(Error::Api { .. }) => true,
+        Err(
+            Error::Network(_)
+            | Error::Core(bonsaidb_core::Error::Networking(_))
+            | Error::InvalidUrl(_)
+            | Error::IncompatibleProtocol
+            | Error::ProtocolVersionMismatch
+            | Error::MissingCapabilities(_)
+            | Error::RequestSigningRequired,
+        ) => false,
+        #[cfg(feature = "websockets")]
+        Err(Error::WebSocket(_)) => false,
+        #[cfg(all(feature = "websockets", not(target_arch = "wasm32")))]
+        Err(Error::Proxy(_)) => false,
+        // Errors from the transport layers are converted to `Other` errors.
+        Err(Error::Core(bonsaidb_core::Error::Other { origin, .. })) => {
+            !matches!(origin.as_str(), "quic" | "bincode")
+        }
+        Err(Error::Core(_)) => true,
+    }
+}
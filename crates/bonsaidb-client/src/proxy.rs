@@ -0,0 +1,266 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use base64::Engine;
+use bonsaidb_core::connection::SensitiveString;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use url::Url;
+
+use crate::client::WebSocketError;
+use crate::Error;
+
+/// The longest HTTP CONNECT response accepted from a proxy.
+const MAX_HTTP_RESPONSE_LENGTH: usize = 8 * 1024;
+
+/// The protocol used to communicate with a proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyProtocol {
+    /// An HTTP proxy that supports the `CONNECT` method.
+    Http,
+    /// A SOCKS version 5 proxy.
+    Socks5,
+}
+
+/// A proxy that WebSocket connections are established through. Used with
+/// [`Builder::with_proxy()`](crate::Builder::with_proxy).
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    protocol: ProxyProtocol,
+    address: String,
+    credentials: Option<(String, SensitiveString)>,
+}
+
+impl ProxyConfig {
+    /// Returns a configuration for an HTTP proxy listening on `address`,
+    /// which is a `host:port` pair.
+    pub fn http(address: impl Into<String>) -> Self {
+        Self::new(ProxyProtocol::Http, address)
+    }
+
+    /// Returns a configuration for a SOCKS5 proxy listening on `address`,
+    /// which is a `host:port` pair.
+    pub fn socks5(address: impl Into<String>) -> Self {
+        Self::new(ProxyProtocol::Socks5, address)
+    }
+
+    /// Returns a configuration for a proxy listening on `address` using
+    /// `protocol`.
+    pub fn new(protocol: ProxyProtocol, address: impl Into<String>) -> Self {
+        Self {
+            protocol,
+            address: address.into(),
+            credentials: None,
+        }
+    }
+
+    /// Authenticates with the proxy using `username` and `password`. HTTP
+    /// proxies receive the credentials using the `Basic` scheme, and SOCKS5
+    /// proxies using username/password authentication (RFC 1929).
+    #[must_use]
+    pub fn with_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: SensitiveString,
+    ) -> Self {
+        self.credentials = Some((username.into(), password));
+        self
+    }
+
+    /// Returns the protocol used to communicate with the proxy.
+    #[must_use]
+    pub const fn protocol(&self) -> ProxyProtocol {
+        self.protocol
+    }
+
+    /// Returns the `host:port` pair the proxy is listening on.
+    #[must_use]
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Opens a connection to the proxy and asks it to connect to the host and
+    /// port of `url`. The returned stream is connected to the destination.
+    pub(crate) async fn connect(&self, url: &Url) -> Result<TcpStream, Error> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| Error::InvalidUrl(String::from("url has no host")))?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| Error::InvalidUrl(String::from("url has no port")))?;
+
+        let mut stream = TcpStream::connect(&self.address).await.map_err(io_error)?;
+        match self.protocol {
+            ProxyProtocol::Http => self.http_connect(&mut stream, host, port).await?,
+            ProxyProtocol::Socks5 => self.socks5_connect(&mut stream, host, port).await?,
+        }
+        Ok(stream)
+    }
+
+    async fn http_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), Error> {
+        // `host_str()` includes the brackets around IPv6 addresses, which are
+        // also required in the request's authority.
+        let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+        if let Some((username, password)) = &self.credentials {
+            let encoded = base64::engine::general_purpose::STANDARD
+                .encode(format!("{username}:{}", password.0));
+            request.push_str("Proxy-Authorization: Basic ");
+            request.push_str(&encoded);
+            request.push_str("\r\n");
+        }
+        request.push_str("\r\n");
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(io_error)?;
+
+        // The response is read one byte at a time to avoid consuming any data
+        // sent by the destination after the proxy's response.
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_HTTP_RESPONSE_LENGTH {
+                return Err(Error::Proxy(String::from("response too long")));
+            }
+            response.push(stream.read_u8().await.map_err(io_error)?);
+        }
+
+        let status_line = String::from_utf8_lossy(&response);
+        let status_line = status_line.lines().next().unwrap_or_default();
+        let mut parts = status_line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some(version), Some(status)) if version.starts_with("HTTP/1.") => {
+                if status.starts_with('2') {
+                    Ok(())
+                } else {
+                    Err(Error::Proxy(format!("proxy responded with {status_line}")))
+                }
+            }
+            _ => Err(Error::Proxy(format!("invalid response: {status_line}"))),
+        }
+    }
+
+    async fn socks5_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), Error> {
+        const VERSION: u8 = 5;
+        const NO_AUTHENTICATION: u8 = 0;
+        const USERNAME_PASSWORD: u8 = 2;
+        const NO_ACCEPTABLE_METHODS: u8 = 0xFF;
+
+        let greeting = if self.credentials.is_some() {
+            vec![VERSION, 2, NO_AUTHENTICATION, USERNAME_PASSWORD]
+        } else {
+            vec![VERSION, 1, NO_AUTHENTICATION]
+        };
+        stream.write_all(&greeting).await.map_err(io_error)?;
+
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply).await.map_err(io_error)?;
+        if reply[0] != VERSION {
+            return Err(Error::Proxy(String::from("proxy is not a SOCKS5 proxy")));
+        }
+        match (reply[1], &self.credentials) {
+            (NO_AUTHENTICATION, _) => {}
+            (USERNAME_PASSWORD, Some((username, password))) => {
+                let username = username.as_bytes();
+                let password = password.0.as_bytes();
+                let (Ok(username_length), Ok(password_length)) =
+                    (u8::try_from(username.len()), u8::try_from(password.len()))
+                else {
+                    return Err(Error::Proxy(String::from(
+                        "SOCKS5 credentials must be shorter than 256 bytes",
+                    )));
+                };
+                let mut authentication = vec![1, username_length];
+                authentication.extend_from_slice(username);
+                authentication.push(password_length);
+                authentication.extend_from_slice(password);
+                stream.write_all(&authentication).await.map_err(io_error)?;
+
+                stream.read_exact(&mut reply).await.map_err(io_error)?;
+                if reply[1] != 0 {
+                    return Err(Error::Proxy(String::from("proxy rejected the credentials")));
+                }
+            }
+            (NO_ACCEPTABLE_METHODS, _) => {
+                return Err(Error::Proxy(String::from(
+                    "proxy requires an unsupported authentication method",
+                )));
+            }
+            (method, _) => {
+                return Err(Error::Proxy(format!(
+                    "proxy selected an unexpected authentication method: {method}"
+                )));
+            }
+        }
+
+        let mut request = vec![VERSION, 1, 0];
+        let unbracketed = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(address) = unbracketed.parse::<Ipv4Addr>() {
+            request.push(1);
+            request.extend_from_slice(&address.octets());
+        } else if let Ok(address) = unbracketed.parse::<Ipv6Addr>() {
+            request.push(4);
+            request.extend_from_slice(&address.octets());
+        } else {
+            let length = u8::try_from(host.len())
+                .map_err(|_| Error::Proxy(String::from("host name too long")))?;
+            request.push(3);
+            request.push(length);
+            request.extend_from_slice(host.as_bytes());
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await.map_err(io_error)?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply).await.map_err(io_error)?;
+        if reply[1] != 0 {
+            return Err(Error::Proxy(format!(
+                "proxy could not connect: {}",
+                socks5_reply_message(reply[1])
+            )));
+        }
+        // The reply ends with the address the proxy bound to, which isn't
+        // needed.
+        let address_length = match reply[3] {
+            1 => 4,
+            3 => usize::from(stream.read_u8().await.map_err(io_error)?),
+            4 => 16,
+            other => {
+                return Err(Error::Proxy(format!("invalid address type: {other}")));
+            }
+        };
+        let mut bound_address = vec![0; address_length + 2];
+        stream
+            .read_exact(&mut bound_address)
+            .await
+            .map_err(io_error)?;
+
+        Ok(())
+    }
+}
+
+fn socks5_reply_message(reply: u8) -> &'static str {
+    match reply {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+fn io_error(err: std::io::Error) -> Error {
+    Error::from(WebSocketError::Io(err))
+}
@@ -1,8 +1,12 @@
+use std::fmt::Display;
+
 use serde::{Deserialize, Serialize};
 
 use crate::define_basic_unique_mapped_view;
 use crate::document::{CollectionDocument, Emit};
-use crate::schema::{Collection, NamedCollection, SchemaName};
+use crate::keyvalue::Timestamp;
+use crate::pubsub::Topic;
+use crate::schema::{Collection, CollectionName, NamedCollection, SchemaName};
 
 /// A database stored in BonsaiDb.
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize, Collection)]
@@ -12,6 +16,159 @@ pub struct Database {
     pub name: String,
     /// The schema defining the database.
     pub schema: SchemaName,
+    /// The limits on the resources this database can use.
+    #[serde(default)]
+    pub quota: DatabaseQuota,
+    /// When the database was created.
+    #[serde(default)]
+    pub created_at: Option<Timestamp>,
+}
+
+/// A database being created or deleted.
+///
+/// Events are published to the admin database's `PubSub` after the change to
+/// the database's record has been committed. Subscribe to this topic using
+/// [`Subscriber::subscribe_to_topic()`](crate::pubsub::Subscriber::subscribe_to_topic)
+/// on a subscriber of the admin database.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize, Topic)]
+#[topic(authority = "bonsaidb", name = "databases", core = crate)]
+pub enum DatabaseEvent {
+    /// A database was created.
+    Created {
+        /// The name of the database.
+        name: String,
+        /// The schema of the database.
+        schema: SchemaName,
+    },
+    /// A database was deleted.
+    Deleted {
+        /// The name of the database.
+        name: String,
+    },
+}
+
+/// Limits on the resources a database can use. See
+/// [`StorageConnection::set_database_quota()`](crate::connection::StorageConnection::set_database_quota).
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct DatabaseQuota {
+    /// The limit on the number of bytes the database uses on disk.
+    pub bytes: QuotaLimit,
+    /// The limit on the number of documents stored in each collection.
+    pub documents_per_collection: QuotaLimit,
+    /// The limit on the number of keys stored in the key-value store.
+    pub keys: QuotaLimit,
+}
+
+impl DatabaseQuota {
+    /// Builder-style method. Returns self after replacing the limit on the
+    /// number of bytes used on disk.
+    #[must_use]
+    pub const fn with_bytes(mut self, limit: QuotaLimit) -> Self {
+        self.bytes = limit;
+        self
+    }
+
+    /// Builder-style method. Returns self after replacing the limit on the
+    /// number of documents in each collection.
+    #[must_use]
+    pub const fn with_documents_per_collection(mut self, limit: QuotaLimit) -> Self {
+        self.documents_per_collection = limit;
+        self
+    }
+
+    /// Builder-style method. Returns self after replacing the limit on the
+    /// number of key-value keys.
+    #[must_use]
+    pub const fn with_keys(mut self, limit: QuotaLimit) -> Self {
+        self.keys = limit;
+        self
+    }
+
+    /// Returns true if no limits are set.
+    #[must_use]
+    pub const fn is_unlimited(&self) -> bool {
+        self.bytes.is_unlimited()
+            && self.documents_per_collection.is_unlimited()
+            && self.keys.is_unlimited()
+    }
+
+    /// Returns the limit for `kind`.
+    #[must_use]
+    pub const fn limit(&self, kind: &QuotaKind) -> &QuotaLimit {
+        match kind {
+            QuotaKind::Bytes => &self.bytes,
+            QuotaKind::Documents(_) => &self.documents_per_collection,
+            QuotaKind::Keys => &self.keys,
+        }
+    }
+}
+
+/// A limit on a resource used by a database.
+///
+/// Writes that would cause usage to exceed the `hard` limit are rejected with
+/// [`Error::QuotaExceeded`](crate::Error::QuotaExceeded). Exceeding the `soft`
+/// limit only produces a warning.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct QuotaLimit {
+    /// The usage above which warnings are produced.
+    pub soft: Option<u64>,
+    /// The usage that can't be exceeded.
+    pub hard: Option<u64>,
+}
+
+impl QuotaLimit {
+    /// Returns a limit with no soft limit and a hard limit of `limit`.
+    #[must_use]
+    pub const fn hard(limit: u64) -> Self {
+        Self {
+            soft: None,
+            hard: Some(limit),
+        }
+    }
+
+    /// Returns a limit with a soft limit of `limit` and no hard limit.
+    #[must_use]
+    pub const fn soft(limit: u64) -> Self {
+        Self {
+            soft: Some(limit),
+            hard: None,
+        }
+    }
+
+    /// Builder-style method. Returns self after replacing the soft limit with
+    /// `limit`.
+    #[must_use]
+    pub const fn with_soft(mut self, limit: u64) -> Self {
+        self.soft = Some(limit);
+        self
+    }
+
+    /// Returns true if neither limit is set.
+    #[must_use]
+    pub const fn is_unlimited(&self) -> bool {
+        self.soft.is_none() && self.hard.is_none()
+    }
+}
+
+/// A resource limited by a [`DatabaseQuota`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub enum QuotaKind {
+    /// The number of bytes the database uses on disk.
+    Bytes,
+    /// The number of documents stored in a collection.
+    Documents(CollectionName),
+    /// The number of keys stored in the key-value store.
+    Keys,
+}
+
+impl Display for QuotaKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bytes => f.write_str("bytes"),
+            Self::Documents(collection) => write!(f, "documents in {collection}"),
+            Self::Keys => f.write_str("keys"),
+        }
+    }
 }
 
 define_basic_unique_mapped_view!(
@@ -1,9 +1,18 @@
+use std::collections::HashSet;
+
 use serde::{Deserialize, Serialize};
 
+use crate::connection::Connection;
 use crate::define_basic_unique_mapped_view;
 use crate::document::{CollectionDocument, Emit};
-use crate::permissions::Statement;
-use crate::schema::{Collection, NamedCollection};
+use crate::permissions::{ActionNameList, Permissions, Statement};
+use crate::schema::{Collection, Nameable, NamedCollection, SerializedCollection};
+use crate::Error;
+
+/// The maximum number of levels of inheritance that are resolved by
+/// [`PermissionGroup::resolve()`]. Groups that are assigned directly are not
+/// counted.
+pub const MAX_PERMISSION_GROUP_INHERITANCE_DEPTH: usize = 16;
 
 /// A named group of permissions statements.
 #[derive(Clone, Debug, Serialize, Deserialize, Collection)]
@@ -14,6 +23,9 @@ pub struct PermissionGroup {
     pub name: String,
     /// The permission statements.
     pub statements: Vec<Statement>,
+    /// The IDs of the groups whose statements are inherited by this group.
+    #[serde(default)]
+    pub inherits: Vec<u64>,
 }
 
 impl PermissionGroup {
@@ -22,6 +34,7 @@ impl PermissionGroup {
         Self {
             name: name.into(),
             statements: Vec::new(),
+            inherits: Vec::new(),
         }
     }
 
@@ -30,10 +43,161 @@ impl PermissionGroup {
         self.statements = statements.into_iter().collect();
         self
     }
+
+    /// Builder-style method. Returns self after replacing the current
+    /// inherited groups with `group_ids`.
+    pub fn inheriting<I: IntoIterator<Item = u64>>(mut self, group_ids: I) -> Self {
+        self.inherits = group_ids.into_iter().collect();
+        self
+    }
+
+    /// Returns the groups with `group_ids`, along with every group they
+    /// inherit from, directly or indirectly. Each group is only returned once,
+    /// even if it is inherited multiple times. Groups that inherit from each
+    /// other are resolved without error, and groups that no longer exist are
+    /// ignored.
+    ///
+    /// If the inheritance is more than
+    /// [`MAX_PERMISSION_GROUP_INHERITANCE_DEPTH`] levels deep,
+    /// [`Error::PermissionGroupInheritanceTooDeep`] is returned.
+    pub fn resolve<C: Connection, I: IntoIterator<Item = u64>>(
+        group_ids: I,
+        admin: &C,
+    ) -> Result<Vec<CollectionDocument<Self>>, Error> {
+        let mut visited = HashSet::new();
+        let mut pending = group_ids
+            .into_iter()
+            .filter(|id| visited.insert(*id))
+            .collect::<Vec<_>>();
+        let mut groups = Vec::new();
+        let mut depth = 0;
+        while !pending.is_empty() {
+            if depth > MAX_PERMISSION_GROUP_INHERITANCE_DEPTH {
+                return Err(Error::PermissionGroupInheritanceTooDeep);
+            }
+            depth += 1;
+
+            let level = Self::get_multiple(&pending, admin)?;
+            pending = level
+                .iter()
+                .flat_map(|group| group.contents.inherits.iter().copied())
+                .filter(|id| visited.insert(*id))
+                .collect();
+            groups.extend(level);
+        }
+        Ok(groups)
+    }
+
+    /// Returns the permissions granted by `group`, including the permissions
+    /// of every group it inherits from.
+    pub fn resolved_permissions<'name, C: Connection>(
+        group: impl Nameable<'name, u64> + Send + Sync,
+        admin: &C,
+    ) -> Result<Permissions, Error> {
+        let group = Self::load(group, admin)?.ok_or(Error::PermissionGroupNotFound)?;
+        Ok(Self::merged_permissions(
+            Self::resolve([group.header.id], admin)?,
+            &Permissions::default(),
+        ))
+    }
+
+    /// Adds `inherited` to the groups `group` inherits from. Returns false if
+    /// `group` already inherited from `inherited`.
+    ///
+    /// If `inherited` already inherits from `group`, directly or indirectly,
+    /// [`Error::PermissionGroupInheritanceCycle`] is returned.
+    pub fn add_inherited_group<'group, 'inherited, C: Connection>(
+        group: impl Nameable<'group, u64> + Send + Sync,
+        inherited: impl Nameable<'inherited, u64> + Send + Sync,
+        admin: &C,
+    ) -> Result<bool, Error> {
+        let mut group = Self::load(group, admin)?.ok_or(Error::PermissionGroupNotFound)?;
+        let inherited = Self::load(inherited, admin)?.ok_or(Error::PermissionGroupNotFound)?;
+        if group.contents.inherits.contains(&inherited.header.id) {
+            return Ok(false);
+        }
+
+        if Self::resolve([inherited.header.id], admin)?
+            .iter()
+            .any(|ancestor| ancestor.header.id == group.header.id)
+        {
+            return Err(Error::PermissionGroupInheritanceCycle);
+        }
+
+        group.contents.inherits.push(inherited.header.id);
+        group.update(admin)?;
+        Ok(true)
+    }
+
+    /// Removes `inherited` from the groups `group` inherits from. Returns
+    /// false if `group` did not inherit from `inherited`.
+    pub fn remove_inherited_group<'group, 'inherited, C: Connection>(
+        group: impl Nameable<'group, u64> + Send + Sync,
+        inherited: impl Nameable<'inherited, u64> + Send + Sync,
+        admin: &C,
+    ) -> Result<bool, Error> {
+        let mut group = Self::load(group, admin)?.ok_or(Error::PermissionGroupNotFound)?;
+        let inherited = inherited
+            .name()?
+            .id::<Self, _>(admin)?
+            .ok_or(Error::PermissionGroupNotFound)?;
+        let old_len = group.contents.inherits.len();
+        group.contents.inherits.retain(|id| id != &inherited);
+        if old_len == group.contents.inherits.len() {
+            Ok(false)
+        } else {
+            group.update(admin)?;
+            Ok(true)
+        }
+    }
+
+    /// Returns an error if any of `statements` is malformed. A statement is
+    /// malformed if it has no resources, or if it allows an empty list of
+    /// actions. Neither can ever grant a permission, which usually indicates
+    /// a mistake when constructing the statement.
+    pub fn validate_statements(statements: &[Statement]) -> Result<(), Error> {
+        for (index, statement) in statements.iter().enumerate() {
+            let reason = if statement.resources.is_empty() {
+                "statement has no resources"
+            } else if matches!(&statement.actions, Some(ActionNameList::List(actions)) if actions.is_empty())
+            {
+                "statement allows an empty list of actions"
+            } else {
+                continue;
+            };
+
+            return Err(Error::InvalidPermissionStatement {
+                index,
+                reason: String::from(reason),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Combines the statements of `groups` with `inherit_permissions`.
+    pub(crate) fn merged_permissions(
+        groups: Vec<CollectionDocument<Self>>,
+        inherit_permissions: &Permissions,
+    ) -> Permissions {
+        Permissions::merged(
+            groups
+                .into_iter()
+                .map(|group| Permissions::from(group.contents.statements))
+                .collect::<Vec<_>>()
+                .iter()
+                .chain(std::iter::once(inherit_permissions)),
+        )
+    }
 }
 
 impl NamedCollection for PermissionGroup {
     type ByNameView = ByName;
+
+    fn rename_contents(contents: &mut Self, name: &str) -> Result<(), crate::Error> {
+        contents.name = name.to_string();
+        Ok(())
+    }
 }
 
 define_basic_unique_mapped_view!(
@@ -12,8 +12,8 @@ pub mod role;
 pub mod user;
 
 pub use self::authentication_token::AuthenticationToken;
-pub use self::database::Database;
-pub use self::group::PermissionGroup;
+pub use self::database::{Database, DatabaseEvent, DatabaseQuota, QuotaKind, QuotaLimit};
+pub use self::group::{PermissionGroup, MAX_PERMISSION_GROUP_INHERITANCE_DEPTH};
 pub use self::role::Role;
 pub use self::user::User;
 
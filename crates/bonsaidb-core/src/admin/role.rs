@@ -5,7 +5,7 @@ use crate::admin::group;
 use crate::connection::{AsyncStorageConnection, Connection, IdentityReference, StorageConnection};
 use crate::define_basic_unique_mapped_view;
 use crate::document::{CollectionDocument, Emit};
-use crate::schema::{Collection, Nameable, NamedCollection, SerializedCollection};
+use crate::schema::{Collection, Nameable, NamedCollection};
 
 /// An assignable role, which grants permissions based on the associated [`PermissionGroup`](crate::admin::PermissionGroup)s.
 #[derive(Clone, Debug, Serialize, Deserialize, Collection)]
@@ -55,24 +55,23 @@ impl Role {
         admin: &C,
         inherit_permissions: &Permissions,
     ) -> Result<Permissions, crate::Error> {
-        let groups = group::PermissionGroup::get_multiple(&self.groups, admin)?;
+        let groups = group::PermissionGroup::resolve(self.groups.iter().copied(), admin)?;
 
         // Combine the permissions from all the groups into one.
-        let merged_permissions = Permissions::merged(
-            groups
-                .into_iter()
-                .map(|group| Permissions::from(group.contents.statements))
-                .collect::<Vec<_>>()
-                .iter()
-                .chain(std::iter::once(inherit_permissions)),
-        );
-
-        Ok(merged_permissions)
+        Ok(group::PermissionGroup::merged_permissions(
+            groups,
+            inherit_permissions,
+        ))
     }
 }
 
 impl NamedCollection for Role {
     type ByNameView = ByName;
+
+    fn rename_contents(contents: &mut Self, name: &str) -> Result<(), crate::Error> {
+        contents.name = name.to_string();
+        Ok(())
+    }
 }
 
 define_basic_unique_mapped_view!(
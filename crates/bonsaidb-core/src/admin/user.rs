@@ -1,4 +1,3 @@
-use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use crate::admin::{group, role};
@@ -64,35 +63,23 @@ impl User {
         inherit_permissions: &Permissions,
     ) -> Result<Permissions, crate::Error> {
         // List all of the groups that this user belongs to because of role associations.
-        let role_groups = if self.roles.is_empty() {
+        let mut group_ids = if self.roles.is_empty() {
             Vec::default()
         } else {
-            let roles = role::Role::get_multiple(self.groups.iter(), admin)?;
+            let roles = role::Role::get_multiple(self.roles.iter(), admin)?;
             roles
                 .into_iter()
                 .flat_map(|doc| doc.contents.groups)
-                .unique()
                 .collect::<Vec<_>>()
         };
-        // Retrieve all of the groups.
-        let groups = if role_groups.is_empty() {
-            group::PermissionGroup::get_multiple(self.groups.iter(), admin)?
-        } else {
-            let mut all_groups = role_groups;
-            all_groups.extend(self.groups.iter().copied());
-            all_groups.dedup();
-            group::PermissionGroup::get_multiple(&all_groups, admin)?
-        };
+        group_ids.extend(self.groups.iter().copied());
+
+        // Retrieve all of the groups, including the groups they inherit from.
+        let groups = group::PermissionGroup::resolve(group_ids, admin)?;
 
         // Combine the permissions from all the groups into one.
-        let merged_permissions = Permissions::merged(
-            groups
-                .into_iter()
-                .map(|group| Permissions::from(group.contents.statements))
-                .collect::<Vec<_>>()
-                .iter()
-                .chain(std::iter::once(inherit_permissions)),
-        );
+        let merged_permissions =
+            group::PermissionGroup::merged_permissions(groups, inherit_permissions);
 
         Ok(merged_permissions)
     }
@@ -100,6 +87,11 @@ impl User {
 
 impl NamedCollection for User {
     type ByNameView = ByName;
+
+    fn rename_contents(contents: &mut Self, name: &str) -> Result<(), crate::Error> {
+        contents.username = name.to_string();
+        Ok(())
+    }
 }
 
 define_basic_unique_mapped_view!(
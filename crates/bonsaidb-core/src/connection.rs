@@ -1,9 +1,11 @@
 use std::borrow::Borrow;
+use std::collections::BTreeMap;
 use std::convert::Infallible;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::string::FromUtf8Error;
 use std::sync::Arc;
+use std::time::Duration;
 
 use actionable::{Action, Identifier};
 use arc_bytes::serde::Bytes;
@@ -18,11 +20,17 @@ use crate::document::{
     CollectionDocument, CollectionHeader, Document, HasHeader, Header, OwnedDocument,
 };
 use crate::key::{ByteSource, IntoPrefixRange, Key, KeyEncoding, KeyKind, KeyVisitor};
-use crate::permissions::Permissions;
+use crate::keyvalue::namespaced::Namespaced;
+use crate::keyvalue::Timestamp;
+use crate::permissions::bonsai::{
+    database_resource_name, BonsaiAction, DatabaseAction, DocumentAction, KeyValueAction,
+    PubSubAction, ServerAction, TransactionAction, ViewAction,
+};
+use crate::permissions::{Permissions, Statement};
 use crate::schema::view::map::{MappedDocuments, ViewMappings as ViewMappingsCurrent};
 use crate::schema::{
-    self, MappedValue, Nameable, NamedReference, Schema, SchemaName, SchemaSummary,
-    SerializedCollection,
+    self, MappedValue, Nameable, NamedCollection, NamedReference, Schema, SchemaName,
+    SchemaSummary, SerializedCollection,
 };
 use crate::{transaction, Error};
 
@@ -69,6 +77,17 @@ pub trait Connection: LowLevelConnection + Sized + Send + Sync {
     /// Fetches the last transaction id that has been committed, if any.
     fn last_transaction_id(&self) -> Result<Option<u64>, Error>;
 
+    /// Returns the id of the newest transaction this connection has applied
+    /// or observed, if this connection tracks transactions.
+    /// [`Consistency::Strong`] queries wait for views to be updated through
+    /// this transaction.
+    ///
+    /// The default implementation doesn't track transactions and returns
+    /// `None`.
+    fn last_seen_transaction_id(&self) -> Option<u64> {
+        None
+    }
+
     /// Compacts the entire database to reclaim unused disk space.
     ///
     /// This process is done by writing data to a new file and swapping the file
@@ -78,7 +97,7 @@ pub trait Connection: LowLevelConnection + Sized + Send + Sync {
     ///
     /// ## Errors
     ///
-    /// * [`Error::Other`]: an error occurred while compacting the database.
+    /// * [`Error::Storage`]: an error occurred while compacting the database.
     fn compact(&self) -> Result<(), crate::Error>;
 
     /// Compacts the collection to reclaim unused disk space.
@@ -91,7 +110,7 @@ pub trait Connection: LowLevelConnection + Sized + Send + Sync {
     /// ## Errors
     ///
     /// * [`Error::CollectionNotFound`]: database `name` does not exist.
-    /// * [`Error::Other`]: an error occurred while compacting the database.
+    /// * [`Error::Storage`]: an error occurred while compacting the database.
     fn compact_collection<C: schema::Collection>(&self) -> Result<(), crate::Error> {
         self.compact_collection_by_name(C::collection_name())
     }
@@ -105,8 +124,106 @@ pub trait Connection: LowLevelConnection + Sized + Send + Sync {
     ///
     /// ## Errors
     ///
-    /// * [`Error::Other`]: an error occurred while compacting the database.
+    /// * [`Error::Storage`]: an error occurred while compacting the database.
     fn compact_key_value_store(&self) -> Result<(), crate::Error>;
+
+    /// Retrieves the document with the lowest id in the collection `C`, or
+    /// `None` if the collection is empty.
+    fn first<C: SerializedCollection>(&self) -> Result<Option<CollectionDocument<C>>, Error> {
+        self.collection::<C>()
+            .first()?
+            .as_ref()
+            .map(CollectionDocument::try_from)
+            .transpose()
+    }
+
+    /// Retrieves the document with the highest id in the collection `C`, or
+    /// `None` if the collection is empty.
+    fn last<C: SerializedCollection>(&self) -> Result<Option<CollectionDocument<C>>, Error> {
+        self.collection::<C>()
+            .last()?
+            .as_ref()
+            .map(CollectionDocument::try_from)
+            .transpose()
+    }
+
+    /// Loads the document from the [`NamedCollection`] `C` with the given
+    /// `name` or id.
+    fn load_by_name<'name, C, N>(&self, name: N) -> Result<Option<CollectionDocument<C>>, Error>
+    where
+        C: NamedCollection + SerializedCollection + 'static,
+        N: Nameable<'name, C::PrimaryKey> + Send + Sync,
+    {
+        C::load(name, self)
+    }
+
+    /// Stores `contents` as the document named `name` in the
+    /// [`NamedCollection`] `C`. If a document named `name` already exists, it
+    /// is overwritten. Otherwise, a new document is created. `contents` is
+    /// expected to have the name `name`.
+    ///
+    /// This function is safe to call concurrently: if another client creates
+    /// a document with the same name first, the unique
+    /// [`NamedCollection::ByNameView`] rejects the insert and the other
+    /// client's document is overwritten instead.
+    fn upsert_by_name<C>(
+        &self,
+        name: &str,
+        contents: C::Contents,
+    ) -> Result<CollectionDocument<C>, Error>
+    where
+        C: NamedCollection + SerializedCollection + 'static,
+    {
+        if let Some(id) = NamedReference::from(name).id::<C, _>(self)? {
+            return C::overwrite(&id, contents, self).map_err(|err| err.error);
+        }
+
+        match C::push(contents, self) {
+            Ok(doc) => Ok(doc),
+            Err(err) if err.error.is_unique_key_error::<C::ByNameView, _>(self) => {
+                let Error::UniqueKeyViolation {
+                    existing_document, ..
+                } = &err.error
+                else {
+                    unreachable!()
+                };
+                let id = existing_document.id.deserialize::<C::PrimaryKey>()?;
+                C::overwrite(&id, err.contents, self).map_err(|err| err.error)
+            }
+            Err(err) => Err(err.error),
+        }
+    }
+
+    /// Renames the document with the given `name` or id in the
+    /// [`NamedCollection`] `C` to `new_name`, returning the updated document.
+    /// Returns `None` if the document was not found.
+    ///
+    /// The document and its [`NamedCollection::ByNameView`] entry are updated
+    /// in a single transaction. If another document is already named
+    /// `new_name`, [`Error::UniqueKeyViolation`] is returned.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::RenameUnsupported`]: `C` does not implement
+    ///   [`NamedCollection::rename_contents()`].
+    /// * [`Error::DocumentConflict`]: the document was updated by another
+    ///   client while being renamed.
+    fn rename<'name, C, N>(
+        &self,
+        name: N,
+        new_name: &str,
+    ) -> Result<Option<CollectionDocument<C>>, Error>
+    where
+        C: NamedCollection + SerializedCollection + 'static,
+        N: Nameable<'name, C::PrimaryKey> + Send + Sync,
+    {
+        let Some(mut doc) = C::load(name, self)? else {
+            return Ok(None);
+        };
+        C::rename_contents(&mut doc.contents, new_name)?;
+        doc.update(self)?;
+        Ok(Some(doc))
+    }
 }
 
 /// Interacts with a collection over a `Connection`.
@@ -445,6 +562,48 @@ where
         )
     }
 
+    /// Retrieves the document with the lowest id, or `None` if the collection
+    /// is empty.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::Connection;
+    /// # fn test_fn<C: Connection>(db: &C) -> Result<(), Error> {
+    /// if let Some(doc) = db.collection::<MyCollection>().first()? {
+    ///     println!("Lowest id: {}", doc.header.id);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn first(&self) -> Result<Option<OwnedDocument>, Error> {
+        Ok(self
+            .connection
+            .list::<Cl, _, Cl::PrimaryKey>(.., Sort::Ascending, Some(1))?
+            .into_iter()
+            .next())
+    }
+
+    /// Retrieves the document with the highest id, or `None` if the
+    /// collection is empty.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::Connection;
+    /// # fn test_fn<C: Connection>(db: &C) -> Result<(), Error> {
+    /// if let Some(doc) = db.collection::<MyCollection>().last()? {
+    ///     println!("Highest id: {}", doc.header.id);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn last(&self) -> Result<Option<OwnedDocument>, Error> {
+        Ok(self
+            .connection
+            .list::<Cl, _, Cl::PrimaryKey>(.., Sort::Descending, Some(1))?
+            .into_iter()
+            .next())
+    }
+
     /// Removes a `Document` from the database.
     ///
     /// ```rust
@@ -831,6 +990,31 @@ where
         self
     }
 
+    /// Sets the access policy for queries to the policy that provides
+    /// `consistency`. See [`Consistency::access_policy()`].
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use std::time::Duration;
+    /// # use bonsaidb_core::connection::{Connection, Consistency};
+    /// # fn test_fn<C: Connection>(db: C) -> Result<(), Error> {
+    /// // score is an f32 in this example
+    /// for mapping in ScoresByRank::entries(&db)
+    ///     .with_consistency(Consistency::Strong {
+    ///         timeout: Duration::from_secs(5),
+    ///     })
+    ///     .query()?
+    /// {
+    ///     println!("Rank {} has a score of {:3}", mapping.key, mapping.value);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_consistency(mut self, consistency: Consistency) -> Self {
+        self.access_policy = consistency.access_policy(self.connection.last_seen_transaction_id());
+        self
+    }
+
     /// Returns the matching mappings in ascending key order. This is the
     /// default sorting behavior.
     ///
@@ -1023,8 +1207,130 @@ where
     /// ```
     pub fn delete_docs(self) -> Result<u64, Error> {
         self.connection
-            .delete_docs::<V, Key>(self.key, self.access_policy)
+            .delete_docs::<V, Key>(self.key, self.access_policy, false)
+            .map(|deleted| deleted.count)
+    }
+
+    /// Deletes all of the associated documents that match this view query,
+    /// returning the header of each deleted document along with the id of
+    /// the transaction that deleted them.
+    ///
+    /// For queries matching a large number of documents where only the count
+    /// is needed, [`delete_docs()`](Self::delete_docs) avoids building the
+    /// list of headers.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::Connection;
+    /// # fn test_fn<C: Connection>(db: C) -> Result<(), Error> {
+    /// let deleted = ScoresByRank::entries(&db).delete_docs_returning_headers()?;
+    /// for header in deleted.headers.unwrap_or_default() {
+    ///     println!("Deleted {header} in transaction {:?}", deleted.transaction_id);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete_docs_returning_headers(self) -> Result<DeletedDocuments, Error> {
+        self.connection
+            .delete_docs::<V, Key>(self.key, self.access_policy, true)
+    }
+
+    /// Updates all of the associated documents that match this view query,
+    /// returning the header of each updated document. `update` is called once
+    /// for each document, even if the document matches the query more than
+    /// once.
+    ///
+    /// The documents are updated in a single transaction. If any document is
+    /// changed between being queried and being updated, no documents are
+    /// updated and [`Error::DocumentConflict`] is returned.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::Connection;
+    /// # fn test_fn<C: Connection>(db: C) -> Result<(), Error> {
+    /// let updated = ScoresByRank::entries(&db)
+    ///     .with_key(&42)
+    ///     .update_docs(|document| document.contents.score += 1.)?;
+    /// for header in updated {
+    ///     println!("Updated {} to revision {}", header.id, header.revision);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn update_docs<F>(
+        self,
+        update: F,
+    ) -> Result<Vec<CollectionHeader<<V::Collection as schema::Collection>::PrimaryKey>>, Error>
+    where
+        V::Collection: SerializedCollection,
+        <V::Collection as SerializedCollection>::Contents: std::fmt::Debug,
+        F: FnMut(&mut CollectionDocument<V::Collection>),
+    {
+        let connection = self.connection;
+        let documents = self.query_with_collection_docs()?.documents;
+        let transaction = bulk_update(documents.into_values(), update)?;
+        if transaction.operations.is_empty() {
+            return Ok(Vec::new());
+        }
+        updated_headers(transaction.apply(connection)?)
+    }
+}
+
+/// Returns a transaction updating each of `documents` after it has been
+/// modified by `update`.
+fn bulk_update<C, F>(
+    documents: impl IntoIterator<Item = CollectionDocument<C>>,
+    mut update: F,
+) -> Result<transaction::Transaction, Error>
+where
+    C: SerializedCollection,
+    F: FnMut(&mut CollectionDocument<C>),
+{
+    let mut transaction = transaction::Transaction::new();
+    for mut document in documents {
+        update(&mut document);
+        document.update_in_transaction(&mut transaction)?;
     }
+    Ok(transaction)
+}
+
+/// Returns the headers of the documents updated by a transaction applied by
+/// [`bulk_update()`].
+fn updated_headers<PrimaryKey>(
+    results: Vec<transaction::OperationResult>,
+) -> Result<Vec<CollectionHeader<PrimaryKey>>, Error>
+where
+    PrimaryKey: for<'k> Key<'k>,
+{
+    results
+        .into_iter()
+        .filter_map(|result| match result {
+            transaction::OperationResult::DocumentUpdated { header, .. } => {
+                Some(CollectionHeader::try_from(header))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The documents deleted by a view query. Returned from
+/// [`View::delete_docs_returning_headers()`] and
+/// [`AsyncView::delete_docs_returning_headers()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletedDocuments {
+    /// The number of documents deleted.
+    pub count: u64,
+    /// The id of the transaction that deleted the documents, which can be
+    /// used to find the deletions in
+    /// [`list_executed_transactions()`](LowLevelConnection::list_executed_transactions).
+    /// This is `None` if no documents matched the query. Remote connections
+    /// only report the transaction id when the headers are requested.
+    pub transaction_id: Option<u64>,
+    /// The headers of the deleted documents, containing the revision of each
+    /// document at the time it was deleted. This is `None` if the headers
+    /// weren't requested.
+    pub headers: Option<Vec<Header>>,
 }
 
 /// This type is the result of `reduce_grouped()`. It is a list of all matching
@@ -1069,6 +1375,17 @@ pub trait AsyncConnection: AsyncLowLevelConnection + Sized + Send + Sync {
     /// Fetches the last transaction id that has been committed, if any.
     async fn last_transaction_id(&self) -> Result<Option<u64>, Error>;
 
+    /// Returns the id of the newest transaction this connection has applied
+    /// or observed, if this connection tracks transactions.
+    /// [`Consistency::Strong`] queries wait for views to be updated through
+    /// this transaction.
+    ///
+    /// The default implementation doesn't track transactions and returns
+    /// `None`.
+    fn last_seen_transaction_id(&self) -> Option<u64> {
+        None
+    }
+
     /// Compacts the entire database to reclaim unused disk space.
     ///
     /// This process is done by writing data to a new file and swapping the file
@@ -1078,7 +1395,7 @@ pub trait AsyncConnection: AsyncLowLevelConnection + Sized + Send + Sync {
     ///
     /// ## Errors
     ///
-    /// * [`Error::Other`]: an error occurred while compacting the database.
+    /// * [`Error::Storage`]: an error occurred while compacting the database.
     async fn compact(&self) -> Result<(), crate::Error>;
 
     /// Compacts the collection to reclaim unused disk space.
@@ -1091,7 +1408,7 @@ pub trait AsyncConnection: AsyncLowLevelConnection + Sized + Send + Sync {
     /// ## Errors
     ///
     /// * [`Error::CollectionNotFound`]: database `name` does not exist.
-    /// * [`Error::Other`]: an error occurred while compacting the database.
+    /// * [`Error::Storage`]: an error occurred while compacting the database.
     async fn compact_collection<C: schema::Collection>(&self) -> Result<(), crate::Error> {
         self.compact_collection_by_name(C::collection_name()).await
     }
@@ -1105,8 +1422,116 @@ pub trait AsyncConnection: AsyncLowLevelConnection + Sized + Send + Sync {
     ///
     /// ## Errors
     ///
-    /// * [`Error::Other`]: an error occurred while compacting the database.
+    /// * [`Error::Storage`]: an error occurred while compacting the database.
     async fn compact_key_value_store(&self) -> Result<(), crate::Error>;
+
+    /// Retrieves the document with the lowest id in the collection `C`, or
+    /// `None` if the collection is empty.
+    async fn first<C: SerializedCollection>(&self) -> Result<Option<CollectionDocument<C>>, Error> {
+        self.collection::<C>()
+            .first()
+            .await?
+            .as_ref()
+            .map(CollectionDocument::try_from)
+            .transpose()
+    }
+
+    /// Retrieves the document with the highest id in the collection `C`, or
+    /// `None` if the collection is empty.
+    async fn last<C: SerializedCollection>(&self) -> Result<Option<CollectionDocument<C>>, Error> {
+        self.collection::<C>()
+            .last()
+            .await?
+            .as_ref()
+            .map(CollectionDocument::try_from)
+            .transpose()
+    }
+
+    /// Loads the document from the [`NamedCollection`] `C` with the given
+    /// `name` or id.
+    async fn load_by_name<'name, C, N>(
+        &self,
+        name: N,
+    ) -> Result<Option<CollectionDocument<C>>, Error>
+    where
+        C: NamedCollection + SerializedCollection + 'static,
+        N: Nameable<'name, C::PrimaryKey> + Send + Sync,
+    {
+        C::load_async(name, self).await
+    }
+
+    /// Stores `contents` as the document named `name` in the
+    /// [`NamedCollection`] `C`. If a document named `name` already exists, it
+    /// is overwritten. Otherwise, a new document is created. `contents` is
+    /// expected to have the name `name`.
+    ///
+    /// This function is safe to call concurrently: if another client creates
+    /// a document with the same name first, the unique
+    /// [`NamedCollection::ByNameView`] rejects the insert and the other
+    /// client's document is overwritten instead.
+    async fn upsert_by_name<C>(
+        &self,
+        name: &str,
+        contents: C::Contents,
+    ) -> Result<CollectionDocument<C>, Error>
+    where
+        C: NamedCollection + SerializedCollection + 'static,
+        C::Contents: 'async_trait,
+    {
+        if let Some(id) = NamedReference::from(name).id_async::<C, _>(self).await? {
+            return C::overwrite_async(&id, contents, self)
+                .await
+                .map_err(|err| err.error);
+        }
+
+        match C::push_async(contents, self).await {
+            Ok(doc) => Ok(doc),
+            Err(err) if err.error.is_unique_key_error::<C::ByNameView, _>(self) => {
+                let Error::UniqueKeyViolation {
+                    existing_document, ..
+                } = &err.error
+                else {
+                    unreachable!()
+                };
+                let id = existing_document.id.deserialize::<C::PrimaryKey>()?;
+                C::overwrite_async(&id, err.contents, self)
+                    .await
+                    .map_err(|err| err.error)
+            }
+            Err(err) => Err(err.error),
+        }
+    }
+
+    /// Renames the document with the given `name` or id in the
+    /// [`NamedCollection`] `C` to `new_name`, returning the updated document.
+    /// Returns `None` if the document was not found.
+    ///
+    /// The document and its [`NamedCollection::ByNameView`] entry are updated
+    /// in a single transaction. If another document is already named
+    /// `new_name`, [`Error::UniqueKeyViolation`] is returned.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::RenameUnsupported`]: `C` does not implement
+    ///   [`NamedCollection::rename_contents()`].
+    /// * [`Error::DocumentConflict`]: the document was updated by another
+    ///   client while being renamed.
+    async fn rename<'name, C, N>(
+        &self,
+        name: N,
+        new_name: &str,
+    ) -> Result<Option<CollectionDocument<C>>, Error>
+    where
+        C: NamedCollection + SerializedCollection + 'static,
+        N: Nameable<'name, C::PrimaryKey> + Send + Sync,
+    {
+        let Some(mut doc) = C::load_async(name, self).await? else {
+            return Ok(None);
+        };
+        C::rename_contents(&mut doc.contents, new_name)?;
+        doc.update_async(self).await?;
+        Ok(Some(doc))
+    }
 }
 
 /// Interacts with a collection over a `Connection`.
@@ -1474,6 +1899,54 @@ where
         AsyncList::new(MaybeOwned::Borrowed(self), RangeRef::from(..))
     }
 
+    /// Retrieves the document with the lowest id, or `None` if the collection
+    /// is empty.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::AsyncConnection;
+    /// # fn test_fn<C: AsyncConnection>(db: &C) -> Result<(), Error> {
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// if let Some(doc) = db.collection::<MyCollection>().first().await? {
+    ///     println!("Lowest id: {}", doc.header.id);
+    /// }
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
+    pub async fn first(&self) -> Result<Option<OwnedDocument>, Error> {
+        Ok(self
+            .connection
+            .list::<Cl, _, Cl::PrimaryKey>(.., Sort::Ascending, Some(1))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Retrieves the document with the highest id, or `None` if the
+    /// collection is empty.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::AsyncConnection;
+    /// # fn test_fn<C: AsyncConnection>(db: &C) -> Result<(), Error> {
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// if let Some(doc) = db.collection::<MyCollection>().last().await? {
+    ///     println!("Highest id: {}", doc.header.id);
+    /// }
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
+    pub async fn last(&self) -> Result<Option<OwnedDocument>, Error> {
+        Ok(self
+            .connection
+            .list::<Cl, _, Cl::PrimaryKey>(.., Sort::Descending, Some(1))
+            .await?
+            .into_iter()
+            .next())
+    }
+
     /// Removes a `Document` from the database.
     ///
     /// ```rust
@@ -2032,6 +2505,34 @@ where
         self
     }
 
+    /// Sets the access policy for queries to the policy that provides
+    /// `consistency`. See [`Consistency::access_policy()`].
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use std::time::Duration;
+    /// # use bonsaidb_core::connection::{AsyncConnection, Consistency};
+    /// # fn test_fn<C: AsyncConnection>(db: C) -> Result<(), Error> {
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// // score is an f32 in this example
+    /// for mapping in ScoresByRank::entries_async(&db)
+    ///     .with_consistency(Consistency::Strong {
+    ///         timeout: Duration::from_secs(5),
+    ///     })
+    ///     .query()
+    ///     .await?
+    /// {
+    ///     println!("Rank {} has a score of {:3}", mapping.key, mapping.value);
+    /// }
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
+    pub fn with_consistency(mut self, consistency: Consistency) -> Self {
+        self.access_policy = consistency.access_policy(self.connection.last_seen_transaction_id());
+        self
+    }
+
     /// Returns the matching mappings in ascending key order. This is the
     /// default sorting behavior.
     ///
@@ -2245,9 +2746,79 @@ where
     /// ```
     pub async fn delete_docs(self) -> Result<u64, Error> {
         self.connection
-            .delete_docs::<V, _>(self.key, self.access_policy)
+            .delete_docs::<V, _>(self.key, self.access_policy, false)
+            .await
+            .map(|deleted| deleted.count)
+    }
+
+    /// Deletes all of the associated documents that match this view query,
+    /// returning the header of each deleted document along with the id of
+    /// the transaction that deleted them.
+    ///
+    /// For queries matching a large number of documents where only the count
+    /// is needed, [`delete_docs()`](Self::delete_docs) avoids building the
+    /// list of headers.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::AsyncConnection;
+    /// # fn test_fn<C: AsyncConnection>(db: C) -> Result<(), Error> {
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let deleted = ScoresByRank::entries_async(&db)
+    ///     .delete_docs_returning_headers()
+    ///     .await?;
+    /// println!("Deleted {} documents", deleted.count);
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
+    pub async fn delete_docs_returning_headers(self) -> Result<DeletedDocuments, Error> {
+        self.connection
+            .delete_docs::<V, _>(self.key, self.access_policy, true)
             .await
     }
+
+    /// Updates all of the associated documents that match this view query,
+    /// returning the header of each updated document. `update` is called once
+    /// for each document, even if the document matches the query more than
+    /// once.
+    ///
+    /// The documents are updated in a single transaction. If any document is
+    /// changed between being queried and being updated, no documents are
+    /// updated and [`Error::DocumentConflict`] is returned.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::AsyncConnection;
+    /// # fn test_fn<C: AsyncConnection>(db: C) -> Result<(), Error> {
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let updated = ScoresByRank::entries_async(&db)
+    ///     .with_key(&42)
+    ///     .update_docs(|document| document.contents.score += 1.)
+    ///     .await?;
+    /// println!("Updated {} documents", updated.len());
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub async fn update_docs<F>(
+        self,
+        update: F,
+    ) -> Result<Vec<CollectionHeader<<V::Collection as schema::Collection>::PrimaryKey>>, Error>
+    where
+        V::Collection: SerializedCollection,
+        <V::Collection as SerializedCollection>::Contents: std::fmt::Debug,
+        F: FnMut(&mut CollectionDocument<V::Collection>) + Send,
+    {
+        let connection = self.connection;
+        let documents = self.query_with_collection_docs().await?.documents;
+        let transaction = bulk_update(documents.into_values(), update)?;
+        if transaction.operations.is_empty() {
+            return Ok(Vec::new());
+        }
+        updated_headers(transaction.apply_async(connection).await?)
+    }
 }
 
 /// A sort order.
@@ -2953,6 +3524,66 @@ pub enum AccessPolicy {
     /// shouldn't have much overhead, this option removes all overhead related
     /// to view updating from the query.
     NoUpdate,
+
+    /// Update the view until it has mapped every transaction through
+    /// `transaction_id` before returning a response. If `transaction_id` is
+    /// `None`, the view is updated through the last transaction committed
+    /// when the query is received.
+    ///
+    /// If the view hasn't been updated within `timeout`,
+    /// [`Error::ViewUpdateTimeout`] is returned.
+    UpdateThrough {
+        /// The id of the transaction the view must have mapped.
+        transaction_id: Option<u64>,
+        /// The maximum amount of time to wait for the view to be updated.
+        timeout: Duration,
+    },
+}
+
+/// How up-to-date the results of a view query must be. Set using
+/// [`View::with_consistency()`] or [`AsyncView::with_consistency()`].
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+pub enum Consistency {
+    /// The view is updated with every transaction committed before the query
+    /// before returning a response. This is the default behavior of view
+    /// queries, [`AccessPolicy::UpdateBefore`].
+    #[default]
+    Latest,
+
+    /// The view is updated with every transaction the connection has applied
+    /// or observed, as reported by
+    /// [`Connection::last_seen_transaction_id()`], before returning a
+    /// response. Connections that don't track transactions wait until the view
+    /// has been updated with every transaction committed before the query.
+    ///
+    /// If the view hasn't been updated within `timeout`,
+    /// [`Error::ViewUpdateTimeout`] is returned rather than waiting
+    /// indefinitely.
+    Strong {
+        /// The maximum amount of time to wait for the view to be updated.
+        timeout: Duration,
+    },
+
+    /// The view's current contents are returned without updating the view,
+    /// even if they are out-of-date. This is [`AccessPolicy::NoUpdate`].
+    Eventual,
+}
+
+impl Consistency {
+    /// Returns the [`AccessPolicy`] that provides this consistency for a
+    /// connection whose newest known transaction is
+    /// `last_seen_transaction_id`.
+    #[must_use]
+    pub const fn access_policy(self, last_seen_transaction_id: Option<u64>) -> AccessPolicy {
+        match self {
+            Self::Latest => AccessPolicy::UpdateBefore,
+            Self::Strong { timeout } => AccessPolicy::UpdateThrough {
+                transaction_id: last_seen_transaction_id,
+                timeout,
+            },
+            Self::Eventual => AccessPolicy::NoUpdate,
+        }
+    }
 }
 
 /// Functions for interacting with a multi-database BonsaiDb instance.
@@ -3008,9 +3639,40 @@ pub trait StorageConnection: HasSession + Sized + Send + Sync {
     /// ## Errors
     ///
     /// * [`Error::DatabaseNotFound`]: database `name` does not exist.
-    /// * [`Error::Other`]: an error occurred while deleting files.
+    /// * [`Error::Storage`]: an error occurred while deleting files.
     fn delete_database(&self, name: &str) -> Result<(), crate::Error>;
 
+    /// Deletes each database in `names`, returning the result of each
+    /// deletion in the same order as `names`. A failure to delete one database
+    /// doesn't prevent the others from being deleted.
+    ///
+    /// The default implementation calls
+    /// [`delete_database()`](Self::delete_database) for each name.
+    /// Implementations may delete the databases more efficiently.
+    fn delete_databases(&self, names: &[&str]) -> Vec<Result<(), crate::Error>> {
+        names
+            .iter()
+            .map(|name| self.delete_database(name))
+            .collect()
+    }
+
+    /// Sets the quota limiting the resources database `name` can use. The
+    /// quota is stored with the database, and replaces any quota previously
+    /// set.
+    ///
+    /// Writes that would cause the database to exceed a hard limit are
+    /// rejected with [`Error::QuotaExceeded`]. Reads and deletes are always
+    /// allowed.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::DatabaseNotFound`]: database `name` does not exist.
+    fn set_database_quota(
+        &self,
+        name: &str,
+        quota: crate::admin::DatabaseQuota,
+    ) -> Result<(), crate::Error>;
+
     /// Lists the databases in this storage.
     fn list_databases(&self) -> Result<Vec<Database>, crate::Error>;
 
@@ -3026,6 +3688,22 @@ pub trait StorageConnection: HasSession + Sized + Send + Sync {
         user: U,
     ) -> Result<(), crate::Error>;
 
+    /// Deletes each user in `users`, returning the result of each deletion in
+    /// the same order as `users`. A failure to delete one user doesn't prevent
+    /// the others from being deleted.
+    ///
+    /// The default implementation calls [`delete_user()`](Self::delete_user)
+    /// for each user. Implementations may delete the users more efficiently.
+    fn delete_users<'user, U: Nameable<'user, u64> + Clone + Send + Sync>(
+        &self,
+        users: &[U],
+    ) -> Vec<Result<(), crate::Error>> {
+        users
+            .iter()
+            .map(|user| self.delete_user(user.clone()))
+            .collect()
+    }
+
     /// Sets a user's password.
     #[cfg(feature = "password-hashing")]
     fn set_user_password<'user, U: Nameable<'user, u64> + Send + Sync>(
@@ -3045,11 +3723,29 @@ pub trait StorageConnection: HasSession + Sized + Send + Sync {
 
     /// Assumes the `identity`. If successful, the returned instance will have
     ///  the permissions from `identity`.
+    ///
+    /// The returned instance is granted every permission `identity` has, even
+    /// if they exceed the permissions of this connection. Only trusted callers
+    /// should be given permission to assume identities this way. To impersonate
+    /// an identity without gaining permissions, use
+    /// [`assume_identity_capped()`](Self::assume_identity_capped).
     fn assume_identity(
         &self,
         identity: IdentityReference<'_>,
     ) -> Result<Self::Authenticated, crate::Error>;
 
+    /// Assumes the `identity`, limiting the returned instance's permissions to
+    /// those allowed by both `identity` and this connection. Assuming an
+    /// identity this way never grants permissions this connection doesn't
+    /// already have.
+    ///
+    /// This requires the same permission as
+    /// [`assume_identity()`](Self::assume_identity).
+    fn assume_identity_capped(
+        &self,
+        identity: IdentityReference<'_>,
+    ) -> Result<Self::Authenticated, crate::Error>;
+
     /// Authenticates using an
     /// [`AuthenticationToken`](crate::admin::AuthenticationToken). If
     ///  successful, the returned instance will have the permissions from
@@ -3143,6 +3839,59 @@ pub trait StorageConnection: HasSession + Sized + Send + Sync {
         user: U,
         role: R,
     ) -> Result<(), crate::Error>;
+
+    /// Creates a permission group named `name` containing `statements`,
+    /// returning the new group's id.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::InvalidPermissionStatement`](crate::Error::InvalidPermissionStatement):
+    ///   a statement was rejected by
+    ///   [`PermissionGroup::validate_statements()`](crate::admin::PermissionGroup::validate_statements).
+    /// * [`Error::UniqueKeyViolation`](crate::Error::UniqueKeyViolation): a
+    ///   permission group named `name` already exists.
+    fn create_permission_group(
+        &self,
+        name: &str,
+        statements: Vec<Statement>,
+    ) -> Result<u64, crate::Error>;
+
+    /// Replaces the statements of a permission group with `statements`.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::InvalidPermissionStatement`](crate::Error::InvalidPermissionStatement):
+    ///   a statement was rejected by
+    ///   [`PermissionGroup::validate_statements()`](crate::admin::PermissionGroup::validate_statements).
+    /// * [`Error::PermissionGroupNotFound`](crate::Error::PermissionGroupNotFound):
+    ///   `permission_group` does not exist.
+    fn update_permission_group_statements<'group, G: Nameable<'group, u64> + Send + Sync>(
+        &self,
+        permission_group: G,
+        statements: Vec<Statement>,
+    ) -> Result<(), crate::Error>;
+
+    /// Creates a role named `name` that grants the permissions of the
+    /// permission groups with the ids `groups`, returning the new role's id.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::PermissionGroupNotFound`](crate::Error::PermissionGroupNotFound):
+    ///   a group in `groups` does not exist.
+    /// * [`Error::UniqueKeyViolation`](crate::Error::UniqueKeyViolation): a
+    ///   role named `name` already exists.
+    fn create_role(&self, name: &str, groups: Vec<u64>) -> Result<u64, crate::Error>;
+
+    /// Deletes a role.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::RoleNotFound`](crate::Error::RoleNotFound): `role` does not
+    ///   exist.
+    fn delete_role<'role, R: Nameable<'role, u64> + Send + Sync>(
+        &self,
+        role: R,
+    ) -> Result<(), crate::Error>;
 }
 
 /// Functions for interacting with a multi-database BonsaiDb instance.
@@ -3198,9 +3947,41 @@ pub trait AsyncStorageConnection: HasSession + Sized + Send + Sync {
     /// ## Errors
     ///
     /// * [`Error::DatabaseNotFound`]: database `name` does not exist.
-    /// * [`Error::Other`]: an error occurred while deleting files.
+    /// * [`Error::Storage`]: an error occurred while deleting files.
     async fn delete_database(&self, name: &str) -> Result<(), crate::Error>;
 
+    /// Deletes each database in `names`, returning the result of each
+    /// deletion in the same order as `names`. A failure to delete one database
+    /// doesn't prevent the others from being deleted.
+    ///
+    /// The default implementation calls
+    /// [`delete_database()`](Self::delete_database) for each name.
+    /// Implementations may delete the databases more efficiently.
+    async fn delete_databases(&self, names: &[&str]) -> Vec<Result<(), crate::Error>> {
+        let mut results = Vec::with_capacity(names.len());
+        for name in names {
+            results.push(self.delete_database(name).await);
+        }
+        results
+    }
+
+    /// Sets the quota limiting the resources database `name` can use. The
+    /// quota is stored with the database, and replaces any quota previously
+    /// set.
+    ///
+    /// Writes that would cause the database to exceed a hard limit are
+    /// rejected with [`Error::QuotaExceeded`]. Reads and deletes are always
+    /// allowed.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::DatabaseNotFound`]: database `name` does not exist.
+    async fn set_database_quota(
+        &self,
+        name: &str,
+        quota: crate::admin::DatabaseQuota,
+    ) -> Result<(), crate::Error>;
+
     /// Lists the databases in this storage.
     async fn list_databases(&self) -> Result<Vec<Database>, crate::Error>;
 
@@ -3216,6 +3997,23 @@ pub trait AsyncStorageConnection: HasSession + Sized + Send + Sync {
         user: U,
     ) -> Result<(), crate::Error>;
 
+    /// Deletes each user in `users`, returning the result of each deletion in
+    /// the same order as `users`. A failure to delete one user doesn't prevent
+    /// the others from being deleted.
+    ///
+    /// The default implementation calls [`delete_user()`](Self::delete_user)
+    /// for each user. Implementations may delete the users more efficiently.
+    async fn delete_users<'user, U: Nameable<'user, u64> + Clone + Send + Sync>(
+        &self,
+        users: &[U],
+    ) -> Vec<Result<(), crate::Error>> {
+        let mut results = Vec::with_capacity(users.len());
+        for user in users {
+            results.push(self.delete_user(user.clone()).await);
+        }
+        results
+    }
+
     /// Sets a user's password.
     #[cfg(feature = "password-hashing")]
     async fn set_user_password<'user, U: Nameable<'user, u64> + Send + Sync>(
@@ -3286,11 +4084,29 @@ pub trait AsyncStorageConnection: HasSession + Sized + Send + Sync {
     /// Assumes the `identity`. If successful, the returned instance will have
     /// the merged permissions of the current authentication session and the
     /// permissions from `identity`.
+    ///
+    /// The returned instance is granted every permission `identity` has, even
+    /// if they exceed the permissions of this connection. Only trusted callers
+    /// should be given permission to assume identities this way. To impersonate
+    /// an identity without gaining permissions, use
+    /// [`assume_identity_capped()`](Self::assume_identity_capped).
     async fn assume_identity(
         &self,
         identity: IdentityReference<'_>,
     ) -> Result<Self::Authenticated, crate::Error>;
 
+    /// Assumes the `identity`, limiting the returned instance's permissions to
+    /// those allowed by both `identity` and this connection. Assuming an
+    /// identity this way never grants permissions this connection doesn't
+    /// already have.
+    ///
+    /// This requires the same permission as
+    /// [`assume_identity()`](Self::assume_identity).
+    async fn assume_identity_capped(
+        &self,
+        identity: IdentityReference<'_>,
+    ) -> Result<Self::Authenticated, crate::Error>;
+
     /// Adds a user to a permission group.
     async fn add_permission_group_to_user<
         'user,
@@ -3338,19 +4154,146 @@ pub trait AsyncStorageConnection: HasSession + Sized + Send + Sync {
         user: U,
         role: R,
     ) -> Result<(), crate::Error>;
+
+    /// Creates a permission group named `name` containing `statements`,
+    /// returning the new group's id.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::InvalidPermissionStatement`](crate::Error::InvalidPermissionStatement):
+    ///   a statement was rejected by
+    ///   [`PermissionGroup::validate_statements()`](crate::admin::PermissionGroup::validate_statements).
+    /// * [`Error::UniqueKeyViolation`](crate::Error::UniqueKeyViolation): a
+    ///   permission group named `name` already exists.
+    async fn create_permission_group(
+        &self,
+        name: &str,
+        statements: Vec<Statement>,
+    ) -> Result<u64, crate::Error>;
+
+    /// Replaces the statements of a permission group with `statements`.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::InvalidPermissionStatement`](crate::Error::InvalidPermissionStatement):
+    ///   a statement was rejected by
+    ///   [`PermissionGroup::validate_statements()`](crate::admin::PermissionGroup::validate_statements).
+    /// * [`Error::PermissionGroupNotFound`](crate::Error::PermissionGroupNotFound):
+    ///   `permission_group` does not exist.
+    async fn update_permission_group_statements<'group, G: Nameable<'group, u64> + Send + Sync>(
+        &self,
+        permission_group: G,
+        statements: Vec<Statement>,
+    ) -> Result<(), crate::Error>;
+
+    /// Creates a role named `name` that grants the permissions of the
+    /// permission groups with the ids `groups`, returning the new role's id.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::PermissionGroupNotFound`](crate::Error::PermissionGroupNotFound):
+    ///   a group in `groups` does not exist.
+    /// * [`Error::UniqueKeyViolation`](crate::Error::UniqueKeyViolation): a
+    ///   role named `name` already exists.
+    async fn create_role(&self, name: &str, groups: Vec<u64>) -> Result<u64, crate::Error>;
+
+    /// Deletes a role.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::RoleNotFound`](crate::Error::RoleNotFound): `role` does not
+    ///   exist.
+    async fn delete_role<'role, R: Nameable<'role, u64> + Send + Sync>(
+        &self,
+        role: R,
+    ) -> Result<(), crate::Error>;
 }
 
 /// A database stored in BonsaiDb.
+///
+/// When returned from
+/// [`StorageConnection::list_databases()`], the optional fields describe the
+/// database's current state. Each field is `None` if the information is
+/// unavailable, including when connected to a server that doesn't report it.
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct Database {
     /// The name of the database.
     pub name: String,
     /// The schema defining the database.
     pub schema: SchemaName,
+    /// When the database was created. Databases created before this was
+    /// recorded report `None`.
+    #[serde(default)]
+    pub created_at: Option<Timestamp>,
+    /// When the last transaction was committed to the database.
+    #[serde(default)]
+    pub last_transaction_at: Option<Timestamp>,
+    /// The approximate number of bytes the database uses on disk, if it has
+    /// been measured recently.
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// Whether the database is currently open.
+    #[serde(default)]
+    pub open: Option<bool>,
+}
+
+impl Database {
+    /// Returns a database named `name` using `schema`, with no other
+    /// information.
+    #[must_use]
+    pub fn new(name: impl Into<String>, schema: SchemaName) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+            created_at: None,
+            last_transaction_at: None,
+            size: None,
+            open: None,
+        }
+    }
+}
+
+/// The result of verifying that a database's stored data can be read.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ScrubReport {
+    /// The number of trees that were read.
+    pub trees_checked: u64,
+    /// The number of stored values that were read successfully.
+    pub chunks_checked: u64,
+    /// The problems found while reading.
+    pub errors: Vec<ScrubError>,
+    /// Whether the scrub reached the end of the database. If false, the next
+    /// scrub resumes where this one stopped.
+    pub complete: bool,
+}
+
+/// A problem found while scrubbing a database.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ScrubError {
+    /// The name of the tree the problem was found in.
+    pub tree: String,
+    /// The key whose value could not be read, or `None` if the tree's
+    /// structure could not be read.
+    pub key: Option<Bytes>,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl std::fmt::Display for ScrubError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{} [{key:?}]: {}", self.tree, self.message),
+            None => write!(f, "{}: {}", self.tree, self.message),
+        }
+    }
 }
 
 /// A string containing sensitive (private) data. This struct automatically
 /// overwrites its contents with zeroes when dropped.
+///
+/// The contents are never included in the [`Debug`](std::fmt::Debug) or
+/// [`Display`](std::fmt::Display) output. Serializing this type includes the
+/// contents, which is required to send passwords and tokens to a server.
 #[derive(Clone, Default, Serialize, Deserialize, Zeroize, Eq, PartialEq)]
 #[zeroize(drop)]
 #[serde(transparent)]
@@ -3358,7 +4301,13 @@ pub struct SensitiveString(pub String);
 
 impl std::fmt::Debug for SensitiveString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("SensitiveString(...)")
+        f.write_str("SensitiveString(REDACTED)")
+    }
+}
+
+impl std::fmt::Display for SensitiveString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
     }
 }
 
@@ -3415,6 +4364,10 @@ impl<'a> From<&'a str> for SensitiveString {
 
 /// A buffer containing sensitive (private) data. This struct automatically
 /// overwrites its contents with zeroes when dropped.
+///
+/// The contents are never included in the [`Debug`](std::fmt::Debug) or
+/// [`Display`](std::fmt::Display) output. Serializing this type includes the
+/// contents.
 #[derive(Clone, Serialize, Deserialize, Zeroize, Eq, PartialEq)]
 #[zeroize(drop)]
 #[serde(transparent)]
@@ -3422,7 +4375,13 @@ pub struct SensitiveBytes(pub Bytes);
 
 impl std::fmt::Debug for SensitiveBytes {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("SensitiveBytes(...)")
+        f.write_str("SensitiveBytes(REDACTED)")
+    }
+}
+
+impl std::fmt::Display for SensitiveBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
     }
 }
 
@@ -3465,6 +4424,71 @@ impl KeyEncoding<Self> for SensitiveBytes {
     }
 }
 
+impl From<Vec<u8>> for SensitiveBytes {
+    fn from(sensitive: Vec<u8>) -> Self {
+        Self(Bytes::from(sensitive))
+    }
+}
+
+impl<'a> From<&'a [u8]> for SensitiveBytes {
+    fn from(sensitive: &'a [u8]) -> Self {
+        Self(Bytes::from(sensitive))
+    }
+}
+
+#[test]
+fn sensitive_values_are_redacted() {
+    use zeroize::Zeroize;
+
+    let mut password = SensitiveString::from("hunter2");
+    assert_eq!(format!("{password:?}"), "SensitiveString(REDACTED)");
+    assert_eq!(password.to_string(), "SensitiveString(REDACTED)");
+    // Serializing includes the contents, which is needed to send passwords to
+    // the server.
+    let serialized = pot::to_vec(&password).unwrap();
+    assert!(serialized.windows(7).any(|window| window == b"hunter2"));
+    assert_eq!(
+        pot::from_slice::<SensitiveString>(&serialized).unwrap(),
+        password
+    );
+
+    // Every type that serializes a sensitive value redacts it when debugged.
+    let user = crate::admin::User {
+        argon_hash: Some(password.clone()),
+        ..crate::admin::User::default()
+    };
+    assert!(!format!("{user:?}").contains("hunter2"));
+    let token = crate::admin::AuthenticationToken {
+        identity: IdentityId::User(1),
+        token: password.clone(),
+        created_at: crate::key::time::TimestampAsNanoseconds::now(),
+    };
+    assert!(!format!("{token:?}").contains("hunter2"));
+    #[cfg(feature = "password-hashing")]
+    {
+        assert!(!format!(
+            "{:?}",
+            Authentication::password("ecton", password.clone()).unwrap()
+        )
+        .contains("hunter2"));
+        let request = crate::networking::SetUserPassword {
+            user: NamedReference::from("ecton"),
+            password: password.clone(),
+        };
+        assert!(!format!("{request:?}").contains("hunter2"));
+    }
+    password.zeroize();
+    assert!(password.bytes().all(|byte| byte == 0));
+
+    let mut secret = SensitiveBytes::from(&b"hunter2"[..]);
+    assert_eq!(format!("{secret:?}"), "SensitiveBytes(REDACTED)");
+    assert_eq!(secret.to_string(), "SensitiveBytes(REDACTED)");
+    let serialized = pot::to_vec(&secret).unwrap();
+    assert!(serialized.windows(7).any(|window| window == b"hunter2"));
+    secret.zeroize();
+    assert!(secret.iter().all(|byte| *byte == 0));
+}
+
 /// Authentication methods.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[must_use]
@@ -3625,6 +4649,13 @@ pub struct Session {
     pub authentication: SessionAuthentication,
     /// The effective permissions of the session.
     pub permissions: Permissions,
+    /// Additional limits on the session's permissions. An action is only
+    /// permitted if it is allowed by `permissions` and by every entry in this
+    /// list. Sessions created by
+    /// [`StorageConnection::assume_identity_capped()`] are limited to the
+    /// permissions of the session that assumed the identity.
+    #[serde(default)]
+    pub permission_limits: Vec<Permissions>,
 }
 
 /// The authentication state of a [`Session`].
@@ -3715,7 +4746,12 @@ impl Session {
         resource_name: R,
         action: &P,
     ) -> bool {
+        let resource_name = resource_name.as_ref();
         self.permissions.allowed_to(resource_name, action)
+            && self
+                .permission_limits
+                .iter()
+                .all(|limit| limit.allowed_to(resource_name, action))
     }
 
     /// Checks if `action` is permitted against `resource_name`. If permission
@@ -3726,9 +4762,23 @@ impl Session {
         resource_name: R,
         action: &P,
     ) -> Result<(), Error> {
-        self.permissions
-            .check(resource_name, action)
-            .map_err(Error::from)
+        let resource_name = resource_name.as_ref();
+        self.permissions.check(resource_name, action)?;
+        for limit in &self.permission_limits {
+            limit.check(resource_name, action)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the limits that apply to sessions created by this session using
+    /// [`StorageConnection::assume_identity_capped()`]: all of this session's
+    /// permissions and limits.
+    #[must_use]
+    pub fn capped_permission_limits(&self) -> Vec<Permissions> {
+        let mut limits = Vec::with_capacity(self.permission_limits.len() + 1);
+        limits.push(self.permissions.clone());
+        limits.extend(self.permission_limits.iter().cloned());
+        limits
     }
 
     /// Returns the identity that this session is authenticated as, if any.
@@ -3740,6 +4790,142 @@ impl Session {
             None
         }
     }
+
+    /// Returns the [`UserScope`] of the user this session is authenticated
+    /// as. Returns `None` if the session isn't authenticated as a user.
+    #[must_use]
+    pub fn user_scope(&self) -> Option<UserScope> {
+        match self.identity()? {
+            Identity::User { id, .. } => Some(UserScope::for_user(*id)),
+            Identity::Role { .. } | Identity::External { .. } => None,
+        }
+    }
+
+    /// Returns the claims of the [`Identity::External`] this session is
+    /// authenticated as. Returns `None` if the session isn't authenticated as
+    /// an external identity.
+    #[must_use]
+    pub fn claims(&self) -> Option<&BTreeMap<String, String>> {
+        match self.identity()? {
+            Identity::External { claims, .. } => Some(claims),
+            Identity::User { .. } | Identity::Role { .. } => None,
+        }
+    }
+}
+
+/// The data reserved for a single [`User`](crate::admin::User).
+///
+/// Each user owns a database named `user.<id>`, which is returned from
+/// [`UserScope::database_name()`]. Every document and key stored in this
+/// database is tagged as belonging to the user by the database's name, and
+/// [`UserScope::owner_of_database()`] returns the scope a database belongs
+/// to. Every authenticated user is allowed to create, delete, and use their
+/// own database with the statement returned from [`UserScope::statement()`].
+/// No other database is covered by this statement, including the admin
+/// database.
+///
+/// Within databases shared by multiple users, each user's keys can be kept
+/// apart using the key-value namespace `user.<id>`, which
+/// [`UserScope::key_value()`] applies. Access to the namespace must be granted
+/// for each shared database with the statement returned from
+/// [`UserScope::key_value_statement()`].
+///
+/// Accessing another user's data, which can be done by creating their scope
+/// using [`UserScope::for_user()`], requires being granted permissions for
+/// the other user's database or namespace.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct UserScope {
+    user_id: u64,
+}
+
+impl UserScope {
+    /// Returns the scope of the user with id `user_id`.
+    #[must_use]
+    pub const fn for_user(user_id: u64) -> Self {
+        Self { user_id }
+    }
+
+    /// Returns the id of the user this scope belongs to.
+    #[must_use]
+    pub const fn user_id(&self) -> u64 {
+        self.user_id
+    }
+
+    /// Returns the name of the database owned by this scope's user.
+    #[must_use]
+    pub fn database_name(&self) -> String {
+        format!("user.{}", self.user_id)
+    }
+
+    /// Returns the scope of the user that owns the database named `name`, or
+    /// `None` if the database isn't owned by a user.
+    #[must_use]
+    pub fn owner_of_database(name: &str) -> Option<Self> {
+        let user_id = name.strip_prefix("user.")?.parse().ok()?;
+        Some(Self::for_user(user_id))
+    }
+
+    /// Returns the key-value namespace of this scope.
+    #[must_use]
+    pub fn key_namespace(&self) -> String {
+        format!("user.{}", self.user_id)
+    }
+
+    /// Returns `kv` namespaced to this scope. `kv` can be any
+    /// [`KeyValue`](crate::keyvalue::KeyValue) or
+    /// [`AsyncKeyValue`](crate::keyvalue::AsyncKeyValue) implementor, such as a
+    /// local or remote database.
+    pub fn key_value<'a, K>(&self, kv: &'a K) -> Namespaced<'a, K> {
+        Namespaced::new(self.key_namespace(), kv)
+    }
+
+    /// Returns a statement allowing the user to create, delete, and use the
+    /// database they own. The database's quota can't be changed.
+    pub fn statement(&self) -> Statement {
+        [
+            BonsaiAction::Server(ServerAction::CreateDatabase),
+            BonsaiAction::Server(ServerAction::DeleteDatabase),
+            BonsaiAction::Database(DatabaseAction::Compact),
+            BonsaiAction::Database(DatabaseAction::Scrub),
+            BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
+            BonsaiAction::Database(DatabaseAction::Document(DocumentAction::List)),
+            BonsaiAction::Database(DatabaseAction::Document(DocumentAction::ListHeaders)),
+            BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Count)),
+            BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Insert)),
+            BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Update)),
+            BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Overwrite)),
+            BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Delete)),
+            BonsaiAction::Database(DatabaseAction::View(ViewAction::Query)),
+            BonsaiAction::Database(DatabaseAction::View(ViewAction::Reduce)),
+            BonsaiAction::Database(DatabaseAction::View(ViewAction::DeleteDocs)),
+            BonsaiAction::Database(DatabaseAction::Transaction(TransactionAction::ListExecuted)),
+            BonsaiAction::Database(DatabaseAction::Transaction(TransactionAction::GetLastId)),
+            BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::CreateSuscriber)),
+            BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::Publish)),
+            BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::SubscribeTo)),
+            BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::UnsubscribeFrom)),
+            BonsaiAction::Database(DatabaseAction::KeyValue(KeyValueAction::ExecuteOperation)),
+            BonsaiAction::Database(DatabaseAction::KeyValue(KeyValueAction::Persist)),
+        ]
+        .iter()
+        .fold(
+            Statement::for_resource(database_resource_name(self.database_name())),
+            |statement, action| statement.allowing(action),
+        )
+    }
+
+    /// Returns a statement allowing all key-value operations within this
+    /// scope's namespace in the database named `database`.
+    pub fn key_value_statement(&self, database: &str) -> Statement {
+        Statement::for_resource(
+            database_resource_name(database.to_owned())
+                .and("keyvalue")
+                .and(self.key_namespace()),
+        )
+        .allowing(&BonsaiAction::Database(DatabaseAction::KeyValue(
+            KeyValueAction::ExecuteOperation,
+        )))
+    }
 }
 
 impl Eq for Session {}
@@ -3774,6 +4960,19 @@ pub enum Identity {
         /// The name of the role.
         name: String,
     },
+    /// An identity authenticated by an application-defined provider, such as
+    /// an OpenID Connect issuer. External identities aren't stored in the
+    /// admin database; the storage's external identity resolver verifies the
+    /// provider's credential and determines the identity's subject, claims,
+    /// and permissions when the identity is assumed.
+    External {
+        /// The name of the provider that authenticated this identity.
+        provider: String,
+        /// The identifier of this identity, unique within `provider`.
+        subject: String,
+        /// The claims the provider made about this identity.
+        claims: BTreeMap<String, String>,
+    },
 }
 
 impl Eq for Identity {}
@@ -3783,6 +4982,18 @@ impl PartialEq for Identity {
         match (self, other) {
             (Self::User { id: l_id, .. }, Self::User { id: r_id, .. })
             | (Self::Role { id: l_id, .. }, Self::Role { id: r_id, .. }) => l_id == r_id,
+            (
+                Self::External {
+                    provider: l_provider,
+                    subject: l_subject,
+                    ..
+                },
+                Self::External {
+                    provider: r_provider,
+                    subject: r_subject,
+                    ..
+                },
+            ) => l_provider == r_provider && l_subject == r_subject,
             _ => false,
         }
     }
@@ -3799,6 +5010,13 @@ impl std::hash::Hash for Identity {
                 1_u8.hash(state); // "Tag" for the variant
                 id.hash(state);
             }
+            Identity::External {
+                provider, subject, ..
+            } => {
+                2_u8.hash(state); // "Tag" for the variant
+                provider.hash(state);
+                subject.hash(state);
+            }
         }
     }
 }
@@ -3811,6 +5029,17 @@ pub enum IdentityReference<'name> {
     User(NamedReference<'name, u64>),
     /// A reference to a [`Role`](crate::admin::Role).
     Role(NamedReference<'name, u64>),
+    /// An [`Identity::External`], identified by a credential issued by its
+    /// provider.
+    External {
+        /// The name of the provider that authenticated the identity.
+        provider: String,
+        /// The credential issued by `provider`, such as an OpenID Connect ID
+        /// token. The credential is verified by the storage's external
+        /// identity resolver, which determines the identity's subject and
+        /// claims.
+        credential: SensitiveBytes,
+    },
 }
 
 impl<'name> IdentityReference<'name> {
@@ -3826,24 +5055,43 @@ impl<'name> IdentityReference<'name> {
         Ok(Self::Role(role.name()?))
     }
 
+    /// Returns a reference to the [`Identity::External`] that `credential`
+    /// was issued to by `provider`.
+    pub fn external(provider: impl Into<String>, credential: impl Into<SensitiveBytes>) -> Self {
+        Self::External {
+            provider: provider.into(),
+            credential: credential.into(),
+        }
+    }
+
     /// Converts this reference to an owned reference with a `'static` lifetime.
     #[must_use]
     pub fn into_owned(self) -> IdentityReference<'static> {
         match self {
             IdentityReference::User(user) => IdentityReference::User(user.into_owned()),
             IdentityReference::Role(role) => IdentityReference::Role(role.into_owned()),
+            IdentityReference::External {
+                provider,
+                credential,
+            } => IdentityReference::External {
+                provider,
+                credential,
+            },
         }
     }
 
-    /// Resolves this reference to the unique id.
+    /// Resolves this reference to the unique id. External identities aren't
+    /// stored in the admin database, and always resolve to `None`.
     pub fn resolve<C: Connection>(&self, admin: &C) -> Result<Option<IdentityId>, crate::Error> {
         match self {
             IdentityReference::User(name) => Ok(name.id::<User, _>(admin)?.map(IdentityId::User)),
             IdentityReference::Role(name) => Ok(name.id::<Role, _>(admin)?.map(IdentityId::Role)),
+            IdentityReference::External { .. } => Ok(None),
         }
     }
 
-    /// Resolves this reference to the unique id.
+    /// Resolves this reference to the unique id. External identities aren't
+    /// stored in the admin database, and always resolve to `None`.
     pub async fn resolve_async<C: AsyncConnection>(
         &self,
         admin: &C,
@@ -3855,6 +5103,7 @@ impl<'name> IdentityReference<'name> {
             IdentityReference::Role(name) => {
                 Ok(name.id_async::<Role, _>(admin).await?.map(IdentityId::Role))
             }
+            IdentityReference::External { .. } => Ok(None),
         }
     }
 }
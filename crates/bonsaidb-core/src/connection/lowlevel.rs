@@ -4,7 +4,7 @@ use std::collections::BTreeMap;
 use arc_bytes::serde::Bytes;
 use async_trait::async_trait;
 
-use super::GroupedReductions;
+use super::{DeletedDocuments, GroupedReductions};
 use crate::connection::{
     AccessPolicy, HasSession, QueryKey, Range, RangeRef, SerializedQueryKey, Sort,
 };
@@ -450,11 +450,15 @@ pub trait LowLevelConnection: HasSchema + HasSession {
     /// [`SerializedView::entries()`](schema::SerializedView::entries),
     /// [`SerializedView::entries_async()`](schema::SerializedView::entries_async),
     /// or [`Connection::view()`](super::Connection::view).
+    ///
+    /// If `include_headers` is true, the headers of the deleted documents are
+    /// returned in [`DeletedDocuments::headers`].
     fn delete_docs<V: schema::SerializedView, Key>(
         &self,
         key: Option<QueryKey<'_, V::Key, Key>>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, Error>
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, Error>
     where
         Key: KeyEncoding<V::Key> + PartialEq + ?Sized,
         V::Key: Borrow<Key> + PartialEq<Key>,
@@ -464,6 +468,7 @@ pub trait LowLevelConnection: HasSchema + HasSession {
             &view.view_name(),
             key.map(|key| key.serialized()).transpose()?,
             access_policy,
+            include_headers,
         )
     }
 
@@ -558,7 +563,7 @@ pub trait LowLevelConnection: HasSchema + HasSession {
     /// ## Errors
     ///
     /// * [`Error::CollectionNotFound`]: database `name` does not exist.
-    /// * [`Error::Other`]: an error occurred while compacting the database.
+    /// * [`Error::Storage`]: an error occurred while compacting the database.
     fn compact_collection_by_name(&self, collection: CollectionName) -> Result<(), Error>;
 
     /// Queries for view entries from the named `view`.
@@ -629,12 +634,16 @@ pub trait LowLevelConnection: HasSchema + HasSession {
     /// [`View::entries(self).delete_docs()`](super::View::delete_docs())
     /// instead. The parameters for the query can be customized on the builder
     /// returned from [`Connection::view()`](super::Connection::view).
+    ///
+    /// If `include_headers` is true, the headers of the deleted documents are
+    /// returned in [`DeletedDocuments::headers`].
     fn delete_docs_by_name(
         &self,
         view: &ViewName,
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, Error>;
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, Error>;
 }
 
 /// The low-level interface to a database's [`schema::Schema`], giving access to
@@ -1075,12 +1084,16 @@ pub trait AsyncLowLevelConnection: HasSchema + HasSession + Send + Sync {
     /// [`View::entries(self).delete_docs()`](super::AsyncView::delete_docs)
     /// instead. The parameters for the query can be customized on the builder
     /// returned from [`AsyncConnection::view()`](super::AsyncConnection::view).
+    ///
+    /// If `include_headers` is true, the headers of the deleted documents are
+    /// returned in [`DeletedDocuments::headers`].
     #[must_use]
     async fn delete_docs<V: schema::SerializedView, Key>(
         &self,
         key: Option<QueryKey<'_, V::Key, Key>>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, Error>
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, Error>
     where
         Key: KeyEncoding<V::Key> + PartialEq + ?Sized,
         V::Key: Borrow<Key> + PartialEq<Key>,
@@ -1090,6 +1103,7 @@ pub trait AsyncLowLevelConnection: HasSchema + HasSession + Send + Sync {
             &view.view_name(),
             key.map(|key| key.serialized()).transpose()?,
             access_policy,
+            include_headers,
         )
         .await
     }
@@ -1187,7 +1201,7 @@ pub trait AsyncLowLevelConnection: HasSchema + HasSession + Send + Sync {
     /// ## Errors
     ///
     /// * [`Error::CollectionNotFound`]: database `name` does not exist.
-    /// * [`Error::Other`]: an error occurred while compacting the database.
+    /// * [`Error::Storage`]: an error occurred while compacting the database.
     async fn compact_collection_by_name(&self, collection: CollectionName) -> Result<(), Error>;
 
     /// Queries for view entries from the named `view`.
@@ -1258,12 +1272,16 @@ pub trait AsyncLowLevelConnection: HasSchema + HasSession + Send + Sync {
     /// [`View::entries(self).delete_docs()`](super::AsyncView::delete_docs)
     /// instead. The parameters for the query can be customized on the builder
     /// returned from [`AsyncConnection::view()`](super::AsyncConnection::view).
+    ///
+    /// If `include_headers` is true, the headers of the deleted documents are
+    /// returned in [`DeletedDocuments::headers`].
     async fn delete_docs_by_name(
         &self,
         view: &ViewName,
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, Error>;
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, Error>;
 }
 
 /// Access to a connection's schema.
@@ -5,7 +5,7 @@ mod varint;
 mod deprecated;
 
 use std::borrow::{Borrow, Cow};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::convert::Infallible;
 use std::io::{self, ErrorKind};
 use std::num::{
@@ -205,6 +205,17 @@ pub trait Key<'k>: KeyEncoding<Self> + Clone + Send + Sync {
     fn next_value(&self) -> Result<Self, NextValueError> {
         Err(NextValueError::Unsupported)
     }
+
+    /// Returns a best-effort name of this type, which is included in
+    /// [`SchemaDescription`](crate::schema::SchemaDescription)s.
+    ///
+    /// By default, this returns [`std::any::type_name()`], whose output is not
+    /// guaranteed to be the same between compiler versions. Implementors can
+    /// override this function to report a stable name.
+    #[must_use]
+    fn type_name() -> Cow<'static, str> {
+        Cow::Borrowed(std::any::type_name::<Self>())
+    }
 }
 
 impl<'a, 'k, K, KE> KeyEncoding<K> for &'a KE
@@ -439,7 +450,7 @@ pub struct CompositeKeyDescription {
     /// The fields contained within this key.
     pub fields: Vec<KeyDescription>,
     /// The attributes of this key.
-    pub attributes: HashMap<Cow<'static, str>, KeyAttibuteValue>,
+    pub attributes: BTreeMap<Cow<'static, str>, KeyAttibuteValue>,
 }
 
 #[derive(Default)]
@@ -477,7 +488,7 @@ impl KeyVisitor for KeyDescriber {
         self.stack.push(CompositeKeyDescription {
             kind,
             fields: Vec::with_capacity(count),
-            attributes: HashMap::new(),
+            attributes: BTreeMap::new(),
         });
     }
 
@@ -1218,6 +1229,26 @@ macro_rules! count_args {
     ( $arg:tt $($remaining:tt)* ) => (1usize + count_args!($($remaining)*));
 }
 
+/// A [`Key`] whose leading fields can be used to group related keys.
+///
+/// Each prefix returned is a byte prefix of the key's encoding, and every key
+/// sharing the same leading fields begins with the same prefix. This is
+/// implemented for tuples, which are encoded using [`CompositeKeyEncoder`].
+pub trait KeyPrefixes {
+    /// Returns the encoded leading fields of this key, from the shortest
+    /// prefix to the longest. The key's complete encoding is not included.
+    ///
+    /// ```rust
+    /// # use bonsaidb_core::key::{KeyEncoding, KeyPrefixes};
+    /// let key = (2023_u16, 4_u8, 1_u8);
+    /// let encoded = key.as_ord_bytes().unwrap();
+    /// let prefixes = key.encoded_prefixes().unwrap();
+    /// assert_eq!(prefixes, vec![vec![7, 231], vec![7, 231, 4]]);
+    /// assert!(prefixes.iter().all(|prefix| encoded.starts_with(prefix)));
+    /// ```
+    fn encoded_prefixes(&self) -> Result<Vec<Vec<u8>>, CompositeKeyError>;
+}
+
 macro_rules! impl_key_for_tuple {
     ($(($index:tt, $varname:ident, $generic:ident)),+) => {
         impl<'k, $($generic),+> Key<'k> for ($($generic),+,)
@@ -1261,6 +1292,27 @@ macro_rules! impl_key_for_tuple {
                 Ok(Cow::Owned(encoder.finish()))
             }
         }
+
+        impl<$($generic),+> KeyPrefixes for ($($generic),+,)
+        where
+            $($generic: for<'k> Key<'k>),+
+        {
+            fn encoded_prefixes(&self) -> Result<Vec<Vec<u8>>, CompositeKeyError> {
+                let mut encoder = CompositeKeyEncoder::default();
+                let mut prefixes = Vec::new();
+
+                // The lengths of variable length fields are written after all
+                // of the fields, so the bytes encoded so far are a prefix of
+                // the complete key.
+                $(
+                    encoder.encode(&self.$index)?;
+                    prefixes.push(encoder.bytes.clone());
+                )+
+                prefixes.pop();
+
+                Ok(prefixes)
+            }
+        }
     };
 }
 
@@ -1817,6 +1869,38 @@ fn composite_key_tests() {
         true,
     );
 
+    // Time keys composed with other keys, such as a view keyed by tenant and
+    // timestamp.
+    let times = [
+        std::time::Duration::ZERO,
+        std::time::Duration::from_nanos(1),
+        std::time::Duration::from_secs(1),
+        std::time::Duration::from_secs(u64::from(u32::MAX) + 1),
+    ];
+    let mut cases = Vec::new();
+    for tenant in [0_u64, 1, u64::MAX] {
+        for time in times {
+            cases.push((
+                tenant,
+                crate::keyvalue::Timestamp::default() + time,
+                std::time::UNIX_EPOCH + time,
+                tenant % 2 == 0,
+            ));
+        }
+    }
+    verify_key_ordering(cases, false);
+
+    #[cfg(feature = "uuid")]
+    {
+        let mut cases = Vec::new();
+        for id in [0_u128, 1, 0xFF << 64, u128::MAX] {
+            for flag in [false, true] {
+                cases.push((uuid::Uuid::from_u128(id), flag));
+            }
+        }
+        verify_key_ordering(cases, false);
+    }
+
     let values = [0_u16, 0xFF00, 0x0FF0, 0xFF];
     macro_rules! test_enum_variations {
         ($($ident:ident),+) => {
@@ -2789,7 +2873,7 @@ fn key_descriptions() {
                 KeyDescription::Basic(KeyKind::String),
                 KeyDescription::Basic(KeyKind::Bytes),
             ],
-            attributes: HashMap::new(),
+            attributes: BTreeMap::new(),
         })
     );
 }
@@ -1,8 +1,10 @@
 use arc_bytes::serde::Bytes;
 use serde::{Deserialize, Serialize};
 
+mod filter;
 mod timestamp;
 
+pub use self::filter::{NumericComparison, ValueFilter};
 pub use self::timestamp::Timestamp;
 use crate::Error;
 
@@ -21,6 +23,8 @@ mod implementation {
     pub mod increment;
     /// Types for handling key namespaces.
     pub mod namespaced;
+    /// Types for executing scan operations.
+    pub mod scan;
     /// Types for executing set operations.
     pub mod set;
 
@@ -52,6 +56,15 @@ mod implementation {
         /// Executes a single [`KeyOperation`].
         fn execute_key_operation(&self, op: KeyOperation) -> Result<Output, Error>;
 
+        /// Persists all pending changes to the Key-Value store before
+        /// returning. Once this function returns successfully, every operation
+        /// that completed before it was called will survive a crash, regardless
+        /// of the configured persistence thresholds.
+        ///
+        /// This applies to the entire Key-Value store, not just the current
+        /// namespace.
+        fn persist(&self) -> Result<(), Error>;
+
         /// Sets `key` to `value`. This function returns a builder that is also a
         /// Future. Awaiting the builder will execute [`Command::Set`] with the options
         /// given.
@@ -152,6 +165,13 @@ mod implementation {
             get::Builder::new(self, self.key_namespace().map(Into::into), key.into())
         }
 
+        /// Scans the keys that begin with `prefix`, returning a builder that
+        /// executes [`Command::Scan`] when queried. An empty `prefix` scans
+        /// every key in the current namespace.
+        fn scan_keys<S: Into<String>>(&'_ self, prefix: S) -> scan::Builder<'_, Self> {
+            scan::Builder::new(self, self.key_namespace().map(Into::into), prefix.into())
+        }
+
         /// Deletes the value stored at `key`.
         fn delete_key<S: Into<String> + Send>(&'_ self, key: S) -> Result<KeyStatus, Error> {
             match self.execute_key_operation(KeyOperation {
@@ -160,7 +180,69 @@ mod implementation {
                 command: Command::Delete,
             })? {
                 Output::Status(status) => Ok(status),
-                Output::Value(_) => unreachable!("invalid output from delete operation"),
+                Output::Value(_) | Output::Entries(_) => {
+                    unreachable!("invalid output from delete operation")
+                }
+            }
+        }
+
+        /// Deletes the value stored at `key` only if it is equal to
+        /// `expected`. The comparison and the delete are performed atomically,
+        /// which prevents deleting a value another writer has just replaced.
+        ///
+        /// Values are compared exactly: a [`Value::Bytes`] must contain the
+        /// same bytes, and a [`Value::Numeric`] must contain the same number
+        /// stored as the same type.
+        ///
+        /// Returns [`KeyStatus::Deleted`] if the key was deleted, or
+        /// [`KeyStatus::NotChanged`] if the key wasn't present or contained a
+        /// different value.
+        fn delete_key_if_equals<S: Into<String> + Send>(
+            &'_ self,
+            key: S,
+            expected: Value,
+        ) -> Result<KeyStatus, Error> {
+            match self.execute_key_operation(KeyOperation {
+                namespace: self.key_namespace().map(ToOwned::to_owned),
+                key: key.into(),
+                command: Command::DeleteIfEquals { expected },
+            })? {
+                Output::Status(status) => Ok(status),
+                Output::Value(_) | Output::Entries(_) => {
+                    unreachable!("invalid output from delete operation")
+                }
+            }
+        }
+
+        /// Atomically exchanges the entries stored at `key_a` within
+        /// `namespace_a` and `key_b` within `namespace_b`, including their
+        /// expirations. If only one of the keys is present, its entry is moved
+        /// to the other key.
+        ///
+        /// The namespaces provided are used as-is, ignoring
+        /// [`key_namespace()`](Self::key_namespace).
+        ///
+        /// Returns [`KeyStatus::Updated`] if any entries were moved, or
+        /// [`KeyStatus::NotChanged`] if neither key was present.
+        fn swap<A: Into<String> + Send, B: Into<String> + Send>(
+            &'_ self,
+            namespace_a: Option<&str>,
+            key_a: A,
+            namespace_b: Option<&str>,
+            key_b: B,
+        ) -> Result<KeyStatus, Error> {
+            match self.execute_key_operation(KeyOperation {
+                namespace: namespace_a.map(ToOwned::to_owned),
+                key: key_a.into(),
+                command: Command::Swap {
+                    other_namespace: namespace_b.map(ToOwned::to_owned),
+                    other_key: key_b.into(),
+                },
+            })? {
+                Output::Status(status) => Ok(status),
+                Output::Value(_) | Output::Entries(_) => {
+                    unreachable!("invalid output from swap operation")
+                }
             }
         }
 
@@ -203,6 +285,15 @@ mod implementation {
         /// Executes a single [`KeyOperation`].
         async fn execute_key_operation(&self, op: KeyOperation) -> Result<Output, Error>;
 
+        /// Persists all pending changes to the Key-Value store before
+        /// returning. Once this function returns successfully, every operation
+        /// that completed before it was called will survive a crash, regardless
+        /// of the configured persistence thresholds.
+        ///
+        /// This applies to the entire Key-Value store, not just the current
+        /// namespace.
+        async fn persist(&self) -> Result<(), Error>;
+
         /// Sets `key` to `value`. This function returns a builder that is also a
         /// Future. Awaiting the builder will execute [`Command::Set`] with the options
         /// given.
@@ -303,6 +394,14 @@ mod implementation {
             get::AsyncBuilder::new(self, self.key_namespace().map(Into::into), key.into())
         }
 
+        /// Scans the keys that begin with `prefix`. This function returns a
+        /// builder that is also a Future. Awaiting the builder will execute
+        /// [`Command::Scan`] with the options given. An empty `prefix` scans
+        /// every key in the current namespace.
+        fn scan_keys<S: Into<String>>(&'_ self, prefix: S) -> scan::AsyncBuilder<'_, Self> {
+            scan::AsyncBuilder::new(self, self.key_namespace().map(Into::into), prefix.into())
+        }
+
         /// Deletes the value stored at `key`.
         async fn delete_key<S: Into<String> + Send>(&'_ self, key: S) -> Result<KeyStatus, Error> {
             match self
@@ -314,7 +413,75 @@ mod implementation {
                 .await?
             {
                 Output::Status(status) => Ok(status),
-                Output::Value(_) => unreachable!("invalid output from delete operation"),
+                Output::Value(_) | Output::Entries(_) => {
+                    unreachable!("invalid output from delete operation")
+                }
+            }
+        }
+
+        /// Deletes the value stored at `key` only if it is equal to
+        /// `expected`. The comparison and the delete are performed atomically,
+        /// which prevents deleting a value another writer has just replaced.
+        ///
+        /// Values are compared exactly: a [`Value::Bytes`] must contain the
+        /// same bytes, and a [`Value::Numeric`] must contain the same number
+        /// stored as the same type.
+        ///
+        /// Returns [`KeyStatus::Deleted`] if the key was deleted, or
+        /// [`KeyStatus::NotChanged`] if the key wasn't present or contained a
+        /// different value.
+        async fn delete_key_if_equals<S: Into<String> + Send>(
+            &'_ self,
+            key: S,
+            expected: Value,
+        ) -> Result<KeyStatus, Error> {
+            match self
+                .execute_key_operation(KeyOperation {
+                    namespace: self.key_namespace().map(ToOwned::to_owned),
+                    key: key.into(),
+                    command: Command::DeleteIfEquals { expected },
+                })
+                .await?
+            {
+                Output::Status(status) => Ok(status),
+                Output::Value(_) | Output::Entries(_) => {
+                    unreachable!("invalid output from delete operation")
+                }
+            }
+        }
+
+        /// Atomically exchanges the entries stored at `key_a` within
+        /// `namespace_a` and `key_b` within `namespace_b`, including their
+        /// expirations. If only one of the keys is present, its entry is moved
+        /// to the other key.
+        ///
+        /// The namespaces provided are used as-is, ignoring
+        /// [`key_namespace()`](Self::key_namespace).
+        ///
+        /// Returns [`KeyStatus::Updated`] if any entries were moved, or
+        /// [`KeyStatus::NotChanged`] if neither key was present.
+        async fn swap<A: Into<String> + Send, B: Into<String> + Send>(
+            &'_ self,
+            namespace_a: Option<&str>,
+            key_a: A,
+            namespace_b: Option<&str>,
+            key_b: B,
+        ) -> Result<KeyStatus, Error> {
+            match self
+                .execute_key_operation(KeyOperation {
+                    namespace: namespace_a.map(ToOwned::to_owned),
+                    key: key_a.into(),
+                    command: Command::Swap {
+                        other_namespace: namespace_b.map(ToOwned::to_owned),
+                        other_key: key_b.into(),
+                    },
+                })
+                .await?
+            {
+                Output::Status(status) => Ok(status),
+                Output::Value(_) | Output::Entries(_) => {
+                    unreachable!("invalid output from swap operation")
+                }
             }
         }
 
@@ -373,7 +540,8 @@ pub enum KeyCheck {
 pub struct KeyOperation {
     /// The namespace for the key.
     pub namespace: Option<String>,
-    /// The key to operate on.
+    /// The key to operate on. For [`Command::Scan`], this is the prefix of the
+    /// keys to scan.
     pub key: String,
     /// The command to execute.
     pub command: Command,
@@ -389,30 +557,77 @@ pub enum Command {
         /// Remove the key after retrieving the value.
         delete: bool,
     },
-    /// Increment a numeric key. Returns an error if the key cannot be
-    /// deserialized to the same numeric type as `amount`. If `saturating` is
-    /// true, overflows will be prevented and the value will remain within the
-    /// numeric bounds.
+    /// Increment a numeric key. The stored value is converted to the same
+    /// numeric type as `amount` before incrementing. `overflow` controls how
+    /// the conversion and the increment handle values outside of the bounds of
+    /// `amount`'s type.
     Increment {
         /// The amount to increment by.
         amount: Numeric,
-        /// If true, the result will be constrained to the numerical bounds of
-        /// the type of `amount`.
-        saturating: bool,
+        /// How overflows are handled.
+        overflow: OverflowPolicy,
+        /// If true, the value before the increment is returned, converted to the
+        /// same numeric type as `amount`. Otherwise, the new value is
+        /// returned.
+        return_previous: bool,
     },
-    /// Decrement a numeric key. Returns an error if the key cannot be
-    /// deserialized to the same numeric type as `amount`. If `saturating` is
-    /// true, overflows will be prevented and the value will remain within the
-    /// numeric bounds.
+    /// Decrement a numeric key. The stored value is converted to the same
+    /// numeric type as `amount` before decrementing. `overflow` controls how
+    /// the conversion and the decrement handle values outside of the bounds of
+    /// `amount`'s type.
     Decrement {
         /// The amount to increment by.
         amount: Numeric,
-        /// If true, the result will be constrained to the numerical bounds of
-        /// the type of `amount`.
-        saturating: bool,
+        /// How overflows are handled.
+        overflow: OverflowPolicy,
+        /// If true, the value before the decrement is returned, converted to the
+        /// same numeric type as `amount`. Otherwise, the new value is
+        /// returned.
+        return_previous: bool,
     },
     /// Delete a key.
     Delete,
+    /// Exchange the entries stored at this key and another key. If only one
+    /// of the keys is present, its entry is moved to the other key.
+    Swap {
+        /// The namespace of the other key.
+        other_namespace: Option<String>,
+        /// The other key to swap with.
+        other_key: String,
+    },
+    /// Return the entries whose keys begin with the operation's key, sorted
+    /// by key. Keys within nested namespaces are not included.
+    Scan(ScanCommand),
+    /// Delete a key only if its current value is equal to `expected`.
+    /// Returns [`KeyStatus::NotChanged`] if the key isn't present or contains
+    /// a different value.
+    DeleteIfEquals {
+        /// The value the key must contain to be deleted.
+        expected: Value,
+    },
+}
+
+/// Controls how [`Command::Increment`] and [`Command::Decrement`] handle
+/// values that don't fit within the numeric type of the amount.
+///
+/// This policy applies both when converting the stored value to the amount's
+/// type and when performing the operation itself. For example, incrementing a
+/// stored `u128` by an `u64` amount first converts the stored value to an
+/// `u64`.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Default)]
+pub enum OverflowPolicy {
+    /// The result is constrained to the numerical bounds of the amount's type.
+    /// Stored floats are truncated when converted to an integer type.
+    #[default]
+    Saturate,
+    /// The result wraps around the numerical bounds of the amount's type.
+    /// Stored floats are truncated when converted to an integer type.
+    Wrap,
+    /// [`Error::NumericOverflow`] is returned instead of overflowing. An error
+    /// is also returned if the stored value cannot be converted to the
+    /// amount's type without losing precision. For floats, an error is
+    /// returned if the result is not finite.
+    Error,
 }
 
 /// Set a key/value pair.
@@ -430,6 +645,39 @@ pub struct SetCommand {
     pub check: Option<KeyCheck>,
     /// If true and the key already exists, the existing key will be returned if overwritten.
     pub return_previous_value: bool,
+    /// Controls whether the operation waits for the value to be persisted
+    /// before returning.
+    #[serde(default)]
+    pub durability: DurabilityMode,
+}
+
+/// Controls when a [`Command::Set`] returns relative to when its change
+/// becomes durable.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Default)]
+pub enum DurabilityMode {
+    /// The operation returns once the change has been applied in memory. The
+    /// change is persisted the next time the Key-Value store is committed,
+    /// which is controlled by the database's configured key-value
+    /// persistence. If the process crashes before then, the change is lost.
+    #[default]
+    Deferred,
+    /// All pending changes to the Key-Value store, including this one, are
+    /// persisted before the operation returns.
+    Immediate,
+}
+
+/// Scan the keys within a namespace.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct ScanCommand {
+    /// If set, only keys that sort after this key are returned. This enables
+    /// paginating through results by passing the last key returned from the
+    /// previous scan.
+    pub start_after: Option<String>,
+    /// The maximum number of entries to return.
+    pub limit: Option<u32>,
+    /// If set, only entries matching this filter are returned. The filter is
+    /// evaluated by the database, and the limit counts only matching entries.
+    pub filter: Option<ValueFilter>,
 }
 
 /// A value stored in a key.
@@ -480,6 +728,24 @@ impl Value {
         }
     }
 
+    /// Returns this value as an `i128`, allowing for precision to be lost if the type was not an `i128` originally. If saturating is true, the conversion will not allow overflows. Returns None if the value is bytes.
+    #[must_use]
+    pub fn as_i128_lossy(&self, saturating: bool) -> Option<i128> {
+        match self {
+            Self::Bytes(_) => None,
+            Self::Numeric(value) => Some(value.as_i128_lossy(saturating)),
+        }
+    }
+
+    /// Returns this value as an `u128`, allowing for precision to be lost if the type was not an `u128` originally. If saturating is true, the conversion will not allow overflows. Returns None if the value is bytes.
+    #[must_use]
+    pub fn as_u128_lossy(&self, saturating: bool) -> Option<u128> {
+        match self {
+            Self::Bytes(_) => None,
+            Self::Numeric(value) => Some(value.as_u128_lossy(saturating)),
+        }
+    }
+
     /// Returns this value as an `f64`, allowing for precision to be lost if the type was not an `f64` originally. Returns None if the value is bytes.
     #[must_use]
     pub const fn as_f64_lossy(&self) -> Option<f64> {
@@ -507,6 +773,24 @@ impl Value {
         }
     }
 
+    /// Returns this numeric as an `i128`. If this conversion cannot be done without losing precision or overflowing, None will be returned. Returns None if the value is bytes.
+    #[must_use]
+    pub fn as_i128(&self) -> Option<i128> {
+        match self {
+            Self::Bytes(_) => None,
+            Self::Numeric(value) => value.as_i128(),
+        }
+    }
+
+    /// Returns this numeric as an `u128`. If this conversion cannot be done without losing precision or overflowing, None will be returned. Returns None if the value is bytes.
+    #[must_use]
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            Self::Bytes(_) => None,
+            Self::Numeric(value) => value.as_u128(),
+        }
+    }
+
     /// Returns this numeric as an `f64`, allowing for precision to be lost if the type was not an `f64` originally. Returns None if the value is bytes.
     #[must_use]
     pub const fn as_f64(&self) -> Option<f64> {
@@ -518,6 +802,19 @@ impl Value {
 }
 
 /// A numerical value.
+///
+/// # Interactions between floats and 128-bit integers
+///
+/// An `f64` can only represent integers exactly up to 2<sup>53</sup> in
+/// magnitude, while [`Self::Integer128`] and [`Self::UnsignedInteger128`] can
+/// hold much larger values. Converting a 128-bit integer to an `f64` using
+/// [`Self::as_f64()`] returns `None` if the value is outside of that range,
+/// while [`Self::as_f64_lossy()`] rounds it to the nearest representable
+/// value. Converting an `f64` to a 128-bit integer requires the float to have
+/// no fractional component and to be within the integer type's bounds.
+///
+/// The 128-bit variants were added after the original variants. Values stored
+/// using the 64-bit variants continue to deserialize unchanged.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum Numeric {
     /// A 64-bit signed integer.
@@ -526,6 +823,10 @@ pub enum Numeric {
     UnsignedInteger(u64),
     /// A 64-bit floating point number.
     Float(f64),
+    /// A 128-bit signed integer.
+    Integer128(i128),
+    /// A 128-bit unsigned integer.
+    UnsignedInteger128(u128),
 }
 
 impl Numeric {
@@ -553,6 +854,8 @@ impl Numeric {
         match self {
             Self::Integer(value) => Some(*value),
             Self::UnsignedInteger(value) => (*value).try_into().ok(),
+            Self::Integer128(value) => (*value).try_into().ok(),
+            Self::UnsignedInteger128(value) => (*value).try_into().ok(),
             Self::Float(value) => {
                 if value.fract().abs() > 0. {
                     None
@@ -578,6 +881,20 @@ impl Numeric {
                     *value as i64
                 }
             }
+            Self::Integer128(value) => {
+                if saturating {
+                    (*value).clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64
+                } else {
+                    *value as i64
+                }
+            }
+            Self::UnsignedInteger128(value) => {
+                if saturating {
+                    (*value).try_into().unwrap_or(i64::MAX)
+                } else {
+                    *value as i64
+                }
+            }
             Self::Float(value) => *value as i64,
         }
     }
@@ -590,6 +907,8 @@ impl Numeric {
         match self {
             Self::UnsignedInteger(value) => Some(*value),
             Self::Integer(value) => (*value).try_into().ok(),
+            Self::Integer128(value) => (*value).try_into().ok(),
+            Self::UnsignedInteger128(value) => (*value).try_into().ok(),
             Self::Float(value) => {
                 if value.fract() < f64::EPSILON && value.is_sign_positive() {
                     Some(*value as u64)
@@ -615,10 +934,119 @@ impl Numeric {
                     *value as u64
                 }
             }
+            Self::Integer128(value) => {
+                if saturating {
+                    (*value).clamp(0, i128::from(u64::MAX)) as u64
+                } else {
+                    *value as u64
+                }
+            }
+            Self::UnsignedInteger128(value) => {
+                if saturating {
+                    (*value).try_into().unwrap_or(u64::MAX)
+                } else {
+                    *value as u64
+                }
+            }
             Self::Float(value) => *value as u64,
         }
     }
 
+    /// Returns this numeric as an `i128`. If this conversion cannot be done
+    /// without losing precision or overflowing, None will be returned.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn as_i128(&self) -> Option<i128> {
+        match self {
+            Self::Integer(value) => Some(i128::from(*value)),
+            Self::UnsignedInteger(value) => Some(i128::from(*value)),
+            Self::Integer128(value) => Some(*value),
+            Self::UnsignedInteger128(value) => (*value).try_into().ok(),
+            Self::Float(value) => {
+                // i128::MAX as f64 rounds up to 2^127, which is out of range.
+                if value.fract().abs() > 0. || !(i128::MIN as f64..i128::MAX as f64).contains(value)
+                {
+                    None
+                } else {
+                    Some(*value as i128)
+                }
+            }
+        }
+    }
+
+    /// Returns this numeric as an `i128`, allowing for precision to be lost if
+    /// the type was not an `i128` originally. If saturating is true, the
+    /// conversion will not allow overflows.
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+    pub fn as_i128_lossy(&self, saturating: bool) -> i128 {
+        match self {
+            Self::Integer(value) => i128::from(*value),
+            Self::UnsignedInteger(value) => i128::from(*value),
+            Self::Integer128(value) => *value,
+            Self::UnsignedInteger128(value) => {
+                if saturating {
+                    (*value).try_into().unwrap_or(i128::MAX)
+                } else {
+                    *value as i128
+                }
+            }
+            Self::Float(value) => *value as i128,
+        }
+    }
+
+    /// Returns this numeric as an `u128`. If this conversion cannot be done
+    /// without losing precision or overflowing, None will be returned.
+    #[must_use]
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss
+    )]
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            Self::UnsignedInteger(value) => Some(u128::from(*value)),
+            Self::UnsignedInteger128(value) => Some(*value),
+            Self::Integer(value) => (*value).try_into().ok(),
+            Self::Integer128(value) => (*value).try_into().ok(),
+            Self::Float(value) => {
+                // u128::MAX as f64 rounds up to 2^128, which is out of range.
+                if value.fract().abs() > 0. || !(0. ..u128::MAX as f64).contains(value) {
+                    None
+                } else {
+                    Some(*value as u128)
+                }
+            }
+        }
+    }
+
+    /// Returns this numeric as an `u128`, allowing for precision to be lost if
+    /// the type was not an `u128` originally. If saturating is true, the
+    /// conversion will not allow overflows.
+    #[must_use]
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    pub fn as_u128_lossy(&self, saturating: bool) -> u128 {
+        match self {
+            Self::UnsignedInteger(value) => u128::from(*value),
+            Self::UnsignedInteger128(value) => *value,
+            Self::Integer(value) => {
+                if saturating {
+                    (*value).try_into().unwrap_or(0)
+                } else {
+                    *value as u128
+                }
+            }
+            Self::Integer128(value) => {
+                if saturating {
+                    (*value).try_into().unwrap_or(0)
+                } else {
+                    *value as u128
+                }
+            }
+            Self::Float(value) => *value as u128,
+        }
+    }
+
     /// Returns this numeric as an `f64`. If this conversion cannot be done
     /// without losing precision, None will be returned.
     #[must_use]
@@ -641,6 +1069,22 @@ impl Numeric {
                     Some(*value as f64)
                 }
             }
+            Self::UnsignedInteger128(value) => {
+                if *value > 2_u128.pow(f64::MANTISSA_DIGITS) {
+                    None
+                } else {
+                    Some(*value as f64)
+                }
+            }
+            Self::Integer128(value) => {
+                if *value > 2_i128.pow(f64::MANTISSA_DIGITS)
+                    || *value < -(2_i128.pow(f64::MANTISSA_DIGITS))
+                {
+                    None
+                } else {
+                    Some(*value as f64)
+                }
+            }
             Self::Float(value) => Some(*value),
         }
     }
@@ -653,6 +1097,8 @@ impl Numeric {
         match self {
             Self::UnsignedInteger(value) => *value as f64,
             Self::Integer(value) => *value as f64,
+            Self::UnsignedInteger128(value) => *value as f64,
+            Self::Integer128(value) => *value as f64,
             Self::Float(value) => *value,
         }
     }
@@ -681,6 +1127,18 @@ impl From<u64> for Numeric {
     }
 }
 
+impl From<i128> for Numeric {
+    fn from(value: i128) -> Self {
+        Self::Integer128(value)
+    }
+}
+
+impl From<u128> for Numeric {
+    fn from(value: u128) -> Self {
+        Self::UnsignedInteger128(value)
+    }
+}
+
 #[allow(clippy::fallible_impl_from)]
 impl TryFrom<Numeric> for f64 {
     type Error = IncompatibleTypeError;
@@ -720,6 +1178,32 @@ impl TryFrom<Numeric> for i64 {
     }
 }
 
+#[allow(clippy::fallible_impl_from)]
+impl TryFrom<Numeric> for u128 {
+    type Error = IncompatibleTypeError;
+
+    fn try_from(value: Numeric) -> Result<Self, IncompatibleTypeError> {
+        if let Numeric::UnsignedInteger128(value) = value {
+            Ok(value)
+        } else {
+            Err(IncompatibleTypeError)
+        }
+    }
+}
+
+#[allow(clippy::fallible_impl_from)]
+impl TryFrom<Numeric> for i128 {
+    type Error = IncompatibleTypeError;
+
+    fn try_from(value: Numeric) -> Result<Self, IncompatibleTypeError> {
+        if let Numeric::Integer128(value) = value {
+            Ok(value)
+        } else {
+            Err(IncompatibleTypeError)
+        }
+    }
+}
+
 /// The result of a [`KeyOperation`].
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum Output {
@@ -727,6 +1211,19 @@ pub enum Output {
     Status(KeyStatus),
     /// A value was returned.
     Value(Option<Value>),
+    /// The entries matched by a [`Command::Scan`].
+    Entries(Vec<KeyEntry>),
+}
+
+/// An entry returned from [`Command::Scan`].
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct KeyEntry {
+    /// The key of the entry, without its namespace.
+    pub key: String,
+    /// The stored value.
+    pub value: Value,
+    /// The expiration of the entry, if set.
+    pub expiration: Option<Timestamp>,
 }
 /// The status of an operation on a Key.
 #[derive(Copy, Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
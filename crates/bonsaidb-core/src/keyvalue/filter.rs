@@ -0,0 +1,147 @@
+use std::cmp::Ordering;
+
+use arc_bytes::serde::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::keyvalue::{Numeric, Timestamp, Value};
+
+/// A filter applied to each entry visited by [`Command::Scan`](super::Command::Scan).
+///
+/// Filters are evaluated by the database while scanning, which allows
+/// returning only the entries of interest without transferring every value in
+/// a namespace.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum ValueFilter {
+    /// Matches [`Value::Numeric`] values.
+    IsNumeric,
+    /// Matches [`Value::Bytes`] values.
+    IsBytes,
+    /// Matches [`Value::Numeric`] values that compare to `value` using
+    /// `comparison`. Numbers of different types are compared by value, and
+    /// [`Value::Bytes`] values never match.
+    Numeric {
+        /// The comparison to perform. The stored value is the left-hand side.
+        comparison: NumericComparison,
+        /// The value to compare against.
+        value: Numeric,
+    },
+    /// Matches [`Value::Bytes`] values that begin with the contained bytes.
+    BytesStartWith(Bytes),
+    /// Matches entries that have an expiration if true, or entries that do not
+    /// have an expiration if false.
+    HasExpiration(bool),
+    /// Matches if every contained filter matches. An empty list matches every
+    /// entry.
+    All(Vec<ValueFilter>),
+    /// Matches if any contained filter matches. An empty list matches no
+    /// entries.
+    Any(Vec<ValueFilter>),
+    /// Matches if the contained filter does not match.
+    Not(Box<ValueFilter>),
+}
+
+impl ValueFilter {
+    /// Returns a filter matching numeric values greater than `value`.
+    #[must_use]
+    pub fn greater_than(value: impl Into<Numeric>) -> Self {
+        Self::Numeric {
+            comparison: NumericComparison::GreaterThan,
+            value: value.into(),
+        }
+    }
+
+    /// Returns a filter matching numeric values less than `value`.
+    #[must_use]
+    pub fn less_than(value: impl Into<Numeric>) -> Self {
+        Self::Numeric {
+            comparison: NumericComparison::LessThan,
+            value: value.into(),
+        }
+    }
+
+    /// Returns a filter matching bytes values that begin with `prefix`.
+    #[must_use]
+    pub fn bytes_start_with(prefix: impl Into<Bytes>) -> Self {
+        Self::BytesStartWith(prefix.into())
+    }
+
+    /// Returns true if an entry containing `value` that expires at
+    /// `expiration` matches this filter.
+    #[must_use]
+    pub fn matches(&self, value: &Value, expiration: Option<Timestamp>) -> bool {
+        match self {
+            Self::IsNumeric => matches!(value, Value::Numeric(_)),
+            Self::IsBytes => matches!(value, Value::Bytes(_)),
+            Self::Numeric {
+                comparison,
+                value: compare_to,
+            } => match value {
+                Value::Numeric(stored) => compare_numeric(stored, compare_to)
+                    .map_or(false, |ordering| comparison.is_satisfied_by(ordering)),
+                Value::Bytes(_) => false,
+            },
+            Self::BytesStartWith(prefix) => match value {
+                Value::Bytes(bytes) => bytes.starts_with(prefix),
+                Value::Numeric(_) => false,
+            },
+            Self::HasExpiration(expected) => expiration.is_some() == *expected,
+            Self::All(filters) => filters
+                .iter()
+                .all(|filter| filter.matches(value, expiration)),
+            Self::Any(filters) => filters
+                .iter()
+                .any(|filter| filter.matches(value, expiration)),
+            Self::Not(filter) => !filter.matches(value, expiration),
+        }
+    }
+}
+
+/// A comparison performed by [`ValueFilter::Numeric`].
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum NumericComparison {
+    /// The stored value is less than the filter's value.
+    LessThan,
+    /// The stored value is less than or equal to the filter's value.
+    LessThanOrEqual,
+    /// The stored value is equal to the filter's value.
+    Equal,
+    /// The stored value is not equal to the filter's value.
+    NotEqual,
+    /// The stored value is greater than or equal to the filter's value.
+    GreaterThanOrEqual,
+    /// The stored value is greater than the filter's value.
+    GreaterThan,
+}
+
+impl NumericComparison {
+    const fn is_satisfied_by(self, ordering: Ordering) -> bool {
+        match self {
+            Self::LessThan => matches!(ordering, Ordering::Less),
+            Self::LessThanOrEqual => !matches!(ordering, Ordering::Greater),
+            Self::Equal => matches!(ordering, Ordering::Equal),
+            Self::NotEqual => !matches!(ordering, Ordering::Equal),
+            Self::GreaterThanOrEqual => !matches!(ordering, Ordering::Less),
+            Self::GreaterThan => matches!(ordering, Ordering::Greater),
+        }
+    }
+}
+
+/// Compares two numbers by value regardless of their types. If either number
+/// is a float, both are compared as floats.
+fn compare_numeric(a: &Numeric, b: &Numeric) -> Option<Ordering> {
+    if matches!(a, Numeric::Float(_)) || matches!(b, Numeric::Float(_)) {
+        return a.as_f64_lossy().partial_cmp(&b.as_f64_lossy());
+    }
+
+    if let (Some(a), Some(b)) = (a.as_i128(), b.as_i128()) {
+        return Some(a.cmp(&b));
+    }
+
+    // At least one value is an u128 that is larger than i128::MAX.
+    match (a.as_u128(), b.as_u128()) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        (Some(_), None) => Some(Ordering::Greater),
+        (None, Some(_)) => Some(Ordering::Less),
+        (None, None) => None,
+    }
+}
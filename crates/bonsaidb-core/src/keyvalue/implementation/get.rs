@@ -76,6 +76,42 @@ where
         }
     }
 
+    /// Converts the [`Value`] to an `u128` before returning. If the value is not
+    /// a [`Numeric`](crate::keyvalue::Numeric), an error will be returned. If the conversion to `u128`
+    /// cannot be done without losing data, an error will be returned.
+    pub fn into_u128(self) -> Result<Option<u128>, Error> {
+        match self.query()? {
+            Some(value) => value.as_u128().map_or_else(
+                || {
+                    Err(Error::other(
+                        "key-value",
+                        "value not an u128 or would lose precision when converted to an u128",
+                    ))
+                },
+                |value| Ok(Some(value)),
+            ),
+            None => Ok(None),
+        }
+    }
+
+    /// Converts the [`Value`] to an `i128` before returning. If the value is not
+    /// a [`Numeric`](crate::keyvalue::Numeric), an error will be returned. If the conversion to `i128`
+    /// cannot be done without losing data, an error will be returned.
+    pub fn into_i128(self) -> Result<Option<i128>, Error> {
+        match self.query()? {
+            Some(value) => value.as_i128().map_or_else(
+                || {
+                    Err(Error::other(
+                        "key-value",
+                        "value not an i128 or would lose precision when converted to an i128",
+                    ))
+                },
+                |value| Ok(Some(value)),
+            ),
+            None => Ok(None),
+        }
+    }
+
     /// Converts the [`Value`] to an `f64` before returning. If the value is not
     /// a [`Numeric`](crate::keyvalue::Numeric), an error will be returned. If the conversion to `f64`
     /// cannot be done without losing data, an error will be returned.
@@ -244,6 +280,42 @@ where
         }
     }
 
+    /// Converts the [`Value`] to an `u128` before returning. If the value is not
+    /// a [`Numeric`](crate::keyvalue::Numeric), an error will be returned. If the conversion to `u128`
+    /// cannot be done without losing data, an error will be returned.
+    pub async fn into_u128(self) -> Result<Option<u128>, Error> {
+        match self.await? {
+            Some(value) => value.as_u128().map_or_else(
+                || {
+                    Err(Error::other(
+                        "key-value",
+                        "value not an u128 or would lose precision when converted to an u128",
+                    ))
+                },
+                |value| Ok(Some(value)),
+            ),
+            None => Ok(None),
+        }
+    }
+
+    /// Converts the [`Value`] to an `i128` before returning. If the value is not
+    /// a [`Numeric`](crate::keyvalue::Numeric), an error will be returned. If the conversion to `i128`
+    /// cannot be done without losing data, an error will be returned.
+    pub async fn into_i128(self) -> Result<Option<i128>, Error> {
+        match self.await? {
+            Some(value) => value.as_i128().map_or_else(
+                || {
+                    Err(Error::other(
+                        "key-value",
+                        "value not an i128 or would lose precision when converted to an i128",
+                    ))
+                },
+                |value| Ok(Some(value)),
+            ),
+            None => Ok(None),
+        }
+    }
+
     /// Converts the [`Value`] to an `f64` before returning. If the value is not
     /// a [`Numeric`](crate::keyvalue::Numeric), an error will be returned. If the conversion to `f64`
     /// cannot be done without losing data, an error will be returned.
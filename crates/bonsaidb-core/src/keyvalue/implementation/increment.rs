@@ -3,7 +3,7 @@ use std::marker::PhantomData;
 use futures::{Future, FutureExt};
 
 use super::{BuilderState, Command, KeyOperation, KeyValue, Output};
-use crate::keyvalue::{AsyncKeyValue, IncompatibleTypeError, Numeric, Value};
+use crate::keyvalue::{AsyncKeyValue, IncompatibleTypeError, Numeric, OverflowPolicy, Value};
 use crate::Error;
 
 /// Executes a [`Command::Increment`] or [`Command::Decrement`] key-value operation.
@@ -14,7 +14,8 @@ pub struct Builder<'a, KeyValue, V> {
     key: String,
     increment: bool,
     amount: Numeric,
-    saturating: bool,
+    overflow: OverflowPolicy,
+    return_previous: bool,
     _value: PhantomData<V>,
 }
 
@@ -36,14 +37,38 @@ where
             namespace,
             increment,
             amount,
-            saturating: true,
+            overflow: OverflowPolicy::Saturate,
+            return_previous: false,
             _value: PhantomData,
         }
     }
 
-    /// Allows overflowing the value.
+    /// Allows overflowing the value. This is equivalent to
+    /// `overflow_policy(OverflowPolicy::Wrap)`.
     pub const fn allow_overflow(mut self) -> Self {
-        self.saturating = false;
+        self.overflow = OverflowPolicy::Wrap;
+        self
+    }
+
+    /// Returns an error instead of overflowing the value. This is equivalent
+    /// to `overflow_policy(OverflowPolicy::Error)`.
+    pub const fn error_on_overflow(mut self) -> Self {
+        self.overflow = OverflowPolicy::Error;
+        self
+    }
+
+    /// Sets how overflows are handled. By default,
+    /// [`OverflowPolicy::Saturate`] is used.
+    pub const fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow = policy;
+        self
+    }
+
+    /// Returns the value before the operation instead of the new value. The
+    /// previous value is converted to `V` using the configured overflow
+    /// policy. If the key was not present, 0 is returned.
+    pub const fn returning_previous(mut self) -> Self {
+        self.return_previous = true;
         self
     }
 
@@ -56,16 +81,25 @@ where
             key,
             increment,
             amount,
-            saturating,
+            overflow,
+            return_previous,
             ..
         } = self;
         let result = kv.execute_key_operation(KeyOperation {
             namespace,
             key,
             command: if increment {
-                Command::Increment { amount, saturating }
+                Command::Increment {
+                    amount,
+                    overflow,
+                    return_previous,
+                }
             } else {
-                Command::Decrement { amount, saturating }
+                Command::Decrement {
+                    amount,
+                    overflow,
+                    return_previous,
+                }
             },
         })?;
         if let Output::Value(Some(Value::Numeric(value))) = result {
@@ -88,7 +122,8 @@ struct Options<'a, KeyValue> {
     key: String,
     increment: bool,
     amount: Numeric,
-    saturating: bool,
+    overflow: OverflowPolicy,
+    return_previous: bool,
 }
 
 impl<'a, K, V> AsyncBuilder<'a, K, V>
@@ -109,7 +144,8 @@ where
                 namespace,
                 increment,
                 amount,
-                saturating: true,
+                overflow: OverflowPolicy::Saturate,
+                return_previous: false,
             })),
         }
     }
@@ -122,9 +158,32 @@ where
         }
     }
 
-    /// Allows overflowing the value.
+    /// Allows overflowing the value. This is equivalent to
+    /// `overflow_policy(OverflowPolicy::Wrap)`.
     pub fn allow_overflow(mut self) -> Self {
-        self.options().saturating = false;
+        self.options().overflow = OverflowPolicy::Wrap;
+        self
+    }
+
+    /// Returns an error instead of overflowing the value. This is equivalent
+    /// to `overflow_policy(OverflowPolicy::Error)`.
+    pub fn error_on_overflow(mut self) -> Self {
+        self.options().overflow = OverflowPolicy::Error;
+        self
+    }
+
+    /// Sets how overflows are handled. By default,
+    /// [`OverflowPolicy::Saturate`] is used.
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.options().overflow = policy;
+        self
+    }
+
+    /// Returns the value before the operation instead of the new value. The
+    /// previous value is converted to `V` using the configured overflow
+    /// policy. If the key was not present, 0 is returned.
+    pub fn returning_previous(mut self) -> Self {
+        self.options().return_previous = true;
         self
     }
 }
@@ -149,7 +208,8 @@ where
                     key,
                     increment,
                     amount,
-                    saturating,
+                    overflow,
+                    return_previous,
                 } = builder.take().expect("expected builder to have options");
                 let future = async move {
                     let result = kv
@@ -157,9 +217,17 @@ where
                             namespace,
                             key,
                             command: if increment {
-                                Command::Increment { amount, saturating }
+                                Command::Increment {
+                                    amount,
+                                    overflow,
+                                    return_previous,
+                                }
                             } else {
-                                Command::Decrement { amount, saturating }
+                                Command::Decrement {
+                                    amount,
+                                    overflow,
+                                    return_previous,
+                                }
                             },
                         })
                         .await?;
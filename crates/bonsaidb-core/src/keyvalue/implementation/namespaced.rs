@@ -26,6 +26,10 @@ where
         self.kv.execute_key_operation(op)
     }
 
+    fn persist(&self) -> Result<(), Error> {
+        self.kv.persist()
+    }
+
     fn key_namespace(&self) -> Option<&'_ str> {
         Some(&self.namespace)
     }
@@ -50,6 +54,10 @@ where
         self.kv.execute_key_operation(op).await
     }
 
+    async fn persist(&self) -> Result<(), Error> {
+        self.kv.persist().await
+    }
+
     fn key_namespace(&self) -> Option<&'_ str> {
         Some(&self.namespace)
     }
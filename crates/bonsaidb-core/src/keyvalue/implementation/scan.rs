@@ -0,0 +1,174 @@
+use futures::{Future, FutureExt};
+
+use super::{BuilderState, Command, KeyOperation, KeyValue, Output};
+use crate::keyvalue::{AsyncKeyValue, KeyEntry, ScanCommand, ValueFilter};
+use crate::Error;
+
+/// Builder for a [`Command::Scan`] key-value operation.
+#[must_use = "the key-value operation is not performed until query() is called"]
+pub struct Builder<'a, KeyValue> {
+    kv: &'a KeyValue,
+    namespace: Option<String>,
+    prefix: String,
+    command: ScanCommand,
+}
+
+impl<'a, K> Builder<'a, K>
+where
+    K: KeyValue,
+{
+    pub(crate) const fn new(kv: &'a K, namespace: Option<String>, prefix: String) -> Self {
+        Self {
+            kv,
+            namespace,
+            prefix,
+            command: ScanCommand {
+                start_after: None,
+                limit: None,
+                filter: None,
+            },
+        }
+    }
+
+    /// Only return keys that sort after `key`. Passing the last key of a
+    /// previous scan returns the next page of results.
+    pub fn starting_after(mut self, key: impl Into<String>) -> Self {
+        self.command.start_after = Some(key.into());
+        self
+    }
+
+    /// Return at most `limit` entries.
+    pub const fn limit(mut self, limit: u32) -> Self {
+        self.command.limit = Some(limit);
+        self
+    }
+
+    /// Only return entries that match `filter`.
+    pub fn filter(mut self, filter: ValueFilter) -> Self {
+        self.command.filter = Some(filter);
+        self
+    }
+
+    /// Executes the scan, returning the matching entries sorted by key.
+    pub fn query(self) -> Result<Vec<KeyEntry>, Error> {
+        let Self {
+            kv,
+            namespace,
+            prefix,
+            command,
+        } = self;
+        let result = kv.execute_key_operation(KeyOperation {
+            namespace,
+            key: prefix,
+            command: Command::Scan(command),
+        })?;
+        if let Output::Entries(entries) = result {
+            Ok(entries)
+        } else {
+            unreachable!("Unexpected result from scan")
+        }
+    }
+}
+
+/// Builder for a [`Command::Scan`] key-value operation. Queries upon being
+/// awaited.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct AsyncBuilder<'a, KeyValue> {
+    state: BuilderState<'a, Options<'a, KeyValue>, Result<Vec<KeyEntry>, Error>>,
+}
+
+struct Options<'a, KeyValue> {
+    kv: &'a KeyValue,
+    namespace: Option<String>,
+    prefix: String,
+    command: ScanCommand,
+}
+
+impl<'a, K> AsyncBuilder<'a, K>
+where
+    K: AsyncKeyValue,
+{
+    pub(crate) const fn new(kv: &'a K, namespace: Option<String>, prefix: String) -> Self {
+        Self {
+            state: BuilderState::Pending(Some(Options {
+                kv,
+                namespace,
+                prefix,
+                command: ScanCommand {
+                    start_after: None,
+                    limit: None,
+                    filter: None,
+                },
+            })),
+        }
+    }
+
+    fn options(&mut self) -> &mut Options<'a, K> {
+        if let BuilderState::Pending(Some(options)) = &mut self.state {
+            options
+        } else {
+            unreachable!("Attempted to use after retrieving the result")
+        }
+    }
+
+    /// Only return keys that sort after `key`. Passing the last key of a
+    /// previous scan returns the next page of results.
+    pub fn starting_after(mut self, key: impl Into<String>) -> Self {
+        self.options().command.start_after = Some(key.into());
+        self
+    }
+
+    /// Return at most `limit` entries.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.options().command.limit = Some(limit);
+        self
+    }
+
+    /// Only return entries that match `filter`.
+    pub fn filter(mut self, filter: ValueFilter) -> Self {
+        self.options().command.filter = Some(filter);
+        self
+    }
+}
+
+impl<'a, K> Future for AsyncBuilder<'a, K>
+where
+    K: AsyncKeyValue,
+{
+    type Output = Result<Vec<KeyEntry>, Error>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        match &mut self.state {
+            BuilderState::Executing(future) => future.as_mut().poll(cx),
+            BuilderState::Pending(builder) => {
+                let Options {
+                    kv,
+                    namespace,
+                    prefix,
+                    command,
+                } = builder.take().expect("expected builder to have options");
+                let future = async move {
+                    let result = kv
+                        .execute_key_operation(KeyOperation {
+                            namespace,
+                            key: prefix,
+                            command: Command::Scan(command),
+                        })
+                        .await?;
+                    if let Output::Entries(entries) = result {
+                        Ok(entries)
+                    } else {
+                        unreachable!("Unexpected result from scan")
+                    }
+                }
+                .boxed();
+
+                self.state = BuilderState::Executing(future);
+                self.poll(cx)
+            }
+        }
+    }
+}
@@ -8,7 +8,7 @@ use super::{
     BuilderState, Command, KeyCheck, KeyOperation, KeyStatus, KeyValue, Output, PendingValue,
     Timestamp,
 };
-use crate::keyvalue::{AsyncKeyValue, SetCommand, Value};
+use crate::keyvalue::{AsyncKeyValue, DurabilityMode, SetCommand, Value};
 use crate::Error;
 
 /// Builder for a [`Command::Set`] key-value operation.
@@ -21,6 +21,7 @@ pub struct Builder<'a, KeyValue, V> {
     expiration: Option<Timestamp>,
     keep_existing_expiration: bool,
     check: Option<KeyCheck>,
+    durability: DurabilityMode,
 }
 
 impl<'a, K, V> Builder<'a, K, V>
@@ -42,6 +43,7 @@ where
             expiration: None,
             keep_existing_expiration: false,
             check: None,
+            durability: DurabilityMode::Deferred,
         }
     }
 
@@ -77,6 +79,13 @@ where
         self
     }
 
+    /// Controls whether this operation waits for the change to be persisted
+    /// before returning. The default is [`DurabilityMode::Deferred`].
+    pub const fn durability(mut self, durability: DurabilityMode) -> Self {
+        self.durability = durability;
+        self
+    }
+
     /// Executes the Set operation, requesting the previous value be returned.
     /// If no change is made, None will be returned.
     #[allow(clippy::missing_panics_doc)]
@@ -89,6 +98,7 @@ where
             expiration,
             keep_existing_expiration,
             check,
+            durability,
         } = self;
 
         let result = kv.execute_key_operation(KeyOperation {
@@ -100,12 +110,13 @@ where
                 keep_existing_expiration,
                 check,
                 return_previous_value: true,
+                durability,
             }),
         })?;
         match result {
             Output::Value(value) => Ok(value),
             Output::Status(KeyStatus::NotChanged) => Ok(None),
-            Output::Status(_) => unreachable!("Unexpected output from Set"),
+            Output::Status(_) | Output::Entries(_) => unreachable!("Unexpected output from Set"),
         }
     }
 
@@ -130,6 +141,7 @@ where
             expiration,
             keep_existing_expiration,
             check,
+            durability,
         } = self;
         let result = kv.execute_key_operation(KeyOperation {
             namespace,
@@ -140,6 +152,7 @@ where
                 keep_existing_expiration,
                 check,
                 return_previous_value: false,
+                durability,
             }),
         })?;
         if let Output::Status(status) = result {
@@ -164,6 +177,7 @@ struct Options<'a, KeyValue, V> {
     expiration: Option<Timestamp>,
     keep_existing_expiration: bool,
     check: Option<KeyCheck>,
+    durability: DurabilityMode,
 }
 
 impl<'a, K, V> AsyncBuilder<'a, K, V>
@@ -186,6 +200,7 @@ where
                 expiration: None,
                 keep_existing_expiration: false,
                 check: None,
+                durability: DurabilityMode::Deferred,
             })),
         }
     }
@@ -230,6 +245,13 @@ where
         self
     }
 
+    /// Controls whether this operation waits for the change to be persisted
+    /// before returning. The default is [`DurabilityMode::Deferred`].
+    pub fn durability(mut self, durability: DurabilityMode) -> Self {
+        self.options().durability = durability;
+        self
+    }
+
     /// Executes the Set operation, requesting the previous value be returned.
     /// If no change is made, None will be returned.
     #[allow(clippy::missing_panics_doc)]
@@ -243,6 +265,7 @@ where
                 expiration,
                 keep_existing_expiration,
                 check,
+                durability,
             } = builder;
 
             let result = kv
@@ -255,13 +278,16 @@ where
                         keep_existing_expiration,
                         check,
                         return_previous_value: true,
+                        durability,
                     }),
                 })
                 .await?;
             match result {
                 Output::Value(value) => Ok(value),
                 Output::Status(KeyStatus::NotChanged) => Ok(None),
-                Output::Status(_) => unreachable!("Unexpected output from Set"),
+                Output::Status(_) | Output::Entries(_) => {
+                    unreachable!("Unexpected output from Set")
+                }
             }
         } else {
             panic!("Using future after it's been executed")
@@ -303,6 +329,7 @@ where
                     expiration,
                     keep_existing_expiration,
                     check,
+                    durability,
                 } = builder.take().expect("expected builder to have options");
                 let future = async move {
                     let result = kv
@@ -315,6 +342,7 @@ where
                                 keep_existing_expiration,
                                 check,
                                 return_previous_value: false,
+                                durability,
                             }),
                         })
                         .await?;
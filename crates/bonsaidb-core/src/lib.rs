@@ -47,6 +47,7 @@ pub mod pubsub;
 
 use std::fmt::Display;
 use std::string::FromUtf8Error;
+use std::sync::Arc;
 
 use schema::{view, CollectionName, SchemaName, ViewName};
 use serde::{Deserialize, Serialize};
@@ -63,6 +64,9 @@ use crate::key::NextValueError;
 use crate::schema::InsertError;
 
 /// an enumeration of errors that this crate can produce
+///
+/// Errors are sent over the network encoded by variant index, so new variants
+/// must only be added after the existing ones.
 #[derive(Clone, thiserror::Error, Debug, Serialize, Deserialize)]
 pub enum Error {
     /// The database named `database_name` was created with a different schema
@@ -206,6 +210,190 @@ pub enum Error {
         /// The error message.
         error: String,
     },
+
+    // Variants above this point keep the indexes they had in the
+    // `bonsai-pre-1` protocol. Add new variants at the end.
+    /// A collection was defined by two different schemas that were composed
+    /// using [`Schematic::extend_with()`](schema::Schematic::extend_with).
+    #[error("collection {collection} is defined by both schema {schema} and schema {conflicting_schema}")]
+    CollectionDefinedByMultipleSchemas {
+        /// The name of the collection defined more than once.
+        collection: CollectionName,
+        /// The schema that defined the collection first.
+        schema: SchemaName,
+        /// The schema that attempted to define the collection again.
+        conflicting_schema: SchemaName,
+    },
+
+    /// The permission group specified was not found.
+    #[error("permission group not found")]
+    PermissionGroupNotFound,
+
+    /// A permission statement was rejected by
+    /// [`PermissionGroup::validate_statements()`](admin::PermissionGroup::validate_statements).
+    #[error("invalid permission statement at index {index}: {reason}")]
+    InvalidPermissionStatement {
+        /// The index of the invalid statement.
+        index: usize,
+        /// Why the statement is invalid.
+        reason: String,
+    },
+
+    /// The role specified was not found.
+    #[error("role not found")]
+    RoleNotFound,
+
+    /// A permission group can't inherit from a group that already inherits
+    /// from it.
+    #[error("permission group inheritance would create a cycle")]
+    PermissionGroupInheritanceCycle,
+
+    /// Permission group inheritance was more than
+    /// [`MAX_PERMISSION_GROUP_INHERITANCE_DEPTH`](admin::MAX_PERMISSION_GROUP_INHERITANCE_DEPTH)
+    /// levels deep.
+    #[error("permission group inheritance is too deep")]
+    PermissionGroupInheritanceTooDeep,
+
+    /// A request was not signed, or its signature was invalid. See
+    /// [`RequestSigningKey`](networking::RequestSigningKey).
+    #[error("request signature invalid")]
+    SignatureInvalid,
+
+    /// The collection does not support renaming documents. See
+    /// [`NamedCollection::rename_contents()`](schema::NamedCollection::rename_contents).
+    #[error("documents in {0} can't be renamed")]
+    RenameUnsupported(CollectionName),
+
+    /// The storage is read-only, and the operation would have modified data.
+    #[error("storage is read-only")]
+    ReadOnly,
+
+    /// A [`TypedMessage`](pubsub::TypedMessage) was not published to its
+    /// expected [`Topic`](pubsub::Topic).
+    #[error("message was not published to topic {0}")]
+    UnexpectedTopic(pubsub::TopicName),
+
+    /// A view's map function looked up a document in a collection that is not
+    /// one of the view's [dependencies](schema::ViewSchema::dependencies).
+    #[error("view {view} does not depend on collection {collection}")]
+    ViewDependencyNotDeclared {
+        /// The name of the view that looked up the document.
+        view: ViewName,
+        /// The collection the document was looked up from.
+        collection: CollectionName,
+    },
+
+    /// A view's map function read an auxiliary input that is not one of the
+    /// view's [auxiliary inputs](schema::ViewSchema::auxiliary_inputs).
+    #[error("view {view} does not have an auxiliary input named {name}")]
+    ViewAuxiliaryInputNotDeclared {
+        /// The name of the view that read the input.
+        view: ViewName,
+        /// The name of the input that was read.
+        name: String,
+    },
+
+    /// A view wasn't updated through a transaction within the timeout of an
+    /// [`AccessPolicy::UpdateThrough`](connection::AccessPolicy::UpdateThrough)
+    /// query.
+    #[error("view {view} was not updated through transaction {transaction_id} in time")]
+    ViewUpdateTimeout {
+        /// The name of the view being queried.
+        view: ViewName,
+        /// The id of the transaction the view needed to have mapped.
+        transaction_id: u64,
+    },
+
+    /// An error occurred while reading from or writing to storage.
+    #[error("storage error: {message}")]
+    Storage {
+        /// The kind of storage error.
+        kind: StorageErrorKind,
+        /// A description of the error.
+        message: String,
+        /// The error that caused this error. This is only available where
+        /// the error occurred, as it is not serialized.
+        #[serde(skip)]
+        source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+    },
+
+    /// An error occurred while serializing or deserializing data.
+    #[error("error while serializing with {format}: {message}")]
+    Serialization {
+        /// The serialization format that produced the error.
+        format: String,
+        /// A description of the error.
+        message: String,
+        /// The error that caused this error. This is only available where
+        /// the error occurred, as it is not serialized.
+        #[serde(skip)]
+        source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+    },
+
+    /// An operation could not be performed because it conflicts with another
+    /// operation that is in progress. Retrying the operation after the other
+    /// operation has finished may succeed.
+    #[error("conflicting operation: {0}")]
+    Conflict(String),
+
+    /// A transaction was too large to execute.
+    #[error("transaction is too large")]
+    TransactionTooLarge,
+
+    /// A document's contents were larger than the maximum document size
+    /// allowed by its collection.
+    #[error("document contents are {size} bytes, which exceeds the limit of {limit} bytes")]
+    DocumentTooLarge {
+        /// The size of the document's contents, in bytes.
+        size: u64,
+        /// The maximum size allowed, in bytes.
+        limit: u64,
+    },
+
+    /// A request sent to a server was larger than the server allows.
+    #[error("request payload is {size} bytes, which exceeds the limit of {limit} bytes")]
+    PayloadTooLarge {
+        /// The size of the request's payload, in bytes.
+        size: u64,
+        /// The maximum size allowed, in bytes.
+        limit: u64,
+    },
+
+    /// A numeric Key-Value operation was attempted on a key whose stored
+    /// value is not numeric.
+    #[error("type of stored `Value` is not `Numeric`")]
+    ValueNotNumeric,
+
+    /// A numeric Key-Value operation using
+    /// [`OverflowPolicy::Error`](keyvalue::OverflowPolicy::Error) would have
+    /// overflowed or lost precision.
+    #[error("numeric operation overflowed")]
+    NumericOverflow,
+
+    /// A write was rejected because it would have caused a database to exceed
+    /// a hard limit of its [`DatabaseQuota`](admin::DatabaseQuota).
+    #[error("quota exceeded for {kind}: limit {limit}, usage {usage}")]
+    QuotaExceeded {
+        /// The resource whose limit would have been exceeded.
+        kind: admin::QuotaKind,
+        /// The hard limit of the resource.
+        limit: u64,
+        /// The usage of the resource before the rejected write.
+        usage: u64,
+    },
+
+    /// A subscriber could not be created because the configured maximum
+    /// number of subscribers has been reached.
+    #[error("the maximum number of subscribers has been reached")]
+    TooManySubscribers,
+
+    /// An operation was rejected because the storage is already performing
+    /// as much work of the same kind as it has been configured to allow.
+    #[error("the storage is overloaded, retry after {retry_after_hint:?}")]
+    Overloaded {
+        /// How long the caller should wait before retrying the operation.
+        retry_after_hint: std::time::Duration,
+    },
 }
 
 impl Error {
@@ -244,9 +432,38 @@ impl Error {
     }
 }
 
+/// The kind of an [`Error::Storage`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum StorageErrorKind {
+    /// An I/O error occurred.
+    Io,
+    /// Stored data could not be decoded.
+    Corruption,
+    /// An error occurred within the storage engine.
+    Internal,
+    /// The storage device is full, or a disk quota has been exceeded.
+    OutOfSpace,
+}
+
+impl Display for StorageErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io => f.write_str("io"),
+            Self::Corruption => f.write_str("corruption"),
+            Self::Internal => f.write_str("internal"),
+            Self::OutOfSpace => f.write_str("out of space"),
+        }
+    }
+}
+
 impl From<pot::Error> for Error {
     fn from(err: pot::Error) -> Self {
-        Self::other("pot", err)
+        Self::Serialization {
+            format: String::from("pot"),
+            message: err.to_string(),
+            source: Some(Arc::new(err)),
+        }
     }
 }
 
@@ -291,3 +508,72 @@ pub const ENCRYPTION_ENABLED: bool = false;
 pub trait AnyError: std::error::Error + Send + Sync + 'static {}
 
 impl<T> AnyError for T where T: std::error::Error + Send + Sync + 'static {}
+
+#[test]
+fn error_variant_indexes_are_stable() {
+    use actionable::{ActionName, ResourceName};
+
+    use crate::document::Revision;
+    use crate::schema::Qualified;
+
+    fn variant_index(error: &Error) -> u32 {
+        let bytes = bincode::serialize(error).unwrap();
+        u32::from_le_bytes(bytes[..4].try_into().unwrap())
+    }
+
+    let schema_name = SchemaName::private("schema");
+    let collection = CollectionName::private("collection");
+    let view = ViewName::new(collection.clone(), "view").unwrap();
+    let header = Header {
+        id: DocumentId::from_u64(1),
+        revision: Revision::new(b"contents"),
+    };
+
+    let baseline = [
+        Error::SchemaMismatch {
+            database_name: String::from("db"),
+            schema: schema_name.clone(),
+            stored_schema: schema_name.clone(),
+        },
+        Error::SchemaAlreadyRegistered(schema_name.clone()),
+        Error::SchemaNotRegistered(schema_name),
+        Error::ViewAlreadyRegistered(view.clone()),
+        Error::InvalidDatabaseName(String::from("db")),
+        Error::DatabaseNotFound(String::from("db")),
+        Error::ViewNotFound,
+        Error::CollectionNotFound,
+        Error::ApiNotFound(ApiName::private("api")),
+        Error::DatabaseNameAlreadyTaken(String::from("db")),
+        Error::Networking(networking::Error::ConnectTimeout),
+        Error::CollectionAlreadyDefined,
+        Error::DocumentNotFound(collection.clone(), Box::new(header.id.clone())),
+        Error::DocumentIdTooLong,
+        Error::DocumentConflict(collection.clone(), Box::new(header.clone())),
+        Error::UniqueKeyViolation {
+            view,
+            conflicting_document: Box::new(header.clone()),
+            existing_document: Box::new(header),
+        },
+        Error::DocumentPush(collection, NextValueError::WouldWrap),
+        Error::InvalidName(schema::InvalidNameError(String::from("name"))),
+        Error::PermissionDenied(actionable::PermissionDenied {
+            resource: ResourceName::named("resource"),
+            action: ActionName(vec!["action".into()]),
+        }),
+        Error::Password(String::from("password")),
+        Error::UserNotFound,
+        Error::InvalidUnicode(String::from("unicode")),
+        Error::InvalidCredentials,
+        Error::ReduceUnimplemented,
+        Error::NotANumber,
+        Error::Time(TimeError::DeltaNotRepresentable),
+        Error::Other {
+            origin: String::from("origin"),
+            error: String::from("error"),
+        },
+    ];
+
+    for (expected, error) in (0..).zip(&baseline) {
+        assert_eq!(variant_index(error), expected, "{error:?}");
+    }
+}
@@ -1,19 +1,112 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::time::Duration;
+
 use arc_bytes::serde::Bytes;
 use serde::{Deserialize, Serialize};
 
+use crate::admin::DatabaseQuota;
 use crate::api::{Api, ApiName};
 use crate::connection::{
-    AccessPolicy, Database, IdentityReference, Range, SerializedQueryKey, Session, SessionId, Sort,
+    AccessPolicy, Database, DeletedDocuments, IdentityReference, Range, ScrubReport,
+    SerializedQueryKey, Session, SessionId, Sort,
 };
 use crate::document::{DocumentId, Header, OwnedDocument};
 use crate::keyvalue::{KeyOperation, Output};
+use crate::permissions::Statement;
 use crate::schema::view::map::{self, MappedSerializedDocuments};
-use crate::schema::{CollectionName, NamedReference, Qualified, SchemaSummary, ViewName};
+use crate::schema::{
+    CollectionName, NamedReference, Qualified, SchemaDescription, SchemaName, SchemaSummary,
+    ViewName,
+};
 use crate::transaction::{Executed, OperationResult, Transaction};
 
+mod signing;
+
+pub use self::signing::{RequestSigningKey, SignedRequest};
+
 /// The current protocol version.
 pub const CURRENT_PROTOCOL_VERSION: &str = "bonsai-pre-1";
 
+/// All protocol versions supported by this version of BonsaiDb, ordered from
+/// newest to oldest.
+///
+/// Clients offer all of these versions when connecting, and servers advertise
+/// all of them. The newest version both sides support is used for the
+/// connection, which allows clients and servers to be upgraded independently.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &[CURRENT_PROTOCOL_VERSION];
+
+/// Returns the first version from `supported` that is also contained in
+/// `offered`. `supported` is ordered from newest to oldest, which is typically
+/// [`SUPPORTED_PROTOCOL_VERSIONS`]. Returns `None` if no versions are shared.
+#[must_use]
+pub fn negotiate_protocol_version<'a, Offered>(
+    supported: &[&'static str],
+    offered: Offered,
+) -> Option<&'static str>
+where
+    Offered: IntoIterator<Item = &'a str>,
+    Offered::IntoIter: Clone,
+{
+    let offered = offered.into_iter();
+    supported
+        .iter()
+        .copied()
+        .find(|supported| offered.clone().any(|version| version == *supported))
+}
+
+/// A feature that a server may or may not support. Servers report the
+/// capabilities they support in response to [`ListCapabilities`].
+///
+/// Capabilities are identified by name, which allows a client to receive
+/// capabilities from a newer server that it doesn't know about.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Capability(Cow<'static, str>);
+
+impl Capability {
+    /// The server supports at-rest encryption.
+    pub const ENCRYPTION: Self = Self(Cow::Borrowed("encryption"));
+    /// The server supports storing data compressed.
+    pub const COMPRESSION: Self = Self(Cow::Borrowed("compression"));
+    /// The server supports authenticating using passwords.
+    pub const PASSWORD_AUTHENTICATION: Self = Self(Cow::Borrowed("password-authentication"));
+    /// The server verifies that every request is signed using its
+    /// [`RequestSigningKey`].
+    pub const REQUEST_SIGNING: Self = Self(Cow::Borrowed("request-signing"));
+    /// The server supports authenticating using authentication tokens.
+    pub const TOKEN_AUTHENTICATION: Self = Self(Cow::Borrowed("token-authentication"));
+
+    /// Returns a capability named `name`.
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self(name.into())
+    }
+
+    /// Returns the name of this capability.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Lists the [`Capability`]s supported by the server.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct ListCapabilities;
+
+impl Api for ListCapabilities {
+    type Error = crate::Error;
+    type Response = Vec<Capability>;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "ListCapabilities")
+    }
+}
+
 /// A payload with an associated id.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct Payload {
@@ -61,6 +154,24 @@ impl Api for DeleteDatabase {
     }
 }
 
+/// Sets the quota of the database named `name`.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SetDatabaseQuota {
+    /// The name of the database.
+    pub name: String,
+    /// The new quota of the database.
+    pub quota: DatabaseQuota,
+}
+
+impl Api for SetDatabaseQuota {
+    type Error = crate::Error;
+    type Response = ();
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "SetDatabaseQuota")
+    }
+}
+
 /// Lists all databases.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct ListDatabases;
@@ -87,6 +198,22 @@ impl Api for ListAvailableSchemas {
     }
 }
 
+/// Describes a registered schema.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct DescribeSchema {
+    /// The name of the schema to describe.
+    pub schema: SchemaName,
+}
+
+impl Api for DescribeSchema {
+    type Error = crate::Error;
+    type Response = SchemaDescription;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "DescribeSchema")
+    }
+}
+
 /// Creates a user.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct CreateUser {
@@ -157,6 +284,31 @@ impl Api for Authenticate {
     }
 }
 
+/// Authenticate the current connection, replacing the session used by
+/// requests that don't specify a session.
+///
+/// Clients send this request while a connection is being established when
+/// configured to connect using a token. Authenticating with
+/// [`Authentication::Token`](crate::connection::Authentication::Token)
+/// returns a session pending a challenge response, which is sent using this
+/// request from that session.
+#[cfg(feature = "token-authentication")]
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct AuthenticateConnection {
+    /// The method of authentication.
+    pub authentication: crate::connection::Authentication,
+}
+
+#[cfg(feature = "token-authentication")]
+impl Api for AuthenticateConnection {
+    type Error = crate::Error;
+    type Response = Session;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "AuthenticateConnection")
+    }
+}
+
 /// Assume an identity.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct AssumeIdentity(pub IdentityReference<'static>);
@@ -170,6 +322,19 @@ impl Api for AssumeIdentity {
     }
 }
 
+/// Assume an identity, limited to the permissions of the current session.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct AssumeCappedIdentity(pub IdentityReference<'static>);
+
+impl Api for AssumeCappedIdentity {
+    type Error = crate::Error;
+    type Response = Session;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "AssumeCappedIdentity")
+    }
+}
+
 /// Logs out from a session.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct LogOutSession(pub SessionId);
@@ -227,6 +392,76 @@ impl Api for AlterUserRoleMembership {
     }
 }
 
+/// Creates a permission group.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct CreatePermissionGroup {
+    /// The unique name of the group.
+    pub name: String,
+    /// The permission statements of the group.
+    pub statements: Vec<Statement>,
+}
+
+impl Api for CreatePermissionGroup {
+    type Error = crate::Error;
+    type Response = u64;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "CreatePermissionGroup")
+    }
+}
+
+/// Replaces the statements of a permission group.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct UpdatePermissionGroupStatements {
+    /// The name or id of the group.
+    pub group: NamedReference<'static, u64>,
+    /// The new permission statements of the group.
+    pub statements: Vec<Statement>,
+}
+
+impl Api for UpdatePermissionGroupStatements {
+    type Error = crate::Error;
+    type Response = ();
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "UpdatePermissionGroupStatements")
+    }
+}
+
+/// Creates a role.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct CreateRole {
+    /// The unique name of the role.
+    pub name: String,
+    /// The ids of the permission groups the role grants.
+    pub groups: Vec<u64>,
+}
+
+impl Api for CreateRole {
+    type Error = crate::Error;
+    type Response = u64;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "CreateRole")
+    }
+}
+
+/// Deletes a role.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct DeleteRole {
+    /// The name or id of the role.
+    pub role: NamedReference<'static, u64>,
+}
+
+impl Api for DeleteRole {
+    type Error = crate::Error;
+    type Response = ();
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "DeleteRole")
+    }
+}
+
 /// Retrieve a single document.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct Get {
@@ -420,6 +655,20 @@ impl Api for DeleteDocs {
     }
 }
 
+/// Deletes the associated documents resulting from the view query, returning
+/// the headers of the deleted documents.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct DeleteDocsReturningHeaders(pub DeleteDocs);
+
+impl Api for DeleteDocsReturningHeaders {
+    type Error = crate::Error;
+    type Response = DeletedDocuments;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "DeleteDocsReturningHeaders")
+    }
+}
+
 /// Applies a transaction.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct ApplyTransaction {
@@ -438,6 +687,29 @@ impl Api for ApplyTransaction {
     }
 }
 
+/// Applies a transaction, returning the id the transaction was assigned along
+/// with its results.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct ApplyTransactionReturningId(pub ApplyTransaction);
+
+impl Api for ApplyTransactionReturningId {
+    type Error = crate::Error;
+    type Response = AppliedTransaction;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "ApplyTransactionReturningId")
+    }
+}
+
+/// The response to [`ApplyTransactionReturningId`].
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct AppliedTransaction {
+    /// The id the transaction was assigned.
+    pub transaction_id: u64,
+    /// The result of each operation in the transaction.
+    pub results: Vec<OperationResult>,
+}
+
 /// Lists executed transactions.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct ListExecutedTransactions {
@@ -626,6 +898,39 @@ impl Api for ExecuteKeyOperation {
     }
 }
 
+/// Persists all pending changes to the key-value store.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct PersistKeyValueStore {
+    /// The name of the database.
+    pub database: String,
+}
+
+impl Api for PersistKeyValueStore {
+    type Error = crate::Error;
+    type Response = ();
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "PersistKeyValueStore")
+    }
+}
+
+/// Waits until every change made to the key-value store before this request
+/// was received has been persisted, without forcing them to be persisted.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct AwaitKeyValueDurability {
+    /// The name of the database.
+    pub database: String,
+}
+
+impl Api for AwaitKeyValueDurability {
+    type Error = crate::Error;
+    type Response = ();
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "AwaitKeyValueDurability")
+    }
+}
+
 /// Compacts the collection.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct CompactCollection {
@@ -676,6 +981,75 @@ impl Api for Compact {
     }
 }
 
+/// Verifies that the data stored in a database can be read. This request must
+/// be sent scoped to the database using a [`DatabaseRequest`].
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct Scrub {
+    /// The maximum amount of time to spend scrubbing. If `None`, the scrub
+    /// continues until the end of the database is reached.
+    pub duration: Option<Duration>,
+}
+
+impl Api for Scrub {
+    type Error = crate::Error;
+    type Response = ScrubReport;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "Scrub")
+    }
+}
+
+/// A request that the server executes at most once within its idempotency
+/// window.
+///
+/// Clients wrap requests in this type when they may need to be retried, such
+/// as when replaying requests that were queued while offline. If a request
+/// with the same `operation_id` has already succeeded, the server responds
+/// with the original response rather than executing the request again.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct IdempotentRequest {
+    /// The client-generated id of this operation.
+    pub operation_id: u64,
+    /// The name of the wrapped request's api.
+    pub name: ApiName,
+    /// The serialized request.
+    pub value: Bytes,
+}
+
+impl Api for IdempotentRequest {
+    type Error = crate::Error;
+    type Response = Bytes;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "IdempotentRequest")
+    }
+}
+
+/// A request that is dispatched with a database's context available to its
+/// handler.
+///
+/// Clients wrap requests in this type to send an api request that is scoped
+/// to a database. The server opens `database` using the client's permissions
+/// before dispatching the wrapped request.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct DatabaseRequest {
+    /// The name of the database the request is scoped to.
+    pub database: String,
+    /// The name of the wrapped request's api.
+    pub name: ApiName,
+    /// The serialized request.
+    pub value: Bytes,
+}
+
+impl Api for DatabaseRequest {
+    type Error = crate::Error;
+    type Response = Bytes;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "DatabaseRequest")
+    }
+}
+
 /// A networking error.
 #[derive(Clone, thiserror::Error, Debug, Serialize, Deserialize)]
 pub enum Error {
@@ -0,0 +1,305 @@
+use arc_bytes::serde::Bytes;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::Payload;
+use crate::api::{Api, ApiName};
+use crate::connection::SessionId;
+use crate::schema::Qualified;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A request that has been signed using a [`RequestSigningKey`].
+///
+/// When request signing is enabled, the client sends every request wrapped in
+/// this type, and the server verifies the signature before the wrapped request
+/// is dispatched.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SignedRequest {
+    /// The name of the signed request's api.
+    pub name: ApiName,
+    /// The serialized request.
+    pub value: Bytes,
+    /// The HMAC-SHA256 signature of the request.
+    pub signature: Bytes,
+}
+
+impl Api for SignedRequest {
+    type Error = crate::Error;
+    type Response = Bytes;
+
+    fn name() -> ApiName {
+        ApiName::new("bonsaidb", "SignedRequest")
+    }
+}
+
+/// A secret shared between a client and a server that is used to sign
+/// requests, allowing the server to verify that requests were not tampered
+/// with between the client and the server.
+///
+/// Request signing is opt-in, and is enabled by configuring the same key on
+/// both the server and the client. A key can either be derived from a secret
+/// using [`RequestSigningKey::new()`], or be randomly generated by the server
+/// and stored alongside its storage. Once enabled on a server, all requests that
+/// are unsigned or have an invalid signature are rejected with
+/// [`Error::SignatureInvalid`](crate::Error::SignatureInvalid).
+///
+/// Each request is signed with an HMAC-SHA256 using a key derived from this
+/// secret and the request's [`SessionId`]. The signature covers the request's
+/// session, id, api name, and serialized contents. Signatures do not prevent
+/// a valid request from being replayed.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct RequestSigningKey([u8; 32]);
+
+impl std::fmt::Debug for RequestSigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RequestSigningKey(REDACTED)")
+    }
+}
+
+impl RequestSigningKey {
+    /// Returns a key derived from `secret`.
+    #[must_use]
+    pub fn new(secret: &[u8]) -> Self {
+        // The label only separates this use of `secret` from any other. The
+        // secret itself is always provided by the caller.
+        let mut mac = Self::mac(b"bonsaidb request-signing");
+        mac.update(secret);
+        Self(mac.finalize().into_bytes().into())
+    }
+
+    /// Returns a key using `bytes` as-is. The bytes should be uniformly
+    /// random, such as those returned from [`Self::to_bytes()`].
+    #[must_use]
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the raw bytes of this key, allowing it to be stored and later
+    /// loaded using [`Self::from_bytes()`].
+    #[must_use]
+    pub const fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    fn mac(key: &[u8]) -> HmacSha256 {
+        HmacSha256::new_from_slice(key).expect("hmac accepts keys of any length")
+    }
+
+    fn request_mac(
+        &self,
+        session_id: Option<SessionId>,
+        id: Option<u32>,
+        name: &ApiName,
+        value: &[u8],
+    ) -> HmacSha256 {
+        let mut session_key = Self::mac(&self.0);
+        match session_id {
+            Some(session_id) => {
+                session_key.update(&[1]);
+                session_key.update(&session_id.0.to_be_bytes());
+            }
+            None => session_key.update(&[0]),
+        }
+        let mut session_key: [u8; 32] = session_key.finalize().into_bytes().into();
+        let mut mac = Self::mac(&session_key);
+        session_key.zeroize();
+
+        match id {
+            Some(id) => {
+                mac.update(&[1]);
+                mac.update(&id.to_be_bytes());
+            }
+            None => mac.update(&[0]),
+        }
+        let name = name.to_string();
+        mac.update(&(name.len() as u64).to_be_bytes());
+        mac.update(name.as_bytes());
+        mac.update(value);
+        mac
+    }
+
+    fn http_mac(&self, method: &str, path_and_query: &str, body: &[u8]) -> HmacSha256 {
+        // The label separates HTTP signatures from request signatures, which
+        // are computed using a key derived from this one.
+        let mut mac = Self::mac(&self.0);
+        mac.update(b"http");
+        mac.update(&(method.len() as u64).to_be_bytes());
+        mac.update(method.as_bytes());
+        mac.update(&(path_and_query.len() as u64).to_be_bytes());
+        mac.update(path_and_query.as_bytes());
+        mac.update(body);
+        mac
+    }
+
+    /// Returns the signature of an HTTP request sent to a server's HTTP
+    /// bridge. The signature covers the request's method, its path including
+    /// the query, and its body.
+    #[must_use]
+    pub fn sign_http_request(&self, method: &str, path_and_query: &str, body: &[u8]) -> Vec<u8> {
+        self.http_mac(method, path_and_query, body)
+            .finalize()
+            .into_bytes()
+            .to_vec()
+    }
+
+    /// Verifies that `signature` was returned from
+    /// [`sign_http_request()`](Self::sign_http_request) for the same request.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::SignatureInvalid`](crate::Error::SignatureInvalid):
+    ///   `signature` is invalid.
+    pub fn verify_http_request(
+        &self,
+        method: &str,
+        path_and_query: &str,
+        body: &[u8],
+        signature: &[u8],
+    ) -> Result<(), crate::Error> {
+        self.http_mac(method, path_and_query, body)
+            .verify_slice(signature)
+            .map_err(|_| crate::Error::SignatureInvalid)
+    }
+
+    /// Signs `payload`, returning a new payload containing a
+    /// [`SignedRequest`].
+    pub fn sign(&self, payload: Payload) -> Result<Payload, crate::Error> {
+        let Payload {
+            session_id,
+            id,
+            name,
+            value,
+        } = payload;
+        let value = value?;
+        let signature = self
+            .request_mac(session_id, id, &name, &value)
+            .finalize()
+            .into_bytes();
+        let signed = SignedRequest {
+            name,
+            value,
+            signature: Bytes::from(signature.to_vec()),
+        };
+        Ok(Payload {
+            session_id,
+            id,
+            name: SignedRequest::name(),
+            value: Ok(Bytes::from(pot::to_vec(&signed)?)),
+        })
+    }
+
+    /// Verifies that `payload` contains a [`SignedRequest`] with a valid
+    /// signature, returning the payload that was signed.
+    ///
+    /// ## Errors
+    ///
+    /// * [`Error::SignatureInvalid`](crate::Error::SignatureInvalid): `payload`
+    ///   is not signed, or its signature is invalid.
+    pub fn verify(&self, payload: Payload) -> Result<Payload, crate::Error> {
+        if payload.name != SignedRequest::name() {
+            return Err(crate::Error::SignatureInvalid);
+        }
+        let signed = payload
+            .value
+            .ok()
+            .and_then(|value| pot::from_slice::<SignedRequest>(&value).ok())
+            .ok_or(crate::Error::SignatureInvalid)?;
+
+        self.request_mac(payload.session_id, payload.id, &signed.name, &signed.value)
+            .verify_slice(&signed.signature)
+            .map_err(|_| crate::Error::SignatureInvalid)?;
+
+        Ok(Payload {
+            session_id: payload.session_id,
+            id: payload.id,
+            name: signed.name,
+            value: Ok(signed.value),
+        })
+    }
+}
+
+#[test]
+fn signed_requests_are_verified() {
+    let key = RequestSigningKey::new(b"secret");
+    let payload = Payload {
+        session_id: Some(SessionId(1)),
+        id: Some(2),
+        name: ApiName::new("test", "Request"),
+        value: Ok(Bytes::from(b"request".to_vec())),
+    };
+
+    let signed = key.sign(payload.clone()).unwrap();
+    let verified = key.verify(signed.clone()).unwrap();
+    assert_eq!(verified.name, payload.name);
+    assert_eq!(verified.value.unwrap(), payload.value.unwrap());
+
+    // Unsigned requests are rejected.
+    let unsigned = Payload {
+        value: Ok(Bytes::from(b"request".to_vec())),
+        ..verified
+    };
+    assert!(matches!(
+        key.verify(unsigned),
+        Err(crate::Error::SignatureInvalid)
+    ));
+
+    // Requests signed with another key are rejected.
+    let other_key = RequestSigningKey::new(b"other secret");
+    assert!(matches!(
+        other_key.verify(signed.clone()),
+        Err(crate::Error::SignatureInvalid)
+    ));
+
+    // Signatures are bound to the session.
+    let other_session = Payload {
+        session_id: Some(SessionId(3)),
+        ..signed.clone()
+    };
+    assert!(matches!(
+        key.verify(other_session),
+        Err(crate::Error::SignatureInvalid)
+    ));
+
+    // Tampering with the request is detected.
+    let mut tampered = pot::from_slice::<SignedRequest>(signed.value.as_ref().unwrap()).unwrap();
+    tampered.value = Bytes::from(b"tampered".to_vec());
+    let tampered = Payload {
+        value: Ok(Bytes::from(pot::to_vec(&tampered).unwrap())),
+        ..signed
+    };
+    assert!(matches!(
+        key.verify(tampered),
+        Err(crate::Error::SignatureInvalid)
+    ));
+}
+
+#[test]
+fn signed_http_requests_are_verified() {
+    let key = RequestSigningKey::new(b"secret");
+    let signature = key.sign_http_request("PUT", "/databases/a/kv/b", b"1");
+    key.verify_http_request("PUT", "/databases/a/kv/b", b"1", &signature)
+        .unwrap();
+
+    for (method, path, body) in [
+        ("GET", "/databases/a/kv/b", &b"1"[..]),
+        ("PUT", "/databases/a/kv/c", b"1"),
+        ("PUT", "/databases/a/kv/b", b"2"),
+    ] {
+        assert!(matches!(
+            key.verify_http_request(method, path, body, &signature),
+            Err(crate::Error::SignatureInvalid)
+        ));
+    }
+    assert!(matches!(
+        RequestSigningKey::new(b"other secret").verify_http_request(
+            "PUT",
+            "/databases/a/kv/b",
+            b"1",
+            &signature
+        ),
+        Err(crate::Error::SignatureInvalid)
+    ));
+}
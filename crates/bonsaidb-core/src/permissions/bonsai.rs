@@ -60,6 +60,17 @@ pub fn kv_resource_name(database: &str) -> ResourceName<'_> {
     database_resource_name(database).and("keyvalue")
 }
 
+/// Creates a resource name for `namespace` within the key-value store of
+/// `database`. This resource name is used when scanning the keys of a
+/// namespace.
+#[must_use]
+pub fn keyvalue_namespace_resource_name<'a>(
+    database: &'a str,
+    namespace: Option<&'a str>,
+) -> ResourceName<'a> {
+    kv_resource_name(database).and(namespace.unwrap_or(""))
+}
+
 /// Creates a resource name for `key` within `namespace` within the key-value store of `database`.
 #[must_use]
 pub fn keyvalue_key_resource_name<'a>(
@@ -97,6 +108,21 @@ pub fn role_resource_name<'a>(role_id: u64) -> ResourceName<'a> {
     bonsaidb_resource_name().and("role").and(role_id)
 }
 
+/// Creates a resource name for the external identities authenticated by
+/// `provider`.
+#[must_use]
+pub fn external_identity_resource_name(provider: &str) -> ResourceName<'_> {
+    bonsaidb_resource_name().and("external").and(provider)
+}
+
+/// Creates a resource name for `group_id`.
+#[must_use]
+pub fn permission_group_resource_name<'a>(group_id: u64) -> ResourceName<'a> {
+    bonsaidb_resource_name()
+        .and("permission-group")
+        .and(group_id)
+}
+
 /// Creates a resource name for `token_id`.
 #[must_use]
 pub fn authentication_token_resource_name<'a>(token_id: u64) -> ResourceName<'a> {
@@ -130,6 +156,8 @@ pub enum ServerAction {
     CreateDatabase,
     /// Permits [`StorageConnection::delete_database`](crate::connection::StorageConnection::delete_database).
     DeleteDatabase,
+    /// Permits [`StorageConnection::set_database_quota`](crate::connection::StorageConnection::set_database_quota).
+    SetDatabaseQuota,
     /// Permits [`StorageConnection::create_user`](crate::connection::StorageConnection::create_user).
     CreateUser,
     /// Permits [`StorageConnection::delete_user`](crate::connection::StorageConnection::delete_user).
@@ -146,6 +174,14 @@ pub enum ServerAction {
     /// Permits .
     /// Permits [`StorageConnection::add_role_to_user`](crate::connection::StorageConnection::add_role_to_user) and [`StorageConnection::remove_role_from_user`](crate::connection::StorageConnection::remove_role_from_user).
     ModifyUserRoles,
+    /// Permits [`StorageConnection::create_permission_group`](crate::connection::StorageConnection::create_permission_group).
+    CreatePermissionGroup,
+    /// Permits [`StorageConnection::update_permission_group_statements`](crate::connection::StorageConnection::update_permission_group_statements).
+    UpdatePermissionGroup,
+    /// Permits [`StorageConnection::create_role`](crate::connection::StorageConnection::create_role).
+    CreateRole,
+    /// Permits [`StorageConnection::delete_role`](crate::connection::StorageConnection::delete_role).
+    DeleteRole,
 }
 
 /// Actions that operate on a specific database.
@@ -153,6 +189,8 @@ pub enum ServerAction {
 pub enum DatabaseAction {
     /// The ability to compact data to reclaim space.
     Compact,
+    /// The ability to verify that all stored data can be read.
+    Scrub,
     /// Actions that operate on a document.
     Document(DocumentAction),
     /// Actions that operate on a view.
@@ -175,9 +213,10 @@ pub enum DocumentAction {
     /// names.
     Get,
     /// Allows listing documents through
-    /// [`Connection::list()`](crate::connection::LowLevelConnection::list). See
-    /// [`collection_resource_name()`] for the format of collection resource
-    /// names.
+    /// [`Connection::list()`](crate::connection::LowLevelConnection::list).
+    /// Querying a view also requires this permission for the view's
+    /// collection. See [`collection_resource_name()`] for the format of
+    /// collection resource names.
     List,
     /// Allows listing documents through
     /// [`Connection::list_headers()`](crate::connection::LowLevelConnection::list_headers). See
@@ -217,6 +256,10 @@ pub enum ViewAction {
     /// Allows querying a view with
     /// [`Connection::query()`](crate::connection::LowLevelConnection::query). See
     /// [`view_resource_name`] for the format of view resource names.
+    ///
+    /// Queries also require [`DocumentAction::List`] for the view's
+    /// collection, and querying with documents requires [`DocumentAction::Get`]
+    /// for each document returned.
     Query,
     /// Allows reducing a view with
     /// [`Connection::reduce()`](crate::connection::LowLevelConnection::reduce). See
@@ -274,7 +317,13 @@ pub enum KeyValueAction {
     /// Allows executing a key-value store operation with
     /// [`KeyValue::execute_key_operation()`](crate::keyvalue::KeyValue::execute_key_operation).
     /// See [`keyvalue_key_resource_name()`] for the format of key resource names.
+    /// Scans are checked against [`keyvalue_namespace_resource_name()`].
     ExecuteOperation,
+    /// Allows persisting pending changes with
+    /// [`KeyValue::persist()`](crate::keyvalue::KeyValue::persist). See
+    /// [`kv_resource_name()`] for the format of key-value store resource
+    /// names.
+    Persist,
 }
 
 /// Actions that use encryption keys.
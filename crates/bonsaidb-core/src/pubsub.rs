@@ -1,10 +1,23 @@
+use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
+use std::ops::Deref;
+
 use async_trait::async_trait;
+pub use bonsaidb_macros::Topic;
 use circulate::{flume, Message};
-use serde::Serialize;
+use derive_where::derive_where;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
+use crate::schema::{Authority, Name, Qualified, QualifiedName};
 use crate::Error;
 
 /// Publishes and Subscribes to messages on topics.
+///
+/// Topics are scoped to the database they are published in: a message
+/// published to a topic in one database is never received by subscribers of
+/// the same topic in another database. To deliver a message to subscribers in
+/// several databases, publish it using a connection to each of them.
 pub trait PubSub {
     /// The Subscriber type for this `PubSub` connection.
     type Subscriber: Subscriber;
@@ -24,6 +37,11 @@ pub trait PubSub {
     /// Publishes a `payload` to all subscribers of `topic`.
     fn publish_bytes(&self, topic: Vec<u8>, payload: Vec<u8>) -> Result<(), Error>;
 
+    /// Publishes a `payload` to all subscribers of the [`Topic`] `T`.
+    fn publish_topic<T: Topic>(&self, payload: &T::Payload) -> Result<(), Error> {
+        self.publish(&T::name(), payload)
+    }
+
     /// Publishes a `payload` to all subscribers of all `topics`.
     fn publish_to_all<
         'topics,
@@ -68,11 +86,23 @@ pub trait Subscriber {
     /// Unsubscribe from [`Message`]s published to `topic`.
     fn unsubscribe_from_bytes(&self, topic: &[u8]) -> Result<(), Error>;
 
+    /// Subscribe to [`Message`]s published to the [`Topic`] `T`. Use
+    /// [`Receiver::typed()`] to receive the messages as [`TypedMessage`]s.
+    fn subscribe_to_topic<T: Topic>(&self) -> Result<(), Error> {
+        self.subscribe_to(&T::name())
+    }
+
+    /// Unsubscribe from [`Message`]s published to the [`Topic`] `T`.
+    fn unsubscribe_from_topic<T: Topic>(&self) -> Result<(), Error> {
+        self.unsubscribe_from(&T::name())
+    }
+
     /// Returns the receiver to receive [`Message`]s.
     fn receiver(&self) -> &Receiver;
 }
 
-/// Publishes and Subscribes to messages on topics.
+/// Publishes and Subscribes to messages on topics. Like [`PubSub`], topics
+/// are scoped to the database they are published in.
 #[async_trait]
 pub trait AsyncPubSub: Send + Sync {
     /// The Subscriber type for this `PubSub` connection.
@@ -95,6 +125,13 @@ pub trait AsyncPubSub: Send + Sync {
     /// Publishes a `payload` to all subscribers of `topic`.
     async fn publish_bytes(&self, topic: Vec<u8>, payload: Vec<u8>) -> Result<(), Error>;
 
+    /// Publishes a `payload` to all subscribers of the [`Topic`] `T`.
+    async fn publish_topic<T: Topic>(&self, payload: &T::Payload) -> Result<(), Error> {
+        let topic = pot::to_vec(&T::name())?;
+        let payload = pot::to_vec(payload)?;
+        self.publish_bytes(topic, payload).await
+    }
+
     /// Publishes a `payload` to all subscribers of all `topics`.
     async fn publish_to_all<
         'topics,
@@ -147,6 +184,17 @@ pub trait AsyncSubscriber: Send + Sync {
     /// Unsubscribe from [`Message`]s published to `topic`.
     async fn unsubscribe_from_bytes(&self, topic: &[u8]) -> Result<(), Error>;
 
+    /// Subscribe to [`Message`]s published to the [`Topic`] `T`. Use
+    /// [`Receiver::typed()`] to receive the messages as [`TypedMessage`]s.
+    async fn subscribe_to_topic<T: Topic>(&self) -> Result<(), Error> {
+        self.subscribe_to_bytes(pot::to_vec(&T::name())?).await
+    }
+
+    /// Unsubscribe from [`Message`]s published to the [`Topic`] `T`.
+    async fn unsubscribe_from_topic<T: Topic>(&self) -> Result<(), Error> {
+        self.unsubscribe_from_bytes(&pot::to_vec(&T::name())?).await
+    }
+
     /// Returns the receiver to receive [`Message`]s.
     fn receiver(&self) -> &Receiver;
 }
@@ -206,6 +254,26 @@ impl Receiver {
             .map_err(TryReceiveError::from)
     }
 
+    /// Receives all [`Message`]s that are currently available without
+    /// blocking. If no messages are available, an empty list is returned.
+    #[must_use]
+    pub fn drain(&self) -> Vec<Message> {
+        self.receiver
+            .try_iter()
+            .map(|message| self.remove_database_prefix(message))
+            .collect()
+    }
+
+    /// Returns a receiver that returns each [`Message`] as a
+    /// [`TypedMessage`] of the [`Topic`] `T`. Both receivers receive from the
+    /// same underlying channel: each message is only returned by one of them.
+    pub fn typed<T: Topic>(&self) -> TypedReceiver<T> {
+        TypedReceiver {
+            receiver: self.clone(),
+            _topic: PhantomData,
+        }
+    }
+
     fn remove_database_prefix(&self, mut message: Message) -> Message {
         if self.strip_database {
             if let Some(database_length) = message.topic.iter().position(|b| b == 0) {
@@ -225,6 +293,102 @@ impl Iterator for Receiver {
     }
 }
 
+/// A [`Receiver`] of [`TypedMessage`]s for the [`Topic`] `T`. Returned from
+/// [`Receiver::typed()`].
+#[derive_where(Clone, Debug)]
+#[must_use]
+pub struct TypedReceiver<T> {
+    receiver: Receiver,
+    _topic: PhantomData<T>,
+}
+
+impl<T: Topic> TypedReceiver<T> {
+    /// Receive the next [`TypedMessage`]. Blocks the current thread until a
+    /// message is available. If the receiver becomes disconnected, an error
+    /// will be returned.
+    pub fn receive(&self) -> Result<TypedMessage<T>, Disconnected> {
+        self.receiver.receive().map(TypedMessage::from)
+    }
+
+    /// Receive the next [`TypedMessage`]. Blocks the current task until a new
+    /// message is available. If the receiver becomes disconnected, an error
+    /// will be returned.
+    pub async fn receive_async(&self) -> Result<TypedMessage<T>, Disconnected> {
+        self.receiver.receive_async().await.map(TypedMessage::from)
+    }
+
+    /// Try to receive the next [`TypedMessage`]. This function will not
+    /// block, and only returns a message if one is already available.
+    pub fn try_receive(&self) -> Result<TypedMessage<T>, TryReceiveError> {
+        self.receiver.try_receive().map(TypedMessage::from)
+    }
+
+    /// Returns the untyped [`Receiver`].
+    pub fn untyped(&self) -> &Receiver {
+        &self.receiver
+    }
+}
+
+impl<T: Topic> Iterator for TypedReceiver<T> {
+    type Item = TypedMessage<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receive().ok()
+    }
+}
+
+/// A [`Message`] expected to have been published to the [`Topic`] `T`.
+#[derive_where(Clone, Debug)]
+pub struct TypedMessage<T> {
+    message: Message,
+    _topic: PhantomData<T>,
+}
+
+impl<T: Topic> TypedMessage<T> {
+    /// Deserializes and returns the payload of this message.
+    ///
+    /// Returns [`Error::UnexpectedTopic`] if this message was published to a
+    /// topic other than `T`, which can happen if the subscriber is subscribed
+    /// to more than one topic. Returns an error if the payload can't be
+    /// deserialized as [`Topic::Payload`].
+    pub fn payload(&self) -> Result<T::Payload, Error> {
+        if !self.is_topic() {
+            return Err(Error::UnexpectedTopic(T::name()));
+        }
+
+        Ok(self.message.payload::<T::Payload>()?)
+    }
+
+    /// Returns true if this message was published to the topic `T`.
+    #[must_use]
+    pub fn is_topic(&self) -> bool {
+        self.message
+            .topic::<TopicName>()
+            .map_or(false, |topic| topic == T::name())
+    }
+
+    /// Returns the underlying [`Message`].
+    #[must_use]
+    pub const fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// Returns the underlying [`Message`].
+    #[must_use]
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+}
+
+impl<T> From<Message> for TypedMessage<T> {
+    fn from(message: Message) -> Self {
+        Self {
+            message,
+            _topic: PhantomData,
+        }
+    }
+}
+
 /// The [`Receiver`] was disconnected
 #[derive(thiserror::Error, Debug, Clone, Eq, PartialEq)]
 #[error("the receiver is disconnected")]
@@ -250,12 +414,78 @@ impl From<flume::TryRecvError> for TryReceiveError {
     }
 }
 
+/// A PubSub topic with a strongly-typed payload.
+///
+/// Publishing with [`PubSub::publish_topic()`] and subscribing with
+/// [`Subscriber::subscribe_to_topic()`] ensures that the topic's name and
+/// payload type always agree. Typed topics are published to the serialized
+/// [`TopicName`], so [`PubSub::publish()`] can be used with [`Topic::name()`]
+/// to publish to a typed topic from code that doesn't know the type.
+///
+/// # Deriving this trait
+///
+/// This trait can be derived. The only required attribute is `name`:
+///
+/// - `name = "topic-name"` or `name = "topic-name", authority =
+///   "topic-authority"`: Configures the topic's fully qualified name.
+/// - `payload = PayloadType`: Configures the [`Topic::Payload`] associated
+///   type. If not specified, `Self` is used.
+///
+/// ```rust
+/// use bonsaidb_core::pubsub::Topic;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Topic, Debug, Serialize, Deserialize)]
+/// #[topic(name = "chat")]
+/// # #[topic(core = bonsaidb_core)]
+/// struct ChatMessage {
+///     author: String,
+///     text: String,
+/// }
+/// ```
+pub trait Topic: Send + Sync + 'static {
+    /// The type of the messages published to this topic.
+    type Payload: Serialize + DeserializeOwned + Send + Sync;
+
+    /// Returns the unique name of this topic.
+    fn name() -> TopicName;
+}
+
+/// The qualified name of a [`Topic`].
+#[derive(Hash, PartialEq, Eq, Deserialize, Serialize, Debug, Clone, Ord, PartialOrd)]
+#[serde(transparent)]
+pub struct TopicName(QualifiedName);
+
+impl Qualified for TopicName {
+    fn new<A: Into<Authority>, N: Into<Name>>(authority: A, name: N) -> Self {
+        Self(QualifiedName::new(authority, name))
+    }
+}
+
+impl Display for TopicName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Deref for TopicName {
+    type Target = QualifiedName;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 /// Creates a topic for use in a server. This is an internal API, which is why
 /// the documentation is hidden. This is an implementation detail, but both
 /// Client and Server must agree on this format, which is why it lives in core.
+///
+/// Database names can't contain a null byte, which keeps topics from different
+/// databases distinct regardless of the bytes `topic` contains.
 #[doc(hidden)]
 #[must_use]
 pub fn database_topic(database: &str, topic: &[u8]) -> Vec<u8> {
+    debug_assert!(!database.contains('\0'));
     let mut namespaced_topic = Vec::with_capacity(database.len() + topic.len() + 1);
 
     namespaced_topic.extend(database.bytes());
@@ -445,6 +675,45 @@ macro_rules! define_async_pubsub_test_suite {
 
                 Ok(())
             }
+
+            #[tokio::test]
+            async fn typed_topic_test() -> anyhow::Result<()> {
+                use $crate::pubsub::Topic;
+                use $crate::test_util::BasicTopic;
+
+                let harness =
+                    $harness::new($crate::test_util::HarnessTest::PubSubTypedTopic).await?;
+                let pubsub = harness.connect().await?;
+                let subscriber = AsyncPubSub::create_subscriber(&pubsub).await?;
+                AsyncSubscriber::subscribe_to_topic::<BasicTopic>(&subscriber).await?;
+                AsyncSubscriber::subscribe_to(&subscriber, &"untyped").await?;
+                let message = BasicTopic {
+                    value: String::from("typed"),
+                };
+                AsyncPubSub::publish_topic::<BasicTopic>(&pubsub, &message).await?;
+                // Untyped publishes to the topic's name are received as typed
+                // messages.
+                AsyncPubSub::publish(&pubsub, &BasicTopic::name(), &String::from("mismatched"))
+                    .await?;
+                AsyncPubSub::publish(&pubsub, &"untyped", &message).await?;
+
+                let receiver = subscriber.receiver().typed::<BasicTopic>();
+                let received = receiver.receive_async().await?;
+                assert!(received.is_topic());
+                assert_eq!(received.payload()?, message);
+                // Mismatched payloads surface their decode errors.
+                let received = receiver.receive_async().await?;
+                assert!(received.is_topic());
+                assert!(received.payload().is_err());
+                // Messages on other topics are never decoded as `BasicTopic`.
+                let received = receiver.receive_async().await?;
+                assert!(!received.is_topic());
+                assert!(matches!(
+                    received.payload(),
+                    Err($crate::Error::UnexpectedTopic(_))
+                ));
+                Ok(())
+            }
         }
     };
 }
@@ -594,6 +863,43 @@ macro_rules! define_blocking_pubsub_test_suite {
 
                 Ok(())
             }
+
+            #[test]
+            fn typed_topic_test() -> anyhow::Result<()> {
+                use $crate::pubsub::Topic;
+                use $crate::test_util::BasicTopic;
+
+                let harness = $harness::new($crate::test_util::HarnessTest::PubSubTypedTopic)?;
+                let pubsub = harness.connect()?;
+                let subscriber = PubSub::create_subscriber(&pubsub)?;
+                Subscriber::subscribe_to_topic::<BasicTopic>(&subscriber)?;
+                Subscriber::subscribe_to(&subscriber, &"untyped")?;
+                let message = BasicTopic {
+                    value: String::from("typed"),
+                };
+                PubSub::publish_topic::<BasicTopic>(&pubsub, &message)?;
+                // Untyped publishes to the topic's name are received as typed
+                // messages.
+                PubSub::publish(&pubsub, &BasicTopic::name(), &String::from("mismatched"))?;
+                PubSub::publish(&pubsub, &"untyped", &message)?;
+
+                let receiver = subscriber.receiver().typed::<BasicTopic>();
+                let received = receiver.receive()?;
+                assert!(received.is_topic());
+                assert_eq!(received.payload()?, message);
+                // Mismatched payloads surface their decode errors.
+                let received = receiver.receive()?;
+                assert!(received.is_topic());
+                assert!(received.payload().is_err());
+                // Messages on other topics are never decoded as `BasicTopic`.
+                let received = receiver.receive()?;
+                assert!(!received.is_topic());
+                assert!(matches!(
+                    received.payload(),
+                    Err($crate::Error::UnexpectedTopic(_))
+                ));
+                Ok(())
+            }
         }
     };
 }
@@ -1,4 +1,6 @@
 mod collection;
+mod description;
+mod format;
 mod names;
 mod schematic;
 mod summary;
@@ -11,6 +13,8 @@ pub use self::collection::{
     AsyncEntry, AsyncList, Collection, DefaultSerialization, InsertError, List, Nameable,
     NamedCollection, NamedReference, SerializedCollection,
 };
+pub use self::description::{CollectionDescription, SchemaDescription, ViewDescription};
+pub use self::format::{DocumentFormat, SerializationFormat};
 pub use self::names::{
     Authority, CollectionName, InvalidNameError, Name, Qualified, QualifiedName, SchemaName,
     ViewName,
@@ -20,7 +24,7 @@ pub use self::summary::{CollectionSummary, SchemaSummary, ViewSummary};
 pub use self::view::map::{Map, MappedValue, ViewMappedValue};
 pub use self::view::{
     CollectionMapReduce, DefaultViewSerialization, MapReduce, ReduceResult, SerializedView, View,
-    ViewMapResult, ViewSchema,
+    ViewLookup, ViewMapResult, ViewSchema,
 };
 use crate::Error;
 
@@ -17,7 +17,7 @@ use crate::document::{
     OwnedDocument, OwnedDocuments, Revision,
 };
 use crate::key::{IntoPrefixRange, Key, KeyEncoding};
-use crate::schema::{CollectionName, Schematic};
+use crate::schema::{CollectionName, DocumentFormat, Schematic};
 use crate::transaction::{Operation, OperationResult, Transaction};
 use crate::Error;
 
@@ -228,6 +228,12 @@ use crate::Error;
 /// If the collection type implements or derives the [`Key`](crate::key::Key)
 /// trait, `serialization = Key` can be passed to serialize using the [key
 /// format](crate::key::KeyFormat).
+///
+/// Alternatively, `format` can be set to `"pot"`, `"json"`, `"bincode"`, or
+/// `"cbor"` to serialize using a [`DocumentFormat`](crate::schema::DocumentFormat).
+/// Because each stored document's format is detected when it is read, the
+/// chosen format can be changed later without migrating existing documents.
+/// All formats except Pot require enabling the feature of the same name.
 pub trait Collection: Send + Sync {
     /// The unique id type. Each document stored in a collection will be
     /// uniquely identified by this type.
@@ -252,6 +258,24 @@ pub trait Collection: Send + Sync {
     fn encryption_key() -> Option<KeyId> {
         None
     }
+
+    /// If a size is returned, documents in this collection whose contents are
+    /// larger than the returned number of bytes are rejected, overriding any
+    /// limit configured for the storage.
+    #[must_use]
+    fn max_document_size() -> Option<u64> {
+        None
+    }
+
+    /// If a [`DocumentFormat`] is returned, this collection's documents are
+    /// serialized using it. This allows tools that don't know this
+    /// collection's Rust types, such as the server's HTTP bridge, to store
+    /// documents in a format this collection can read. The `Collection`
+    /// derive macro returns the format selected using `format`.
+    #[must_use]
+    fn document_format() -> Option<DocumentFormat> {
+        None
+    }
 }
 
 /// A collection that knows how to serialize and deserialize documents to an associated type.
@@ -300,6 +324,28 @@ pub trait SerializedCollection: Collection {
             .map_err(|err| crate::Error::other("serialization", err))
     }
 
+    /// Deserialize only the parts of `data` that `Projection` defines, using
+    /// this collection's format.
+    ///
+    /// `Projection` is usually a struct containing a subset of the fields of
+    /// [`Self::Contents`]. With formats that can skip values without decoding
+    /// them, such as the default [`Pot`] format, fields that `Projection`
+    /// doesn't contain are skipped instead of being deserialized. This makes
+    /// reading a small field of a document that also contains large fields
+    /// much cheaper than deserializing the entire document.
+    ///
+    /// If a field of `Projection` isn't present in `data`, an error is
+    /// returned unless the field has a default value, such as an `Option` or a
+    /// field annotated with `#[serde(default)]`.
+    fn deserialize_projection<Projection>(data: &[u8]) -> Result<Projection, Error>
+    where
+        Self::Format: OwnedDeserializer<Projection>,
+    {
+        Self::format()
+            .deserialize_owned(data)
+            .map_err(|err| crate::Error::other("serialization", err))
+    }
+
     /// Returns the deserialized contents of `doc`.
     fn document_contents<D: Document<Self>>(doc: &D) -> Result<Self::Contents, Error>
     where
@@ -384,6 +430,80 @@ pub trait SerializedCollection: Collection {
         Ok(possible_doc.as_ref().map(TryInto::try_into).transpose()?)
     }
 
+    /// Gets the document with `id` from `connection`, deserializing only the
+    /// parts of its contents that `Projection` defines. See
+    /// [`Self::deserialize_projection()`] for more information.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::Connection;
+    /// # fn test_fn<C: Connection>(db: C) -> Result<(), Error> {
+    /// #[derive(Debug, Deserialize)]
+    /// struct Summary {
+    ///     rank: u32,
+    /// }
+    ///
+    /// if let Some(summary) = MyCollection::get_projection::<Summary, _, _>(&42, &db)? {
+    ///     println!("Retrieved rank {}", summary.rank);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn get_projection<Projection, C, PrimaryKey>(
+        id: &PrimaryKey,
+        connection: &C,
+    ) -> Result<Option<Projection>, Error>
+    where
+        C: Connection,
+        PrimaryKey: KeyEncoding<Self::PrimaryKey>,
+        Self::Format: OwnedDeserializer<Projection>,
+        Self: Sized,
+    {
+        connection
+            .get::<Self, _>(id)?
+            .map(|document| Self::deserialize_projection(&document.contents))
+            .transpose()
+    }
+
+    /// Gets the document with `id` from `connection`, deserializing only the
+    /// parts of its contents that `Projection` defines. See
+    /// [`Self::deserialize_projection()`] for more information.
+    ///
+    /// ```rust
+    /// # bonsaidb_core::__doctest_prelude!();
+    /// # use bonsaidb_core::connection::AsyncConnection;
+    /// # fn test_fn<C: AsyncConnection>(db: C) -> Result<(), Error> {
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// #[derive(Debug, Deserialize)]
+    /// struct Summary {
+    ///     rank: u32,
+    /// }
+    ///
+    /// let summary = MyCollection::get_projection_async::<Summary, _, _>(&42, &db).await?;
+    /// if let Some(summary) = summary {
+    ///     println!("Retrieved rank {}", summary.rank);
+    /// }
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
+    async fn get_projection_async<Projection, C, PrimaryKey>(
+        id: &PrimaryKey,
+        connection: &C,
+    ) -> Result<Option<Projection>, Error>
+    where
+        C: AsyncConnection,
+        PrimaryKey: KeyEncoding<Self::PrimaryKey>,
+        Self::Format: OwnedDeserializer<Projection>,
+        Self: Sized,
+    {
+        connection
+            .get::<Self, _>(id)
+            .await?
+            .map(|document| Self::deserialize_projection(&document.contents))
+            .transpose()
+    }
+
     /// Retrieves all documents matching `ids`. Documents that are not found
     /// are not returned, but no error will be generated.
     ///
@@ -1351,6 +1471,21 @@ pub trait NamedCollection: Collection + Unpin {
     /// The name view defined for the collection.
     type ByNameView: crate::schema::SerializedView<Key = String, Collection = Self>;
 
+    /// Changes the name stored in `contents` to `name`. This is used by
+    /// [`Connection::rename()`](crate::connection::Connection::rename).
+    ///
+    /// The default implementation returns [`Error::RenameUnsupported`].
+    fn rename_contents(
+        contents: &mut <Self as SerializedCollection>::Contents,
+        name: &str,
+    ) -> Result<(), Error>
+    where
+        Self: SerializedCollection + Sized,
+    {
+        let _ = (contents, name);
+        Err(Error::RenameUnsupported(Self::collection_name()))
+    }
+
     /// Gets a [`CollectionDocument`] with `id` from `connection`.
     fn load<'name, N: Nameable<'name, Self::PrimaryKey> + Send + Sync, C: Connection>(
         id: N,
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use crate::document::KeyId;
+use crate::key::KeyDescription;
+use crate::schema::view::ViewUpdatePolicy;
+use crate::schema::{CollectionName, SchemaName, Schematic, ViewName};
+
+/// A machine-readable description of a [`Schema`](crate::schema::Schema).
+///
+/// This type is returned from [`Schematic::description()`], and can be
+/// requested from a server using
+/// [`DescribeSchema`](crate::networking::DescribeSchema). Unlike
+/// [`SchemaSummary`](crate::schema::SchemaSummary), collections and views are
+/// sorted by name, which ensures describing the same schema always serializes
+/// identically. This makes it suitable for generating code or detecting
+/// changes to a schema.
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone)]
+pub struct SchemaDescription {
+    /// The name of the described schema.
+    pub name: SchemaName,
+    /// The collections contained in the schema, sorted by name.
+    pub collections: Vec<CollectionDescription>,
+}
+
+impl SchemaDescription {
+    /// Returns the description of the named collection, if the schema contains
+    /// it.
+    #[must_use]
+    pub fn collection(&self, name: &CollectionName) -> Option<&CollectionDescription> {
+        self.collections
+            .binary_search_by(|collection| collection.name.cmp(name))
+            .ok()
+            .map(|index| &self.collections[index])
+    }
+}
+
+impl<'a> From<&'a Schematic> for SchemaDescription {
+    fn from(schematic: &'a Schematic) -> Self {
+        let mut collections = schematic
+            .collections()
+            .map(|collection_name| {
+                let mut views = schematic
+                    .views_in_collection(collection_name)
+                    .map(|view| ViewDescription {
+                        name: view.view_name(),
+                        version: view.version(),
+                        policy: view.update_policy(),
+                        key: view.key_description(),
+                        key_type: view.key_type_name().into_owned(),
+                    })
+                    .collect::<Vec<_>>();
+                views.sort_by(|a, b| a.name.cmp(&b.name));
+
+                CollectionDescription {
+                    name: collection_name.clone(),
+                    primary_key: schematic
+                        .collection_primary_key_description(collection_name)
+                        .expect("invalid schematic")
+                        .clone(),
+                    primary_key_type: schematic
+                        .collection_primary_key_type_name(collection_name)
+                        .expect("invalid schematic")
+                        .to_string(),
+                    encryption_key: schematic
+                        .encryption_key_for_collection(collection_name)
+                        .cloned(),
+                    views,
+                }
+            })
+            .collect::<Vec<_>>();
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            name: schematic.name.clone(),
+            collections,
+        }
+    }
+}
+
+/// A description of a [`Collection`](crate::schema::Collection).
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CollectionDescription {
+    /// The name of the described collection.
+    pub name: CollectionName,
+    /// The description of
+    /// [`Collection::PrimaryKey`](crate::schema::Collection::PrimaryKey).
+    pub primary_key: KeyDescription,
+    /// The result of [`Key::type_name()`](crate::key::Key::type_name) for
+    /// [`Collection::PrimaryKey`](crate::schema::Collection::PrimaryKey).
+    pub primary_key_type: String,
+    /// The result of
+    /// [`Collection::encryption_key()`](crate::schema::Collection::encryption_key).
+    pub encryption_key: Option<KeyId>,
+    /// The views in this collection, sorted by name.
+    pub views: Vec<ViewDescription>,
+}
+
+impl CollectionDescription {
+    /// Returns the description of the named view, if it is contained in this
+    /// collection.
+    #[must_use]
+    pub fn view(&self, name: &ViewName) -> Option<&ViewDescription> {
+        self.views
+            .binary_search_by(|view| view.name.cmp(name))
+            .ok()
+            .map(|index| &self.views[index])
+    }
+}
+
+/// A description of a [`ViewSchema`](crate::schema::ViewSchema).
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ViewDescription {
+    /// The name of the described view.
+    pub name: ViewName,
+    /// The result of
+    /// [`ViewSchema::version()`](crate::schema::ViewSchema::version) for this
+    /// view.
+    pub version: u64,
+    /// The result of
+    /// [`ViewSchema::update_policy()`](crate::schema::ViewSchema::update_policy)
+    /// for this view.
+    pub policy: ViewUpdatePolicy,
+    /// The description of [`View::Key`](crate::schema::View::Key).
+    pub key: KeyDescription,
+    /// The result of [`Key::type_name()`](crate::key::Key::type_name) for
+    /// [`View::Key`](crate::schema::View::Key).
+    pub key_type: String,
+}
+
+#[test]
+fn description_is_stable() -> anyhow::Result<()> {
+    use crate::key::{Key, KeyKind};
+    use crate::schema::{Collection, View};
+    use crate::test_util::{Basic, BasicByParentId, BasicSchema};
+
+    // Each schematic's maps are seeded differently, so this verifies the
+    // description doesn't depend on the iteration order of the schematic.
+    let json = serde_json::to_string(&Schematic::from_schema::<BasicSchema>()?.description())?;
+    for _ in 0..5 {
+        let other = Schematic::from_schema::<BasicSchema>()?.description();
+        assert_eq!(serde_json::to_string(&other)?, json);
+    }
+
+    let description = serde_json::from_str::<SchemaDescription>(&json)?;
+    assert_eq!(
+        description,
+        Schematic::from_schema::<BasicSchema>()?.description()
+    );
+
+    let basic = description
+        .collection(&Basic::collection_name())
+        .expect("collection missing");
+    assert_eq!(basic.primary_key, KeyDescription::Basic(KeyKind::U64));
+    // Type names aren't guaranteed to be stable between compiler versions, so
+    // only check that they are reported.
+    assert_eq!(basic.primary_key_type, <u64 as Key>::type_name());
+    assert_eq!(basic.encryption_key, None);
+    let by_parent_id = basic
+        .view(&BasicByParentId.view_name())
+        .expect("view missing");
+    assert_eq!(by_parent_id.version, 1);
+    assert_eq!(by_parent_id.key, KeyDescription::for_key::<Option<u64>>());
+    assert_eq!(by_parent_id.key_type, <Option<u64> as Key>::type_name());
+
+    Ok(())
+}
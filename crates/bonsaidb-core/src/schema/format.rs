@@ -0,0 +1,316 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Every Pot payload begins with this header. Documents stored using
+/// [`DefaultSerialization`](crate::schema::DefaultSerialization) have no
+/// format header, and are recognized by this header instead.
+const POT_HEADER: &[u8] = b"Pot";
+/// Every document serialized using [`DocumentFormat`] begins with this
+/// header, followed by a byte identifying the format used. It can't be
+/// confused with [`POT_HEADER`].
+const FORMAT_HEADER: &[u8] = b"BDf";
+/// The names of the formats that can be recorded in a document's header. The
+/// byte following [`FORMAT_HEADER`] is an index into this list. Formats are
+/// listed here even if their feature is disabled, so that documents stored
+/// using them can still be recognized.
+const FORMAT_NAMES: [&str; 4] = ["pot", "json", "bincode", "cbor"];
+
+/// A serialization format that a collection's documents can be stored using.
+/// See [`DocumentFormat`] for more information.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum SerializationFormat {
+    /// Serialize using [Pot](https://github.com/khonsulabs/pot), the format
+    /// used by [`DefaultSerialization`](crate::schema::DefaultSerialization).
+    #[default]
+    Pot,
+    /// Serialize using JSON. Requires the `json` feature.
+    #[cfg(feature = "json")]
+    Json,
+    /// Serialize using [Bincode](https://github.com/bincode-org/bincode).
+    /// Requires the `bincode` feature.
+    #[cfg(feature = "bincode")]
+    Bincode,
+    /// Serialize using CBOR. Requires the `cbor` feature.
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl SerializationFormat {
+    /// Returns the name of this format, which matches the name accepted by
+    /// the `Collection` derive macro.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        FORMAT_NAMES[self.id() as usize]
+    }
+
+    /// Returns true if data serialized using this format describes its own
+    /// structure, which allows it to be converted to and from other
+    /// self-describing formats such as JSON without knowing its type.
+    #[must_use]
+    pub const fn is_self_describing(self) -> bool {
+        #[cfg(feature = "bincode")]
+        if matches!(self, Self::Bincode) {
+            return false;
+        }
+        true
+    }
+
+    /// Returns the byte recorded in the header of documents stored using
+    /// this format.
+    const fn id(self) -> u8 {
+        match self {
+            Self::Pot => 0,
+            #[cfg(feature = "json")]
+            Self::Json => 1,
+            #[cfg(feature = "bincode")]
+            Self::Bincode => 2,
+            #[cfg(feature = "cbor")]
+            Self::Cbor => 3,
+        }
+    }
+}
+
+/// A Transmog [`Format`](transmog::Format) that serializes using a
+/// [`SerializationFormat`], and deserializes data that was serialized using
+/// any [`SerializationFormat`].
+///
+/// Each document is stored with a short header recording the format it was
+/// serialized with, and is deserialized using the recorded format. This
+/// allows a collection's format to be changed without migrating the
+/// documents it already contains. Documents without a header are read as
+/// Pot, which allows collections that were stored using
+/// [`DefaultSerialization`](crate::schema::DefaultSerialization) to switch to
+/// this format.
+///
+/// Backups store the payload of each document without its header, in a
+/// container named after the collection and the format, such as
+/// `khonsulabs.basic.json`. This allows other tools to read JSON and CBOR
+/// documents directly from backups.
+///
+/// This format can be selected using the `Collection` derive macro:
+///
+/// ```rust
+/// use bonsaidb_core::schema::Collection;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Serialize, Deserialize, Collection)]
+/// #[collection(name = "MyCollection", format = "pot")]
+/// # #[collection(core = bonsaidb_core)]
+/// pub struct MyCollection {
+///     pub rank: u32,
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct DocumentFormat {
+    format: SerializationFormat,
+}
+
+impl DocumentFormat {
+    /// Returns a format that serializes using `format`.
+    #[must_use]
+    pub const fn new(format: SerializationFormat) -> Self {
+        Self { format }
+    }
+
+    /// Returns the format used when serializing.
+    #[must_use]
+    pub const fn format(&self) -> SerializationFormat {
+        self.format
+    }
+
+    /// Returns the names of every format that can be recorded in a stored
+    /// document, including formats whose features are disabled.
+    #[must_use]
+    pub const fn stored_format_names() -> &'static [&'static str] {
+        &FORMAT_NAMES
+    }
+
+    /// If `contents` was serialized using a [`DocumentFormat`], returns the
+    /// name of the format recorded in its header and the payload following
+    /// the header. Returns `None` for contents without a header.
+    #[must_use]
+    pub fn split_stored(contents: &[u8]) -> Option<(&'static str, &[u8])> {
+        let (id, payload) = contents.strip_prefix(FORMAT_HEADER)?.split_first()?;
+        FORMAT_NAMES
+            .get(usize::from(*id))
+            .map(|name| (*name, payload))
+    }
+
+    /// Returns the stored contents of `payload`, which was serialized using
+    /// the format named `name`. This is the inverse of
+    /// [`split_stored()`](Self::split_stored). Returns `None` if `name` is not
+    /// one of [`stored_format_names()`](Self::stored_format_names).
+    #[must_use]
+    pub fn join_stored(name: &str, payload: &[u8]) -> Option<Vec<u8>> {
+        let id = FORMAT_NAMES.iter().position(|known| *known == name)?;
+        let mut contents = Vec::with_capacity(FORMAT_HEADER.len() + 1 + payload.len());
+        contents.extend_from_slice(FORMAT_HEADER);
+        contents.push(u8::try_from(id).expect("too many formats"));
+        contents.extend_from_slice(payload);
+        Some(contents)
+    }
+}
+
+impl From<SerializationFormat> for DocumentFormat {
+    fn from(format: SerializationFormat) -> Self {
+        Self::new(format)
+    }
+}
+
+impl<'a, T> transmog::Format<'a, T> for DocumentFormat
+where
+    T: Serialize,
+{
+    type Error = io::Error;
+
+    fn serialize_into<W: Write>(&self, value: &T, mut writer: W) -> Result<(), Self::Error> {
+        writer.write_all(FORMAT_HEADER)?;
+        writer.write_all(&[self.format.id()])?;
+        match self.format {
+            SerializationFormat::Pot => pot::to_writer(value, writer).map_err(invalid_input),
+            #[cfg(feature = "json")]
+            SerializationFormat::Json => {
+                serde_json::to_writer(writer, value).map_err(invalid_input)
+            }
+            #[cfg(feature = "bincode")]
+            SerializationFormat::Bincode => {
+                bincode::serialize_into(writer, value).map_err(invalid_input)
+            }
+            #[cfg(feature = "cbor")]
+            SerializationFormat::Cbor => {
+                ciborium::ser::into_writer(value, writer).map_err(invalid_input)
+            }
+        }
+    }
+}
+
+impl<T> transmog::OwnedDeserializer<T> for DocumentFormat
+where
+    T: DeserializeOwned,
+{
+    fn deserialize_from<R: Read>(&self, mut reader: R) -> Result<T, Self::Error> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        self.deserialize_owned(&buffer)
+    }
+
+    fn deserialize_owned(&self, data: &[u8]) -> Result<T, Self::Error> {
+        match Self::split_stored(data) {
+            Some(("pot", payload)) => pot::from_slice(payload).map_err(invalid_data),
+            Some(("json", payload)) => deserialize_json(payload),
+            Some(("bincode", payload)) => deserialize_bincode(payload),
+            Some(("cbor", payload)) => deserialize_cbor(payload),
+            Some((name, _)) => unreachable!("format {name} not handled"),
+            None if data.starts_with(FORMAT_HEADER) => {
+                Err(invalid_data("document was stored using an unknown format"))
+            }
+            None if data.starts_with(POT_HEADER) => pot::from_slice(data).map_err(invalid_data),
+            None => Err(invalid_data("document is missing its format header")),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn deserialize_json<T: DeserializeOwned>(data: &[u8]) -> io::Result<T> {
+    serde_json::from_slice(data).map_err(invalid_data)
+}
+
+#[cfg(not(feature = "json"))]
+fn deserialize_json<T: DeserializeOwned>(_data: &[u8]) -> io::Result<T> {
+    Err(missing_feature("JSON", "json"))
+}
+
+#[cfg(feature = "bincode")]
+fn deserialize_bincode<T: DeserializeOwned>(data: &[u8]) -> io::Result<T> {
+    bincode::deserialize(data).map_err(invalid_data)
+}
+
+#[cfg(not(feature = "bincode"))]
+fn deserialize_bincode<T: DeserializeOwned>(_data: &[u8]) -> io::Result<T> {
+    Err(missing_feature("Bincode", "bincode"))
+}
+
+#[cfg(feature = "cbor")]
+fn deserialize_cbor<T: DeserializeOwned>(data: &[u8]) -> io::Result<T> {
+    ciborium::de::from_reader(data).map_err(invalid_data)
+}
+
+#[cfg(not(feature = "cbor"))]
+fn deserialize_cbor<T: DeserializeOwned>(_data: &[u8]) -> io::Result<T> {
+    Err(missing_feature("CBOR", "cbor"))
+}
+
+#[allow(dead_code)] // Unused when every optional format is enabled.
+fn missing_feature(format: &str, feature: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::Unsupported,
+        format!("document was stored using {format}, which requires the `{feature}` feature"),
+    )
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn invalid_input(err: impl ToString) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, err.to_string())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn invalid_data(err: impl ToString) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, err.to_string())
+}
+
+#[test]
+fn reads_every_enabled_format() {
+    use transmog::{Format, OwnedDeserializer};
+
+    #[derive(Serialize, serde::Deserialize, Debug, Eq, PartialEq)]
+    struct Contents {
+        value: String,
+    }
+
+    let contents = Contents {
+        value: String::from("hello"),
+    };
+    let mut serialized = vec![pot::to_vec(&contents).unwrap()];
+    for format in [
+        SerializationFormat::Pot,
+        #[cfg(feature = "json")]
+        SerializationFormat::Json,
+        #[cfg(feature = "bincode")]
+        SerializationFormat::Bincode,
+        #[cfg(feature = "cbor")]
+        SerializationFormat::Cbor,
+    ] {
+        serialized.push(DocumentFormat::new(format).serialize(&contents).unwrap());
+    }
+
+    // The format used to serialize doesn't affect deserialization.
+    for data in &serialized {
+        let deserialized: Contents = DocumentFormat::default().deserialize_owned(data).unwrap();
+        assert_eq!(deserialized, contents);
+    }
+
+    #[cfg(feature = "json")]
+    {
+        let stored = DocumentFormat::new(SerializationFormat::Json)
+            .serialize(&contents)
+            .unwrap();
+        let (name, payload) = DocumentFormat::split_stored(&stored).unwrap();
+        assert_eq!(name, SerializationFormat::Json.name());
+        assert_eq!(payload, br#"{"value":"hello"}"#);
+        assert_eq!(DocumentFormat::join_stored(name, payload).unwrap(), stored);
+    }
+
+    // Contents stored with DefaultSerialization have no header.
+    assert!(DocumentFormat::split_stored(&serialized[0]).is_none());
+    // The format is recorded rather than guessed, so a JSON payload without a
+    // header isn't accepted.
+    assert!(
+        <DocumentFormat as OwnedDeserializer<Contents>>::deserialize_owned(
+            &DocumentFormat::default(),
+            br#"{"value":"hello"}"#
+        )
+        .is_err()
+    );
+}
@@ -1,5 +1,6 @@
 use std::any::TypeId;
-use std::collections::{hash_map, HashMap};
+use std::borrow::Cow;
+use std::collections::{hash_map, HashMap, HashSet};
 use std::fmt::Debug;
 use std::marker::PhantomData;
 
@@ -10,9 +11,12 @@ use crate::key::{ByteSource, Key, KeyDescription};
 use crate::schema::collection::Collection;
 use crate::schema::view::map::{self, MappedValue};
 use crate::schema::view::{
-    self, MapReduce, Serialized, SerializedView, ViewSchema, ViewUpdatePolicy,
+    self, AuxiliaryInput, DocumentLookup, MapReduce, ReducePrefixesFn, Serialized, SerializedView,
+    ViewLookup, ViewSchema, ViewUpdatePolicy,
+};
+use crate::schema::{
+    CollectionName, DocumentFormat, Schema, SchemaDescription, SchemaName, View, ViewName,
 };
-use crate::schema::{CollectionName, Schema, SchemaName, View, ViewName};
 use crate::Error;
 
 /// A collection of defined collections and views.
@@ -20,31 +24,93 @@ pub struct Schematic {
     /// The name of the schema this was built from.
     pub name: SchemaName,
     contained_collections: HashMap<CollectionName, KeyDescription>,
+    collection_primary_key_type_names: HashMap<CollectionName, Cow<'static, str>>,
+    collection_sources: HashMap<CollectionName, SchemaName>,
+    included_schemas: HashSet<SchemaName>,
+    defining_schema: SchemaName,
     collections_by_type_id: HashMap<TypeId, CollectionName>,
     collection_encryption_keys: HashMap<CollectionName, KeyId>,
+    collection_max_document_sizes: HashMap<CollectionName, u64>,
+    collection_document_formats: HashMap<CollectionName, DocumentFormat>,
     collection_id_generators: HashMap<CollectionName, Box<dyn IdGenerator>>,
     views: HashMap<TypeId, Box<dyn view::Serialized>>,
     views_by_name: HashMap<ViewName, TypeId>,
     views_by_collection: HashMap<CollectionName, Vec<TypeId>>,
     eager_views_by_collection: HashMap<CollectionName, Vec<TypeId>>,
+    views_by_dependency: HashMap<CollectionName, Vec<TypeId>>,
 }
 
 impl Schematic {
     /// Returns an initialized version from `S`.
     pub fn from_schema<S: Schema + ?Sized>() -> Result<Self, Error> {
-        let mut schematic = Self {
-            name: S::schema_name(),
+        let mut schematic = Self::empty(S::schema_name());
+        S::define_collections(&mut schematic)?;
+        Ok(schematic)
+    }
+
+    /// Returns a schematic named `name` containing `collections` without
+    /// knowing the Rust types that define them.
+    ///
+    /// This allows tools to operate on databases whose schema isn't available
+    /// to them. Because the types are unknown, the returned schematic contains
+    /// no views, each collection's primary key is described as
+    /// [`KeyDescription::Other`], and ids can't be generated: documents can
+    /// only be inserted into these collections with an explicit id.
+    #[must_use]
+    pub fn untyped(
+        name: SchemaName,
+        collections: impl IntoIterator<Item = CollectionName>,
+    ) -> Self {
+        let mut schematic = Self::empty(name);
+        for collection in collections {
+            schematic
+                .collection_sources
+                .insert(collection.clone(), schematic.defining_schema.clone());
+            schematic
+                .contained_collections
+                .insert(collection, KeyDescription::Other(Cow::Borrowed("unknown")));
+        }
+        schematic
+    }
+
+    fn empty(name: SchemaName) -> Self {
+        Self {
             contained_collections: HashMap::new(),
+            collection_primary_key_type_names: HashMap::new(),
+            collection_sources: HashMap::new(),
+            included_schemas: HashSet::from([name.clone()]),
+            defining_schema: name.clone(),
+            name,
             collections_by_type_id: HashMap::new(),
             collection_encryption_keys: HashMap::new(),
+            collection_max_document_sizes: HashMap::new(),
+            collection_document_formats: HashMap::new(),
             collection_id_generators: HashMap::new(),
             views: HashMap::new(),
             views_by_name: HashMap::new(),
             views_by_collection: HashMap::new(),
             eager_views_by_collection: HashMap::new(),
-        };
-        S::define_collections(&mut schematic)?;
-        Ok(schematic)
+            views_by_dependency: HashMap::new(),
+        }
+    }
+
+    /// Adds the collections and views of the schema `S`.
+    ///
+    /// The name of this schematic is unchanged, so databases using the
+    /// composed schema are opened using the outer schema's name. Including
+    /// the same schema more than once has no additional effect. If `S`
+    /// defines a collection that has already been defined by another schema,
+    /// [`Error::CollectionDefinedByMultipleSchemas`] is returned.
+    pub fn extend_with<S: Schema + ?Sized>(&mut self) -> Result<(), Error> {
+        let name = S::schema_name();
+        if !self.included_schemas.insert(name.clone()) {
+            return Ok(());
+        }
+
+        let outer_schema = std::mem::replace(&mut self.defining_schema, name);
+        let result = S::define_collections(self);
+        self.defining_schema = outer_schema;
+        result
     }
 
     /// Adds the collection `C` and its views.
@@ -57,12 +123,33 @@ impl Schematic {
                 if let Some(key) = C::encryption_key() {
                     self.collection_encryption_keys.insert(name.clone(), key);
                 }
+                if let Some(limit) = C::max_document_size() {
+                    self.collection_max_document_sizes
+                        .insert(name.clone(), limit);
+                }
+                if let Some(format) = C::document_format() {
+                    self.collection_document_formats
+                        .insert(name.clone(), format);
+                }
                 self.collection_id_generators
-                    .insert(name, Box::<KeyIdGenerator<C>>::default());
+                    .insert(name.clone(), Box::<KeyIdGenerator<C>>::default());
+                self.collection_primary_key_type_names
+                    .insert(name.clone(), <C::PrimaryKey as Key>::type_name());
+                self.collection_sources
+                    .insert(name, self.defining_schema.clone());
                 entry.insert(KeyDescription::for_key::<C::PrimaryKey>());
                 C::define_views(self)
             }
-            hash_map::Entry::Occupied(_) => Err(Error::CollectionAlreadyDefined),
+            hash_map::Entry::Occupied(_) => match self.collection_sources.get(&name) {
+                Some(source) if source != &self.defining_schema => {
+                    Err(Error::CollectionDefinedByMultipleSchemas {
+                        collection: name,
+                        schema: source.clone(),
+                        conflicting_schema: self.defining_schema.clone(),
+                    })
+                }
+                _ => Err(Error::CollectionAlreadyDefined),
+            },
         }
     }
 
@@ -83,7 +170,16 @@ impl Schematic {
         view: V,
         schema: S,
     ) -> Result<(), Error> {
-        let instance = ViewInstance { view, schema };
+        let dependencies = schema.dependencies();
+        let auxiliary_inputs = schema.auxiliary_inputs();
+        let reduce_prefixes = schema.reduce_prefixes();
+        let instance = ViewInstance {
+            view,
+            schema,
+            dependencies,
+            auxiliary_inputs,
+            reduce_prefixes,
+        };
         let name = instance.view_name();
         if self.views_by_name.contains_key(&name) {
             return Err(Error::ViewAlreadyRegistered(name));
@@ -91,6 +187,13 @@ impl Schematic {
 
         let collection = instance.collection();
         let eager = instance.update_policy().is_eager();
+        for dependency in &instance.dependencies {
+            let views = self
+                .views_by_dependency
+                .entry(dependency.clone())
+                .or_insert_with(Vec::new);
+            views.push(TypeId::of::<V>());
+        }
         self.views.insert(TypeId::of::<V>(), Box::new(instance));
         self.views_by_name.insert(name, TypeId::of::<V>());
 
@@ -126,6 +229,16 @@ impl Schematic {
         self.contained_collections.get(collection)
     }
 
+    /// Returns the result of [`Key::type_name()`] for the primary key of the
+    /// collection with the given name, or `None` if the collection can't be
+    /// found.
+    #[must_use]
+    pub fn collection_primary_key_type_name(&self, collection: &CollectionName) -> Option<&str> {
+        self.collection_primary_key_type_names
+            .get(collection)
+            .map(AsRef::as_ref)
+    }
+
     /// Returns the next id in sequence for the collection, if the primary key
     /// type supports the operation and the next id would not overflow.
     pub fn next_id_for_collection(
@@ -193,16 +306,57 @@ impl Schematic {
             })
     }
 
+    /// Iterates over all views that declare `collection` as one of their
+    /// [dependencies](ViewSchema::dependencies).
+    pub fn views_depending_on(
+        &self,
+        collection: &CollectionName,
+    ) -> impl Iterator<Item = &'_ dyn view::Serialized> {
+        self.views_by_dependency
+            .get(collection)
+            .into_iter()
+            .flat_map(|view_ids| {
+                view_ids
+                    .iter()
+                    .filter_map(|id| self.views.get(id).map(AsRef::as_ref))
+            })
+    }
+
     /// Returns a collection's default encryption key, if one was defined.
     #[must_use]
     pub fn encryption_key_for_collection(&self, collection: &CollectionName) -> Option<&KeyId> {
         self.collection_encryption_keys.get(collection)
     }
 
+    /// Returns the maximum document size defined by a collection, if one was
+    /// defined. See [`Collection::max_document_size()`].
+    #[must_use]
+    pub fn max_document_size_for_collection(&self, collection: &CollectionName) -> Option<u64> {
+        self.collection_max_document_sizes.get(collection).copied()
+    }
+
+    /// Returns the format a collection's documents are serialized using, if
+    /// the collection defined one. See [`Collection::document_format()`].
+    #[must_use]
+    pub fn document_format_for_collection(
+        &self,
+        collection: &CollectionName,
+    ) -> Option<DocumentFormat> {
+        self.collection_document_formats.get(collection).copied()
+    }
+
     /// Returns a list of all collections contained in this schematic.
     pub fn collections(&self) -> impl Iterator<Item = &CollectionName> {
         self.contained_collections.keys()
     }
+
+    /// Returns a machine-readable description of this schematic's collections
+    /// and views. Describing the same schema always produces the same
+    /// description, making it suitable for detecting schema changes.
+    #[must_use]
+    pub fn description(&self) -> SchemaDescription {
+        SchemaDescription::from(self)
+    }
 }
 
 impl Debug for Schematic {
@@ -217,16 +371,31 @@ impl Debug for Schematic {
         f.debug_struct("Schematic")
             .field("name", &self.name)
             .field("contained_collections", &self.contained_collections)
+            .field(
+                "collection_primary_key_type_names",
+                &self.collection_primary_key_type_names,
+            )
+            .field("collection_sources", &self.collection_sources)
+            .field("included_schemas", &self.included_schemas)
             .field("collections_by_type_id", &self.collections_by_type_id)
             .field(
                 "collection_encryption_keys",
                 &self.collection_encryption_keys,
             )
+            .field(
+                "collection_max_document_sizes",
+                &self.collection_max_document_sizes,
+            )
+            .field(
+                "collection_document_formats",
+                &self.collection_document_formats,
+            )
             .field("collection_id_generators", &self.collection_id_generators)
             .field("views", &views)
             .field("views_by_name", &self.views_by_name)
             .field("views_by_collection", &self.views_by_collection)
             .field("eager_views_by_collection", &self.eager_views_by_collection)
+            .field("views_by_dependency", &self.views_by_dependency)
             .finish()
     }
 }
@@ -235,6 +404,9 @@ impl Debug for Schematic {
 struct ViewInstance<V, S> {
     view: V,
     schema: S,
+    dependencies: Vec<CollectionName>,
+    auxiliary_inputs: Vec<AuxiliaryInput>,
+    reduce_prefixes: Option<ReducePrefixesFn<V>>,
 }
 
 impl<V, S> Serialized for ViewInstance<V, S>
@@ -250,6 +422,10 @@ where
         KeyDescription::for_key::<<V as View>::Key>()
     }
 
+    fn key_type_name(&self) -> Cow<'static, str> {
+        <<V as View>::Key as Key>::type_name()
+    }
+
     fn update_policy(&self) -> ViewUpdatePolicy {
         self.schema.update_policy()
     }
@@ -258,12 +434,39 @@ where
         self.schema.version()
     }
 
+    fn dependencies(&self) -> &[CollectionName] {
+        &self.dependencies
+    }
+
+    fn auxiliary_inputs(&self) -> &[AuxiliaryInput] {
+        &self.auxiliary_inputs
+    }
+
+    fn caches_reduce_prefixes(&self) -> bool {
+        self.reduce_prefixes.is_some()
+    }
+
+    fn reduce_prefixes(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, view::Error> {
+        let Some(reduce_prefixes) = self.reduce_prefixes else {
+            return Ok(Vec::new());
+        };
+        let key = <<V as View>::Key as Key>::from_ord_bytes(ByteSource::Borrowed(key))
+            .map_err(view::Error::key_serialization)?;
+        reduce_prefixes(&key).map_err(view::Error::key_serialization)
+    }
+
     fn view_name(&self) -> ViewName {
         self.view.view_name()
     }
 
-    fn map(&self, document: &BorrowedDocument<'_>) -> Result<Vec<map::Serialized>, view::Error> {
-        let mappings = self.schema.map(document)?;
+    fn map(
+        &self,
+        document: &BorrowedDocument<'_>,
+        documents: &dyn DocumentLookup,
+    ) -> Result<Vec<map::Serialized>, view::Error> {
+        let lookup = ViewLookup::new(self.view_name(), &self.dependencies, documents)
+            .with_auxiliary_inputs(&self.auxiliary_inputs);
+        let mappings = self.schema.map_with_lookup(document, &lookup)?;
 
         mappings
             .iter()
@@ -336,3 +539,46 @@ fn schema_tests() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn extend_with_tests() -> anyhow::Result<()> {
+    use crate::test_util::{
+        Basic, BasicByParentId, BasicCollectionWithNoViews, BasicSchema, Unique,
+    };
+
+    #[derive(Debug, crate::schema::Schema)]
+    #[schema(name = "composed", collections = [Composed], include = [BasicSchema, BasicSchema], core = crate)]
+    struct ComposedSchema;
+
+    #[derive(Debug, crate::schema::Collection)]
+    #[collection(name = "composed", core = crate)]
+    struct Composed;
+
+    let schema = Schematic::from_schema::<ComposedSchema>()?;
+    assert_eq!(schema.name, ComposedSchema::schema_name());
+    assert!(schema.contains_collection::<Composed>());
+    assert!(schema.contains_collection::<Basic>());
+    assert!(schema.contains_collection::<Unique>());
+    assert!(schema.view::<BasicByParentId>().is_ok());
+
+    #[derive(Debug, crate::schema::Schema)]
+    #[schema(name = "conflicting", collections = [BasicCollectionWithNoViews], include = [BasicSchema], core = crate)]
+    struct ConflictingSchema;
+
+    let err = Schematic::from_schema::<ConflictingSchema>().unwrap_err();
+    assert!(matches!(
+        &err,
+        Error::CollectionDefinedByMultipleSchemas {
+            collection,
+            schema,
+            conflicting_schema,
+        } if collection == &Basic::collection_name()
+            && schema == &ConflictingSchema::schema_name()
+            && conflicting_schema == &BasicSchema::schema_name()
+    ));
+    let message = err.to_string();
+    assert!(message.contains(&ConflictingSchema::schema_name().to_string()));
+    assert!(message.contains(&BasicSchema::schema_name().to_string()));
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::fmt::Debug;
 
 use serde::de::DeserializeOwned;
@@ -6,8 +7,8 @@ use transmog::{Format, OwnedDeserializer};
 use transmog_pot::Pot;
 
 use crate::connection::{self, AsyncConnection, Connection};
-use crate::document::{BorrowedDocument, CollectionDocument};
-use crate::key::{ByteSource, Key, KeyDescription};
+use crate::document::{BorrowedDocument, CollectionDocument, DocumentId, OwnedDocument};
+use crate::key::{ByteSource, CompositeKeyError, Key, KeyDescription, KeyEncoding};
 use crate::schema::view::map::{MappedValue, Mappings, ViewMappedValue};
 use crate::schema::{Collection, CollectionName, Name, SerializedCollection, ViewName};
 use crate::AnyError;
@@ -15,6 +16,10 @@ use crate::AnyError;
 /// Types for defining a `Map` within a `View`.
 pub mod map;
 
+mod auxiliary;
+
+pub use self::auxiliary::{AuxiliaryData, AuxiliaryEntries, AuxiliaryInput, AuxiliarySource};
+
 /// Errors that arise when interacting with views.
 #[derive(thiserror::Error, Debug)]
 // TODO add which view name and collection
@@ -50,6 +55,10 @@ pub type ViewMapResult<'doc, V> = Result<
 /// A type alias for the result of `ViewSchema::reduce()`.
 pub type ReduceResult<V> = Result<<V as View>::Value, crate::Error>;
 
+/// A function returning the prefixes of a key whose reductions are cached. See
+/// [`ViewSchema::reduce_prefixes()`].
+pub type ReducePrefixesFn<V> = fn(&<V as View>::Key) -> Result<Vec<Vec<u8>>, CompositeKeyError>;
+
 /// An lazy index of mapped and/or reduced data from a [`Collection`].
 ///
 /// A view provides an efficient way to query data within a collection. BonsaiDb
@@ -196,6 +205,66 @@ pub trait ViewSchema: Send + Sync + 'static {
     fn version(&self) -> u64 {
         0
     }
+
+    /// Returns the collections that this view's map function looks up
+    /// documents from using [`ViewLookup`]. The provided implementation
+    /// returns no dependencies.
+    ///
+    /// Whenever a document in any of these collections is inserted, updated,
+    /// or deleted, the documents in the view's collection that looked it up
+    /// are invalidated and will be mapped again the next time the view is
+    /// updated. This happens for all update policies: changes to a dependency
+    /// are always applied lazily, and for [`ViewUpdatePolicy::Unique`] views,
+    /// a uniqueness violation caused by a change to a dependency is returned
+    /// from the next query rather than from the transaction that changed the
+    /// dependency.
+    ///
+    /// Querying the view requires permission to get documents from each of
+    /// these collections.
+    fn dependencies(&self) -> Vec<CollectionName> {
+        Vec::new()
+    }
+
+    /// Returns the named auxiliary inputs that this view's map function reads
+    /// using [`ViewLookup::auxiliary()`]. The provided implementation returns
+    /// no inputs.
+    ///
+    /// Before each batch of documents is mapped, a read-only snapshot of every
+    /// input is captured, ensuring every document in the batch is mapped using
+    /// the same data. Whenever the contents of an input change, the view's
+    /// effective version changes, causing every document in the view's
+    /// collection to be mapped again the next time the view is queried.
+    ///
+    /// Each input is loaded in its entirety each time the view is queried or
+    /// updated, so inputs are best suited for small lookup tables.
+    fn auxiliary_inputs(&self) -> Vec<AuxiliaryInput> {
+        Vec::new()
+    }
+
+    /// Returns the function used to find the prefixes of this view's keys
+    /// whose reductions are cached. The provided implementation returns
+    /// `None`, disabling the cache.
+    ///
+    /// For views whose keys are tuples, such as `(year, month, day)`,
+    /// [`KeyPrefixes::encoded_prefixes`](crate::key::KeyPrefixes) returns
+    /// each group of leading fields. The reduced value of each prefix is
+    /// stored and kept up to date as the view is updated, and reducing a range
+    /// of keys uses the stored values of the prefixes entirely contained in
+    /// the range rather than reducing each key's value individually. A reduce
+    /// query for a month of a `(year, month, day)` view reads a single value
+    /// instead of one value per day.
+    ///
+    /// Each time a key is changed, the reductions of each of its prefixes are
+    /// recomputed. The longest prefix is reduced from the keys sharing it, and
+    /// each shorter prefix from the cached reductions of the longer prefixes
+    /// it contains, which makes updating the view more expensive. Because caching requires
+    /// re-reducing previously reduced values, the view's `reduce()` function
+    /// must produce the same result regardless of how its values are grouped.
+    /// The [`version()`](Self::version) of the view must be changed when this
+    /// function's result changes.
+    fn reduce_prefixes(&self) -> Option<ReducePrefixesFn<Self::View>> {
+        None
+    }
 }
 
 /// The policy under which a [`View`] is updated when documents are saved.
@@ -248,6 +317,20 @@ pub trait MapReduce: ViewSchema {
     /// works](https://dev.bonsaidb.io/main/guide/about/concepts/view.html#map).
     fn map<'doc>(&self, document: &'doc BorrowedDocument<'_>) -> ViewMapResult<'doc, Self>;
 
+    /// The map function for views that look up related documents from the
+    /// view's [dependencies](ViewSchema::dependencies) while mapping. This is
+    /// the function invoked when the view is updated, so `map()` is never
+    /// called for views that override it. The provided implementation calls
+    /// [`MapReduce::map()`].
+    #[allow(unused_variables)]
+    fn map_with_lookup<'doc>(
+        &self,
+        document: &'doc BorrowedDocument<'_>,
+        lookup: &ViewLookup<'_>,
+    ) -> ViewMapResult<'doc, Self> {
+        self.map(document)
+    }
+
     /// Returns a value that is produced by reducing a list of `mappings` into a
     /// single value. If `rereduce` is true, the values contained in the
     /// mappings have already been reduced at least one time. If an error of
@@ -338,6 +421,23 @@ where
     where
         CollectionDocument<<Self::View as View>::Collection>: 'doc;
 
+    /// The map function for views that look up related documents from the
+    /// view's [dependencies](ViewSchema::dependencies) while mapping. This is
+    /// the function invoked when the view is updated, so `map()` is never
+    /// called for views that override it. The provided implementation calls
+    /// [`CollectionMapReduce::map()`].
+    #[allow(unused_variables)]
+    fn map_with_lookup<'doc>(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+        lookup: &ViewLookup<'_>,
+    ) -> ViewMapResult<'doc, Self>
+    where
+        CollectionDocument<<Self::View as View>::Collection>: 'doc,
+    {
+        CollectionMapReduce::map(self, document)
+    }
+
     /// The reduce function for this view. If `Err(Error::ReduceUnimplemented)`
     /// is returned, queries that ask for a reduce operation will return an
     /// error. See [`CouchDB`'s Reduce/Rereduce
@@ -363,6 +463,14 @@ where
         T::map(self, CollectionDocument::try_from(document)?)
     }
 
+    fn map_with_lookup<'doc>(
+        &self,
+        document: &'doc BorrowedDocument<'_>,
+        lookup: &ViewLookup<'_>,
+    ) -> ViewMapResult<'doc, Self> {
+        T::map_with_lookup(self, CollectionDocument::try_from(document)?, lookup)
+    }
+
     fn reduce(
         &self,
         mappings: &[ViewMappedValue<'_, Self>],
@@ -372,21 +480,145 @@ where
     }
 }
 
+/// Retrieves documents on behalf of a [`ViewLookup`].
+pub trait DocumentLookup: Send + Sync {
+    /// Returns the document with `id` from `collection`, if it exists.
+    fn get(
+        &self,
+        collection: &CollectionName,
+        id: &DocumentId,
+    ) -> Result<Option<OwnedDocument>, crate::Error>;
+
+    /// Returns the snapshot of the [auxiliary
+    /// inputs](ViewSchema::auxiliary_inputs) of the view being mapped, if one
+    /// was captured. The provided implementation returns `None`.
+    fn auxiliary_data(&self) -> Option<&AuxiliaryData> {
+        None
+    }
+}
+
+/// Provides access to the documents of a view's
+/// [dependencies](ViewSchema::dependencies) from within
+/// [`MapReduce::map_with_lookup()`].
+///
+/// Documents are read from the most recently committed state of the
+/// database. Looking up a document from a collection that is not a dependency
+/// of the view returns [`Error::ViewDependencyNotDeclared`](crate::Error::ViewDependencyNotDeclared).
+pub struct ViewLookup<'a> {
+    view: ViewName,
+    dependencies: &'a [CollectionName],
+    auxiliary_inputs: &'a [AuxiliaryInput],
+    documents: &'a dyn DocumentLookup,
+}
+
+impl<'a> ViewLookup<'a> {
+    /// Returns a lookup for `view` that retrieves documents in
+    /// `dependencies` from `documents`.
+    #[must_use]
+    pub fn new(
+        view: ViewName,
+        dependencies: &'a [CollectionName],
+        documents: &'a dyn DocumentLookup,
+    ) -> Self {
+        Self {
+            view,
+            dependencies,
+            auxiliary_inputs: &[],
+            documents,
+        }
+    }
+
+    /// Allows the [auxiliary inputs](ViewSchema::auxiliary_inputs) in
+    /// `auxiliary_inputs` to be read from this lookup and returns self.
+    #[must_use]
+    pub const fn with_auxiliary_inputs(mut self, auxiliary_inputs: &'a [AuxiliaryInput]) -> Self {
+        self.auxiliary_inputs = auxiliary_inputs;
+        self
+    }
+
+    /// Returns the document with `id` from the collection `C`, if it exists.
+    pub fn get<C, PrimaryKey>(
+        &self,
+        id: &PrimaryKey,
+    ) -> Result<Option<CollectionDocument<C>>, crate::Error>
+    where
+        C: SerializedCollection,
+        PrimaryKey: KeyEncoding<C::PrimaryKey> + ?Sized,
+    {
+        let document = self.get_from_collection(&C::collection_name(), &DocumentId::new(id)?)?;
+        document.as_ref().map(TryInto::try_into).transpose()
+    }
+
+    /// Returns the document with `id` from `collection`, if it exists.
+    pub fn get_from_collection(
+        &self,
+        collection: &CollectionName,
+        id: &DocumentId,
+    ) -> Result<Option<OwnedDocument>, crate::Error> {
+        if !self.dependencies.contains(collection) {
+            return Err(crate::Error::ViewDependencyNotDeclared {
+                view: self.view.clone(),
+                collection: collection.clone(),
+            });
+        }
+
+        self.documents.get(collection, id)
+    }
+
+    /// Returns the entries of the [auxiliary
+    /// input](ViewSchema::auxiliary_inputs) named `name`.
+    ///
+    /// Every document mapped in the same batch observes the same entries.
+    /// Reading an input that the view didn't declare returns
+    /// [`Error::ViewAuxiliaryInputNotDeclared`](crate::Error::ViewAuxiliaryInputNotDeclared).
+    pub fn auxiliary(&self, name: &str) -> Result<&AuxiliaryEntries, crate::Error> {
+        if self.auxiliary_inputs.iter().any(|input| input.name == name) {
+            if let Some(entries) = self
+                .documents
+                .auxiliary_data()
+                .and_then(|data| data.get(name))
+            {
+                return Ok(entries);
+            }
+        }
+
+        Err(crate::Error::ViewAuxiliaryInputNotDeclared {
+            view: self.view.clone(),
+            name: name.to_owned(),
+        })
+    }
+}
+
 /// Wraps a [`View`] with serialization to erase the associated types
 pub trait Serialized: Send + Sync {
     /// Wraps returing [`<View::Collection as Collection>::collection_name()`](crate::schema::Collection::collection_name)
     fn collection(&self) -> CollectionName;
     /// Returns the description of the view's `Key`.
     fn key_description(&self) -> KeyDescription;
+    /// Wraps [`Key::type_name`] for the view's `Key`.
+    fn key_type_name(&self) -> Cow<'static, str>;
     /// Wraps [`ViewSchema::update_policy`]
     fn update_policy(&self) -> ViewUpdatePolicy;
 
     /// Wraps [`ViewSchema::version`]
     fn version(&self) -> u64;
+    /// Wraps [`ViewSchema::dependencies`]
+    fn dependencies(&self) -> &[CollectionName];
+    /// Wraps [`ViewSchema::auxiliary_inputs`]
+    fn auxiliary_inputs(&self) -> &[AuxiliaryInput];
+    /// Returns true if [`ViewSchema::reduce_prefixes`] returns a function.
+    fn caches_reduce_prefixes(&self) -> bool;
+    /// Returns the prefixes of the serialized `key` whose reductions are
+    /// cached. See [`ViewSchema::reduce_prefixes`].
+    fn reduce_prefixes(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, Error>;
     /// Wraps [`View::view_name`]
     fn view_name(&self) -> ViewName;
-    /// Wraps [`MapReduce::map`]
-    fn map(&self, document: &BorrowedDocument<'_>) -> Result<Vec<map::Serialized>, Error>;
+    /// Wraps [`MapReduce::map_with_lookup`]
+    fn map(
+        &self,
+        document: &BorrowedDocument<'_>,
+        documents: &dyn DocumentLookup,
+    ) -> Result<Vec<map::Serialized>, Error>;
     /// Wraps [`MapReduce::reduce`]
     fn reduce(&self, mappings: &[(&[u8], &[u8])], rereduce: bool) -> Result<Vec<u8>, Error>;
 }
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::document::{CollectionDocument, DocumentId, OwnedDocument};
+use crate::key::KeyEncoding;
+use crate::keyvalue::{Numeric, Value};
+use crate::schema::{Collection, CollectionName, SerializedCollection};
+
+/// A named input to a view's map function, declared using
+/// [`ViewSchema::auxiliary_inputs()`](super::ViewSchema::auxiliary_inputs).
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct AuxiliaryInput {
+    /// The name the input is accessed by from within the map function.
+    pub name: String,
+    /// The source the input is loaded from.
+    pub source: AuxiliarySource,
+}
+
+impl AuxiliaryInput {
+    /// Returns an input named `name` containing every document in the
+    /// collection `C`.
+    #[must_use]
+    pub fn collection<C: Collection>(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            source: AuxiliarySource::Collection(C::collection_name()),
+        }
+    }
+
+    /// Returns an input named `name` containing every key in the key-value
+    /// `namespace`. A namespace of `None` is the default namespace.
+    #[must_use]
+    pub fn key_value(name: impl Into<String>, namespace: Option<&str>) -> Self {
+        Self {
+            name: name.into(),
+            source: AuxiliarySource::KeyValue {
+                namespace: namespace.map(ToOwned::to_owned),
+            },
+        }
+    }
+}
+
+/// The source of an [`AuxiliaryInput`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum AuxiliarySource {
+    /// Every document in a collection.
+    Collection(CollectionName),
+    /// Every key in a key-value namespace.
+    KeyValue {
+        /// The namespace of the keys. `None` is the default namespace.
+        namespace: Option<String>,
+    },
+}
+
+/// The contents of an [`AuxiliaryInput`].
+#[derive(Clone, Debug)]
+pub enum AuxiliaryEntries {
+    /// The documents of an [`AuxiliarySource::Collection`].
+    Documents(BTreeMap<DocumentId, OwnedDocument>),
+    /// The values of an [`AuxiliarySource::KeyValue`] namespace.
+    KeyValue(BTreeMap<String, Value>),
+}
+
+impl AuxiliaryEntries {
+    /// Returns the document with `id` from the collection `C`, if it exists.
+    /// Returns `None` if these entries are from a key-value namespace.
+    pub fn document<C, PrimaryKey>(
+        &self,
+        id: &PrimaryKey,
+    ) -> Result<Option<CollectionDocument<C>>, crate::Error>
+    where
+        C: SerializedCollection,
+        PrimaryKey: KeyEncoding<C::PrimaryKey> + ?Sized,
+    {
+        match self {
+            Self::Documents(documents) => documents
+                .get(&DocumentId::new(id)?)
+                .map(TryInto::try_into)
+                .transpose(),
+            Self::KeyValue(_) => Ok(None),
+        }
+    }
+
+    /// Returns every document, deserialized as the collection `C`. Returns an
+    /// empty list if these entries are from a key-value namespace.
+    pub fn documents<C: SerializedCollection>(
+        &self,
+    ) -> Result<Vec<CollectionDocument<C>>, crate::Error> {
+        match self {
+            Self::Documents(documents) => documents.values().map(TryInto::try_into).collect(),
+            Self::KeyValue(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the value stored for `key`, if it exists. Returns `None` if
+    /// these entries are from a collection.
+    #[must_use]
+    pub fn value(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Documents(_) => None,
+            Self::KeyValue(values) => values.get(key),
+        }
+    }
+}
+
+/// A read-only snapshot of a view's [auxiliary
+/// inputs](super::ViewSchema::auxiliary_inputs), captured before a batch of
+/// documents is mapped.
+#[derive(Clone, Debug, Default)]
+pub struct AuxiliaryData {
+    inputs: BTreeMap<String, AuxiliaryEntries>,
+    version: u64,
+}
+
+impl AuxiliaryData {
+    /// Returns a snapshot of `inputs`, keyed by the input's name.
+    #[must_use]
+    pub fn new(inputs: BTreeMap<String, AuxiliaryEntries>) -> Self {
+        let mut digest = Sha256::new();
+        for (name, entries) in &inputs {
+            hash_bytes(&mut digest, name.as_bytes());
+            match entries {
+                AuxiliaryEntries::Documents(documents) => {
+                    digest.update([0]);
+                    for (id, document) in documents {
+                        hash_bytes(&mut digest, id.as_ref());
+                        digest.update(document.header.revision.id.to_le_bytes());
+                        digest.update(document.header.revision.sha256);
+                    }
+                }
+                AuxiliaryEntries::KeyValue(values) => {
+                    digest.update([1]);
+                    for (key, value) in values {
+                        hash_bytes(&mut digest, key.as_bytes());
+                        hash_value(&mut digest, value);
+                    }
+                }
+            }
+        }
+        let digest = digest.finalize();
+        let version = u64::from_le_bytes(digest[..8].try_into().expect("digest is 32 bytes"));
+
+        Self { inputs, version }
+    }
+
+    /// Returns a value that changes whenever the contents of this snapshot
+    /// change.
+    #[must_use]
+    pub const fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the entries of the input named `name`, if it was captured.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&AuxiliaryEntries> {
+        self.inputs.get(name)
+    }
+}
+
+fn hash_bytes(digest: &mut Sha256, bytes: &[u8]) {
+    digest.update((bytes.len() as u64).to_le_bytes());
+    digest.update(bytes);
+}
+
+fn hash_value(digest: &mut Sha256, value: &Value) {
+    match value {
+        Value::Bytes(bytes) => {
+            digest.update([0]);
+            hash_bytes(digest, bytes);
+        }
+        Value::Numeric(Numeric::Integer(value)) => {
+            digest.update([1]);
+            digest.update(value.to_le_bytes());
+        }
+        Value::Numeric(Numeric::UnsignedInteger(value)) => {
+            digest.update([2]);
+            digest.update(value.to_le_bytes());
+        }
+        Value::Numeric(Numeric::Float(value)) => {
+            digest.update([3]);
+            digest.update(value.to_le_bytes());
+        }
+        Value::Numeric(Numeric::Integer128(value)) => {
+            digest.update([4]);
+            digest.update(value.to_le_bytes());
+        }
+        Value::Numeric(Numeric::UnsignedInteger128(value)) => {
+            digest.update([5]);
+            digest.update(value.to_le_bytes());
+        }
+    }
+}
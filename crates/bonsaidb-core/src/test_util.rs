@@ -22,6 +22,7 @@ use crate::document::{
 };
 use crate::keyvalue::{AsyncKeyValue, KeyValue};
 use crate::limits::{LIST_TRANSACTIONS_DEFAULT_RESULT_COUNT, LIST_TRANSACTIONS_MAX_RESULTS};
+use crate::pubsub::Topic;
 use crate::schema::view::map::{Mappings, ViewMappedValue};
 use crate::schema::view::{MapReduce, ReduceResult, SerializedView, ViewUpdatePolicy};
 use crate::schema::{
@@ -365,6 +366,11 @@ impl MapReduce for UniqueValue {
 
 impl NamedCollection for Unique {
     type ByNameView = UniqueValue;
+
+    fn rename_contents(contents: &mut Self, name: &str) -> Result<(), Error> {
+        contents.value = name.to_string();
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -455,6 +461,12 @@ impl Collection for BasicCollectionWithOnlyBrokenParentId {
 #[collection(name = "unassociated", authority = "khonsulabs", core = crate)]
 pub struct UnassociatedCollection;
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Topic)]
+#[topic(name = "basic-topic", authority = "khonsulabs", core = crate)]
+pub struct BasicTopic {
+    pub value: String,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum HarnessTest {
     ServerConnectionTests = 1,
@@ -480,6 +492,7 @@ pub enum HarnessTest {
     Encryption,
     UniqueViews,
     NamedCollection,
+    NamedDocuments,
     PubSubSimple,
     UserManagement,
     TokenAuthentication,
@@ -495,6 +508,9 @@ pub enum HarnessTest {
     KvExpiration,
     KvDeleteExpire,
     KvTransactions,
+    KvSwap,
+    PubSubTypedTopic,
+    KvDeleteIfEquals,
 }
 
 impl HarnessTest {
@@ -716,6 +732,15 @@ macro_rules! define_async_connection_test_suite {
                 harness.shutdown().await
             }
 
+            #[tokio::test]
+            async fn named_documents() -> anyhow::Result<()> {
+                let harness = $harness::new($crate::test_util::HarnessTest::NamedDocuments).await?;
+                let db = harness.connect().await?;
+
+                $crate::test_util::named_document_tests(&db).await?;
+                harness.shutdown().await
+            }
+
             #[tokio::test]
             async fn user_management() -> anyhow::Result<()> {
                 use $crate::connection::AsyncStorageConnection;
@@ -965,6 +990,15 @@ macro_rules! define_blocking_connection_test_suite {
                 harness.shutdown()
             }
 
+            #[test]
+            fn named_documents() -> anyhow::Result<()> {
+                let harness = $harness::new($crate::test_util::HarnessTest::NamedDocuments)?;
+                let db = harness.connect()?;
+
+                $crate::test_util::blocking_named_document_tests(&db)?;
+                harness.shutdown()
+            }
+
             #[test]
             fn user_management() -> anyhow::Result<()> {
                 use $crate::connection::StorageConnection;
@@ -1492,12 +1526,32 @@ pub fn blocking_get_multiple_tests<C: Connection>(db: &C) -> anyhow::Result<()>
 
 pub async fn list_tests<C: AsyncConnection>(db: &C) -> anyhow::Result<()> {
     let collection = db.collection::<Basic>();
+    assert!(collection.first().await?.is_none());
+    assert!(db.last::<Basic>().await?.is_none());
+
     let doc1_value = Basic::new("initial_value");
     let doc1 = collection.push(&doc1_value).await?;
 
     let doc2_value = Basic::new("second_value");
     let doc2 = collection.push(&doc2_value).await?;
 
+    let first = db.first::<Basic>().await?.expect("first document missing");
+    assert_eq!(first.header.id, doc1.id);
+    assert_eq!(first.contents.value, doc1_value.value);
+    let last = db.last::<Basic>().await?.expect("last document missing");
+    assert_eq!(last.header.id, doc2.id);
+    assert_eq!(last.contents.value, doc2_value.value);
+    assert_eq!(
+        collection
+            .last()
+            .await?
+            .unwrap()
+            .header
+            .id
+            .deserialize::<u64>()?,
+        doc2.id
+    );
+
     let all_docs = Basic::all_async(db).await?;
     assert_eq!(all_docs.len(), 2);
     assert_eq!(Basic::all_async(db).count().await?, 2);
@@ -1528,12 +1582,26 @@ pub async fn list_tests<C: AsyncConnection>(db: &C) -> anyhow::Result<()> {
 
 pub fn blocking_list_tests<C: Connection>(db: &C) -> anyhow::Result<()> {
     let collection = db.collection::<Basic>();
+    assert!(collection.first()?.is_none());
+    assert!(db.last::<Basic>()?.is_none());
+
     let doc1_value = Basic::new("initial_value");
     let doc1 = collection.push(&doc1_value)?;
 
     let doc2_value = Basic::new("second_value");
     let doc2 = collection.push(&doc2_value)?;
 
+    let first = db.first::<Basic>()?.expect("first document missing");
+    assert_eq!(first.header.id, doc1.id);
+    assert_eq!(first.contents.value, doc1_value.value);
+    let last = db.last::<Basic>()?.expect("last document missing");
+    assert_eq!(last.header.id, doc2.id);
+    assert_eq!(last.contents.value, doc2_value.value);
+    assert_eq!(
+        collection.last()?.unwrap().header.id.deserialize::<u64>()?,
+        doc2.id
+    );
+
     let all_docs = Basic::all(db).query()?;
     assert_eq!(all_docs.len(), 2);
     assert_eq!(Basic::all(db).count()?, 2);
@@ -1573,6 +1641,11 @@ pub async fn list_transactions_tests<C: AsyncConnection>(db: &C) -> anyhow::Resu
     )
     .await;
 
+    // The connection tracks the transactions it applied.
+    let last_seen = db.last_seen_transaction_id();
+    assert!(last_seen.is_some());
+    assert_eq!(last_seen, db.last_transaction_id().await?);
+
     // Test defaults
     let transactions = db.list_executed_transactions(None, None).await?;
     assert_eq!(
@@ -1641,6 +1714,11 @@ pub fn blocking_list_transactions_tests<C: Connection + Clone + 'static>(
         thread.join().unwrap();
     }
 
+    // The connection tracks the transactions it applied.
+    let last_seen = db.last_seen_transaction_id();
+    assert!(last_seen.is_some());
+    assert_eq!(last_seen, db.last_transaction_id()?);
+
     // Test defaults
     let transactions = db.list_executed_transactions(None, None)?;
     assert_eq!(
@@ -1908,6 +1986,19 @@ pub async fn view_query_tests<C: AsyncConnection>(db: &C) -> anyhow::Result<()>
     let items_with_categories = db.view::<BasicByCategory>().query().await?;
     assert_eq!(items_with_categories.len(), 3);
 
+    // Test updating
+    let updated = db
+        .view::<BasicByParentId>()
+        .with_key(&Some(b.id))
+        .update_docs(|document| document.contents.category = Some(String::from("updated")))
+        .await?;
+    assert_eq!(updated.len(), b_children.len());
+    for header in &updated {
+        let document = Basic::get_async(&header.id, db).await?.unwrap();
+        assert_eq!(&document.header, header);
+        assert_eq!(document.contents.category.as_deref(), Some("updated"));
+    }
+
     // Test deleting
     let deleted_count = db
         .view::<BasicByParentId>()
@@ -1924,6 +2015,36 @@ pub async fn view_query_tests<C: AsyncConnection>(db: &C) -> anyhow::Result<()>
         0
     );
 
+    // Test deleting while returning the deleted headers
+    let deleted = db
+        .view::<BasicByParentId>()
+        .with_key(&Some(a.id))
+        .delete_docs_returning_headers()
+        .await?;
+    assert_eq!(deleted.count, 1);
+    assert_eq!(deleted.headers, Some(vec![Header::try_from(a_child)?]));
+    let transaction_id = deleted.transaction_id.expect("no transaction id");
+    let transactions = db
+        .list_executed_transactions(Some(transaction_id), Some(1))
+        .await?;
+    assert_eq!(transactions[0].id, transaction_id);
+    let changes = transactions[0]
+        .changes
+        .documents()
+        .expect("incorrect transaction type");
+    assert_eq!(changes.documents.len(), 1);
+    assert_eq!(changes.documents[0].id, DocumentId::from_u64(a_child.id));
+    assert!(changes.documents[0].deleted);
+
+    let deleted = db
+        .view::<BasicByParentId>()
+        .with_key(&Some(a.id))
+        .delete_docs_returning_headers()
+        .await?;
+    assert_eq!(deleted.count, 0);
+    assert_eq!(deleted.transaction_id, None);
+    assert_eq!(deleted.headers, Some(Vec::new()));
+
     Ok(())
 }
 
@@ -1988,6 +2109,18 @@ pub fn blocking_view_query_tests<C: Connection>(db: &C) -> anyhow::Result<()> {
     let items_with_categories = db.view::<BasicByCategory>().query()?;
     assert_eq!(items_with_categories.len(), 3);
 
+    // Test updating
+    let updated = db
+        .view::<BasicByParentId>()
+        .with_key(&Some(b.id))
+        .update_docs(|document| document.contents.category = Some(String::from("updated")))?;
+    assert_eq!(updated.len(), b_children.len());
+    for header in &updated {
+        let document = Basic::get(&header.id, db)?.unwrap();
+        assert_eq!(&document.header, header);
+        assert_eq!(document.contents.category.as_deref(), Some("updated"));
+    }
+
     // Test deleting
     let deleted_count = db
         .view::<BasicByParentId>()
@@ -2002,6 +2135,24 @@ pub fn blocking_view_query_tests<C: Connection>(db: &C) -> anyhow::Result<()> {
         0
     );
 
+    // Test deleting while returning the deleted headers
+    let deleted = db
+        .view::<BasicByParentId>()
+        .with_key(&Some(a.id))
+        .delete_docs_returning_headers()?;
+    assert_eq!(deleted.count, 1);
+    assert_eq!(deleted.headers, Some(vec![Header::try_from(a_child)?]));
+    let transaction_id = deleted.transaction_id.expect("no transaction id");
+    let transactions = db.list_executed_transactions(Some(transaction_id), Some(1))?;
+    assert_eq!(transactions[0].id, transaction_id);
+    assert!(transactions[0]
+        .changes
+        .documents()
+        .expect("incorrect transaction type")
+        .documents
+        .iter()
+        .all(|document| document.deleted));
+
     Ok(())
 }
 
@@ -2696,6 +2847,78 @@ pub fn blocking_named_collection_tests<C: Connection>(db: &C) -> anyhow::Result<
     Ok(())
 }
 
+pub async fn named_document_tests<C: AsyncConnection>(db: &C) -> anyhow::Result<()> {
+    let inserted = db
+        .upsert_by_name::<Unique>("upsert", Unique::new("upsert"))
+        .await?;
+    let upserted = db
+        .upsert_by_name::<Unique>("upsert", Unique::new("upsert"))
+        .await?;
+    assert_eq!(inserted.header.id, upserted.header.id);
+
+    let loaded = db
+        .load_by_name::<Unique, _>("upsert")
+        .await?
+        .expect("document not found");
+    assert_eq!(loaded.header, upserted.header);
+    assert!(db.load_by_name::<Unique, _>("missing").await?.is_none());
+
+    let renamed = db
+        .rename::<Unique, _>("upsert", "renamed")
+        .await?
+        .expect("document not found");
+    assert_eq!(renamed.header.id, inserted.header.id);
+    assert_eq!(renamed.contents.value, "renamed");
+    assert!(db.load_by_name::<Unique, _>("upsert").await?.is_none());
+    assert_eq!(
+        db.load_by_name::<Unique, _>("renamed")
+            .await?
+            .expect("document not found")
+            .header,
+        renamed.header
+    );
+    assert!(db.rename::<Unique, _>("missing", "other").await?.is_none());
+
+    db.upsert_by_name::<Unique>("taken", Unique::new("taken"))
+        .await?;
+    let conflict = db.rename::<Unique, _>("renamed", "taken").await;
+    assert!(matches!(conflict, Err(Error::UniqueKeyViolation { .. })));
+
+    Ok(())
+}
+
+pub fn blocking_named_document_tests<C: Connection>(db: &C) -> anyhow::Result<()> {
+    let inserted = db.upsert_by_name::<Unique>("upsert", Unique::new("upsert"))?;
+    let upserted = db.upsert_by_name::<Unique>("upsert", Unique::new("upsert"))?;
+    assert_eq!(inserted.header.id, upserted.header.id);
+
+    let loaded = db
+        .load_by_name::<Unique, _>("upsert")?
+        .expect("document not found");
+    assert_eq!(loaded.header, upserted.header);
+    assert!(db.load_by_name::<Unique, _>("missing")?.is_none());
+
+    let renamed = db
+        .rename::<Unique, _>("upsert", "renamed")?
+        .expect("document not found");
+    assert_eq!(renamed.header.id, inserted.header.id);
+    assert_eq!(renamed.contents.value, "renamed");
+    assert!(db.load_by_name::<Unique, _>("upsert")?.is_none());
+    assert_eq!(
+        db.load_by_name::<Unique, _>("renamed")?
+            .expect("document not found")
+            .header,
+        renamed.header
+    );
+    assert!(db.rename::<Unique, _>("missing", "other")?.is_none());
+
+    db.upsert_by_name::<Unique>("taken", Unique::new("taken"))?;
+    let conflict = db.rename::<Unique, _>("renamed", "taken");
+    assert!(matches!(conflict, Err(Error::UniqueKeyViolation { .. })));
+
+    Ok(())
+}
+
 pub async fn compaction_tests<C: AsyncConnection + AsyncKeyValue>(db: &C) -> anyhow::Result<()> {
     let original_value = Basic::new("initial_value");
     let collection = db.collection::<Basic>();
@@ -2982,6 +3205,7 @@ macro_rules! define_async_kv_test_suite {
                     db.get_key("akey").into().await?,
                     Some(String::from("avalue"))
                 );
+                db.persist().await?;
                 assert_eq!(
                     db.set_key("akey", &String::from("new_value"))
                         .returning_previous_as()
@@ -3011,6 +3235,83 @@ macro_rules! define_async_kv_test_suite {
                 Ok(())
             }
 
+            #[tokio::test]
+            async fn kv_swap() -> anyhow::Result<()> {
+                use $crate::keyvalue::{AsyncKeyValue, KeyStatus};
+                let harness = $harness::new($crate::test_util::HarnessTest::KvSwap).await?;
+                let db = harness.connect().await?;
+
+                // Swapping two present keys
+                db.set_key("current", &String::from("a")).await?;
+                db.set_key("next", &String::from("b")).await?;
+                assert_eq!(
+                    db.swap(None, "current", None, "next").await?,
+                    KeyStatus::Updated
+                );
+                assert_eq!(db.get_key("current").into().await?, Some(String::from("b")));
+                assert_eq!(db.get_key("next").into().await?, Some(String::from("a")));
+
+                // Swapping with an absent key in another namespace moves the entry.
+                assert_eq!(
+                    db.swap(None, "current", Some("other"), "absent").await?,
+                    KeyStatus::Updated
+                );
+                assert_eq!(db.get_key("current").await?, None);
+                assert_eq!(
+                    db.with_key_namespace("other")
+                        .get_key("absent")
+                        .into()
+                        .await?,
+                    Some(String::from("b"))
+                );
+
+                // Swapping two absent keys changes nothing.
+                assert_eq!(
+                    db.swap(None, "current", None, "missing").await?,
+                    KeyStatus::NotChanged
+                );
+
+                harness.shutdown().await?;
+
+                Ok(())
+            }
+
+            #[tokio::test]
+            async fn kv_delete_if_equals() -> anyhow::Result<()> {
+                use $crate::keyvalue::{AsyncKeyValue, KeyStatus, Numeric, Value};
+                let harness =
+                    $harness::new($crate::test_util::HarnessTest::KvDeleteIfEquals).await?;
+                let db = harness.connect().await?;
+
+                // A mismatched value leaves the key alone.
+                db.set_numeric_key("lock", 1_u64).await?;
+                assert_eq!(
+                    db.delete_key_if_equals("lock", Value::Numeric(Numeric::UnsignedInteger(2)))
+                        .await?,
+                    KeyStatus::NotChanged
+                );
+                assert_eq!(db.get_key("lock").into_u64().await?, Some(1));
+
+                // A matching value deletes the key.
+                assert_eq!(
+                    db.delete_key_if_equals("lock", Value::Numeric(Numeric::UnsignedInteger(1)))
+                        .await?,
+                    KeyStatus::Deleted
+                );
+                assert_eq!(db.get_key("lock").await?, None);
+
+                // An absent key is never deleted.
+                assert_eq!(
+                    db.delete_key_if_equals("lock", Value::Numeric(Numeric::UnsignedInteger(1)))
+                        .await?,
+                    KeyStatus::NotChanged
+                );
+
+                harness.shutdown().await?;
+
+                Ok(())
+            }
+
             #[tokio::test]
             async fn kv_concurrency() -> anyhow::Result<()> {
                 use $crate::keyvalue::{AsyncKeyValue, KeyStatus};
@@ -3134,6 +3435,77 @@ macro_rules! define_async_kv_test_suite {
                 assert_eq!(kv.decrement_key_by("i64", 2_i64).await?, i64::MIN);
                 assert_eq!(kv.decrement_key_by("u64", 2_u64).await?, u64::MIN);
 
+                // The previous value can be returned instead of the new value.
+                assert_eq!(
+                    kv.increment_key_by("previous", 5_u64)
+                        .returning_previous()
+                        .await?,
+                    0_u64
+                );
+                assert_eq!(kv.increment_key_by("previous", 5_u64).await?, 10_u64);
+                assert_eq!(
+                    kv.decrement_key_by("previous", 3_u64)
+                        .returning_previous()
+                        .await?,
+                    10_u64
+                );
+                assert_eq!(kv.decrement_key_by("previous", 3_u64).await?, 4_u64);
+                assert_eq!(
+                    kv.increment_key_by("previous", 1_i64)
+                        .returning_previous()
+                        .await?,
+                    4_i64
+                );
+                assert_eq!(kv.get_key("previous").into_i64().await?, Some(5_i64));
+
+                // Test 128-bit values, which can't be represented by the
+                // 64-bit types.
+                kv.set_numeric_key("u128", u128::from(u64::MAX)).await?;
+                assert_eq!(
+                    kv.increment_key_by("u128", 1_u128).await?,
+                    u128::from(u64::MAX) + 1
+                );
+                assert_eq!(
+                    kv.get_key("u128").into_u128().await?,
+                    Some(u128::from(u64::MAX) + 1)
+                );
+                assert!(kv.get_key("u128").into_u64().await.is_err());
+                assert_eq!(kv.increment_key_by("u128", u128::MAX).await?, u128::MAX);
+                assert!(matches!(
+                    kv.increment_key_by("u128", 1_u128)
+                        .error_on_overflow()
+                        .await,
+                    Err($crate::Error::NumericOverflow)
+                ));
+                assert_eq!(
+                    kv.increment_key_by("u128", 1_u128).allow_overflow().await?,
+                    u128::MIN
+                );
+                assert_eq!(kv.decrement_key_by("i128", i128::MAX).await?, -i128::MAX);
+                assert_eq!(kv.decrement_key_by("i128", 2_i128).await?, i128::MIN);
+
+                // Existing values are converted to the amount's type.
+                kv.set_numeric_key("u64_128", u64::MAX).await?;
+                assert_eq!(
+                    kv.increment_key_by("u64_128", 1_i128).await?,
+                    i128::from(u64::MAX) + 1
+                );
+                assert_eq!(kv.increment_key_by("u64_128", 1_u64).await?, u64::MAX);
+                assert!(matches!(
+                    kv.increment_key_by("u64_128", 1_u64)
+                        .error_on_overflow()
+                        .await,
+                    Err($crate::Error::NumericOverflow)
+                ));
+                kv.set_numeric_key("u64_128", 1.5_f64).await?;
+                assert!(matches!(
+                    kv.increment_key_by("u64_128", 1_i128)
+                        .error_on_overflow()
+                        .await,
+                    Err($crate::Error::NumericOverflow)
+                ));
+                assert_eq!(kv.increment_key_by("u64_128", 1_i128).await?, 2_i128);
+
                 // Test numerical conversion safety using get
                 {
                     // For i64 -> f64, the limit is 2^52 + 1 in either posive or
@@ -3454,6 +3826,7 @@ macro_rules! define_blocking_kv_test_suite {
                     KeyStatus::Inserted
                 );
                 assert_eq!(db.get_key("akey").into()?, Some(String::from("avalue")));
+                db.persist()?;
                 assert_eq!(
                     db.set_key("akey", &String::from("new_value"))
                         .returning_previous_as()?,
@@ -3478,6 +3851,75 @@ macro_rules! define_blocking_kv_test_suite {
                 Ok(())
             }
 
+            #[test]
+            fn kv_swap() -> anyhow::Result<()> {
+                use $crate::keyvalue::{KeyStatus, KeyValue};
+                let harness = $harness::new($crate::test_util::HarnessTest::KvSwap)?;
+                let db = harness.connect()?;
+
+                // Swapping two present keys
+                db.set_key("current", &String::from("a")).execute()?;
+                db.set_key("next", &String::from("b")).execute()?;
+                assert_eq!(db.swap(None, "current", None, "next")?, KeyStatus::Updated);
+                assert_eq!(db.get_key("current").into()?, Some(String::from("b")));
+                assert_eq!(db.get_key("next").into()?, Some(String::from("a")));
+
+                // Swapping with an absent key in another namespace moves the entry.
+                assert_eq!(
+                    db.swap(None, "current", Some("other"), "absent")?,
+                    KeyStatus::Updated
+                );
+                assert_eq!(db.get_key("current").query()?, None);
+                assert_eq!(
+                    db.with_key_namespace("other").get_key("absent").into()?,
+                    Some(String::from("b"))
+                );
+
+                // Swapping two absent keys changes nothing.
+                assert_eq!(
+                    db.swap(None, "current", None, "missing")?,
+                    KeyStatus::NotChanged
+                );
+
+                harness.shutdown()?;
+
+                Ok(())
+            }
+
+            #[test]
+            fn kv_delete_if_equals() -> anyhow::Result<()> {
+                use $crate::keyvalue::{KeyStatus, KeyValue};
+                let harness = $harness::new($crate::test_util::HarnessTest::KvDeleteIfEquals)?;
+                let db = harness.connect()?;
+
+                // A mismatched value leaves the key alone.
+                db.set_key("owner", &String::from("a")).execute()?;
+                db.set_key("other", &String::from("b")).execute()?;
+                let other = db.get_key("other").query()?.expect("key missing");
+                assert_eq!(
+                    db.delete_key_if_equals("owner", other)?,
+                    KeyStatus::NotChanged
+                );
+                let current = db.get_key("owner").query()?.expect("key missing");
+
+                // A matching value deletes the key.
+                assert_eq!(
+                    db.delete_key_if_equals("owner", current.clone())?,
+                    KeyStatus::Deleted
+                );
+                assert_eq!(db.get_key("owner").query()?, None);
+
+                // An absent key is never deleted.
+                assert_eq!(
+                    db.delete_key_if_equals("owner", current)?,
+                    KeyStatus::NotChanged
+                );
+
+                harness.shutdown()?;
+
+                Ok(())
+            }
+
             #[test]
             fn kv_concurrency() -> anyhow::Result<()> {
                 use $crate::keyvalue::{KeyStatus, KeyValue};
@@ -3612,6 +4054,82 @@ macro_rules! define_blocking_kv_test_suite {
                 assert_eq!(kv.decrement_key_by("i64", 2_i64).execute()?, i64::MIN);
                 assert_eq!(kv.decrement_key_by("u64", 2_u64).execute()?, u64::MIN);
 
+                // The previous value can be returned instead of the new value.
+                assert_eq!(
+                    kv.increment_key_by("previous", 5_u64)
+                        .returning_previous()
+                        .execute()?,
+                    0_u64
+                );
+                assert_eq!(kv.increment_key_by("previous", 5_u64).execute()?, 10_u64);
+                assert_eq!(
+                    kv.decrement_key_by("previous", 3_u64)
+                        .returning_previous()
+                        .execute()?,
+                    10_u64
+                );
+                assert_eq!(kv.decrement_key_by("previous", 3_u64).execute()?, 4_u64);
+                assert_eq!(
+                    kv.increment_key_by("previous", 1_i64)
+                        .returning_previous()
+                        .execute()?,
+                    4_i64
+                );
+                assert_eq!(kv.get_key("previous").into_i64().execute()?, Some(5_i64));
+
+                // Test 128-bit values, which can't be represented by the
+                // 64-bit types.
+                kv.set_numeric_key("u128", u128::from(u64::MAX)).execute()?;
+                assert_eq!(
+                    kv.increment_key_by("u128", 1_u128).execute()?,
+                    u128::from(u64::MAX) + 1
+                );
+                assert_eq!(
+                    kv.get_key("u128").into_u128().execute()?,
+                    Some(u128::from(u64::MAX) + 1)
+                );
+                assert!(kv.get_key("u128").into_u64().execute().is_err());
+                assert_eq!(kv.increment_key_by("u128", u128::MAX).execute()?, u128::MAX);
+                assert!(matches!(
+                    kv.increment_key_by("u128", 1_u128)
+                        .error_on_overflow()
+                        .execute(),
+                    Err($crate::Error::NumericOverflow)
+                ));
+                assert_eq!(
+                    kv.increment_key_by("u128", 1_u128)
+                        .allow_overflow()
+                        .execute()?,
+                    u128::MIN
+                );
+                assert_eq!(
+                    kv.decrement_key_by("i128", i128::MAX).execute()?,
+                    -i128::MAX
+                );
+                assert_eq!(kv.decrement_key_by("i128", 2_i128).execute()?, i128::MIN);
+
+                // Existing values are converted to the amount's type.
+                kv.set_numeric_key("u64_128", u64::MAX).execute()?;
+                assert_eq!(
+                    kv.increment_key_by("u64_128", 1_i128).execute()?,
+                    i128::from(u64::MAX) + 1
+                );
+                assert_eq!(kv.increment_key_by("u64_128", 1_u64).execute()?, u64::MAX);
+                assert!(matches!(
+                    kv.increment_key_by("u64_128", 1_u64)
+                        .error_on_overflow()
+                        .execute(),
+                    Err($crate::Error::NumericOverflow)
+                ));
+                kv.set_numeric_key("u64_128", 1.5_f64).execute()?;
+                assert!(matches!(
+                    kv.increment_key_by("u64_128", 1_i128)
+                        .error_on_overflow()
+                        .execute(),
+                    Err($crate::Error::NumericOverflow)
+                ));
+                assert_eq!(kv.increment_key_by("u64_128", 1_i128).execute()?, 2_i128);
+
                 // Test numerical conversion safety using get
                 {
                     // For i64 -> f64, the limit is 2^52 + 1 in either posive or
@@ -3966,7 +4484,9 @@ pub async fn basic_server_connection_tests<C: AsyncStorageConnection>(
         .any(|s| s.name == SchemaName::new("khonsulabs", "bonsaidb-admin")));
 
     let databases = server.list_databases().await?;
-    assert!(databases.iter().any(|db| db.name == "tests"));
+    let tests = databases.iter().find(|db| db.name == "tests").unwrap();
+    assert!(tests.created_at.is_some());
+    assert!(tests.open.is_some());
 
     server
         .create_database::<BasicSchema>(newdb_name, false)
@@ -4029,7 +4549,9 @@ pub fn blocking_basic_server_connection_tests<C: StorageConnection>(
         .any(|s| s.name == SchemaName::new("khonsulabs", "bonsaidb-admin")));
 
     let databases = server.list_databases()?;
-    assert!(databases.iter().any(|db| db.name == "tests"));
+    let tests = databases.iter().find(|db| db.name == "tests").unwrap();
+    assert!(tests.created_at.is_some());
+    assert!(tests.open.is_some());
 
     server.create_database::<BasicSchema>(newdb_name, false)?;
     server.delete_database(newdb_name)?;
@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use crate::connection::{AsyncLowLevelConnection, LowLevelConnection};
 use crate::document::{CollectionHeader, DocumentId, HasHeader, Header, Revision};
 use crate::key::KeyEncoding;
+use crate::keyvalue::{Timestamp, Value};
 use crate::schema::{Collection, CollectionName, SerializedCollection};
 use crate::Error;
 
@@ -421,6 +422,12 @@ pub enum Changes {
     /// A list of changed documents.
     Documents(DocumentChanges),
     /// A list of changed keys.
+    ///
+    /// Key-value changes are recorded when they are persisted rather than when
+    /// they are executed, so each entry marks a durability boundary: changes
+    /// made with [`DurabilityMode::Deferred`](crate::keyvalue::DurabilityMode)
+    /// that don't appear in the log were not yet durable and may be lost after
+    /// a crash.
     Keys(Vec<ChangedKey>),
 }
 
@@ -553,21 +560,25 @@ fn document_changes_iter() {
                 collection: 0,
                 id: DocumentId::from_u64(0),
                 deleted: false,
+                contents: None,
             },
             ChangedDocument {
                 collection: 0,
                 id: DocumentId::from_u64(1),
                 deleted: false,
+                contents: None,
             },
             ChangedDocument {
                 collection: 1,
                 id: DocumentId::from_u64(2),
                 deleted: false,
+                contents: None,
             },
             ChangedDocument {
                 collection: 2,
                 id: DocumentId::from_u64(3),
                 deleted: false,
+                contents: None,
             },
         ],
     };
@@ -617,6 +628,12 @@ pub struct ChangedDocument {
 
     /// If the `Document` has been deleted, this will be `true`.
     pub deleted: bool,
+
+    /// The contents the transaction wrote to the `Document`. This is only
+    /// recorded by storage that keeps a replication log, and is `None` for
+    /// deleted documents.
+    #[serde(default)]
+    pub contents: Option<Bytes>,
 }
 
 /// A record of a changed `KeyValue` entry.
@@ -630,4 +647,14 @@ pub struct ChangedKey {
 
     /// True if the key was deleted.
     pub deleted: bool,
+
+    /// The value that was persisted. This is only recorded by storage that
+    /// keeps a replication log, and is `None` for deleted keys.
+    #[serde(default)]
+    pub value: Option<Value>,
+
+    /// The expiration of the persisted value, if the value was recorded and
+    /// expires.
+    #[serde(default)]
+    pub expiration: Option<Timestamp>,
 }
@@ -1,25 +1,37 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use bonsaidb_core::admin::DatabaseQuota;
 use bonsaidb_core::connection::{
     self, AccessPolicy, AsyncConnection, AsyncLowLevelConnection, AsyncStorageConnection,
-    Connection, HasSchema, HasSession, IdentityReference, LowLevelConnection, Range,
-    SerializedQueryKey, Session, Sort, StorageConnection,
+    Connection, DeletedDocuments, HasSchema, HasSession, IdentityReference, LowLevelConnection,
+    Range, ScrubReport, SerializedQueryKey, Session, Sort, StorageConnection, UserScope,
 };
 use bonsaidb_core::document::{DocumentId, Header, OwnedDocument};
-use bonsaidb_core::keyvalue::{AsyncKeyValue, KeyOperation, KeyValue, Output};
-use bonsaidb_core::permissions::Permissions;
+use bonsaidb_core::keyvalue::{AsyncKeyValue, Command, KeyOperation, KeyValue, Output, Value};
+use bonsaidb_core::permissions::{Permissions, Statement};
 use bonsaidb_core::pubsub::{self, AsyncPubSub, AsyncSubscriber, PubSub, Receiver};
 use bonsaidb_core::schema::view::map::MappedSerializedValue;
 use bonsaidb_core::schema::{
-    self, CollectionName, Nameable, Schema, SchemaName, SchemaSummary, Schematic, ViewName,
+    self, CollectionName, Nameable, Schema, SchemaDescription, SchemaName, SchemaSummary,
+    Schematic, ViewName,
 };
 use bonsaidb_core::transaction::{self, OperationResult, Transaction};
 
-use crate::config::StorageConfiguration;
+use crate::config::{StorageConfiguration, WorkCategory};
+use crate::database::activity::ActivityStats;
+use crate::database::diff::DatabaseDiff;
+use crate::database::keyvalue::DurabilityReceipt;
+use crate::database::quota::QuotaUsage;
 use crate::database::DatabaseNonBlocking;
-use crate::storage::{AnyBackupLocation, StorageNonBlocking};
-use crate::{Database, Error, Storage, Subscriber};
+use crate::storage::{
+    AdminImportMode, AdmissionStatistics, AnyBackupLocation, Priority, PubSubStatistics,
+    StorageNonBlocking,
+};
+use crate::tasks::Cancellation;
+use crate::{Database, Error, IntegrityScanStatus, Storage, Subscriber};
 
 /// A file-based, multi-database, multi-user database engine. This type is
 /// designed for use with [Tokio](https://tokio.rs). For blocking
@@ -135,20 +147,152 @@ impl AsyncStorage {
             .map(Storage::into_async)
     }
 
-    /// Restores all data from a previously stored backup `location`.
-    pub async fn restore<L: AnyBackupLocation + 'static>(&self, location: L) -> Result<(), Error> {
-        let task_self = self.clone();
+    /// Runs `work` on a blocking thread once the storage has admitted it as
+    /// [`WorkCategory::Maintenance`].
+    async fn spawn_maintenance<F>(&self, work: F) -> Result<(), Error>
+    where
+        F: FnOnce(&Storage) -> Result<(), Error> + Send + 'static,
+    {
+        let permit = self
+            .storage
+            .admission()
+            .admit(WorkCategory::Maintenance, Priority::Low)?;
+        let storage = self.storage.clone();
         self.runtime
-            .spawn_blocking(move || task_self.storage.restore(&location))
+            .spawn_blocking(move || {
+                let result = work(&storage);
+                drop(permit);
+                result
+            })
             .await?
     }
 
+    /// Restores all data from a previously stored backup `location`.
+    pub async fn restore<L: AnyBackupLocation + 'static>(&self, location: L) -> Result<(), Error> {
+        self.spawn_maintenance(move |storage| storage.restore(&location))
+            .await
+    }
+
     /// Stores a copy of all data in this instance to `location`.
     pub async fn backup<L: AnyBackupLocation + 'static>(&self, location: L) -> Result<(), Error> {
+        self.spawn_maintenance(move |storage| storage.backup(&location))
+            .await
+    }
+
+    /// Stores a copy of all data in this instance to `location`, compressing
+    /// each exported object larger than 128 bytes using `compression`.
+    #[cfg(feature = "compression")]
+    pub async fn backup_compressed<L: AnyBackupLocation + 'static>(
+        &self,
+        location: L,
+        compression: crate::config::Compression,
+    ) -> Result<(), Error> {
+        self.spawn_maintenance(move |storage| storage.backup_compressed(&location, compression))
+            .await
+    }
+
+    /// Stores a copy of the admin database to `location`. See
+    /// [`Storage::export_admin()`] for more information.
+    pub async fn export_admin<L: AnyBackupLocation + 'static>(
+        &self,
+        location: L,
+    ) -> Result<(), Error> {
+        self.spawn_maintenance(move |storage| storage.export_admin(&location))
+            .await
+    }
+
+    /// Loads the admin database previously exported to `location`. See
+    /// [`Storage::import_admin()`] for more information.
+    pub async fn import_admin<L: AnyBackupLocation + 'static>(
+        &self,
+        location: L,
+        mode: AdminImportMode,
+    ) -> Result<(), Error> {
+        self.spawn_maintenance(move |storage| storage.import_admin(&location, mode))
+            .await
+    }
+
+    /// Returns a machine-readable description of the registered schema named
+    /// `schema`. See [`Storage::describe_schema()`] for more information.
+    pub async fn describe_schema(
+        &self,
+        schema: SchemaName,
+    ) -> Result<SchemaDescription, bonsaidb_core::Error> {
         let task_self = self.clone();
         self.runtime
-            .spawn_blocking(move || task_self.storage.backup(&location))
-            .await?
+            .spawn_blocking(move || task_self.storage.describe_schema(&schema))
+            .await
+            .map_err(Error::from)?
+    }
+
+    /// Returns the approximate resources used by the database named `name`.
+    /// See [`Storage::database_quota_usage()`] for more information.
+    pub async fn database_quota_usage(
+        &self,
+        name: &str,
+    ) -> Result<QuotaUsage, bonsaidb_core::Error> {
+        let task_self = self.clone();
+        let name = name.to_owned();
+        self.runtime
+            .spawn_blocking(move || task_self.storage.database_quota_usage(&name))
+            .await
+            .map_err(Error::from)?
+    }
+
+    /// Returns statistics about the `PubSub` messages published in every
+    /// database of this storage. See [`Storage::pubsub_statistics()`] for more
+    /// information.
+    #[must_use]
+    pub fn pubsub_statistics(&self) -> PubSubStatistics {
+        self.storage.pubsub_statistics()
+    }
+
+    /// Returns the number of operations running in each
+    /// [`WorkCategory`]. See [`Storage::admission_statistics()`] for more
+    /// information.
+    #[must_use]
+    pub fn admission_statistics(&self) -> AdmissionStatistics {
+        self.storage.admission_statistics()
+    }
+
+    /// Resets all counters returned from [`Self::pubsub_statistics()`] to 0.
+    pub fn reset_pubsub_statistics(&self) {
+        self.storage.reset_pubsub_statistics();
+    }
+
+    /// Returns a subscriber that receives a
+    /// [`DatabaseEvent`](bonsaidb_core::admin::DatabaseEvent) each time a
+    /// database is created or deleted in this storage. See
+    /// [`Storage::subscribe_to_database_events()`] for more information.
+    pub fn subscribe_to_database_events(&self) -> Result<Subscriber, bonsaidb_core::Error> {
+        self.storage.subscribe_to_database_events()
+    }
+
+    /// Returns the [`UserScope`] of the user this storage is authenticated
+    /// as. See [`Storage::user_scope()`] for more information.
+    #[must_use]
+    pub fn user_scope(&self) -> Option<UserScope> {
+        self.storage.user_scope()
+    }
+
+    /// Returns true if this storage is in read-only mode. See
+    /// [`Storage::set_read_only()`] for more information.
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.storage.is_read_only()
+    }
+
+    /// Enables or disables read-only mode. See [`Storage::set_read_only()`]
+    /// for more information.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.storage.set_read_only(read_only);
+    }
+
+    /// Returns the status of the view integrity scans that are running or
+    /// waiting to run.
+    #[must_use]
+    pub fn integrity_scan_status(&self) -> IntegrityScanStatus {
+        self.storage.integrity_scan_status()
     }
 
     /// Restricts an unauthenticated instance to having `effective_permissions`.
@@ -163,6 +307,21 @@ impl AsyncStorage {
             })
     }
 
+    /// Opens the database named `name` without knowing its schema's type. See
+    /// [`Storage::with_schema_from_database()`] for more information.
+    pub async fn with_schema_from_database(&self, name: &str) -> Result<AsyncDatabase, Error> {
+        let name = name.to_owned();
+        let task_self = self.clone();
+        self.runtime
+            .spawn_blocking(move || {
+                task_self
+                    .storage
+                    .with_schema_from_database(&name)
+                    .map(Database::into_async)
+            })
+            .await?
+    }
+
     #[cfg(feature = "internal-apis")]
     #[doc(hidden)]
     pub async fn database_without_schema(&self, name: &str) -> Result<AsyncDatabase, Error> {
@@ -281,6 +440,59 @@ pub struct AsyncDatabase {
 }
 
 impl AsyncDatabase {
+    /// Runs `work` on a blocking thread once the storage has admitted it. See
+    /// [`Admission`](crate::config::Admission).
+    async fn spawn_admitted<T, F>(
+        &self,
+        category: WorkCategory,
+        priority: Priority,
+        work: F,
+    ) -> Result<T, bonsaidb_core::Error>
+    where
+        F: FnOnce(&Database) -> Result<T, bonsaidb_core::Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self
+            .database
+            .storage
+            .admission()
+            .admit(category, priority)?;
+        let database = self.database.clone();
+        self.runtime
+            .spawn_blocking(move || {
+                let result = work(&database);
+                drop(permit);
+                result
+            })
+            .await
+            .map_err(Error::from)?
+    }
+
+    /// Runs `query` on a blocking thread. If the returned future is dropped
+    /// before completing, the query stops waiting for views to update and
+    /// skips any remaining work.
+    async fn spawn_cancellable_query<T, F>(&self, query: F) -> Result<T, bonsaidb_core::Error>
+    where
+        F: FnOnce(Database) -> Result<T, bonsaidb_core::Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self
+            .database
+            .storage
+            .admission()
+            .admit(WorkCategory::Views, Priority::High)?;
+        let (cancellation, _cancel_on_drop) = Cancellation::new();
+        let database = self.database.with_cancellation(cancellation);
+        self.runtime
+            .spawn_blocking(move || {
+                let result = query(database);
+                drop(permit);
+                result
+            })
+            .await
+            .map_err(Error::from)?
+    }
+
     /// Creates a `Storage` with a single-database named "default" with its data stored at `path`.
     pub async fn open<DB: Schema>(configuration: StorageConfiguration) -> Result<Self, Error> {
         tokio::task::spawn_blocking(move || {
@@ -321,6 +533,111 @@ impl AsyncDatabase {
     pub fn as_blocking(&self) -> &Database {
         &self.database
     }
+
+    /// Returns statistics about the transactions committed to this database.
+    /// See [`Database::activity()`] for more information.
+    pub fn activity(&self) -> Result<ActivityStats, bonsaidb_core::Error> {
+        self.database.activity()
+    }
+
+    /// Maps every document in `C` using `map`, and reduces the values emitted
+    /// for each key using `reduce`, without creating a persistent view. See
+    /// [`Database::map_reduce()`] for more information.
+    pub async fn map_reduce<C, K, V, Map, Reduce>(
+        &self,
+        map: Map,
+        reduce: Reduce,
+    ) -> Result<BTreeMap<K, V>, bonsaidb_core::Error>
+    where
+        C: schema::SerializedCollection + 'static,
+        K: Ord + Send + 'static,
+        V: Send + 'static,
+        Map: Fn(&C::Contents) -> Vec<(K, V)> + Send + 'static,
+        Reduce: Fn(&[V]) -> V + Send + 'static,
+    {
+        self.spawn_admitted(WorkCategory::Documents, Priority::High, move |database| {
+            database.map_reduce::<C, K, V, Map, Reduce>(map, reduce)
+        })
+        .await
+    }
+
+    /// Returns the key and value of each entry in `namespace` whose value
+    /// matches `filter`. `filter` is invoked on a blocking thread. See
+    /// [`Database::scan_key_values()`] for more information.
+    pub async fn scan_key_values<F: Fn(&Value) -> bool + Send + 'static>(
+        &self,
+        namespace: Option<&str>,
+        filter: F,
+    ) -> Result<Vec<(String, Value)>, bonsaidb_core::Error> {
+        let namespace = namespace.map(ToOwned::to_owned);
+        self.spawn_admitted(WorkCategory::KeyValue, Priority::High, move |database| {
+            database.scan_key_values(namespace.as_deref(), filter)
+        })
+        .await
+    }
+
+    /// Compares the documents in this database against the ones in `other`.
+    /// See [`Database::diff()`] for more information.
+    pub async fn diff(&self, other: &Self) -> Result<DatabaseDiff, bonsaidb_core::Error> {
+        let other = other.database.clone();
+        self.spawn_admitted(WorkCategory::Documents, Priority::High, move |database| {
+            database.diff(&other)
+        })
+        .await
+    }
+
+    /// Applies `transaction`, returning the id the transaction was assigned
+    /// along with the result of each of its operations. See
+    /// [`Database::apply_transaction_with_id()`] for more information.
+    pub async fn apply_transaction_with_id(
+        &self,
+        transaction: Transaction,
+    ) -> Result<(u64, Vec<OperationResult>), bonsaidb_core::Error> {
+        self.spawn_admitted(WorkCategory::Documents, Priority::Low, move |database| {
+            database.apply_transaction_with_id(transaction)
+        })
+        .await
+    }
+
+    /// Returns a receipt that completes once every Key-Value change made
+    /// before this call has been persisted. See
+    /// [`Database::durability_receipt()`] for more information.
+    pub fn durability_receipt(&self) -> DurabilityReceipt {
+        self.database.durability_receipt()
+    }
+
+    /// Compacts the key-value store, returning the number of bytes reclaimed.
+    /// See [`Database::compact_key_value()`] for more information.
+    pub async fn compact_key_value(&self) -> Result<u64, bonsaidb_core::Error> {
+        self.spawn_admitted(
+            WorkCategory::Maintenance,
+            Priority::Low,
+            Database::compact_key_value,
+        )
+        .await
+    }
+
+    /// Verifies that every value stored in this database can be read. See
+    /// [`Database::scrub()`] for more information.
+    pub async fn scrub(&self) -> Result<ScrubReport, bonsaidb_core::Error> {
+        self.scrub_with_limit(None).await
+    }
+
+    /// Scrubs this database, stopping once `duration` has elapsed. See
+    /// [`Database::scrub_for()`] for more information.
+    pub async fn scrub_for(&self, duration: Duration) -> Result<ScrubReport, bonsaidb_core::Error> {
+        self.scrub_with_limit(Some(duration)).await
+    }
+
+    async fn scrub_with_limit(
+        &self,
+        duration: Option<Duration>,
+    ) -> Result<ScrubReport, bonsaidb_core::Error> {
+        self.spawn_admitted(WorkCategory::Maintenance, Priority::Low, move |database| {
+            database.scrub_with_limit(duration)
+        })
+        .await
+    }
 }
 
 impl From<AsyncDatabase> for Database {
@@ -339,6 +656,10 @@ impl DatabaseNonBlocking for AsyncDatabase {
     fn name(&self) -> &str {
         self.database.name()
     }
+
+    fn max_document_size(&self, collection: &CollectionName) -> Option<u64> {
+        self.database.max_document_size(collection)
+    }
 }
 
 impl HasSession for AsyncStorage {
@@ -409,6 +730,35 @@ impl AsyncStorageConnection for AsyncStorage {
             .map_err(Error::from)?
     }
 
+    async fn delete_databases(&self, names: &[&str]) -> Vec<Result<(), bonsaidb_core::Error>> {
+        let task_self = self.clone();
+        let names = names
+            .iter()
+            .map(|&name| name.to_owned())
+            .collect::<Vec<_>>();
+        let count = names.len();
+        self.runtime
+            .spawn_blocking(move || {
+                let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+                task_self.storage.delete_databases(&names)
+            })
+            .await
+            .unwrap_or_else(|err| vec![Err(bonsaidb_core::Error::from(Error::from(err))); count])
+    }
+
+    async fn set_database_quota(
+        &self,
+        name: &str,
+        quota: DatabaseQuota,
+    ) -> Result<(), bonsaidb_core::Error> {
+        let task_self = self.clone();
+        let name = name.to_owned();
+        self.runtime
+            .spawn_blocking(move || task_self.storage.set_database_quota(&name, quota))
+            .await
+            .map_err(Error::from)?
+    }
+
     async fn list_databases(&self) -> Result<Vec<connection::Database>, bonsaidb_core::Error> {
         let task_self = self.clone();
         self.runtime
@@ -446,6 +796,32 @@ impl AsyncStorageConnection for AsyncStorage {
             .map_err(Error::from)?
     }
 
+    async fn delete_users<'user, U: Nameable<'user, u64> + Clone + Send + Sync>(
+        &self,
+        users: &[U],
+    ) -> Vec<Result<(), bonsaidb_core::Error>> {
+        let task_self = self.clone();
+        let mut named = Vec::with_capacity(users.len());
+        let mut results = users
+            .iter()
+            .map(|user| -> Result<(), bonsaidb_core::Error> {
+                named.push(user.clone().name()?.into_owned());
+                Ok(())
+            })
+            .collect::<Vec<_>>();
+        let count = named.len();
+        let mut deleted = self
+            .runtime
+            .spawn_blocking(move || task_self.storage.delete_users(&named))
+            .await
+            .unwrap_or_else(|err| vec![Err(bonsaidb_core::Error::from(Error::from(err))); count])
+            .into_iter();
+        for result in results.iter_mut().filter(|result| result.is_ok()) {
+            *result = deleted.next().expect("one result per user");
+        }
+        results
+    }
+
     #[cfg(feature = "password-hashing")]
     async fn set_user_password<'user, U: Nameable<'user, u64> + Send + Sync>(
         &self,
@@ -494,6 +870,23 @@ impl AsyncStorageConnection for AsyncStorage {
             .map_err(Error::from)?
     }
 
+    async fn assume_identity_capped(
+        &self,
+        identity: IdentityReference<'_>,
+    ) -> Result<Self::Authenticated, bonsaidb_core::Error> {
+        let task_self = self.clone();
+        let identity = identity.into_owned();
+        self.runtime
+            .spawn_blocking(move || {
+                task_self
+                    .storage
+                    .assume_identity_capped(identity)
+                    .map(Storage::into_async)
+            })
+            .await
+            .map_err(Error::from)?
+    }
+
     async fn add_permission_group_to_user<
         'user,
         'group,
@@ -573,6 +966,57 @@ impl AsyncStorageConnection for AsyncStorage {
             .await
             .map_err(Error::from)?
     }
+
+    async fn create_permission_group(
+        &self,
+        name: &str,
+        statements: Vec<Statement>,
+    ) -> Result<u64, bonsaidb_core::Error> {
+        let task_self = self.clone();
+        let name = name.to_owned();
+        self.runtime
+            .spawn_blocking(move || task_self.storage.create_permission_group(&name, statements))
+            .await
+            .map_err(Error::from)?
+    }
+
+    async fn update_permission_group_statements<'group, G: Nameable<'group, u64> + Send + Sync>(
+        &self,
+        permission_group: G,
+        statements: Vec<Statement>,
+    ) -> Result<(), bonsaidb_core::Error> {
+        let task_self = self.clone();
+        let permission_group = permission_group.name()?.into_owned();
+        self.runtime
+            .spawn_blocking(move || {
+                task_self
+                    .storage
+                    .update_permission_group_statements(permission_group, statements)
+            })
+            .await
+            .map_err(Error::from)?
+    }
+
+    async fn create_role(&self, name: &str, groups: Vec<u64>) -> Result<u64, bonsaidb_core::Error> {
+        let task_self = self.clone();
+        let name = name.to_owned();
+        self.runtime
+            .spawn_blocking(move || task_self.storage.create_role(&name, groups))
+            .await
+            .map_err(Error::from)?
+    }
+
+    async fn delete_role<'role, R: Nameable<'role, u64> + Send + Sync>(
+        &self,
+        role: R,
+    ) -> Result<(), bonsaidb_core::Error> {
+        let task_self = self.clone();
+        let role = role.name()?.into_owned();
+        self.runtime
+            .spawn_blocking(move || task_self.storage.delete_role(role))
+            .await
+            .map_err(Error::from)?
+    }
 }
 
 impl HasSession for AsyncDatabase {
@@ -609,35 +1053,38 @@ impl AsyncConnection for AsyncDatabase {
     }
 
     async fn last_transaction_id(&self) -> Result<Option<u64>, bonsaidb_core::Error> {
-        Ok(self
+        let transaction_id = self
             .database
             .roots()
             .transactions()
-            .current_transaction_id())
+            .current_transaction_id();
+        self.database.saw_transaction(transaction_id);
+        Ok(transaction_id)
+    }
+
+    fn last_seen_transaction_id(&self) -> Option<u64> {
+        self.database.last_seen_transaction_id()
     }
 
     async fn compact(&self) -> Result<(), bonsaidb_core::Error> {
-        let task_self = self.clone();
-        self.runtime
-            .spawn_blocking(move || Connection::compact(&task_self.database))
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Maintenance, Priority::Low, |database| {
+            Connection::compact(database)
+        })
+        .await
     }
 
     async fn compact_collection<C: schema::Collection>(&self) -> Result<(), bonsaidb_core::Error> {
-        let task_self = self.clone();
-        self.runtime
-            .spawn_blocking(move || Connection::compact_collection::<C>(&task_self.database))
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Maintenance, Priority::Low, |database| {
+            Connection::compact_collection::<C>(database)
+        })
+        .await
     }
 
     async fn compact_key_value_store(&self) -> Result<(), bonsaidb_core::Error> {
-        let task_self = self.clone();
-        self.runtime
-            .spawn_blocking(move || Connection::compact_key_value_store(&task_self.database))
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Maintenance, Priority::Low, |database| {
+            Connection::compact_key_value_store(database)
+        })
+        .await
     }
 }
 
@@ -647,11 +1094,22 @@ impl AsyncKeyValue for AsyncDatabase {
         &self,
         op: KeyOperation,
     ) -> Result<Output, bonsaidb_core::Error> {
-        let task_self = self.clone();
-        self.runtime
-            .spawn_blocking(move || KeyValue::execute_key_operation(&task_self.database, op))
+        // Reads are always admitted, while anything that modifies a key can be
+        // rejected when the key-value store is overloaded. Gets that delete
+        // the key they return are writes.
+        let priority = match &op.command {
+            Command::Get { delete: false } | Command::Scan(_) => Priority::High,
+            _ => Priority::Low,
+        };
+        self.spawn_admitted(WorkCategory::KeyValue, priority, move |database| {
+            KeyValue::execute_key_operation(database, op)
+        })
+        .await
+    }
+
+    async fn persist(&self) -> Result<(), bonsaidb_core::Error> {
+        self.spawn_admitted(WorkCategory::KeyValue, Priority::Low, KeyValue::persist)
             .await
-            .map_err(Error::from)?
     }
 }
 
@@ -707,11 +1165,10 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         &self,
         transaction: Transaction,
     ) -> Result<Vec<OperationResult>, bonsaidb_core::Error> {
-        let task_self = self.clone();
-        self.runtime
-            .spawn_blocking(move || task_self.database.apply_transaction(transaction))
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Documents, Priority::Low, move |database| {
+            database.apply_transaction(transaction)
+        })
+        .await
     }
 
     async fn get_from_collection(
@@ -719,12 +1176,11 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         id: DocumentId,
         collection: &CollectionName,
     ) -> Result<Option<OwnedDocument>, bonsaidb_core::Error> {
-        let task_self = self.clone();
         let collection = collection.clone();
-        self.runtime
-            .spawn_blocking(move || task_self.database.get_from_collection(id, &collection))
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Documents, Priority::High, move |database| {
+            database.get_from_collection(id, &collection)
+        })
+        .await
     }
 
     async fn list_from_collection(
@@ -734,16 +1190,11 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         limit: Option<u32>,
         collection: &CollectionName,
     ) -> Result<Vec<OwnedDocument>, bonsaidb_core::Error> {
-        let task_self = self.clone();
         let collection = collection.clone();
-        self.runtime
-            .spawn_blocking(move || {
-                task_self
-                    .database
-                    .list_from_collection(ids, order, limit, &collection)
-            })
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Documents, Priority::High, move |database| {
+            database.list_from_collection(ids, order, limit, &collection)
+        })
+        .await
     }
 
     async fn list_headers_from_collection(
@@ -753,16 +1204,11 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         limit: Option<u32>,
         collection: &CollectionName,
     ) -> Result<Vec<Header>, bonsaidb_core::Error> {
-        let task_self = self.clone();
         let collection = collection.clone();
-        self.runtime
-            .spawn_blocking(move || {
-                task_self
-                    .database
-                    .list_headers_from_collection(ids, order, limit, &collection)
-            })
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Documents, Priority::High, move |database| {
+            database.list_headers_from_collection(ids, order, limit, &collection)
+        })
+        .await
     }
 
     async fn count_from_collection(
@@ -770,12 +1216,11 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         ids: Range<DocumentId>,
         collection: &CollectionName,
     ) -> Result<u64, bonsaidb_core::Error> {
-        let task_self = self.clone();
         let collection = collection.clone();
-        self.runtime
-            .spawn_blocking(move || task_self.database.count_from_collection(ids, &collection))
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Documents, Priority::High, move |database| {
+            database.count_from_collection(ids, &collection)
+        })
+        .await
     }
 
     async fn get_multiple_from_collection(
@@ -783,29 +1228,23 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         ids: &[DocumentId],
         collection: &CollectionName,
     ) -> Result<Vec<OwnedDocument>, bonsaidb_core::Error> {
-        let task_self = self.clone();
         // TODO avoid the allocation here, switch to IntoIterator.
         let ids = ids.to_vec();
         let collection = collection.clone();
-        self.runtime
-            .spawn_blocking(move || {
-                task_self
-                    .database
-                    .get_multiple_from_collection(&ids, &collection)
-            })
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Documents, Priority::High, move |database| {
+            database.get_multiple_from_collection(&ids, &collection)
+        })
+        .await
     }
 
     async fn compact_collection_by_name(
         &self,
         collection: CollectionName,
     ) -> Result<(), bonsaidb_core::Error> {
-        let task_self = self.clone();
-        self.runtime
-            .spawn_blocking(move || task_self.database.compact_collection_by_name(collection))
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Maintenance, Priority::Low, move |database| {
+            database.compact_collection_by_name(collection)
+        })
+        .await
     }
 
     async fn query_by_name(
@@ -816,16 +1255,11 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         limit: Option<u32>,
         access_policy: AccessPolicy,
     ) -> Result<Vec<schema::view::map::Serialized>, bonsaidb_core::Error> {
-        let task_self = self.clone();
         let view = view.clone();
-        self.runtime
-            .spawn_blocking(move || {
-                task_self
-                    .database
-                    .query_by_name(&view, key, order, limit, access_policy)
-            })
-            .await
-            .map_err(Error::from)?
+        self.spawn_cancellable_query(move |database| {
+            database.query_by_name(&view, key, order, limit, access_policy)
+        })
+        .await
     }
 
     async fn query_by_name_with_docs(
@@ -836,16 +1270,11 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         limit: Option<u32>,
         access_policy: AccessPolicy,
     ) -> Result<schema::view::map::MappedSerializedDocuments, bonsaidb_core::Error> {
-        let task_self = self.clone();
         let view = view.clone();
-        self.runtime
-            .spawn_blocking(move || {
-                task_self
-                    .database
-                    .query_by_name_with_docs(&view, key, order, limit, access_policy)
-            })
-            .await
-            .map_err(Error::from)?
+        self.spawn_cancellable_query(move |database| {
+            database.query_by_name_with_docs(&view, key, order, limit, access_policy)
+        })
+        .await
     }
 
     async fn reduce_by_name(
@@ -854,12 +1283,11 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
     ) -> Result<Vec<u8>, bonsaidb_core::Error> {
-        let task_self = self.clone();
         let view = view.clone();
-        self.runtime
-            .spawn_blocking(move || task_self.database.reduce_by_name(&view, key, access_policy))
-            .await
-            .map_err(Error::from)?
+        self.spawn_cancellable_query(move |database| {
+            database.reduce_by_name(&view, key, access_policy)
+        })
+        .await
     }
 
     async fn reduce_grouped_by_name(
@@ -868,16 +1296,11 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
     ) -> Result<Vec<MappedSerializedValue>, bonsaidb_core::Error> {
-        let task_self = self.clone();
         let view = view.clone();
-        self.runtime
-            .spawn_blocking(move || {
-                task_self
-                    .database
-                    .reduce_grouped_by_name(&view, key, access_policy)
-            })
-            .await
-            .map_err(Error::from)?
+        self.spawn_cancellable_query(move |database| {
+            database.reduce_grouped_by_name(&view, key, access_policy)
+        })
+        .await
     }
 
     async fn delete_docs_by_name(
@@ -885,16 +1308,12 @@ impl AsyncLowLevelConnection for AsyncDatabase {
         view: &ViewName,
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, bonsaidb_core::Error> {
-        let task_self = self.clone();
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, bonsaidb_core::Error> {
         let view = view.clone();
-        self.runtime
-            .spawn_blocking(move || {
-                task_self
-                    .database
-                    .delete_docs_by_name(&view, key, access_policy)
-            })
-            .await
-            .map_err(Error::from)?
+        self.spawn_admitted(WorkCategory::Views, Priority::Low, move |database| {
+            database.delete_docs_by_name(&view, key, access_policy, include_headers)
+        })
+        .await
     }
 }
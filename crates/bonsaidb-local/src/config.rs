@@ -1,15 +1,26 @@
 use std::collections::HashMap;
+#[cfg(feature = "compression")]
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
 #[cfg(feature = "encryption")]
 use bonsaidb_core::document::KeyId;
+use bonsaidb_core::keyvalue::DurabilityMode;
 use bonsaidb_core::permissions::Permissions;
-use bonsaidb_core::schema::{Schema, SchemaName};
+use bonsaidb_core::schema::{
+    Collection, CollectionName, Schema, SchemaName, Schematic, View, ViewName,
+};
 use sysinfo::{CpuRefreshKind, RefreshKind, System, SystemExt};
 
-use crate::storage::{DatabaseOpener, StorageSchemaOpener};
+pub use crate::database::keyvalue::Clock;
+use crate::database::quota::QuotaMonitor;
+use crate::database::scrub::ScrubMonitor;
+use crate::storage::{
+    admin_schematic, AdminExtension, DatabaseLifecycle, DatabaseOpener, ExternalIdentityResolver,
+    StorageSchemaOpener,
+};
 #[cfg(feature = "encryption")]
 use crate::vault::AnyVaultKeyStorage;
 use crate::Error;
@@ -65,18 +76,111 @@ pub struct StorageConfiguration {
     /// Controls how the key-value store persists keys, on a per-database basis.
     pub key_value_persistence: KeyValuePersistence,
 
+    /// Settings that override this configuration for specific databases,
+    /// keyed by the database's name.
+    pub databases: HashMap<String, DatabaseConfiguration>,
+
+    /// The clock the key-value store uses to determine when keys expire and
+    /// when changes should be persisted.
+    pub key_value_clock: KeyValueClock,
+
+    /// Receives notifications when a database exceeds a soft limit of its
+    /// [`DatabaseQuota`](bonsaidb_core::admin::DatabaseQuota). If `None`,
+    /// warnings are logged instead.
+    pub quota_monitor: Option<Arc<dyn QuotaMonitor>>,
+
+    /// Receives notifications about problems found while scrubbing a
+    /// database. If `None`, problems are logged instead.
+    pub scrub_monitor: Option<Arc<dyn ScrubMonitor>>,
+
+    /// Receives notifications as databases are opened, closed, and deleted.
+    pub database_lifecycle: Option<Arc<dyn DatabaseLifecycle>>,
+
+    /// Configuration options related to `PubSub` subscribers.
+    pub subscribers: Subscribers,
+
+    /// Limits on the blocking work performed by
+    /// [`AsyncStorage`](crate::AsyncStorage) and
+    /// [`AsyncDatabase`](crate::AsyncDatabase) at once.
+    pub admission: Admission,
+
+    /// If set, every document is checked while the storage is opened, and
+    /// the results are available from
+    /// [`Storage::last_recovery_report()`](crate::Storage::last_recovery_report).
+    /// Checking reads every document in every database, so this is intended
+    /// to be enabled after an unclean shutdown or when problems are
+    /// suspected. The default is `None`.
+    pub repair_mode: Option<RepairMode>,
+
+    /// The maximum size, in bytes, of a document's contents. Inserting or
+    /// updating a document with larger contents returns
+    /// [`Error::DocumentTooLarge`](bonsaidb_core::Error::DocumentTooLarge).
+    /// Collections can override this limit using
+    /// [`Collection::max_document_size()`](bonsaidb_core::schema::Collection::max_document_size).
+    ///
+    /// Existing documents larger than the limit can still be read, deleted,
+    /// and updated with contents that aren't any larger. The default is
+    /// `None`, which doesn't limit document sizes.
+    pub max_document_size: Option<u64>,
+
+    /// How long [`Storage::open()`](crate::Storage::open) waits for another
+    /// handle to close the storage before returning
+    /// [`Error::StorageInUse`](crate::Error::StorageInUse). Background
+    /// threads, such as the one persisting key-value changes, keep the
+    /// storage open for a short time after the last handle is dropped. The
+    /// default is `None`, which waits until the other handle is closed.
+    pub lock_timeout: Option<Duration>,
+
+    /// If true, the contents each transaction writes are recorded in the
+    /// transaction log alongside the ids of the changed documents and keys.
+    /// This allows a [`Follower`](crate::Follower) to replay each transaction
+    /// exactly, at the cost of storing the contents of every write a second
+    /// time until the log is compacted. The default is false.
+    pub replication_log: bool,
+
+    /// The [`CompactionPriority`] of each collection. Collections that aren't
+    /// present use [`CompactionPriority::Normal`].
+    pub compaction_priorities: HashMap<CollectionName, CompactionPriority>,
+
     /// Sets the default compression algorithm.
     #[cfg(feature = "compression")]
     pub default_compression: Option<Compression>,
 
+    /// The dictionaries used to compress each collection's data. The last
+    /// dictionary provided for a collection is used to compress new data, and
+    /// every dictionary provided is able to decompress existing data.
+    ///
+    /// Providing a dictionary for a collection enables compression for that
+    /// collection, even if [`Self::default_compression`] is `None`.
+    #[cfg(feature = "compression")]
+    pub compression_dictionaries: HashMap<CollectionName, Vec<CompressionDictionary>>,
+
+    /// If true, a checksum is stored alongside each compressed or encrypted
+    /// block and verified when the block is read. A block that fails
+    /// verification returns [`Error::BlockCorrupted`](crate::Error::BlockCorrupted)
+    /// instead of being decompressed.
+    ///
+    /// Computing checksums adds a small cost to every write. Existing blocks
+    /// are verified regardless of this setting if they were written with a
+    /// checksum. Blocks are only checksummed if compression or encryption is
+    /// in use for their tree. The default is false.
+    #[cfg(feature = "compression")]
+    pub block_checksums: bool,
+
     /// The permissions granted to authenticated connections to this server.
     pub authenticated_permissions: Permissions,
 
+    /// Verifies the credentials of external identities as they are assumed,
+    /// and determines their permissions. If `None`, external identities can't
+    /// be assumed.
+    pub external_identities: Option<Arc<dyn ExternalIdentityResolver>>,
+
     /// Password hashing configuration.
     #[cfg(feature = "password-hashing")]
     pub argon: ArgonConfiguration,
 
     pub(crate) initial_schemas: HashMap<SchemaName, Arc<dyn DatabaseOpener>>,
+    pub(crate) admin_extensions: Vec<AdminExtension>,
 }
 
 impl Default for StorageConfiguration {
@@ -96,13 +200,31 @@ impl Default for StorageConfiguration {
             default_encryption_key: None,
             #[cfg(feature = "compression")]
             default_compression: None,
+            #[cfg(feature = "compression")]
+            compression_dictionaries: HashMap::default(),
+            #[cfg(feature = "compression")]
+            block_checksums: false,
             workers: Tasks::default_for(&system),
             views: Views::default(),
             key_value_persistence: KeyValuePersistence::default(),
+            databases: HashMap::default(),
+            key_value_clock: KeyValueClock::default(),
+            quota_monitor: None,
+            scrub_monitor: None,
+            database_lifecycle: None,
+            subscribers: Subscribers::default(),
+            admission: Admission::default(),
+            repair_mode: None,
+            max_document_size: None,
+            lock_timeout: None,
+            replication_log: false,
+            compaction_priorities: HashMap::default(),
             authenticated_permissions: Permissions::default(),
+            external_identities: None,
             #[cfg(feature = "password-hashing")]
             argon: ArgonConfiguration::default_for(&system),
             initial_schemas: HashMap::default(),
+            admin_extensions: Vec::new(),
         }
     }
 }
@@ -118,15 +240,30 @@ impl std::fmt::Debug for StorageConfiguration {
             .field("workers", &self.workers)
             .field("views", &self.views)
             .field("key_value_persistence", &self.key_value_persistence)
+            .field("databases", &self.databases)
+            .field("key_value_clock", &self.key_value_clock)
+            .field("quota_monitor", &self.quota_monitor)
+            .field("scrub_monitor", &self.scrub_monitor)
+            .field("database_lifecycle", &self.database_lifecycle)
+            .field("subscribers", &self.subscribers)
+            .field("admission", &self.admission)
+            .field("repair_mode", &self.repair_mode)
+            .field("max_document_size", &self.max_document_size)
+            .field("replication_log", &self.replication_log)
+            .field("compaction_priorities", &self.compaction_priorities)
             .field("authenticated_permissions", &self.authenticated_permissions)
-            .field("initial_schemas", &schemas);
+            .field("external_identities", &self.external_identities)
+            .field("initial_schemas", &schemas)
+            .field("admin_extensions", &self.admin_extensions.len());
 
         #[cfg(feature = "encryption")]
         f.field("vault_key_storage", &self.vault_key_storage)
             .field("default_encryption_key", &self.default_encryption_key);
 
         #[cfg(feature = "compression")]
-        f.field("default_compression", &self.default_compression);
+        f.field("default_compression", &self.default_compression)
+            .field("compression_dictionaries", &self.compression_dictionaries)
+            .field("block_checksums", &self.block_checksums);
 
         #[cfg(feature = "password-hashing")]
         f.field("argon", &self.argon);
@@ -143,6 +280,53 @@ impl StorageConfiguration {
             .insert(S::schema_name(), Arc::new(StorageSchemaOpener::<S>::new()?));
         Ok(())
     }
+
+    /// Adds the collections and views of `S` to the admin database. See
+    /// [`Builder::with_admin_extension()`] for more information.
+    pub fn register_admin_extension<S: Schema>(&mut self) -> Result<(), Error> {
+        let extend: AdminExtension = Schematic::extend_with::<S>;
+        self.admin_extensions.push(extend);
+        // Build the schematic now so that collisions with the built-in
+        // collections are reported before the storage is opened.
+        if let Err(err) = admin_schematic(&self.admin_extensions) {
+            self.admin_extensions.pop();
+            return Err(Error::Core(err));
+        }
+        Ok(())
+    }
+}
+
+/// How urgently a collection's data is compacted, relative to the other
+/// collections in its database.
+///
+/// When a database is compacted, its collections are compacted in order of
+/// their priority's [`weight()`](Self::weight) multiplied by the estimated
+/// fraction of the collection's document file that is no longer in use. A
+/// frequently updated collection can be given [`CompactionPriority::High`] to
+/// compact it before an archive, while a heavily fragmented archive is still
+/// compacted before a collection that has little to reclaim.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum CompactionPriority {
+    /// The collection is rarely modified, such as an archive.
+    Low,
+    /// The default priority.
+    #[default]
+    Normal,
+    /// The collection is frequently modified.
+    High,
+}
+
+impl CompactionPriority {
+    /// Returns the factor that the collection's estimated fragmentation is
+    /// multiplied by when ordering compactions.
+    #[must_use]
+    pub const fn weight(self) -> u32 {
+        match self {
+            Self::Low => 1,
+            Self::Normal => 2,
+            Self::High => 4,
+        }
+    }
 }
 
 /// Configuration options for background tasks.
@@ -157,6 +341,22 @@ pub struct Tasks {
     /// parallelizable. This defaults to the nuber of cpu cores available to the
     /// system.
     pub parallelization: usize,
+
+    /// Defines how many view integrity scans can run at once, regardless of
+    /// [`Self::worker_count`]. This defaults to the number of cpu cores
+    /// available to the system.
+    ///
+    /// Scans that a query is waiting on are run before scans that were
+    /// scheduled when a database was opened, due to
+    /// [`Views::check_integrity_on_open`].
+    pub max_concurrent_integrity_scans: usize,
+
+    /// The number of persisted Key-Value entries examined in each batch when
+    /// loading a database's key expirations after it is opened. The key-value
+    /// store is locked while a batch is examined, and other operations are
+    /// able to run between batches. Every persisted entry is examined each
+    /// time a database is opened. This defaults to 1,000.
+    pub expiration_load_batch_size: usize,
 }
 
 impl SystemDefault for Tasks {
@@ -169,6 +369,8 @@ impl SystemDefault for Tasks {
         Self {
             worker_count: num_cpus * 2,
             parallelization: num_cpus,
+            max_concurrent_integrity_scans: num_cpus,
+            expiration_load_batch_size: 1_000,
         }
     }
 }
@@ -182,6 +384,229 @@ pub struct Views {
     /// be checked. However, for faster startup time, you may wish to delay the
     /// integrity scan. Default value is `false`.
     pub check_integrity_on_open: bool,
+
+    /// Overrides [`Self::check_integrity_on_open`] for all views of specific
+    /// collections.
+    pub collection_integrity_checks_on_open: HashMap<CollectionName, bool>,
+
+    /// Overrides [`Self::check_integrity_on_open`] for specific views. These
+    /// take priority over
+    /// [`Self::collection_integrity_checks_on_open`].
+    pub view_integrity_checks_on_open: HashMap<ViewName, bool>,
+}
+
+impl Views {
+    /// Returns true if `view` should be checked for integrity when its
+    /// database is opened.
+    #[must_use]
+    pub fn check_integrity_on_open_for(&self, view: &ViewName) -> bool {
+        self.view_integrity_checks_on_open
+            .get(view)
+            .or_else(|| {
+                self.collection_integrity_checks_on_open
+                    .get(&view.collection)
+            })
+            .copied()
+            .unwrap_or(self.check_integrity_on_open)
+    }
+}
+
+/// Settings that override the [`StorageConfiguration`] for a single database.
+/// Each setting that is `None` inherits the storage's configuration.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct DatabaseConfiguration {
+    /// Overrides [`StorageConfiguration::key_value_persistence`].
+    pub key_value_persistence: Option<KeyValuePersistence>,
+
+    /// Controls whether this database's data is kept in the storage's shared
+    /// cache of recently read chunks. Databases that are rarely read, or whose
+    /// data shouldn't push other databases' data out of the cache, can disable
+    /// this. Defaults to true.
+    pub cache: Option<bool>,
+
+    /// The minimum durability of this database's key-value changes. With
+    /// [`DurabilityMode::Immediate`], every operation that modifies a key
+    /// persists all pending changes before it returns, regardless of the
+    /// durability requested by the operation. Defaults to
+    /// [`DurabilityMode::Deferred`], which leaves the durability of each
+    /// change to the operation and to the key-value persistence.
+    pub key_value_durability: Option<DurabilityMode>,
+
+    /// Overrides [`StorageConfiguration::default_compression`]. `Some(None)`
+    /// disables compression for this database. Data written before the
+    /// override was changed remains readable.
+    #[cfg(feature = "compression")]
+    pub compression: Option<Option<Compression>>,
+}
+
+impl DatabaseConfiguration {
+    /// Sets [`Self::key_value_persistence`] to `persistence` and returns self.
+    #[must_use]
+    pub fn key_value_persistence(mut self, persistence: KeyValuePersistence) -> Self {
+        self.key_value_persistence = Some(persistence);
+        self
+    }
+
+    /// Sets [`Self::cache`] to `enabled` and returns self.
+    #[must_use]
+    pub const fn cache(mut self, enabled: bool) -> Self {
+        self.cache = Some(enabled);
+        self
+    }
+
+    /// Sets [`Self::key_value_durability`] to `durability` and returns self.
+    #[must_use]
+    pub const fn key_value_durability(mut self, durability: DurabilityMode) -> Self {
+        self.key_value_durability = Some(durability);
+        self
+    }
+
+    /// Sets [`Self::compression`] to `compression` and returns self.
+    #[cfg(feature = "compression")]
+    #[must_use]
+    pub const fn compression(mut self, compression: Option<Compression>) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+}
+
+/// The clock used by the key-value store to determine when keys expire and
+/// when changes should be persisted.
+#[derive(Clone, Debug, Default)]
+pub enum KeyValueClock {
+    /// Use the system clock. If the system clock is adjusted, keys may expire
+    /// earlier or later than expected.
+    #[default]
+    System,
+    /// Measure elapsed time using a monotonic clock that is anchored to the
+    /// system clock. Adjustments to the system clock up to `max_skew` are
+    /// ignored. If the system clock differs from the monotonic clock by more
+    /// than `max_skew`, the monotonic clock is re-anchored to the system clock.
+    Monotonic {
+        /// The largest difference between the system clock and the monotonic
+        /// clock that is ignored.
+        max_skew: Duration,
+    },
+    /// Use the provided clock. This is primarily useful for testing
+    /// expiration deterministically, such as with `test_util::TestClock` when
+    /// the `test-util` feature is enabled.
+    Custom(Arc<dyn Clock>),
+}
+
+/// Controls what [`Storage::open()`](crate::Storage::open) does with the
+/// problems it finds while checking the storage's data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RepairMode {
+    /// Problems are only reported. Nothing is modified.
+    ReportOnly,
+    /// Documents that can't be read are moved into their collection's
+    /// quarantine, allowing the rest of the collection to be used normally.
+    /// Quarantined documents can be listed using
+    /// [`Database::quarantined_documents()`](crate::Database::quarantined_documents)
+    /// and restored using
+    /// [`Database::restore_quarantined_document()`](crate::Database::restore_quarantined_document)
+    /// once the underlying problem has been fixed.
+    RepairAggressively,
+}
+
+/// Configuration options for `PubSub` subscribers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Subscribers {
+    /// The maximum number of subscribers a single session may have at once.
+    /// Subscribers created without a session only count towards
+    /// [`Self::max_total`]. Default value is `None`, which places no limit on
+    /// the number of subscribers.
+    pub max_per_session: Option<usize>,
+
+    /// The maximum number of subscribers that may exist at once across all
+    /// sessions. Default value is `None`, which places no limit on the number
+    /// of subscribers.
+    pub max_total: Option<usize>,
+}
+
+/// A category of blocking work whose concurrency is limited by
+/// [`Admission`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum WorkCategory {
+    /// Key-value operations.
+    KeyValue,
+    /// View queries and deleting documents using views.
+    Views,
+    /// Transactions and reading documents.
+    Documents,
+    /// Compaction, scrubbing, and backups.
+    Maintenance,
+}
+
+/// Limits on the blocking work performed by
+/// [`AsyncStorage`](crate::AsyncStorage) and
+/// [`AsyncDatabase`](crate::AsyncDatabase) at once.
+///
+/// Each operation counts towards its [`WorkCategory`] from when it is
+/// admitted until it completes. Once a category has reached its limit, new
+/// operations in it that write data or perform maintenance fail immediately
+/// with [`Error::Overloaded`](bonsaidb_core::Error::Overloaded) rather than
+/// waiting for a blocking thread. Operations that only read data are always
+/// admitted, which keeps a flood of writes from delaying them behind a long
+/// queue of blocking work.
+///
+/// Operations performed using the blocking [`Storage`](crate::Storage) and
+/// [`Database`](crate::Database) types are not limited. The current
+/// utilization is returned from
+/// [`Storage::admission_statistics()`](crate::Storage::admission_statistics).
+#[derive(Clone, Copy, Debug)]
+pub struct Admission {
+    /// The limit for [`WorkCategory::KeyValue`]. Default value is `None`,
+    /// which places no limit on the number of operations.
+    pub key_value: Option<usize>,
+    /// The limit for [`WorkCategory::Views`]. Default value is `None`, which
+    /// places no limit on the number of operations.
+    pub views: Option<usize>,
+    /// The limit for [`WorkCategory::Documents`]. Default value is `None`,
+    /// which places no limit on the number of operations.
+    pub documents: Option<usize>,
+    /// The limit for [`WorkCategory::Maintenance`]. Default value is `None`,
+    /// which places no limit on the number of operations.
+    pub maintenance: Option<usize>,
+    /// The delay returned to callers whose operations are rejected, as a hint
+    /// for when to retry. Default value is 100 milliseconds.
+    pub retry_after_hint: Duration,
+}
+
+impl Default for Admission {
+    fn default() -> Self {
+        Self {
+            key_value: None,
+            views: None,
+            documents: None,
+            maintenance: None,
+            retry_after_hint: Duration::from_millis(100),
+        }
+    }
+}
+
+impl Admission {
+    /// Returns the limit for `category`.
+    #[must_use]
+    pub const fn limit(&self, category: WorkCategory) -> Option<usize> {
+        match category {
+            WorkCategory::KeyValue => self.key_value,
+            WorkCategory::Views => self.views,
+            WorkCategory::Documents => self.documents,
+            WorkCategory::Maintenance => self.maintenance,
+        }
+    }
+
+    /// Sets the limit for `category` to `limit`.
+    pub fn set_limit(&mut self, category: WorkCategory, limit: Option<usize>) {
+        match category {
+            WorkCategory::KeyValue => self.key_value = limit,
+            WorkCategory::Views => self.views = limit,
+            WorkCategory::Documents => self.documents = limit,
+            WorkCategory::Maintenance => self.maintenance = limit,
+        }
+    }
 }
 
 /// Rules for persisting key-value changes. Default persistence is to
@@ -350,6 +775,21 @@ pub trait Builder: Sized {
     }
     /// Registers the schema and returns self.
     fn with_schema<S: Schema>(self) -> Result<Self, Error>;
+    /// Adds the collections and views of `S` to the admin database and
+    /// returns self.
+    ///
+    /// This allows applications to store their own data in the admin
+    /// database, where it is included in backups and protected by the same
+    /// permissions as the built-in collections. The admin database keeps its
+    /// own schema name, so an existing admin database accepts the new
+    /// collections the next time it is opened. The extension's views are
+    /// integrity checked like any other view.
+    ///
+    /// `S` does not need to be registered using
+    /// [`with_schema()`](Self::with_schema). If `S` defines a collection or
+    /// view whose name is already used by the admin database, an error is
+    /// returned.
+    fn with_admin_extension<S: Schema>(self) -> Result<Self, Error>;
 
     /// Sets [`StorageConfiguration::memory_only`](StorageConfiguration#structfield.memory_only) to true and returns self.
     #[must_use]
@@ -377,19 +817,94 @@ pub trait Builder: Sized {
     /// Sets [`Tasks::parallelization`] to `parallelization` and returns self.
     #[must_use]
     fn tasks_parallelization(self, parallelization: usize) -> Self;
+    /// Sets [`Tasks::max_concurrent_integrity_scans`] to `max_scans` and
+    /// returns self.
+    #[must_use]
+    fn tasks_max_concurrent_integrity_scans(self, max_scans: usize) -> Self;
+    /// Sets [`Tasks::expiration_load_batch_size`] to `batch_size` and returns
+    /// self.
+    #[must_use]
+    fn tasks_expiration_load_batch_size(self, batch_size: usize) -> Self;
     /// Sets [`Views::check_integrity_on_open`] to `check` and returns self.
     #[must_use]
     fn check_view_integrity_on_open(self, check: bool) -> Self;
+    /// Inserts `check` into [`Views::collection_integrity_checks_on_open`] for
+    /// `C` and returns self.
+    #[must_use]
+    fn check_view_integrity_on_open_for_collection<C: Collection>(self, check: bool) -> Self;
+    /// Inserts `check` into [`Views::view_integrity_checks_on_open`] for `view`
+    /// and returns self.
+    #[must_use]
+    fn check_view_integrity_on_open_for_view<V: View>(self, view: &V, check: bool) -> Self;
     /// Sets [`StorageConfiguration::default_compression`](StorageConfiguration#structfield.default_compression) to `path` and returns self.
     #[cfg(feature = "compression")]
     #[must_use]
     fn default_compression(self, compression: Compression) -> Self;
+    /// Appends `dictionary` to the [`StorageConfiguration::compression_dictionaries`](StorageConfiguration#structfield.compression_dictionaries) for `C` and returns self.
+    #[cfg(feature = "compression")]
+    #[must_use]
+    fn compression_dictionary<C: Collection>(self, dictionary: CompressionDictionary) -> Self;
+    /// Sets [`StorageConfiguration::block_checksums`](StorageConfiguration#structfield.block_checksums) to `enabled` and returns self.
+    #[cfg(feature = "compression")]
+    #[must_use]
+    fn block_checksums(self, enabled: bool) -> Self;
     /// Sets [`StorageConfiguration::key_value_persistence`](StorageConfiguration#structfield.key_value_persistence) to `persistence` and returns self.
     #[must_use]
     fn key_value_persistence(self, persistence: KeyValuePersistence) -> Self;
+    /// Overrides the configuration of the database named `name` with
+    /// `configuration` and returns self.
+    #[must_use]
+    fn database_configuration(
+        self,
+        name: impl Into<String>,
+        configuration: DatabaseConfiguration,
+    ) -> Self;
+    /// Sets [`StorageConfiguration::key_value_clock`](StorageConfiguration#structfield.key_value_clock) to `clock` and returns self.
+    #[must_use]
+    fn key_value_clock(self, clock: KeyValueClock) -> Self;
+    /// Sets [`StorageConfiguration::quota_monitor`](StorageConfiguration#structfield.quota_monitor) to `monitor` and returns self.
+    #[must_use]
+    fn quota_monitor<M: QuotaMonitor>(self, monitor: M) -> Self;
+    /// Sets [`StorageConfiguration::scrub_monitor`](StorageConfiguration#structfield.scrub_monitor) to `monitor` and returns self.
+    #[must_use]
+    fn scrub_monitor<M: ScrubMonitor>(self, monitor: M) -> Self;
+    /// Sets [`StorageConfiguration::database_lifecycle`](StorageConfiguration#structfield.database_lifecycle) to `lifecycle` and returns self.
+    #[must_use]
+    fn database_lifecycle<L: DatabaseLifecycle>(self, lifecycle: L) -> Self;
+    /// Sets [`Subscribers::max_per_session`] to `max` and returns self.
+    #[must_use]
+    fn max_subscribers_per_session(self, max: usize) -> Self;
+    /// Sets [`StorageConfiguration::repair_mode`](StorageConfiguration#structfield.repair_mode) to `mode` and returns self.
+    #[must_use]
+    fn repair_mode(self, mode: RepairMode) -> Self;
+    /// Sets [`StorageConfiguration::max_document_size`](StorageConfiguration#structfield.max_document_size) to `bytes` and returns self.
+    #[must_use]
+    fn max_document_size(self, bytes: u64) -> Self;
+    /// Sets [`StorageConfiguration::lock_timeout`](StorageConfiguration#structfield.lock_timeout) to `timeout` and returns self.
+    #[must_use]
+    fn lock_timeout(self, timeout: Duration) -> Self;
+    /// Sets [`StorageConfiguration::replication_log`](StorageConfiguration#structfield.replication_log) to `enabled` and returns self.
+    #[must_use]
+    fn replication_log(self, enabled: bool) -> Self;
+    /// Inserts `priority` into [`StorageConfiguration::compaction_priorities`](StorageConfiguration#structfield.compaction_priorities)
+    /// for `C` and returns self.
+    #[must_use]
+    fn compaction_priority<C: Collection>(self, priority: CompactionPriority) -> Self;
+    /// Sets [`Subscribers::max_total`] to `max` and returns self.
+    #[must_use]
+    fn max_subscribers(self, max: usize) -> Self;
+    /// Sets the [`Admission`] limit of `category` to `max` and returns self.
+    #[must_use]
+    fn max_in_flight(self, category: WorkCategory, max: usize) -> Self;
+    /// Sets [`Admission::retry_after_hint`] to `hint` and returns self.
+    #[must_use]
+    fn overload_retry_after_hint(self, hint: Duration) -> Self;
     /// Sets [`Self::authenticated_permissions`](Self#structfield.authenticated_permissions) to `authenticated_permissions` and returns self.
     #[must_use]
     fn authenticated_permissions<P: Into<Permissions>>(self, authenticated_permissions: P) -> Self;
+    /// Sets [`StorageConfiguration::external_identities`](StorageConfiguration#structfield.external_identities) to `resolver` and returns self.
+    #[must_use]
+    fn external_identities<R: ExternalIdentityResolver>(self, resolver: R) -> Self;
     /// Sets [`StorageConfiguration::argon`](StorageConfiguration#structfield.argon) to `argon` and returns self.
     #[cfg(feature = "password-hashing")]
     #[must_use]
@@ -402,6 +917,11 @@ impl Builder for StorageConfiguration {
         Ok(self)
     }
 
+    fn with_admin_extension<S: Schema>(mut self) -> Result<Self, Error> {
+        self.register_admin_extension::<S>()?;
+        Ok(self)
+    }
+
     fn memory_only(mut self) -> Self {
         self.memory_only = true;
         self
@@ -438,6 +958,21 @@ impl Builder for StorageConfiguration {
         self
     }
 
+    #[cfg(feature = "compression")]
+    fn compression_dictionary<C: Collection>(mut self, dictionary: CompressionDictionary) -> Self {
+        self.compression_dictionaries
+            .entry(C::collection_name())
+            .or_default()
+            .push(dictionary);
+        self
+    }
+
+    #[cfg(feature = "compression")]
+    fn block_checksums(mut self, enabled: bool) -> Self {
+        self.block_checksums = enabled;
+        self
+    }
+
     fn tasks_worker_count(mut self, worker_count: usize) -> Self {
         self.workers.worker_count = worker_count;
         self
@@ -448,16 +983,115 @@ impl Builder for StorageConfiguration {
         self
     }
 
+    fn tasks_max_concurrent_integrity_scans(mut self, max_scans: usize) -> Self {
+        self.workers.max_concurrent_integrity_scans = max_scans;
+        self
+    }
+
+    fn tasks_expiration_load_batch_size(mut self, batch_size: usize) -> Self {
+        self.workers.expiration_load_batch_size = batch_size;
+        self
+    }
+
     fn check_view_integrity_on_open(mut self, check: bool) -> Self {
         self.views.check_integrity_on_open = check;
         self
     }
 
+    fn check_view_integrity_on_open_for_collection<C: Collection>(mut self, check: bool) -> Self {
+        self.views
+            .collection_integrity_checks_on_open
+            .insert(C::collection_name(), check);
+        self
+    }
+
+    fn check_view_integrity_on_open_for_view<V: View>(mut self, view: &V, check: bool) -> Self {
+        self.views
+            .view_integrity_checks_on_open
+            .insert(view.view_name(), check);
+        self
+    }
+
     fn key_value_persistence(mut self, persistence: KeyValuePersistence) -> Self {
         self.key_value_persistence = persistence;
         self
     }
 
+    fn database_configuration(
+        mut self,
+        name: impl Into<String>,
+        configuration: DatabaseConfiguration,
+    ) -> Self {
+        self.databases.insert(name.into(), configuration);
+        self
+    }
+
+    fn key_value_clock(mut self, clock: KeyValueClock) -> Self {
+        self.key_value_clock = clock;
+        self
+    }
+
+    fn quota_monitor<M: QuotaMonitor>(mut self, monitor: M) -> Self {
+        self.quota_monitor = Some(Arc::new(monitor));
+        self
+    }
+
+    fn scrub_monitor<M: ScrubMonitor>(mut self, monitor: M) -> Self {
+        self.scrub_monitor = Some(Arc::new(monitor));
+        self
+    }
+
+    fn database_lifecycle<L: DatabaseLifecycle>(mut self, lifecycle: L) -> Self {
+        self.database_lifecycle = Some(Arc::new(lifecycle));
+        self
+    }
+
+    fn max_subscribers_per_session(mut self, max: usize) -> Self {
+        self.subscribers.max_per_session = Some(max);
+        self
+    }
+
+    fn repair_mode(mut self, mode: RepairMode) -> Self {
+        self.repair_mode = Some(mode);
+        self
+    }
+
+    fn max_document_size(mut self, bytes: u64) -> Self {
+        self.max_document_size = Some(bytes);
+        self
+    }
+
+    fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    fn replication_log(mut self, enabled: bool) -> Self {
+        self.replication_log = enabled;
+        self
+    }
+
+    fn compaction_priority<C: Collection>(mut self, priority: CompactionPriority) -> Self {
+        self.compaction_priorities
+            .insert(C::collection_name(), priority);
+        self
+    }
+
+    fn max_subscribers(mut self, max: usize) -> Self {
+        self.subscribers.max_total = Some(max);
+        self
+    }
+
+    fn max_in_flight(mut self, category: WorkCategory, max: usize) -> Self {
+        self.admission.set_limit(category, Some(max));
+        self
+    }
+
+    fn overload_retry_after_hint(mut self, hint: Duration) -> Self {
+        self.admission.retry_after_hint = hint;
+        self
+    }
+
     fn authenticated_permissions<P: Into<Permissions>>(
         mut self,
         authenticated_permissions: P,
@@ -466,6 +1100,11 @@ impl Builder for StorageConfiguration {
         self
     }
 
+    fn external_identities<R: ExternalIdentityResolver>(mut self, resolver: R) -> Self {
+        self.external_identities = Some(Arc::new(resolver));
+        self
+    }
+
     #[cfg(feature = "password-hashing")]
     fn argon(mut self, argon: ArgonConfiguration) -> Self {
         self.argon = argon;
@@ -505,3 +1144,125 @@ impl Compression {
         }
     }
 }
+
+/// A dictionary that improves the compression ratio of small values that share
+/// similar contents.
+///
+/// Compressing a small value on its own is rarely effective, because there is
+/// little repetition within the value itself. When a dictionary is provided,
+/// repeated sequences can instead refer to the dictionary's contents. Each
+/// compressed value records the [`id()`](Self::id) of the dictionary used, so
+/// it can only be decompressed if the same dictionary is still configured.
+///
+/// Only the last 64KiB of a dictionary are used.
+#[cfg(feature = "compression")]
+#[derive(Clone)]
+pub struct CompressionDictionary {
+    id: u32,
+    bytes: Arc<[u8]>,
+}
+
+#[cfg(feature = "compression")]
+impl CompressionDictionary {
+    /// Returns a dictionary containing `bytes`.
+    #[must_use]
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        let bytes = bytes.into();
+        Self {
+            id: fnv1a(&bytes),
+            bytes: Arc::from(bytes),
+        }
+    }
+
+    /// Builds a dictionary of at most `max_size` bytes from `samples`.
+    ///
+    /// The dictionary is made up of the byte sequences that appear in the most
+    /// samples. The samples should be representative of the values that will
+    /// be compressed, such as a selection of serialized documents from the
+    /// collection the dictionary is for.
+    #[must_use]
+    pub fn train<'a, Samples: IntoIterator<Item = &'a [u8]>>(
+        samples: Samples,
+        max_size: usize,
+    ) -> Self {
+        const SEGMENT_LENGTH: usize = 8;
+        const OVERLAP: usize = SEGMENT_LENGTH - 1;
+
+        // Count the number of samples each segment appears in, noting the
+        // order segments were first seen in.
+        let mut segments = HashMap::<&'a [u8], (usize, usize)>::new();
+        for sample in samples {
+            let mut seen = HashSet::new();
+            for segment in sample.windows(SEGMENT_LENGTH) {
+                if seen.insert(segment) {
+                    let order = segments.len();
+                    segments.entry(segment).or_insert((0, order)).0 += 1;
+                }
+            }
+        }
+
+        // Sorting segments that appear equally often by the order they were
+        // seen keeps overlapping segments of longer sequences adjacent, which
+        // allows them to be joined back together.
+        let mut segments = segments.into_iter().collect::<Vec<_>>();
+        segments.sort_by(|(_, (a_count, a_order)), (_, (b_count, b_order))| {
+            b_count.cmp(a_count).then_with(|| a_order.cmp(b_order))
+        });
+
+        let mut dictionary = Vec::new();
+        let mut included = HashSet::<Vec<u8>>::new();
+        for (segment, _) in segments {
+            if included.contains(segment) {
+                continue;
+            }
+
+            let new_bytes = if dictionary.ends_with(&segment[..OVERLAP]) {
+                &segment[OVERLAP..]
+            } else {
+                segment
+            };
+            if dictionary.len() + new_bytes.len() > max_size {
+                break;
+            }
+
+            let start = dictionary.len().saturating_sub(OVERLAP);
+            dictionary.extend_from_slice(new_bytes);
+            for window in dictionary[start..].windows(SEGMENT_LENGTH) {
+                included.insert(window.to_vec());
+            }
+        }
+
+        Self::new(dictionary)
+    }
+
+    /// Returns the id of this dictionary, which is derived from its contents.
+    #[must_use]
+    pub const fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the contents of this dictionary.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(feature = "compression")]
+impl std::fmt::Debug for CompressionDictionary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompressionDictionary")
+            .field("id", &self.id)
+            .field("length", &self.bytes.len())
+            .finish()
+    }
+}
+
+/// Hashes `bytes` using 32-bit FNV-1a. This must never change, as the result
+/// is stored alongside data compressed using a [`CompressionDictionary`].
+#[cfg(feature = "compression")]
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
@@ -1,20 +1,24 @@
 use std::borrow::{Borrow, Cow};
+use std::cell::Cell;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::Infallible;
 use std::ops::{self, Deref};
 use std::sync::Arc;
+use std::time::Instant;
 use std::u8;
 
-use bonsaidb_core::arc_bytes::serde::CowBytes;
-use bonsaidb_core::arc_bytes::ArcBytes;
+use bonsaidb_core::admin::ADMIN_DATABASE_NAME;
+use bonsaidb_core::arc_bytes::serde::{Bytes, CowBytes};
+use bonsaidb_core::arc_bytes::{ArcBytes, OwnedBytes};
 use bonsaidb_core::connection::{
-    self, AccessPolicy, Connection, HasSchema, HasSession, LowLevelConnection, Range,
-    SerializedQueryKey, Session, Sort, StorageConnection,
+    self, AccessPolicy, Connection, DeletedDocuments, HasSchema, HasSession, LowLevelConnection,
+    Range, SerializedQueryKey, Session, Sort, StorageConnection,
 };
 #[cfg(any(feature = "encryption", feature = "compression"))]
 use bonsaidb_core::document::KeyId;
 use bonsaidb_core::document::{BorrowedDocument, DocumentId, Header, OwnedDocument, Revision};
-use bonsaidb_core::keyvalue::{KeyOperation, Output, Timestamp};
+use bonsaidb_core::key::KeyEncoding;
+use bonsaidb_core::keyvalue::{DurabilityMode, KeyEntry, KeyOperation, Output, SetCommand, Value};
 use bonsaidb_core::limits::{
     LIST_TRANSACTIONS_DEFAULT_RESULT_COUNT, LIST_TRANSACTIONS_MAX_RESULTS,
 };
@@ -43,22 +47,35 @@ use serde::{Deserialize, Serialize};
 use watchable::Watchable;
 
 use crate::config::{Builder, KeyValuePersistence, StorageConfiguration};
+use crate::database::activity::ActivityTracker;
 use crate::database::keyvalue::BackgroundWorkerProcessTarget;
+use crate::database::purge::PurgeState;
+use crate::database::quota::{QuotaState, UsageChanges};
+use crate::database::snapshot::SnapshotState;
 use crate::error::Error;
 use crate::open_trees::OpenTrees;
-use crate::storage::StorageLock;
 #[cfg(feature = "encryption")]
 use crate::storage::TreeVault;
+use crate::storage::{quarantine_tree_name, StorageLock, MIGRATION_BATCH_SIZE};
+use crate::tasks::handle::Handle;
+use crate::tasks::{Cancellation, ViewUpdateWait};
 use crate::views::{
-    mapper, view_document_map_tree_name, view_entries_tree_name, view_invalidated_docs_tree_name,
-    ViewEntry,
+    dependency_key, mapper, prefix_reductions, view_dependents_tree_name,
+    view_document_map_tree_name, view_entries_tree_name, view_invalidated_docs_tree_name,
+    view_prefix_reductions_tree_name, ViewEntry,
 };
 use crate::Storage;
 
+pub(crate) mod activity;
 pub mod keyvalue;
 
 pub(crate) mod compat;
+pub(crate) mod diff;
 pub mod pubsub;
+pub(crate) mod purge;
+pub(crate) mod quota;
+pub(crate) mod scrub;
+pub(crate) mod snapshot;
 
 /// A database stored in BonsaiDb. This type blocks the current thread when
 /// used. See [`AsyncDatabase`](crate::AsyncDatabase) for this type's async counterpart.
@@ -114,6 +131,7 @@ pub mod pubsub;
 pub struct Database {
     pub(crate) data: Arc<Data>,
     pub(crate) storage: Storage,
+    cancellation: Option<Cancellation>,
 }
 
 #[derive(Debug)]
@@ -121,6 +139,13 @@ pub struct Data {
     pub name: Arc<Cow<'static, str>>,
     context: Context,
     pub(crate) schema: Arc<Schematic>,
+    /// The newest transaction applied or observed using this handle. See
+    /// [`Connection::last_seen_transaction_id()`].
+    last_seen_transaction_id: Mutex<Option<u64>>,
+    /// Whether documents can't be modified because the schematic was
+    /// discovered from the stored data. See
+    /// [`Storage::with_schema_from_database()`].
+    documents_read_only: bool,
 }
 
 impl Database {
@@ -129,21 +154,37 @@ impl Database {
         name: S,
         context: Context,
         storage: &Storage,
+    ) -> Result<Self, Error> {
+        Self::with_schematic(name, context, storage, Arc::new(DB::schematic()?), false)
+    }
+
+    pub(crate) fn with_schematic<S: Into<Cow<'static, str>> + Send>(
+        name: S,
+        context: Context,
+        storage: &Storage,
+        schema: Arc<Schematic>,
+        documents_read_only: bool,
     ) -> Result<Self, Error> {
         let name = name.into();
-        let schema = Arc::new(DB::schematic()?);
         let db = Self {
             storage: storage.clone(),
             data: Arc::new(Data {
                 name: Arc::new(name),
                 context,
                 schema,
+                last_seen_transaction_id: Mutex::default(),
+                documents_read_only,
             }),
+            cancellation: None,
         };
+        db.finish_interrupted_purges()?;
 
-        if storage.instance.check_view_integrity_on_database_open() {
-            for view in db.data.schema.views() {
-                storage.instance.tasks().spawn_integrity_check(view, &db);
+        for view in db.data.schema.views() {
+            if storage
+                .instance
+                .check_view_integrity_on_database_open(&view.view_name())
+            {
+                storage.instance.tasks().schedule_integrity_check(view, &db);
             }
         }
 
@@ -164,9 +205,74 @@ impl Database {
             .map(|storage| Self {
                 storage,
                 data: self.data.clone(),
+                cancellation: self.cancellation.clone(),
             })
     }
 
+    /// Returns a clone of this instance whose view queries stop early once
+    /// `cancellation` is cancelled.
+    #[cfg(feature = "async")]
+    pub(crate) fn with_cancellation(&self, cancellation: Cancellation) -> Self {
+        Self {
+            cancellation: Some(cancellation),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a clone of this instance that isn't tied to the cancellation of
+    /// the current caller. This is used when handing the database to
+    /// background jobs, which may be shared with other callers.
+    pub(crate) fn without_cancellation(&self) -> Self {
+        Self {
+            cancellation: None,
+            ..self.clone()
+        }
+    }
+
+    /// Returns [`Error::InternalCommunication`] if the caller of this instance
+    /// is no longer waiting for the result.
+    pub(crate) fn check_cancelled(&self) -> Result<(), Error> {
+        self.cancellation
+            .as_ref()
+            .map_or(Ok(()), Cancellation::check)
+    }
+
+    /// Waits for `job` to complete, returning early if the caller of this
+    /// instance is no longer waiting for the result.
+    pub(crate) fn receive_job<T, E>(&self, job: Handle<T, E>) -> Result<Result<T, Arc<E>>, Error>
+    where
+        T: Send + Sync + 'static,
+        E: Send + Sync + 'static,
+    {
+        match &self.cancellation {
+            Some(cancellation) => cancellation.receive(job),
+            None => Ok(job.receive()?),
+        }
+    }
+
+    /// Behaves like [`Self::receive_job()`], returning `None` if `job` hasn't
+    /// completed by `deadline`.
+    pub(crate) fn receive_job_until<T, E>(
+        &self,
+        job: Handle<T, E>,
+        deadline: Instant,
+    ) -> Result<Option<Result<T, Arc<E>>>, Error>
+    where
+        T: Send + Sync + 'static,
+        E: Send + Sync + 'static,
+    {
+        match &self.cancellation {
+            Some(cancellation) => cancellation.receive_until(job, deadline),
+            None => match job.receiver.recv_deadline(deadline) {
+                Ok(result) => Ok(Some(result)),
+                Err(flume::RecvTimeoutError::Timeout) => Ok(None),
+                Err(flume::RecvTimeoutError::Disconnected) => {
+                    Err(Error::from(flume::RecvError::Disconnected))
+                }
+            },
+        }
+    }
+
     /// Creates a `Storage` with a single-database named "default" with its data
     /// stored at `path`. This requires exclusive access to the storage location
     /// configured. Attempting to open the same path multiple times concurrently
@@ -197,6 +303,281 @@ impl Database {
         &self.data.context.roots
     }
 
+    /// Returns true if a document with `id` exists in the collection `C`.
+    ///
+    /// Unlike retrieving the document, only the keys of the collection's
+    /// document tree are examined. The document's contents are never read or
+    /// deserialized. This requires the same permission as retrieving the
+    /// document.
+    pub fn contains_document<C, PrimaryKey>(
+        &self,
+        id: &PrimaryKey,
+    ) -> Result<bool, bonsaidb_core::Error>
+    where
+        C: schema::Collection,
+        PrimaryKey: KeyEncoding<C::PrimaryKey> + ?Sized,
+    {
+        let collection = C::collection_name();
+        let id = DocumentId::new(id)?;
+        self.check_permission(
+            document_resource_name(self.name(), &collection, &id),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
+        )?;
+        let _reads = self.begin_document_read();
+        let tree = self
+            .data
+            .context
+            .roots
+            .tree(
+                self.collection_tree::<Versioned, _>(&collection, document_tree_name(&collection))?,
+            )
+            .map_err(Error::from)?;
+        let key: &[u8] = id.as_ref();
+        let mut found = false;
+        tree.scan::<Infallible, _, _, _, _>(
+            &(key..=key),
+            true,
+            |_, _, _| ScanEvaluation::ReadData,
+            |_, _| {
+                found = true;
+                ScanEvaluation::Skip
+            },
+            |_, _, _| unreachable!(),
+        )
+        .map_err(Error::from)?;
+
+        Ok(found)
+    }
+
+    /// Returns an estimate of the number of documents in the collection `C`.
+    ///
+    /// The estimate is read from the statistics nebari maintains in the root
+    /// of the collection's document tree, so its cost doesn't depend on the
+    /// number of documents and no documents are read. Deleted documents are
+    /// not included. Transactions that are committing while the estimate is
+    /// read may not be reflected, making this suitable for display and
+    /// planning rather than for decisions that need an exact count. Use
+    /// [`List::count()`](bonsaidb_core::connection::List::count) for an exact
+    /// count of a range of documents.
+    ///
+    /// This requires the same permission as counting the collection's
+    /// documents.
+    pub fn estimated_row_count<C: schema::Collection>(&self) -> Result<u64, bonsaidb_core::Error> {
+        let collection = C::collection_name();
+        self.check_permission(
+            collection_resource_name(self.name(), &collection),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Count)),
+        )?;
+        let _reads = self.begin_document_read();
+        let tree = self
+            .data
+            .context
+            .roots
+            .tree(
+                self.collection_tree::<Versioned, _>(&collection, document_tree_name(&collection))?,
+            )
+            .map_err(Error::from)?;
+        let stats = tree.reduce(&(..)).map_err(Error::from)?;
+
+        Ok(stats.alive_keys)
+    }
+
+    /// Appends a new version of the document with `id` without reading its
+    /// current contents, returning the new version's header.
+    ///
+    /// `expected_revision` is the revision the caller expects to be current.
+    /// If it is `None`, the document must not exist yet. If the stored
+    /// document doesn't match the expectation,
+    /// [`Error::DocumentConflict`](bonsaidb_core::Error::DocumentConflict) is
+    /// returned containing the current header, allowing the caller to retry
+    /// against the latest version. If a revision is expected but the document
+    /// doesn't exist,
+    /// [`Error::DocumentNotFound`](bonsaidb_core::Error::DocumentNotFound) is
+    /// returned.
+    ///
+    /// Appending contents identical to the current version does not create a
+    /// new version, and the current header is returned.
+    pub fn append_document_version<C, PrimaryKey>(
+        &self,
+        id: &PrimaryKey,
+        expected_revision: Option<&Revision>,
+        contents: impl Into<Bytes>,
+    ) -> Result<Header, bonsaidb_core::Error>
+    where
+        C: schema::Collection,
+        PrimaryKey: KeyEncoding<C::PrimaryKey> + ?Sized,
+    {
+        let id = DocumentId::new(id)?;
+        let operation = match expected_revision {
+            Some(revision) => Operation::update(
+                C::collection_name(),
+                Header {
+                    id,
+                    revision: *revision,
+                },
+                contents,
+            ),
+            None => Operation::insert(C::collection_name(), Some(id), contents),
+        };
+        match self.apply_transaction(Transaction::from(operation))?.pop() {
+            Some(OperationResult::DocumentUpdated { header, .. }) => Ok(header),
+            other => unreachable!("unexpected operation result: {other:?}"),
+        }
+    }
+
+    /// Returns every stored version of the document with `id`, oldest first.
+    ///
+    /// Each write to a document is retained in its collection's versioned
+    /// tree, which allows a document to be used as an append-only log with
+    /// [`append_document_version()`](Self::append_document_version). If the
+    /// document was deleted, the versions written before it was deleted are
+    /// still returned. This requires the same permission as retrieving the
+    /// document.
+    ///
+    /// Each version in the versioned tree records the sequence of the version
+    /// written before it. The history is read by looking up the document's
+    /// latest sequence and following these links, so the cost is proportional
+    /// to the number of versions of this document rather than to the number
+    /// of writes to the collection.
+    pub fn document_history<C, PrimaryKey>(
+        &self,
+        id: &PrimaryKey,
+    ) -> Result<Vec<OwnedDocument>, bonsaidb_core::Error>
+    where
+        C: schema::Collection,
+        PrimaryKey: KeyEncoding<C::PrimaryKey> + ?Sized,
+    {
+        let collection = C::collection_name();
+        let id = DocumentId::new(id)?;
+        self.check_permission(
+            document_resource_name(self.name(), &collection, &id),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
+        )?;
+        let _reads = self.begin_document_read();
+        let tree = self
+            .data
+            .context
+            .roots
+            .tree(
+                self.collection_tree::<Versioned, _>(&collection, document_tree_name(&collection))?,
+            )
+            .map_err(Error::from)?;
+
+        // Find the sequence of the most recent write to this document.
+        let mut next_sequence = None;
+        let ids = DocumentIdRange(Range::from(id..=id));
+        tree.scan::<Infallible, _, _, _, _>(
+            &ids.borrow_as_bytes(),
+            true,
+            |_, _, _| ScanEvaluation::ReadData,
+            |_, index| {
+                next_sequence = Some(index.sequence_id);
+                ScanEvaluation::Skip
+            },
+            |_, _, _| unreachable!(),
+        )
+        .map_err(|err| match err {
+            AbortError::Other(never) => match never {},
+            AbortError::Nebari(err) => crate::Error::from(err),
+        })?;
+
+        // Walk backwards through the document's versions.
+        let mut versions = Vec::new();
+        while let Some(sequence) = next_sequence.take() {
+            tree.scan_sequences(
+                sequence..=sequence,
+                true,
+                &mut |entry| {
+                    next_sequence = entry.last_sequence;
+                    ScanEvaluation::ReadData
+                },
+                &mut |_, data| {
+                    // Entries without data are deletions.
+                    if !data.is_empty() {
+                        versions.push(
+                            deserialize_document(&data)
+                                .map(BorrowedDocument::into_owned)
+                                .map_err(AbortError::Other)?,
+                        );
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(|err| match err {
+                AbortError::Other(err) => err,
+                AbortError::Nebari(err) => crate::Error::from(err),
+            })?;
+        }
+        versions.reverse();
+
+        Ok(versions)
+    }
+
+    /// Maps every document in `C` using `map`, and reduces the values emitted
+    /// for each key using `reduce`, without creating a persistent view.
+    ///
+    /// Unlike a view, the results are not stored or updated incrementally:
+    /// each call scans and deserializes every document in the collection. This
+    /// is intended for ad-hoc queries and exploration. For queries that are
+    /// executed regularly, define a [`View`](schema::View) instead.
+    ///
+    /// This requires the same permission as listing the collection's
+    /// documents.
+    pub fn map_reduce<C, K, V, Map, Reduce>(
+        &self,
+        map: Map,
+        reduce: Reduce,
+    ) -> Result<BTreeMap<K, V>, bonsaidb_core::Error>
+    where
+        C: schema::SerializedCollection,
+        K: Ord,
+        Map: Fn(&C::Contents) -> Vec<(K, V)>,
+        Reduce: Fn(&[V]) -> V,
+    {
+        let collection = C::collection_name();
+        self.check_permission(
+            collection_resource_name(self.name(), &collection),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::List)),
+        )?;
+        let _reads = self.begin_document_read();
+        let tree = self
+            .data
+            .context
+            .roots
+            .tree(
+                self.collection_tree::<Versioned, _>(&collection, document_tree_name(&collection))?,
+            )
+            .map_err(Error::from)?;
+        let mut mapped = BTreeMap::<K, Vec<V>>::new();
+        tree.scan(
+            &(..),
+            true,
+            |_, _, _| ScanEvaluation::ReadData,
+            |_, _| ScanEvaluation::ReadData,
+            |_, _, doc| {
+                let doc = deserialize_document(&doc).map_err(AbortError::Other)?;
+                let contents = C::deserialize(&doc.contents)
+                    .map_err(|err| AbortError::Other(Error::Core(err)))?;
+                for (key, value) in map(&contents) {
+                    mapped.entry(key).or_default().push(value);
+                }
+                Ok(())
+            },
+        )
+        .map_err(|err| match err {
+            AbortError::Other(err) => err,
+            AbortError::Nebari(err) => crate::Error::from(err),
+        })?;
+
+        Ok(mapped
+            .into_iter()
+            .map(|(key, values)| {
+                let value = reduce(&values);
+                (key, value)
+            })
+            .collect())
+    }
+
     fn for_each_in_view<F: FnMut(ViewEntry) -> Result<(), bonsaidb_core::Error> + Send + Sync>(
         &self,
         view: &dyn view::Serialized,
@@ -206,21 +587,64 @@ impl Database {
         access_policy: AccessPolicy,
         mut callback: F,
     ) -> Result<(), bonsaidb_core::Error> {
-        if matches!(access_policy, AccessPolicy::UpdateBefore) {
-            self.storage
-                .instance
-                .tasks()
-                .update_view_if_needed(view, self, true)?;
-        } else if let Some(integrity_check) = self
-            .storage
-            .instance
-            .tasks()
-            .spawn_integrity_check(view, self)
-        {
-            integrity_check
-                .receive()
-                .map_err(Error::from)?
-                .map_err(Error::from)?;
+        self.query_view(view, access_policy, |view_entries| {
+            for entry in Self::create_view_iterator(view_entries, key, order, limit)? {
+                self.check_cancelled()?;
+                callback(entry)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Invokes `query` with the entries of `view`, updating the view before or
+    /// after the query as dictated by `access_policy`.
+    fn query_view<R, F: FnOnce(&Tree<Unversioned, AnyFile>) -> Result<R, bonsaidb_core::Error>>(
+        &self,
+        view: &dyn view::Serialized,
+        access_policy: AccessPolicy,
+        query: F,
+    ) -> Result<R, bonsaidb_core::Error> {
+        self.check_cancelled()?;
+        // A view's entries are derived from the documents it looks up, so
+        // querying it requires access to the collections it depends on.
+        for dependency in view.dependencies() {
+            self.check_permission(
+                collection_resource_name(self.name(), dependency),
+                &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
+            )?;
+        }
+        match access_policy {
+            AccessPolicy::UpdateBefore => {
+                self.storage
+                    .instance
+                    .tasks()
+                    .update_view_if_needed(view, self, true)?;
+            }
+            AccessPolicy::UpdateThrough {
+                transaction_id,
+                timeout,
+            } => {
+                let wait = Instant::now()
+                    .checked_add(timeout)
+                    .map_or(ViewUpdateWait::Forever, ViewUpdateWait::Until);
+                self.storage.instance.tasks().update_view_through(
+                    view,
+                    self,
+                    transaction_id,
+                    wait,
+                )?;
+            }
+            AccessPolicy::UpdateAfter | AccessPolicy::NoUpdate => {
+                if let Some(integrity_check) = self
+                    .storage
+                    .instance
+                    .tasks()
+                    .spawn_integrity_check(view, self)
+                {
+                    self.receive_job(integrity_check)?.map_err(Error::from)?;
+                }
+            }
         }
 
         let view_entries = self
@@ -231,11 +655,7 @@ impl Database {
             )?)
             .map_err(Error::from)?;
 
-        {
-            for entry in Self::create_view_iterator(&view_entries, key, order, limit)? {
-                callback(entry)?;
-            }
-        }
+        let result = query(&view_entries)?;
 
         if matches!(access_policy, AccessPolicy::UpdateAfter) {
             let db = self.clone();
@@ -251,7 +671,36 @@ impl Database {
                 .update_view_if_needed(view, &db, false)?;
         }
 
-        Ok(())
+        Ok(result)
+    }
+
+    /// Returns the values needed to reduce `range`, using the cached
+    /// reductions of the key prefixes contained within the range.
+    fn reduce_grouped_by_prefix(
+        &self,
+        view: &dyn view::Serialized,
+        range: &Range<Bytes>,
+        access_policy: AccessPolicy,
+    ) -> Result<Vec<MappedSerializedValue>, bonsaidb_core::Error> {
+        self.check_permission(
+            view_resource_name(self.name(), &view.view_name()),
+            &BonsaiAction::Database(DatabaseAction::View(ViewAction::Reduce)),
+        )?;
+        self.query_view(view, access_policy, |view_entries| {
+            let prefix_reductions = self
+                .roots()
+                .tree(self.collection_tree(
+                    &view.collection(),
+                    view_prefix_reductions_tree_name(&view.view_name()),
+                )?)
+                .map_err(Error::from)?;
+            Ok(prefix_reductions::reduce_range(
+                view,
+                view_entries,
+                &prefix_reductions,
+                range,
+            )?)
+        })
     }
 
     fn open_trees_for_transaction(&self, transaction: &Transaction) -> Result<OpenTrees, Error> {
@@ -266,12 +715,38 @@ impl Database {
                 return Err(Error::Core(bonsaidb_core::Error::CollectionNotFound));
             }
 
-            #[cfg(any(feature = "encryption", feature = "compression"))]
-            let vault = if let Some(encryption_key) =
-                self.collection_encryption_key(&op.collection).cloned()
-            {
+            open_trees.open_trees_for_document_change(
+                &op.collection,
+                &self.data.schema,
+                #[cfg(any(feature = "encryption", feature = "compression"))]
+                self.transaction_tree_vault(&op.collection)?,
+            );
+
+            for view in self.data.schema.views_depending_on(&op.collection) {
+                open_trees.open_trees_for_dependency_change(
+                    &view.view_name(),
+                    #[cfg(any(feature = "encryption", feature = "compression"))]
+                    self.transaction_tree_vault(&view.collection())?,
+                );
+            }
+        }
+
+        Ok(open_trees)
+    }
+
+    #[cfg(any(feature = "encryption", feature = "compression"))]
+    #[cfg_attr(feature = "encryption", allow(clippy::unnecessary_wraps))]
+    pub(crate) fn transaction_tree_vault(
+        &self,
+        collection: &CollectionName,
+    ) -> Result<Option<TreeVault>, Error> {
+        let vault =
+            if let Some(encryption_key) = self.collection_encryption_key(collection).cloned() {
                 #[cfg(feature = "encryption")]
-                if let Some(mut vault) = self.storage().tree_vault().cloned() {
+                if let Some(mut vault) = self
+                    .storage()
+                    .collection_tree_vault(self.name(), collection)
+                {
                     vault.key = Some(encryption_key);
                     Some(vault)
                 } else {
@@ -289,25 +764,162 @@ impl Database {
                     return Err(Error::EncryptionDisabled);
                 }
             } else {
-                self.storage().tree_vault().cloned()
+                self.storage()
+                    .collection_tree_vault(self.name(), collection)
             };
 
-            open_trees.open_trees_for_document_change(
-                &op.collection,
-                &self.data.schema,
-                #[cfg(any(feature = "encryption", feature = "compression"))]
-                vault,
-            );
+        Ok(vault)
+    }
+
+    /// Returns [`Error::ReadOnly`](bonsaidb_core::Error::ReadOnly) if this
+    /// database can't currently be modified because its storage is
+    /// [read-only](Storage::set_read_only). The admin database is always
+    /// writable.
+    pub(crate) fn check_writable(&self) -> Result<(), bonsaidb_core::Error> {
+        if self.storage.instance.is_read_only() && self.name() != ADMIN_DATABASE_NAME {
+            Err(bonsaidb_core::Error::ReadOnly)
+        } else {
+            Ok(())
         }
+    }
 
-        Ok(open_trees)
+    /// Returns [`Error::ReadOnly`](bonsaidb_core::Error::ReadOnly) if this
+    /// database's documents can't currently be modified. In addition to the
+    /// checks made by [`Self::check_writable()`], the documents of a database
+    /// opened with a schematic discovered from its stored data are read-only:
+    /// the views and encryption keys of its collections are unknown, so
+    /// writing documents would leave views stale and store contents
+    /// unencrypted.
+    pub(crate) fn check_documents_writable(&self) -> Result<(), bonsaidb_core::Error> {
+        if self.data.documents_read_only {
+            Err(bonsaidb_core::Error::ReadOnly)
+        } else {
+            self.check_writable()
+        }
+    }
+
+    /// Applies `transaction`, returning the id the transaction was assigned
+    /// along with the result of each of its operations.
+    pub fn apply_transaction_with_id(
+        &self,
+        transaction: Transaction,
+    ) -> Result<(u64, Vec<OperationResult>), bonsaidb_core::Error> {
+        self.check_documents_writable()?;
+        self.apply_transaction_returning_id(transaction, None)
+    }
+
+    /// Applies `transaction` even if the storage is read-only. This is used to
+    /// restore backups and replicate changes into read-only storage.
+    pub(crate) fn apply_transaction_bypassing_read_only(
+        &self,
+        transaction: Transaction,
+    ) -> Result<Vec<OperationResult>, bonsaidb_core::Error> {
+        self.apply_transaction_returning_id(transaction, None)
+            .map(|(_, results)| results)
+    }
+
+    /// Applies `transaction` even if the storage is read-only, returning the
+    /// id the transaction was assigned along with its results.
+    ///
+    /// If `restoring` is provided, the document is removed from its
+    /// collection's quarantine as part of the same transaction.
+    pub(crate) fn apply_transaction_returning_id(
+        &self,
+        transaction: Transaction,
+        restoring: Option<(&CollectionName, &DocumentId)>,
+    ) -> Result<(u64, Vec<OperationResult>), bonsaidb_core::Error> {
+        for op in &transaction.operations {
+            let (resource, action) = match &op.command {
+                Command::Insert { .. } => (
+                    collection_resource_name(self.name(), &op.collection),
+                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Insert)),
+                ),
+                Command::Update { header, .. } => (
+                    document_resource_name(self.name(), &op.collection, &header.id),
+                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Update)),
+                ),
+                Command::Overwrite { id, .. } => (
+                    document_resource_name(self.name(), &op.collection, id),
+                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Overwrite)),
+                ),
+                Command::Delete { header } => (
+                    document_resource_name(self.name(), &op.collection, &header.id),
+                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Delete)),
+                ),
+                Command::Check { id, .. } => (
+                    document_resource_name(self.name(), &op.collection, id),
+                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
+                ),
+            };
+            self.check_permission(resource, &action)?;
+        }
+
+        let mut eager_view_tasks = Vec::new();
+        for collection_name in transaction
+            .operations
+            .iter()
+            .map(|op| &op.collection)
+            .collect::<HashSet<_>>()
+        {
+            for view in self.data.schema.eager_views_in_collection(collection_name) {
+                if let Some(task) = self
+                    .storage
+                    .instance
+                    .tasks()
+                    .spawn_integrity_check(view, self)
+                {
+                    eager_view_tasks.push(task);
+                }
+            }
+        }
+
+        let mut eager_view_mapping_tasks = Vec::new();
+        for task in eager_view_tasks {
+            if let Some(spawned_task) = task.receive().map_err(Error::from)?.map_err(Error::from)? {
+                eager_view_mapping_tasks.push(spawned_task);
+            }
+        }
+
+        for task in eager_view_mapping_tasks {
+            let mut task = task.lock();
+            if let Some(task) = task.take() {
+                task.receive().map_err(Error::from)?.map_err(Error::from)?;
+            }
+        }
+
+        let (transaction_id, results) = self
+            .apply_transaction_to_roots(&transaction, restoring)
+            .map_err(bonsaidb_core::Error::from)?;
+        self.saw_transaction(Some(transaction_id));
+        Ok((transaction_id, results))
+    }
+
+    /// Records that `transaction_id` has been applied or observed using this
+    /// handle.
+    pub(crate) fn saw_transaction(&self, transaction_id: Option<u64>) {
+        if let Some(transaction_id) = transaction_id {
+            let mut last_seen = self.data.last_seen_transaction_id.lock();
+            *last_seen = Some(last_seen.map_or(transaction_id, |last| last.max(transaction_id)));
+        }
     }
 
     fn apply_transaction_to_roots(
         &self,
         transaction: &Transaction,
-    ) -> Result<Vec<OperationResult>, Error> {
-        let open_trees = self.open_trees_for_transaction(transaction)?;
+        restoring: Option<(&CollectionName, &DocumentId)>,
+    ) -> Result<(u64, Vec<OperationResult>), Error> {
+        let writes = self.data.context.purges.begin_write();
+        // The quota must be acquired before the transaction begins, as
+        // measuring usage reads the trees being modified.
+        let quota = self.begin_quota_write()?;
+        let mut open_trees = self.open_trees_for_transaction(transaction)?;
+        if let Some((collection, _)) = restoring {
+            open_trees.open_tree::<Unversioned>(
+                &quarantine_tree_name(collection),
+                #[cfg(any(feature = "encryption", feature = "compression"))]
+                self.transaction_tree_vault(collection)?,
+            );
+        }
 
         let mut roots_transaction = self
             .data
@@ -315,6 +927,7 @@ impl Database {
             .roots
             .transaction::<_, dyn AnyTreeRoot<AnyFile>>(&open_trees.trees)?;
 
+        let mut usage_changes = UsageChanges::default();
         let mut results = Vec::new();
         let mut changed_documents = Vec::new();
         let mut collection_indexes = HashMap::new();
@@ -324,6 +937,7 @@ impl Database {
                 op,
                 &mut roots_transaction,
                 &open_trees.trees_index_by_name,
+                &mut usage_changes,
             )?;
 
             if let Some((collection, id, deleted)) = match &result {
@@ -347,10 +961,21 @@ impl Database {
                         }
                     }
                 };
+                let contents = if self.data.context.replication_log && !deleted {
+                    match &op.command {
+                        Command::Insert { contents, .. }
+                        | Command::Update { contents, .. }
+                        | Command::Overwrite { contents, .. } => Some(contents.clone()),
+                        Command::Delete { .. } | Command::Check { .. } => None,
+                    }
+                } else {
+                    None
+                };
                 changed_documents.push(ChangedDocument {
                     collection,
                     id,
                     deleted,
+                    contents,
                 });
             }
             results.push(result);
@@ -363,6 +988,15 @@ impl Database {
             &changed_documents,
         )?;
 
+        if let Some((collection, id)) = restoring {
+            roots_transaction
+                .tree::<Unversioned>(
+                    open_trees.trees_index_by_name[&quarantine_tree_name(collection)],
+                )
+                .unwrap()
+                .remove(id.as_ref())?;
+        }
+
         roots_transaction
             .entry_mut()
             .set_data(compat::serialize_executed_transaction_changes(
@@ -372,9 +1006,22 @@ impl Database {
                 }),
             )?)?;
 
+        if let Some(quota) = &quota {
+            quota.check(&usage_changes)?;
+        }
+
+        let transaction_id = roots_transaction.entry().id;
+        let commit = self.data.context.snapshots.begin_commit();
         roots_transaction.commit()?;
+        drop(commit);
+        drop(writes);
+        self.data.context.activity.transaction_committed();
 
-        Ok(results)
+        if let Some(quota) = quota {
+            quota.apply(&usage_changes);
+        }
+
+        Ok((transaction_id, results))
     }
 
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
@@ -409,6 +1056,54 @@ impl Database {
                 }
             }
         }
+
+        // Views that look up documents from a changed collection invalidate
+        // the documents that looked up each changed document.
+        for changed_document in changed_documents {
+            let collection = &collections[usize::from(changed_document.collection)];
+            for view in self.data.schema.views_depending_on(collection) {
+                Self::invalidate_dependents(
+                    roots_transaction,
+                    open_trees,
+                    &view.view_name(),
+                    &dependency_key(collection, &changed_document.id),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Invalidates the documents of `view_name` that looked up the dependency
+    /// stored under `key`. The record is removed, because the invalidated
+    /// documents record their lookups again when they are mapped.
+    fn invalidate_dependents(
+        roots_transaction: &mut ExecutingTransaction<AnyFile>,
+        open_trees: &OpenTrees,
+        view_name: &ViewName,
+        key: &ArcBytes<'static>,
+    ) -> Result<(), Error> {
+        let dependents = roots_transaction
+            .tree::<Unversioned>(
+                open_trees.trees_index_by_name[&view_dependents_tree_name(view_name)],
+            )
+            .unwrap()
+            .remove(key.as_slice())?;
+        if let Some(dependents) = dependents {
+            let mut document_ids = bincode::deserialize::<HashSet<OwnedBytes>>(&dependents)?
+                .into_iter()
+                .map(|id| id.0)
+                .collect::<Vec<_>>();
+            document_ids.sort();
+            let mut invalidated_docs = roots_transaction
+                .tree::<Unversioned>(
+                    open_trees.trees_index_by_name[&view_invalidated_docs_tree_name(view_name)],
+                )
+                .unwrap();
+            invalidated_docs.modify(
+                document_ids,
+                nebari::tree::Operation::Set(ArcBytes::default()),
+            )?;
+        }
         Ok(())
     }
 
@@ -417,11 +1112,17 @@ impl Database {
         operation: &Operation,
         transaction: &mut ExecutingTransaction<AnyFile>,
         tree_index_map: &HashMap<String, usize>,
+        usage_changes: &mut UsageChanges,
     ) -> Result<OperationResult, Error> {
         match &operation.command {
-            Command::Insert { id, contents } => {
-                self.execute_insert(operation, transaction, tree_index_map, id.clone(), contents)
-            }
+            Command::Insert { id, contents } => self.execute_insert(
+                operation,
+                transaction,
+                tree_index_map,
+                id.clone(),
+                contents,
+                usage_changes,
+            ),
             Command::Update { header, contents } => self.execute_update(
                 operation,
                 transaction,
@@ -429,13 +1130,24 @@ impl Database {
                 &header.id,
                 Some(&header.revision),
                 contents,
+                usage_changes,
+            ),
+            Command::Overwrite { id, contents } => self.execute_update(
+                operation,
+                transaction,
+                tree_index_map,
+                id,
+                None,
+                contents,
+                usage_changes,
+            ),
+            Command::Delete { header } => self.execute_delete(
+                operation,
+                transaction,
+                tree_index_map,
+                header,
+                usage_changes,
             ),
-            Command::Overwrite { id, contents } => {
-                self.execute_update(operation, transaction, tree_index_map, id, None, contents)
-            }
-            Command::Delete { header } => {
-                self.execute_delete(operation, transaction, tree_index_map, header)
-            }
             Command::Check { id, revision } => Self::execute_check(
                 operation,
                 transaction,
@@ -450,7 +1162,7 @@ impl Database {
         feature = "tracing",
         tracing::instrument(
             level = "trace",
-            skip(self, operation, transaction, tree_index_map, contents),
+            skip(self, operation, transaction, tree_index_map, contents, usage_changes),
             fields(
                 database = self.name(),
                 collection.name = operation.collection.name.as_ref(),
@@ -458,6 +1170,7 @@ impl Database {
             )
         )
     )]
+    #[allow(clippy::too_many_arguments)]
     fn execute_update(
         &self,
         operation: &Operation,
@@ -466,11 +1179,13 @@ impl Database {
         id: &DocumentId,
         check_revision: Option<&Revision>,
         contents: &[u8],
+        usage_changes: &mut UsageChanges,
     ) -> Result<OperationResult, crate::Error> {
         let mut documents = transaction
             .tree::<Versioned>(tree_index_map[&document_tree_name(&operation.collection)])
             .unwrap();
         let document_id = ArcBytes::from(id.to_vec());
+        let size_limit = self.max_document_size(&operation.collection);
         let mut result = None;
         let mut updated = false;
         documents.modify(
@@ -488,6 +1203,12 @@ impl Database {
                         }
                     };
                     if check_revision.is_none() || Some(&doc.header.revision) == check_revision {
+                        if let Err(err) =
+                            check_document_size(size_limit, contents, Some(doc.contents.len()))
+                        {
+                            result = Some(Err(err));
+                            return nebari::tree::KeyOperation::Skip;
+                        }
                         if let Some(updated_revision) = doc.header.revision.next_revision(contents)
                         {
                             let updated_header = Header {
@@ -509,6 +1230,11 @@ impl Database {
                                 header: updated_header,
                             }));
                             updated = true;
+                            usage_changes.document_written(
+                                &operation.collection,
+                                serialized_doc.len(),
+                                false,
+                            );
                             return nebari::tree::KeyOperation::Set(ArcBytes::from(serialized_doc));
                         }
 
@@ -527,6 +1253,10 @@ impl Database {
                         ))));
                     }
                 } else if check_revision.is_none() {
+                    if let Err(err) = check_document_size(size_limit, contents, None) {
+                        result = Some(Err(err));
+                        return nebari::tree::KeyOperation::Skip;
+                    }
                     let doc = BorrowedDocument::new(id.clone(), contents);
                     match serialize_document(&doc).map(|bytes| (doc, bytes)) {
                         Ok((doc, serialized)) => {
@@ -535,6 +1265,11 @@ impl Database {
                                 header: doc.header,
                             }));
                             updated = true;
+                            usage_changes.document_written(
+                                &operation.collection,
+                                serialized.len(),
+                                true,
+                            );
                             return nebari::tree::KeyOperation::Set(ArcBytes::from(serialized));
                         }
                         Err(err) => {
@@ -563,7 +1298,7 @@ impl Database {
         feature = "tracing",
         tracing::instrument(
             level = "trace",
-            skip(self, operation, transaction, tree_index_map, contents),
+            skip(self, operation, transaction, tree_index_map, contents, usage_changes),
             fields(
                 database = self.name(),
                 collection.name = operation.collection.name.as_ref(),
@@ -578,6 +1313,7 @@ impl Database {
         tree_index_map: &HashMap<String, usize>,
         id: Option<DocumentId>,
         contents: &[u8],
+        usage_changes: &mut UsageChanges,
     ) -> Result<OperationResult, Error> {
         let mut documents = transaction
             .tree::<Versioned>(tree_index_map[&document_tree_name(&operation.collection)])
@@ -595,8 +1331,14 @@ impl Database {
                 .next_id_for_collection(&operation.collection, None)?
         };
 
+        check_document_size(
+            self.max_document_size(&operation.collection),
+            contents,
+            None,
+        )?;
         let doc = BorrowedDocument::new(id, contents);
         let serialized: Vec<u8> = serialize_document(&doc)?;
+        usage_changes.document_written(&operation.collection, serialized.len(), true);
         let document_id = ArcBytes::from(doc.header.id.as_ref().to_vec());
         if let Some(document) = documents.replace(document_id.clone(), serialized)? {
             let doc = deserialize_document(&document)?;
@@ -617,7 +1359,7 @@ impl Database {
 
     #[cfg_attr(feature = "tracing", tracing::instrument(
         level = "trace",
-        skip(self, operation, transaction, tree_index_map),
+        skip(self, operation, transaction, tree_index_map, usage_changes),
         fields(
             database = self.name(),
             collection.name = operation.collection.name.as_ref(),
@@ -630,6 +1372,7 @@ impl Database {
         transaction: &mut ExecutingTransaction<AnyFile>,
         tree_index_map: &HashMap<String, usize>,
         header: &Header,
+        usage_changes: &mut UsageChanges,
     ) -> Result<OperationResult, Error> {
         let mut documents = transaction
             .tree::<Versioned>(tree_index_map[&document_tree_name(&operation.collection)])
@@ -644,6 +1387,7 @@ impl Database {
                     transaction,
                     tree_index_map,
                 )?;
+                usage_changes.document_deleted(&operation.collection);
 
                 Ok(OperationResult::DocumentDeleted {
                     collection: operation.collection.clone(),
@@ -696,6 +1440,16 @@ impl Database {
                 let view_entries = transaction
                     .unlocked_tree(tree_index_map[&view_entries_tree_name(&name)])
                     .unwrap();
+                let prefix_reductions = view.caches_reduce_prefixes().then(|| {
+                    transaction
+                        .unlocked_tree(tree_index_map[&view_prefix_reductions_tree_name(&name)])
+                        .unwrap()
+                });
+                let dependents = (!view.dependencies().is_empty()).then(|| {
+                    transaction
+                        .unlocked_tree(tree_index_map[&view_dependents_tree_name(&name)])
+                        .unwrap()
+                });
                 mapper::DocumentRequest {
                     database: self,
                     document_ids: vec![document_id.clone()],
@@ -707,6 +1461,8 @@ impl Database {
                     document_map,
                     documents,
                     view_entries,
+                    prefix_reductions,
+                    dependents,
                     view,
                 }
                 .map()?;
@@ -858,7 +1614,8 @@ impl Database {
         #[cfg(any(feature = "encryption", feature = "compression"))]
         match (
             self.collection_encryption_key(collection),
-            self.storage().tree_vault().cloned(),
+            self.storage()
+                .collection_tree_vault(self.name(), collection),
         ) {
             (Some(override_key), Some(mut vault)) => {
                 #[cfg(feature = "encryption")]
@@ -896,14 +1653,69 @@ impl Database {
         Ok(tree)
     }
 
-    pub(crate) fn update_key_expiration<'key>(
-        &self,
-        tree_key: impl Into<Cow<'key, str>>,
-        expiration: Option<Timestamp>,
-    ) {
-        self.data
-            .context
-            .update_key_expiration(tree_key, expiration);
+    /// Rewrites each document stored using the encoding from before headers
+    /// were versioned, leaving its header and contents unchanged.
+    ///
+    /// Documents are migrated [`MIGRATION_BATCH_SIZE`] at a time, and each
+    /// batch is rewritten while transactions and document reads are blocked.
+    pub(crate) fn reencode_legacy_documents(&self) -> Result<(), Error> {
+        let tree_names = self.roots().tree_names()?;
+        for collection in self.schematic().collections() {
+            let tree_name = document_tree_name(collection);
+            if !tree_names.contains(&tree_name) {
+                continue;
+            }
+
+            let tree = self
+                .roots()
+                .tree(self.collection_tree::<Versioned, _>(collection, tree_name)?)?;
+            let mut last_key = None::<ArcBytes<'static>>;
+            loop {
+                // Block transactions and document reads while this batch is
+                // rewritten, as purges do.
+                let access = self.data.context.purges.access.write();
+                let start = last_key
+                    .as_deref()
+                    .map_or(ops::Bound::Unbounded, ops::Bound::Excluded);
+                let examined = Cell::new(0);
+                let mut batch_last_key = None;
+                let mut legacy_documents = Vec::new();
+                tree.scan::<Error, _, _, _, _>(
+                    &(start, ops::Bound::Unbounded),
+                    true,
+                    |_, _, _| ScanEvaluation::ReadData,
+                    |_, _| {
+                        if examined.get() < MIGRATION_BATCH_SIZE {
+                            examined.set(examined.get() + 1);
+                            ScanEvaluation::ReadData
+                        } else {
+                            ScanEvaluation::Stop
+                        }
+                    },
+                    |key, _, bytes: ArcBytes<'static>| {
+                        batch_last_key = Some(key.clone());
+                        if pot::from_slice::<BorrowedDocument<'_>>(&bytes).is_err() {
+                            let document =
+                                deserialize_document(&bytes).map_err(AbortError::Other)?;
+                            let reencoded = serialize_document(&document)
+                                .map_err(|err| AbortError::Other(Error::Core(err)))?;
+                            legacy_documents.push((key, reencoded));
+                        }
+                        Ok(())
+                    },
+                )?;
+                for (key, document) in legacy_documents {
+                    tree.set(key, document)?;
+                }
+                drop(access);
+
+                if batch_last_key.is_none() {
+                    break;
+                }
+                last_key = batch_last_key;
+            }
+        }
+        Ok(())
     }
 
     /// Converts this instance into its blocking version, which is able to be
@@ -1075,7 +1887,13 @@ impl Connection for Database {
             database_resource_name(self.name()),
             &BonsaiAction::Database(DatabaseAction::Transaction(TransactionAction::GetLastId)),
         )?;
-        Ok(self.roots().transactions().current_transaction_id())
+        let transaction_id = self.roots().transactions().current_transaction_id();
+        self.saw_transaction(transaction_id);
+        Ok(transaction_id)
+    }
+
+    fn last_seen_transaction_id(&self) -> Option<u64> {
+        *self.data.last_seen_transaction_id.lock()
     }
 
     #[cfg_attr(feature = "tracing", tracing::instrument(
@@ -1090,10 +1908,12 @@ impl Connection for Database {
             database_resource_name(self.name()),
             &BonsaiAction::Database(DatabaseAction::Compact),
         )?;
+        let compaction = self.data.context.snapshots.begin_compaction()?;
         self.storage()
             .instance
             .tasks()
             .compact_database(self.clone())?;
+        drop(compaction);
         Ok(())
     }
 
@@ -1129,67 +1949,8 @@ impl LowLevelConnection for Database {
         &self,
         transaction: Transaction,
     ) -> Result<Vec<OperationResult>, bonsaidb_core::Error> {
-        for op in &transaction.operations {
-            let (resource, action) = match &op.command {
-                Command::Insert { .. } => (
-                    collection_resource_name(self.name(), &op.collection),
-                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Insert)),
-                ),
-                Command::Update { header, .. } => (
-                    document_resource_name(self.name(), &op.collection, &header.id),
-                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Update)),
-                ),
-                Command::Overwrite { id, .. } => (
-                    document_resource_name(self.name(), &op.collection, id),
-                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Overwrite)),
-                ),
-                Command::Delete { header } => (
-                    document_resource_name(self.name(), &op.collection, &header.id),
-                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Delete)),
-                ),
-                Command::Check { id, .. } => (
-                    document_resource_name(self.name(), &op.collection, id),
-                    BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
-                ),
-            };
-            self.check_permission(resource, &action)?;
-        }
-
-        let mut eager_view_tasks = Vec::new();
-        for collection_name in transaction
-            .operations
-            .iter()
-            .map(|op| &op.collection)
-            .collect::<HashSet<_>>()
-        {
-            for view in self.data.schema.eager_views_in_collection(collection_name) {
-                if let Some(task) = self
-                    .storage
-                    .instance
-                    .tasks()
-                    .spawn_integrity_check(view, self)
-                {
-                    eager_view_tasks.push(task);
-                }
-            }
-        }
-
-        let mut eager_view_mapping_tasks = Vec::new();
-        for task in eager_view_tasks {
-            if let Some(spawned_task) = task.receive().map_err(Error::from)?.map_err(Error::from)? {
-                eager_view_mapping_tasks.push(spawned_task);
-            }
-        }
-
-        for task in eager_view_mapping_tasks {
-            let mut task = task.lock();
-            if let Some(task) = task.take() {
-                task.receive().map_err(Error::from)?.map_err(Error::from)?;
-            }
-        }
-
-        self.apply_transaction_to_roots(&transaction)
-            .map_err(bonsaidb_core::Error::from)
+        self.check_documents_writable()?;
+        self.apply_transaction_bypassing_read_only(transaction)
     }
 
     #[cfg_attr(feature = "tracing", tracing::instrument(
@@ -1210,6 +1971,7 @@ impl LowLevelConnection for Database {
             document_resource_name(self.name(), collection, &id),
             &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
         )?;
+        let _reads = self.begin_document_read();
         let tree = self
             .data
             .context
@@ -1243,6 +2005,7 @@ impl LowLevelConnection for Database {
             collection_resource_name(self.name(), collection),
             &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::List)),
         )?;
+        let _reads = self.begin_document_read();
         let tree = self
             .data
             .context
@@ -1306,6 +2069,7 @@ impl LowLevelConnection for Database {
             collection_resource_name(self.name(), collection),
             &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::ListHeaders)),
         )?;
+        let _reads = self.begin_document_read();
         let tree = self
             .data
             .context
@@ -1367,6 +2131,7 @@ impl LowLevelConnection for Database {
             collection_resource_name(self.name(), collection),
             &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Count)),
         )?;
+        let _reads = self.begin_document_read();
         let tree = self
             .data
             .context
@@ -1401,6 +2166,7 @@ impl LowLevelConnection for Database {
         }
         let mut ids = ids.to_vec();
         let collection = collection.clone();
+        let _reads = self.begin_document_read();
         let tree = self
             .data
             .context
@@ -1438,10 +2204,12 @@ impl LowLevelConnection for Database {
             collection_resource_name(self.name(), &collection),
             &BonsaiAction::Database(DatabaseAction::Compact),
         )?;
+        let compaction = self.data.context.snapshots.begin_compaction()?;
         self.storage()
             .instance
             .tasks()
             .compact_collection(self.clone(), collection)?;
+        drop(compaction);
         Ok(())
     }
 
@@ -1468,6 +2236,11 @@ impl LowLevelConnection for Database {
             view_resource_name(self.name(), &view.view_name()),
             &BonsaiAction::Database(DatabaseAction::View(ViewAction::Query)),
         )?;
+        // Mappings expose the ids of the source collection's documents.
+        self.check_permission(
+            collection_resource_name(self.name(), &view.collection()),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::List)),
+        )?;
         let mut results = Vec::new();
         self.for_each_in_view(view, key, order, limit, access_policy, |entry| {
             for mapping in entry.mappings {
@@ -1540,12 +2313,20 @@ impl LowLevelConnection for Database {
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
     ) -> Result<Vec<u8>, bonsaidb_core::Error> {
-        let mut mappings = self.reduce_grouped_by_name(view_name, key, access_policy)?;
+        let view = self.data.schema.view_by_name(view_name)?;
+        let mut mappings = match key {
+            Some(SerializedQueryKey::Range(range)) if view.caches_reduce_prefixes() => {
+                self.reduce_grouped_by_prefix(view, &range, access_policy)?
+            }
+            None if view.caches_reduce_prefixes() => {
+                self.reduce_grouped_by_prefix(view, &Range::default(), access_policy)?
+            }
+            key => self.reduce_grouped_by_name(view_name, key, access_policy)?,
+        };
 
         let result = if mappings.len() == 1 {
             mappings.pop().unwrap().value.into_vec()
         } else {
-            let view = self.data.schema.view_by_name(view_name)?;
             view.reduce(
                 &mappings
                     .iter()
@@ -1607,21 +2388,42 @@ impl LowLevelConnection for Database {
         view: &ViewName,
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, bonsaidb_core::Error> {
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, bonsaidb_core::Error> {
         let view = self.data.schema.view_by_name(view)?;
         let collection = view.collection();
         let mut transaction = Transaction::default();
+        let mut headers = include_headers.then(Vec::new);
         self.for_each_in_view(view, key, Sort::Ascending, None, access_policy, |entry| {
             for mapping in entry.mappings {
+                if let Some(headers) = &mut headers {
+                    headers.push(mapping.source.clone());
+                }
                 transaction.push(Operation::delete(collection.clone(), mapping.source));
             }
 
             Ok(())
         })?;
 
-        let results = LowLevelConnection::apply_transaction(self, transaction)?;
+        if transaction.operations.is_empty() {
+            return Ok(DeletedDocuments {
+                count: 0,
+                transaction_id: None,
+                headers,
+            });
+        }
 
-        Ok(results.len() as u64)
+        // Each document is deleted only if its revision still matches the
+        // mapping's source, which makes the headers an exact record of what
+        // this transaction removed.
+        self.check_documents_writable()?;
+        let (transaction_id, results) = self.apply_transaction_returning_id(transaction, None)?;
+
+        Ok(DeletedDocuments {
+            count: results.len() as u64,
+            transaction_id: Some(transaction_id),
+            headers,
+        })
     }
 }
 
@@ -1666,6 +2468,15 @@ impl Deref for Context {
 pub(crate) struct ContextData {
     pub(crate) roots: Roots<AnyFile>,
     key_value_state: Arc<Mutex<keyvalue::KeyValueState>>,
+    pub(crate) quota: Arc<QuotaState>,
+    pub(crate) activity: ActivityTracker,
+    pub(crate) snapshots: SnapshotState,
+    pub(crate) purges: PurgeState,
+    /// If true, the contents of each change are recorded in the transaction
+    /// log.
+    pub(crate) replication_log: bool,
+    /// The minimum durability of changes made to the key-value store.
+    key_value_durability: DurabilityMode,
 }
 
 impl Borrow<Roots<AnyFile>> for Context {
@@ -1678,20 +2489,33 @@ impl Context {
     pub(crate) fn new(
         roots: Roots<AnyFile>,
         key_value_persistence: KeyValuePersistence,
+        key_value_durability: DurabilityMode,
         storage_lock: Option<StorageLock>,
+        clock: Arc<dyn keyvalue::Clock>,
+        replication_log: bool,
     ) -> Self {
         let background_worker_target = Watchable::new(BackgroundWorkerProcessTarget::Never);
         let mut background_worker_target_watcher = background_worker_target.watch();
+        let quota = Arc::new(QuotaState::default());
         let key_value_state = Arc::new(Mutex::new(keyvalue::KeyValueState::new(
             key_value_persistence,
             roots.clone(),
             background_worker_target,
+            clock.clone(),
+            quota.clone(),
+            replication_log,
         )));
         let background_worker_state = Arc::downgrade(&key_value_state);
         let context = Self {
             data: Arc::new(ContextData {
                 roots,
                 key_value_state,
+                quota,
+                activity: ActivityTracker::default(),
+                snapshots: SnapshotState::default(),
+                purges: PurgeState::default(),
+                replication_log,
+                key_value_durability,
             }),
         };
         std::thread::Builder::new()
@@ -1700,6 +2524,7 @@ impl Context {
                 keyvalue::background_worker(
                     &background_worker_state,
                     &mut background_worker_target_watcher,
+                    clock.as_ref(),
                     storage_lock,
                 );
             })
@@ -1711,21 +2536,102 @@ impl Context {
         &self,
         op: KeyOperation,
     ) -> Result<Output, bonsaidb_core::Error> {
+        use bonsaidb_core::keyvalue::Command;
+
+        let immediate = match &op.command {
+            Command::Set(SetCommand { durability, .. }) => {
+                *durability == DurabilityMode::Immediate
+                    || self.data.key_value_durability == DurabilityMode::Immediate
+            }
+            Command::Get { delete: true }
+            | Command::Increment { .. }
+            | Command::Decrement { .. }
+            | Command::Delete
+            | Command::Swap { .. }
+            | Command::DeleteIfEquals { .. } => {
+                self.data.key_value_durability == DurabilityMode::Immediate
+            }
+            Command::Get { delete: false } | Command::Scan(_) => false,
+        };
         let mut state = self.data.key_value_state.lock();
-        state.perform_kv_operation(op, &self.data.key_value_state)
+        let output = state.perform_kv_operation(op, &self.data.key_value_state)?;
+        drop(state);
+        if immediate {
+            self.persist_key_value_store()?;
+        }
+        Ok(output)
     }
 
+    /// Performs each of `ops` while holding the key-value store's lock, which
+    /// ensures that they are persisted together. The changes are persisted
+    /// before this function returns.
+    pub(crate) fn perform_kv_operations(
+        &self,
+        ops: Vec<KeyOperation>,
+    ) -> Result<Vec<Output>, bonsaidb_core::Error> {
+        let mut state = self.data.key_value_state.lock();
+        let outputs = ops
+            .into_iter()
+            .map(|op| state.perform_kv_operation(op, &self.data.key_value_state))
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(state);
+        self.persist_key_value_store()?;
+        Ok(outputs)
+    }
+
+    pub(crate) fn scan_key_values<F: Fn(&Value) -> bool>(
+        &self,
+        namespace: Option<&str>,
+        filter: F,
+    ) -> Result<Vec<KeyEntry>, bonsaidb_core::Error> {
+        let mut state = self.data.key_value_state.lock();
+        state.scan_matching(namespace, filter, &self.data.key_value_state)
+    }
+
+    pub(crate) fn contains_key(
+        &self,
+        namespace: Option<&str>,
+        key: &str,
+    ) -> Result<bool, bonsaidb_core::Error> {
+        let state = self.data.key_value_state.lock();
+        state
+            .contains_key(namespace, key)
+            .map_err(|err| bonsaidb_core::Error::from(Error::from(err)))
+    }
+
+    pub(crate) fn persist_key_value_store(&self) -> Result<(), bonsaidb_core::Error> {
+        keyvalue::KeyValueState::persist(&self.data.key_value_state)
+    }
+
+    pub(crate) fn durability_receipt(&self) -> keyvalue::DurabilityReceipt {
+        self.data.key_value_state.lock().durability_receipt()
+    }
+
+    #[cfg(test)]
     pub(crate) fn update_key_expiration<'key>(
         &self,
         tree_key: impl Into<Cow<'key, str>>,
-        expiration: Option<Timestamp>,
+        expiration: Option<bonsaidb_core::keyvalue::Timestamp>,
     ) {
         let mut state = self.data.key_value_state.lock();
         state.update_key_expiration(tree_key, expiration);
     }
 
+    pub(crate) fn load_expirations(
+        &self,
+        start: ops::Bound<&[u8]>,
+        end: ops::Bound<&[u8]>,
+        batch_size: usize,
+        status: &mut keyvalue::ExpirationLoadStatus,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let mut state = self.data.key_value_state.lock();
+        state.load_expirations(start, end, batch_size, status, &self.data.key_value_state)
+    }
+
     #[cfg(test)]
-    pub(crate) fn kv_persistence_watcher(&self) -> watchable::Watcher<Timestamp> {
+    pub(crate) fn kv_persistence_watcher(
+        &self,
+    ) -> watchable::Watcher<bonsaidb_core::keyvalue::Timestamp> {
         let state = self.data.key_value_state.lock();
         state.persistence_watcher()
     }
@@ -1771,10 +2677,45 @@ pub trait DatabaseNonBlocking {
     /// Returns the name of the database.
     #[must_use]
     fn name(&self) -> &str;
+
+    /// Returns the maximum size, in bytes, of a document's contents in
+    /// `collection`, or `None` if the size is unlimited. The collection's
+    /// [`Collection::max_document_size()`](schema::Collection::max_document_size)
+    /// takes precedence over
+    /// [`StorageConfiguration::max_document_size`](crate::config::StorageConfiguration::max_document_size).
+    #[must_use]
+    fn max_document_size(&self, collection: &CollectionName) -> Option<u64>;
 }
 
 impl DatabaseNonBlocking for Database {
     fn name(&self) -> &str {
         self.data.name.as_ref()
     }
+
+    fn max_document_size(&self, collection: &CollectionName) -> Option<u64> {
+        self.schematic()
+            .max_document_size_for_collection(collection)
+            .or_else(|| self.storage.max_document_size())
+    }
+}
+
+/// Returns an error if `contents` are larger than `limit`. Documents that
+/// already exceed the limit can be updated as long as they don't grow.
+fn check_document_size(
+    limit: Option<u64>,
+    contents: &[u8],
+    previous_size: Option<usize>,
+) -> Result<(), Error> {
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+    let size = contents.len() as u64;
+    if size <= limit || previous_size.map_or(false, |previous| contents.len() <= previous) {
+        Ok(())
+    } else {
+        Err(Error::Core(bonsaidb_core::Error::DocumentTooLarge {
+            size,
+            limit,
+        }))
+    }
 }
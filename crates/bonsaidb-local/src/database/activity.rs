@@ -0,0 +1,112 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use bonsaidb_core::connection::HasSession;
+use bonsaidb_core::keyvalue::Timestamp;
+use bonsaidb_core::permissions::bonsai::{
+    database_resource_name, BonsaiAction, DatabaseAction, TransactionAction,
+};
+use parking_lot::Mutex;
+
+use crate::{Database, DatabaseNonBlocking};
+
+/// The write activity of a [`Database`]. Returned from
+/// [`Database::activity()`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ActivityStats {
+    /// The id of the last transaction committed to the database's transaction
+    /// log, or `None` if no transactions have been committed. Transactions
+    /// are committed for document changes, persisted key-value changes, and
+    /// view updates.
+    pub last_transaction_id: Option<u64>,
+    /// The number of document transactions committed since the database was
+    /// opened by this process.
+    pub transactions_committed: u64,
+    /// The amount of time since the database was opened by this process.
+    pub elapsed: Duration,
+}
+
+impl ActivityStats {
+    /// Returns the average number of document transactions committed per
+    /// second since the database was opened.
+    ///
+    /// To measure the current write rate, compare two snapshots using
+    /// [`Self::transactions_per_second_since()`].
+    #[must_use]
+    pub fn transactions_per_second(&self) -> f64 {
+        rate(self.transactions_committed, self.elapsed)
+    }
+
+    /// Returns the number of document transactions committed per second
+    /// between `earlier` and this snapshot.
+    #[must_use]
+    pub fn transactions_per_second_since(&self, earlier: &Self) -> f64 {
+        rate(
+            self.transactions_committed
+                .saturating_sub(earlier.transactions_committed),
+            self.elapsed.saturating_sub(earlier.elapsed),
+        )
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn rate(transactions: u64, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0. {
+        transactions as f64 / seconds
+    } else {
+        0.
+    }
+}
+
+/// Counts the transactions committed to an open database.
+#[derive(Debug)]
+pub(crate) struct ActivityTracker {
+    opened_at: Instant,
+    transactions_committed: AtomicU64,
+    last_committed_at: Mutex<Option<Timestamp>>,
+}
+
+impl Default for ActivityTracker {
+    fn default() -> Self {
+        Self {
+            opened_at: Instant::now(),
+            transactions_committed: AtomicU64::new(0),
+            last_committed_at: Mutex::default(),
+        }
+    }
+}
+
+impl ActivityTracker {
+    pub fn transaction_committed(&self) {
+        self.transactions_committed.fetch_add(1, Ordering::Relaxed);
+        *self.last_committed_at.lock() = Some(Timestamp::now());
+    }
+
+    /// Returns when the last document transaction was committed since the
+    /// database was opened by this process.
+    pub fn last_committed_at(&self) -> Option<Timestamp> {
+        *self.last_committed_at.lock()
+    }
+}
+
+impl Database {
+    /// Returns statistics about the transactions committed to this database.
+    ///
+    /// This only reads values that are already in memory, which makes it
+    /// inexpensive enough to poll frequently. This requires the same permission
+    /// as
+    /// [`Connection::last_transaction_id()`](bonsaidb_core::connection::Connection::last_transaction_id).
+    pub fn activity(&self) -> Result<ActivityStats, bonsaidb_core::Error> {
+        self.check_permission(
+            database_resource_name(self.name()),
+            &BonsaiAction::Database(DatabaseAction::Transaction(TransactionAction::GetLastId)),
+        )?;
+        let activity = &self.data.context.activity;
+        Ok(ActivityStats {
+            last_transaction_id: self.roots().transactions().current_transaction_id(),
+            transactions_committed: activity.transactions_committed.load(Ordering::Relaxed),
+            elapsed: activity.opened_at.elapsed(),
+        })
+    }
+}
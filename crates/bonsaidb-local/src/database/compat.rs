@@ -109,6 +109,7 @@ impl TryFrom<ChangesV0> for Changes {
                         collection,
                         id: changed.id.try_into()?,
                         deleted: changed.deleted,
+                        contents: None,
                     });
                 }
                 Ok(Self::Documents(DocumentChanges {
@@ -0,0 +1,182 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use bonsaidb_core::connection::{Bound, HasSchema, LowLevelConnection, Range, Sort};
+use bonsaidb_core::document::{DocumentId, OwnedDocument};
+use bonsaidb_core::schema::CollectionName;
+
+use crate::Database;
+
+/// The number of documents read from each database at a time while diffing.
+const PAGE_SIZE: u32 = 100;
+
+/// The differences between the documents of two databases. Returned from
+/// [`Database::diff()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatabaseDiff {
+    /// The differences within each collection. Collections whose documents
+    /// are identical are not included.
+    pub collections: BTreeMap<CollectionName, CollectionDiff>,
+}
+
+impl DatabaseDiff {
+    /// Returns true if no differences were found.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.collections.is_empty()
+    }
+}
+
+/// The differences between the documents of a single collection in two
+/// databases. Each list of ids is sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CollectionDiff {
+    /// Documents that only exist in the other database.
+    pub added: Vec<DocumentId>,
+    /// Documents that only exist in this database.
+    pub removed: Vec<DocumentId>,
+    /// Documents that exist in both databases with different contents.
+    pub changed: Vec<DocumentId>,
+}
+
+impl CollectionDiff {
+    /// Returns true if no differences were found.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Database {
+    /// Compares the documents in this database against the ones in `other`,
+    /// returning the differences found in each collection.
+    ///
+    /// `other` can be any connection, which allows comparing a database
+    /// against a [`Snapshot`](crate::Snapshot) of itself, or against a
+    /// replica. Documents are compared by id and contents; differences in
+    /// revisions alone are not reported. Collections that only exist in one of
+    /// the schemas are compared as if the other database's collection were
+    /// empty.
+    ///
+    /// Documents are read from both databases in pages, so the memory used is
+    /// bounded by the number of differences rather than the size of the
+    /// databases. Each page is read independently, so if either database is
+    /// modified while it is being compared, the result may reflect some of
+    /// the changes. Comparing against a [`Snapshot`](crate::Snapshot) avoids
+    /// this.
+    pub fn diff<Other: LowLevelConnection>(
+        &self,
+        other: &Other,
+    ) -> Result<DatabaseDiff, bonsaidb_core::Error> {
+        let ours = self.schematic().collections().collect::<BTreeSet<_>>();
+        let theirs = other.schematic().collections().collect::<BTreeSet<_>>();
+
+        let mut diff = DatabaseDiff::default();
+        for collection in ours.union(&theirs).copied() {
+            let mut our_pages = ours
+                .contains(collection)
+                .then(|| Pages::new(self, collection));
+            let mut their_pages = theirs
+                .contains(collection)
+                .then(|| Pages::new(other, collection));
+            let collection_diff = diff_collection(our_pages.as_mut(), their_pages.as_mut())?;
+            if !collection_diff.is_empty() {
+                diff.collections.insert(collection.clone(), collection_diff);
+            }
+        }
+
+        Ok(diff)
+    }
+}
+
+fn diff_collection<A: LowLevelConnection, B: LowLevelConnection>(
+    mut ours: Option<&mut Pages<'_, A>>,
+    mut theirs: Option<&mut Pages<'_, B>>,
+) -> Result<CollectionDiff, bonsaidb_core::Error> {
+    let mut diff = CollectionDiff::default();
+    loop {
+        let our_next = match &mut ours {
+            Some(pages) => pages.peek()?,
+            None => None,
+        };
+        let their_next = match &mut theirs {
+            Some(pages) => pages.peek()?,
+            None => None,
+        };
+        // Documents only present in one database are ordered as if the other
+        // database's next document comes after them.
+        let order = match (our_next, their_next) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(our_next), Some(their_next)) => our_next.header.id.cmp(&their_next.header.id),
+        };
+        match order {
+            Ordering::Less => diff.removed.push(pop(&mut ours).header.id),
+            Ordering::Greater => diff.added.push(pop(&mut theirs).header.id),
+            Ordering::Equal => {
+                let ours = pop(&mut ours);
+                let theirs = pop(&mut theirs);
+                if ours.contents != theirs.contents {
+                    diff.changed.push(ours.header.id);
+                }
+            }
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Removes the document that was just peeked from `pages`.
+fn pop<C: LowLevelConnection>(pages: &mut Option<&mut Pages<'_, C>>) -> OwnedDocument {
+    pages
+        .as_mut()
+        .and_then(|pages| pages.page.pop_front())
+        .expect("peeked document missing")
+}
+
+/// Reads a collection's documents in ascending order, one page at a time.
+struct Pages<'a, C> {
+    connection: &'a C,
+    collection: &'a CollectionName,
+    page: VecDeque<OwnedDocument>,
+    last_id: Option<DocumentId>,
+    finished: bool,
+}
+
+impl<'a, C: LowLevelConnection> Pages<'a, C> {
+    fn new(connection: &'a C, collection: &'a CollectionName) -> Self {
+        Self {
+            connection,
+            collection,
+            page: VecDeque::new(),
+            last_id: None,
+            finished: false,
+        }
+    }
+
+    /// Returns the next document without removing it, reading the next page
+    /// if needed.
+    fn peek(&mut self) -> Result<Option<&OwnedDocument>, bonsaidb_core::Error> {
+        if self.page.is_empty() && !self.finished {
+            let start = self
+                .last_id
+                .take()
+                .map_or(Bound::Unbounded, Bound::Excluded);
+            let page = self.connection.list_from_collection(
+                Range {
+                    start,
+                    end: Bound::Unbounded,
+                },
+                Sort::Ascending,
+                Some(PAGE_SIZE),
+                self.collection,
+            )?;
+            self.finished = page.len() < PAGE_SIZE as usize;
+            self.last_id = page.last().map(|document| document.header.id.clone());
+            self.page = VecDeque::from(page);
+        }
+
+        Ok(self.page.front())
+    }
+}
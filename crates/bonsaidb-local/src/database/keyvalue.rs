@@ -1,29 +1,36 @@
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::{btree_map, BTreeMap, VecDeque};
+use std::fmt::Debug;
+use std::ops::Bound;
 use std::sync::{Arc, Weak};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use bonsaidb_core::arc_bytes::serde::Bytes;
 use bonsaidb_core::connection::{Connection, HasSession};
 use bonsaidb_core::keyvalue::{
-    Command, KeyCheck, KeyOperation, KeyStatus, KeyValue, Numeric, Output, SetCommand, Timestamp,
-    Value,
+    Command, DurabilityMode, KeyCheck, KeyEntry, KeyOperation, KeyStatus, KeyValue, Numeric,
+    Output, OverflowPolicy, ScanCommand, SetCommand, Timestamp, Value,
 };
 use bonsaidb_core::permissions::bonsai::{
-    keyvalue_key_resource_name, BonsaiAction, DatabaseAction, KeyValueAction,
+    keyvalue_key_resource_name, keyvalue_namespace_resource_name, kv_resource_name, BonsaiAction,
+    DatabaseAction, KeyValueAction,
 };
 use bonsaidb_core::transaction::{ChangedKey, Changes};
 use nebari::io::any::AnyFile;
 use nebari::tree::{CompareSwap, Operation, Root, ScanEvaluation, Unversioned};
-use nebari::{AbortError, ArcBytes, Roots};
+use nebari::{AbortError, ArcBytes, Roots, Tree};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use watchable::{Watchable, Watcher};
 
 use crate::config::KeyValuePersistence;
 use crate::database::compat;
-use crate::storage::StorageLock;
+use crate::database::quota::{QuotaState, UsageChanges};
+use crate::files::DatabaseCursors;
+use crate::storage::{StorageLock, MIGRATION_BATCH_SIZE};
 use crate::tasks::{Job, Keyed, Task};
-use crate::{Database, DatabaseNonBlocking, Error};
+use crate::{Database, DatabaseNonBlocking, Error, StorageNonBlocking};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Entry {
@@ -40,7 +47,7 @@ impl Entry {
         key: String,
         database: &Database,
     ) -> Result<(), bonsaidb_core::Error> {
-        database.execute_key_operation(KeyOperation {
+        database.execute_key_operation_bypassing_read_only(KeyOperation {
             namespace,
             key,
             command: Command::Set(SetCommand {
@@ -49,6 +56,7 @@ impl Entry {
                 keep_existing_expiration: false,
                 check: None,
                 return_previous_value: false,
+                durability: DurabilityMode::Deferred,
             }),
         })?;
         Ok(())
@@ -57,15 +65,209 @@ impl Entry {
 
 impl KeyValue for Database {
     fn execute_key_operation(&self, op: KeyOperation) -> Result<Output, bonsaidb_core::Error> {
+        if !matches!(
+            op.command,
+            Command::Get { delete: false } | Command::Scan(_)
+        ) {
+            self.check_writable()?;
+        }
+        self.execute_key_operation_bypassing_read_only(op)
+    }
+
+    fn persist(&self) -> Result<(), bonsaidb_core::Error> {
         self.check_permission(
-            keyvalue_key_resource_name(self.name(), op.namespace.as_deref(), &op.key),
-            &BonsaiAction::Database(DatabaseAction::KeyValue(KeyValueAction::ExecuteOperation)),
+            kv_resource_name(self.name()),
+            &BonsaiAction::Database(DatabaseAction::KeyValue(KeyValueAction::Persist)),
         )?;
-        self.data.context.perform_kv_operation(op)
+        self.data.context.persist_key_value_store()
     }
 }
 
 impl Database {
+    /// Returns a receipt that completes once every Key-Value change made
+    /// before this call has been persisted to disk.
+    ///
+    /// Unlike [`KeyValue::persist()`], this does not force the pending changes
+    /// to be written. Instead, the receipt completes after the background
+    /// persistence configured by [`KeyValuePersistence`] has written them. This
+    /// allows writing with [`DurabilityMode::Deferred`] while still learning
+    /// when the writes are safe from a crash.
+    pub fn durability_receipt(&self) -> DurabilityReceipt {
+        self.data.context.durability_receipt()
+    }
+
+    /// Returns the progress of loading the expirations of this database's
+    /// persisted Key-Value entries.
+    ///
+    /// Expirations are tracked in memory. Each time a database is opened by a
+    /// process, its persisted entries are examined in the background in
+    /// batches of [`Tasks::expiration_load_batch_size`] entries, deleting the
+    /// entries that expired while the database was closed and tracking when
+    /// the remaining entries expire. Opening the database doesn't wait for
+    /// this to complete, and entries that have expired are never returned,
+    /// even if they haven't been examined yet.
+    ///
+    /// Every persisted entry is examined each time the database is opened,
+    /// so the cost of loading grows with the size of the key-value store. If
+    /// a previous load was interrupted, the entries it never reached are
+    /// examined first, but the entries it had already examined are still
+    /// examined afterwards.
+    ///
+    /// [`Tasks::expiration_load_batch_size`]: crate::config::Tasks::expiration_load_batch_size
+    #[must_use]
+    pub fn key_value_expiration_status(&self) -> ExpirationLoadStatus {
+        self.storage
+            .instance
+            .tasks()
+            .key_value_expiration_status(&self.data.name)
+    }
+
+    /// Executes `op` even if the storage is read-only. This is used to restore
+    /// backups and replicate changes into read-only storage.
+    pub(crate) fn execute_key_operation_bypassing_read_only(
+        &self,
+        op: KeyOperation,
+    ) -> Result<Output, bonsaidb_core::Error> {
+        // Scans can return any key in the namespace, so they require
+        // permission to the entire namespace.
+        let resource_name = if matches!(op.command, Command::Scan(_)) {
+            keyvalue_namespace_resource_name(self.name(), op.namespace.as_deref())
+        } else {
+            keyvalue_key_resource_name(self.name(), op.namespace.as_deref(), &op.key)
+        };
+        self.check_permission(
+            resource_name,
+            &BonsaiAction::Database(DatabaseAction::KeyValue(KeyValueAction::ExecuteOperation)),
+        )?;
+        if let Command::Swap {
+            other_namespace,
+            other_key,
+        } = &op.command
+        {
+            self.check_permission(
+                keyvalue_key_resource_name(self.name(), other_namespace.as_deref(), other_key),
+                &BonsaiAction::Database(DatabaseAction::KeyValue(KeyValueAction::ExecuteOperation)),
+            )?;
+        }
+        let Some(quota) = self.begin_quota_write()? else {
+            return self.data.context.perform_kv_operation(op);
+        };
+
+        // Only keys that don't exist yet count against the quota.
+        let may_create_key = matches!(
+            op.command,
+            Command::Set(_) | Command::Increment { .. } | Command::Decrement { .. }
+        ) && !self
+            .data
+            .context
+            .contains_key(op.namespace.as_deref(), &op.key)?;
+        let may_remove_key = matches!(
+            op.command,
+            Command::Delete | Command::DeleteIfEquals { .. } | Command::Get { delete: true }
+        );
+        let mut usage_changes = UsageChanges::default();
+        if may_create_key {
+            usage_changes.keys = 1;
+            quota.check(&usage_changes)?;
+        }
+
+        let output = self.data.context.perform_kv_operation(op)?;
+        usage_changes.keys =
+            if may_create_key && !matches!(output, Output::Status(KeyStatus::NotChanged)) {
+                1
+            } else if may_remove_key
+                && matches!(
+                    output,
+                    Output::Status(KeyStatus::Deleted) | Output::Value(Some(_))
+                )
+            {
+                -1
+            } else {
+                0
+            };
+        quota.apply(&usage_changes);
+        Ok(output)
+    }
+
+    /// Executes each of `ops` even if the storage is read-only, persisting
+    /// them together in a single transaction. Quotas are updated but not
+    /// enforced. This is used to replicate changes into read-only storage.
+    pub(crate) fn execute_key_operations_bypassing_read_only(
+        &self,
+        ops: Vec<KeyOperation>,
+    ) -> Result<(), bonsaidb_core::Error> {
+        for op in &ops {
+            self.check_permission(
+                keyvalue_key_resource_name(self.name(), op.namespace.as_deref(), &op.key),
+                &BonsaiAction::Database(DatabaseAction::KeyValue(KeyValueAction::ExecuteOperation)),
+            )?;
+        }
+        let quota = self.begin_quota_write()?;
+        let outputs = self.data.context.perform_kv_operations(ops)?;
+        if let Some(quota) = quota {
+            let mut usage_changes = UsageChanges::default();
+            for output in outputs {
+                match output {
+                    Output::Status(KeyStatus::Inserted) => usage_changes.keys += 1,
+                    Output::Status(KeyStatus::Deleted) => usage_changes.keys -= 1,
+                    _ => {}
+                }
+            }
+            quota.apply(&usage_changes);
+        }
+        Ok(())
+    }
+
+    /// Returns the key and value of each entry in `namespace` whose value
+    /// matches `filter`, sorted by key. Expired entries are skipped, as are
+    /// entries in namespaces nested within `namespace`.
+    ///
+    /// Unlike [`scan_keys()`](KeyValue::scan_keys), `filter` can examine
+    /// values in any way, such as by deserializing them. Because `filter`
+    /// can't be sent over a network connection, this function is only
+    /// available on local databases. While `filter` is invoked, the key-value
+    /// store is locked, so `filter` must not access the key-value store.
+    ///
+    /// This requires the same permission as scanning `namespace`.
+    pub fn scan_key_values<F: Fn(&Value) -> bool>(
+        &self,
+        namespace: Option<&str>,
+        filter: F,
+    ) -> Result<Vec<(String, Value)>, bonsaidb_core::Error> {
+        self.check_permission(
+            keyvalue_namespace_resource_name(self.name(), namespace),
+            &BonsaiAction::Database(DatabaseAction::KeyValue(KeyValueAction::ExecuteOperation)),
+        )?;
+        let entries = self.data.context.scan_key_values(namespace, filter)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect())
+    }
+
+    /// Compacts the key-value store, returning the number of bytes reclaimed
+    /// from the file it is stored in.
+    ///
+    /// Deleting or expiring a key only records that the key was removed,
+    /// leaving the previous value in the file until it is compacted. Pending
+    /// changes are persisted before the store is compacted.
+    ///
+    /// This requires the same permission as
+    /// [`compact_key_value_store()`](Connection::compact_key_value_store).
+    pub fn compact_key_value(&self) -> Result<u64, bonsaidb_core::Error> {
+        self.persist_key_value_store()?;
+        let file_size = || -> Result<u64, Error> {
+            Ok(self
+                .storage
+                .instance
+                .tree_file_length(self.name(), KEY_TREE)?
+                .unwrap_or_default())
+        };
+        let before = file_size()?;
+        self.compact_key_value_store()?;
+        Ok(before.saturating_sub(file_size()?))
+    }
+
     pub(crate) fn all_key_value_entries(
         &self,
     ) -> Result<BTreeMap<(Option<String>, String), Entry>, Error> {
@@ -120,10 +322,107 @@ impl Database {
 
         Ok(all_entries)
     }
+
+    /// Rewrites the persisted entries whose keys are missing the separator
+    /// between the namespace and the key as keys without a namespace, and
+    /// re-encodes every entry using the current [`Entry`] encoding. If a key
+    /// already exists in its rewritten form, the existing entry is kept.
+    ///
+    /// Entries are migrated [`MIGRATION_BATCH_SIZE`] at a time. Each batch is
+    /// read and rewritten while holding the key-value state's lock with no
+    /// pending changes, which prevents the rewrites from racing with the
+    /// persistence of newer values.
+    pub(crate) fn reencode_key_value_keys(&self) -> Result<(), Error> {
+        let tree = self.roots().tree(Unversioned::tree(KEY_TREE))?;
+        let mut last_key = None::<ArcBytes<'static>>;
+        loop {
+            self.persist_key_value_store()?;
+            let state = self.data.key_value_state.lock();
+            if !state.dirty_keys.is_empty() || state.keys_being_persisted.is_some() {
+                // Changes were made or are being persisted since persisting.
+                drop(state);
+                continue;
+            }
+
+            let rewrites = reencode_key_value_batch(&tree, last_key.as_deref())?;
+            let Some(batch_last_key) = rewrites.last_key else {
+                return Ok(());
+            };
+            for (stored_key, canonical_key, entry) in rewrites.rewrites {
+                if canonical_key.as_bytes() == &stored_key[..] {
+                    tree.set(stored_key, entry)?;
+                } else {
+                    if tree.get(canonical_key.as_bytes())?.is_none() {
+                        tree.set(canonical_key.into_bytes(), entry)?;
+                    }
+                    tree.remove(&stored_key)?;
+                }
+            }
+            drop(state);
+            last_key = Some(batch_last_key);
+        }
+    }
+}
+
+/// A batch of key-value entries read by [`reencode_key_value_batch()`].
+struct KeyValueRewrites {
+    /// The last key examined, or None if no keys remained.
+    last_key: Option<ArcBytes<'static>>,
+    /// The stored key, canonical key, and re-encoded entry of each entry that
+    /// needs to be rewritten.
+    rewrites: Vec<(ArcBytes<'static>, String, Vec<u8>)>,
+}
+
+/// Reads up to [`MIGRATION_BATCH_SIZE`] entries following `after`, returning
+/// the entries that need to be rewritten.
+fn reencode_key_value_batch(
+    tree: &Tree<Unversioned, AnyFile>,
+    after: Option<&[u8]>,
+) -> Result<KeyValueRewrites, Error> {
+    let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+    let examined = Cell::new(0);
+    let mut last_key = None;
+    let mut rewrites = Vec::new();
+    tree.scan::<Error, _, _, _, _>(
+        &(start, Bound::Unbounded),
+        true,
+        |_, _, _| ScanEvaluation::ReadData,
+        |_, _| {
+            if examined.get() < MIGRATION_BATCH_SIZE {
+                examined.set(examined.get() + 1);
+                ScanEvaluation::ReadData
+            } else {
+                ScanEvaluation::Stop
+            }
+        },
+        |key, _, bytes: ArcBytes<'static>| {
+            last_key = Some(key.clone());
+            let entry = bincode::deserialize::<Entry>(&bytes)
+                .map_err(|err| AbortError::Other(Error::from(err)))?;
+            let stored_key =
+                std::str::from_utf8(&key).map_err(|err| AbortError::Other(Error::from(err)))?;
+            let canonical_key = match split_key(stored_key) {
+                Some((namespace, key)) => full_key(namespace.as_deref(), &key),
+                None => full_key(None, stored_key),
+            };
+            let reencoded =
+                bincode::serialize(&entry).map_err(|err| AbortError::Other(Error::from(err)))?;
+            if canonical_key.as_bytes() != &key[..] || reencoded != &bytes[..] {
+                rewrites.push((key, canonical_key, reencoded));
+            }
+            Ok(())
+        },
+    )?;
+    Ok(KeyValueRewrites { last_key, rewrites })
 }
 
 pub(crate) const KEY_TREE: &str = "kv";
 
+pub(crate) const EXPIRATION_CURSORS_FILE: &str = "kv-expiration.cursors";
+
+/// The last key examined by each database's incomplete expiration load.
+pub(crate) type ExpirationCursors = DatabaseCursors<Bytes>;
+
 fn full_key(namespace: Option<&str>, key: &str) -> String {
     let full_length = namespace.map_or_else(|| 0, str::len) + key.len() + 1;
     let mut full_key = String::with_capacity(full_length);
@@ -135,7 +434,7 @@ fn full_key(namespace: Option<&str>, key: &str) -> String {
     full_key
 }
 
-fn split_key(full_key: &str) -> Option<(Option<String>, String)> {
+pub(crate) fn split_key(full_key: &str) -> Option<(Option<String>, String)> {
     if let Some((namespace, key)) = full_key.split_once('\0') {
         let namespace = if namespace.is_empty() {
             None
@@ -148,59 +447,257 @@ fn split_key(full_key: &str) -> Option<(Option<String>, String)> {
     }
 }
 
-fn increment(existing: &Numeric, amount: &Numeric, saturating: bool) -> Numeric {
-    match amount {
+/// Converts `$existing` to the numeric type of the amount, following the
+/// rules of `$overflow`.
+macro_rules! convert_existing {
+    ($existing:expr, $overflow:expr, $exact:ident, $lossy:ident) => {
+        match $overflow {
+            OverflowPolicy::Saturate => $existing.$lossy(true),
+            OverflowPolicy::Wrap => $existing.$lossy(false),
+            OverflowPolicy::Error => $existing
+                .$exact()
+                .ok_or(bonsaidb_core::Error::NumericOverflow)?,
+        }
+    };
+}
+
+/// Performs an integer operation, following the rules of `$overflow`.
+macro_rules! integer_operation {
+    ($existing:expr, $amount:expr, $overflow:expr, $saturating:ident, $wrapping:ident, $checked:ident) => {
+        match $overflow {
+            OverflowPolicy::Saturate => $existing.$saturating($amount),
+            OverflowPolicy::Wrap => $existing.$wrapping($amount),
+            OverflowPolicy::Error => $existing
+                .$checked($amount)
+                .ok_or(bonsaidb_core::Error::NumericOverflow)?,
+        }
+    };
+}
+
+/// Converts `existing` to the numeric type of `amount`, following the rules of
+/// `overflow`.
+fn convert_to_type_of(
+    existing: &Numeric,
+    amount: &Numeric,
+    overflow: OverflowPolicy,
+) -> Result<Numeric, bonsaidb_core::Error> {
+    Ok(match amount {
+        Numeric::Integer(_) => {
+            Numeric::Integer(convert_existing!(existing, overflow, as_i64, as_i64_lossy))
+        }
+        Numeric::UnsignedInteger(_) => {
+            Numeric::UnsignedInteger(convert_existing!(existing, overflow, as_u64, as_u64_lossy))
+        }
+        Numeric::Integer128(_) => Numeric::Integer128(convert_existing!(
+            existing,
+            overflow,
+            as_i128,
+            as_i128_lossy
+        )),
+        Numeric::UnsignedInteger128(_) => Numeric::UnsignedInteger128(convert_existing!(
+            existing,
+            overflow,
+            as_u128,
+            as_u128_lossy
+        )),
+        Numeric::Float(_) => Numeric::Float(match overflow {
+            OverflowPolicy::Saturate | OverflowPolicy::Wrap => existing.as_f64_lossy(),
+            OverflowPolicy::Error => existing
+                .as_f64()
+                .ok_or(bonsaidb_core::Error::NumericOverflow)?,
+        }),
+    })
+}
+
+fn float_operation(
+    existing: &Numeric,
+    overflow: OverflowPolicy,
+    op: impl FnOnce(f64) -> f64,
+) -> Result<Numeric, bonsaidb_core::Error> {
+    let existing_value = match overflow {
+        OverflowPolicy::Saturate | OverflowPolicy::Wrap => existing.as_f64_lossy(),
+        OverflowPolicy::Error => existing
+            .as_f64()
+            .ok_or(bonsaidb_core::Error::NumericOverflow)?,
+    };
+    let new_value = op(existing_value);
+    if overflow == OverflowPolicy::Error && !new_value.is_finite() {
+        return Err(bonsaidb_core::Error::NumericOverflow);
+    }
+    Ok(Numeric::Float(new_value))
+}
+
+fn increment(
+    existing: &Numeric,
+    amount: &Numeric,
+    overflow: OverflowPolicy,
+) -> Result<Numeric, bonsaidb_core::Error> {
+    Ok(match amount {
         Numeric::Integer(amount) => {
-            let existing_value = existing.as_i64_lossy(saturating);
-            let new_value = if saturating {
-                existing_value.saturating_add(*amount)
-            } else {
-                existing_value.wrapping_add(*amount)
-            };
-            Numeric::Integer(new_value)
+            let existing_value = convert_existing!(existing, overflow, as_i64, as_i64_lossy);
+            Numeric::Integer(integer_operation!(
+                existing_value,
+                *amount,
+                overflow,
+                saturating_add,
+                wrapping_add,
+                checked_add
+            ))
         }
         Numeric::UnsignedInteger(amount) => {
-            let existing_value = existing.as_u64_lossy(saturating);
-            let new_value = if saturating {
-                existing_value.saturating_add(*amount)
-            } else {
-                existing_value.wrapping_add(*amount)
-            };
-            Numeric::UnsignedInteger(new_value)
+            let existing_value = convert_existing!(existing, overflow, as_u64, as_u64_lossy);
+            Numeric::UnsignedInteger(integer_operation!(
+                existing_value,
+                *amount,
+                overflow,
+                saturating_add,
+                wrapping_add,
+                checked_add
+            ))
+        }
+        Numeric::Integer128(amount) => {
+            let existing_value = convert_existing!(existing, overflow, as_i128, as_i128_lossy);
+            Numeric::Integer128(integer_operation!(
+                existing_value,
+                *amount,
+                overflow,
+                saturating_add,
+                wrapping_add,
+                checked_add
+            ))
+        }
+        Numeric::UnsignedInteger128(amount) => {
+            let existing_value = convert_existing!(existing, overflow, as_u128, as_u128_lossy);
+            Numeric::UnsignedInteger128(integer_operation!(
+                existing_value,
+                *amount,
+                overflow,
+                saturating_add,
+                wrapping_add,
+                checked_add
+            ))
         }
         Numeric::Float(amount) => {
-            let existing_value = existing.as_f64_lossy();
-            let new_value = existing_value + *amount;
-            Numeric::Float(new_value)
+            return float_operation(existing, overflow, |existing| existing + *amount)
         }
-    }
+    })
 }
 
-fn decrement(existing: &Numeric, amount: &Numeric, saturating: bool) -> Numeric {
-    match amount {
+fn decrement(
+    existing: &Numeric,
+    amount: &Numeric,
+    overflow: OverflowPolicy,
+) -> Result<Numeric, bonsaidb_core::Error> {
+    Ok(match amount {
         Numeric::Integer(amount) => {
-            let existing_value = existing.as_i64_lossy(saturating);
-            let new_value = if saturating {
-                existing_value.saturating_sub(*amount)
-            } else {
-                existing_value.wrapping_sub(*amount)
-            };
-            Numeric::Integer(new_value)
+            let existing_value = convert_existing!(existing, overflow, as_i64, as_i64_lossy);
+            Numeric::Integer(integer_operation!(
+                existing_value,
+                *amount,
+                overflow,
+                saturating_sub,
+                wrapping_sub,
+                checked_sub
+            ))
         }
         Numeric::UnsignedInteger(amount) => {
-            let existing_value = existing.as_u64_lossy(saturating);
-            let new_value = if saturating {
-                existing_value.saturating_sub(*amount)
-            } else {
-                existing_value.wrapping_sub(*amount)
-            };
-            Numeric::UnsignedInteger(new_value)
+            let existing_value = convert_existing!(existing, overflow, as_u64, as_u64_lossy);
+            Numeric::UnsignedInteger(integer_operation!(
+                existing_value,
+                *amount,
+                overflow,
+                saturating_sub,
+                wrapping_sub,
+                checked_sub
+            ))
+        }
+        Numeric::Integer128(amount) => {
+            let existing_value = convert_existing!(existing, overflow, as_i128, as_i128_lossy);
+            Numeric::Integer128(integer_operation!(
+                existing_value,
+                *amount,
+                overflow,
+                saturating_sub,
+                wrapping_sub,
+                checked_sub
+            ))
+        }
+        Numeric::UnsignedInteger128(amount) => {
+            let existing_value = convert_existing!(existing, overflow, as_u128, as_u128_lossy);
+            Numeric::UnsignedInteger128(integer_operation!(
+                existing_value,
+                *amount,
+                overflow,
+                saturating_sub,
+                wrapping_sub,
+                checked_sub
+            ))
         }
         Numeric::Float(amount) => {
-            let existing_value = existing.as_f64_lossy();
-            let new_value = existing_value - *amount;
-            Numeric::Float(new_value)
+            return float_operation(existing, overflow, |existing| existing - *amount)
         }
+    })
+}
+
+/// A source of the current time for the key-value store's expiration and
+/// persistence logic.
+pub trait Clock: Debug + Send + Sync + 'static {
+    /// Returns the current time.
+    fn now(&self) -> Timestamp;
+}
+
+/// A [`Clock`] that returns the system time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+}
+
+/// A [`Clock`] that measures elapsed time using a monotonic clock, anchored
+/// to the system time.
+///
+/// Adjustments to the system clock smaller than `max_skew` are ignored, which
+/// prevents small steps of the system clock from causing keys to expire early
+/// or late. Once the system clock differs from this clock by more than
+/// `max_skew`, such as after the system resumes from sleep, this clock is
+/// re-anchored to the system time.
+#[derive(Debug)]
+pub struct MonotonicClock {
+    max_skew: Duration,
+    anchor: Mutex<(Timestamp, Instant)>,
+}
+
+impl MonotonicClock {
+    pub fn new(max_skew: Duration) -> Self {
+        Self {
+            max_skew,
+            anchor: Mutex::new((Timestamp::now(), Instant::now())),
+        }
+    }
+
+    fn now_given(&self, system_now: Timestamp, instant_now: Instant) -> Timestamp {
+        let mut anchor = self.anchor.lock();
+        let (anchor_timestamp, anchor_instant) = *anchor;
+        let monotonic_now =
+            anchor_timestamp + instant_now.saturating_duration_since(anchor_instant);
+        let skew = (system_now - monotonic_now)
+            .or(monotonic_now - system_now)
+            .unwrap_or_default();
+        if skew > self.max_skew {
+            *anchor = (system_now, instant_now);
+            system_now
+        } else {
+            monotonic_now
+        }
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Timestamp {
+        self.now_given(Timestamp::now(), Instant::now())
     }
 }
 
@@ -208,6 +705,7 @@ fn decrement(existing: &Numeric, amount: &Numeric, saturating: bool) -> Numeric
 pub struct KeyValueState {
     roots: Roots<AnyFile>,
     persistence: KeyValuePersistence,
+    clock: Arc<dyn Clock>,
     last_commit: Timestamp,
     background_worker_target: Watchable<BackgroundWorkerProcessTarget>,
     expiring_keys: BTreeMap<String, Timestamp>,
@@ -215,26 +713,38 @@ pub struct KeyValueState {
     dirty_keys: BTreeMap<String, Option<Entry>>,
     keys_being_persisted: Option<Arc<BTreeMap<String, Option<Entry>>>>,
     last_persistence: Watchable<Timestamp>,
+    dirty_receipts: Vec<flume::Sender<()>>,
+    receipts_being_persisted: Vec<flume::Sender<()>>,
     shutdown: Option<flume::Sender<()>>,
+    quota: Arc<QuotaState>,
+    replication_log: bool,
 }
 
 impl KeyValueState {
-    pub fn new(
+    pub(crate) fn new(
         persistence: KeyValuePersistence,
         roots: Roots<AnyFile>,
         background_worker_target: Watchable<BackgroundWorkerProcessTarget>,
+        clock: Arc<dyn Clock>,
+        quota: Arc<QuotaState>,
+        replication_log: bool,
     ) -> Self {
         Self {
             roots,
             persistence,
-            last_commit: Timestamp::now(),
+            last_commit: clock.now(),
+            clock,
             expiring_keys: BTreeMap::new(),
             background_worker_target,
             expiration_order: VecDeque::new(),
             dirty_keys: BTreeMap::new(),
             keys_being_persisted: None,
             last_persistence: Watchable::new(Timestamp::MIN),
+            dirty_receipts: Vec::new(),
+            receipts_being_persisted: Vec::new(),
             shutdown: None,
+            quota,
+            replication_log,
         }
     }
 
@@ -253,7 +763,7 @@ impl KeyValueState {
         op: KeyOperation,
         state: &Arc<Mutex<KeyValueState>>,
     ) -> Result<Output, bonsaidb_core::Error> {
-        let now = Timestamp::now();
+        let now = self.clock.now();
         // If there are any keys that have expired, clear them before executing any operations.
         self.remove_expired_keys(now);
         let result = match op.command {
@@ -261,33 +771,78 @@ impl KeyValueState {
                 self.execute_set_operation(op.namespace.as_deref(), &op.key, command, now)
             }
             Command::Get { delete } => {
-                self.execute_get_operation(op.namespace.as_deref(), &op.key, delete)
+                self.execute_get_operation(op.namespace.as_deref(), &op.key, delete, now)
             }
             Command::Delete => self.execute_delete_operation(op.namespace.as_deref(), &op.key),
-            Command::Increment { amount, saturating } => self.execute_increment_operation(
+            Command::Increment {
+                amount,
+                overflow,
+                return_previous,
+            } => self.execute_increment_operation(
                 op.namespace.as_deref(),
                 &op.key,
                 &amount,
-                saturating,
+                overflow,
+                return_previous,
                 now,
             ),
-            Command::Decrement { amount, saturating } => self.execute_decrement_operation(
+            Command::Decrement {
+                amount,
+                overflow,
+                return_previous,
+            } => self.execute_decrement_operation(
                 op.namespace.as_deref(),
                 &op.key,
                 &amount,
-                saturating,
+                overflow,
+                return_previous,
+                now,
+            ),
+            Command::Swap {
+                other_namespace,
+                other_key,
+            } => self.execute_swap_operation(
+                op.namespace.as_deref(),
+                &op.key,
+                other_namespace.as_deref(),
+                &other_key,
                 now,
             ),
+            Command::Scan(scan) => {
+                self.execute_scan_operation(op.namespace.as_deref(), &op.key, scan)
+            }
+            Command::DeleteIfEquals { expected } => {
+                self.execute_delete_if_equals_operation(op.namespace.as_deref(), &op.key, &expected)
+            }
         };
         if result.is_ok() {
-            if self.needs_commit(now) {
-                self.commit_dirty_keys(state);
-            }
-            self.update_background_worker_target();
+            self.finish_operation(now, state);
         }
         result
     }
 
+    pub fn scan_matching<F: Fn(&Value) -> bool>(
+        &mut self,
+        namespace: Option<&str>,
+        filter: F,
+        state: &Arc<Mutex<KeyValueState>>,
+    ) -> Result<Vec<KeyEntry>, bonsaidb_core::Error> {
+        let now = self.clock.now();
+        self.remove_expired_keys(now);
+        let entries = self.scan_entries(namespace, "", None, usize::MAX, |entry| {
+            filter(&entry.value)
+        })?;
+        self.finish_operation(now, state);
+        Ok(entries)
+    }
+
+    fn finish_operation(&mut self, now: Timestamp, state: &Arc<Mutex<KeyValueState>>) {
+        if self.needs_commit(now) {
+            self.commit_dirty_keys(state);
+        }
+        self.update_background_worker_target();
+    }
+
     #[cfg_attr(
         feature = "tracing",
         tracing::instrument(level = "trace", skip(self, set, now),)
@@ -425,15 +980,28 @@ impl KeyValueState {
         namespace: Option<&str>,
         key: &str,
         delete: bool,
+        now: Timestamp,
     ) -> Result<Output, bonsaidb_core::Error> {
         let full_key = full_key(namespace, key);
         let entry = if delete {
-            self.remove(full_key).map_err(Error::from)?
+            self.remove(full_key.clone()).map_err(Error::from)?
         } else {
             self.get(&full_key).map_err(Error::from)?
         };
 
-        Ok(Output::Value(entry.map(|e| e.value)))
+        // The expirations of persisted entries are loaded in the background
+        // after the database is opened, so an entry may have expired without
+        // having been removed yet.
+        match entry {
+            Some(entry) if entry.expiration.is_some_and(|expiration| expiration <= now) => {
+                if !delete {
+                    self.remove(full_key).map_err(Error::from)?;
+                }
+                self.quota.keys_removed(1);
+                Ok(Output::Value(None))
+            }
+            entry => Ok(Output::Value(entry.map(|e| e.value))),
+        }
     }
 
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
@@ -451,42 +1019,256 @@ impl KeyValueState {
         }
     }
 
+    fn execute_delete_if_equals_operation(
+        &mut self,
+        namespace: Option<&str>,
+        key: &str,
+        expected: &Value,
+    ) -> Result<Output, bonsaidb_core::Error> {
+        let full_key = full_key(namespace, key);
+        let matches = self
+            .get(&full_key)
+            .map_err(Error::from)?
+            .map_or(false, |entry| entry.value == *expected);
+        if matches {
+            self.remove(full_key).map_err(Error::from)?;
+            Ok(Output::Status(KeyStatus::Deleted))
+        } else {
+            Ok(Output::Status(KeyStatus::NotChanged))
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self, now))
+    )]
+    fn execute_swap_operation(
+        &mut self,
+        namespace: Option<&str>,
+        key: &str,
+        other_namespace: Option<&str>,
+        other_key: &str,
+        now: Timestamp,
+    ) -> Result<Output, bonsaidb_core::Error> {
+        let key = full_key(namespace, key);
+        let other_key = full_key(other_namespace, other_key);
+        if key == other_key {
+            return Ok(Output::Status(KeyStatus::NotChanged));
+        }
+
+        let entry = self.get(&key).map_err(Error::from)?;
+        let other_entry = self.get(&other_key).map_err(Error::from)?;
+        if entry.is_none() && other_entry.is_none() {
+            return Ok(Output::Status(KeyStatus::NotChanged));
+        }
+
+        // Both keys are marked dirty while the state is locked, which ensures
+        // that they are persisted together in the same transaction.
+        self.store_swapped_entry(key, other_entry, now);
+        self.store_swapped_entry(other_key, entry, now);
+
+        Ok(Output::Status(KeyStatus::Updated))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
+    fn execute_scan_operation(
+        &self,
+        namespace: Option<&str>,
+        prefix: &str,
+        scan: ScanCommand,
+    ) -> Result<Output, bonsaidb_core::Error> {
+        let limit = scan.limit.map_or(usize::MAX, |limit| {
+            usize::try_from(limit).unwrap_or(usize::MAX)
+        });
+        self.scan_entries(
+            namespace,
+            prefix,
+            scan.start_after.as_deref(),
+            limit,
+            |entry| {
+                scan.filter.as_ref().map_or(true, |filter| {
+                    filter.matches(&entry.value, entry.expiration)
+                })
+            },
+        )
+        .map(Output::Entries)
+    }
+
+    /// Returns up to `limit` entries whose keys begin with `prefix` within
+    /// `namespace`, sort after `start_after`, and match `filter`.
+    fn scan_entries<F: Fn(&Entry) -> bool>(
+        &self,
+        namespace: Option<&str>,
+        prefix: &str,
+        start_after: Option<&str>,
+        limit: usize,
+        filter: F,
+    ) -> Result<Vec<KeyEntry>, bonsaidb_core::Error> {
+        let namespace_length = full_key(namespace, "").len();
+        let full_prefix = full_key(namespace, prefix);
+        let (start, start_is_excluded) = match start_after {
+            Some(start_after) if start_after >= prefix => (full_key(namespace, start_after), true),
+            _ => (full_prefix.clone(), false),
+        };
+        let matches = |full_key: &str, entry: &Entry| {
+            full_key.starts_with(&full_prefix)
+                // Skip keys that belong to a nested namespace.
+                && !full_key[namespace_length..].contains('\0')
+                && filter(entry)
+        };
+
+        // Keys that haven't been persisted yet take precedence over the keys
+        // stored on disk.
+        let mut pending = BTreeMap::new();
+        let range = if start_is_excluded {
+            (Bound::Excluded(start.as_str()), Bound::Unbounded)
+        } else {
+            (Bound::Included(start.as_str()), Bound::Unbounded)
+        };
+        if let Some(persisting) = &self.keys_being_persisted {
+            for (key, entry) in persisting.range::<str, _>(range) {
+                if !key.starts_with(&full_prefix) {
+                    break;
+                }
+                pending.insert(key.as_str(), entry.as_ref());
+            }
+        }
+        for (key, entry) in self.dirty_keys.range::<str, _>(range) {
+            if !key.starts_with(&full_prefix) {
+                break;
+            }
+            pending.insert(key.as_str(), entry.as_ref());
+        }
+
+        let mut entries = Vec::new();
+        // Only the first `limit` matches on disk can be included in the
+        // result, regardless of how many pending keys also match.
+        let matched_on_disk = Cell::new(0_usize);
+        let disk_range = if start_is_excluded {
+            (Bound::Excluded(start.as_bytes()), Bound::Unbounded)
+        } else {
+            (Bound::Included(start.as_bytes()), Bound::Unbounded)
+        };
+        self.roots
+            .tree(Unversioned::tree(KEY_TREE))
+            .map_err(Error::from)?
+            .scan::<Error, _, _, _, _>(
+                &disk_range,
+                true,
+                |_, _, _| ScanEvaluation::ReadData,
+                |key, _| {
+                    if !key.starts_with(full_prefix.as_bytes()) || matched_on_disk.get() >= limit {
+                        ScanEvaluation::Stop
+                    } else if std::str::from_utf8(key).map_or(true, |key| {
+                        key[namespace_length..].contains('\0') || pending.contains_key(key)
+                    }) {
+                        ScanEvaluation::Skip
+                    } else {
+                        ScanEvaluation::ReadData
+                    }
+                },
+                |key, _, entry: ArcBytes<'static>| {
+                    let entry = bincode::deserialize::<Entry>(&entry)
+                        .map_err(|err| AbortError::Other(Error::from(err)))?;
+                    let full_key = std::str::from_utf8(&key)
+                        .map_err(|err| AbortError::Other(Error::from(err)))?;
+                    if matches(full_key, &entry) {
+                        matched_on_disk.set(matched_on_disk.get() + 1);
+                        entries.push(KeyEntry {
+                            key: full_key[namespace_length..].to_string(),
+                            value: entry.value,
+                            expiration: entry.expiration,
+                        });
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(Error::from)?;
+
+        for (full_key, entry) in pending {
+            if let Some(entry) = entry.filter(|entry| matches(full_key, entry)) {
+                entries.push(KeyEntry {
+                    key: full_key[namespace_length..].to_string(),
+                    value: entry.value.clone(),
+                    expiration: entry.expiration,
+                });
+            }
+        }
+        entries.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+        entries.truncate(limit);
+
+        Ok(entries)
+    }
+
+    fn store_swapped_entry(&mut self, key: String, entry: Option<Entry>, now: Timestamp) {
+        if let Some(mut entry) = entry {
+            entry.last_updated = now;
+            self.update_key_expiration(&key, entry.expiration);
+            self.set(key, entry);
+        } else {
+            self.update_key_expiration(&key, None);
+            self.dirty_keys.insert(key, None);
+        }
+    }
+
     #[cfg_attr(
         feature = "tracing",
-        tracing::instrument(level = "trace", skip(self, amount, saturating, now))
+        tracing::instrument(level = "trace", skip(self, amount, overflow, now))
     )]
     fn execute_increment_operation(
         &mut self,
         namespace: Option<&str>,
         key: &str,
         amount: &Numeric,
-        saturating: bool,
+        overflow: OverflowPolicy,
+        return_previous: bool,
         now: Timestamp,
     ) -> Result<Output, bonsaidb_core::Error> {
-        self.execute_numeric_operation(namespace, key, amount, saturating, now, increment)
+        self.execute_numeric_operation(
+            namespace,
+            key,
+            amount,
+            overflow,
+            return_previous,
+            now,
+            increment,
+        )
     }
 
     #[cfg_attr(
         feature = "tracing",
-        tracing::instrument(level = "trace", skip(self, amount, saturating, now))
+        tracing::instrument(level = "trace", skip(self, amount, overflow, now))
     )]
     fn execute_decrement_operation(
         &mut self,
         namespace: Option<&str>,
         key: &str,
         amount: &Numeric,
-        saturating: bool,
+        overflow: OverflowPolicy,
+        return_previous: bool,
         now: Timestamp,
     ) -> Result<Output, bonsaidb_core::Error> {
-        self.execute_numeric_operation(namespace, key, amount, saturating, now, decrement)
+        self.execute_numeric_operation(
+            namespace,
+            key,
+            amount,
+            overflow,
+            return_previous,
+            now,
+            decrement,
+        )
     }
 
-    fn execute_numeric_operation<F: Fn(&Numeric, &Numeric, bool) -> Numeric>(
+    #[allow(clippy::too_many_arguments)]
+    fn execute_numeric_operation<
+        F: Fn(&Numeric, &Numeric, OverflowPolicy) -> Result<Numeric, bonsaidb_core::Error>,
+    >(
         &mut self,
         namespace: Option<&str>,
         key: &str,
         amount: &Numeric,
-        saturating: bool,
+        overflow: OverflowPolicy,
+        return_previous: bool,
         now: Timestamp,
         op: F,
     ) -> Result<Output, bonsaidb_core::Error> {
@@ -500,16 +1282,18 @@ impl KeyValueState {
 
         match entry.value {
             Value::Numeric(existing) => {
-                let value = Value::Numeric(op(&existing, amount, saturating).validate()?);
-                entry.value = value.clone();
+                let value = Value::Numeric(op(&existing, amount, overflow)?.validate()?);
+                let result = if return_previous {
+                    Value::Numeric(convert_to_type_of(&existing, amount, overflow)?)
+                } else {
+                    value.clone()
+                };
+                entry.value = value;
 
                 self.set(full_key, entry);
-                Ok(Output::Value(Some(value)))
+                Ok(Output::Value(Some(result)))
             }
-            Value::Bytes(_) => Err(bonsaidb_core::Error::other(
-                "bonsaidb-local",
-                "type of stored `Value` is not `Numeric`",
-            )),
+            Value::Bytes(_) => Err(bonsaidb_core::Error::ValueNotNumeric),
         }
     }
 
@@ -535,6 +1319,11 @@ impl KeyValueState {
     }
 
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
+    pub fn contains_key(&self, namespace: Option<&str>, key: &str) -> Result<bool, nebari::Error> {
+        self.get(&full_key(namespace, key))
+            .map(|entry| entry.is_some())
+    }
+
     fn get(&self, key: &str) -> Result<Option<Entry>, nebari::Error> {
         if let Some(entry) = self.dirty_keys.get(key) {
             Ok(entry.clone())
@@ -596,7 +1385,7 @@ impl KeyValueState {
             let expiration_timeout = self.expiring_keys.get(key).unwrap();
             *expiration_timeout
         });
-        let now = Timestamp::now();
+        let now = self.clock.now();
         let persisting = self.keys_being_persisted.is_some();
         let commit_target = (!persisting)
             .then(|| {
@@ -631,13 +1420,86 @@ impl KeyValueState {
     }
 
     fn remove_expired_keys(&mut self, now: Timestamp) {
+        let mut removed = 0;
         while !self.expiration_order.is_empty()
             && self.expiring_keys.get(&self.expiration_order[0]).unwrap() <= &now
         {
             let key = self.expiration_order.pop_front().unwrap();
             self.expiring_keys.remove(&key);
             self.dirty_keys.insert(key, None);
+            removed += 1;
         }
+        self.quota.keys_removed(removed);
+    }
+
+    /// Examines up to `batch_size` persisted entries within `start` and `end`,
+    /// deleting the entries that have expired and tracking when the others
+    /// expire. Entries with changes that haven't been persisted are skipped,
+    /// as their expirations are already tracked. Returns the last key
+    /// examined, or `None` if no entries remain in the range.
+    pub fn load_expirations(
+        &mut self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        batch_size: usize,
+        status: &mut ExpirationLoadStatus,
+        state: &Arc<Mutex<KeyValueState>>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let now = self.clock.now();
+        let examined = Cell::new(0);
+        let mut entries = Vec::new();
+        self.roots
+            .tree(Unversioned::tree(KEY_TREE))?
+            .scan::<Error, _, _, _, _>(
+                &(start, end),
+                true,
+                |_, _, _| ScanEvaluation::ReadData,
+                |_, _| {
+                    if examined.get() < batch_size.max(1) {
+                        examined.set(examined.get() + 1);
+                        ScanEvaluation::ReadData
+                    } else {
+                        ScanEvaluation::Stop
+                    }
+                },
+                |key, _, entry: ArcBytes<'static>| {
+                    entries.push((key, entry));
+                    Ok(())
+                },
+            )?;
+        let Some(last_key) = entries.last().map(|(key, _)| key.to_vec()) else {
+            return Ok(None);
+        };
+
+        for (key, entry) in entries {
+            status.keys_scanned += 1;
+            let key = std::str::from_utf8(&key)?;
+            if self.dirty_keys.contains_key(key)
+                || self
+                    .keys_being_persisted
+                    .as_ref()
+                    .map_or(false, |keys| keys.contains_key(key))
+            {
+                continue;
+            }
+
+            match bincode::deserialize::<Entry>(&entry)?.expiration {
+                Some(expiration) if expiration <= now => {
+                    self.update_key_expiration(key, None);
+                    self.dirty_keys.insert(key.to_string(), None);
+                    self.quota.keys_removed(1);
+                    status.expired_keys_removed += 1;
+                }
+                Some(expiration) => {
+                    self.update_key_expiration(key.to_string(), Some(expiration));
+                    status.expirations_loaded += 1;
+                }
+                None => {}
+            }
+        }
+        self.finish_operation(now, state);
+
+        Ok(Some(last_key))
     }
 
     fn needs_commit(&mut self, now: Timestamp) -> bool {
@@ -654,6 +1516,7 @@ impl KeyValueState {
         if !self.dirty_keys.is_empty() && self.keys_being_persisted.is_none() {
             let keys = Arc::new(std::mem::take(&mut self.dirty_keys));
             self.keys_being_persisted = Some(keys.clone());
+            self.receipts_being_persisted = std::mem::take(&mut self.dirty_receipts);
             Some(keys)
         } else {
             None
@@ -668,13 +1531,62 @@ impl KeyValueState {
                 .name(String::from("keyvalue-persist"))
                 .spawn(move || Self::persist_keys(&state, &roots, &keys))
                 .unwrap();
-            self.last_commit = Timestamp::now();
+            self.last_commit = self.clock.now();
             true
         } else {
             false
         }
     }
 
+    /// Persists all dirty keys before returning. If keys are already being
+    /// persisted, this waits for that persistence to complete first, since
+    /// the keys being persisted may include writes that this call needs to
+    /// make durable.
+    pub fn persist(
+        key_value_state: &Arc<Mutex<KeyValueState>>,
+    ) -> Result<(), bonsaidb_core::Error> {
+        loop {
+            let mut state = key_value_state.lock();
+            if state.keys_being_persisted.is_some() {
+                // The persistence thread updates `last_persistence` while
+                // holding the lock, so creating the watcher here cannot miss
+                // the notification.
+                let mut persistence_watcher = state.last_persistence.watch();
+                drop(state);
+                persistence_watcher
+                    .watch()
+                    .map_err(|_| Error::InternalCommunication)?;
+                continue;
+            }
+
+            let Some(keys) = state.stage_dirty_keys() else {
+                return Ok(());
+            };
+            state.last_commit = state.clock.now();
+            let roots = state.roots.clone();
+            drop(state);
+
+            return Self::persist_keys(key_value_state, &roots, &keys);
+        }
+    }
+
+    /// Returns a receipt that is signaled once every change made before this
+    /// call has been persisted.
+    pub fn durability_receipt(&mut self) -> DurabilityReceipt {
+        let receipts = if !self.dirty_keys.is_empty() {
+            &mut self.dirty_receipts
+        } else if self.keys_being_persisted.is_some() {
+            &mut self.receipts_being_persisted
+        } else {
+            return DurabilityReceipt { pending: None };
+        };
+        let (sender, receiver) = flume::bounded(1);
+        receipts.push(sender);
+        DurabilityReceipt {
+            pending: Some(receiver),
+        }
+    }
+
     #[cfg(test)]
     pub fn persistence_watcher(&self) -> Watcher<Timestamp> {
         self.last_persistence.watch()
@@ -686,6 +1598,7 @@ impl KeyValueState {
         roots: &Roots<AnyFile>,
         keys: &BTreeMap<String, Option<Entry>>,
     ) -> Result<(), bonsaidb_core::Error> {
+        let replication_log = key_value_state.lock().replication_log;
         let mut transaction = roots
             .transaction(&[Unversioned::tree(KEY_TREE)])
             .map_err(Error::from)?;
@@ -704,10 +1617,17 @@ impl KeyValueState {
                     let (namespace, key) = split_key(full_key).unwrap();
 
                     if let Some(new_value) = keys.get(full_key).unwrap() {
+                        let (value, expiration) = if replication_log {
+                            (Some(new_value.value.clone()), new_value.expiration)
+                        } else {
+                            (None, None)
+                        };
                         changed_keys.push(ChangedKey {
                             namespace,
                             key,
                             deleted: false,
+                            value,
+                            expiration,
                         });
                         let bytes = bincode::serialize(new_value).unwrap();
                         nebari::tree::KeyOperation::Set(ArcBytes::from(bytes))
@@ -716,6 +1636,8 @@ impl KeyValueState {
                             namespace,
                             key,
                             deleted: existing_value.is_some(),
+                            value: None,
+                            expiration: None,
                         });
                         nebari::tree::KeyOperation::Remove
                     } else {
@@ -738,8 +1660,12 @@ impl KeyValueState {
         // If we are shutting down, check if we still have dirty keys.
         let final_keys = {
             let mut state = key_value_state.lock();
-            state.last_persistence.replace(Timestamp::now());
+            let now = state.clock.now();
+            state.last_persistence.replace(now);
             state.keys_being_persisted = None;
+            for receipt in state.receipts_being_persisted.drain(..) {
+                drop(receipt.send(()));
+            }
             state.update_background_worker_target();
             // This block is a little ugly to avoid having to acquire the lock
             // twice. If we're shutting down and have no dirty keys, we notify
@@ -767,6 +1693,7 @@ impl KeyValueState {
 pub fn background_worker(
     key_value_state: &Weak<Mutex<KeyValueState>>,
     timestamp_receiver: &mut Watcher<BackgroundWorkerProcessTarget>,
+    clock: &dyn Clock,
     storage_lock: Option<StorageLock>,
 ) {
     loop {
@@ -782,7 +1709,7 @@ pub fn background_worker(
             BackgroundWorkerProcessTarget::Timestamp(target) => {
                 // With a target, we need to wait to receive a target only as
                 // long as there is time remaining.
-                let remaining = target - Timestamp::now();
+                let remaining = target - clock.now();
                 if let Some(remaining) = remaining {
                     // recv_timeout panics if Instant::checked_add(remaining)
                     // fails. So, we will cap the sleep time at 1 day.
@@ -808,7 +1735,7 @@ pub fn background_worker(
 
         if perform_operations {
             let mut state = key_value_state.lock();
-            let now = Timestamp::now();
+            let now = clock.now();
             state.remove_expired_keys(now);
             if state.needs_commit(now) {
                 state.commit_dirty_keys(&key_value_state);
@@ -824,6 +1751,51 @@ pub fn background_worker(
     drop(storage_lock);
 }
 
+/// Signals when changes made to the Key-Value store have been persisted.
+/// Returned from [`Database::durability_receipt()`].
+///
+/// Changes are persisted according to the database's configured
+/// [`KeyValuePersistence`], so a receipt may not complete until the next
+/// persistence threshold is reached. [`KeyValue::persist()`] can be called to
+/// persist all pending changes immediately.
+#[derive(Debug)]
+#[must_use = "the receipt does nothing unless waited upon"]
+pub struct DurabilityReceipt {
+    pending: Option<flume::Receiver<()>>,
+}
+
+impl DurabilityReceipt {
+    /// Returns true if the changes this receipt covers have been persisted.
+    #[must_use]
+    pub fn is_durable(&self) -> bool {
+        self.pending
+            .as_ref()
+            .map_or(true, |pending| !pending.is_empty())
+    }
+
+    /// Blocks the current thread until the changes this receipt covers have
+    /// been persisted.
+    pub fn wait(self) -> Result<(), bonsaidb_core::Error> {
+        match self.pending {
+            Some(pending) => pending
+                .recv()
+                .map_err(|_| bonsaidb_core::Error::from(Error::InternalCommunication)),
+            None => Ok(()),
+        }
+    }
+
+    /// Waits until the changes this receipt covers have been persisted.
+    pub async fn wait_async(self) -> Result<(), bonsaidb_core::Error> {
+        match self.pending {
+            Some(pending) => pending
+                .recv_async()
+                .await
+                .map_err(|_| bonsaidb_core::Error::from(Error::InternalCommunication)),
+            None => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BackgroundWorkerProcessTarget {
     Now,
@@ -831,10 +1803,27 @@ pub enum BackgroundWorkerProcessTarget {
     Never,
 }
 
+/// The progress of loading the expirations of a database's persisted
+/// Key-Value entries. Returned from
+/// [`Database::key_value_expiration_status()`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ExpirationLoadStatus {
+    /// The number of persisted entries that have been examined.
+    pub keys_scanned: u64,
+    /// The number of entries that had expired and were deleted.
+    pub expired_keys_removed: u64,
+    /// The number of entries whose expirations are now being tracked.
+    pub expirations_loaded: u64,
+    /// The number of batches that have been examined.
+    pub batches: u64,
+    /// True once every persisted entry has been examined.
+    pub complete: bool,
+}
+
 #[derive(Debug)]
 pub struct ExpirationLoader {
     pub database: Database,
-    pub launched_at: Timestamp,
+    pub batch_size: usize,
 }
 
 impl Keyed<Task> for ExpirationLoader {
@@ -849,40 +1838,89 @@ impl Job for ExpirationLoader {
 
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     fn execute(&mut self) -> Result<Self::Output, Self::Error> {
-        let database = self.database.clone();
-        let launched_at = self.launched_at;
-
-        for ((namespace, key), entry) in database.all_key_value_entries()? {
-            if entry.last_updated < launched_at && entry.expiration.is_some() {
-                self.database
-                    .update_key_expiration(full_key(namespace.as_deref(), &key), entry.expiration);
+        let storage = &self.database.storage.instance;
+        let name = self.database.data.name.clone();
+        let cursors = storage.expiration_cursors();
+
+        // Expirations are only tracked in memory, so every entry must be
+        // examined each time the database is loaded. Resuming after the last
+        // key an interrupted load examined visits the entries it never
+        // reached first, followed by the entries before it.
+        let passes = match cursors.cursor(&name)? {
+            Some(resume_after) => vec![
+                (Bound::Excluded(resume_after.to_vec()), Bound::Unbounded),
+                (Bound::Unbounded, Bound::Included(resume_after.to_vec())),
+            ],
+            None => vec![(Bound::Unbounded, Bound::Unbounded)],
+        };
+        let mut status = ExpirationLoadStatus::default();
+        for (mut start, end) in passes {
+            loop {
+                storage.tasks().check_cancelled(&name)?;
+                let Some(last_key) = self.database.data.context.load_expirations(
+                    borrow_bound(&start),
+                    borrow_bound(&end),
+                    self.batch_size,
+                    &mut status,
+                )?
+                else {
+                    break;
+                };
+                status.batches += 1;
+                storage
+                    .tasks()
+                    .update_key_value_expiration_status(name.clone(), status);
+                cursors.save(&name, Some(Bytes::from(last_key.clone())))?;
+                start = Bound::Excluded(last_key);
+                // Give the operations waiting on the key-value store a chance
+                // to run between batches.
+                std::thread::yield_now();
             }
         }
 
-        self.database
-            .storage()
-            .instance
+        cursors.save(&name, None)?;
+        status.complete = true;
+        storage
             .tasks()
-            .mark_key_value_expiration_loaded(self.database.data.name.clone());
+            .update_key_value_expiration_status(name, status);
 
         Ok(())
     }
 }
 
+fn borrow_bound(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Unbounded => Bound::Unbounded,
+        Bound::Included(key) => Bound::Included(key),
+        Bound::Excluded(key) => Bound::Excluded(key),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::time::{Duration, Instant};
+    use std::time::Duration;
 
     use bonsaidb_core::arc_bytes::serde::Bytes;
-    use bonsaidb_core::test_util::{TestDirectory, TimingTest};
+    use bonsaidb_core::test_util::TestDirectory;
     use nebari::io::any::{AnyFile, AnyFileManager};
 
     use super::*;
     use crate::config::PersistenceThreshold;
     use crate::database::Context;
+    use crate::test_util::TestClock;
+
+    /// Advances `clock` by `duration` and wakes the background worker so that
+    /// it can act upon the new time.
+    fn advance_clock(context: &Context, clock: &TestClock, duration: Duration) {
+        clock.advance(duration);
+        context
+            .key_value_state
+            .lock()
+            .update_background_worker_target();
+    }
 
     fn run_test_with_persistence<
-        F: Fn(Context, nebari::Roots<AnyFile>) -> anyhow::Result<()> + Send,
+        F: Fn(Context, nebari::Roots<AnyFile>, &TestClock) -> anyhow::Result<()> + Send,
     >(
         name: &str,
         persistence: KeyValuePersistence,
@@ -893,14 +1931,50 @@ mod tests {
             .file_manager(AnyFileManager::std())
             .open()?;
 
-        let context = Context::new(sled.clone(), persistence, None);
+        let clock = TestClock::default();
+        let context = Context::new(
+            sled.clone(),
+            persistence,
+            DurabilityMode::Deferred,
+            None,
+            Arc::new(clock.clone()),
+            false,
+        );
 
-        test_contents(context, sled)?;
+        test_contents(context, sled, &clock)?;
 
         Ok(())
     }
 
-    fn run_test<F: Fn(Context, nebari::Roots<AnyFile>) -> anyhow::Result<()> + Send>(
+    #[test]
+    fn monotonic_clock() {
+        let clock = MonotonicClock::new(Duration::from_secs(1));
+        let (start, start_instant) = *clock.anchor.lock();
+        let later = start_instant + Duration::from_secs(10);
+
+        // Small adjustments of the system clock are ignored.
+        assert_eq!(
+            clock.now_given(start + Duration::from_millis(9_500), later),
+            start + Duration::from_secs(10)
+        );
+        assert_eq!(
+            clock.now_given(start + Duration::from_millis(10_500), later),
+            start + Duration::from_secs(10)
+        );
+
+        // Large adjustments re-anchor the clock to the system time.
+        let adjusted = start + Duration::from_secs(60 * 60);
+        assert_eq!(clock.now_given(adjusted, later), adjusted);
+        assert_eq!(
+            clock.now_given(
+                adjusted + Duration::from_millis(500),
+                later + Duration::from_secs(1)
+            ),
+            adjusted + Duration::from_secs(1)
+        );
+    }
+
+    fn run_test<F: Fn(Context, nebari::Roots<AnyFile>, &TestClock) -> anyhow::Result<()> + Send>(
         name: &str,
         test_contents: F,
     ) -> anyhow::Result<()> {
@@ -909,23 +1983,28 @@ mod tests {
 
     #[test]
     fn basic_expiration() -> anyhow::Result<()> {
-        run_test("kv-basic-expiration", |context, roots| {
+        run_test("kv-basic-expiration", |context, roots, clock| {
             // Initialize the test state
             let mut persistence_watcher = context.kv_persistence_watcher();
             roots.delete_tree(KEY_TREE)?;
             let tree = roots.tree(Unversioned::tree(KEY_TREE))?;
             tree.set(b"atree\0akey", b"somevalue")?;
+            let start = clock.now();
 
             // Expire the existing key
             context.update_key_expiration(
                 full_key(Some("atree"), "akey"),
-                Some(Timestamp::now() + Duration::from_millis(100)),
+                Some(start + Duration::from_millis(100)),
             );
+            advance_clock(&context, clock, Duration::from_millis(100));
             // Wait for persistence.
-            persistence_watcher.next_value()?;
+            assert_eq!(
+                persistence_watcher.next_value()?,
+                start + Duration::from_millis(100)
+            );
 
             // Verify it is gone.
-            assert!(tree.get(b"akey")?.is_none());
+            assert!(tree.get(b"atree\0akey")?.is_none());
 
             Ok(())
         })
@@ -933,13 +2012,13 @@ mod tests {
 
     #[test]
     fn updating_expiration() -> anyhow::Result<()> {
-        run_test("kv-updating-expiration", |context, roots| {
+        run_test("kv-updating-expiration", |context, roots, clock| {
             // Initialize the test state
             let mut persistence_watcher = context.kv_persistence_watcher();
             roots.delete_tree(KEY_TREE)?;
             let tree = roots.tree(Unversioned::tree(KEY_TREE))?;
             tree.set(b"atree\0akey", b"somevalue")?;
-            let start = Timestamp::now();
+            let start = clock.now();
 
             // Set the expiration once.
             context.update_key_expiration(
@@ -951,9 +2030,14 @@ mod tests {
             context
                 .update_key_expiration(full_key(Some("atree"), "akey"), Some(correct_expiration));
 
-            // Wait for persistence, and ensure that the next persistence is
-            // after our expiration timestamp.
-            assert!(persistence_watcher.next_value()? > correct_expiration);
+            // Passing the original expiration must not remove the key.
+            advance_clock(&context, clock, Duration::from_millis(500));
+            assert!(tree.get(b"atree\0akey")?.is_some());
+
+            // Wait for persistence, and ensure that it happened at our
+            // expiration timestamp.
+            advance_clock(&context, clock, Duration::from_millis(500));
+            assert_eq!(persistence_watcher.next_value()?, correct_expiration);
 
             // Verify the key is gone now.
             assert_eq!(tree.get(b"atree\0akey")?, None);
@@ -964,31 +2048,40 @@ mod tests {
 
     #[test]
     fn multiple_keys_expiration() -> anyhow::Result<()> {
-        run_test("kv-multiple-keys-expiration", |context, roots| {
+        run_test("kv-multiple-keys-expiration", |context, roots, clock| {
             // Initialize the test state
             let mut persistence_watcher = context.kv_persistence_watcher();
             roots.delete_tree(KEY_TREE)?;
             let tree = roots.tree(Unversioned::tree(KEY_TREE))?;
             tree.set(b"atree\0akey", b"somevalue")?;
             tree.set(b"atree\0bkey", b"somevalue")?;
+            let start = clock.now();
 
             // Expire both keys, one for a shorter time than the other.
             context.update_key_expiration(
                 full_key(Some("atree"), "akey"),
-                Some(Timestamp::now() + Duration::from_millis(100)),
+                Some(start + Duration::from_millis(100)),
             );
             context.update_key_expiration(
                 full_key(Some("atree"), "bkey"),
-                Some(Timestamp::now() + Duration::from_secs(1)),
+                Some(start + Duration::from_secs(1)),
             );
 
             // Wait for the first persistence.
-            persistence_watcher.next_value()?;
+            advance_clock(&context, clock, Duration::from_millis(100));
+            assert_eq!(
+                persistence_watcher.next_value()?,
+                start + Duration::from_millis(100)
+            );
             assert!(tree.get(b"atree\0akey")?.is_none());
             assert!(tree.get(b"atree\0bkey")?.is_some());
 
             // Wait for the second persistence.
-            persistence_watcher.next_value()?;
+            advance_clock(&context, clock, Duration::from_millis(900));
+            assert_eq!(
+                persistence_watcher.next_value()?,
+                start + Duration::from_secs(1)
+            );
             assert!(tree.get(b"atree\0bkey")?.is_none());
 
             Ok(())
@@ -997,25 +2090,21 @@ mod tests {
 
     #[test]
     fn clearing_expiration() -> anyhow::Result<()> {
-        run_test("kv-clearing-expiration", |sender, sled| {
-            loop {
-                sled.delete_tree(KEY_TREE)?;
-                let tree = sled.tree(Unversioned::tree(KEY_TREE))?;
-                tree.set(b"atree\0akey", b"somevalue")?;
-                let timing = TimingTest::new(Duration::from_millis(100));
-                sender.update_key_expiration(
-                    full_key(Some("atree"), "akey"),
-                    Some(Timestamp::now() + Duration::from_millis(100)),
-                );
-                sender.update_key_expiration(full_key(Some("atree"), "akey"), None);
-                if timing.elapsed() > Duration::from_millis(100) {
-                    // Restart, took too long.
-                    continue;
-                }
-                timing.wait_until(Duration::from_millis(150));
-                assert!(tree.get(b"atree\0akey")?.is_some());
-                break;
-            }
+        run_test("kv-clearing-expiration", |context, roots, clock| {
+            roots.delete_tree(KEY_TREE)?;
+            let tree = roots.tree(Unversioned::tree(KEY_TREE))?;
+            tree.set(b"atree\0akey", b"somevalue")?;
+            context.update_key_expiration(
+                full_key(Some("atree"), "akey"),
+                Some(clock.now() + Duration::from_millis(100)),
+            );
+            context.update_key_expiration(full_key(Some("atree"), "akey"), None);
+
+            // With the expiration cleared, there is nothing left for the
+            // background worker to do once the original expiration passes.
+            advance_clock(&context, clock, Duration::from_millis(150));
+            assert!(context.key_value_state.lock().expiring_keys.is_empty());
+            assert!(tree.get(b"atree\0akey")?.is_some());
 
             Ok(())
         })
@@ -1023,71 +2112,55 @@ mod tests {
 
     #[test]
     fn out_of_order_expiration() -> anyhow::Result<()> {
-        run_test("kv-out-of-order-expiration", |context, roots| loop {
-            context.update_key_expiration(full_key(Some("atree"), "akey"), None);
-            context.update_key_expiration(full_key(Some("atree"), "bkey"), None);
-            context.update_key_expiration(full_key(Some("atree"), "ckey"), None);
+        run_test("kv-out-of-order-expiration", |context, roots, clock| {
             let mut persistence_watcher = context.kv_persistence_watcher();
-            drop(roots.delete_tree(KEY_TREE));
+            roots.delete_tree(KEY_TREE)?;
             let tree = roots.tree(Unversioned::tree(KEY_TREE))?;
             tree.set(b"atree\0akey", b"somevalue")?;
             tree.set(b"atree\0bkey", b"somevalue")?;
             tree.set(b"atree\0ckey", b"somevalue")?;
-            let timing = TimingTest::new(Duration::from_millis(100));
+            let start = clock.now();
             context.update_key_expiration(
                 full_key(Some("atree"), "akey"),
-                Some(Timestamp::now() + Duration::from_secs(3)),
+                Some(start + Duration::from_secs(3)),
             );
             context.update_key_expiration(
                 full_key(Some("atree"), "ckey"),
-                Some(Timestamp::now() + Duration::from_secs(1)),
+                Some(start + Duration::from_secs(1)),
             );
             context.update_key_expiration(
                 full_key(Some("atree"), "bkey"),
-                Some(Timestamp::now() + Duration::from_secs(2)),
+                Some(start + Duration::from_secs(2)),
             );
-            persistence_watcher.mark_read();
-            if timing.elapsed() > Duration::from_millis(500) {
-                println!("Restarting");
-                continue;
-            }
 
             // Wait for the first key to expire.
-            persistence_watcher
-                .watch_timeout(Duration::from_secs(5))
-                .unwrap();
-            persistence_watcher.mark_read();
-            if timing.elapsed() > Duration::from_millis(1500) {
-                println!("Restarting");
-                continue;
-            }
+            advance_clock(&context, clock, Duration::from_secs(1));
+            assert_eq!(
+                persistence_watcher.next_value()?,
+                start + Duration::from_secs(1)
+            );
             assert!(tree.get(b"atree\0akey")?.is_some());
             assert!(tree.get(b"atree\0bkey")?.is_some());
             assert!(tree.get(b"atree\0ckey")?.is_none());
 
             // Wait for the next key to expire.
-            persistence_watcher
-                .watch_timeout(Duration::from_secs(5))
-                .unwrap();
-            persistence_watcher.mark_read();
-            if timing.elapsed() > Duration::from_millis(2500) {
-                println!("Restarting");
-                continue;
-            }
+            advance_clock(&context, clock, Duration::from_secs(1));
+            assert_eq!(
+                persistence_watcher.next_value()?,
+                start + Duration::from_secs(2)
+            );
             assert!(tree.get(b"atree\0akey")?.is_some());
             assert!(tree.get(b"atree\0bkey")?.is_none());
 
             // Wait for the final key to expire.
-            persistence_watcher
-                .watch_timeout(Duration::from_secs(5))
-                .unwrap();
-            if timing.elapsed() > Duration::from_millis(3500) {
-                println!("Restarting");
-                continue;
-            }
+            advance_clock(&context, clock, Duration::from_secs(1));
+            assert_eq!(
+                persistence_watcher.next_value()?,
+                start + Duration::from_secs(3)
+            );
             assert!(tree.get(b"atree\0akey")?.is_none());
 
-            return Ok(());
+            Ok(())
         })
     }
 
@@ -1099,11 +2172,11 @@ mod tests {
                 PersistenceThreshold::after_changes(2),
                 PersistenceThreshold::after_changes(1).and_duration(Duration::from_secs(2)),
             ]),
-            &|context, roots| {
+            &|context, roots, clock| {
                 // Initialize the test state
                 let mut persistence_watcher = context.kv_persistence_watcher();
                 let tree = roots.tree(Unversioned::tree(KEY_TREE))?;
-                let start = Instant::now();
+                let start = clock.now();
                 // Set three keys in quick succession. The first two should
                 // persist immediately after the second is set, and the
                 // third should show up after 2 seconds.
@@ -1117,6 +2190,7 @@ mod tests {
                             keep_existing_expiration: false,
                             check: None,
                             return_previous_value: false,
+                            durability: DurabilityMode::Deferred,
                         }),
                     })
                     .unwrap();
@@ -1130,6 +2204,7 @@ mod tests {
                             keep_existing_expiration: false,
                             check: None,
                             return_previous_value: false,
+                            durability: DurabilityMode::Deferred,
                         }),
                     })
                     .unwrap();
@@ -1143,23 +2218,24 @@ mod tests {
                             keep_existing_expiration: false,
                             check: None,
                             return_previous_value: false,
+                            durability: DurabilityMode::Deferred,
                         }),
                     })
                     .unwrap();
                 // Wait for the first persistence to occur.
-                persistence_watcher.next_value()?;
+                assert_eq!(persistence_watcher.next_value()?, start);
 
                 assert!(tree.get(b"\0key1").unwrap().is_some());
                 assert!(tree.get(b"\0key2").unwrap().is_some());
                 assert!(tree.get(b"\0key3").unwrap().is_none());
 
-                // Wait for the second persistence
-                persistence_watcher.next_value()?;
+                // The second persistence should be delayed for two seconds.
+                advance_clock(&context, clock, Duration::from_secs(2));
+                assert_eq!(
+                    persistence_watcher.next_value()?,
+                    start + Duration::from_secs(2)
+                );
                 assert!(tree.get(b"\0key3").unwrap().is_some());
-                // The total operation should have taken *at least* two seconds,
-                // since the second persistence should have delayed for two
-                // seconds itself.
-                assert!(start.elapsed() > Duration::from_secs(2));
 
                 Ok(())
             },
@@ -1177,7 +2253,10 @@ mod tests {
         let context = Context::new(
             sled,
             KeyValuePersistence::lazy([PersistenceThreshold::after_changes(2)]),
+            DurabilityMode::Deferred,
             None,
+            Arc::new(SystemClock),
+            false,
         );
         context
             .perform_kv_operation(KeyOperation {
@@ -1189,6 +2268,7 @@ mod tests {
                     keep_existing_expiration: false,
                     check: None,
                     return_previous_value: false,
+                    durability: DurabilityMode::Deferred,
                 }),
             })
             .unwrap();
@@ -1201,4 +2281,57 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn persist_survives_crash() -> anyhow::Result<()> {
+        let dir = TestDirectory::new("kv-persist-survives-crash.bonsaidb");
+        {
+            let roots = nebari::Config::new(&dir)
+                .file_manager(AnyFileManager::std())
+                .open()?;
+            let tree = roots.tree(Unversioned::tree(KEY_TREE))?;
+
+            // This threshold will never be reached during this test.
+            let context = Context::new(
+                roots,
+                KeyValuePersistence::lazy([PersistenceThreshold::after_changes(1_000)]),
+                DurabilityMode::Deferred,
+                None,
+                Arc::new(SystemClock),
+                false,
+            );
+            context
+                .perform_kv_operation(KeyOperation {
+                    namespace: None,
+                    key: String::from("key1"),
+                    command: Command::Set(SetCommand {
+                        value: Value::Bytes(Bytes::from(b"somevalue".to_vec())),
+                        expiration: None,
+                        keep_existing_expiration: false,
+                        check: None,
+                        return_previous_value: false,
+                        durability: DurabilityMode::Deferred,
+                    }),
+                })
+                .unwrap();
+            assert!(tree.get(b"\0key1").unwrap().is_none());
+
+            context.persist_key_value_store()?;
+            assert!(tree.get(b"\0key1").unwrap().is_some());
+
+            // Simulate a crash by never running the context's shutdown
+            // logic, which would otherwise persist any remaining keys.
+            std::mem::forget(context);
+        }
+
+        let roots = nebari::Config::new(&dir)
+            .file_manager(AnyFileManager::std())
+            .open()?;
+        let tree = roots.tree(Unversioned::tree(KEY_TREE))?;
+        let entry = tree.get(b"\0key1")?.expect("key not persisted");
+        let entry = bincode::deserialize::<Entry>(&entry)?;
+        assert!(matches!(entry.value, Value::Bytes(bytes) if bytes.as_slice() == b"somevalue"));
+
+        Ok(())
+    }
 }
@@ -4,7 +4,7 @@ use bonsaidb_core::connection::{Connection, HasSession};
 use bonsaidb_core::permissions::bonsai::{
     database_resource_name, pubsub_topic_resource_name, BonsaiAction, DatabaseAction, PubSubAction,
 };
-use bonsaidb_core::pubsub::{self, database_topic, PubSub, Receiver};
+use bonsaidb_core::pubsub::{self, database_topic, Message, PubSub, Receiver, TryReceiveError};
 use bonsaidb_core::{circulate, Error};
 
 use crate::{Database, DatabaseNonBlocking};
@@ -17,10 +17,9 @@ impl PubSub for super::Database {
             database_resource_name(self.name()),
             &BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::CreateSuscriber)),
         )?;
-        Ok(self
-            .storage()
+        self.storage()
             .instance
-            .register_subscriber(self.session().and_then(|session| session.id), self.clone()))
+            .register_subscriber(self.session().and_then(|session| session.id), self.clone())
     }
 
     fn publish_bytes(&self, topic: Vec<u8>, payload: Vec<u8>) -> Result<(), bonsaidb_core::Error> {
@@ -28,10 +27,11 @@ impl PubSub for super::Database {
             pubsub_topic_resource_name(self.name(), &topic),
             &BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::Publish)),
         )?;
+        let topic = database_topic(&self.data.name, &topic);
         self.storage
             .instance
-            .relay()
-            .publish_raw(database_topic(&self.data.name, &topic), payload);
+            .record_published_message([topic.as_slice()]);
+        self.storage.instance.relay().publish_raw(topic, payload);
         Ok(())
     }
 
@@ -40,19 +40,23 @@ impl PubSub for super::Database {
         topics: impl IntoIterator<Item = Vec<u8>> + Send,
         payload: Vec<u8>,
     ) -> Result<(), bonsaidb_core::Error> {
-        self.storage.instance.relay().publish_raw_to_all(
-            topics
-                .into_iter()
-                .map(|topic| {
-                    self.check_permission(
-                        pubsub_topic_resource_name(self.name(), &topic),
-                        &BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::Publish)),
-                    )
-                    .map(|_| OwnedBytes::from(database_topic(&self.data.name, &topic)))
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-            payload,
-        );
+        let topics = topics
+            .into_iter()
+            .map(|topic| {
+                self.check_permission(
+                    pubsub_topic_resource_name(self.name(), &topic),
+                    &BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::Publish)),
+                )
+                .map(|_| OwnedBytes::from(database_topic(&self.data.name, &topic)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.storage
+            .instance
+            .record_published_message(topics.iter().map(|topic| topic.0.as_slice()));
+        self.storage
+            .instance
+            .relay()
+            .publish_raw_to_all(topics, payload);
         Ok(())
     }
 }
@@ -72,6 +76,20 @@ impl Subscriber {
     pub const fn id(&self) -> u64 {
         self.id
     }
+
+    /// Receives the next [`Message`] if one is already available, without
+    /// blocking. This is a shortcut for
+    /// [`Receiver::try_receive()`](Receiver::try_receive).
+    pub fn try_receive(&self) -> Result<Message, TryReceiveError> {
+        self.receiver.try_receive()
+    }
+
+    /// Receives all [`Message`]s that are currently available without
+    /// blocking. This is a shortcut for [`Receiver::drain()`](Receiver::drain).
+    #[must_use]
+    pub fn drain(&self) -> Vec<Message> {
+        self.receiver.drain()
+    }
 }
 
 impl Drop for Subscriber {
@@ -86,8 +104,12 @@ impl pubsub::Subscriber for Subscriber {
             pubsub_topic_resource_name(self.database.name(), &topic),
             &BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::SubscribeTo)),
         )?;
-        self.subscriber
-            .subscribe_to_raw(database_topic(self.database.name(), &topic));
+        let topic = database_topic(self.database.name(), &topic);
+        self.database
+            .storage()
+            .instance
+            .record_subscribe(self.id, topic.clone());
+        self.subscriber.subscribe_to_raw(topic);
         Ok(())
     }
 
@@ -96,8 +118,12 @@ impl pubsub::Subscriber for Subscriber {
             pubsub_topic_resource_name(self.database.name(), topic),
             &BonsaiAction::Database(DatabaseAction::PubSub(PubSubAction::UnsubscribeFrom)),
         )?;
-        self.subscriber
-            .unsubscribe_from_raw(&database_topic(self.database.name(), topic));
+        let topic = database_topic(self.database.name(), topic);
+        self.database
+            .storage()
+            .instance
+            .record_unsubscribe(self.id, &topic);
+        self.subscriber.unsubscribe_from_raw(&topic);
         Ok(())
     }
 
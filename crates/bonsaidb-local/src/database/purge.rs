@@ -0,0 +1,291 @@
+use std::convert::Infallible;
+
+use bonsaidb_core::connection::LowLevelConnection;
+use bonsaidb_core::document::DocumentId;
+use bonsaidb_core::key::KeyEncoding;
+use bonsaidb_core::permissions::bonsai::{
+    document_resource_name, BonsaiAction, DatabaseAction, DocumentAction,
+};
+use bonsaidb_core::schema::{self, CollectionName};
+use bonsaidb_core::transaction::{Operation, Transaction};
+use nebari::io::any::AnyFile;
+use nebari::tree::{AnyTreeRoot, ScanEvaluation, Unversioned, Versioned};
+use nebari::{AbortError, ArcBytes, Tree};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+
+use crate::database::document_tree_name;
+use crate::{Database, DatabaseNonBlocking, Error};
+
+/// Coordinates rewriting a collection's document tree with the other
+/// accesses to a database's documents.
+#[derive(Debug, Default)]
+pub(crate) struct PurgeState {
+    /// Held for reading while documents are read or a transaction is applied,
+    /// and held for writing while a document tree is rewritten.
+    access: RwLock<()>,
+    /// Whether purges interrupted before the database was opened have been
+    /// completed.
+    recovered: Mutex<bool>,
+}
+
+impl PurgeState {
+    /// Blocks while a document tree is being rewritten. The guard must be
+    /// held while the transaction is applied.
+    pub fn begin_write(&self) -> RwLockReadGuard<'_, ()> {
+        // Readers may apply transactions or wait for jobs that read, so the
+        // lock is acquired recursively to avoid deadlocking with a waiting
+        // purge.
+        self.access.read_recursive()
+    }
+
+    /// Blocks while a document tree is being rewritten. The guard must be
+    /// held while documents are read, ensuring a partially rewritten tree is
+    /// never observed.
+    pub fn begin_read(&self) -> RwLockReadGuard<'_, ()> {
+        self.access.read_recursive()
+    }
+}
+
+impl Database {
+    /// Returns a guard that must be held while reading documents, which
+    /// prevents a purge from rewriting a document tree while it is read.
+    pub(crate) fn begin_document_read(&self) -> RwLockReadGuard<'_, ()> {
+        self.data.context.purges.begin_read()
+    }
+
+    /// Permanently removes every version of the document with `id`,
+    /// including its current version if it hasn't been deleted.
+    ///
+    /// Deleting a document only records a new version, leaving the previous
+    /// contents in the collection's versioned tree. This function rewrites
+    /// the collection's document tree without any of the document's
+    /// versions, retaining the history of every other document. The previous
+    /// contents remain in unused regions of the database's files until the
+    /// collection is [compacted](bonsaidb_core::connection::Connection::compact_collection).
+    ///
+    /// After purging, [`document_history()`](Self::document_history) returns
+    /// no versions for `id`, and [snapshots](Self::read_snapshot) can no
+    /// longer read the document as of any point in time. Purging fails while
+    /// any snapshots exist. Reads and writes of the database's documents,
+    /// including view updates, wait until the tree has been rewritten, which
+    /// requires copying every version of every document in the collection.
+    ///
+    /// This requires the same permission as deleting the document.
+    pub fn purge_document_history<C, PrimaryKey>(
+        &self,
+        id: &PrimaryKey,
+    ) -> Result<(), bonsaidb_core::Error>
+    where
+        C: schema::Collection,
+        PrimaryKey: KeyEncoding<C::PrimaryKey> + ?Sized,
+    {
+        let collection = C::collection_name();
+        let id = DocumentId::new(id)?;
+        self.check_permission(
+            document_resource_name(self.name(), &collection, &id),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Delete)),
+        )?;
+        self.check_documents_writable()?;
+
+        loop {
+            // Deleting the current version through a transaction keeps the
+            // collection's views up to date.
+            if let Some(document) = self.get_from_collection(id.clone(), &collection)? {
+                match self.apply_transaction(Transaction::from(Operation::delete(
+                    collection.clone(),
+                    document.header,
+                ))) {
+                    Ok(_)
+                    | Err(
+                        bonsaidb_core::Error::DocumentConflict(..)
+                        | bonsaidb_core::Error::DocumentNotFound(..),
+                    ) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+
+            let access = self.data.context.purges.access.write();
+            let commits = self.data.context.snapshots.block_commits();
+            let compaction = self.data.context.snapshots.begin_compaction()?;
+            // The document may have been written again before writes were
+            // blocked.
+            if self
+                .roots()
+                .tree(self.collection_tree::<Versioned, _>(
+                    &collection,
+                    document_tree_name(&collection),
+                )?)
+                .map_err(Error::from)?
+                .get(id.as_ref())
+                .map_err(Error::from)?
+                .is_some()
+            {
+                continue;
+            }
+
+            self.rewrite_document_tree(&collection, &id)?;
+            drop(compaction);
+            drop(commits);
+            drop(access);
+            return Ok(());
+        }
+    }
+
+    /// Copies every version of every document in `collection` except
+    /// `purged` into a new tree, and then replaces the collection's document
+    /// tree with it.
+    fn rewrite_document_tree(
+        &self,
+        collection: &CollectionName,
+        purged: &DocumentId,
+    ) -> Result<(), Error> {
+        let names = PurgeTrees::new(collection);
+        let roots = self.roots();
+        roots.delete_tree(names.replacement.clone())?;
+        let replacement = roots
+            .tree(self.collection_tree::<Versioned, _>(collection, names.replacement.clone())?)?;
+        copy_versions(
+            &roots
+                .tree(self.collection_tree::<Versioned, _>(collection, names.documents.clone())?)?,
+            |key| key != purged.as_ref(),
+            |key, data| {
+                if data.is_empty() {
+                    replacement.remove(&key).map(drop)
+                } else {
+                    replacement.set(key, data).map(drop)
+                }
+            },
+        )?;
+        // Once the marker is set, the replacement is complete, and an
+        // interrupted purge can be finished when the database is next opened.
+        roots
+            .tree(Unversioned::tree(names.marker.clone()))?
+            .set(PURGE_COMPLETE.to_vec(), Vec::new())?;
+        self.finish_purge(collection, &names)
+    }
+
+    /// Replaces the collection's document tree with the completed
+    /// replacement. The replacement's versions are written and the marker is
+    /// cleared in a single transaction, so the document tree either contains
+    /// every version or the purge is finished again when the database is next
+    /// opened.
+    fn finish_purge(&self, collection: &CollectionName, names: &PurgeTrees) -> Result<(), Error> {
+        let roots = self.roots();
+        let replacement = roots
+            .tree(self.collection_tree::<Versioned, _>(collection, names.replacement.clone())?)?;
+        roots.delete_tree(names.documents.clone())?;
+        let trees: Vec<Box<dyn AnyTreeRoot<AnyFile>>> = vec![
+            Box::new(self.collection_tree::<Versioned, _>(collection, names.documents.clone())?),
+            Box::new(Unversioned::tree(names.marker.clone())),
+        ];
+        let mut transaction = roots.transaction::<_, dyn AnyTreeRoot<AnyFile>>(&trees)?;
+        {
+            let mut documents = transaction.tree::<Versioned>(0).unwrap();
+            copy_versions(
+                &replacement,
+                |_| true,
+                |key, data| {
+                    if data.is_empty() {
+                        documents.remove(&key).map(drop)
+                    } else {
+                        documents.set(key, data).map(drop)
+                    }
+                },
+            )?;
+        }
+        transaction
+            .tree::<Unversioned>(1)
+            .unwrap()
+            .remove(PURGE_COMPLETE)?;
+        transaction.commit()?;
+
+        roots.delete_tree(names.marker.clone())?;
+        roots.delete_tree(names.replacement.clone())?;
+        Ok(())
+    }
+
+    /// Completes any purges that were interrupted before they replaced their
+    /// collection's document tree, and removes the leftover trees of purges
+    /// interrupted before or after that.
+    pub(crate) fn finish_interrupted_purges(&self) -> Result<(), Error> {
+        let mut recovered = self.data.context.purges.recovered.lock();
+        if *recovered {
+            return Ok(());
+        }
+
+        let tree_names = self.roots().tree_names()?;
+        for collection in self.schematic().collections() {
+            let names = PurgeTrees::new(collection);
+            let complete = tree_names.contains(&names.marker)
+                && self
+                    .roots()
+                    .tree(Unversioned::tree(names.marker.clone()))?
+                    .get(PURGE_COMPLETE)?
+                    .is_some();
+            if complete {
+                self.finish_purge(collection, &names)?;
+            } else {
+                for tree in [&names.marker, &names.replacement] {
+                    if tree_names.contains(tree) {
+                        self.roots().delete_tree(tree.clone())?;
+                    }
+                }
+            }
+        }
+        *recovered = true;
+        Ok(())
+    }
+}
+
+/// The key set in a purge's marker tree once its replacement tree is
+/// complete.
+const PURGE_COMPLETE: &[u8] = b"complete";
+
+pub(crate) struct PurgeTrees {
+    pub(crate) documents: String,
+    pub(crate) replacement: String,
+    pub(crate) marker: String,
+}
+
+impl PurgeTrees {
+    pub(crate) fn new(collection: &CollectionName) -> Self {
+        let documents = document_tree_name(collection);
+        Self {
+            replacement: format!("purge.{documents}"),
+            marker: format!("purge-complete.{documents}"),
+            documents,
+        }
+    }
+}
+
+/// Passes each version in `source` whose key matches `include` to `write`, in
+/// the order they were written. Versions without data are deletions.
+fn copy_versions<F, W>(
+    source: &Tree<Versioned, AnyFile>,
+    include: F,
+    mut write: W,
+) -> Result<(), Error>
+where
+    F: Fn(&[u8]) -> bool,
+    W: FnMut(ArcBytes<'static>, ArcBytes<'static>) -> Result<(), nebari::Error>,
+{
+    source
+        .scan_sequences(
+            ..,
+            true,
+            &mut |sequence| {
+                if include(&sequence.key) {
+                    ScanEvaluation::ReadData
+                } else {
+                    ScanEvaluation::Skip
+                }
+            },
+            &mut |sequence, data| -> Result<(), AbortError<Infallible>> {
+                write(sequence.key.clone(), data).map_err(AbortError::Nebari)
+            },
+        )
+        .map_err(|err| match err {
+            AbortError::Nebari(err) => Error::from(err),
+            AbortError::Other(never) => match never {},
+        })
+}
@@ -0,0 +1,419 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use bonsaidb_core::admin::{DatabaseQuota, QuotaKind, QuotaLimit};
+use bonsaidb_core::connection::HasSession;
+use bonsaidb_core::permissions::bonsai::{bonsaidb_resource_name, BonsaiAction, ServerAction};
+use bonsaidb_core::schema::CollectionName;
+use nebari::tree::Versioned;
+use parking_lot::{Mutex, MutexGuard, RwLock};
+
+use crate::database::document_tree_name;
+use crate::tasks::{Job, Keyed, Task};
+use crate::{Database, DatabaseNonBlocking, Error, Storage, StorageNonBlocking};
+
+/// How often the usage of a database with a quota is recounted while it is
+/// being written to.
+const RECONCILIATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The approximate resources used by a database. Returned from
+/// [`Storage::database_quota_usage()`](crate::Storage::database_quota_usage).
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct QuotaUsage {
+    /// The number of bytes the database uses on disk.
+    pub bytes: u64,
+    /// The number of documents stored in each collection.
+    pub documents: BTreeMap<CollectionName, u64>,
+    /// The number of keys stored in the key-value store.
+    pub keys: u64,
+}
+
+impl QuotaUsage {
+    /// Returns the usage of `kind`.
+    #[must_use]
+    pub fn usage(&self, kind: &QuotaKind) -> u64 {
+        match kind {
+            QuotaKind::Bytes => self.bytes,
+            QuotaKind::Documents(collection) => {
+                self.documents.get(collection).copied().unwrap_or_default()
+            }
+            QuotaKind::Keys => self.keys,
+        }
+    }
+}
+
+/// Receives notifications about the quotas of databases in a
+/// [`Storage`](crate::Storage).
+///
+/// If no monitor is configured using
+/// [`Builder::quota_monitor()`](crate::config::Builder::quota_monitor), soft
+/// limit warnings are logged using the `log` crate.
+pub trait QuotaMonitor: Debug + Send + Sync + 'static {
+    /// Invoked when a write causes the usage of `kind` in `database` to exceed
+    /// its soft `limit`. This is only invoked when the usage crosses the
+    /// limit, not for every write while it is exceeded.
+    fn soft_limit_exceeded(&self, database: &str, kind: &QuotaKind, limit: u64, usage: u64);
+}
+
+/// The quota of a database and its tracked usage.
+#[derive(Debug, Default)]
+pub(crate) struct QuotaState {
+    quota: RwLock<DatabaseQuota>,
+    usage: Mutex<TrackedUsage>,
+    /// The number of keys removed without a quota write, which haven't been
+    /// subtracted from the tracked usage yet.
+    removed_keys: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct TrackedUsage {
+    usage: QuotaUsage,
+    reconciled_at: Option<Instant>,
+}
+
+impl QuotaState {
+    pub fn set_quota(&self, quota: DatabaseQuota) {
+        *self.quota.write() = quota;
+        // Usage isn't tracked while a database is unlimited, so it must be
+        // recounted before the new quota is enforced.
+        self.usage.lock().reconciled_at = None;
+    }
+
+    pub fn quota(&self) -> DatabaseQuota {
+        *self.quota.read()
+    }
+
+    /// Returns the tracked number of bytes used, if the usage has been
+    /// counted.
+    pub fn tracked_bytes(&self) -> Option<u64> {
+        let tracked = self.usage.lock();
+        tracked.reconciled_at.map(|_| tracked.usage.bytes)
+    }
+
+    /// Records that `count` keys were removed without a quota write, such as
+    /// when they expire. This is called while the key-value store is locked,
+    /// and writes lock the tracked usage before the key-value store, so the
+    /// keys are subtracted the next time the tracked usage is locked instead.
+    pub fn keys_removed(&self, count: u64) {
+        if count > 0 {
+            self.removed_keys.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    fn take_removed_keys(&self) -> u64 {
+        self.removed_keys.swap(0, Ordering::Relaxed)
+    }
+
+    fn reconciled(&self, usage: QuotaUsage) {
+        let mut tracked = self.usage.lock();
+        tracked.usage = usage;
+        tracked.reconciled_at = Some(Instant::now());
+    }
+}
+
+/// The changes to a database's usage made by a write.
+#[derive(Debug, Default)]
+pub(crate) struct UsageChanges {
+    pub bytes: u64,
+    pub documents: HashMap<CollectionName, i64>,
+    pub keys: i64,
+}
+
+impl UsageChanges {
+    pub fn document_written(&mut self, collection: &CollectionName, bytes: usize, created: bool) {
+        self.bytes = self
+            .bytes
+            .saturating_add(u64::try_from(bytes).unwrap_or(u64::MAX));
+        if created {
+            *self.documents.entry(collection.clone()).or_default() += 1;
+        }
+    }
+
+    pub fn document_deleted(&mut self, collection: &CollectionName) {
+        *self.documents.entry(collection.clone()).or_default() -= 1;
+    }
+}
+
+/// Exclusive access to a database's tracked usage while a write is performed.
+pub(crate) struct QuotaWrite<'a> {
+    database: &'a Database,
+    quota: DatabaseQuota,
+    tracked: MutexGuard<'a, TrackedUsage>,
+}
+
+impl QuotaWrite<'_> {
+    /// Returns an error if applying `changes` would exceed a hard limit.
+    /// Changes that don't increase usage are always allowed.
+    pub fn check(&self, changes: &UsageChanges) -> Result<(), bonsaidb_core::Error> {
+        let usage = &self.tracked.usage;
+        if changes.bytes > 0 {
+            check_limit(
+                QuotaKind::Bytes,
+                &self.quota.bytes,
+                usage.bytes,
+                changes.bytes,
+            )?;
+        }
+        for (collection, &delta) in &changes.documents {
+            if let Ok(delta) = u64::try_from(delta) {
+                let kind = QuotaKind::Documents(collection.clone());
+                check_limit(
+                    kind.clone(),
+                    &self.quota.documents_per_collection,
+                    usage.usage(&kind),
+                    delta,
+                )?;
+            }
+        }
+        if let Ok(delta) = u64::try_from(changes.keys) {
+            check_limit(QuotaKind::Keys, &self.quota.keys, usage.keys, delta)?;
+        }
+        Ok(())
+    }
+
+    /// Records `changes` after the write has been performed, notifying the
+    /// quota monitor of any soft limits that were exceeded as a result.
+    pub fn apply(mut self, changes: &UsageChanges) {
+        let mut exceeded = Vec::new();
+        let usage = &mut self.tracked.usage;
+
+        let previous = usage.bytes;
+        usage.bytes = usage.bytes.saturating_add(changes.bytes);
+        exceeded.extend(soft_limit_crossed(
+            QuotaKind::Bytes,
+            &self.quota.bytes,
+            previous,
+            usage.bytes,
+        ));
+
+        for (collection, &delta) in &changes.documents {
+            let count = usage.documents.entry(collection.clone()).or_default();
+            let previous = *count;
+            *count = apply_delta(*count, delta);
+            exceeded.extend(soft_limit_crossed(
+                QuotaKind::Documents(collection.clone()),
+                &self.quota.documents_per_collection,
+                previous,
+                *count,
+            ));
+        }
+
+        let previous = usage.keys;
+        usage.keys = apply_delta(usage.keys, changes.keys);
+        exceeded.extend(soft_limit_crossed(
+            QuotaKind::Keys,
+            &self.quota.keys,
+            previous,
+            usage.keys,
+        ));
+
+        drop(self.tracked);
+        for (kind, limit, usage) in exceeded {
+            self.database.storage.instance.notify_soft_limit_exceeded(
+                self.database.name(),
+                &kind,
+                limit,
+                usage,
+            );
+        }
+    }
+}
+
+fn check_limit(
+    kind: QuotaKind,
+    limit: &QuotaLimit,
+    usage: u64,
+    delta: u64,
+) -> Result<(), bonsaidb_core::Error> {
+    match limit.hard {
+        Some(limit) if delta > 0 && usage.saturating_add(delta) > limit => {
+            Err(bonsaidb_core::Error::QuotaExceeded { kind, limit, usage })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn soft_limit_crossed(
+    kind: QuotaKind,
+    limit: &QuotaLimit,
+    previous: u64,
+    usage: u64,
+) -> Option<(QuotaKind, u64, u64)> {
+    limit
+        .soft
+        .filter(|&limit| previous <= limit && usage > limit)
+        .map(|limit| (kind, limit, usage))
+}
+
+fn apply_delta(value: u64, delta: i64) -> u64 {
+    if delta < 0 {
+        value.saturating_sub(delta.unsigned_abs())
+    } else {
+        value.saturating_add(delta.unsigned_abs())
+    }
+}
+
+impl Storage {
+    /// Returns the approximate resources used by the database named `name`.
+    ///
+    /// For databases with a [`DatabaseQuota`], this returns the usage tracked
+    /// while enforcing the quota, which is recounted periodically. For all
+    /// other databases, the usage is measured when this function is called.
+    ///
+    /// This requires the same permission as
+    /// [`StorageConnection::list_databases()`](bonsaidb_core::connection::StorageConnection::list_databases).
+    pub fn database_quota_usage(&self, name: &str) -> Result<QuotaUsage, bonsaidb_core::Error> {
+        self.check_permission(
+            bonsaidb_resource_name(),
+            &BonsaiAction::Server(ServerAction::ListDatabases),
+        )?;
+
+        let database = self
+            .instance
+            .database_without_schema(name, Some(self), None)?;
+        Ok(database.quota_usage()?)
+    }
+}
+
+impl Database {
+    fn quota_state(&self) -> &QuotaState {
+        &self.data.context.quota
+    }
+
+    /// Begins a write that is subject to this database's quota. Returns `None`
+    /// if this database has no quota.
+    ///
+    /// The returned guard must be held until the write has completed, which
+    /// serializes writes to databases that have a quota.
+    pub(crate) fn begin_quota_write(&self) -> Result<Option<QuotaWrite<'_>>, Error> {
+        let state = self.quota_state();
+        let quota = state.quota();
+        if quota.is_unlimited() {
+            return Ok(None);
+        }
+
+        let mut tracked = state.usage.lock();
+        let removed_keys = state.take_removed_keys();
+        match tracked.reconciled_at {
+            // The measured usage already excludes the removed keys.
+            None => {
+                tracked.usage = self.measure_quota_usage(tracked.usage.bytes)?;
+                tracked.reconciled_at = Some(Instant::now());
+            }
+            Some(reconciled_at) if reconciled_at.elapsed() > RECONCILIATION_INTERVAL => {
+                // Prevent spawning another reconciliation until this one has
+                // had a chance to complete.
+                tracked.reconciled_at = Some(Instant::now());
+                self.storage
+                    .instance
+                    .tasks()
+                    .spawn_quota_reconciliation(self);
+                tracked.usage.keys = tracked.usage.keys.saturating_sub(removed_keys);
+            }
+            Some(_) => {
+                tracked.usage.keys = tracked.usage.keys.saturating_sub(removed_keys);
+            }
+        }
+
+        Ok(Some(QuotaWrite {
+            database: self,
+            quota,
+            tracked,
+        }))
+    }
+
+    /// Returns the usage of this database. If this database has a quota, the
+    /// tracked usage is returned. Otherwise, the usage is measured.
+    pub(crate) fn quota_usage(&self) -> Result<QuotaUsage, Error> {
+        if let Some(write) = self.begin_quota_write()? {
+            Ok(write.tracked.usage.clone())
+        } else {
+            self.measure_quota_usage(0)
+        }
+    }
+
+    /// Counts the resources used by this database. If this database isn't
+    /// stored on disk, `tracked_bytes` is returned as the number of bytes
+    /// used.
+    fn measure_quota_usage(&self, tracked_bytes: u64) -> Result<QuotaUsage, Error> {
+        let mut documents = BTreeMap::new();
+        for collection in self.data.schema.collections() {
+            let stats =
+                self.roots()
+                    .tree(self.collection_tree::<Versioned, _>(
+                        collection,
+                        document_tree_name(collection),
+                    )?)?
+                    .reduce(&(..))?;
+            documents.insert(collection.clone(), stats.alive_keys);
+        }
+
+        let keys = u64::try_from(self.all_key_value_entries()?.len()).unwrap_or(u64::MAX);
+
+        let bytes =
+            directory_size(&self.storage.path().join(self.name()))?.unwrap_or(tracked_bytes);
+
+        Ok(QuotaUsage {
+            bytes,
+            documents,
+            keys,
+        })
+    }
+}
+
+/// Returns the total size of the files in `path`, or `None` if `path` does
+/// not exist.
+fn directory_size(path: &Path) -> Result<Option<u64>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut total = 0_u64;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        for entry in std::fs::read_dir(directory)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                total = total.saturating_add(metadata.len());
+            }
+        }
+    }
+    Ok(Some(total))
+}
+
+#[derive(Debug)]
+pub struct QuotaReconciler {
+    pub database: Database,
+}
+
+impl Keyed<Task> for QuotaReconciler {
+    fn key(&self) -> Task {
+        Task::QuotaReconciliation(self.database.data.name.clone())
+    }
+}
+
+impl Job for QuotaReconciler {
+    type Error = Error;
+    type Output = ();
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    fn execute(&mut self) -> Result<Self::Output, Self::Error> {
+        let state = self.database.quota_state();
+        let tracked_bytes = {
+            let tracked = state.usage.lock();
+            // Keys removed before measuring are excluded from the measured
+            // usage.
+            state.take_removed_keys();
+            tracked.usage.bytes
+        };
+        let usage = self.database.measure_quota_usage(tracked_bytes)?;
+        state.reconciled(usage);
+        Ok(())
+    }
+}
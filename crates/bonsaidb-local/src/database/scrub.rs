@@ -0,0 +1,332 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::fmt::Debug;
+use std::ops::Bound;
+use std::time::{Duration, Instant};
+
+use bonsaidb_core::arc_bytes::serde::Bytes;
+use bonsaidb_core::connection::{ScrubError, ScrubReport};
+use bonsaidb_core::permissions::bonsai::{database_resource_name, BonsaiAction, DatabaseAction};
+use bonsaidb_core::schema::CollectionName;
+use nebari::io::any::AnyFile;
+use nebari::tree::{Root, ScanEvaluation, TreeRoot, Unversioned, Versioned};
+use nebari::AbortError;
+use serde::{Deserialize, Serialize};
+
+use crate::database::document_tree_name;
+use crate::database::keyvalue::KEY_TREE;
+use crate::files::DatabaseCursors;
+use crate::tasks::{Job, Keyed, Task};
+use crate::views::{
+    view_dependents_tree_name, view_document_map_tree_name, view_entries_tree_name,
+    view_invalidated_docs_tree_name, view_prefix_reductions_tree_name, view_versions_tree_name,
+};
+use crate::{Database, DatabaseNonBlocking, Error};
+
+pub(crate) const SCRUB_CURSORS_FILE: &str = "scrub.cursors";
+
+/// Receives notifications about problems found while scrubbing databases in a
+/// [`Storage`](crate::Storage).
+///
+/// If no monitor is configured using
+/// [`Builder::scrub_monitor()`](crate::config::Builder::scrub_monitor),
+/// problems are logged using the `log` crate.
+pub trait ScrubMonitor: Debug + Send + Sync + 'static {
+    /// Invoked for each problem found while scrubbing `database`.
+    fn scrub_error(&self, database: &str, error: &ScrubError);
+}
+
+/// Where a database's previous scrub stopped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ScrubCursor {
+    tree: String,
+    last_key: Option<Bytes>,
+}
+
+/// The persisted cursors of every database whose last scrub was incomplete.
+pub(crate) type ScrubCursors = DatabaseCursors<ScrubCursor>;
+
+impl Database {
+    /// Reads every value stored in this database's document, view, and
+    /// key-value trees, reporting any that can't be read. Reading a value
+    /// verifies its checksum and decrypts and decompresses it if needed.
+    ///
+    /// If a previous scrub stopped before reaching the end of the database,
+    /// this scrub resumes where it stopped. Each problem found is also
+    /// reported to the storage's
+    /// [`ScrubMonitor`](crate::ScrubMonitor).
+    ///
+    /// This requires the [`DatabaseAction::Scrub`] permission.
+    pub fn scrub(&self) -> Result<ScrubReport, bonsaidb_core::Error> {
+        self.scrub_with_limit(None)
+    }
+
+    /// Scrubs this database like [`Self::scrub()`], stopping once `duration`
+    /// has elapsed. The next scrub resumes where this one stopped, allowing a
+    /// large database to be scrubbed across several maintenance windows.
+    pub fn scrub_for(&self, duration: Duration) -> Result<ScrubReport, bonsaidb_core::Error> {
+        self.scrub_with_limit(Some(duration))
+    }
+
+    pub(crate) fn scrub_with_limit(
+        &self,
+        duration: Option<Duration>,
+    ) -> Result<ScrubReport, bonsaidb_core::Error> {
+        self.check_permission(
+            database_resource_name(self.name()),
+            &BonsaiAction::Database(DatabaseAction::Scrub),
+        )?;
+        let report = self
+            .storage
+            .instance
+            .tasks()
+            .scrub(self.clone(), duration)?;
+        Ok(report)
+    }
+}
+
+#[derive(Debug)]
+pub struct Scrubber {
+    pub database: Database,
+    pub duration: Option<Duration>,
+}
+
+impl Keyed<Task> for Scrubber {
+    fn key(&self) -> Task {
+        Task::Scrub(self.database.data.name.clone(), self.duration)
+    }
+}
+
+impl Job for Scrubber {
+    type Error = Error;
+    type Output = ScrubReport;
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    fn execute(&mut self) -> Result<Self::Output, Self::Error> {
+        let cursors = self.database.storage.instance.scrub_cursors();
+        let cursor = cursors.cursor(self.database.name())?;
+        let mut scrub = Scrub {
+            database: &self.database,
+            deadline: self
+                .duration
+                .and_then(|duration| Instant::now().checked_add(duration)),
+            report: ScrubReport::default(),
+        };
+
+        let mut stopped_at = None;
+        for target in self.targets()? {
+            let start_after = match &cursor {
+                Some(cursor) if target.name < cursor.tree => continue,
+                Some(cursor) if target.name == cursor.tree => cursor.last_key.clone(),
+                _ => None,
+            };
+            if let Some(last_key) = scrub.tree(&target, start_after) {
+                stopped_at = Some(ScrubCursor {
+                    tree: target.name,
+                    last_key,
+                });
+                break;
+            }
+        }
+
+        scrub.report.complete = stopped_at.is_none();
+        cursors.save(self.database.name(), stopped_at)?;
+        for error in &scrub.report.errors {
+            self.database
+                .storage
+                .instance
+                .notify_scrub_error(self.database.name(), error);
+        }
+        Ok(scrub.report)
+    }
+}
+
+impl Scrubber {
+    /// Returns the trees to scrub, sorted by name so that a scrub can resume
+    /// where a previous one stopped. Opening a tree creates it, so only trees
+    /// that already exist are returned.
+    fn targets(&self) -> Result<Vec<Target>, Error> {
+        let existing = self
+            .database
+            .roots()
+            .tree_names()?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let mut targets = vec![Target {
+            name: KEY_TREE.to_string(),
+            collection: None,
+            versioned: false,
+        }];
+        for collection in self.database.schematic().collections() {
+            let mut push = |name: String, versioned: bool| {
+                targets.push(Target {
+                    name,
+                    collection: Some(collection.clone()),
+                    versioned,
+                });
+            };
+            push(document_tree_name(collection), true);
+            push(view_versions_tree_name(collection), false);
+            for view in self.database.data.schema.views_in_collection(collection) {
+                let name = view.view_name();
+                push(view_entries_tree_name(&name), false);
+                push(view_document_map_tree_name(&name), false);
+                push(view_invalidated_docs_tree_name(&name), false);
+                if view.caches_reduce_prefixes() {
+                    push(view_prefix_reductions_tree_name(&name), false);
+                }
+                if !view.dependencies().is_empty() {
+                    push(view_dependents_tree_name(&name), false);
+                }
+            }
+        }
+        targets.retain(|target| existing.contains(&target.name));
+        targets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(targets)
+    }
+}
+
+#[derive(Debug)]
+struct Target {
+    name: String,
+    collection: Option<CollectionName>,
+    versioned: bool,
+}
+
+struct Scrub<'a> {
+    database: &'a Database,
+    deadline: Option<Instant>,
+    report: ScrubReport,
+}
+
+impl Scrub<'_> {
+    /// Scrubs `target`, returning the last key checked if the deadline was
+    /// reached before the end of the tree.
+    fn tree(&mut self, target: &Target, start_after: Option<Bytes>) -> Option<Option<Bytes>> {
+        let result = match (&target.collection, target.versioned) {
+            (Some(collection), true) => self
+                .database
+                .collection_tree::<Versioned, _>(collection, target.name.clone())
+                .and_then(|root| self.scan(target, root, start_after)),
+            (Some(collection), false) => self
+                .database
+                .collection_tree::<Unversioned, _>(collection, target.name.clone())
+                .and_then(|root| self.scan(target, root, start_after)),
+            (None, _) => self.scan(target, Unversioned::tree(target.name.clone()), start_after),
+        };
+        self.report.trees_checked += 1;
+        match result {
+            Ok(stopped_at) => stopped_at,
+            Err(err) => {
+                self.error(target, None, &err);
+                None
+            }
+        }
+    }
+
+    fn scan<R: Root>(
+        &mut self,
+        target: &Target,
+        root: TreeRoot<R, AnyFile>,
+        start_after: Option<Bytes>,
+    ) -> Result<Option<Option<Bytes>>, Error> {
+        let tree = self.database.roots().tree(root)?;
+        let mut last_key = start_after.clone();
+        let stopped = Cell::new(false);
+        let chunks_checked = Cell::new(self.report.chunks_checked);
+        let result = tree.scan::<Infallible, _, _, _, _>(
+            &key_range(start_after.as_ref()),
+            true,
+            |_, _, _| ScanEvaluation::ReadData,
+            |_, _| {
+                // Every scrub checks at least one value, ensuring progress is
+                // made even if the deadline passed while it was queued.
+                if chunks_checked.get() > 0 && self.deadline_reached() {
+                    stopped.set(true);
+                    ScanEvaluation::Stop
+                } else {
+                    ScanEvaluation::ReadData
+                }
+            },
+            |key, _, _| {
+                chunks_checked.set(chunks_checked.get() + 1);
+                last_key = Some(Bytes::from(key.to_vec()));
+                Ok(())
+            },
+        );
+        self.report.chunks_checked = chunks_checked.get();
+        match result {
+            Ok(()) if stopped.get() => Ok(Some(last_key)),
+            Ok(()) => Ok(None),
+            // The scan can't report which value failed, so the remaining
+            // values are read individually.
+            Err(AbortError::Nebari(_)) => self.scan_individually(target, &tree, last_key),
+            Err(AbortError::Other(never)) => match never {},
+        }
+    }
+
+    fn scan_individually<R: Root>(
+        &mut self,
+        target: &Target,
+        tree: &nebari::Tree<R, AnyFile>,
+        start_after: Option<Bytes>,
+    ) -> Result<Option<Option<Bytes>>, Error> {
+        let mut keys = Vec::new();
+        tree.scan::<Infallible, _, _, _, _>(
+            &key_range(start_after.as_ref()),
+            true,
+            |_, _, _| ScanEvaluation::ReadData,
+            |key, _| {
+                keys.push(Bytes::from(key.to_vec()));
+                ScanEvaluation::Skip
+            },
+            |_, _, _| Ok(()),
+        )
+        .map_err(|err| match err {
+            AbortError::Nebari(err) => Error::from(err),
+            AbortError::Other(never) => match never {},
+        })?;
+
+        for (index, key) in keys.iter().enumerate() {
+            if index > 0 && self.deadline_reached() {
+                return Ok(Some(Some(keys[index - 1].clone())));
+            }
+            match tree.get(key) {
+                Ok(_) => self.report.chunks_checked += 1,
+                Err(err) => self.error(target, Some(key.clone()), &Error::from(err)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns true if the scrub should stop, either because its deadline
+    /// has passed or because the storage is shutting down.
+    fn deadline_reached(&self) -> bool {
+        self.deadline
+            .map_or(false, |deadline| Instant::now() >= deadline)
+            || self
+                .database
+                .storage
+                .instance
+                .tasks()
+                .check_cancelled(&self.database.data.name)
+                .is_err()
+    }
+
+    fn error(&mut self, target: &Target, key: Option<Bytes>, error: &Error) {
+        self.report.errors.push(ScrubError {
+            tree: target.name.clone(),
+            key,
+            message: error.to_string(),
+        });
+    }
+}
+
+fn key_range(start_after: Option<&Bytes>) -> (Bound<&[u8]>, Bound<&[u8]>) {
+    let start = match start_after {
+        Some(key) => Bound::Excluded(key.as_slice()),
+        None => Bound::Unbounded,
+    };
+    (start, Bound::Unbounded)
+}
@@ -0,0 +1,812 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::{self, RangeBounds};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bonsaidb_core::arc_bytes::serde::{Bytes, CowBytes};
+use bonsaidb_core::connection::{
+    AccessPolicy, Bound, DeletedDocuments, HasSchema, HasSession, LowLevelConnection, Range,
+    SerializedQueryKey, Session, Sort,
+};
+use bonsaidb_core::document::{BorrowedDocument, DocumentId, Header, OwnedDocument};
+use bonsaidb_core::permissions::bonsai::{
+    collection_resource_name, document_resource_name, view_resource_name, BonsaiAction,
+    DatabaseAction, DocumentAction, ViewAction,
+};
+use bonsaidb_core::schema::view::map::{MappedSerializedDocuments, MappedSerializedValue};
+use bonsaidb_core::schema::view::{self, AuxiliaryData, DocumentLookup};
+use bonsaidb_core::schema::{self, CollectionName, Schematic, ViewName};
+use bonsaidb_core::transaction::{OperationResult, Transaction};
+use nebari::io::any::AnyFile;
+use nebari::tree::{BorrowByteRange, ScanEvaluation, SequenceId, Versioned};
+use nebari::{AbortError, Tree};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::database::{deserialize_document, document_tree_name, DocumentIdRange};
+use crate::views::{auxiliary_data, EntryMapping, ViewEntry};
+use crate::{Database, DatabaseNonBlocking, Error};
+
+/// Coordinates the creation of [`Snapshot`]s with the transactions committed
+/// to a database.
+#[derive(Debug, Default)]
+pub(crate) struct SnapshotState {
+    /// Held for reading while a transaction that modifies documents is
+    /// committed, and held for writing while a snapshot records the state of
+    /// each collection.
+    commit: RwLock<()>,
+    /// Held for reading while a collection is compacted, and held for writing
+    /// while a snapshot is registered.
+    compactions: RwLock<()>,
+    /// The number of snapshots that have not been dropped.
+    active: AtomicUsize,
+}
+
+impl SnapshotState {
+    pub fn begin_commit(&self) -> RwLockReadGuard<'_, ()> {
+        self.commit.read()
+    }
+
+    pub fn block_commits(&self) -> RwLockWriteGuard<'_, ()> {
+        self.commit.write()
+    }
+
+    /// Returns a guard that prevents snapshots from being created until it is
+    /// dropped, or an error if any snapshots exist. Compacting a collection
+    /// discards the document versions that snapshots read from, so the guard
+    /// must be held until the compaction has finished.
+    pub fn begin_compaction(&self) -> Result<RwLockReadGuard<'_, ()>, bonsaidb_core::Error> {
+        let compactions = self.compactions.read();
+        if self.active.load(Ordering::Acquire) > 0 {
+            Err(bonsaidb_core::Error::Conflict(String::from(
+                "collections can't be compacted while a snapshot exists",
+            )))
+        } else {
+            Ok(compactions)
+        }
+    }
+}
+
+impl Database {
+    /// Returns a read-only view of this database as of the last committed
+    /// transaction. See [`Snapshot`] for more information.
+    pub fn read_snapshot(&self) -> Result<Snapshot, bonsaidb_core::Error> {
+        Snapshot::new(self).map_err(bonsaidb_core::Error::from)
+    }
+}
+
+/// A read-only view of a [`Database`] as of a single point in time. Returned
+/// from [`Database::read_snapshot()`].
+///
+/// `Snapshot` implements [`LowLevelConnection`]. Documents retrieved and views
+/// queried through a snapshot only reflect the transactions committed at or
+/// before [`Snapshot::transaction_id()`], regardless of how many transactions
+/// are committed while the snapshot is being used. Operations that would
+/// modify data return [`Error::ReadOnly`](bonsaidb_core::Error::ReadOnly).
+///
+/// Creating a snapshot is inexpensive and doesn't prevent other connections
+/// from writing to the database. Documents that haven't changed since the
+/// snapshot was created are read directly, and previous versions of changed
+/// documents are read from each collection's versioned tree. View queries map
+/// the snapshot's version of each changed document in place of the view's
+/// stored mappings. Reads become more expensive as more changes are committed
+/// after the snapshot was created. The mappings of views with
+/// [dependencies](schema::ViewSchema::dependencies) don't reflect changes
+/// made to the dependent collections after the snapshot was created.
+///
+/// While any snapshot of a database exists, its collections can't be
+/// compacted, because compaction discards the document versions snapshots
+/// read from. Snapshots should be dropped once they are no longer needed.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    data: Arc<SnapshotData>,
+}
+
+#[derive(Debug)]
+struct SnapshotData {
+    database: Database,
+    transaction_id: Option<u64>,
+    /// The last sequence written to each collection's document tree when the
+    /// snapshot was created, or `None` if nothing had been written.
+    sequences: HashMap<CollectionName, Option<SequenceId>>,
+}
+
+impl Drop for SnapshotData {
+    fn drop(&mut self) {
+        self.database
+            .data
+            .context
+            .snapshots
+            .active
+            .fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Snapshot {
+    fn new(database: &Database) -> Result<Self, Error> {
+        let state = &database.data.context.snapshots;
+        // The count is incremented before the snapshot's data is created so
+        // that dropping the data below decrements it, even if an error occurs.
+        // Registering the snapshot waits for running compactions to finish.
+        let compactions = state.compactions.write();
+        state.active.fetch_add(1, Ordering::AcqRel);
+        drop(compactions);
+        let mut data = SnapshotData {
+            database: database.clone(),
+            transaction_id: None,
+            sequences: HashMap::new(),
+        };
+
+        let trees = database
+            .schematic()
+            .collections()
+            .map(|collection| {
+                document_tree(database, collection).map(|tree| (collection.clone(), tree))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let commits = state.commit.write();
+        data.transaction_id = database.roots().transactions().current_transaction_id();
+        for (collection, tree) in trees {
+            let mut last_sequence = None;
+            tree.scan_sequences(
+                ..,
+                false,
+                &mut |sequence| {
+                    last_sequence = Some(sequence.sequence);
+                    ScanEvaluation::Stop
+                },
+                &mut |_, _| Ok(()),
+            )
+            .map_err(abort_error)?;
+            data.sequences.insert(collection, last_sequence);
+        }
+        drop(commits);
+
+        Ok(Self {
+            data: Arc::new(data),
+        })
+    }
+
+    /// Returns the id of the last transaction committed before this snapshot
+    /// was created, or `None` if no transactions had been committed.
+    #[must_use]
+    pub fn transaction_id(&self) -> Option<u64> {
+        self.data.transaction_id
+    }
+
+    /// Returns the database this snapshot reads from.
+    #[must_use]
+    pub fn database(&self) -> &Database {
+        &self.data.database
+    }
+
+    fn document_tree(&self, collection: &CollectionName) -> Result<SnapshotTree, Error> {
+        let last_sequence = *self
+            .data
+            .sequences
+            .get(collection)
+            .ok_or(Error::Core(bonsaidb_core::Error::CollectionNotFound))?;
+        Ok(SnapshotTree {
+            tree: document_tree(&self.data.database, collection)?,
+            last_sequence,
+        })
+    }
+
+    /// Returns the documents in `collection` with `ids`, sorted by id.
+    fn documents(
+        &self,
+        collection: &CollectionName,
+        ids: &[DocumentId],
+    ) -> Result<Vec<OwnedDocument>, Error> {
+        let tree = self.document_tree(collection)?;
+        let mut ids = ids.to_vec();
+        ids.sort();
+        ids.dedup();
+        let current = tree
+            .tree
+            .get_multiple(ids.iter().map(|id| id.as_ref()))?
+            .into_iter()
+            .map(|(_, value)| deserialize_document(&value).map(BorrowedDocument::into_owned))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let changed = tree.changed_documents(|id| ids.binary_search(id).is_ok())?;
+        Ok(merge_changed(current, changed))
+    }
+
+    /// Returns up to `limit` documents in `collection` within `ids`, in
+    /// `order`.
+    ///
+    /// The current documents are read in batches of the number of documents
+    /// still needed. Once a batch is read, the changed documents between the
+    /// previous batch and the batch's last id are merged in, which may leave
+    /// fewer documents than needed when documents were created after the
+    /// snapshot.
+    fn documents_in_range(
+        &self,
+        collection: &CollectionName,
+        ids: Range<DocumentId>,
+        order: Sort,
+        limit: Option<u32>,
+    ) -> Result<Vec<OwnedDocument>, Error> {
+        let limit = limit.map(|limit| usize::try_from(limit).unwrap_or(usize::MAX));
+        if limit == Some(0) {
+            return Ok(Vec::new());
+        }
+
+        let tree = self.document_tree(collection)?;
+        let forwards = matches!(order, Sort::Ascending);
+        let mut remaining = ids;
+        let mut documents = Vec::new();
+        loop {
+            let batch_size = limit.map(|limit| limit - documents.len());
+            let mut batch = Vec::new();
+            let mut keys_read = 0;
+            tree.tree
+                .scan(
+                    &DocumentIdRange(remaining.clone()).borrow_as_bytes(),
+                    forwards,
+                    |_, _, _| ScanEvaluation::ReadData,
+                    |_, _| {
+                        if batch_size.map_or(false, |size| keys_read >= size) {
+                            return ScanEvaluation::Stop;
+                        }
+                        keys_read += 1;
+                        ScanEvaluation::ReadData
+                    },
+                    |_, _, document| {
+                        batch.push(
+                            deserialize_document(&document)
+                                .map(BorrowedDocument::into_owned)
+                                .map_err(AbortError::Other)?,
+                        );
+                        Ok(())
+                    },
+                )
+                .map_err(abort_error)?;
+
+            let last_read = match batch_size {
+                Some(size) if batch.len() >= size => batch.last().map(|doc| doc.header.id.clone()),
+                _ => None,
+            };
+            let covered = match &last_read {
+                Some(last) if forwards => Range {
+                    start: remaining.start.clone(),
+                    end: Bound::Included(last.clone()),
+                },
+                Some(last) => Range {
+                    start: Bound::Included(last.clone()),
+                    end: remaining.end.clone(),
+                },
+                None => remaining.clone(),
+            };
+            let changed = tree.changed_documents(|id| covered.contains(id))?;
+            let mut merged = merge_changed(batch, changed);
+            if !forwards {
+                merged.reverse();
+            }
+            documents.extend(merged);
+
+            match last_read {
+                Some(last) if limit.map_or(true, |limit| documents.len() < limit) => {
+                    remaining = if forwards {
+                        Range {
+                            start: Bound::Excluded(last),
+                            end: remaining.end,
+                        }
+                    } else {
+                        Range {
+                            start: remaining.start,
+                            end: Bound::Excluded(last),
+                        }
+                    };
+                }
+                _ => break,
+            }
+        }
+
+        if let Some(limit) = limit {
+            documents.truncate(limit);
+        }
+        Ok(documents)
+    }
+
+    /// Returns the number of documents in `collection` within `ids`.
+    ///
+    /// The current number of documents is read from the tree's index without
+    /// reading any documents, and is then adjusted for the documents changed
+    /// since the snapshot was created. Commits are blocked while counting so
+    /// that the index and the changed documents agree.
+    fn count_in_range(
+        &self,
+        collection: &CollectionName,
+        ids: Range<DocumentId>,
+    ) -> Result<u64, Error> {
+        let tree = self.document_tree(collection)?;
+        let ids = DocumentIdRange(ids);
+        let commits = self.data.database.data.context.snapshots.block_commits();
+        let current = tree.tree.reduce(&ids.borrow_as_bytes())?.alive_keys;
+        let changed = tree.changed_documents(|id| ids.0.contains(id))?;
+        let currently_present = tree
+            .tree
+            .get_multiple(changed.keys().map(|id| id.as_ref()))?
+            .len() as u64;
+        drop(commits);
+
+        let previously_present = changed.values().filter(|version| version.is_some()).count();
+        Ok(current + previously_present as u64 - currently_present)
+    }
+
+    /// Returns the entries of `view` matching `key` as of this snapshot,
+    /// sorted by key.
+    fn view_entries(
+        &self,
+        view: &dyn view::Serialized,
+        key: Option<SerializedQueryKey>,
+        access_policy: AccessPolicy,
+    ) -> Result<Vec<ViewEntry>, bonsaidb_core::Error> {
+        let mut entries = BTreeMap::new();
+        self.data.database.for_each_in_view(
+            view,
+            key.clone(),
+            Sort::Ascending,
+            None,
+            access_policy,
+            |entry| {
+                entries.insert(entry.key.clone(), entry);
+                Ok(())
+            },
+        )?;
+
+        // The stored mappings of documents that changed after the snapshot was
+        // created are replaced by mapping the snapshot's version of each
+        // document.
+        let changed = self
+            .document_tree(&view.collection())?
+            .changed_documents(|_| true)?;
+        if changed.is_empty() {
+            return Ok(entries.into_values().collect());
+        }
+
+        // Auxiliary inputs aren't part of the snapshot, so the documents are
+        // mapped using the current contents of the inputs.
+        let lookup = SnapshotLookup {
+            snapshot: self,
+            auxiliary: auxiliary_data(&self.data.database, view)?,
+        };
+        let mut affected_keys = BTreeSet::new();
+        for entry in entries.values_mut() {
+            let mapping_count = entry.mappings.len();
+            entry
+                .mappings
+                .retain(|mapping| !changed.contains_key(&mapping.source.id));
+            if entry.mappings.len() != mapping_count {
+                affected_keys.insert(entry.key.clone());
+            }
+        }
+
+        for document in changed.values().flatten() {
+            let document = BorrowedDocument {
+                header: document.header.clone(),
+                contents: CowBytes::from(&document.contents[..]),
+            };
+            for mapping in view.map(&document, &lookup)? {
+                if !key_matches(key.as_ref(), &mapping.key) {
+                    continue;
+                }
+                affected_keys.insert(mapping.key.clone());
+                entries
+                    .entry(mapping.key.clone())
+                    .or_insert_with(|| ViewEntry {
+                        view_version: view.version(),
+                        key: mapping.key,
+                        mappings: Vec::new(),
+                        reduced_value: Bytes::default(),
+                    })
+                    .mappings
+                    .push(EntryMapping {
+                        source: mapping.source,
+                        value: mapping.value,
+                    });
+            }
+        }
+
+        for key in affected_keys {
+            let Some(entry) = entries.get_mut(&key) else {
+                continue;
+            };
+            if entry.mappings.is_empty() {
+                entries.remove(&key);
+                continue;
+            }
+
+            let mappings = entry
+                .mappings
+                .iter()
+                .map(|mapping| (entry.key.as_ref(), mapping.value.as_ref()))
+                .collect::<Vec<_>>();
+            entry.reduced_value = match view.reduce(&mappings, false) {
+                Ok(reduced) => Bytes::from(reduced),
+                Err(view::Error::Core(bonsaidb_core::Error::ReduceUnimplemented)) => {
+                    Bytes::default()
+                }
+                Err(other) => return Err(bonsaidb_core::Error::from(other)),
+            };
+        }
+
+        Ok(entries.into_values().collect())
+    }
+}
+
+/// A collection's document tree along with the last sequence written to it
+/// when a snapshot was created.
+struct SnapshotTree {
+    tree: Tree<Versioned, AnyFile>,
+    last_sequence: Option<SequenceId>,
+}
+
+impl SnapshotTree {
+    /// Returns the version as of the snapshot of each document matching
+    /// `filter` that has been written to since the snapshot was created.
+    /// Documents that did not exist when the snapshot was created are `None`.
+    ///
+    /// The changed documents must be found after reading the current
+    /// documents: any document that isn't returned has not been modified since
+    /// the snapshot was created, so the previously read current version is
+    /// the snapshot's version.
+    fn changed_documents<F: Fn(&DocumentId) -> bool>(
+        &self,
+        filter: F,
+    ) -> Result<BTreeMap<DocumentId, Option<OwnedDocument>>, Error> {
+        let mut changed = BTreeSet::new();
+        self.tree
+            .scan_sequences(
+                (
+                    self.last_sequence
+                        .map_or(ops::Bound::Unbounded, ops::Bound::Excluded),
+                    ops::Bound::Unbounded,
+                ),
+                true,
+                &mut |sequence| {
+                    if let Ok(id) = DocumentId::try_from(&sequence.key[..]) {
+                        if filter(&id) {
+                            changed.insert(id);
+                        }
+                    }
+                    ScanEvaluation::Skip
+                },
+                &mut |_, _| Ok(()),
+            )
+            .map_err(abort_error)?;
+
+        let mut versions = changed
+            .iter()
+            .map(|id| (id.clone(), None))
+            .collect::<BTreeMap<_, _>>();
+        if let Some(last_sequence) = self.last_sequence {
+            // Scanning backwards from the snapshot's sequence, the first entry
+            // for each document is its version as of the snapshot. Entries
+            // without data are deletions.
+            let mut remaining = changed;
+            self.tree
+                .scan_sequences(
+                    ..=last_sequence,
+                    false,
+                    &mut |sequence| {
+                        if remaining.is_empty() {
+                            return ScanEvaluation::Stop;
+                        }
+                        match DocumentId::try_from(&sequence.key[..]) {
+                            Ok(id) if remaining.remove(&id) => ScanEvaluation::ReadData,
+                            _ => ScanEvaluation::Skip,
+                        }
+                    },
+                    &mut |_, data| {
+                        if !data.is_empty() {
+                            let document = deserialize_document(&data)
+                                .map(BorrowedDocument::into_owned)
+                                .map_err(AbortError::Other)?;
+                            versions.insert(document.header.id.clone(), Some(document));
+                        }
+                        Ok(())
+                    },
+                )
+                .map_err(abort_error)?;
+        }
+
+        Ok(versions)
+    }
+}
+
+fn document_tree(
+    database: &Database,
+    collection: &CollectionName,
+) -> Result<Tree<Versioned, AnyFile>, Error> {
+    database
+        .roots()
+        .tree(database.collection_tree::<Versioned, _>(collection, document_tree_name(collection))?)
+        .map_err(Error::from)
+}
+
+fn abort_error(err: AbortError<Error>) -> Error {
+    match err {
+        AbortError::Other(err) => err,
+        AbortError::Nebari(err) => Error::from(err),
+    }
+}
+
+/// Replaces the `current` documents that have `changed` with their previous
+/// versions, returning the result sorted by id.
+fn merge_changed(
+    mut current: Vec<OwnedDocument>,
+    changed: BTreeMap<DocumentId, Option<OwnedDocument>>,
+) -> Vec<OwnedDocument> {
+    current.retain(|document| !changed.contains_key(&document.header.id));
+    current.extend(changed.into_values().flatten());
+    current.sort_by(|a, b| a.header.id.cmp(&b.header.id));
+    current
+}
+
+fn key_matches(key: Option<&SerializedQueryKey>, candidate: &Bytes) -> bool {
+    match key {
+        None => true,
+        Some(SerializedQueryKey::Matches(key)) => key == candidate,
+        Some(SerializedQueryKey::Range(range)) => range.contains(candidate),
+        Some(SerializedQueryKey::Multiple(keys)) => keys.contains(candidate),
+    }
+}
+
+fn sorted<T>(mut values: Vec<T>, order: Sort, limit: Option<u32>) -> Vec<T> {
+    if matches!(order, Sort::Descending) {
+        values.reverse();
+    }
+    if let Some(limit) = limit {
+        values.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+    }
+    values
+}
+
+impl DocumentLookup for Snapshot {
+    fn get(
+        &self,
+        collection: &CollectionName,
+        id: &DocumentId,
+    ) -> Result<Option<OwnedDocument>, bonsaidb_core::Error> {
+        Ok(self.documents(collection, std::slice::from_ref(id))?.pop())
+    }
+}
+
+/// Looks up documents as of a [`Snapshot`] while providing a view's auxiliary
+/// data.
+struct SnapshotLookup<'a> {
+    snapshot: &'a Snapshot,
+    auxiliary: Option<AuxiliaryData>,
+}
+
+impl<'a> DocumentLookup for SnapshotLookup<'a> {
+    fn get(
+        &self,
+        collection: &CollectionName,
+        id: &DocumentId,
+    ) -> Result<Option<OwnedDocument>, bonsaidb_core::Error> {
+        DocumentLookup::get(self.snapshot, collection, id)
+    }
+
+    fn auxiliary_data(&self) -> Option<&AuxiliaryData> {
+        self.auxiliary.as_ref()
+    }
+}
+
+impl HasSession for Snapshot {
+    fn session(&self) -> Option<&Session> {
+        self.data.database.session()
+    }
+}
+
+impl HasSchema for Snapshot {
+    fn schematic(&self) -> &Schematic {
+        self.data.database.schematic()
+    }
+}
+
+impl LowLevelConnection for Snapshot {
+    fn apply_transaction(
+        &self,
+        _transaction: Transaction,
+    ) -> Result<Vec<OperationResult>, bonsaidb_core::Error> {
+        Err(bonsaidb_core::Error::ReadOnly)
+    }
+
+    fn get_from_collection(
+        &self,
+        id: DocumentId,
+        collection: &CollectionName,
+    ) -> Result<Option<OwnedDocument>, bonsaidb_core::Error> {
+        self.check_permission(
+            document_resource_name(self.data.database.name(), collection, &id),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
+        )?;
+        DocumentLookup::get(self, collection, &id)
+    }
+
+    fn list_from_collection(
+        &self,
+        ids: Range<DocumentId>,
+        order: Sort,
+        limit: Option<u32>,
+        collection: &CollectionName,
+    ) -> Result<Vec<OwnedDocument>, bonsaidb_core::Error> {
+        self.check_permission(
+            collection_resource_name(self.data.database.name(), collection),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::List)),
+        )?;
+        Ok(self.documents_in_range(collection, ids, order, limit)?)
+    }
+
+    fn list_headers_from_collection(
+        &self,
+        ids: Range<DocumentId>,
+        order: Sort,
+        limit: Option<u32>,
+        collection: &CollectionName,
+    ) -> Result<Vec<Header>, bonsaidb_core::Error> {
+        self.check_permission(
+            collection_resource_name(self.data.database.name(), collection),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::ListHeaders)),
+        )?;
+        Ok(self
+            .documents_in_range(collection, ids, order, limit)?
+            .into_iter()
+            .map(|document| document.header)
+            .collect())
+    }
+
+    fn count_from_collection(
+        &self,
+        ids: Range<DocumentId>,
+        collection: &CollectionName,
+    ) -> Result<u64, bonsaidb_core::Error> {
+        self.check_permission(
+            collection_resource_name(self.data.database.name(), collection),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Count)),
+        )?;
+        Ok(self.count_in_range(collection, ids)?)
+    }
+
+    fn get_multiple_from_collection(
+        &self,
+        ids: &[DocumentId],
+        collection: &CollectionName,
+    ) -> Result<Vec<OwnedDocument>, bonsaidb_core::Error> {
+        for id in ids {
+            self.check_permission(
+                document_resource_name(self.data.database.name(), collection, id),
+                &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
+            )?;
+        }
+        Ok(self.documents(collection, ids)?)
+    }
+
+    fn compact_collection_by_name(
+        &self,
+        collection: CollectionName,
+    ) -> Result<(), bonsaidb_core::Error> {
+        self.data.database.compact_collection_by_name(collection)
+    }
+
+    fn query_by_name(
+        &self,
+        view: &ViewName,
+        key: Option<SerializedQueryKey>,
+        order: Sort,
+        limit: Option<u32>,
+        access_policy: AccessPolicy,
+    ) -> Result<Vec<schema::view::map::Serialized>, bonsaidb_core::Error> {
+        let view = self.schematic().view_by_name(view)?;
+        self.check_permission(
+            view_resource_name(self.data.database.name(), &view.view_name()),
+            &BonsaiAction::Database(DatabaseAction::View(ViewAction::Query)),
+        )?;
+        // Mappings expose the ids of the source collection's documents.
+        self.check_permission(
+            collection_resource_name(self.data.database.name(), &view.collection()),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::List)),
+        )?;
+        let entries = self.view_entries(view, key, access_policy)?;
+        let mut results = Vec::new();
+        for entry in sorted(entries, order, limit) {
+            for mapping in entry.mappings {
+                results.push(schema::view::map::Serialized {
+                    source: mapping.source,
+                    key: entry.key.clone(),
+                    value: mapping.value,
+                });
+            }
+        }
+
+        Ok(results)
+    }
+
+    fn query_by_name_with_docs(
+        &self,
+        view: &ViewName,
+        key: Option<SerializedQueryKey>,
+        order: Sort,
+        limit: Option<u32>,
+        access_policy: AccessPolicy,
+    ) -> Result<MappedSerializedDocuments, bonsaidb_core::Error> {
+        let results = self.query_by_name(view, key, order, limit, access_policy)?;
+        let view = self.schematic().view_by_name(view).unwrap(); // query() will fail if it's not present
+
+        let documents = self
+            .get_multiple_from_collection(
+                &results
+                    .iter()
+                    .map(|m| m.source.id.clone())
+                    .collect::<Vec<_>>(),
+                &view.collection(),
+            )?
+            .into_iter()
+            .map(|doc| (doc.header.id.clone(), doc))
+            .collect::<BTreeMap<_, _>>();
+
+        Ok(MappedSerializedDocuments {
+            mappings: results,
+            documents,
+        })
+    }
+
+    fn reduce_by_name(
+        &self,
+        view_name: &ViewName,
+        key: Option<SerializedQueryKey>,
+        access_policy: AccessPolicy,
+    ) -> Result<Vec<u8>, bonsaidb_core::Error> {
+        let mut mappings = self.reduce_grouped_by_name(view_name, key, access_policy)?;
+
+        let result = if mappings.len() == 1 {
+            mappings.pop().unwrap().value.into_vec()
+        } else {
+            let view = self.schematic().view_by_name(view_name)?;
+            view.reduce(
+                &mappings
+                    .iter()
+                    .map(|map| (map.key.as_ref(), map.value.as_ref()))
+                    .collect::<Vec<_>>(),
+                true,
+            )
+            .map_err(Error::from)?
+        };
+
+        Ok(result)
+    }
+
+    fn reduce_grouped_by_name(
+        &self,
+        view_name: &ViewName,
+        key: Option<SerializedQueryKey>,
+        access_policy: AccessPolicy,
+    ) -> Result<Vec<MappedSerializedValue>, bonsaidb_core::Error> {
+        let view = self.schematic().view_by_name(view_name)?;
+        self.check_permission(
+            view_resource_name(self.data.database.name(), &view.view_name()),
+            &BonsaiAction::Database(DatabaseAction::View(ViewAction::Reduce)),
+        )?;
+        Ok(self
+            .view_entries(view, key, access_policy)?
+            .into_iter()
+            .map(|entry| MappedSerializedValue {
+                key: entry.key,
+                value: entry.reduced_value,
+            })
+            .collect())
+    }
+
+    fn delete_docs_by_name(
+        &self,
+        _view: &ViewName,
+        _key: Option<SerializedQueryKey>,
+        _access_policy: AccessPolicy,
+        _include_headers: bool,
+    ) -> Result<DeletedDocuments, bonsaidb_core::Error> {
+        Err(bonsaidb_core::Error::ReadOnly)
+    }
+}
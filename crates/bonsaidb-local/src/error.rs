@@ -7,7 +7,7 @@ use std::sync::Arc;
 use bonsaidb_core::permissions::PermissionDenied;
 use bonsaidb_core::pubsub::{Disconnected, TryReceiveError};
 use bonsaidb_core::schema::{view, InsertError, InvalidNameError};
-use bonsaidb_core::AnyError;
+use bonsaidb_core::{AnyError, StorageErrorKind};
 use nebari::AbortError;
 
 use crate::database::compat::UnknownVersion;
@@ -45,6 +45,19 @@ pub enum Error {
     #[cfg(feature = "compression")]
     Compression(#[from] lz4_flex::block::DecompressError),
 
+    /// A stored value was compressed with a
+    /// [`CompressionDictionary`](crate::config::CompressionDictionary) that is
+    /// not configured for its collection.
+    #[error("compression dictionary {0:08x} was not found")]
+    #[cfg(feature = "compression")]
+    CompressionDictionaryNotFound(u32),
+
+    /// A stored block's checksum did not match its contents. See
+    /// [`StorageConfiguration::block_checksums`](crate::config::StorageConfiguration::block_checksums).
+    #[error("a stored block failed its checksum verification")]
+    #[cfg(feature = "compression")]
+    BlockCorrupted,
+
     /// A collection requested to be encrypted, but encryption is disabled.
     #[error("encryption is disabled, but a collection is requesting encryption")]
     #[cfg(not(feature = "encryption"))]
@@ -71,6 +84,42 @@ pub enum Error {
     #[error("a backup error: {0}")]
     Backup(Box<dyn AnyError>),
 
+    /// An error returned by code outside of this crate, such as a
+    /// [`BackupLocation`](crate::BackupLocation) implementation. See
+    /// [`Error::custom()`].
+    #[error("{0}")]
+    Custom(Box<dyn std::error::Error + Send + Sync>),
+
+    /// The storage is open, either by this process or another process.
+    /// Returned from [`Storage::open()`](crate::Storage::open) if the other
+    /// handle isn't closed within
+    /// [`StorageConfiguration::lock_timeout`](crate::config::StorageConfiguration::lock_timeout).
+    #[error("the storage is in use")]
+    StorageInUse,
+
+    /// The storage was written with encryption or compression enabled, which
+    /// [`OfflineStorage`](crate::offline::OfflineStorage) can't read.
+    #[error("the storage is encrypted or compressed and can't be inspected offline")]
+    ProtectedStorage,
+
+    /// The storage was written using a newer format than this version
+    /// supports. See [`STORAGE_FORMAT_VERSION`](crate::STORAGE_FORMAT_VERSION).
+    #[error("the storage format version {found} is newer than the supported version {supported}")]
+    NewerStorageFormat {
+        /// The format version recorded in the storage.
+        found: u32,
+        /// The newest format version supported.
+        supported: u32,
+    },
+
+    /// A transaction read from a replication primary didn't record the
+    /// contents it wrote. The primary must be opened with
+    /// [`StorageConfiguration::replication_log`](crate::config::StorageConfiguration::replication_log)
+    /// enabled before a [`Follower`](crate::Follower) can replay its
+    /// transactions.
+    #[error("transaction {0} was not recorded in a replication log")]
+    ReplicationLogMissing(u64),
+
     /// An error occurred with a password hash.
     #[cfg(all(feature = "password-hashing", feature = "cli"))]
     #[error("error reading password: {0}")]
@@ -81,6 +130,146 @@ impl Error {
     pub(crate) fn other(origin: impl Display, error: impl Display) -> Self {
         Self::Core(bonsaidb_core::Error::other(origin, error))
     }
+
+    /// Returns a [`Error::Custom`] containing `error`. This allows code that
+    /// extends storage to return its own error types, which callers can
+    /// retrieve using [`Error::custom_error()`].
+    ///
+    /// When converted to a [`bonsaidb_core::Error`], a custom
+    /// `bonsaidb_core::Error` is unwrapped, and all other custom errors are
+    /// converted to [`bonsaidb_core::Error::Other`].
+    pub fn custom<E: std::error::Error + Send + Sync + 'static>(error: E) -> Self {
+        Self::Custom(Box::new(error))
+    }
+
+    /// Returns the custom error contained within this error, if it contains a
+    /// custom error of type `E`. Custom errors are found even when they were
+    /// wrapped by another error while being returned, such as an error from a
+    /// task that was shared between multiple callers.
+    #[must_use]
+    pub fn custom_error<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        match self {
+            Self::Custom(error) => error.downcast_ref(),
+            Self::Job(job) => job.custom_error(),
+            Self::Nebari(err) => {
+                let mut source = std::error::Error::source(err);
+                while let Some(error) = source {
+                    if let Some(error) = error.downcast_ref::<Error>() {
+                        return error.custom_error();
+                    }
+                    source = error.source();
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Converts this error into the most specific [`bonsaidb_core::Error`]
+    /// that describes it. `source` is the error being converted, and it is
+    /// kept as the source of the converted error.
+    fn to_core_error(&self, source: &Arc<Error>) -> bonsaidb_core::Error {
+        match self {
+            Self::View(view::Error::Core(core)) | Self::Core(core) => core.clone(),
+            Self::Job(job) => job.to_core_error(source),
+            Self::Nebari(err) => nebari_error_to_core_error(err, source),
+            Self::Io(err) => storage_error(io_error_kind(err), err, source),
+            #[cfg(feature = "compression")]
+            Self::Compression(err) => storage_error(StorageErrorKind::Corruption, err, source),
+            #[cfg(feature = "compression")]
+            Self::CompressionDictionaryNotFound(_) | Self::BlockCorrupted => {
+                storage_error(StorageErrorKind::Corruption, self, source)
+            }
+            Self::Serialization(err) => bonsaidb_core::Error::Serialization {
+                format: String::from("pot"),
+                message: err.to_string(),
+                source: Some(source.clone()),
+            },
+            Self::TransactionTooLarge => bonsaidb_core::Error::TransactionTooLarge,
+            Self::Custom(err) => err
+                .downcast_ref::<bonsaidb_core::Error>()
+                .cloned()
+                .unwrap_or_else(|| bonsaidb_core::Error::other("custom", err)),
+            other => bonsaidb_core::Error::other("bonsaidb-local", other),
+        }
+    }
+}
+
+fn storage_error(
+    kind: StorageErrorKind,
+    message: impl Display,
+    source: &Arc<Error>,
+) -> bonsaidb_core::Error {
+    bonsaidb_core::Error::Storage {
+        kind,
+        message: message.to_string(),
+        source: Some(source.clone()),
+    }
+}
+
+/// Converts `err` into a [`bonsaidb_core::Error`]. If `err` was caused by one
+/// of our own errors, such as an error raised while reading a tree, that
+/// error is converted instead.
+fn nebari_error_to_core_error(err: &nebari::Error, source: &Arc<Error>) -> bonsaidb_core::Error {
+    // Vault errors describe a problem with the encryption keys rather than
+    // with the storage itself.
+    if matches!(err.kind, nebari::ErrorKind::Vault(_)) {
+        return bonsaidb_core::Error::other("bonsaidb-local", err);
+    }
+
+    let mut kind = StorageErrorKind::Internal;
+    let mut cause = std::error::Error::source(err);
+    while let Some(error) = cause {
+        if let Some(error) = error.downcast_ref::<Error>() {
+            return error.to_core_error(source);
+        } else if let Some(error) = error.downcast_ref::<std::io::Error>() {
+            kind = io_error_kind(error);
+            break;
+        }
+        cause = error.source();
+    }
+
+    storage_error(kind, err, source)
+}
+
+/// Returns [`StorageErrorKind::OutOfSpace`] if `err` was caused by running
+/// out of disk space or exceeding a disk quota. The `io::ErrorKind`s for these
+/// conditions aren't available in our minimum supported Rust version, so the
+/// operating system's error codes are checked instead.
+fn io_error_kind(err: &std::io::Error) -> StorageErrorKind {
+    // ENOSPC and EDQUOT.
+    #[cfg(target_os = "linux")]
+    const OUT_OF_SPACE: &[i32] = &[28, 122];
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    const OUT_OF_SPACE: &[i32] = &[28, 69];
+    // ERROR_HANDLE_DISK_FULL and ERROR_DISK_FULL.
+    #[cfg(windows)]
+    const OUT_OF_SPACE: &[i32] = &[39, 112];
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        windows
+    )))]
+    const OUT_OF_SPACE: &[i32] = &[];
+
+    if err
+        .raw_os_error()
+        .map_or(false, |code| OUT_OF_SPACE.contains(&code))
+    {
+        StorageErrorKind::OutOfSpace
+    } else {
+        StorageErrorKind::Io
+    }
 }
 
 impl<T> From<InsertError<T>> for Error {
@@ -109,7 +298,11 @@ impl From<Disconnected> for Error {
 
 impl From<bincode::Error> for Error {
     fn from(err: bincode::Error) -> Self {
-        Self::other("bincode", err)
+        Self::Core(bonsaidb_core::Error::Serialization {
+            format: String::from("bincode"),
+            message: err.to_string(),
+            source: Some(Arc::new(err)),
+        })
     }
 }
 
@@ -151,7 +344,14 @@ impl From<Error> for bonsaidb_core::Error {
     fn from(err: Error) -> Self {
         match err {
             Error::View(view::Error::Core(core)) | Error::Core(core) => core,
-            other => Self::other("bonsaidb-local", other),
+            Error::Job(job) => match Arc::try_unwrap(job) {
+                Ok(err) => Self::from(err),
+                Err(job) => job.to_core_error(&job),
+            },
+            other => {
+                let other = Arc::new(other);
+                other.to_core_error(&other)
+            }
         }
     }
 }
@@ -212,9 +412,50 @@ fn test_converting_error() {
     use serde::ser::Error as _;
     let err: bonsaidb_core::Error = Error::Serialization(pot::Error::custom("mymessage")).into();
     match err {
-        bonsaidb_core::Error::Other { error, .. } => {
-            assert!(error.contains("mymessage"));
+        bonsaidb_core::Error::Serialization {
+            format,
+            message,
+            source,
+        } => {
+            assert_eq!(format, "pot");
+            assert!(message.contains("mymessage"));
+            let source = source.expect("source missing");
+            assert!(matches!(
+                source.downcast_ref::<Error>(),
+                Some(Error::Serialization(_))
+            ));
+        }
+        _ => unreachable!(),
+    }
+
+    let err: bonsaidb_core::Error = Error::Job(Arc::new(Error::Io(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "mymessage",
+    ))))
+    .into();
+    match err {
+        bonsaidb_core::Error::Storage {
+            kind,
+            message,
+            source,
+        } => {
+            assert_eq!(kind, StorageErrorKind::Io);
+            assert!(message.contains("mymessage"));
+            assert!(std::error::Error::source(&*source.expect("source missing")).is_some());
         }
         _ => unreachable!(),
     }
+
+    // ENOSPC
+    #[cfg(unix)]
+    {
+        let err: bonsaidb_core::Error = Error::Io(std::io::Error::from_raw_os_error(28)).into();
+        assert!(matches!(
+            err,
+            bonsaidb_core::Error::Storage {
+                kind: StorageErrorKind::OutOfSpace,
+                ..
+            }
+        ));
+    }
 }
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::Error;
+
+/// Controls what [`atomic_write()`] guarantees once it returns.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FileDurability {
+    /// The new contents survive a crash once the write returns. The
+    /// containing directory is synced after the rename, which is the slowest
+    /// option.
+    Durable,
+    /// The new contents replace the old contents atomically, but a crash
+    /// shortly after the write returns may revert `path` to its previous
+    /// contents. Suitable for files that are cheap to recreate, such as
+    /// progress cursors.
+    Atomic,
+}
+
+/// Replaces the contents of `path` with `bytes` without ever exposing a
+/// partially written file at `path`.
+///
+/// The contents are written to a temporary file in the same directory, synced
+/// to disk, and then renamed over `path`. With [`FileDurability::Durable`],
+/// the containing directory is synced afterwards on Unix so that the rename
+/// itself is durable. If the process dies at any point, `path` contains either
+/// its previous contents or `bytes`.
+pub fn atomic_write(path: &Path, bytes: &[u8], durability: FileDurability) -> io::Result<()> {
+    atomic_write_with(path, durability, |file| file.write_all(bytes))
+}
+
+fn atomic_write_with<F: FnOnce(&mut File) -> io::Result<()>>(
+    path: &Path,
+    durability: FileDurability,
+    write: F,
+) -> io::Result<()> {
+    let temporary_path = temporary_path(path)?;
+    let result = write_then_rename(path, &temporary_path, durability, write);
+    if result.is_err() {
+        let _: Result<_, _> = fs::remove_file(&temporary_path);
+    }
+    result
+}
+
+fn write_then_rename<F: FnOnce(&mut File) -> io::Result<()>>(
+    path: &Path,
+    temporary_path: &Path,
+    durability: FileDurability,
+    write: F,
+) -> io::Result<()> {
+    let mut file = File::create(temporary_path)?;
+    write(&mut file)?;
+    file.sync_all()?;
+    drop(file);
+
+    // On Windows, `fs::rename` replaces the destination if it already exists.
+    fs::rename(temporary_path, path)?;
+    match durability {
+        FileDurability::Durable => sync_parent_directory(path),
+        FileDurability::Atomic => Ok(()),
+    }
+}
+
+/// Returns the path of the temporary file used when writing `path`. A
+/// leftover temporary file from an interrupted write is truncated by the next
+/// write.
+fn temporary_path(path: &Path) -> io::Result<PathBuf> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} does not name a file", path.display()),
+        )
+    })?;
+    let mut temporary_name = OsString::from(".");
+    temporary_name.push(file_name);
+    temporary_name.push(".tmp");
+    Ok(path.with_file_name(temporary_name))
+}
+
+#[cfg(unix)]
+fn sync_parent_directory(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+// Directories can't be opened as files on Windows, and NTFS journals the
+// rename itself.
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn sync_parent_directory(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Per-database cursors recording where an incomplete background job stopped,
+/// persisted to a single file shared by every database in a storage.
+#[derive(Debug)]
+pub(crate) struct DatabaseCursors<T> {
+    path: PathBuf,
+    cursors: Mutex<Option<BTreeMap<String, T>>>,
+}
+
+impl<T> DatabaseCursors<T>
+where
+    T: Clone + Serialize + DeserializeOwned,
+{
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            cursors: Mutex::default(),
+        }
+    }
+
+    pub fn cursor(&self, database: &str) -> Result<Option<T>, Error> {
+        let mut cursors = self.cursors.lock();
+        Ok(self.loaded(&mut cursors)?.get(database).cloned())
+    }
+
+    pub fn save(&self, database: &str, cursor: Option<T>) -> Result<(), Error> {
+        let mut cursors = self.cursors.lock();
+        let loaded = self.loaded(&mut cursors)?;
+        let changed = match cursor {
+            Some(cursor) => {
+                loaded.insert(database.to_string(), cursor);
+                true
+            }
+            None => loaded.remove(database).is_some(),
+        };
+        if changed {
+            atomic_write(&self.path, &pot::to_vec(&*loaded)?, FileDurability::Atomic)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the cursor for `database`, ensuring a database created later
+    /// with the same name starts from the beginning.
+    pub fn forget(&self, database: &str) -> Result<(), Error> {
+        self.save(database, None)
+    }
+
+    fn loaded<'a>(
+        &self,
+        cursors: &'a mut Option<BTreeMap<String, T>>,
+    ) -> Result<&'a mut BTreeMap<String, T>, Error> {
+        if cursors.is_none() {
+            *cursors = Some(match fs::read(&self.path) {
+                Ok(contents) => pot::from_slice(&contents)?,
+                Err(err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+                Err(err) => return Err(Error::from(err)),
+            });
+        }
+        Ok(cursors.as_mut().expect("just loaded"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use bonsaidb_core::test_util::TestDirectory;
+
+    use super::*;
+
+    #[test]
+    fn replaces_contents() -> anyhow::Result<()> {
+        let dir = TestDirectory::new("atomic-write-replaces-contents");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("file");
+
+        atomic_write(&path, b"first", FileDurability::Durable)?;
+        assert_eq!(fs::read(&path)?, b"first");
+        atomic_write(&path, b"second", FileDurability::Atomic)?;
+        assert_eq!(fs::read(&path)?, b"second");
+        assert!(!temporary_path(&path)?.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn crash_preserves_contents() -> anyhow::Result<()> {
+        let dir = TestDirectory::new("atomic-write-crash-preserves-contents");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("file");
+        atomic_write(&path, b"original contents", FileDurability::Durable)?;
+
+        // Simulate the process dying halfway through writing the new contents.
+        // Panicking skips all cleanup, leaving the temporary file behind just
+        // like a real crash would.
+        let crashed = catch_unwind(AssertUnwindSafe(|| {
+            atomic_write_with(&path, FileDurability::Durable, |file| {
+                file.write_all(b"new con")?;
+                panic!("simulated crash");
+            })
+        }));
+        assert!(crashed.is_err());
+        assert_eq!(fs::read(&path)?, b"original contents");
+        assert!(temporary_path(&path)?.exists());
+
+        // The leftover temporary file shouldn't prevent the next write.
+        atomic_write(&path, b"new contents", FileDurability::Durable)?;
+        assert_eq!(fs::read(&path)?, b"new contents");
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_write_preserves_contents() -> anyhow::Result<()> {
+        let dir = TestDirectory::new("atomic-write-failed-write-preserves-contents");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("file");
+        atomic_write(&path, b"original contents", FileDurability::Durable)?;
+
+        let result = atomic_write_with(&path, FileDurability::Durable, |file| {
+            file.write_all(b"new con")?;
+            Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "simulated failure",
+            ))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read(&path)?, b"original contents");
+        assert!(!temporary_path(&path)?.exists());
+
+        Ok(())
+    }
+}
@@ -22,9 +22,16 @@ pub mod cli;
 pub mod config;
 mod database;
 mod error;
+mod files;
+/// Read-only inspection of storage while it isn't open.
+pub mod offline;
 mod open_trees;
+#[cfg(feature = "async")]
+mod replication;
 mod storage;
 mod tasks;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 #[cfg(feature = "encryption")]
 pub mod vault;
 mod views;
@@ -34,16 +41,32 @@ pub use argon2;
 #[cfg(not(feature = "included-from-omnibus"))]
 pub use bonsaidb_core as core;
 
+pub use self::database::activity::ActivityStats;
+pub use self::database::diff::{CollectionDiff, DatabaseDiff};
+pub use self::database::keyvalue::{DurabilityReceipt, ExpirationLoadStatus};
 pub use self::database::pubsub::Subscriber;
+pub use self::database::quota::{QuotaMonitor, QuotaUsage};
+pub use self::database::scrub::ScrubMonitor;
+pub use self::database::snapshot::Snapshot;
 pub use self::database::{Database, DatabaseNonBlocking};
 pub use self::error::Error;
-pub use self::storage::{BackupLocation, Storage, StorageId, StorageNonBlocking};
+pub use self::files::{atomic_write, FileDurability};
+pub use self::storage::{
+    AdminImportMode, AdmissionStatistics, BackupLocation, DatabaseLifecycle, DatabaseRecovery,
+    ExternalIdentityResolver, FederatedMapping, IntegrityProblem, IntegrityReport,
+    InvalidStorageId, PubSubStatistics, QuarantinedDocument, RecoveryReport, ResolvedIdentity,
+    Storage, StorageId, StorageNonBlocking, StorageUpgrade, TopicStatistics, UnreadableCollection,
+    UnreadableDocument, WorkUtilization, STORAGE_FORMAT_VERSION,
+};
+pub use self::tasks::IntegrityScanStatus;
 
 #[cfg(feature = "async")]
 mod r#async;
 
 #[cfg(feature = "async")]
 pub use r#async::*;
+#[cfg(feature = "async")]
+pub use replication::{Follower, ReplicationStatus};
 
 #[cfg(test)]
 mod tests;
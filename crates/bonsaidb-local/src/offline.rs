@@ -0,0 +1,512 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bonsaidb_core::admin::{self, ADMIN_DATABASE_NAME};
+use bonsaidb_core::document::{DocumentId, Revision};
+use bonsaidb_core::keyvalue::{Timestamp, Value};
+use bonsaidb_core::schema::{CollectionName, Qualified, SerializedCollection, ViewName};
+use fs2::FileExt;
+use nebari::io::any::{AnyFile, AnyFileManager};
+use nebari::tree::{Root, Unversioned, Versioned};
+use nebari::{Roots, Tree};
+use serde::Serialize;
+
+use crate::database::keyvalue::{split_key, Entry, KEY_TREE};
+use crate::database::{deserialize_document, document_tree_name};
+use crate::storage::integrity::for_each_entry;
+use crate::views::integrity_scanner::ViewVersion;
+use crate::views::{
+    view_dependents_tree_name, view_document_map_tree_name, view_entries_tree_name,
+    view_invalidated_docs_tree_name, view_prefix_reductions_tree_name,
+};
+use crate::Error;
+
+const DOCUMENT_TREE_PREFIX: &str = "collection.";
+const VIEW_VERSIONS_TREE_PREFIX: &str = "view-versions.";
+
+/// Storage opened for inspection without its [`Schema`](bonsaidb_core::schema::Schema)s.
+///
+/// This is meant for inspecting a storage directory while the application
+/// that owns it isn't running. Unlike [`Storage::open()`](crate::Storage::open),
+/// opening an `OfflineStorage` doesn't require the application's schema types,
+/// doesn't create or upgrade anything, and doesn't start any background tasks.
+///
+/// No data is written to the storage's trees. However, the underlying storage
+/// engine can't open files read-only: each database's files are opened for
+/// writing, and opening a database can repair its transaction log if it was
+/// left incomplete by a crash.
+///
+/// Databases are read by opening their files directly rather than through a
+/// registered schema, which means that views can't be queried and that
+/// documents are returned as their stored bytes. Storage that was written with
+/// encryption or compression enabled can't be inspected: opening it returns
+/// [`Error::ProtectedStorage`].
+///
+/// While open, the storage is locked: [`Storage::open()`](crate::Storage::open)
+/// waits until every `OfflineStorage` for the same path has been dropped.
+/// Multiple `OfflineStorage`s can be open at the same time.
+#[derive(Debug)]
+pub struct OfflineStorage {
+    path: PathBuf,
+    admin: OfflineDatabase,
+    _lock: StorageLock,
+}
+
+impl OfflineStorage {
+    /// Opens the storage at `path` for inspection.
+    ///
+    /// Returns [`Error::StorageInUse`] if the storage is currently open,
+    /// [`Error::ProtectedStorage`] if it was written with encryption or
+    /// compression enabled, and [`bonsaidb_core::Error::DatabaseNotFound`] if
+    /// `path` doesn't contain a storage.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let lock = StorageLock::acquire(&path)?;
+        let admin = OfflineDatabase::open(&path, ADMIN_DATABASE_NAME)?;
+        Ok(Self {
+            path,
+            admin,
+            _lock: lock,
+        })
+    }
+
+    /// Returns the path of this storage.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the admin database's records of the databases in this storage.
+    /// The admin database itself isn't included.
+    pub fn databases(&self) -> Result<Vec<admin::Database>, Error> {
+        let tree_name = document_tree_name(&admin::Database::collection_name());
+        let mut databases = Vec::new();
+        if let Some(tree) = self.admin.versioned_tree(tree_name)? {
+            for_each_entry(&tree, |_, document| {
+                let document = deserialize_document(document)?;
+                databases.push(admin::Database::deserialize(&document.contents)?);
+                Ok(())
+            })?;
+        }
+        databases.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(databases)
+    }
+
+    /// Opens the database named `name` for inspection. The admin database can
+    /// be opened using [`ADMIN_DATABASE_NAME`].
+    pub fn database(&self, name: &str) -> Result<OfflineDatabase, Error> {
+        if name == ADMIN_DATABASE_NAME {
+            return OfflineDatabase::open(&self.path, name);
+        }
+
+        if self
+            .databases()?
+            .iter()
+            .all(|database| database.name != name)
+        {
+            return Err(Error::Core(bonsaidb_core::Error::DatabaseNotFound(
+                name.to_string(),
+            )));
+        }
+        OfflineDatabase::open(&self.path, name)
+    }
+
+    /// Returns statistics about every database in this storage, including the
+    /// admin database.
+    pub fn statistics(&self) -> Result<StorageStatistics, Error> {
+        let mut statistics = StorageStatistics::default();
+        statistics
+            .databases
+            .insert(ADMIN_DATABASE_NAME.to_string(), self.admin.statistics()?);
+        for database in self.databases()? {
+            // A database's files aren't created until it is first used.
+            let database_statistics = match OfflineDatabase::open(&self.path, &database.name) {
+                Ok(database) => database.statistics()?,
+                Err(Error::Core(bonsaidb_core::Error::DatabaseNotFound(_))) => {
+                    DatabaseStatistics::default()
+                }
+                Err(other) => return Err(other),
+            };
+            statistics
+                .databases
+                .insert(database.name, database_statistics);
+        }
+        Ok(statistics)
+    }
+}
+
+/// A database opened for inspection without its
+/// [`Schema`](bonsaidb_core::schema::Schema). Returned from
+/// [`OfflineStorage::database()`].
+///
+/// The contents of the database are discovered from the trees stored on disk.
+#[derive(Debug)]
+pub struct OfflineDatabase {
+    name: String,
+    path: PathBuf,
+    roots: Roots<AnyFile>,
+    tree_names: BTreeSet<String>,
+    vault: PlaintextVault,
+}
+
+impl OfflineDatabase {
+    fn open(storage_path: &Path, name: &str) -> Result<Self, Error> {
+        let path = storage_path.join(name);
+        // Opening the database's roots creates its directory if it doesn't
+        // exist.
+        if !path.is_dir() {
+            return Err(Error::Core(bonsaidb_core::Error::DatabaseNotFound(
+                name.to_string(),
+            )));
+        }
+        let roots = nebari::Config::new(&path)
+            .file_manager(AnyFileManager::std())
+            .open()?;
+        let tree_names = roots.tree_names()?.into_iter().collect();
+        let database = Self {
+            name: name.to_string(),
+            path,
+            roots,
+            tree_names,
+            vault: PlaintextVault::default(),
+        };
+        database.check_unprotected()?;
+        Ok(database)
+    }
+
+    /// Returns [`Error::ProtectedStorage`] if any of this database's trees
+    /// were written with encryption or compression. Every chunk of a tree is
+    /// written the same way, so reading the root of each tree is enough.
+    fn check_unprotected(&self) -> Result<(), Error> {
+        for name in &self.tree_names {
+            // Only the vault's detection matters here. Other errors are
+            // returned when the tree is read.
+            let _opened = if name.starts_with(DOCUMENT_TREE_PREFIX) {
+                self.tree::<Versioned>(name.clone()).map(drop)
+            } else {
+                self.tree::<Unversioned>(name.clone()).map(drop)
+            };
+            if self.vault.detected_protected_chunk() {
+                return Err(Error::ProtectedStorage);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name of this database.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the names of all trees stored in this database.
+    pub fn tree_names(&self) -> impl Iterator<Item = &str> {
+        self.tree_names.iter().map(String::as_str)
+    }
+
+    /// Returns the names of the collections that have documents stored in
+    /// this database.
+    #[must_use]
+    pub fn collections(&self) -> Vec<CollectionName> {
+        self.tree_names
+            .iter()
+            .filter_map(|name| name.strip_prefix(DOCUMENT_TREE_PREFIX))
+            .filter_map(|collection| CollectionName::parse_encoded(collection).ok())
+            .collect()
+    }
+
+    /// Returns every document stored in `collection`, ordered by id.
+    pub fn documents(&self, collection: &CollectionName) -> Result<Vec<OfflineDocument>, Error> {
+        let mut documents = Vec::new();
+        if let Some(tree) = self.versioned_tree(document_tree_name(collection))? {
+            for_each_entry(&tree, |_, document| {
+                let document = deserialize_document(document)?;
+                let value = pot::from_slice::<pot::Value<'_>>(&document.contents)
+                    .ok()
+                    .map(pot::Value::into_static);
+                documents.push(OfflineDocument {
+                    id: document.header.id,
+                    revision: document.header.revision,
+                    contents: document.contents.to_vec(),
+                    value,
+                });
+                Ok(())
+            })?;
+        }
+        Ok(documents)
+    }
+
+    /// Returns the views that have been indexed in this database, along with
+    /// the version each view was indexed with.
+    pub fn views(&self) -> Result<Vec<OfflineView>, Error> {
+        let mut views = Vec::new();
+        for tree_name in &self.tree_names {
+            let Some(collection) = tree_name.strip_prefix(VIEW_VERSIONS_TREE_PREFIX) else {
+                continue;
+            };
+            let Ok(collection) = CollectionName::parse_encoded(collection) else {
+                continue;
+            };
+            let tree = self.tree::<Unversioned>(tree_name.clone())?;
+            for_each_entry(&tree, |name, version| {
+                let name = String::from_utf8(name.to_vec())?;
+                let version = ViewVersion::from_bytes(version).ok();
+                let trees = name
+                    .parse::<ViewName>()
+                    .map(|view| {
+                        [
+                            view_entries_tree_name(&view),
+                            view_document_map_tree_name(&view),
+                            view_invalidated_docs_tree_name(&view),
+                            view_prefix_reductions_tree_name(&view),
+                            view_dependents_tree_name(&view),
+                        ]
+                        .into_iter()
+                        .filter(|tree| self.tree_names.contains(tree))
+                        .collect()
+                    })
+                    .unwrap_or_default();
+                views.push(OfflineView {
+                    collection: collection.clone(),
+                    name,
+                    schema_version: version.as_ref().map(ViewVersion::schema_version),
+                    auxiliary_version: version.and_then(|version| version.auxiliary_version()),
+                    trees,
+                });
+                Ok(())
+            })?;
+        }
+        Ok(views)
+    }
+
+    /// Returns the persisted key-value entries in `namespace`, ordered by key.
+    pub fn key_value_entries(
+        &self,
+        namespace: Option<&str>,
+    ) -> Result<Vec<OfflineKeyValueEntry>, Error> {
+        let mut entries = Vec::new();
+        if self.tree_names.contains(KEY_TREE) {
+            let tree = self.tree::<Unversioned>(String::from(KEY_TREE))?;
+            for_each_entry(&tree, |key, entry| {
+                let Some((entry_namespace, key)) =
+                    std::str::from_utf8(key).ok().and_then(split_key)
+                else {
+                    return Ok(());
+                };
+                if entry_namespace.as_deref() == namespace {
+                    let entry = bincode::deserialize::<Entry>(entry)?;
+                    entries.push(OfflineKeyValueEntry {
+                        key,
+                        value: entry.value,
+                        expiration: entry.expiration,
+                        last_updated: entry.last_updated,
+                    });
+                }
+                Ok(())
+            })?;
+        }
+        Ok(entries)
+    }
+
+    /// Returns statistics about this database.
+    pub fn statistics(&self) -> Result<DatabaseStatistics, Error> {
+        let mut statistics = DatabaseStatistics::default();
+        for tree_name in &self.tree_names {
+            let bytes = match std::fs::metadata(self.path.join(format!("{tree_name}.nebari"))) {
+                Ok(metadata) => metadata.len(),
+                Err(err) if err.kind() == ErrorKind::NotFound => 0,
+                Err(err) => return Err(Error::from(err)),
+            };
+            statistics.bytes = statistics.bytes.saturating_add(bytes);
+            statistics.trees.insert(tree_name.clone(), bytes);
+        }
+
+        for collection in self.collections() {
+            if let Some(tree) = self.versioned_tree(document_tree_name(&collection))? {
+                statistics
+                    .documents
+                    .insert(collection, tree.reduce(&(..))?.alive_keys);
+            }
+        }
+
+        if self.tree_names.contains(KEY_TREE) {
+            let tree = self.tree::<Unversioned>(String::from(KEY_TREE))?;
+            for_each_entry(&tree, |_, _| {
+                statistics.keys += 1;
+                Ok(())
+            })?;
+        }
+
+        Ok(statistics)
+    }
+
+    /// Opens the versioned tree named `name`, if it exists. Opening a tree
+    /// creates it if it doesn't exist.
+    fn versioned_tree(&self, name: String) -> Result<Option<Tree<Versioned, AnyFile>>, Error> {
+        if self.tree_names.contains(&name) {
+            Ok(Some(self.tree::<Versioned>(name)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Opens the tree named `name` through this database's
+    /// [`PlaintextVault`].
+    fn tree<R: Root>(&self, name: String) -> Result<Tree<R, AnyFile>, Error> {
+        Ok(self
+            .roots
+            .tree(R::tree(name).with_vault(self.vault.clone()))?)
+    }
+}
+
+/// The vault every tree opened by an [`OfflineDatabase`] uses. Chunks are
+/// returned as they were stored, except for chunks that were encrypted or
+/// compressed, which are rejected and recorded so that opening the database
+/// can return [`Error::ProtectedStorage`]. Writing through this vault always
+/// fails.
+#[derive(Debug, Clone, Default)]
+struct PlaintextVault {
+    detected_protected_chunk: Arc<AtomicBool>,
+}
+
+impl PlaintextVault {
+    fn detected_protected_chunk(&self) -> bool {
+        self.detected_protected_chunk.load(Ordering::Relaxed)
+    }
+
+    fn is_protected(payload: &[u8]) -> bool {
+        // Chunks written with compression, or with encryption when
+        // compression is enabled, begin with the `TreeVault` header.
+        if payload.len() >= 4 && &payload[0..3] == b"trv" {
+            return true;
+        }
+        #[cfg(feature = "encryption")]
+        if crate::vault::Vault::is_encrypted_payload(payload) {
+            return true;
+        }
+        false
+    }
+}
+
+impl nebari::Vault for PlaintextVault {
+    type Error = Error;
+
+    fn encrypt(&self, _payload: &[u8]) -> Result<Vec<u8>, Error> {
+        Err(Error::other(
+            "bonsaidb-local",
+            "offline storage can't be written to",
+        ))
+    }
+
+    fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        if Self::is_protected(payload) {
+            self.detected_protected_chunk.store(true, Ordering::Relaxed);
+            Err(Error::ProtectedStorage)
+        } else {
+            Ok(payload.to_vec())
+        }
+    }
+}
+
+/// A document read by an [`OfflineDatabase`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OfflineDocument {
+    /// The id of the document.
+    pub id: DocumentId,
+    /// The revision of the document.
+    pub revision: Revision,
+    /// The stored contents of the document.
+    pub contents: Vec<u8>,
+    /// The contents of the document decoded as a [`pot::Value`], if the
+    /// contents were serialized using Pot, the default serialization format.
+    pub value: Option<pot::Value<'static>>,
+}
+
+/// A view indexed in an [`OfflineDatabase`].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct OfflineView {
+    /// The collection the view belongs to.
+    pub collection: CollectionName,
+    /// The name of the view.
+    pub name: String,
+    /// The version of the view that was last indexed, or None if the recorded
+    /// version couldn't be read.
+    pub schema_version: Option<u64>,
+    /// The version of the view's auxiliary inputs when it was last indexed,
+    /// if it has any.
+    pub auxiliary_version: Option<u64>,
+    /// The names of the trees storing the view's index.
+    pub trees: Vec<String>,
+}
+
+/// A key-value entry read by an [`OfflineDatabase`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OfflineKeyValueEntry {
+    /// The key of the entry.
+    pub key: String,
+    /// The value stored for the key.
+    pub value: Value,
+    /// When the entry expires, if it has an expiration.
+    pub expiration: Option<Timestamp>,
+    /// When the entry was last updated.
+    pub last_updated: Timestamp,
+}
+
+/// Statistics about an [`OfflineStorage`]. Returned from
+/// [`OfflineStorage::statistics()`].
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct StorageStatistics {
+    /// The statistics of each database, keyed by database name.
+    pub databases: BTreeMap<String, DatabaseStatistics>,
+}
+
+/// Statistics about an [`OfflineDatabase`]. Returned from
+/// [`OfflineDatabase::statistics()`].
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct DatabaseStatistics {
+    /// The total number of bytes used by the database's trees on disk.
+    pub bytes: u64,
+    /// The number of bytes used by each tree on disk, keyed by tree name.
+    pub trees: BTreeMap<String, u64>,
+    /// The number of documents stored in each collection.
+    pub documents: BTreeMap<CollectionName, u64>,
+    /// The number of persisted key-value entries.
+    pub keys: u64,
+}
+
+/// A shared lock on a storage's id file. [`Storage::open()`](crate::Storage::open)
+/// holds an exclusive lock on the same file.
+#[derive(Debug)]
+struct StorageLock(File);
+
+impl StorageLock {
+    fn acquire(path: &Path) -> Result<Self, Error> {
+        let id_path = ["server-id", "storage-id"]
+            .into_iter()
+            .map(|name| path.join(name))
+            .find(|path| path.exists())
+            .ok_or_else(|| {
+                Error::Core(bonsaidb_core::Error::DatabaseNotFound(
+                    path.display().to_string(),
+                ))
+            })?;
+        let file = File::open(id_path)?;
+        match file.try_lock_shared() {
+            Ok(()) => Ok(Self(file)),
+            Err(err) if err.kind() == fs2::lock_contended_error().kind() => {
+                Err(Error::StorageInUse)
+            }
+            Err(err) => Err(Error::from(err)),
+        }
+    }
+}
+
+impl Drop for StorageLock {
+    fn drop(&mut self) {
+        drop(self.0.unlock());
+    }
+}
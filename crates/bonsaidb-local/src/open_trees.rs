@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use bonsaidb_core::schema::{CollectionName, Schematic};
+use bonsaidb_core::schema::{CollectionName, Schematic, ViewName};
 use nebari::io::any::AnyFile;
 use nebari::tree::{AnyTreeRoot, Root, Unversioned, Versioned};
 
@@ -8,7 +8,8 @@ use crate::database::document_tree_name;
 #[cfg(any(feature = "encryption", feature = "compression"))]
 use crate::storage::TreeVault;
 use crate::views::{
-    view_document_map_tree_name, view_entries_tree_name, view_invalidated_docs_tree_name,
+    view_dependents_tree_name, view_document_map_tree_name, view_entries_tree_name,
+    view_invalidated_docs_tree_name, view_prefix_reductions_tree_name,
 };
 
 #[derive(Default)]
@@ -65,6 +66,20 @@ impl OpenTrees {
                     #[cfg(any(feature = "encryption", feature = "compression"))]
                     vault.clone(),
                 );
+                if view.caches_reduce_prefixes() {
+                    self.open_tree::<Unversioned>(
+                        &view_prefix_reductions_tree_name(&view_name),
+                        #[cfg(any(feature = "encryption", feature = "compression"))]
+                        vault.clone(),
+                    );
+                }
+                if !view.dependencies().is_empty() {
+                    self.open_tree::<Unversioned>(
+                        &view_dependents_tree_name(&view_name),
+                        #[cfg(any(feature = "encryption", feature = "compression"))]
+                        vault.clone(),
+                    );
+                }
             } else {
                 self.open_tree::<Unversioned>(
                     &view_invalidated_docs_tree_name(&view_name),
@@ -74,4 +89,24 @@ impl OpenTrees {
             }
         }
     }
+
+    /// Opens the trees needed to invalidate the documents in `view` that
+    /// looked up a document from a collection it depends on when that
+    /// collection changes.
+    pub fn open_trees_for_dependency_change(
+        &mut self,
+        view: &ViewName,
+        #[cfg(any(feature = "encryption", feature = "compression"))] vault: Option<TreeVault>,
+    ) {
+        self.open_tree::<Unversioned>(
+            &view_dependents_tree_name(view),
+            #[cfg(any(feature = "encryption", feature = "compression"))]
+            vault.clone(),
+        );
+        self.open_tree::<Unversioned>(
+            &view_invalidated_docs_tree_name(view),
+            #[cfg(any(feature = "encryption", feature = "compression"))]
+            vault,
+        );
+    }
 }
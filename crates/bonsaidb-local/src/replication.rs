@@ -0,0 +1,611 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use bonsaidb_core::connection::{
+    AsyncConnection, AsyncLowLevelConnection, AsyncStorageConnection, Bound, HasSchema,
+    LowLevelConnection, Range, Sort,
+};
+use bonsaidb_core::keyvalue::{
+    AsyncKeyValue, Command, DurabilityMode, KeyOperation, Output, SetCommand, Timestamp, Value,
+};
+use bonsaidb_core::schema::{Schema, SchemaName};
+use bonsaidb_core::transaction::{Changes, Executed, Operation, Transaction};
+use parking_lot::Mutex;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::database::keyvalue::Entry;
+use crate::files::{atomic_write, FileDurability};
+use crate::{AsyncStorage, BackupLocation, Database, Error, Storage, StorageNonBlocking};
+
+/// The name of the file in the follower's storage directory that the
+/// replication cursors are saved in.
+const CURSORS_FILE: &str = "replication.cursors";
+/// The maximum number of transactions requested from the primary at once.
+const TRANSACTION_BATCH_SIZE: u32 = 1_000;
+/// The maximum number of documents requested from the primary at once while
+/// seeding a database.
+const SEED_BATCH_SIZE: u32 = 1_000;
+/// The default delay between checking the primary for new transactions once
+/// a follower has caught up.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keeps a read-only [`AsyncStorage`] nearly up to date with the databases of
+/// a primary server, allowing it to take over if the primary is lost.
+///
+/// A follower replicates each database requested with
+/// [`Follower::replicate()`]. The first time a database is replicated, it is
+/// seeded with a copy of the primary's documents and keys using the same code
+/// path as [`Storage::restore()`]. Afterwards, the follower reads the
+/// primary's committed transactions from its [transaction
+/// log](AsyncConnection::list_executed_transactions) and applies them in the
+/// order they were committed. The id of the last transaction applied is saved
+/// in the follower's storage directory, allowing replication to resume where
+/// it left off after a restart.
+///
+/// While replicating, the follower's storage is
+/// [read-only](Storage::set_read_only), which ensures that the replicated data
+/// can't conflict with local changes. Calling [`Follower::promote()`] stops
+/// replication and lifts the restriction. Dropping a follower stops
+/// replication without lifting the restriction.
+///
+/// The primary must be opened with
+/// [`replication_log`](crate::config::StorageConfiguration::replication_log)
+/// enabled, which records the contents each transaction wrote. Each
+/// transaction is replayed using those contents in a single local transaction,
+/// so the follower passes through the same states as the primary, including
+/// the expirations of keys. Replicating a transaction that was committed
+/// without the log enabled returns [`Error::ReplicationLogMissing`].
+pub struct Follower<P: AsyncStorageConnection> {
+    storage: AsyncStorage,
+    primary: P,
+    poll_interval: Duration,
+    state: Arc<State>,
+    shutdown: watch::Sender<bool>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl<P: AsyncStorageConnection> Debug for Follower<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Follower")
+            .field("storage", &self.storage)
+            .field("poll_interval", &self.poll_interval)
+            .field("status", &self.status())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The replication progress of a database. Returned from
+/// [`Follower::status()`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReplicationStatus {
+    /// The name of the database.
+    pub database: String,
+    /// The id of the last transaction from the primary that has been applied
+    /// to the follower.
+    pub applied_transaction_id: Option<u64>,
+    /// The id of the last transaction committed on the primary, as of the last
+    /// time the primary was checked.
+    pub primary_transaction_id: Option<u64>,
+    /// The last time the follower had applied every transaction committed on
+    /// the primary.
+    pub last_caught_up: Option<SystemTime>,
+    /// The most recent error encountered while replicating, if the database
+    /// hasn't caught up since the error occurred.
+    pub last_error: Option<String>,
+}
+
+impl ReplicationStatus {
+    /// Returns the number of transaction ids between the last transaction
+    /// applied and the last transaction committed on the primary.
+    #[must_use]
+    pub fn lag(&self) -> u64 {
+        match (self.primary_transaction_id, self.applied_transaction_id) {
+            (Some(primary), Some(applied)) => primary.saturating_sub(applied),
+            (Some(primary), None) => primary + 1,
+            (None, _) => 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    cursors_path: PathBuf,
+    cursors: Mutex<BTreeMap<String, Option<u64>>>,
+    statuses: Mutex<BTreeMap<String, ReplicationStatus>>,
+}
+
+impl State {
+    fn cursor(&self, database: &str) -> Option<Option<u64>> {
+        self.cursors.lock().get(database).copied()
+    }
+
+    fn save_cursor(&self, database: &str, transaction_id: Option<u64>) -> Result<(), Error> {
+        let mut cursors = self.cursors.lock();
+        cursors.insert(database.to_string(), transaction_id);
+        atomic_write(
+            &self.cursors_path,
+            &pot::to_vec(&*cursors)?,
+            FileDurability::Atomic,
+        )?;
+        drop(cursors);
+
+        self.update_status(database, |status| {
+            status.applied_transaction_id = transaction_id;
+        });
+        Ok(())
+    }
+
+    fn update_status<F: FnOnce(&mut ReplicationStatus)>(&self, database: &str, update: F) {
+        let mut statuses = self.statuses.lock();
+        let status = statuses
+            .entry(database.to_string())
+            .or_insert_with(|| ReplicationStatus {
+                database: database.to_string(),
+                applied_transaction_id: None,
+                primary_transaction_id: None,
+                last_caught_up: None,
+                last_error: None,
+            });
+        update(status);
+    }
+}
+
+impl<P> Follower<P>
+where
+    P: AsyncStorageConnection,
+    P::Database: AsyncKeyValue + 'static,
+{
+    /// Returns a follower that replicates databases from `primary` into
+    /// `storage`. `storage` is placed in read-only mode until the follower is
+    /// [promoted](Self::promote).
+    pub fn new(storage: AsyncStorage, primary: P) -> Result<Self, Error> {
+        let cursors_path = storage.path().join(CURSORS_FILE);
+        let cursors = match std::fs::read(&cursors_path) {
+            Ok(contents) => pot::from_slice(&contents)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(Error::from(err)),
+        };
+        storage.set_read_only(true);
+        let (shutdown, _) = watch::channel(false);
+        Ok(Self {
+            storage,
+            primary,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            state: Arc::new(State {
+                cursors_path,
+                cursors: Mutex::new(cursors),
+                statuses: Mutex::default(),
+            }),
+            shutdown,
+            tasks: Mutex::default(),
+        })
+    }
+
+    /// Sets the delay between checking the primary for new transactions once
+    /// a database has caught up. The default is one second.
+    #[must_use]
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Begins replicating the database named `name` from the primary.
+    ///
+    /// If `name` has not been replicated by this follower before, the local
+    /// database named `name` is replaced with a copy of the primary's
+    /// database before this function returns. `DB` must be registered with
+    /// the follower's storage.
+    pub async fn replicate<DB: Schema>(&self, name: &str) -> Result<(), Error> {
+        let primary = self.primary.database::<DB>(name).await?;
+        if let Some(applied_transaction_id) = self.state.cursor(name) {
+            self.state.update_status(name, |status| {
+                status.applied_transaction_id = applied_transaction_id;
+            });
+        } else {
+            let applied_transaction_id =
+                seed(&self.storage, &primary, name, DB::schema_name()).await?;
+            self.state.save_cursor(name, applied_transaction_id)?;
+        }
+
+        let replication = Replication {
+            storage: self.storage.clone(),
+            database: self.storage.storage.instance.database_without_schema(
+                name,
+                Some(&self.storage.storage),
+                None,
+            )?,
+            primary,
+            name: name.to_string(),
+            state: self.state.clone(),
+        };
+        let task = self
+            .storage
+            .runtime
+            .spawn(replication.run(self.poll_interval, self.shutdown.subscribe()));
+        self.tasks.lock().push(task);
+        Ok(())
+    }
+
+    /// Returns the replication progress of each database this follower
+    /// replicates.
+    #[must_use]
+    pub fn status(&self) -> Vec<ReplicationStatus> {
+        self.state.statuses.lock().values().cloned().collect()
+    }
+
+    /// Returns the storage that databases are replicated into.
+    pub const fn storage(&self) -> &AsyncStorage {
+        &self.storage
+    }
+
+    /// Stops replicating all databases and lifts the read-only restriction,
+    /// returning the storage so that it can be used in place of the primary.
+    ///
+    /// Any transactions being applied are allowed to finish before this
+    /// function returns.
+    pub async fn promote(self) -> Result<AsyncStorage, Error> {
+        self.shutdown.send_replace(true);
+        let tasks = std::mem::take(&mut *self.tasks.lock());
+        for task in tasks {
+            task.await?;
+        }
+        self.storage.set_read_only(false);
+        Ok(self.storage)
+    }
+}
+
+/// Replaces the local database named `name` with a copy of the primary's
+/// contents, returning the id of the primary's last transaction before the
+/// copy began.
+///
+/// Changes committed on the primary while the copy is being made are replayed
+/// again once replication begins. Until replication catches up, documents
+/// copied after such a change may briefly revert to the contents written by
+/// the replayed transaction.
+async fn seed<D: AsyncConnection + AsyncKeyValue>(
+    storage: &AsyncStorage,
+    primary: &D,
+    name: &str,
+    schema: SchemaName,
+) -> Result<Option<u64>, Error> {
+    let last_transaction_id = primary.last_transaction_id().await?;
+    let location = SeedLocation {
+        schema: schema.clone(),
+        database: name.to_string(),
+        objects: Mutex::default(),
+    };
+
+    for collection in primary.schematic().collections() {
+        let collection_name = collection.encoded();
+        let mut start = Bound::Unbounded;
+        loop {
+            let documents = primary
+                .list_from_collection(
+                    Range {
+                        start,
+                        end: Bound::Unbounded,
+                    },
+                    Sort::Ascending,
+                    Some(SEED_BATCH_SIZE),
+                    collection,
+                )
+                .await?;
+            let Some(last) = documents.last() else {
+                break;
+            };
+            start = Bound::Excluded(last.header.id.clone());
+            for document in &documents {
+                location.insert(
+                    &collection_name,
+                    document.header.id.to_string(),
+                    document.contents.to_vec(),
+                );
+            }
+        }
+    }
+
+    // The key-value store can't be listed remotely, so every key that has ever
+    // been persisted is discovered from the transaction log. The most recent
+    // change to each key records its value and expiration.
+    let mut keys = BTreeMap::new();
+    let mut starting_id = None;
+    loop {
+        let transactions = primary
+            .list_executed_transactions(starting_id, Some(TRANSACTION_BATCH_SIZE))
+            .await?;
+        let Some(last) = transactions.last() else {
+            break;
+        };
+        starting_id = Some(last.id + 1);
+        for transaction in transactions {
+            if let Changes::Keys(changed) = transaction.changes {
+                for changed in changed {
+                    keys.insert((changed.namespace.clone(), changed.key.clone()), changed);
+                }
+            }
+        }
+    }
+    let now = Timestamp::now();
+    for ((namespace, key), changed) in keys {
+        let entry = match changed.value {
+            Some(value) => Entry {
+                value,
+                expiration: changed.expiration,
+                last_updated: now,
+            },
+            // Changes persisted before the replication log was enabled
+            // don't record their values, and their expirations are lost.
+            None if !changed.deleted => {
+                match get_key(primary, namespace.clone(), key.clone()).await? {
+                    Some(value) => Entry {
+                        value,
+                        expiration: None,
+                        last_updated: now,
+                    },
+                    None => continue,
+                }
+            }
+            None => continue,
+        };
+        if entry
+            .expiration
+            .map_or(false, |expiration| expiration <= now)
+        {
+            continue;
+        }
+        location.insert(
+            "_kv",
+            format!("{}._key._{key}", namespace.as_deref().unwrap_or("")),
+            pot::to_vec(&entry)?,
+        );
+    }
+
+    let blocking = storage.to_blocking();
+    let name = name.to_string();
+    storage
+        .runtime
+        .spawn_blocking(move || {
+            let database = blocking.recreate_database_bypassing_read_only(&name, schema)?;
+            Storage::restore_database(&database, &location)
+        })
+        .await??;
+
+    Ok(last_transaction_id)
+}
+
+async fn get_key<D: AsyncKeyValue>(
+    primary: &D,
+    namespace: Option<String>,
+    key: String,
+) -> Result<Option<Value>, Error> {
+    match primary
+        .execute_key_operation(KeyOperation {
+            namespace,
+            key,
+            command: Command::Get { delete: false },
+        })
+        .await?
+    {
+        Output::Value(value) => Ok(value),
+        other => Err(Error::other(
+            "replication",
+            format!("unexpected output from get operation: {other:?}"),
+        )),
+    }
+}
+
+struct Replication<D> {
+    storage: AsyncStorage,
+    database: Database,
+    primary: D,
+    name: String,
+    state: Arc<State>,
+}
+
+impl<D> Replication<D>
+where
+    D: AsyncConnection + AsyncKeyValue + 'static,
+{
+    async fn run(self, poll_interval: Duration, mut shutdown: watch::Receiver<bool>) {
+        while !is_shutting_down(&shutdown) {
+            match self.catch_up(&shutdown).await {
+                Ok(true) => {
+                    self.state.update_status(&self.name, |status| {
+                        status.last_caught_up = Some(SystemTime::now());
+                        status.last_error = None;
+                    });
+                }
+                Ok(false) => continue,
+                Err(err) => {
+                    log::error!(
+                        "[replication] error replicating database {}: {err}",
+                        self.name
+                    );
+                    self.state.update_status(&self.name, |status| {
+                        status.last_error = Some(err.to_string());
+                    });
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(poll_interval) => {}
+                changed = shutdown.changed() => {
+                    if changed.is_err() {
+                        // The follower was dropped.
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Applies the next batch of transactions from the primary. Returns true
+    /// if every transaction committed on the primary has been applied.
+    async fn catch_up(&self, shutdown: &watch::Receiver<bool>) -> Result<bool, Error> {
+        let primary_transaction_id = self.primary.last_transaction_id().await?;
+        self.state.update_status(&self.name, |status| {
+            status.primary_transaction_id = primary_transaction_id;
+        });
+
+        let applied = self.state.cursor(&self.name).flatten();
+        let transactions = self
+            .primary
+            .list_executed_transactions(applied.map(|id| id + 1), Some(TRANSACTION_BATCH_SIZE))
+            .await?;
+        for transaction in transactions {
+            if is_shutting_down(shutdown) {
+                return Ok(false);
+            }
+            let id = transaction.id;
+            let database = self.database.clone();
+            self.storage
+                .runtime
+                .spawn_blocking(move || apply(&database, transaction))
+                .await??;
+            self.state.save_cursor(&self.name, Some(id))?;
+        }
+
+        let applied = self.state.cursor(&self.name).flatten();
+        Ok(applied >= primary_transaction_id)
+    }
+}
+
+fn is_shutting_down(shutdown: &watch::Receiver<bool>) -> bool {
+    *shutdown.borrow()
+}
+
+/// Replays `transaction` from the primary using the contents recorded in its
+/// replication log. Document changes are applied in a single transaction, and
+/// key changes are persisted together.
+fn apply(database: &Database, transaction: Executed) -> Result<(), Error> {
+    match transaction.changes {
+        Changes::Documents(changes) => {
+            let mut operations = Transaction::new();
+            for (collection, changed) in changes {
+                if changed.deleted {
+                    if let Some(existing) = database.get_from_collection(changed.id, &collection)? {
+                        operations.push(Operation::delete(collection, existing.header));
+                    }
+                } else {
+                    let contents = changed
+                        .contents
+                        .ok_or(Error::ReplicationLogMissing(transaction.id))?;
+                    operations.push(Operation::overwrite(collection, changed.id, contents));
+                }
+            }
+
+            if !operations.operations.is_empty() {
+                database.apply_transaction_bypassing_read_only(operations)?;
+            }
+        }
+        Changes::Keys(changed) => {
+            let mut operations = Vec::with_capacity(changed.len());
+            for changed in changed {
+                let command = if changed.deleted {
+                    Command::Delete
+                } else {
+                    Command::Set(SetCommand {
+                        value: changed
+                            .value
+                            .ok_or(Error::ReplicationLogMissing(transaction.id))?,
+                        expiration: changed.expiration,
+                        keep_existing_expiration: false,
+                        check: None,
+                        return_previous_value: false,
+                        durability: DurabilityMode::Deferred,
+                    })
+                };
+                operations.push(KeyOperation {
+                    namespace: changed.namespace,
+                    key: changed.key,
+                    command,
+                });
+            }
+
+            database.execute_key_operations_bypassing_read_only(operations)?;
+        }
+    }
+    Ok(())
+}
+
+/// An in-memory [`BackupLocation`] containing a single database, used to seed
+/// a follower using [`Storage::restore_database()`].
+struct SeedLocation {
+    schema: SchemaName,
+    database: String,
+    objects: Mutex<BTreeMap<String, BTreeMap<String, Vec<u8>>>>,
+}
+
+impl SeedLocation {
+    fn insert(&self, container: &str, name: String, object: Vec<u8>) {
+        self.objects
+            .lock()
+            .entry(container.to_string())
+            .or_default()
+            .insert(name, object);
+    }
+}
+
+impl BackupLocation for SeedLocation {
+    type Error = bonsaidb_core::Error;
+
+    fn store(
+        &self,
+        _schema: &SchemaName,
+        _database_name: &str,
+        container: &str,
+        name: &str,
+        object: &[u8],
+    ) -> Result<(), Self::Error> {
+        self.insert(container, name.to_string(), object.to_vec());
+        Ok(())
+    }
+
+    fn list_schemas(&self) -> Result<Vec<SchemaName>, Self::Error> {
+        Ok(vec![self.schema.clone()])
+    }
+
+    fn list_databases(&self, _schema: &SchemaName) -> Result<Vec<String>, Self::Error> {
+        Ok(vec![self.database.clone()])
+    }
+
+    fn list_stored(
+        &self,
+        _schema: &SchemaName,
+        _database_name: &str,
+        container: &str,
+    ) -> Result<Vec<String>, Self::Error> {
+        Ok(self
+            .objects
+            .lock()
+            .get(container)
+            .map(|objects| objects.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn load(
+        &self,
+        _schema: &SchemaName,
+        _database_name: &str,
+        container: &str,
+        name: &str,
+    ) -> Result<Vec<u8>, Self::Error> {
+        self.objects
+            .lock()
+            .get(container)
+            .and_then(|objects| objects.get(name))
+            .cloned()
+            .ok_or_else(|| {
+                bonsaidb_core::Error::other("replication", format!("{container}/{name} not found"))
+            })
+    }
+
+    fn convert_error(error: Self::Error) -> Error {
+        Error::Core(error)
+    }
+}
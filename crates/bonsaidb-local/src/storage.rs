@@ -2,31 +2,42 @@ use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display};
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::Read;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 
 use bonsaidb_core::admin::database::{self, ByName, Database as DatabaseRecord};
 use bonsaidb_core::admin::user::User;
-use bonsaidb_core::admin::{self, Admin, PermissionGroup, Role, ADMIN_DATABASE_NAME};
+use bonsaidb_core::admin::{
+    self, Admin, DatabaseEvent, DatabaseQuota, PermissionGroup, QuotaKind, Role,
+    ADMIN_DATABASE_NAME,
+};
 use bonsaidb_core::circulate;
 pub use bonsaidb_core::circulate::Relay;
 use bonsaidb_core::connection::{
-    self, Connection, HasSession, Identity, IdentityReference, LowLevelConnection, Session,
-    SessionAuthentication, SessionId, StorageConnection,
+    self, Connection, HasSession, Identity, IdentityReference, LowLevelConnection, ScrubError,
+    Session, SessionAuthentication, SessionId, StorageConnection, UserScope,
 };
 use bonsaidb_core::document::CollectionDocument;
 #[cfg(any(feature = "encryption", feature = "compression"))]
 use bonsaidb_core::document::KeyId;
+use bonsaidb_core::keyvalue::Timestamp;
 use bonsaidb_core::permissions::bonsai::{
-    bonsaidb_resource_name, database_resource_name, role_resource_name, user_resource_name,
-    BonsaiAction, ServerAction,
+    bonsaidb_resource_name, database_resource_name, external_identity_resource_name,
+    permission_group_resource_name, role_resource_name, user_resource_name, BonsaiAction,
+    ServerAction,
 };
-use bonsaidb_core::permissions::Permissions;
+use bonsaidb_core::permissions::{Permissions, Statement};
 use bonsaidb_core::schema::{
-    Nameable, NamedCollection, Schema, SchemaName, SchemaSummary, Schematic,
+    Collection, CollectionName, Nameable, NamedCollection, Schema, SchemaDescription, SchemaName,
+    SchemaSummary, Schematic, SerializedCollection, ViewName,
 };
+use bonsaidb_core::transaction::{Operation, Transaction};
+use easy_parallel::Parallel;
 use fs2::FileExt;
 use itertools::Itertools;
 use nebari::io::any::{AnyFile, AnyFileManager};
@@ -35,12 +46,19 @@ use nebari::{ChunkCache, ThreadPool};
 use parking_lot::{Mutex, RwLock};
 use rand::{thread_rng, Rng};
 
+use crate::config::{
+    CompactionPriority, DatabaseConfiguration, KeyValueClock, KeyValuePersistence,
+    StorageConfiguration, Subscribers, Views,
+};
 #[cfg(feature = "compression")]
-use crate::config::Compression;
-use crate::config::{KeyValuePersistence, StorageConfiguration};
-use crate::database::Context;
+use crate::config::{Compression, CompressionDictionary};
+use crate::database::keyvalue::{ExpirationCursors, EXPIRATION_CURSORS_FILE};
+use crate::database::quota::QuotaMonitor;
+use crate::database::scrub::{ScrubCursors, ScrubMonitor, SCRUB_CURSORS_FILE};
+use crate::database::{keyvalue, Context};
+use crate::files::{atomic_write, FileDurability};
 use crate::tasks::manager::Manager;
-use crate::tasks::TaskManager;
+use crate::tasks::{IntegrityScanStatus, TaskManager};
 #[cfg(feature = "encryption")]
 use crate::vault::{self, LocalVaultKeyStorage, Vault};
 use crate::{Database, Error};
@@ -50,9 +68,32 @@ mod argon;
 #[cfg(feature = "token-authentication")]
 mod token_authentication;
 
+mod admission;
 mod backup;
+mod external_identity;
+mod federated;
+mod format;
+pub(crate) mod integrity;
+mod lifecycle;
 mod pubsub;
-pub use backup::{AnyBackupLocation, BackupLocation};
+mod recovery;
+pub(crate) use admission::AdmissionController;
+#[cfg(feature = "async")]
+pub(crate) use admission::Priority;
+pub use admission::{AdmissionStatistics, WorkUtilization};
+pub use backup::{AdminImportMode, AnyBackupLocation, BackupLocation};
+pub use external_identity::{ExternalIdentityResolver, ResolvedIdentity};
+pub use federated::FederatedMapping;
+pub(crate) use format::MIGRATION_BATCH_SIZE;
+pub use format::{StorageUpgrade, STORAGE_FORMAT_VERSION};
+pub use integrity::{IntegrityProblem, IntegrityReport};
+pub use lifecycle::DatabaseLifecycle;
+use lifecycle::{LifecycleEvent, LifecycleNotifier};
+pub use pubsub::{PubSubStatistics, TopicStatistics};
+pub(crate) use recovery::quarantine_tree_name;
+pub use recovery::{
+    DatabaseRecovery, QuarantinedDocument, RecoveryReport, UnreadableCollection, UnreadableDocument,
+};
 
 /// A file-based, multi-database, multi-user database engine. This type blocks
 /// the current thread when used. See [`AsyncStorage`](crate::AsyncStorage) for
@@ -166,18 +207,23 @@ pub struct SessionSubscribers {
     pub subscribers: HashMap<u64, SessionSubscriber>,
     pub subscribers_by_session: HashMap<SessionId, HashSet<u64>>,
     pub last_id: u64,
+    pub topics: HashMap<Vec<u8>, pubsub::TopicSubscribers>,
+    pub statistics: pubsub::PubSubCounters,
 }
 
 impl SessionSubscribers {
     pub fn unregister(&mut self, subscriber_id: u64) {
-        if let Some(session_id) = self
-            .subscribers
-            .remove(&subscriber_id)
-            .and_then(|sub| sub.session_id)
-        {
-            if let Some(session_subscribers) = self.subscribers_by_session.get_mut(&session_id) {
-                session_subscribers.remove(&subscriber_id);
+        if let Some(subscriber) = self.subscribers.remove(&subscriber_id) {
+            if let Some(session_id) = subscriber.session_id {
+                if let Some(session_subscribers) = self.subscribers_by_session.get_mut(&session_id)
+                {
+                    session_subscribers.remove(&subscriber_id);
+                    if session_subscribers.is_empty() {
+                        self.subscribers_by_session.remove(&session_id);
+                    }
+                }
             }
+            self.drop_subscriptions(subscriber_id, subscriber);
         }
     }
 }
@@ -186,6 +232,7 @@ impl SessionSubscribers {
 pub struct SessionSubscriber {
     pub session_id: Option<SessionId>,
     pub subscriber: circulate::Subscriber,
+    pub topics: HashSet<Vec<u8>>,
 }
 
 impl Drop for AuthenticatedSession {
@@ -242,11 +289,21 @@ struct Data {
     pub(crate) tasks: TaskManager,
     schemas: RwLock<HashMap<SchemaName, Arc<dyn DatabaseOpener>>>,
     available_databases: RwLock<HashMap<String, SchemaName>>,
+    database_quotas: RwLock<HashMap<String, DatabaseQuota>>,
+    database_created_at: RwLock<HashMap<String, Timestamp>>,
+    quota_monitor: Option<Arc<dyn QuotaMonitor>>,
+    scrub_monitor: Option<Arc<dyn ScrubMonitor>>,
+    scrub_cursors: ScrubCursors,
+    expiration_cursors: ExpirationCursors,
+    lifecycle: LifecycleNotifier,
     open_roots: Mutex<HashMap<String, Context>>,
     // cfg check matches `Connection::authenticate`
     authenticated_permissions: Permissions,
+    external_identities: Option<Arc<dyn ExternalIdentityResolver>>,
     sessions: RwLock<AuthenticatedSessions>,
     pub(crate) subscribers: Arc<RwLock<SessionSubscribers>>,
+    pub(crate) subscriber_limits: Subscribers,
+    pub(crate) admission: Arc<AdmissionController>,
     #[cfg(feature = "password-hashing")]
     argon: argon::Hasher,
     #[cfg(feature = "encryption")]
@@ -255,14 +312,35 @@ struct Data {
     default_encryption_key: Option<KeyId>,
     #[cfg(any(feature = "compression", feature = "encryption"))]
     tree_vault: Option<TreeVault>,
+    /// The vaults of the databases whose compression is overridden.
+    #[cfg(feature = "compression")]
+    database_tree_vaults: HashMap<String, Option<TreeVault>>,
+    #[cfg(feature = "compression")]
+    compression_dictionaries: HashMap<CollectionName, Arc<[CompressionDictionary]>>,
+    #[cfg(feature = "compression")]
+    block_checksums: bool,
     pub(crate) key_value_persistence: KeyValuePersistence,
+    max_document_size: Option<u64>,
+    pub(crate) replication_log: bool,
+    compaction_priorities: HashMap<CollectionName, CompactionPriority>,
+    admin_schematic: Arc<Schematic>,
+    database_configurations: HashMap<String, DatabaseConfiguration>,
+    key_value_clock: Arc<dyn keyvalue::Clock>,
     chunk_cache: ChunkCache,
-    pub(crate) check_view_integrity_on_database_open: bool,
+    view_integrity_checks: Views,
     relay: Relay,
+    read_only: AtomicBool,
+    recovery_report: RwLock<Option<Arc<RecoveryReport>>>,
 }
 
 impl Storage {
     /// Creates or opens a multi-database [`Storage`] with its data stored in `directory`.
+    ///
+    /// Writing to the same storage from two handles corrupts it, so opening
+    /// storage that is already open in this process or another process waits
+    /// until the other handle is closed. If
+    /// [`StorageConfiguration::lock_timeout`] is set, [`Error::StorageInUse`]
+    /// is returned if the other handle isn't closed in time.
     pub fn open(configuration: StorageConfiguration) -> Result<Self, Error> {
         let owned_path = configuration
             .path
@@ -275,12 +353,14 @@ impl Storage {
         };
 
         let manager = Manager::default();
+        manager.set_throttle_limit(configuration.workers.max_concurrent_integrity_scans);
         for _ in 0..configuration.workers.worker_count {
             manager.spawn_worker();
         }
-        let tasks = TaskManager::new(manager);
+        let tasks = TaskManager::new(manager, configuration.workers.expiration_load_batch_size);
 
         fs::create_dir_all(&owned_path)?;
+        format::check_format_version(&owned_path)?;
 
         let storage_lock = Self::lookup_or_create_id(&configuration, &owned_path)?;
 
@@ -301,9 +381,17 @@ impl Storage {
             )?)
         };
 
+        let admin_schematic = Arc::new(admin_schematic(&configuration.admin_extensions)?);
         let parallelization = configuration.workers.parallelization;
-        let check_view_integrity_on_database_open = configuration.views.check_integrity_on_open;
+        let view_integrity_checks = configuration.views;
         let key_value_persistence = configuration.key_value_persistence;
+        let key_value_clock: Arc<dyn keyvalue::Clock> = match &configuration.key_value_clock {
+            KeyValueClock::System => Arc::new(keyvalue::SystemClock),
+            KeyValueClock::Monotonic { max_skew } => {
+                Arc::new(keyvalue::MonotonicClock::new(*max_skew))
+            }
+            KeyValueClock::Custom(clock) => clock.clone(),
+        };
         #[cfg(feature = "password-hashing")]
         let argon = argon::Hasher::new(configuration.argon);
         #[cfg(feature = "encryption")]
@@ -318,8 +406,28 @@ impl Storage {
         let tree_vault = TreeVault::new_if_needed(default_encryption_key.clone(), &vault);
         #[cfg(all(feature = "compression", not(feature = "encryption")))]
         let tree_vault = TreeVault::new_if_needed(configuration.default_compression);
+        #[cfg(feature = "compression")]
+        let tree_vault =
+            tree_vault.map(|vault| vault.with_block_checksums(configuration.block_checksums));
+        #[cfg(feature = "compression")]
+        let database_tree_vaults = configuration
+            .databases
+            .iter()
+            .filter_map(|(name, database)| {
+                let vault = TreeVault::new_if_needed(
+                    #[cfg(feature = "encryption")]
+                    default_encryption_key.clone(),
+                    #[cfg(feature = "encryption")]
+                    &vault,
+                    database.compression?,
+                )
+                .map(|vault| vault.with_block_checksums(configuration.block_checksums));
+                Some((name.clone(), vault))
+            })
+            .collect();
 
         let authenticated_permissions = configuration.authenticated_permissions;
+        let repair_mode = configuration.repair_mode;
 
         let storage = Self {
             instance: StorageInstance {
@@ -328,7 +436,10 @@ impl Storage {
                     tasks,
                     parallelization,
                     subscribers: Arc::default(),
+                    subscriber_limits: configuration.subscribers,
+                    admission: AdmissionController::new(configuration.admission),
                     authenticated_permissions,
+                    external_identities: configuration.external_identities,
                     sessions: RwLock::default(),
                     #[cfg(feature = "password-hashing")]
                     argon,
@@ -338,16 +449,43 @@ impl Storage {
                     default_encryption_key,
                     #[cfg(any(feature = "compression", feature = "encryption"))]
                     tree_vault,
+                    #[cfg(feature = "compression")]
+                    database_tree_vaults,
+                    #[cfg(feature = "compression")]
+                    compression_dictionaries: configuration
+                        .compression_dictionaries
+                        .into_iter()
+                        .map(|(collection, dictionaries)| (collection, Arc::from(dictionaries)))
+                        .collect(),
+                    #[cfg(feature = "compression")]
+                    block_checksums: configuration.block_checksums,
                     path: owned_path,
                     file_manager,
                     chunk_cache: ChunkCache::new(2000, 160_384),
                     threadpool: ThreadPool::new(parallelization),
                     schemas: RwLock::new(configuration.initial_schemas),
                     available_databases: RwLock::default(),
+                    database_quotas: RwLock::default(),
+                    database_created_at: RwLock::default(),
+                    quota_monitor: configuration.quota_monitor,
+                    scrub_monitor: configuration.scrub_monitor,
+                    scrub_cursors: ScrubCursors::new(owned_path.join(SCRUB_CURSORS_FILE)),
+                    expiration_cursors: ExpirationCursors::new(
+                        owned_path.join(EXPIRATION_CURSORS_FILE),
+                    ),
+                    lifecycle: LifecycleNotifier::new(configuration.database_lifecycle)?,
                     open_roots: Mutex::default(),
                     key_value_persistence,
-                    check_view_integrity_on_database_open,
+                    max_document_size: configuration.max_document_size,
+                    replication_log: configuration.replication_log,
+                    compaction_priorities: configuration.compaction_priorities,
+                    admin_schematic,
+                    database_configurations: configuration.databases,
+                    key_value_clock,
+                    view_integrity_checks,
                     relay: Relay::default(),
+                    read_only: AtomicBool::new(false),
+                    recovery_report: RwLock::default(),
                 }),
             },
             authentication: None,
@@ -358,6 +496,10 @@ impl Storage {
 
         storage.create_admin_database_if_needed()?;
 
+        if let Some(mode) = repair_mode {
+            storage.recover(mode)?;
+        }
+
         Ok(storage)
     }
 
@@ -369,6 +511,22 @@ impl Storage {
             .database_without_schema(&name, Some(self), None)
     }
 
+    /// Opens the database named `name` without knowing its schema's type.
+    ///
+    /// If the schema the database was created with is registered with this
+    /// storage, the database is opened using it. Otherwise, the collections
+    /// are discovered from the database's stored data and the returned
+    /// database supports schema-agnostic operations: key-value operations, and
+    /// reading, counting, and listing documents by collection name through
+    /// [`LowLevelConnection`](bonsaidb_core::connection::LowLevelConnection).
+    /// Views can't be queried, and because the views and encryption keys of
+    /// the collections are unknown, documents can't be modified: applying a
+    /// transaction returns [`Error::ReadOnly`](bonsaidb_core::Error::ReadOnly).
+    /// See [`Schematic::untyped()`] for more information.
+    pub fn with_schema_from_database(&self, name: &str) -> Result<Database, Error> {
+        self.instance.database_with_stored_schema(name, Some(self))
+    }
+
     fn lookup_or_create_id(
         configuration: &StorageConfiguration,
         path: &Path,
@@ -387,15 +545,11 @@ impl Storage {
             // mostly to prevent someone from accidentally adding this
             // configuration, realizing it breaks things, and then wanting to
             // revert. This makes reverting to the old value easier.
-            let file = if id_path.exists() {
-                File::open(id_path)?
-            } else {
-                let mut file = File::create(id_path)?;
-                let id = id.to_string();
-                file.write_all(id.as_bytes())?;
-                file
-            };
-            file.lock_exclusive()?;
+            if !id_path.exists() {
+                atomic_write(&id_path, id.to_string().as_bytes(), FileDurability::Durable)?;
+            }
+            let file = File::open(id_path)?;
+            lock_exclusive(&file, configuration.lock_timeout)?;
             (id, file)
         } else {
             // Load/Store a randomly generated id into a file. While the value
@@ -406,7 +560,7 @@ impl Storage {
                 // This value is important enought to not allow launching the
                 // server if the file can't be read or contains unexpected data.
                 let mut file = File::open(id_path)?;
-                file.lock_exclusive()?;
+                lock_exclusive(&file, configuration.lock_timeout)?;
                 let mut bytes = Vec::new();
                 file.read_to_end(&mut bytes)?;
                 let existing_id =
@@ -415,32 +569,46 @@ impl Storage {
                 (existing_id.parse().expect("server-id isn't numeric"), file)
             } else {
                 let id = { thread_rng().gen::<u64>() };
-                let mut file = File::create(id_path)?;
-                file.lock_exclusive()?;
-
-                file.write_all(id.to_string().as_bytes())?;
+                atomic_write(&id_path, id.to_string().as_bytes(), FileDurability::Durable)?;
+                let file = File::open(id_path)?;
+                lock_exclusive(&file, configuration.lock_timeout)?;
 
                 (id, file)
             }
         };
-        Ok(StorageLock::new(StorageId(id), file))
+        Ok(StorageLock::new(StorageId::from_u64(id), file))
     }
 
     fn cache_available_databases(&self) -> Result<(), Error> {
-        let available_databases = self
-            .admin()
+        let admin = self.admin();
+        let available_databases = admin
             .view::<ByName>()
             .query()?
             .into_iter()
             .map(|map| (map.key, map.value))
             .collect();
+        let mut database_quotas = HashMap::new();
+        let mut database_created_at = HashMap::new();
+        for record in DatabaseRecord::all(&admin).query()? {
+            if let Some(created_at) = record.contents.created_at {
+                database_created_at.insert(record.contents.name.clone(), created_at);
+            }
+            if !record.contents.quota.is_unlimited() {
+                database_quotas.insert(record.contents.name, record.contents.quota);
+            }
+        }
         let mut storage_databases = self.instance.data.available_databases.write();
         *storage_databases = available_databases;
+        *self.instance.data.database_quotas.write() = database_quotas;
+        *self.instance.data.database_created_at.write() = database_created_at;
         Ok(())
     }
 
     fn create_admin_database_if_needed(&self) -> Result<(), Error> {
-        self.register_schema::<Admin>()?;
+        let opener = AdminSchemaOpener {
+            schematic: self.instance.data.admin_schematic.clone(),
+        };
+        self.register_opener(Admin::schema_name(), Arc::new(opener))?;
         match self.database::<Admin>(ADMIN_DATABASE_NAME) {
             Ok(_) => {}
             Err(bonsaidb_core::Error::DatabaseNotFound(_)) => {
@@ -469,6 +637,111 @@ impl Storage {
         self.instance.data.parallelization
     }
 
+    /// Returns the [`UserScope`] of the user this storage is authenticated
+    /// as, or `None` if it isn't authenticated as a user. Each authenticated
+    /// user is permitted to create and use the database their scope owns.
+    #[must_use]
+    pub fn user_scope(&self) -> Option<UserScope> {
+        self.session().and_then(Session::user_scope)
+    }
+
+    fn assume_identity_with_limits(
+        &self,
+        identity: IdentityReference<'_>,
+        permission_limits: Vec<Permissions>,
+    ) -> Result<Storage, bonsaidb_core::Error> {
+        match identity {
+            IdentityReference::User(user) => {
+                let admin = self.admin();
+                let user =
+                    User::load(user, &admin)?.ok_or(bonsaidb_core::Error::InvalidCredentials)?;
+                self.check_permission(
+                    user_resource_name(user.header.id),
+                    &BonsaiAction::Server(ServerAction::AssumeIdentity),
+                )?;
+                self.instance.assume_user(user, &admin, permission_limits)
+            }
+            IdentityReference::Role(role) => {
+                let admin = self.admin();
+                let role =
+                    Role::load(role, &admin)?.ok_or(bonsaidb_core::Error::InvalidCredentials)?;
+                self.check_permission(
+                    role_resource_name(role.header.id),
+                    &BonsaiAction::Server(ServerAction::AssumeIdentity),
+                )?;
+                self.instance.assume_role(role, &admin, permission_limits)
+            }
+            IdentityReference::External {
+                provider,
+                credential,
+            } => {
+                self.check_permission(
+                    external_identity_resource_name(&provider),
+                    &BonsaiAction::Server(ServerAction::AssumeIdentity),
+                )?;
+                self.instance
+                    .assume_external(provider, &credential, permission_limits)
+            }
+
+            _ => Err(bonsaidb_core::Error::InvalidCredentials),
+        }
+    }
+
+    /// Returns true if this storage is in read-only mode. See
+    /// [`Storage::set_read_only()`].
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.instance.is_read_only()
+    }
+
+    /// Enables or disables read-only mode.
+    ///
+    /// While in read-only mode, operations that would modify any database
+    /// other than the admin database return
+    /// [`Error::ReadOnly`](bonsaidb_core::Error::ReadOnly), and databases
+    /// can't be created, deleted, or restored. Users, permission groups, and
+    /// roles can still be managed.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.instance
+            .data
+            .read_only
+            .store(read_only, Ordering::SeqCst);
+    }
+
+    /// Returns the status of the view integrity scans that are running or
+    /// waiting to run.
+    #[must_use]
+    pub fn integrity_scan_status(&self) -> IntegrityScanStatus {
+        self.instance.tasks().integrity_scan_status()
+    }
+
+    pub(crate) fn check_writable(&self) -> Result<(), bonsaidb_core::Error> {
+        if self.is_read_only() {
+            Err(bonsaidb_core::Error::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Deletes the database named `name` if it exists, and creates an empty
+    /// database in its place using `schema`. This is allowed even if this
+    /// storage is read-only.
+    #[cfg(feature = "async")]
+    pub(crate) fn recreate_database_bypassing_read_only(
+        &self,
+        name: &str,
+        schema: SchemaName,
+    ) -> Result<Database, Error> {
+        match self.instance.delete_database(name) {
+            Ok(()) | Err(bonsaidb_core::Error::DatabaseNotFound(_)) => {}
+            Err(err) => return Err(Error::Core(err)),
+        }
+        self.instance
+            .create_database_with_schema(name, schema, false)?;
+        self.instance
+            .database_without_schema(name, Some(self), None)
+    }
+
     #[must_use]
     #[cfg(feature = "encryption")]
     pub(crate) fn vault(&self) -> &Arc<Vault> {
@@ -481,6 +754,49 @@ impl Storage {
         self.instance.data.tree_vault.as_ref()
     }
 
+    /// Returns the vault to use for `collection`'s trees in the database
+    /// named `database`, before applying the collection's encryption key.
+    #[must_use]
+    #[cfg(any(feature = "encryption", feature = "compression"))]
+    pub(crate) fn collection_tree_vault(
+        &self,
+        database: &str,
+        collection: &CollectionName,
+    ) -> Option<TreeVault> {
+        #[cfg(feature = "compression")]
+        if let Some(dictionaries) = self.instance.data.compression_dictionaries.get(collection) {
+            let vault = self.instance.database_tree_vault(database).or_else(|| {
+                TreeVault::new_if_needed(
+                    #[cfg(feature = "encryption")]
+                    None,
+                    #[cfg(feature = "encryption")]
+                    self.vault(),
+                    Some(Compression::Lz4),
+                )
+                .map(|vault| vault.with_block_checksums(self.instance.data.block_checksums))
+            });
+            return vault.map(|vault| vault.with_compression_dictionaries(dictionaries.clone()));
+        }
+
+        #[cfg(not(feature = "compression"))]
+        let _ = collection;
+
+        self.instance.database_tree_vault(database)
+    }
+
+    pub(crate) fn max_document_size(&self) -> Option<u64> {
+        self.instance.data.max_document_size
+    }
+
+    pub(crate) fn compaction_priority(&self, collection: &CollectionName) -> CompactionPriority {
+        self.instance
+            .data
+            .compaction_priorities
+            .get(collection)
+            .copied()
+            .unwrap_or_default()
+    }
+
     #[must_use]
     #[cfg(feature = "encryption")]
     pub(crate) fn default_encryption_key(&self) -> Option<&KeyId> {
@@ -496,22 +812,61 @@ impl Storage {
 
     /// Registers a schema for use within the server.
     pub fn register_schema<DB: Schema>(&self) -> Result<(), Error> {
+        self.register_opener(
+            DB::schema_name(),
+            Arc::new(StorageSchemaOpener::<DB>::new()?),
+        )
+    }
+
+    fn register_opener(
+        &self,
+        name: SchemaName,
+        opener: Arc<dyn DatabaseOpener>,
+    ) -> Result<(), Error> {
         let mut schemas = self.instance.data.schemas.write();
-        if schemas
-            .insert(
-                DB::schema_name(),
-                Arc::new(StorageSchemaOpener::<DB>::new()?),
-            )
-            .is_none()
-        {
+        if schemas.insert(name.clone(), opener).is_none() {
             Ok(())
         } else {
             Err(Error::Core(bonsaidb_core::Error::SchemaAlreadyRegistered(
-                DB::schema_name(),
+                name,
             )))
         }
     }
 
+    /// Returns the names of all databases that were created using `schema`,
+    /// sorted by name.
+    ///
+    /// This can be used to find the databases that need to be migrated before
+    /// changing a schema, or to refuse to remove a schema that is still in use.
+    /// This requires the same permission as
+    /// [`StorageConnection::list_databases()`].
+    pub fn databases_using_schema(
+        &self,
+        schema: &SchemaName,
+    ) -> Result<Vec<String>, bonsaidb_core::Error> {
+        self.check_permission(
+            bonsaidb_resource_name(),
+            &BonsaiAction::Server(ServerAction::ListDatabases),
+        )?;
+        Ok(self.instance.databases_using_schema(schema))
+    }
+
+    /// Returns a machine-readable description of the registered schema named
+    /// `schema`. See [`Schematic::description()`] for more information.
+    ///
+    /// This requires the same permission as
+    /// [`StorageConnection::list_available_schemas()`].
+    pub fn describe_schema(
+        &self,
+        schema: &SchemaName,
+    ) -> Result<SchemaDescription, bonsaidb_core::Error> {
+        self.check_permission(
+            bonsaidb_resource_name(),
+            &BonsaiAction::Server(ServerAction::ListAvailableSchemas),
+        )?;
+        self.instance.describe_schema(schema)
+    }
+
     fn validate_name(name: &str) -> Result<(), Error> {
         if name.chars().enumerate().all(|(index, c)| {
             c.is_ascii_alphanumeric()
@@ -540,6 +895,7 @@ impl Storage {
                     id: None,
                     authentication: SessionAuthentication::None,
                     permissions: effective_permissions,
+                    permission_limits: Vec::new(),
                 })),
             })
         }
@@ -599,17 +955,30 @@ impl Debug for Data {
             .field("file_manager", &self.file_manager)
             .field("tasks", &self.tasks)
             .field("available_databases", &self.available_databases)
+            .field("database_quotas", &self.database_quotas)
+            .field("database_created_at", &self.database_created_at)
+            .field("quota_monitor", &self.quota_monitor)
+            .field("scrub_monitor", &self.scrub_monitor)
+            .field("scrub_cursors", &self.scrub_cursors)
+            .field("expiration_cursors", &self.expiration_cursors)
+            .field("lifecycle", &self.lifecycle)
             .field("open_roots", &self.open_roots)
             .field("authenticated_permissions", &self.authenticated_permissions)
+            .field("external_identities", &self.external_identities)
             .field("sessions", &self.sessions)
             .field("subscribers", &self.subscribers)
+            .field("subscriber_limits", &self.subscriber_limits)
+            .field("admission", &self.admission)
             .field("key_value_persistence", &self.key_value_persistence)
+            .field("max_document_size", &self.max_document_size)
+            .field("replication_log", &self.replication_log)
+            .field("compaction_priorities", &self.compaction_priorities)
+            .field("database_configurations", &self.database_configurations)
+            .field("key_value_clock", &self.key_value_clock)
             .field("chunk_cache", &self.chunk_cache)
-            .field(
-                "check_view_integrity_on_database_open",
-                &self.check_view_integrity_on_database_open,
-            )
-            .field("relay", &self.relay);
+            .field("view_integrity_checks", &self.view_integrity_checks)
+            .field("relay", &self.relay)
+            .field("read_only", &self.read_only);
 
         if let Some(schemas) = self.schemas.try_read() {
             let mut schemas = schemas.keys().collect::<Vec<_>>();
@@ -628,41 +997,127 @@ impl Debug for Data {
         }
         #[cfg(any(feature = "compression", feature = "encryption"))]
         f.field("tree_vault", &self.tree_vault);
+        #[cfg(feature = "compression")]
+        f.field("database_tree_vaults", &self.database_tree_vaults)
+            .field("compression_dictionaries", &self.compression_dictionaries)
+            .field("block_checksums", &self.block_checksums);
 
         f.finish()
     }
 }
 
+impl Drop for Data {
+    fn drop(&mut self) {
+        // Jobs use the databases' contexts, so the workers must stop before
+        // the contexts are closed.
+        self.tasks.stop(true);
+        for (name, _) in self.open_roots.get_mut().drain() {
+            if name != ADMIN_DATABASE_NAME {
+                self.lifecycle.notify(LifecycleEvent::Closed(name));
+            }
+        }
+        self.lifecycle.shutdown();
+    }
+}
+
+/// The `log` target that administrative changes made through
+/// [`StorageConnection`] are logged to, such as creating permission groups and
+/// roles.
+const AUDIT_LOG_TARGET: &str = "bonsaidb::audit";
+
+/// The name of the file within a database's directory that Nebari stores the
+/// transaction log in.
+const TRANSACTION_LOG_FILE_NAME: &str = "_transactions";
+
 impl StorageInstance {
-    #[cfg_attr(
-        not(any(feature = "encryption", feature = "compression")),
-        allow(unused_mut)
-    )]
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.data.read_only.load(Ordering::SeqCst)
+    }
+
+    /// Returns when the transaction log of the database named `name` was last
+    /// written to, if it is stored on disk.
+    fn transaction_log_modified_at(&self, name: &str) -> Option<Timestamp> {
+        fs::metadata(self.data.path.join(name).join(TRANSACTION_LOG_FILE_NAME))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(Timestamp::from)
+    }
+
+    /// Returns the length of the file storing `tree` in the database named
+    /// `database`, or `None` if the tree has never been written to. The file
+    /// is read through the storage's file manager, so memory-only storage
+    /// reports the length of its in-memory file.
+    pub(crate) fn tree_file_length(
+        &self,
+        database: &str,
+        tree: &str,
+    ) -> Result<Option<u64>, Error> {
+        // Nebari stores each tree in a file named after it within the
+        // database's directory.
+        let path = self.data.path.join(database).join(format!("{tree}.nebari"));
+        if self.data.file_manager.exists(&path)? {
+            Ok(Some(self.data.file_manager.file_length(&path)?))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub(crate) fn open_roots(&self, name: &str) -> Result<Context, Error> {
+        // The schema must be looked up before `open_roots` is locked, because
+        // `delete_databases()` locks `open_roots` while holding
+        // `available_databases`.
+        let schema = if self.data.lifecycle.is_enabled() && name != ADMIN_DATABASE_NAME {
+            self.data.available_databases.read().get(name).cloned()
+        } else {
+            None
+        };
         let mut open_roots = self.data.open_roots.lock();
         if let Some(roots) = open_roots.get(name) {
             Ok(roots.clone())
         } else {
             let task_name = name.to_string();
+            let database_configuration = self.data.database_configurations.get(name);
 
             let mut config = nebari::Config::new(self.data.path.join(task_name))
                 .file_manager(self.data.file_manager.clone())
-                .cache(self.data.chunk_cache.clone())
                 .shared_thread_pool(&self.data.threadpool);
+            if database_configuration
+                .and_then(|configuration| configuration.cache)
+                .unwrap_or(true)
+            {
+                config = config.cache(self.data.chunk_cache.clone());
+            }
 
             #[cfg(any(feature = "encryption", feature = "compression"))]
-            if let Some(vault) = self.data.tree_vault.clone() {
+            if let Some(vault) = self.database_tree_vault(name) {
                 config = config.vault(vault);
             }
 
             let roots = config.open().map_err(Error::from)?;
             let context = Context::new(
                 roots,
-                self.data.key_value_persistence.clone(),
+                database_configuration
+                    .and_then(|configuration| configuration.key_value_persistence.clone())
+                    .unwrap_or_else(|| self.data.key_value_persistence.clone()),
+                database_configuration
+                    .and_then(|configuration| configuration.key_value_durability)
+                    .unwrap_or_default(),
                 Some(self.data.lock.clone()),
+                self.data.key_value_clock.clone(),
+                self.data.replication_log,
             );
 
+            if let Some(quota) = self.data.database_quotas.read().get(name) {
+                context.quota.set_quota(*quota);
+            }
+
             open_roots.insert(name.to_owned(), context.clone());
+            if let Some(schema) = schema {
+                self.data.lifecycle.notify(LifecycleEvent::Opened {
+                    name: name.to_owned(),
+                    schema,
+                });
+            }
 
             Ok(context)
         }
@@ -672,14 +1127,86 @@ impl StorageInstance {
         &self.data.tasks
     }
 
-    pub(crate) fn check_view_integrity_on_database_open(&self) -> bool {
-        self.data.check_view_integrity_on_database_open
+    /// Returns the vault to use for the trees of the database named `name`,
+    /// applying the database's compression override.
+    #[cfg(any(feature = "encryption", feature = "compression"))]
+    fn database_tree_vault(&self, name: &str) -> Option<TreeVault> {
+        #[cfg(feature = "compression")]
+        if let Some(vault) = self.data.database_tree_vaults.get(name) {
+            return vault.clone();
+        }
+
+        #[cfg(not(feature = "compression"))]
+        let _ = name;
+
+        self.data.tree_vault.clone()
+    }
+
+    pub(crate) fn check_view_integrity_on_database_open(&self, view: &ViewName) -> bool {
+        self.data
+            .view_integrity_checks
+            .check_integrity_on_open_for(view)
     }
 
     pub(crate) fn relay(&self) -> &'_ Relay {
         &self.data.relay
     }
 
+    pub(crate) fn describe_schema(
+        &self,
+        schema: &SchemaName,
+    ) -> Result<SchemaDescription, bonsaidb_core::Error> {
+        self.data
+            .schemas
+            .read()
+            .get(schema)
+            .map(|opener| opener.schematic().description())
+            .ok_or_else(|| bonsaidb_core::Error::SchemaNotRegistered(schema.clone()))
+    }
+
+    pub(crate) fn notify_soft_limit_exceeded(
+        &self,
+        database: &str,
+        kind: &QuotaKind,
+        limit: u64,
+        usage: u64,
+    ) {
+        if let Some(monitor) = &self.data.quota_monitor {
+            monitor.soft_limit_exceeded(database, kind, limit, usage);
+        } else {
+            log::warn!(
+                "database '{database}' exceeded its soft limit for {kind}: limit {limit}, usage {usage}"
+            );
+        }
+    }
+
+    pub(crate) fn notify_scrub_error(&self, database: &str, error: &ScrubError) {
+        if let Some(monitor) = &self.data.scrub_monitor {
+            monitor.scrub_error(database, error);
+        } else {
+            log::error!("scrubbing database '{database}' found a problem: {error}");
+        }
+    }
+
+    pub(crate) fn scrub_cursors(&self) -> &ScrubCursors {
+        &self.data.scrub_cursors
+    }
+
+    pub(crate) fn expiration_cursors(&self) -> &ExpirationCursors {
+        &self.data.expiration_cursors
+    }
+
+    pub(crate) fn databases_using_schema(&self, schema: &SchemaName) -> Vec<String> {
+        let available_databases = self.data.available_databases.read();
+        let mut names = available_databases
+            .iter()
+            .filter(|(_, database_schema)| *database_schema == schema)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
     /// Opens a database through a generic-free trait.
     pub(crate) fn database_without_schema(
         &self,
@@ -723,6 +1250,50 @@ impl StorageInstance {
         }
     }
 
+    /// Opens a database using the schema it was created with. If the schema
+    /// isn't registered, the database's collections are discovered from its
+    /// stored data. See [`Storage::with_schema_from_database()`].
+    pub(crate) fn database_with_stored_schema(
+        &self,
+        name: &str,
+        storage: Option<&Storage>,
+    ) -> Result<Database, Error> {
+        let stored_schema = {
+            let available_databases = self.data.available_databases.read();
+            available_databases
+                .get(name)
+                .ok_or_else(|| {
+                    Error::Core(bonsaidb_core::Error::DatabaseNotFound(name.to_string()))
+                })?
+                .clone()
+        };
+
+        if self.data.schemas.read().contains_key(&stored_schema) {
+            return self.database_without_schema(name, storage, Some(stored_schema));
+        }
+
+        let storage =
+            storage.map_or_else(|| Cow::Owned(Storage::from(self.clone())), Cow::Borrowed);
+        let context = self.open_roots(name)?;
+        let collections = context
+            .roots
+            .tree_names()?
+            .into_iter()
+            .filter_map(|tree_name| {
+                tree_name
+                    .strip_prefix("collection.")
+                    .and_then(|collection| collection.parse::<CollectionName>().ok())
+            })
+            .collect::<Vec<_>>();
+        Database::with_schematic(
+            name.to_string(),
+            context,
+            storage.as_ref(),
+            Arc::new(Schematic::untyped(stored_schema, collections)),
+            true,
+        )
+    }
+
     fn update_user_with_named_id<
         'user,
         'other,
@@ -799,7 +1370,7 @@ impl StorageInstance {
                 self.data
                     .argon
                     .verify(user.header.id, password, saved_hash)?;
-                self.assume_user(user, admin)
+                self.assume_user(user, admin, Vec::new())
             }
         }
     }
@@ -808,11 +1379,17 @@ impl StorageInstance {
         &self,
         user: CollectionDocument<User>,
         admin: &Database,
+        permission_limits: Vec<Permissions>,
     ) -> Result<Storage, bonsaidb_core::Error> {
         let permissions = user.contents.effective_permissions(
             admin,
             &admin.storage().instance.data.authenticated_permissions,
         )?;
+        // Every user can access the database they own.
+        let permissions = Permissions::merged([
+            &permissions,
+            &Permissions::from(vec![UserScope::for_user(user.header.id).statement()]),
+        ]);
 
         let mut sessions = self.data.sessions.write();
         sessions.last_session_id += 1;
@@ -824,6 +1401,7 @@ impl StorageInstance {
                 username: user.contents.username,
             })),
             permissions,
+            permission_limits,
         };
         let authentication = Arc::new(AuthenticatedSession {
             storage: Arc::downgrade(&self.data),
@@ -842,6 +1420,7 @@ impl StorageInstance {
         &self,
         role: CollectionDocument<Role>,
         admin: &Database,
+        permission_limits: Vec<Permissions>,
     ) -> Result<Storage, bonsaidb_core::Error> {
         let permissions = role.contents.effective_permissions(
             admin,
@@ -858,6 +1437,52 @@ impl StorageInstance {
                 name: role.contents.name,
             })),
             permissions,
+            permission_limits,
+        };
+        let authentication = Arc::new(AuthenticatedSession {
+            storage: Arc::downgrade(&self.data),
+            session: Mutex::new(session.clone()),
+        });
+        sessions.sessions.insert(session_id, authentication.clone());
+
+        Ok(Storage {
+            instance: self.clone(),
+            authentication: Some(authentication),
+            effective_session: Some(Arc::new(session)),
+        })
+    }
+
+    fn assume_external(
+        &self,
+        provider: String,
+        credential: &[u8],
+        permission_limits: Vec<Permissions>,
+    ) -> Result<Storage, bonsaidb_core::Error> {
+        // Without a resolver, there is no way to verify the credential.
+        let resolver = self
+            .data
+            .external_identities
+            .as_ref()
+            .ok_or(bonsaidb_core::Error::InvalidCredentials)?;
+        let ResolvedIdentity {
+            subject,
+            claims,
+            permissions,
+        } = resolver.resolve(&provider, credential)?;
+        let permissions = Permissions::merged([&permissions, &self.data.authenticated_permissions]);
+
+        let mut sessions = self.data.sessions.write();
+        sessions.last_session_id += 1;
+        let session_id = SessionId(sessions.last_session_id);
+        let session = Session {
+            id: Some(session_id),
+            authentication: SessionAuthentication::Identity(Arc::new(Identity::External {
+                provider,
+                subject,
+                claims,
+            })),
+            permissions,
+            permission_limits,
         };
         let authentication = Arc::new(AuthenticatedSession {
             storage: Arc::downgrade(&self.data),
@@ -947,6 +1572,38 @@ where
     }
 }
 
+/// Adds the collections of a schema registered using
+/// [`Builder::with_admin_extension()`](crate::config::Builder::with_admin_extension)
+/// to the admin database's schematic.
+pub(crate) type AdminExtension = fn(&mut Schematic) -> Result<(), bonsaidb_core::Error>;
+
+/// Returns the schematic of the admin database, including the collections of
+/// `extensions`.
+pub(crate) fn admin_schematic(
+    extensions: &[AdminExtension],
+) -> Result<Schematic, bonsaidb_core::Error> {
+    let mut schematic = Admin::schematic()?;
+    for extend in extensions {
+        extend(&mut schematic)?;
+    }
+    Ok(schematic)
+}
+
+struct AdminSchemaOpener {
+    schematic: Arc<Schematic>,
+}
+
+impl DatabaseOpener for AdminSchemaOpener {
+    fn schematic(&self) -> &'_ Schematic {
+        &self.schematic
+    }
+
+    fn open(&self, name: String, storage: &Storage) -> Result<Database, Error> {
+        let roots = storage.instance.open_roots(&name)?;
+        Database::with_schematic(name, roots, storage, self.schematic.clone(), false)
+    }
+}
+
 impl HasSession for StorageInstance {
     fn session(&self) -> Option<&Session> {
         None
@@ -958,10 +1615,12 @@ impl StorageConnection for StorageInstance {
     type Database = Database;
 
     fn admin(&self) -> Self::Database {
-        Database::new::<Admin, _>(
+        Database::with_schematic(
             ADMIN_DATABASE_NAME,
             self.open_roots(ADMIN_DATABASE_NAME).unwrap(),
             &Storage::from(self.clone()),
+            self.data.admin_schematic.clone(),
+            false,
         )
         .unwrap()
     }
@@ -992,13 +1651,24 @@ impl StorageConnection for StorageInstance {
         let mut available_databases = self.data.available_databases.write();
         let admin = self.admin();
         if !available_databases.contains_key(name) {
+            let created_at = Timestamp::now();
             admin
                 .collection::<DatabaseRecord>()
                 .push(&admin::Database {
                     name: name.to_string(),
                     schema: schema.clone(),
+                    quota: DatabaseQuota::default(),
+                    created_at: Some(created_at),
                 })?;
-            available_databases.insert(name.to_string(), schema);
+            available_databases.insert(name.to_string(), schema.clone());
+            self.data
+                .database_created_at
+                .write()
+                .insert(name.to_string(), created_at);
+            self.publish_database_event(&DatabaseEvent::Created {
+                name: name.to_string(),
+                schema,
+            });
         } else if !only_if_needed {
             return Err(bonsaidb_core::Error::DatabaseNameAlreadyTaken(
                 name.to_string(),
@@ -1015,43 +1685,158 @@ impl StorageConnection for StorageInstance {
 
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
     fn delete_database(&self, name: &str) -> Result<(), bonsaidb_core::Error> {
+        self.delete_databases(&[name])
+            .pop()
+            .expect("one result per database")
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
+    fn delete_databases(&self, names: &[&str]) -> Vec<Result<(), bonsaidb_core::Error>> {
         let admin = self.admin();
-        let mut available_databases = self.data.available_databases.write();
-        available_databases.remove(name);
+        // Removing the databases first prevents them from being opened again,
+        // which would queue new jobs while the existing ones are cancelled.
+        let folders = {
+            let mut available_databases = self.data.available_databases.write();
+            names
+                .iter()
+                .map(|&name| {
+                    available_databases.remove(name);
+                    self.data.database_quotas.write().remove(name);
+                    self.data.database_created_at.write().remove(name);
+                    if self.data.open_roots.lock().remove(name).is_some() {
+                        self.data
+                            .lifecycle
+                            .notify(LifecycleEvent::Closed(name.to_owned()));
+                    }
+                    self.data.path.join(name)
+                })
+                .collect::<Vec<_>>()
+        };
 
-        let mut open_roots = self.data.open_roots.lock();
-        open_roots.remove(name);
-
-        let database_folder = self.data.path.join(name);
-        if database_folder.exists() {
-            let file_manager = self.data.file_manager.clone();
-            file_manager
-                .delete_directory(&database_folder)
-                .map_err(Error::Nebari)?;
+        // Running jobs may have files open within the database's folder, so
+        // they must exit before it is removed.
+        for name in names {
+            self.tasks().cancel_database_jobs(name);
+        }
+
+        let mut results = Parallel::new()
+            .each(&folders, |folder| -> Result<(), bonsaidb_core::Error> {
+                if folder.exists() {
+                    let file_manager = self.data.file_manager.clone();
+                    file_manager
+                        .delete_directory(folder)
+                        .map_err(Error::Nebari)?;
+                }
+                Ok(())
+            })
+            .run();
+        for name in names {
+            self.tasks().forget_database(name);
+            if let Err(err) = self.data.scrub_cursors.forget(name) {
+                log::warn!("error removing the scrub cursor of database '{name}': {err}");
+            }
+            if let Err(err) = self.data.expiration_cursors.forget(name) {
+                log::warn!("error removing the expiration cursor of database '{name}': {err}");
+            }
         }
 
-        if let Some(entry) = admin
+        // Databases whose files couldn't be removed keep their records.
+        let records = match admin
             .view::<database::ByName>()
-            .with_key(name)
-            .query()?
-            .first()
+            .with_keys(
+                names
+                    .iter()
+                    .zip(&results)
+                    .filter(|(_, result)| result.is_ok())
+                    .map(|(&name, _)| name),
+            )
+            .query()
         {
-            admin.delete::<DatabaseRecord, _>(&entry.source)?;
-
+            Ok(records) => records,
+            Err(err) => return vec![Err(err); names.len()],
+        };
+        let mut transaction = Transaction::new();
+        for record in &records {
+            transaction.push(Operation::delete(
+                DatabaseRecord::collection_name(),
+                record.source.clone(),
+            ));
+        }
+        let deleted = if transaction.operations.is_empty() {
             Ok(())
         } else {
-            Err(bonsaidb_core::Error::DatabaseNotFound(name.to_string()))
+            transaction.apply(&admin).map(|_| ())
+        };
+
+        for (name, result) in names.iter().zip(&mut results) {
+            if result.is_ok() {
+                *result = if records.iter().any(|record| record.key == *name) {
+                    deleted.clone()
+                } else {
+                    Err(bonsaidb_core::Error::DatabaseNotFound(name.to_string()))
+                };
+                if result.is_ok() {
+                    self.data
+                        .lifecycle
+                        .notify(LifecycleEvent::Deleted((*name).to_owned()));
+                    self.publish_database_event(&DatabaseEvent::Deleted {
+                        name: (*name).to_owned(),
+                    });
+                }
+            }
         }
+        results
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self, quota))
+    )]
+    fn set_database_quota(
+        &self,
+        name: &str,
+        quota: DatabaseQuota,
+    ) -> Result<(), bonsaidb_core::Error> {
+        let admin = self.admin();
+        let mut record = DatabaseRecord::load(name, &admin)?
+            .ok_or_else(|| bonsaidb_core::Error::DatabaseNotFound(name.to_string()))?;
+        record.contents.quota = quota;
+        record.update(&admin)?;
+
+        {
+            let mut database_quotas = self.data.database_quotas.write();
+            if quota.is_unlimited() {
+                database_quotas.remove(name);
+            } else {
+                database_quotas.insert(name.to_string(), quota);
+            }
+        }
+        if let Some(context) = self.data.open_roots.lock().get(name) {
+            context.quota.set_quota(quota);
+        }
+
+        Ok(())
     }
 
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     fn list_databases(&self) -> Result<Vec<connection::Database>, bonsaidb_core::Error> {
         let available_databases = self.data.available_databases.read();
+        let database_created_at = self.data.database_created_at.read();
+        let open_roots = self.data.open_roots.lock();
         Ok(available_databases
             .iter()
-            .map(|(name, schema)| connection::Database {
-                name: name.to_string(),
-                schema: schema.clone(),
+            .map(|(name, schema)| {
+                let context = open_roots.get(name);
+                connection::Database {
+                    name: name.to_string(),
+                    schema: schema.clone(),
+                    created_at: database_created_at.get(name).copied(),
+                    last_transaction_at: context
+                        .and_then(|context| context.activity.last_committed_at())
+                        .or_else(|| self.transaction_log_modified_at(name)),
+                    size: context.and_then(|context| context.quota.tracked_bytes()),
+                    open: Some(context.is_some()),
+                }
             })
             .collect())
     }
@@ -1086,11 +1871,45 @@ impl StorageConnection for StorageInstance {
         &self,
         user: U,
     ) -> Result<(), bonsaidb_core::Error> {
+        self.delete_users(&[user.name()?])
+            .pop()
+            .expect("one result per user")
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    fn delete_users<'user, U: Nameable<'user, u64> + Clone + Send + Sync>(
+        &self,
+        users: &[U],
+    ) -> Vec<Result<(), bonsaidb_core::Error>> {
         let admin = self.admin();
-        let user = User::load(user, &admin)?.ok_or(bonsaidb_core::Error::UserNotFound)?;
-        user.delete(&admin)?;
+        let mut transaction = Transaction::new();
+        let mut deleting = HashSet::new();
+        let mut results = users
+            .iter()
+            .map(|user| {
+                let user =
+                    User::load(user.clone(), &admin)?.ok_or(bonsaidb_core::Error::UserNotFound)?;
+                // Deleting the same user twice would conflict, so repeated
+                // users are treated as already deleted.
+                if !deleting.insert(user.header.id) {
+                    return Err(bonsaidb_core::Error::UserNotFound);
+                }
+                transaction.push(Operation::delete(
+                    User::collection_name(),
+                    user.header.try_into()?,
+                ));
+                Ok(())
+            })
+            .collect::<Vec<_>>();
 
-        Ok(())
+        if !transaction.operations.is_empty() {
+            if let Err(err) = transaction.apply(&admin) {
+                for result in results.iter_mut().filter(|result| result.is_ok()) {
+                    *result = Err(err.clone());
+                }
+            }
+        }
+        results
     }
 
     #[cfg(feature = "password-hashing")]
@@ -1127,17 +1946,33 @@ impl StorageConnection for StorageInstance {
             IdentityReference::User(user) => {
                 let user =
                     User::load(user, &admin)?.ok_or(bonsaidb_core::Error::InvalidCredentials)?;
-                self.assume_user(user, &admin).map(Storage::from)
+                self.assume_user(user, &admin, Vec::new())
+                    .map(Storage::from)
             }
             IdentityReference::Role(role) => {
                 let role =
                     Role::load(role, &admin)?.ok_or(bonsaidb_core::Error::InvalidCredentials)?;
-                self.assume_role(role, &admin).map(Storage::from)
+                self.assume_role(role, &admin, Vec::new())
+                    .map(Storage::from)
             }
+            IdentityReference::External {
+                provider,
+                credential,
+            } => self
+                .assume_external(provider, &credential, Vec::new())
+                .map(Storage::from),
             _ => Err(bonsaidb_core::Error::InvalidCredentials),
         }
     }
 
+    fn assume_identity_capped(
+        &self,
+        identity: IdentityReference<'_>,
+    ) -> Result<Self::Authenticated, bonsaidb_core::Error> {
+        // The instance has no session to limit the assumed identity to.
+        self.assume_identity(identity)
+    }
+
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     fn add_permission_group_to_user<
         'user,
@@ -1215,6 +2050,83 @@ impl StorageConnection for StorageInstance {
             Ok(Self::remove_role_from_user_inner(user, role_id))
         })
     }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self, statements))
+    )]
+    fn create_permission_group(
+        &self,
+        name: &str,
+        statements: Vec<Statement>,
+    ) -> Result<u64, bonsaidb_core::Error> {
+        PermissionGroup::validate_statements(&statements)?;
+        let group = PermissionGroup::named(name)
+            .with_group_ids(statements)
+            .push_into(&self.admin())?;
+        log::info!(
+            target: AUDIT_LOG_TARGET,
+            "created permission group '{name}' ({})",
+            group.header.id
+        );
+        Ok(group.header.id)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    fn update_permission_group_statements<'group, G: Nameable<'group, u64> + Send + Sync>(
+        &self,
+        permission_group: G,
+        statements: Vec<Statement>,
+    ) -> Result<(), bonsaidb_core::Error> {
+        PermissionGroup::validate_statements(&statements)?;
+        let admin = self.admin();
+        let mut group = PermissionGroup::load(permission_group, &admin)?
+            .ok_or(bonsaidb_core::Error::PermissionGroupNotFound)?;
+        group.contents.statements = statements;
+        group.update(&admin)?;
+        log::info!(
+            target: AUDIT_LOG_TARGET,
+            "updated the statements of permission group '{}' ({})",
+            group.contents.name,
+            group.header.id
+        );
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
+    fn create_role(&self, name: &str, groups: Vec<u64>) -> Result<u64, bonsaidb_core::Error> {
+        let admin = self.admin();
+        let unique_groups = groups.iter().copied().collect::<HashSet<_>>();
+        let unique_groups = unique_groups.into_iter().collect::<Vec<_>>();
+        if PermissionGroup::get_multiple(&unique_groups, &admin)?.len() != unique_groups.len() {
+            return Err(bonsaidb_core::Error::PermissionGroupNotFound);
+        }
+
+        let role = Role::named(name).with_group_ids(groups).push_into(&admin)?;
+        log::info!(
+            target: AUDIT_LOG_TARGET,
+            "created role '{name}' ({})",
+            role.header.id
+        );
+        Ok(role.header.id)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    fn delete_role<'role, R: Nameable<'role, u64> + Send + Sync>(
+        &self,
+        role: R,
+    ) -> Result<(), bonsaidb_core::Error> {
+        let admin = self.admin();
+        let role = Role::load(role, &admin)?.ok_or(bonsaidb_core::Error::RoleNotFound)?;
+        role.delete(&admin)?;
+        log::info!(
+            target: AUDIT_LOG_TARGET,
+            "deleted role '{}' ({})",
+            role.contents.name,
+            role.header.id
+        );
+        Ok(())
+    }
 }
 
 impl HasSession for Storage {
@@ -1241,12 +2153,15 @@ impl StorageConnection for Storage {
             database_resource_name(name),
             &BonsaiAction::Server(ServerAction::CreateDatabase),
         )?;
+        self.check_writable()?;
         self.instance
             .create_database_with_schema(name, schema, only_if_needed)
     }
 
     fn database<DB: Schema>(&self, name: &str) -> Result<Self::Database, bonsaidb_core::Error> {
-        self.instance.database::<DB>(name)
+        self.instance
+            .database_without_schema(name, Some(self), Some(DB::schema_name()))
+            .map_err(bonsaidb_core::Error::from)
     }
 
     fn delete_database(&self, name: &str) -> Result<(), bonsaidb_core::Error> {
@@ -1254,9 +2169,47 @@ impl StorageConnection for Storage {
             database_resource_name(name),
             &BonsaiAction::Server(ServerAction::DeleteDatabase),
         )?;
+        self.check_writable()?;
         self.instance.delete_database(name)
     }
 
+    fn delete_databases(&self, names: &[&str]) -> Vec<Result<(), bonsaidb_core::Error>> {
+        let mut results = names
+            .iter()
+            .map(|&name| {
+                self.check_permission(
+                    database_resource_name(name),
+                    &BonsaiAction::Server(ServerAction::DeleteDatabase),
+                )?;
+                self.check_writable()
+            })
+            .collect::<Vec<_>>();
+        let permitted = names
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_ok())
+            .map(|(&name, _)| name)
+            .collect::<Vec<_>>();
+
+        let mut deleted = self.instance.delete_databases(&permitted).into_iter();
+        for result in results.iter_mut().filter(|result| result.is_ok()) {
+            *result = deleted.next().expect("one result per database");
+        }
+        results
+    }
+
+    fn set_database_quota(
+        &self,
+        name: &str,
+        quota: DatabaseQuota,
+    ) -> Result<(), bonsaidb_core::Error> {
+        self.check_permission(
+            database_resource_name(name),
+            &BonsaiAction::Server(ServerAction::SetDatabaseQuota),
+        )?;
+        self.instance.set_database_quota(name, quota)
+    }
+
     fn list_databases(&self) -> Result<Vec<connection::Database>, bonsaidb_core::Error> {
         self.check_permission(
             bonsaidb_resource_name(),
@@ -1297,6 +2250,35 @@ impl StorageConnection for Storage {
         self.instance.delete_user(user)
     }
 
+    fn delete_users<'user, U: Nameable<'user, u64> + Clone + Send + Sync>(
+        &self,
+        users: &[U],
+    ) -> Vec<Result<(), bonsaidb_core::Error>> {
+        let admin = self.admin();
+        let mut permitted = Vec::with_capacity(users.len());
+        let mut results = users
+            .iter()
+            .map(|user| -> Result<(), bonsaidb_core::Error> {
+                let user = user.clone().name()?;
+                let user_id = user
+                    .id::<User, _>(&admin)?
+                    .ok_or(bonsaidb_core::Error::UserNotFound)?;
+                self.check_permission(
+                    user_resource_name(user_id),
+                    &BonsaiAction::Server(ServerAction::DeleteUser),
+                )?;
+                permitted.push(user);
+                Ok(())
+            })
+            .collect::<Vec<_>>();
+
+        let mut deleted = self.instance.delete_users(&permitted).into_iter();
+        for result in results.iter_mut().filter(|result| result.is_ok()) {
+            *result = deleted.next().expect("one result per user");
+        }
+        results
+    }
+
     #[cfg(feature = "password-hashing")]
     fn set_user_password<'user, U: Nameable<'user, u64> + Send + Sync>(
         &self,
@@ -1363,30 +2345,19 @@ impl StorageConnection for Storage {
         &self,
         identity: IdentityReference<'_>,
     ) -> Result<Self::Authenticated, bonsaidb_core::Error> {
-        match identity {
-            IdentityReference::User(user) => {
-                let admin = self.admin();
-                let user =
-                    User::load(user, &admin)?.ok_or(bonsaidb_core::Error::InvalidCredentials)?;
-                self.check_permission(
-                    user_resource_name(user.header.id),
-                    &BonsaiAction::Server(ServerAction::AssumeIdentity),
-                )?;
-                self.instance.assume_user(user, &admin)
-            }
-            IdentityReference::Role(role) => {
-                let admin = self.admin();
-                let role =
-                    Role::load(role, &admin)?.ok_or(bonsaidb_core::Error::InvalidCredentials)?;
-                self.check_permission(
-                    role_resource_name(role.header.id),
-                    &BonsaiAction::Server(ServerAction::AssumeIdentity),
-                )?;
-                self.instance.assume_role(role, &admin)
-            }
+        self.assume_identity_with_limits(identity, Vec::new())
+    }
 
-            _ => Err(bonsaidb_core::Error::InvalidCredentials),
-        }
+    fn assume_identity_capped(
+        &self,
+        identity: IdentityReference<'_>,
+    ) -> Result<Self::Authenticated, bonsaidb_core::Error> {
+        let permission_limits = self
+            .effective_session
+            .as_deref()
+            .map(Session::capped_permission_limits)
+            .unwrap_or_default();
+        self.assume_identity_with_limits(identity, permission_limits)
     }
 
     fn add_permission_group_to_user<
@@ -1482,6 +2453,100 @@ impl StorageConnection for Storage {
                 Ok(StorageInstance::remove_role_from_user_inner(user, role_id))
             })
     }
+
+    fn create_permission_group(
+        &self,
+        name: &str,
+        statements: Vec<Statement>,
+    ) -> Result<u64, bonsaidb_core::Error> {
+        self.check_permission(
+            bonsaidb_resource_name(),
+            &BonsaiAction::Server(ServerAction::CreatePermissionGroup),
+        )?;
+        self.instance.create_permission_group(name, statements)
+    }
+
+    fn update_permission_group_statements<'group, G: Nameable<'group, u64> + Send + Sync>(
+        &self,
+        permission_group: G,
+        statements: Vec<Statement>,
+    ) -> Result<(), bonsaidb_core::Error> {
+        let admin = self.admin();
+        let permission_group = permission_group.name()?;
+        let group_id = permission_group
+            .id::<PermissionGroup, _>(&admin)?
+            .ok_or(bonsaidb_core::Error::PermissionGroupNotFound)?;
+        self.check_permission(
+            permission_group_resource_name(group_id),
+            &BonsaiAction::Server(ServerAction::UpdatePermissionGroup),
+        )?;
+        self.instance
+            .update_permission_group_statements(permission_group, statements)
+    }
+
+    fn create_role(&self, name: &str, groups: Vec<u64>) -> Result<u64, bonsaidb_core::Error> {
+        self.check_permission(
+            bonsaidb_resource_name(),
+            &BonsaiAction::Server(ServerAction::CreateRole),
+        )?;
+        self.instance.create_role(name, groups)
+    }
+
+    fn delete_role<'role, R: Nameable<'role, u64> + Send + Sync>(
+        &self,
+        role: R,
+    ) -> Result<(), bonsaidb_core::Error> {
+        let admin = self.admin();
+        let role = role.name()?;
+        let role_id = role
+            .id::<Role, _>(&admin)?
+            .ok_or(bonsaidb_core::Error::RoleNotFound)?;
+        self.check_permission(
+            role_resource_name(role_id),
+            &BonsaiAction::Server(ServerAction::DeleteRole),
+        )?;
+        self.instance.delete_role(role)
+    }
+}
+
+#[test]
+fn storage_id_parsing() {
+    for id in [0, 1, 1_000, 0x0123_4567_89ab_cdef, u64::MAX] {
+        let storage_id = StorageId::from_u64(id);
+        assert_eq!(storage_id.as_u64(), id);
+
+        let hex = storage_id.to_string();
+        assert_eq!(hex.len(), 16);
+        assert_eq!(hex.parse::<StorageId>(), Ok(storage_id));
+        assert_eq!(format!("0x{hex}").parse::<StorageId>(), Ok(storage_id));
+        assert_eq!(format!("{storage_id:#}"), format!("0x{hex}"));
+        assert_eq!(format!("{id:#x}").parse::<StorageId>(), Ok(storage_id));
+
+        // The on-disk representation.
+        assert_eq!(id.to_string().parse::<StorageId>(), Ok(storage_id));
+    }
+
+    // Without a prefix, 16 digits are parsed as hexadecimal.
+    assert_eq!(
+        "0000000000001000".parse::<StorageId>(),
+        Ok(StorageId::from_u64(0x1000))
+    );
+    assert_eq!(
+        "0000000000abcdef".parse::<StorageId>(),
+        Ok(StorageId::from_u64(0xab_cdef))
+    );
+
+    assert_eq!("".parse::<StorageId>(), Err(InvalidStorageId));
+    assert_eq!("0x".parse::<StorageId>(), Err(InvalidStorageId));
+    assert_eq!("-1".parse::<StorageId>(), Err(InvalidStorageId));
+    assert_eq!(
+        "00000000000000zz".parse::<StorageId>(),
+        Err(InvalidStorageId)
+    );
+    assert_eq!(
+        "18446744073709551616".parse::<StorageId>(),
+        Err(InvalidStorageId)
+    );
 }
 
 #[test]
@@ -1502,10 +2567,37 @@ fn name_validation_tests() {
 }
 
 /// The unique id of a [`Storage`] instance.
+///
+/// The id is formatted by [`Debug`] and [`Display`] as 16 lowercase
+/// hexadecimal digits. The alternate [`Display`] format (`{:#}`) prefixes the
+/// digits with `0x`. On disk, the id is stored in the `storage-id` file
+/// (`server-id` for storage created by older versions) in the storage's
+/// directory as the ASCII decimal representation of the id, with no trailing
+/// newline.
+///
+/// Both representations can be parsed using [`FromStr`]. Hexadecimal ids
+/// must be prefixed with `0x`:
+///
+/// ```rust
+/// use bonsaidb_local::StorageId;
+///
+/// let id = StorageId::from_u64(1_000);
+/// assert_eq!(id.to_string(), "00000000000003e8");
+/// assert_eq!(format!("{id:#}"), "0x00000000000003e8");
+/// assert_eq!("0x00000000000003e8".parse::<StorageId>().unwrap(), id);
+/// assert_eq!("0x3e8".parse::<StorageId>().unwrap(), id);
+/// assert_eq!("1000".parse::<StorageId>().unwrap(), id);
+/// ```
 #[derive(Clone, Copy, Eq, PartialEq, Hash)]
 pub struct StorageId(u64);
 
 impl StorageId {
+    /// Returns an id from its u64 representation.
+    #[must_use]
+    pub const fn from_u64(id: u64) -> Self {
+        Self(id)
+    }
+
     /// Returns the id as a u64.
     #[must_use]
     pub const fn as_u64(self) -> u64 {
@@ -1513,6 +2605,38 @@ impl StorageId {
     }
 }
 
+impl From<u64> for StorageId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl FromStr for StorageId {
+    type Err = InvalidStorageId;
+
+    /// Parses an id from either its hexadecimal or decimal representation.
+    ///
+    /// Strings prefixed with `0x` and unprefixed strings of exactly 16
+    /// characters, such as the [`Display`] output, are parsed as hexadecimal.
+    /// All other strings are parsed as decimal, matching the format stored on
+    /// disk.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = if let Some(hex) = s.strip_prefix("0x") {
+            u64::from_str_radix(hex, 16)
+        } else if s.len() == 16 {
+            u64::from_str_radix(s, 16)
+        } else {
+            s.parse()
+        };
+        parsed.map(Self).map_err(|_| InvalidStorageId)
+    }
+}
+
+/// A string could not be parsed as a [`StorageId`].
+#[derive(thiserror::Error, Debug, Clone, Copy, Eq, PartialEq)]
+#[error("invalid storage id")]
+pub struct InvalidStorageId;
+
 impl Debug for StorageId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // let formatted_length = format!();
@@ -1522,7 +2646,10 @@ impl Debug for StorageId {
 
 impl Display for StorageId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(self, f)
+        if f.alternate() {
+            f.write_str("0x")?;
+        }
+        write!(f, "{:016x}", self.0)
     }
 }
 
@@ -1531,12 +2658,129 @@ impl Display for StorageId {
 pub(crate) struct TreeVault {
     #[cfg(feature = "compression")]
     compression: Option<Compression>,
+    #[cfg(feature = "compression")]
+    dictionaries: Option<Arc<[CompressionDictionary]>>,
+    #[cfg(feature = "compression")]
+    checksums: bool,
     #[cfg(feature = "encryption")]
     pub key: Option<KeyId>,
     #[cfg(feature = "encryption")]
     pub vault: Arc<Vault>,
 }
 
+/// The compression header bits used for values compressed using a
+/// [`CompressionDictionary`]. The compressed payload is prefixed with the
+/// little-endian id of the dictionary used.
+#[cfg(feature = "compression")]
+const LZ4_DICTIONARY_HEADER: u8 = 2;
+
+/// The header bit set when a block's header is followed by the little-endian
+/// CRC32 of the remainder of the block.
+#[cfg(feature = "compression")]
+const CHECKSUM_HEADER_BIT: u8 = 0b0100_0000;
+
+/// The header bits that identify a block's compression algorithm.
+#[cfg(feature = "compression")]
+const COMPRESSION_HEADER_BITS: u8 = 0b0011_1111;
+
+#[cfg(feature = "compression")]
+impl TreeVault {
+    pub(crate) fn with_compression_dictionaries(
+        mut self,
+        dictionaries: Arc<[CompressionDictionary]>,
+    ) -> Self {
+        self.dictionaries = Some(dictionaries);
+        self
+    }
+
+    pub(crate) fn with_block_checksums(mut self, enabled: bool) -> Self {
+        self.checksums = enabled;
+        self
+    }
+
+    /// Returns the number of bytes [`Self::write_header()`] writes.
+    const fn header_length(&self) -> usize {
+        if self.checksums {
+            8
+        } else {
+            4
+        }
+    }
+
+    /// Writes the `trv` header with `header` bits to the start of `block`,
+    /// which must begin with [`Self::header_length()`] reserved bytes.
+    fn write_header(&self, header: u8, block: &mut [u8]) {
+        let (prefix, payload) = block.split_at_mut(self.header_length());
+        prefix[0..3].copy_from_slice(b"trv");
+        if self.checksums {
+            prefix[3] = header | CHECKSUM_HEADER_BIT;
+            prefix[4..8].copy_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        } else {
+            prefix[3] = header;
+        }
+    }
+
+    /// Prefixes `block` with a `trv` header with `header` bits. Blocks that
+    /// don't need a header are returned unchanged.
+    fn prepend_header(&self, header: u8, mut block: Vec<u8>) -> Vec<u8> {
+        if header != 0 || self.checksums {
+            block.splice(0..0, std::iter::repeat(0).take(self.header_length()));
+            self.write_header(header, &mut block);
+        }
+        block
+    }
+
+    /// Verifies the checksum of `payload` if `header` indicates one is
+    /// present, returning the remainder of the block. Checksummed blocks are
+    /// verified even if checksums are no longer enabled for this vault.
+    fn verify_checksum(header: u8, payload: &[u8]) -> Result<&[u8], Error> {
+        if header & CHECKSUM_HEADER_BIT == 0 {
+            return Ok(payload);
+        }
+        if payload.len() < 4 {
+            return Err(Error::BlockCorrupted);
+        }
+
+        let (checksum, payload) = payload.split_at(4);
+        if crc32fast::hash(payload).to_le_bytes() == checksum {
+            Ok(payload)
+        } else {
+            Err(Error::BlockCorrupted)
+        }
+    }
+
+    /// Compresses `payload` using the most recently configured dictionary.
+    /// Returns None if no dictionary is configured or if compressing with the
+    /// dictionary didn't reduce the size of `payload`.
+    fn compress_with_dictionary(&self, payload: &[u8]) -> Option<Vec<u8>> {
+        let dictionary = self.dictionaries.as_ref()?.last()?;
+        let mut compressed = dictionary.id().to_le_bytes().to_vec();
+        compressed.extend(lz4_flex::block::compress_prepend_size_with_dict(
+            payload,
+            dictionary.as_bytes(),
+        ));
+        (compressed.len() < payload.len()).then_some(compressed)
+    }
+
+    fn decompress_with_dictionary(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        if payload.len() < 4 {
+            return Err(Error::from(
+                lz4_flex::block::DecompressError::ExpectedAnotherByte,
+            ));
+        }
+        let (id, payload) = payload.split_at(4);
+        let id = u32::from_le_bytes(id.try_into().expect("checked length"));
+        let dictionary = self
+            .dictionaries
+            .iter()
+            .flat_map(|dictionaries| dictionaries.iter())
+            .find(|dictionary| dictionary.id() == id)
+            .ok_or(Error::CompressionDictionaryNotFound(id))?;
+        lz4_flex::block::decompress_size_prepended_with_dict(payload, dictionary.as_bytes())
+            .map_err(Error::from)
+    }
+}
+
 #[cfg(all(feature = "compression", feature = "encryption"))]
 impl TreeVault {
     pub(crate) fn new_if_needed(
@@ -1550,18 +2794,18 @@ impl TreeVault {
             Some(Self {
                 key,
                 compression,
+                dictionaries: None,
+                checksums: false,
                 vault: vault.clone(),
             })
         }
     }
 
-    fn header(&self, compressed: bool) -> u8 {
+    fn header(&self, compression: Option<u8>) -> u8 {
         let mut bits = if self.key.is_some() { 0b1000_0000 } else { 0 };
 
-        if compressed {
-            if let Some(compression) = self.compression {
-                bits |= compression as u8;
-            }
+        if let Some(compression) = compression {
+            bits |= compression;
         }
 
         bits
@@ -1575,41 +2819,43 @@ impl nebari::Vault for TreeVault {
     fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
         // TODO this allocates too much. The vault should be able to do an
         // in-place encryption operation so that we can use a single buffer.
-        let mut includes_compression = false;
+        let mut compression = None;
         let compressed = match (payload.len(), self.compression) {
             (128..=usize::MAX, Some(Compression::Lz4)) => {
-                includes_compression = true;
-                Cow::Owned(lz4_flex::block::compress_prepend_size(payload))
+                if let Some(compressed) = self.compress_with_dictionary(payload) {
+                    compression = Some(LZ4_DICTIONARY_HEADER);
+                    Cow::Owned(compressed)
+                } else {
+                    compression = Some(Compression::Lz4 as u8);
+                    Cow::Owned(lz4_flex::block::compress_prepend_size(payload))
+                }
             }
             _ => Cow::Borrowed(payload),
         };
 
-        let mut complete = if let Some(key) = &self.key {
+        let complete = if let Some(key) = &self.key {
             self.vault.encrypt_payload(key, &compressed, None)?
         } else {
             compressed.into_owned()
         };
 
-        let header = self.header(includes_compression);
-        if header != 0 {
-            let header = [b't', b'r', b'v', header];
-            complete.splice(0..0, header);
-        }
-
-        Ok(complete)
+        Ok(self.prepend_header(self.header(compression), complete))
     }
 
     fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
         if payload.len() >= 4 && &payload[0..3] == b"trv" {
             let header = payload[3];
-            let payload = &payload[4..];
+            let payload = Self::verify_checksum(header, &payload[4..])?;
             let encrypted = (header & 0b1000_0000) != 0;
-            let compression = header & 0b0111_1111;
+            let compression = header & COMPRESSION_HEADER_BITS;
             let decrypted = if encrypted {
                 Cow::Owned(self.vault.decrypt_payload(payload, None)?)
             } else {
                 Cow::Borrowed(payload)
             };
+            if compression == LZ4_DICTIONARY_HEADER {
+                return self.decompress_with_dictionary(&decrypted);
+            }
             #[allow(clippy::single_match)] // Make it an error when we add a new algorithm
             return Ok(match Compression::from_u8(compression) {
                 Some(Compression::Lz4) => {
@@ -1668,6 +2914,7 @@ impl StorageNonBlocking for Storage {
             id: authentication_session.id,
             authentication: authentication_session.authentication.clone(),
             permissions: effective_permissions,
+            permission_limits: authentication_session.permission_limits.clone(),
         };
 
         Ok(Self {
@@ -1683,6 +2930,8 @@ impl TreeVault {
     pub(crate) fn new_if_needed(compression: Option<Compression>) -> Option<Self> {
         compression.map(|compression| Self {
             compression: Some(compression),
+            dictionaries: None,
+            checksums: false,
         })
     }
 }
@@ -1694,33 +2943,44 @@ impl nebari::Vault for TreeVault {
     fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
         Ok(match (payload.len(), self.compression) {
             (128..=usize::MAX, Some(Compression::Lz4)) => {
+                if let Some(compressed) = self.compress_with_dictionary(payload) {
+                    return Ok(self.prepend_header(LZ4_DICTIONARY_HEADER, compressed));
+                }
+                let header_length = self.header_length();
                 let mut destination =
-                    vec![0; lz4_flex::block::get_maximum_output_size(payload.len()) + 8];
+                    vec![
+                        0;
+                        lz4_flex::block::get_maximum_output_size(payload.len()) + header_length + 4
+                    ];
                 let compressed_length =
-                    lz4_flex::block::compress_into(payload, &mut destination[8..])
+                    lz4_flex::block::compress_into(payload, &mut destination[header_length + 4..])
                         .expect("lz4-flex documents this shouldn't fail");
-                destination.truncate(compressed_length + 8);
-                destination[0..4].copy_from_slice(&[b't', b'r', b'v', Compression::Lz4 as u8]);
+                destination.truncate(compressed_length + header_length + 4);
                 // to_le_bytes() makes it compatible with lz4-flex decompress_size_prepended.
                 let uncompressed_length =
                     u32::try_from(payload.len()).expect("nebari doesn't support >32 bit blocks");
-                destination[4..8].copy_from_slice(&uncompressed_length.to_le_bytes());
+                destination[header_length..header_length + 4]
+                    .copy_from_slice(&uncompressed_length.to_le_bytes());
+                self.write_header(Compression::Lz4 as u8, &mut destination);
                 destination
             }
             // TODO this shouldn't copy
-            _ => payload.to_vec(),
+            _ => self.prepend_header(0, payload.to_vec()),
         })
     }
 
     fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
         if payload.len() >= 4 && &payload[0..3] == b"trv" {
             let header = payload[3];
-            let payload = &payload[4..];
+            let payload = Self::verify_checksum(header, &payload[4..])?;
             let encrypted = (header & 0b1000_0000) != 0;
-            let compression = header & 0b0111_1111;
+            let compression = header & COMPRESSION_HEADER_BITS;
             if encrypted {
                 return Err(Error::EncryptionDisabled);
             }
+            if compression == LZ4_DICTIONARY_HEADER {
+                return self.decompress_with_dictionary(payload);
+            }
 
             #[allow(clippy::single_match)] // Make it an error when we add a new algorithm
             return Ok(match Compression::from_u8(compression) {
@@ -1794,3 +3054,27 @@ impl Drop for LockData {
         drop(self.0.unlock());
     }
 }
+
+/// Locks the storage's id file. If `timeout` is `None`, this waits until
+/// every other handle has released the lock. Otherwise,
+/// [`Error::StorageInUse`] is returned if the lock isn't released within
+/// `timeout`.
+fn lock_exclusive(file: &File, timeout: Option<Duration>) -> Result<(), Error> {
+    let Some(timeout) = timeout else {
+        return Ok(file.lock_exclusive()?);
+    };
+
+    let started = Instant::now();
+    loop {
+        match file.try_lock_exclusive() {
+            Ok(()) => return Ok(()),
+            Err(err) if err.kind() == fs2::lock_contended_error().kind() => {
+                if started.elapsed() >= timeout {
+                    return Err(Error::StorageInUse);
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(err) => return Err(Error::from(err)),
+        }
+    }
+}
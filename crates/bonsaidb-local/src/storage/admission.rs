@@ -0,0 +1,156 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::config::{Admission, WorkCategory};
+use crate::Storage;
+
+/// The blocking work in a [`WorkCategory`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct WorkUtilization {
+    /// The number of operations that have been admitted and haven't completed.
+    pub in_flight: usize,
+    /// The number of operations at which new low-priority operations are
+    /// rejected. See [`Admission`].
+    pub limit: Option<usize>,
+    /// The number of operations that have been rejected since the storage was
+    /// opened.
+    pub rejected: u64,
+}
+
+/// The utilization of each [`WorkCategory`]. Returned from
+/// [`Storage::admission_statistics()`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct AdmissionStatistics {
+    /// The utilization of [`WorkCategory::KeyValue`].
+    pub key_value: WorkUtilization,
+    /// The utilization of [`WorkCategory::Views`].
+    pub views: WorkUtilization,
+    /// The utilization of [`WorkCategory::Documents`].
+    pub documents: WorkUtilization,
+    /// The utilization of [`WorkCategory::Maintenance`].
+    pub maintenance: WorkUtilization,
+}
+
+impl AdmissionStatistics {
+    /// Returns the utilization of `category`.
+    #[must_use]
+    pub const fn category(&self, category: WorkCategory) -> &WorkUtilization {
+        match category {
+            WorkCategory::KeyValue => &self.key_value,
+            WorkCategory::Views => &self.views,
+            WorkCategory::Documents => &self.documents,
+            WorkCategory::Maintenance => &self.maintenance,
+        }
+    }
+}
+
+impl Storage {
+    /// Returns the number of operations running in each [`WorkCategory`],
+    /// along with the configured limits and how many operations have been
+    /// rejected. See [`Admission`] for more information.
+    #[must_use]
+    pub fn admission_statistics(&self) -> AdmissionStatistics {
+        self.instance.data.admission.statistics()
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn admission(&self) -> &Arc<AdmissionController> {
+        &self.instance.data.admission
+    }
+}
+
+/// Whether an operation can be rejected when its category is at its limit.
+#[cfg(feature = "async")]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Priority {
+    /// The operation is always admitted.
+    High,
+    /// The operation is rejected if its category is at its limit.
+    Low,
+}
+
+/// Tracks the blocking work running in each [`WorkCategory`], rejecting
+/// low-priority work once a category's limit is reached.
+#[derive(Debug)]
+pub(crate) struct AdmissionController {
+    limits: Admission,
+    categories: [CategoryState; 4],
+}
+
+#[derive(Debug, Default)]
+struct CategoryState {
+    in_flight: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+impl AdmissionController {
+    pub fn new(limits: Admission) -> Arc<Self> {
+        Arc::new(Self {
+            limits,
+            categories: Default::default(),
+        })
+    }
+
+    /// Admits an operation in `category`, returning a permit that counts the
+    /// operation as in-flight until it is dropped.
+    #[cfg(feature = "async")]
+    pub fn admit(
+        self: &Arc<Self>,
+        category: WorkCategory,
+        priority: Priority,
+    ) -> Result<AdmissionPermit, bonsaidb_core::Error> {
+        let state = &self.categories[category as usize];
+        let in_flight = state.in_flight.fetch_add(1, Ordering::AcqRel);
+        if priority == Priority::Low {
+            if let Some(limit) = self.limits.limit(category) {
+                if in_flight >= limit {
+                    state.in_flight.fetch_sub(1, Ordering::AcqRel);
+                    state.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(bonsaidb_core::Error::Overloaded {
+                        retry_after_hint: self.limits.retry_after_hint,
+                    });
+                }
+            }
+        }
+
+        Ok(AdmissionPermit {
+            controller: self.clone(),
+            category,
+        })
+    }
+
+    fn statistics(&self) -> AdmissionStatistics {
+        let utilization = |category: WorkCategory| {
+            let state = &self.categories[category as usize];
+            WorkUtilization {
+                in_flight: state.in_flight.load(Ordering::Acquire),
+                limit: self.limits.limit(category),
+                rejected: state.rejected.load(Ordering::Relaxed),
+            }
+        };
+        AdmissionStatistics {
+            key_value: utilization(WorkCategory::KeyValue),
+            views: utilization(WorkCategory::Views),
+            documents: utilization(WorkCategory::Documents),
+            maintenance: utilization(WorkCategory::Maintenance),
+        }
+    }
+}
+
+/// An admitted operation. The operation stops being counted when this is
+/// dropped.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub(crate) struct AdmissionPermit {
+    controller: Arc<AdmissionController>,
+    category: WorkCategory,
+}
+
+#[cfg(feature = "async")]
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        self.controller.categories[self.category as usize]
+            .in_flight
+            .fetch_sub(1, Ordering::AcqRel);
+    }
+}
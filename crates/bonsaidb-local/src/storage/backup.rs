@@ -1,17 +1,38 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs::DirEntry;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 
-use bonsaidb_core::connection::{LowLevelConnection, Range, Sort, StorageConnection};
+use bonsaidb_core::admin::{AuthenticationToken, PermissionGroup, Role, User};
+use bonsaidb_core::connection::{
+    Connection, IdentityId, LowLevelConnection, Range, Sort, StorageConnection,
+};
 use bonsaidb_core::document::DocumentId;
-use bonsaidb_core::schema::{Collection, Qualified, SchemaName};
+use bonsaidb_core::schema::{
+    Collection, CollectionName, DocumentFormat, NamedCollection, Qualified, SchemaName,
+    SerializedCollection,
+};
 use bonsaidb_core::transaction::{Operation, Transaction};
 use bonsaidb_core::{admin, AnyError};
 
+use crate::config::Compression;
 use crate::database::keyvalue::Entry;
 use crate::database::DatabaseNonBlocking;
+use crate::files::{atomic_write, FileDurability};
 use crate::{Database, Error, Storage};
 
+/// The container that backup metadata is stored in, alongside each database's
+/// collections.
+const MANIFEST_CONTAINER: &str = "_backup";
+/// The name of the manifest object recording the compression used. When this
+/// object is present, every exported object is prefixed with a header.
+const COMPRESSION_MANIFEST: &str = "compression";
+/// Objects smaller than this are stored uncompressed, matching the threshold
+/// used when compressing trees.
+#[cfg(feature = "compression")]
+const COMPRESSION_THRESHOLD: usize = 128;
+
 /// A location to store and restore a database from.
 pub trait BackupLocation: Send + Sync {
     /// The error type for the backup location.
@@ -49,11 +70,56 @@ pub trait BackupLocation: Send + Sync {
         container: &str,
         name: &str,
     ) -> Result<Vec<u8>, Self::Error>;
+
+    /// Converts an error returned by this location into an [`Error`]. By
+    /// default, the error is wrapped in [`Error::Backup`].
+    ///
+    /// Locations whose error type is [`Error`] can return the error
+    /// unchanged, which allows them to return [custom errors](Error::custom).
+    fn convert_error(error: Self::Error) -> Error
+    where
+        Self: Sized,
+    {
+        Error::Backup(Box::new(error))
+    }
+}
+
+/// Controls how [`Storage::import_admin()`] handles documents that already
+/// exist.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AdminImportMode {
+    /// Existing documents are left unchanged.
+    SkipExisting,
+    /// Existing documents are replaced with the imported documents.
+    OverwriteExisting,
 }
 
 impl Storage {
     /// Stores a copy of all data in this instance to `location`.
     pub fn backup<L: AnyBackupLocation>(&self, location: &L) -> Result<(), Error> {
+        self.backup_with_compression(location, None)
+    }
+
+    /// Stores a copy of all data in this instance to `location`, compressing
+    /// each exported object larger than 128 bytes using `compression`.
+    ///
+    /// The compression used is recorded alongside the backup, allowing
+    /// [`Storage::restore()`] to decompress the objects. This is independent
+    /// of the compression configured for the live storage.
+    #[cfg(feature = "compression")]
+    pub fn backup_compressed<L: AnyBackupLocation>(
+        &self,
+        location: &L,
+        compression: Compression,
+    ) -> Result<(), Error> {
+        self.backup_with_compression(location, Some(compression))
+    }
+
+    fn backup_with_compression(
+        &self,
+        location: &dyn AnyBackupLocation,
+        compression: Option<Compression>,
+    ) -> Result<(), Error> {
         let databases = {
             self.instance
                 .data
@@ -68,7 +134,7 @@ impl Storage {
             let database = self
                 .instance
                 .database_without_schema(&name, Some(self), None)?;
-            Self::backup_database(&database, location)?;
+            Self::backup_database(&database, location, compression)?;
         }
 
         Ok(())
@@ -76,14 +142,9 @@ impl Storage {
 
     /// Restores all data from a previously stored backup `location`.
     pub fn restore<L: AnyBackupLocation>(&self, location: &L) -> Result<(), Error> {
-        for schema in location
-            .list_schemas()
-            .map_err(|err| Error::Backup(Box::new(err)))?
-        {
-            for database in location
-                .list_databases(&schema)
-                .map_err(|err| Error::Backup(Box::new(err)))?
-            {
+        self.check_writable()?;
+        for schema in location.list_schemas()? {
+            for database in location.list_databases(&schema)? {
                 // The admin database is already going to be created by the process of creating a database.
                 self.create_database_with_schema(&database, schema.clone(), true)?;
 
@@ -97,11 +158,129 @@ impl Storage {
         Ok(())
     }
 
+    /// Stores a copy of the admin database to `location`. This includes all
+    /// users, permission groups, roles, authentication tokens, and database
+    /// records, but none of the data stored in the databases themselves.
+    ///
+    /// The export is stored in the same format as [`Storage::backup()`], and
+    /// can be loaded into another instance using [`Storage::import_admin()`].
+    pub fn export_admin<L: AnyBackupLocation>(&self, location: &L) -> Result<(), Error> {
+        let admin =
+            self.instance
+                .database_without_schema(admin::ADMIN_DATABASE_NAME, Some(self), None)?;
+        Self::backup_database(&admin, location, None)
+    }
+
+    /// Loads the admin database previously exported to `location` using
+    /// [`Storage::export_admin()`] or [`Storage::backup()`].
+    ///
+    /// Users, permission groups, and roles are matched by their unique names,
+    /// databases by their names, and authentication tokens by their tokens.
+    /// `mode` controls whether documents that already exist are skipped or
+    /// overwritten. Imported documents keep their ids when those ids are
+    /// available, and are otherwise assigned new ids. References between
+    /// users, permission groups, roles, and tokens are updated to the ids the
+    /// referenced documents are imported as, and references to documents
+    /// missing from the export are removed.
+    ///
+    /// Databases that do not exist are created empty. The quotas of existing
+    /// databases are only updated when overwriting. All other documents are
+    /// imported in a single transaction.
+    pub fn import_admin<L: AnyBackupLocation>(
+        &self,
+        location: &L,
+        mode: AdminImportMode,
+    ) -> Result<(), Error> {
+        let admin =
+            self.instance
+                .database_without_schema(admin::ADMIN_DATABASE_NAME, Some(self), None)?;
+        let schema = admin.schematic().name.clone();
+        let encoded = is_encoded(location, &schema, admin.name())?;
+        let exported = |collection: CollectionName| {
+            load_documents(
+                location,
+                &schema,
+                admin.name(),
+                &collection.encoded(),
+                encoded,
+            )
+        };
+
+        for (_, contents) in exported(admin::Database::collection_name())? {
+            // Database records are created through the storage so that the
+            // database itself is created.
+            let database = admin::Database::deserialize(&contents)?;
+            let exists = self
+                .instance
+                .data
+                .available_databases
+                .read()
+                .contains_key(&database.name);
+            if !exists {
+                self.create_database_with_schema(&database.name, database.schema, true)?;
+            }
+            if !exists || mode == AdminImportMode::OverwriteExisting {
+                self.set_database_quota(&database.name, database.quota)?;
+            }
+        }
+
+        let (mut groups, group_ids) = plan_named_import::<PermissionGroup>(
+            &admin,
+            exported(PermissionGroup::collection_name())?,
+            |group| &group.name,
+        )?;
+        for group in &mut groups {
+            remap_ids(&mut group.contents.inherits, &group_ids);
+        }
+        let (mut roles, role_ids) =
+            plan_named_import::<Role>(&admin, exported(Role::collection_name())?, |role| {
+                &role.name
+            })?;
+        for role in &mut roles {
+            remap_ids(&mut role.contents.groups, &group_ids);
+        }
+        let (mut users, user_ids) =
+            plan_named_import::<User>(&admin, exported(User::collection_name())?, |user| {
+                &user.username
+            })?;
+        for user in &mut users {
+            remap_ids(&mut user.contents.groups, &group_ids);
+            remap_ids(&mut user.contents.roles, &role_ids);
+        }
+        let tokens = plan_token_import(
+            &admin,
+            exported(AuthenticationToken::collection_name())?,
+            &user_ids,
+            &role_ids,
+        )?;
+
+        let mut transaction = Transaction::new();
+        push_imported(&mut transaction, groups, mode)?;
+        push_imported(&mut transaction, roles, mode)?;
+        push_imported(&mut transaction, users, mode)?;
+        push_imported(&mut transaction, tokens, mode)?;
+        if !transaction.operations.is_empty() {
+            admin.apply_transaction(transaction)?;
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn backup_database(
         database: &Database,
         location: &dyn AnyBackupLocation,
+        compression: Option<Compression>,
     ) -> Result<(), Error> {
         let schema = database.schematic().name.clone();
+        if let Some(compression) = compression {
+            location.store(
+                &schema,
+                database.name(),
+                MANIFEST_CONTAINER,
+                COMPRESSION_MANIFEST,
+                &[compression as u8],
+            )?;
+        }
         for collection in database.schematic().collections() {
             let documents = database.list_from_collection(
                 Range::from(..),
@@ -112,12 +291,24 @@ impl Storage {
             let collection_name = collection.encoded();
             // TODO consider how to best parallelize -- perhaps a location can opt into parallelization?
             for document in documents {
+                // Documents stored using a DocumentFormat are stored without
+                // their header, so that other tools can read them.
+                let (container, object) = match DocumentFormat::split_stored(&document.contents) {
+                    Some((format, payload)) => (
+                        Cow::Owned(format_container(&collection_name, format)),
+                        payload,
+                    ),
+                    None => (
+                        Cow::Borrowed(collection_name.as_str()),
+                        &document.contents[..],
+                    ),
+                };
                 location.store(
                     &schema,
                     database.name(),
-                    &collection_name,
+                    &container,
                     &document.header.id.to_string(),
-                    &document.contents,
+                    &encode_object(object, compression),
                 )?;
             }
             for ((namespace, key), entry) in database.all_key_value_entries()? {
@@ -127,7 +318,7 @@ impl Storage {
                     database.name(),
                     "_kv",
                     &full_name,
-                    &pot::to_vec(&entry)?,
+                    &encode_object(&pot::to_vec(&entry)?, compression),
                 )?;
             }
         }
@@ -139,6 +330,7 @@ impl Storage {
         location: &dyn AnyBackupLocation,
     ) -> Result<(), Error> {
         let schema = database.schematic().name.clone();
+        let encoded = is_encoded(location, &schema, database.name())?;
         let mut transaction = Transaction::new();
         // Restore all the collections. However, there's one collection we don't
         // want to restore: the Databases list. This will be recreated during
@@ -149,27 +341,24 @@ impl Storage {
             .collections()
             .filter(|c| *c != &database_collection)
         {
-            let collection_name = collection.encoded();
-            for (id, id_string) in location
-                .list_stored(&schema, database.name(), &collection_name)?
-                .into_iter()
-                .filter_map(|id_string| {
-                    id_string
-                        .parse::<DocumentId>()
-                        .ok()
-                        .map(|id| (id, id_string))
-                })
-            {
-                let contents =
-                    location.load(&schema, database.name(), &collection_name, &id_string)?;
+            for (id, contents) in load_documents(
+                location,
+                &schema,
+                database.name(),
+                &collection.encoded(),
+                encoded,
+            )? {
                 transaction.push(Operation::insert(collection.clone(), Some(id), contents));
             }
         }
-        database.apply_transaction(transaction)?;
+        database.apply_transaction_bypassing_read_only(transaction)?;
 
         for full_key in location.list_stored(&schema, database.name(), "_kv")? {
             if let Some((namespace, key)) = full_key.split_once("._key._") {
-                let entry = location.load(&schema, database.name(), "_kv", &full_key)?;
+                let entry = decode_object(
+                    location.load(&schema, database.name(), "_kv", &full_key)?,
+                    encoded,
+                )?;
                 let entry = pot::from_slice::<Entry>(&entry)?;
                 let namespace = if namespace.is_empty() {
                     None
@@ -184,6 +373,283 @@ impl Storage {
     }
 }
 
+/// Returns true if the backup of `database_name` in `location` records a
+/// compression algorithm in its manifest.
+fn is_encoded(
+    location: &dyn AnyBackupLocation,
+    schema: &SchemaName,
+    database_name: &str,
+) -> Result<bool, Error> {
+    Ok(location
+        .list_stored(schema, database_name, MANIFEST_CONTAINER)?
+        .iter()
+        .any(|name| name == COMPRESSION_MANIFEST))
+}
+
+/// Returns the name of the container that the documents of `collection`
+/// stored using `format` are backed up in. Encoded collection names contain a
+/// single period, so these names can't conflict with another collection's.
+fn format_container(collection: &str, format: &str) -> String {
+    format!("{collection}.{format}")
+}
+
+/// Loads the documents of `collection` stored in a backup, including the
+/// documents stored in the containers of each [`DocumentFormat`].
+fn load_documents(
+    location: &dyn AnyBackupLocation,
+    schema: &SchemaName,
+    database_name: &str,
+    collection: &str,
+    encoded: bool,
+) -> Result<Vec<(DocumentId, Vec<u8>)>, Error> {
+    let containers = std::iter::once((collection.to_string(), None)).chain(
+        DocumentFormat::stored_format_names()
+            .iter()
+            .map(|format| (format_container(collection, format), Some(*format))),
+    );
+    let mut documents = Vec::new();
+    for (container, format) in containers {
+        for (id, id_string) in stored_documents(location, schema, database_name, &container)? {
+            let mut contents = decode_object(
+                location.load(schema, database_name, &container, &id_string)?,
+                encoded,
+            )?;
+            if let Some(format) = format {
+                contents =
+                    DocumentFormat::join_stored(format, &contents).expect("format names are known");
+            }
+            documents.push((id, contents));
+        }
+    }
+    Ok(documents)
+}
+
+/// A document being imported by [`Storage::import_admin()`].
+struct ImportedDocument<C: SerializedCollection> {
+    /// The id the document is imported as.
+    id: u64,
+    /// True if the document matched an existing document.
+    exists: bool,
+    contents: C::Contents,
+}
+
+/// Assigns the ids of imported documents that don't match an existing
+/// document. Documents keep their exported ids if no other document uses
+/// them.
+#[derive(Default)]
+struct ImportedIds {
+    claimed: HashSet<u64>,
+    next: Option<u64>,
+}
+
+impl ImportedIds {
+    fn assign<C: SerializedCollection<PrimaryKey = u64>>(
+        &mut self,
+        admin: &Database,
+        exported_id: u64,
+    ) -> Result<u64, Error> {
+        if !self.claimed.contains(&exported_id) && C::get(&exported_id, admin)?.is_none() {
+            self.claimed.insert(exported_id);
+            return Ok(exported_id);
+        }
+
+        let mut id = match self.next {
+            Some(next) => next,
+            None => admin.last::<C>()?.map_or(0, |last| last.header.id + 1),
+        };
+        while self.claimed.contains(&id) {
+            id += 1;
+        }
+        self.claimed.insert(id);
+        self.next = Some(id + 1);
+        Ok(id)
+    }
+}
+
+/// Matches each `exported` document with the existing document with the same
+/// `name`, returning the documents and a map from their exported ids to the
+/// ids they are imported as.
+#[allow(clippy::type_complexity)]
+fn plan_named_import<C>(
+    admin: &Database,
+    exported: Vec<(DocumentId, Vec<u8>)>,
+    name: impl Fn(&C::Contents) -> &str,
+) -> Result<(Vec<ImportedDocument<C>>, HashMap<u64, u64>), Error>
+where
+    C: NamedCollection + SerializedCollection<PrimaryKey = u64> + 'static,
+{
+    let mut ids = ImportedIds::default();
+    let mut documents = Vec::with_capacity(exported.len());
+    let mut imported_ids = HashMap::with_capacity(exported.len());
+    for (id, contents) in exported {
+        let exported_id = id.deserialize::<u64>()?;
+        let contents = C::deserialize(&contents)?;
+        let (id, exists) = match C::load(name(&contents), admin)? {
+            Some(existing) => (existing.header.id, true),
+            None => (ids.assign::<C>(admin, exported_id)?, false),
+        };
+        imported_ids.insert(exported_id, id);
+        documents.push(ImportedDocument {
+            id,
+            exists,
+            contents,
+        });
+    }
+    Ok((documents, imported_ids))
+}
+
+/// Matches each exported authentication token with the existing token with
+/// the same value. Tokens whose identity wasn't exported are skipped.
+fn plan_token_import(
+    admin: &Database,
+    exported: Vec<(DocumentId, Vec<u8>)>,
+    user_ids: &HashMap<u64, u64>,
+    role_ids: &HashMap<u64, u64>,
+) -> Result<Vec<ImportedDocument<AuthenticationToken>>, Error> {
+    let existing = AuthenticationToken::all(admin).query()?;
+    let mut ids = ImportedIds::default();
+    let mut documents = Vec::with_capacity(exported.len());
+    for (id, contents) in exported {
+        let exported_id = id.deserialize::<u64>()?;
+        let mut token = AuthenticationToken::deserialize(&contents)?;
+        token.identity = match token.identity {
+            IdentityId::User(id) => match user_ids.get(&id) {
+                Some(id) => IdentityId::User(*id),
+                None => continue,
+            },
+            IdentityId::Role(id) => match role_ids.get(&id) {
+                Some(id) => IdentityId::Role(*id),
+                None => continue,
+            },
+            _ => continue,
+        };
+        let (id, exists) = match existing
+            .iter()
+            .find(|existing| existing.contents.token == token.token)
+        {
+            Some(existing) => (existing.header.id, true),
+            None => (
+                ids.assign::<AuthenticationToken>(admin, exported_id)?,
+                false,
+            ),
+        };
+        documents.push(ImportedDocument {
+            id,
+            exists,
+            contents: token,
+        });
+    }
+    Ok(documents)
+}
+
+/// Replaces each id in `ids` with the id it was imported as, removing ids
+/// that weren't exported.
+fn remap_ids(ids: &mut Vec<u64>, imported_ids: &HashMap<u64, u64>) {
+    ids.retain_mut(|id| match imported_ids.get(id) {
+        Some(imported) => {
+            *id = *imported;
+            true
+        }
+        None => false,
+    });
+}
+
+/// Pushes the operations importing `documents` to `transaction`.
+fn push_imported<C: SerializedCollection<PrimaryKey = u64>>(
+    transaction: &mut Transaction,
+    documents: Vec<ImportedDocument<C>>,
+    mode: AdminImportMode,
+) -> Result<(), Error> {
+    for document in documents {
+        if document.exists && mode == AdminImportMode::SkipExisting {
+            continue;
+        }
+        let id = DocumentId::new(&document.id)?;
+        let contents = C::serialize(&document.contents)?;
+        transaction.push(if document.exists {
+            Operation::overwrite(C::collection_name(), id, contents)
+        } else {
+            Operation::insert(C::collection_name(), Some(id), contents)
+        });
+    }
+    Ok(())
+}
+
+/// Returns the ids of the documents stored in `collection`, along with the
+/// names they are stored under.
+fn stored_documents(
+    location: &dyn AnyBackupLocation,
+    schema: &SchemaName,
+    database_name: &str,
+    collection: &str,
+) -> Result<Vec<(DocumentId, String)>, Error> {
+    Ok(location
+        .list_stored(schema, database_name, collection)?
+        .into_iter()
+        .filter_map(|id_string| {
+            id_string
+                .parse::<DocumentId>()
+                .ok()
+                .map(|id| (id, id_string))
+        })
+        .collect())
+}
+
+/// Encodes `object` for storage in a backup. When `compression` is set, the
+/// object is prefixed with the same `trv` header used by compressed trees. The
+/// header is written even when the object is too small to compress so that
+/// restoring never has to guess whether an object is encoded.
+#[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+fn encode_object(object: &[u8], compression: Option<Compression>) -> Cow<'_, [u8]> {
+    #[cfg(feature = "compression")]
+    if let Some(compression) = compression {
+        let mut encoded = Vec::with_capacity(object.len() + 4);
+        if object.len() >= COMPRESSION_THRESHOLD {
+            encoded.extend_from_slice(&[b't', b'r', b'v', compression as u8]);
+            match compression {
+                Compression::Lz4 => {
+                    encoded.extend(lz4_flex::block::compress_prepend_size(object));
+                }
+            }
+        } else {
+            encoded.extend_from_slice(&[b't', b'r', b'v', 0]);
+            encoded.extend_from_slice(object);
+        }
+        return Cow::Owned(encoded);
+    }
+
+    Cow::Borrowed(object)
+}
+
+/// Decodes an object previously encoded with [`encode_object()`]. `encoded`
+/// should be true if the backup's manifest records a compression algorithm.
+fn decode_object(object: Vec<u8>, encoded: bool) -> Result<Vec<u8>, Error> {
+    if !encoded {
+        return Ok(object);
+    }
+
+    match object.get(0..4) {
+        Some(&[b't', b'r', b'v', 0]) => Ok(object[4..].to_vec()),
+        #[cfg(feature = "compression")]
+        Some(&[b't', b'r', b'v', header]) => match Compression::from_u8(header) {
+            Some(Compression::Lz4) => Ok(lz4_flex::block::decompress_size_prepended(&object[4..])?),
+            None => Err(Error::other(
+                "backup",
+                format!("unknown compression algorithm {header}"),
+            )),
+        },
+        #[cfg(not(feature = "compression"))]
+        Some(&[b't', b'r', b'v', _]) => Err(Error::other(
+            "backup",
+            "backup is compressed, but the compression feature is disabled",
+        )),
+        _ => Err(Error::other(
+            "backup",
+            "backup object is missing its header",
+        )),
+    }
+}
+
 pub trait AnyBackupLocation: Send + Sync {
     fn store(
         &self,
@@ -228,17 +694,15 @@ where
         object: &[u8],
     ) -> Result<(), Error> {
         self.store(schema, database_name, container, name, object)
-            .map_err(|err| Error::Backup(Box::new(err)))
+            .map_err(L::convert_error)
     }
 
     fn list_schemas(&self) -> Result<Vec<SchemaName>, Error> {
-        self.list_schemas()
-            .map_err(|err| Error::Backup(Box::new(err)))
+        self.list_schemas().map_err(L::convert_error)
     }
 
     fn list_databases(&self, schema: &SchemaName) -> Result<Vec<String>, Error> {
-        self.list_databases(schema)
-            .map_err(|err| Error::Backup(Box::new(err)))
+        self.list_databases(schema).map_err(L::convert_error)
     }
 
     fn list_stored(
@@ -248,7 +712,7 @@ where
         container: &str,
     ) -> Result<Vec<String>, Error> {
         self.list_stored(schema, database_name, container)
-            .map_err(|err| Error::Backup(Box::new(err)))
+            .map_err(L::convert_error)
     }
 
     fn load(
@@ -259,7 +723,7 @@ where
         name: &str,
     ) -> Result<Vec<u8>, Error> {
         self.load(schema, database_name, container, name)
-            .map_err(|err| Error::Backup(Box::new(err)))
+            .map_err(L::convert_error)
     }
 }
 
@@ -276,7 +740,17 @@ impl BackupLocation for Path {
     ) -> Result<(), Self::Error> {
         let container_folder = container_folder(self, schema, database_name, container);
         std::fs::create_dir_all(&container_folder)?;
-        std::fs::write(container_folder.join(name), object)?;
+        if container == MANIFEST_CONTAINER {
+            // A partially written manifest would make the entire backup
+            // unreadable.
+            atomic_write(
+                &container_folder.join(name),
+                object,
+                FileDurability::Durable,
+            )?;
+        } else {
+            std::fs::write(container_folder.join(name), object)?;
+        }
 
         Ok(())
     }
@@ -496,4 +970,263 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn backup_restore_native_format() -> anyhow::Result<()> {
+        use bonsaidb_core::connection::HasSchema;
+        use bonsaidb_core::schema::Collection;
+        use serde::{Deserialize, Serialize};
+
+        use crate::BackupLocation;
+
+        #[derive(Collection, Debug, Serialize, Deserialize)]
+        #[collection(name = "formatted", format = "pot", core = bonsaidb_core)]
+        struct Formatted {
+            value: String,
+        }
+
+        let backup_destination = TestDirectory::new("backup-restore-format.bonsaidb.backup");
+        let (header, schema) = {
+            let database_directory = TestDirectory::new("backup-restore-format.bonsaidb");
+            let storage = Storage::open(
+                StorageConfiguration::new(&database_directory).with_schema::<Formatted>()?,
+            )?;
+            let db = storage.create_database::<Formatted>("formatted", false)?;
+            let header = Formatted {
+                value: String::from("native"),
+            }
+            .push_into(&db)?
+            .header;
+            storage.backup(&backup_destination.0)?;
+            (header, db.schematic().name.clone())
+        };
+
+        // The document is stored as a plain Pot payload, without the header
+        // recording its format.
+        let container = format!("{}.pot", Formatted::collection_name().encoded());
+        let exported = BackupLocation::load(
+            &backup_destination.0,
+            &schema,
+            "formatted",
+            &container,
+            &bonsaidb_core::document::DocumentId::new(&header.id)?.to_string(),
+        )?;
+        assert!(exported.starts_with(b"Pot"));
+
+        let database_directory = TestDirectory::new("backup-restore-format.bonsaidb");
+        let restored_storage = Storage::open(
+            StorageConfiguration::new(&database_directory).with_schema::<Formatted>()?,
+        )?;
+        restored_storage.restore(&backup_destination.0)?;
+        let db = restored_storage.database::<Formatted>("formatted")?;
+        let doc = Formatted::get(&header.id, &db)?.expect("document not restored");
+        assert_eq!(doc.contents.value, "native");
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn backup_restore_compressed() -> anyhow::Result<()> {
+        use bonsaidb_core::connection::HasSchema;
+        use bonsaidb_core::schema::Collection;
+
+        use crate::config::Compression;
+        use crate::BackupLocation;
+
+        let backup_destination = TestDirectory::new("backup-restore-compressed.bonsaidb.backup");
+        // Long enough to be compressed, while the short key-value entries
+        // below stay under the threshold.
+        let large_value = "compressible ".repeat(64);
+
+        let (test_doc, schema) = {
+            let database_directory = TestDirectory::new("backup-restore-compressed.bonsaidb");
+            let storage = Storage::open(
+                StorageConfiguration::new(&database_directory).with_schema::<Basic>()?,
+            )?;
+
+            let db = storage.create_database::<Basic>("basic", false)?;
+            let test_doc = db.collection::<Basic>().push(&Basic::new(&large_value))?;
+            db.set_key("large", &large_value).execute()?;
+            db.set_numeric_key("small", 1_u64).execute()?;
+
+            storage.backup_compressed(&backup_destination.0, Compression::Lz4)?;
+
+            (test_doc, db.schematic().name.clone())
+        };
+
+        // The exported document should have been stored compressed.
+        let exported = BackupLocation::load(
+            &backup_destination.0,
+            &schema,
+            "basic",
+            &Basic::collection_name().encoded(),
+            &test_doc.id.to_string(),
+        )?;
+        assert_eq!(&exported[0..4], &[b't', b'r', b'v', Compression::Lz4 as u8]);
+        assert!(exported.len() < large_value.len());
+
+        let database_directory = TestDirectory::new("backup-restore-compressed.bonsaidb");
+        let restored_storage =
+            Storage::open(StorageConfiguration::new(&database_directory).with_schema::<Basic>()?)?;
+        restored_storage.restore(&backup_destination.0)?;
+
+        let db = restored_storage.database::<Basic>("basic")?;
+        let doc = Basic::get(&test_doc.id, &db)?.expect("Backed up document.not found");
+        assert_eq!(doc.contents.value, large_value);
+        assert_eq!(
+            db.get_key("large").into::<String>()?.as_deref(),
+            Some(large_value.as_str())
+        );
+        assert_eq!(db.get_key("small").into_u64()?, Some(1));
+
+        Ok(())
+    }
+    #[test]
+    fn backup_location_custom_error() -> anyhow::Result<()> {
+        use bonsaidb_core::schema::SchemaName;
+
+        use super::BackupLocation;
+        use crate::Error;
+
+        #[derive(thiserror::Error, Debug, Eq, PartialEq)]
+        #[error("quota exceeded")]
+        struct QuotaExceeded;
+
+        /// A location that doesn't allow storing anything.
+        struct Full;
+
+        impl BackupLocation for Full {
+            type Error = Error;
+
+            fn store(
+                &self,
+                _schema: &SchemaName,
+                _database_name: &str,
+                _container: &str,
+                _name: &str,
+                _object: &[u8],
+            ) -> Result<(), Self::Error> {
+                Err(Error::custom(QuotaExceeded))
+            }
+
+            fn list_schemas(&self) -> Result<Vec<SchemaName>, Self::Error> {
+                Ok(Vec::new())
+            }
+
+            fn list_databases(&self, _schema: &SchemaName) -> Result<Vec<String>, Self::Error> {
+                Ok(Vec::new())
+            }
+
+            fn list_stored(
+                &self,
+                _schema: &SchemaName,
+                _database_name: &str,
+                _container: &str,
+            ) -> Result<Vec<String>, Self::Error> {
+                Ok(Vec::new())
+            }
+
+            fn load(
+                &self,
+                _schema: &SchemaName,
+                _database_name: &str,
+                _container: &str,
+                _name: &str,
+            ) -> Result<Vec<u8>, Self::Error> {
+                Err(Error::custom(QuotaExceeded))
+            }
+
+            fn convert_error(error: Self::Error) -> Error {
+                error
+            }
+        }
+
+        let database_directory = TestDirectory::new("backup-custom-error.bonsaidb");
+        let storage =
+            Storage::open(StorageConfiguration::new(&database_directory).with_schema::<Basic>()?)?;
+        let db = storage.create_database::<Basic>("basic", false)?;
+        db.collection::<Basic>().push(&Basic::new("somevalue"))?;
+
+        let err = storage.backup(&Full).unwrap_err();
+        assert!(matches!(err, Error::Custom(_)));
+        assert_eq!(err.custom_error::<QuotaExceeded>(), Some(&QuotaExceeded));
+        assert!(matches!(
+            bonsaidb_core::Error::from(err),
+            bonsaidb_core::Error::Other { origin, error }
+                if origin == "custom" && error == "quota exceeded"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn admin_export_import() -> anyhow::Result<()> {
+        use bonsaidb_core::admin::{PermissionGroup, Role, User};
+        use bonsaidb_core::permissions::bonsai::{BonsaiAction, ServerAction};
+        use bonsaidb_core::permissions::Statement;
+        use bonsaidb_core::schema::NamedCollection;
+
+        use crate::AdminImportMode;
+
+        let export_destination = TestDirectory::new("admin-export.bonsaidb.backup");
+        let role_id = {
+            let database_directory = TestDirectory::new("admin-export.bonsaidb");
+            let storage = Storage::open(
+                StorageConfiguration::new(&database_directory).with_schema::<Basic>()?,
+            )?;
+            let db = storage.create_database::<Basic>("basic", false)?;
+            db.collection::<Basic>().push(&Basic::new("somevalue"))?;
+            storage.create_user("ecton")?;
+            let group_id = storage.create_permission_group("writers", Vec::new())?;
+            let role_id = storage.create_role("writer", vec![group_id])?;
+            storage.add_role_to_user("ecton", role_id)?;
+
+            storage.export_admin(&export_destination.0)?;
+            role_id
+        };
+
+        // Import into a new storage that has a conflicting permission group,
+        // and whose other documents use the exported ids.
+        let database_directory = TestDirectory::new("admin-export.bonsaidb");
+        let storage =
+            Storage::open(StorageConfiguration::new(&database_directory).with_schema::<Basic>()?)?;
+        storage.create_user("someone")?;
+        storage.create_permission_group("others", Vec::new())?;
+        let writers_id =
+            storage.create_permission_group(
+                "writers",
+                vec![Statement::for_any()
+                    .allowing(&BonsaiAction::Server(ServerAction::CreateDatabase))],
+            )?;
+        let other_role_id = storage.create_role("other", Vec::new())?;
+        assert_eq!(other_role_id, role_id);
+        storage.import_admin(&export_destination.0, AdminImportMode::SkipExisting)?;
+
+        let admin = storage.admin();
+        let role = Role::load("writer", &admin)?.expect("role not imported");
+        assert_ne!(role.header.id, role_id);
+        assert_eq!(role.contents.groups, vec![writers_id]);
+        let user = User::load("ecton", &admin)?.expect("user not imported");
+        assert_eq!(user.contents.roles, vec![role.header.id]);
+        let group = PermissionGroup::load("writers", &admin)?.expect("group missing");
+        assert_eq!(group.contents.statements.len(), 1);
+        // Databases are created, but their contents aren't exported.
+        let db = storage.database::<Basic>("basic")?;
+        assert_eq!(Basic::all(&db).count()?, 0);
+
+        // Importing again skips everything that exists.
+        storage.import_admin(&export_destination.0, AdminImportMode::SkipExisting)?;
+        let group = PermissionGroup::load("writers", &admin)?.expect("group missing");
+        assert_eq!(group.contents.statements.len(), 1);
+
+        storage.import_admin(&export_destination.0, AdminImportMode::OverwriteExisting)?;
+        let group = PermissionGroup::load("writers", &admin)?.expect("group missing");
+        assert!(group.contents.statements.is_empty());
+        assert_eq!(User::all(&admin).count()?, 2);
+        let role = Role::load("writer", &admin)?.expect("role missing");
+        assert_eq!(role.contents.groups, vec![writers_id]);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use bonsaidb_core::permissions::Permissions;
+
+/// Verifies the credentials of
+/// [`Identity::External`](bonsaidb_core::connection::Identity::External)
+/// identities as they are assumed, and determines their permissions.
+///
+/// BonsaiDb doesn't understand external credentials itself. An identity is
+/// assumed using
+/// [`IdentityReference::external()`](bonsaidb_core::connection::IdentityReference::external)
+/// with an opaque credential issued by the provider, such as an OpenID Connect
+/// ID token, and the resolver verifies it. The session assuming the identity
+/// must also be permitted
+/// [`ServerAction::AssumeIdentity`](bonsaidb_core::permissions::bonsai::ServerAction::AssumeIdentity)
+/// on
+/// [`external_identity_resource_name(provider)`](bonsaidb_core::permissions::bonsai::external_identity_resource_name).
+///
+/// The permissions returned are merged with the storage's authenticated
+/// permissions, and the claims are stored in the resulting session where they
+/// can be read using
+/// [`Session::claims()`](bonsaidb_core::connection::Session::claims).
+pub trait ExternalIdentityResolver: Debug + Send + Sync + 'static {
+    /// Verifies that `credential` was issued by `provider`, returning the
+    /// identity it was issued to. Returning an error prevents the identity
+    /// from being assumed. Credentials that can't be verified and providers
+    /// that aren't recognized should return
+    /// [`Error::InvalidCredentials`](bonsaidb_core::Error::InvalidCredentials).
+    fn resolve(
+        &self,
+        provider: &str,
+        credential: &[u8],
+    ) -> Result<ResolvedIdentity, bonsaidb_core::Error>;
+}
+
+/// An external identity whose credential was verified by an
+/// [`ExternalIdentityResolver`].
+#[derive(Debug, Clone)]
+pub struct ResolvedIdentity {
+    /// The identifier of the identity, unique within its provider.
+    pub subject: String,
+    /// The claims the provider made about the identity.
+    pub claims: BTreeMap<String, String>,
+    /// The permissions granted to the identity.
+    pub permissions: Permissions,
+}
@@ -0,0 +1,106 @@
+use std::borrow::Borrow;
+
+use bonsaidb_core::connection::{
+    AccessPolicy, LowLevelConnection, QueryKey, Sort, StorageConnection,
+};
+use bonsaidb_core::key::{self, ByteSource, KeyEncoding};
+use bonsaidb_core::schema::view::map::{self, CollectionMap};
+use bonsaidb_core::schema::{view, Collection, Schema, SerializedView};
+use easy_parallel::Parallel;
+
+use crate::Storage;
+
+/// A mapping returned from [`Storage::federated_query()`].
+pub struct FederatedMapping<V: SerializedView> {
+    /// The name of the database the mapping was returned from.
+    pub database: String,
+    /// The mapping returned from the view.
+    pub mapping: CollectionMap<<V::Collection as Collection>::PrimaryKey, V::Key, V::Value>,
+}
+
+impl Storage {
+    /// Queries the view `V` in every database using the schema `DB`, returning
+    /// the combined results. Each mapping is tagged with the name of the
+    /// database that produced it.
+    ///
+    /// `key`, `order`, `limit`, and `access_policy` are applied to each
+    /// database's query independently. The results are ordered by database
+    /// name, and each database's mappings keep their order from the query.
+    /// Up to [`Tasks::parallelization`](crate::config::Tasks::parallelization)
+    /// databases are queried at the same time.
+    ///
+    /// Each database is queried separately, so there is no consistency across
+    /// databases: changes made while this function is running may be visible
+    /// in some databases' results and not in others.
+    pub fn federated_query<DB: Schema, V: SerializedView, Key>(
+        &self,
+        key: Option<QueryKey<'_, V::Key, Key>>,
+        order: Sort,
+        limit: Option<u32>,
+        access_policy: AccessPolicy,
+    ) -> Result<Vec<FederatedMapping<V>>, bonsaidb_core::Error>
+    where
+        Key: KeyEncoding<V::Key> + PartialEq + ?Sized,
+        V::Key: Borrow<Key> + PartialEq<Key>,
+    {
+        let key = key.map(|key| key.serialized()).transpose()?;
+        let databases = self.databases_using_schema(&DB::schema_name())?;
+        let workers = self.parallelization().clamp(1, databases.len().max(1));
+
+        let (database_sender, database_receiver) = flume::unbounded();
+        for database in databases {
+            drop(database_sender.send(database));
+        }
+        drop(database_sender);
+
+        let mut results = Vec::new();
+        for result in Parallel::new()
+            .each(1..=workers, |_| -> Result<_, bonsaidb_core::Error> {
+                let mut results = Vec::new();
+                while let Ok(name) = database_receiver.recv() {
+                    let database = self.database::<DB>(&name)?;
+                    let view = database.schematic().view::<V>()?;
+                    let mappings = database.query_by_name(
+                        &view.view_name(),
+                        key.clone(),
+                        order,
+                        limit,
+                        access_policy,
+                    )?;
+                    results.push((name, mappings));
+                }
+                Ok(results)
+            })
+            .run()
+        {
+            results.extend(result?);
+        }
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut federated = Vec::new();
+        for (database, mappings) in results {
+            for mapping in mappings {
+                federated.push(FederatedMapping {
+                    database: database.clone(),
+                    mapping: deserialize_mapping::<V>(mapping)?,
+                });
+            }
+        }
+        Ok(federated)
+    }
+}
+
+fn deserialize_mapping<V: SerializedView>(
+    mapping: map::Serialized,
+) -> Result<
+    CollectionMap<<V::Collection as Collection>::PrimaryKey, V::Key, V::Value>,
+    bonsaidb_core::Error,
+> {
+    Ok(CollectionMap {
+        key: <V::Key as key::Key>::from_ord_bytes(ByteSource::Borrowed(&mapping.key))
+            .map_err(view::Error::key_serialization)
+            .map_err(bonsaidb_core::Error::from)?,
+        value: V::deserialize(&mapping.value)?,
+        source: mapping.source.try_into()?,
+    })
+}
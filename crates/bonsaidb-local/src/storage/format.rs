@@ -0,0 +1,239 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use bonsaidb_core::admin::ADMIN_DATABASE_NAME;
+use bonsaidb_core::connection::StorageConnection;
+
+use crate::config::{Builder, StorageConfiguration};
+use crate::files::{atomic_write, FileDurability};
+use crate::{Database, Error, Storage};
+
+/// The version of the on-disk format written by this version of BonsaiDb.
+///
+/// The version is stored as ascii decimal in the `format-version` file at the
+/// root of the storage's directory. It is incremented whenever the layout of
+/// the trees, the header of encrypted or compressed blocks, or the admin
+/// schema change in a way that requires existing data to be migrated. Storage
+/// using a newer format than this is refused by [`Storage::open()`], and
+/// storage using an older format can be migrated using [`Storage::upgrade()`].
+pub const STORAGE_FORMAT_VERSION: u32 = 2;
+
+/// The format of storage created before the format version was recorded.
+const LEGACY_FORMAT_VERSION: u32 = 0;
+
+const FORMAT_VERSION_FILE: &str = "format-version";
+
+/// Records the databases that a partially applied migration has finished
+/// migrating. The first line contains the version of the migration, and each
+/// following line names a migrated database.
+const UPGRADE_PROGRESS_FILE: &str = "format-upgrade";
+
+/// The maximum number of entries a migration reads into memory at once.
+pub(crate) const MIGRATION_BATCH_SIZE: usize = 1_000;
+
+/// A migration from the previous format version to `version`.
+struct Migration {
+    version: u32,
+    name: &'static str,
+    migrate: fn(&Database) -> Result<(), Error>,
+}
+
+/// The known migrations, in the order they must be applied. Each migration
+/// must be safe to apply again to a database it has already migrated, as an
+/// upgrade interrupted while migrating a database repeats it when resumed.
+///
+/// Migrations read and rewrite at most [`MIGRATION_BATCH_SIZE`] entries at a
+/// time, and each batch is rewritten while holding the locks that prevent the
+/// database from changing the entries being migrated.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "kv full-key re-encoding",
+        migrate: Database::reencode_key_value_keys,
+    },
+    Migration {
+        version: 2,
+        name: "header-versioning",
+        migrate: Database::reencode_legacy_documents,
+    },
+];
+
+/// The result of [`Storage::upgrade()`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StorageUpgrade {
+    /// The format version the storage was using before it was upgraded.
+    pub previous_version: u32,
+    /// The names of the migrations that were applied, in the order they were
+    /// applied.
+    pub migrations: Vec<&'static str>,
+}
+
+impl Storage {
+    /// Upgrades the storage at `path` to [`STORAGE_FORMAT_VERSION`] by
+    /// applying each migration that hasn't been applied yet. The storage must
+    /// not be open while it is being upgraded.
+    ///
+    /// Progress is recorded after each database is migrated, and after each
+    /// migration completes. If the upgrade is interrupted, calling this
+    /// function again resumes the upgrade.
+    ///
+    /// The storage is opened using
+    /// [`StorageConfiguration::new()`](Builder::new). If the storage needs
+    /// additional configuration to be opened, such as its vault key storage or
+    /// compression dictionaries, use [`Storage::upgrade_with_configuration()`].
+    pub fn upgrade<P: AsRef<Path>>(path: P) -> Result<StorageUpgrade, Error> {
+        Self::upgrade_with_configuration(StorageConfiguration::new(path))
+    }
+
+    /// Upgrades the storage opened using `configuration` to
+    /// [`STORAGE_FORMAT_VERSION`]. See [`Storage::upgrade()`].
+    pub fn upgrade_with_configuration(
+        configuration: StorageConfiguration,
+    ) -> Result<StorageUpgrade, Error> {
+        let path = configuration
+            .path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("db.bonsaidb"));
+        let previous_version = match read_format_version(&path)? {
+            Some(version) => version,
+            None if is_existing_storage(&path) => LEGACY_FORMAT_VERSION,
+            None => {
+                return Err(Error::Io(io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("no storage found at {}", path.display()),
+                )))
+            }
+        };
+        check_supported(previous_version)?;
+
+        let mut upgrade = StorageUpgrade {
+            previous_version,
+            migrations: Vec::new(),
+        };
+        let mut pending = MIGRATIONS
+            .iter()
+            .filter(|migration| migration.version > previous_version)
+            .peekable();
+        if pending.peek().is_none() {
+            return Ok(upgrade);
+        }
+
+        let storage = Self::open(configuration)?;
+        let mut databases = vec![ADMIN_DATABASE_NAME.to_string()];
+        databases.extend(
+            storage
+                .instance
+                .data
+                .available_databases
+                .read()
+                .keys()
+                .filter(|name| name.as_str() != ADMIN_DATABASE_NAME)
+                .cloned(),
+        );
+        for migration in pending {
+            let mut migrated = read_upgrade_progress(&path, migration.version)?;
+            for name in &databases {
+                if migrated.contains(name) {
+                    continue;
+                }
+
+                let database = if name == ADMIN_DATABASE_NAME {
+                    storage.admin()
+                } else {
+                    storage
+                        .instance
+                        .database_with_stored_schema(name, Some(&storage))?
+                };
+                (migration.migrate)(&database)?;
+
+                migrated.push(name.clone());
+                let mut progress = migration.version.to_string();
+                for name in &migrated {
+                    progress.push('\n');
+                    progress.push_str(name);
+                }
+                atomic_write(
+                    &path.join(UPGRADE_PROGRESS_FILE),
+                    progress.as_bytes(),
+                    FileDurability::Durable,
+                )?;
+            }
+
+            write_format_version(&path, migration.version)?;
+            fs::remove_file(path.join(UPGRADE_PROGRESS_FILE))?;
+            upgrade.migrations.push(migration.name);
+        }
+
+        Ok(upgrade)
+    }
+}
+
+/// Verifies that the storage at `path` uses a supported format, recording the
+/// current format if the storage is being created.
+pub(crate) fn check_format_version(path: &Path) -> Result<(), Error> {
+    match read_format_version(path)? {
+        Some(version) => check_supported(version),
+        // Storage created before the format version was recorded uses the
+        // legacy format, which is still readable.
+        None if is_existing_storage(path) => Ok(()),
+        None => write_format_version(path, STORAGE_FORMAT_VERSION),
+    }
+}
+
+fn check_supported(version: u32) -> Result<(), Error> {
+    if version > STORAGE_FORMAT_VERSION {
+        Err(Error::NewerStorageFormat {
+            found: version,
+            supported: STORAGE_FORMAT_VERSION,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn is_existing_storage(path: &Path) -> bool {
+    path.join("storage-id").exists() || path.join("server-id").exists()
+}
+
+fn read_format_version(path: &Path) -> Result<Option<u32>, Error> {
+    match fs::read_to_string(path.join(FORMAT_VERSION_FILE)) {
+        Ok(contents) => contents.trim().parse().map(Some).map_err(|_| {
+            Error::Io(io::Error::new(
+                ErrorKind::InvalidData,
+                "format-version contains invalid data",
+            ))
+        }),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+fn write_format_version(path: &Path, version: u32) -> Result<(), Error> {
+    atomic_write(
+        &path.join(FORMAT_VERSION_FILE),
+        version.to_string().as_bytes(),
+        FileDurability::Durable,
+    )?;
+    Ok(())
+}
+
+/// Returns the databases an interrupted upgrade finished migrating to
+/// `version`.
+fn read_upgrade_progress(path: &Path, version: u32) -> Result<Vec<String>, Error> {
+    let contents = match fs::read_to_string(path.join(UPGRADE_PROGRESS_FILE)) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(Error::from(err)),
+    };
+    let mut lines = contents.split('\n');
+    if lines.next() == Some(version.to_string().as_str()) {
+        Ok(lines
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect())
+    } else {
+        // The progress belongs to a migration that has since completed.
+        Ok(Vec::new())
+    }
+}
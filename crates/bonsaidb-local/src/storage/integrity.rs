@@ -0,0 +1,454 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bonsaidb_core::admin::ADMIN_DATABASE_NAME;
+use bonsaidb_core::arc_bytes::OwnedBytes;
+use bonsaidb_core::connection::{HasSession, StorageConnection};
+use bonsaidb_core::document::DocumentId;
+use bonsaidb_core::permissions::bonsai::{bonsaidb_resource_name, BonsaiAction, ServerAction};
+use bonsaidb_core::schema::{CollectionName, ViewName};
+use nebari::io::any::AnyFile;
+use nebari::tree::{Root, ScanEvaluation, TreeRoot, Unversioned, Versioned};
+use nebari::{AbortError, ArcBytes, Tree};
+
+use crate::database::keyvalue::{Entry, KEY_TREE};
+use crate::database::purge::PurgeTrees;
+use crate::database::{deserialize_document, document_tree_name};
+use crate::storage::recovery::quarantine_tree_name;
+use crate::views::{
+    view_dependents_tree_name, view_document_map_tree_name, view_entries_tree_name,
+    view_invalidated_docs_tree_name, view_prefix_reductions_tree_name, view_versions_tree_name,
+    ViewEntry,
+};
+use crate::{Database, Error, Storage};
+
+/// The results of [`Storage::verify_integrity()`].
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    /// The problems found in each database, keyed by database name. Every
+    /// database that was verified is present, even if no problems were found.
+    pub databases: BTreeMap<String, Vec<IntegrityProblem>>,
+}
+
+impl IntegrityReport {
+    /// Returns true if no problems were found in any database.
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.databases.values().all(Vec::is_empty)
+    }
+
+    /// Returns an iterator over each problem found and the name of the
+    /// database it was found in.
+    pub fn problems(&self) -> impl Iterator<Item = (&str, &IntegrityProblem)> {
+        self.databases.iter().flat_map(|(database, problems)| {
+            problems
+                .iter()
+                .map(move |problem| (database.as_str(), problem))
+        })
+    }
+}
+
+/// A problem found by [`Storage::verify_integrity()`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IntegrityProblem {
+    /// A stored document could not be deserialized.
+    CorruptDocument {
+        /// The collection containing the document.
+        collection: CollectionName,
+        /// The id of the document.
+        id: DocumentId,
+    },
+    /// A view's record of the keys a document emitted could not be
+    /// deserialized.
+    CorruptDocumentMap {
+        /// The view containing the record.
+        view: ViewName,
+        /// The id of the document.
+        document: DocumentId,
+    },
+    /// A view entry could not be deserialized.
+    CorruptViewEntry {
+        /// The view containing the entry.
+        view: ViewName,
+        /// The serialized key of the entry.
+        key: Vec<u8>,
+    },
+    /// A view recorded that `document` emitted `key`, but the view's entry for
+    /// `key` does not contain a mapping from `document`.
+    MissingViewMapping {
+        /// The view containing the entry.
+        view: ViewName,
+        /// The id of the document.
+        document: DocumentId,
+        /// The serialized key of the entry.
+        key: Vec<u8>,
+    },
+    /// A view's entry for `key` contains a mapping from `document`, but the
+    /// view did not record that `document` emitted `key`.
+    UnexpectedViewMapping {
+        /// The view containing the entry.
+        view: ViewName,
+        /// The id of the document.
+        document: DocumentId,
+        /// The serialized key of the entry.
+        key: Vec<u8>,
+    },
+    /// A view contains mappings from a document that does not exist.
+    MappedDocumentMissing {
+        /// The view containing the mappings.
+        view: ViewName,
+        /// The id of the missing document.
+        document: DocumentId,
+    },
+    /// A stored key-value entry could not be deserialized.
+    CorruptKeyValueEntry {
+        /// The full key of the entry, including its namespace.
+        key: String,
+    },
+    /// A tree exists that is not used by any collection or view in the
+    /// database's schema.
+    OrphanedTree {
+        /// The name of the tree.
+        name: String,
+    },
+    /// A check could not be completed because an error occurred. The
+    /// remaining checks are still performed.
+    CheckFailed {
+        /// The name of the tree being checked, or `None` if the database
+        /// could not be opened.
+        tree: Option<String>,
+        /// The error that occurred.
+        error: String,
+    },
+}
+
+impl Storage {
+    /// Verifies the integrity of every database in this storage, returning a
+    /// report of the problems found.
+    ///
+    /// For each database, this verifies that:
+    ///
+    /// - every document can be deserialized,
+    /// - each view's entries agree with its record of which keys each document
+    ///   emitted, ignoring documents that are waiting to be mapped,
+    /// - every persisted key-value entry can be deserialized, and
+    /// - every tree is used by the database's schema.
+    ///
+    /// If an error occurs while checking a tree or opening a database, it is
+    /// reported as [`IntegrityProblem::CheckFailed`] and the remaining checks
+    /// are still performed. Checks that depend on a failed check are skipped.
+    ///
+    /// Nothing is modified by this function, and views are not updated before
+    /// being checked. Each tree is read independently, so changes made while
+    /// this function is running may be reported as problems. Key-value changes
+    /// that have not been persisted yet are not verified.
+    ///
+    /// This requires the same permission as
+    /// [`StorageConnection::list_databases()`].
+    pub fn verify_integrity(&self) -> Result<IntegrityReport, bonsaidb_core::Error> {
+        self.check_permission(
+            bonsaidb_resource_name(),
+            &BonsaiAction::Server(ServerAction::ListDatabases),
+        )?;
+
+        let mut report = IntegrityReport::default();
+        report.databases.insert(
+            ADMIN_DATABASE_NAME.to_string(),
+            self.admin().verify_integrity(),
+        );
+
+        let names = self
+            .instance
+            .data
+            .available_databases
+            .read()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in names {
+            let problems = match self
+                .instance
+                .database_without_schema(&name, Some(self), None)
+            {
+                Ok(database) => database.verify_integrity(),
+                Err(err) => vec![IntegrityProblem::CheckFailed {
+                    tree: None,
+                    error: err.to_string(),
+                }],
+            };
+            report.databases.insert(name, problems);
+        }
+
+        Ok(report)
+    }
+}
+
+impl Database {
+    fn verify_integrity(&self) -> Vec<IntegrityProblem> {
+        let mut problems = Vec::new();
+        // Opening a tree creates it if it doesn't exist, so only trees that
+        // already exist are opened.
+        let tree_names = match self.roots().tree_names() {
+            Ok(names) => names.into_iter().collect::<HashSet<_>>(),
+            Err(err) => {
+                problems.push(IntegrityProblem::CheckFailed {
+                    tree: None,
+                    error: Error::from(err).to_string(),
+                });
+                return problems;
+            }
+        };
+        let mut orphaned_trees = tree_names.clone();
+
+        for collection in self.data.schema.collections() {
+            let documents_tree_name = document_tree_name(collection);
+            let documents = if orphaned_trees.remove(&documents_tree_name) {
+                self.verify_documents(collection, documents_tree_name, &mut problems)
+            } else {
+                Some(HashSet::new())
+            };
+            orphaned_trees.remove(&view_versions_tree_name(collection));
+            orphaned_trees.remove(&quarantine_tree_name(collection));
+            let purge_trees = PurgeTrees::new(collection);
+            orphaned_trees.remove(&purge_trees.replacement);
+            orphaned_trees.remove(&purge_trees.marker);
+
+            for view in self.data.schema.views_in_collection(collection) {
+                let view_name = view.view_name();
+                orphaned_trees.remove(&view_invalidated_docs_tree_name(&view_name));
+                orphaned_trees.remove(&view_document_map_tree_name(&view_name));
+                orphaned_trees.remove(&view_entries_tree_name(&view_name));
+                orphaned_trees.remove(&view_prefix_reductions_tree_name(&view_name));
+                orphaned_trees.remove(&view_dependents_tree_name(&view_name));
+                // Views can't be compared against documents that couldn't be
+                // read.
+                if let Some(documents) = &documents {
+                    self.verify_view(
+                        collection,
+                        &view_name,
+                        documents,
+                        &tree_names,
+                        &mut problems,
+                    );
+                }
+            }
+        }
+
+        if orphaned_trees.remove(KEY_TREE) {
+            check_tree(
+                KEY_TREE,
+                Ok(Unversioned::tree(KEY_TREE)),
+                self,
+                &mut problems,
+                |key, entry, problems| {
+                    if bincode::deserialize::<Entry>(entry).is_err() {
+                        problems.push(IntegrityProblem::CorruptKeyValueEntry {
+                            key: String::from_utf8_lossy(key).into_owned(),
+                        });
+                    }
+                    Ok(())
+                },
+            );
+        }
+
+        let mut orphaned_trees = orphaned_trees.into_iter().collect::<Vec<_>>();
+        orphaned_trees.sort();
+        problems.extend(
+            orphaned_trees
+                .into_iter()
+                .map(|name| IntegrityProblem::OrphanedTree { name }),
+        );
+
+        problems
+    }
+
+    /// Verifies that each document in `collection` can be deserialized,
+    /// returning the ids of all documents found, or `None` if the documents
+    /// could not be read.
+    fn verify_documents(
+        &self,
+        collection: &CollectionName,
+        tree_name: String,
+        problems: &mut Vec<IntegrityProblem>,
+    ) -> Option<HashSet<DocumentId>> {
+        let mut ids = HashSet::new();
+        check_tree(
+            &tree_name,
+            self.collection_tree::<Versioned, _>(collection, tree_name.clone()),
+            self,
+            problems,
+            |key, document, problems| {
+                let id = DocumentId::try_from(key.as_slice())?;
+                if deserialize_document(document).is_err() {
+                    problems.push(IntegrityProblem::CorruptDocument {
+                        collection: collection.clone(),
+                        id: id.clone(),
+                    });
+                }
+                ids.insert(id);
+                Ok(())
+            },
+        )
+        .then_some(ids)
+    }
+
+    fn verify_view(
+        &self,
+        collection: &CollectionName,
+        view: &ViewName,
+        documents: &HashSet<DocumentId>,
+        tree_names: &HashSet<String>,
+        problems: &mut Vec<IntegrityProblem>,
+    ) {
+        // Documents that are waiting to be mapped are allowed to disagree with
+        // the view's current contents.
+        let mut invalidated = HashSet::new();
+        let invalidated_tree_name = view_invalidated_docs_tree_name(view);
+        if tree_names.contains(&invalidated_tree_name)
+            && !check_tree(
+                &invalidated_tree_name,
+                self.collection_tree::<Unversioned, _>(collection, invalidated_tree_name.clone()),
+                self,
+                problems,
+                |key, _, _| {
+                    invalidated.insert(DocumentId::try_from(key.as_slice())?);
+                    Ok(())
+                },
+            )
+        {
+            return;
+        }
+
+        // The keys each document emitted, according to the document map.
+        let mut document_keys = HashMap::<DocumentId, HashSet<Vec<u8>>>::new();
+        let document_map_tree_name = view_document_map_tree_name(view);
+        if tree_names.contains(&document_map_tree_name)
+            && !check_tree(
+                &document_map_tree_name,
+                self.collection_tree::<Unversioned, _>(collection, document_map_tree_name.clone()),
+                self,
+                problems,
+                |key, keys, problems| {
+                    let document = DocumentId::try_from(key.as_slice())?;
+                    if invalidated.contains(&document) {
+                        return Ok(());
+                    }
+                    match bincode::deserialize::<HashSet<OwnedBytes>>(keys) {
+                        Ok(keys) => {
+                            if !keys.is_empty() && !documents.contains(&document) {
+                                problems.push(IntegrityProblem::MappedDocumentMissing {
+                                    view: view.clone(),
+                                    document: document.clone(),
+                                });
+                            }
+                            document_keys.insert(
+                                document,
+                                keys.into_iter().map(|key| key.0.to_vec()).collect(),
+                            );
+                        }
+                        Err(_) => problems.push(IntegrityProblem::CorruptDocumentMap {
+                            view: view.clone(),
+                            document,
+                        }),
+                    }
+                    Ok(())
+                },
+            )
+        {
+            return;
+        }
+
+        // Every mapping in the view's entries must be recorded in the document
+        // map. The mappings found are removed from `document_keys`, leaving
+        // only the mappings that are missing from the entries.
+        let entries_tree_name = view_entries_tree_name(view);
+        if tree_names.contains(&entries_tree_name)
+            && !check_tree(
+                &entries_tree_name,
+                self.collection_tree::<Unversioned, _>(collection, entries_tree_name.clone()),
+                self,
+                problems,
+                |key, entry, problems| {
+                    let Ok(entry) = bincode::deserialize::<ViewEntry>(entry) else {
+                        problems.push(IntegrityProblem::CorruptViewEntry {
+                            view: view.clone(),
+                            key: key.to_vec(),
+                        });
+                        return Ok(());
+                    };
+                    for mapping in entry.mappings {
+                        let document = mapping.source.id;
+                        if invalidated.contains(&document) {
+                            continue;
+                        }
+                        let recorded = document_keys
+                            .get_mut(&document)
+                            .map_or(false, |keys| keys.remove(key.as_slice()));
+                        if !recorded {
+                            problems.push(IntegrityProblem::UnexpectedViewMapping {
+                                view: view.clone(),
+                                document,
+                                key: key.to_vec(),
+                            });
+                        }
+                    }
+                    Ok(())
+                },
+            )
+        {
+            return;
+        }
+
+        let mut missing = document_keys
+            .into_iter()
+            .flat_map(|(document, keys)| keys.into_iter().map(move |key| (document.clone(), key)))
+            .collect::<Vec<_>>();
+        missing.sort();
+        problems.extend(missing.into_iter().map(|(document, key)| {
+            IntegrityProblem::MissingViewMapping {
+                view: view.clone(),
+                document,
+                key,
+            }
+        }));
+    }
+}
+
+/// Invokes `callback` with the key and value of each entry in the tree named
+/// `name`. If the tree can't be opened or read, the error is recorded in
+/// `problems` and false is returned.
+fn check_tree<R: Root>(
+    name: &str,
+    tree: Result<TreeRoot<R, AnyFile>, Error>,
+    database: &Database,
+    problems: &mut Vec<IntegrityProblem>,
+    mut callback: impl FnMut(&ArcBytes<'static>, &[u8], &mut Vec<IntegrityProblem>) -> Result<(), Error>,
+) -> bool {
+    let result = tree
+        .and_then(|tree| database.roots().tree(tree).map_err(Error::from))
+        .and_then(|tree| for_each_entry(&tree, |key, value| callback(key, value, problems)));
+    match result {
+        Ok(()) => true,
+        Err(err) => {
+            problems.push(IntegrityProblem::CheckFailed {
+                tree: Some(name.to_string()),
+                error: err.to_string(),
+            });
+            false
+        }
+    }
+}
+
+/// Invokes `callback` with the key and value of each entry in `tree`.
+pub(crate) fn for_each_entry<R: Root>(
+    tree: &Tree<R, AnyFile>,
+    mut callback: impl FnMut(&ArcBytes<'static>, &[u8]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    tree.scan::<Error, _, _, _, _>(
+        &(..),
+        true,
+        |_, _, _| ScanEvaluation::ReadData,
+        |_, _| ScanEvaluation::ReadData,
+        |key, _, value: ArcBytes<'static>| callback(&key, &value).map_err(AbortError::Other),
+    )?;
+    Ok(())
+}
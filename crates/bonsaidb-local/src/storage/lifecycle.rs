@@ -0,0 +1,112 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use bonsaidb_core::schema::SchemaName;
+
+/// Receives notifications as databases within a
+/// [`Storage`](crate::Storage) are opened, closed, and deleted.
+///
+/// Notifications are delivered in the order the events occurred from a
+/// dedicated thread, which allows implementations to access the storage
+/// without blocking the operation that caused the event. Errors returned are
+/// logged using the `log` crate.
+///
+/// The admin database is opened and closed internally, and no events about it
+/// are delivered.
+pub trait DatabaseLifecycle: Debug + Send + Sync + 'static {
+    /// Invoked the first time the database named `name` is opened by this
+    /// process, or when it is opened again after being closed.
+    #[allow(unused_variables)]
+    fn database_opened(&self, name: &str, schema: &SchemaName) -> Result<(), bonsaidb_core::Error> {
+        Ok(())
+    }
+
+    /// Invoked after the database named `name` is closed, either because it
+    /// is being deleted or because its storage was dropped.
+    #[allow(unused_variables)]
+    fn database_closed(&self, name: &str) -> Result<(), bonsaidb_core::Error> {
+        Ok(())
+    }
+
+    /// Invoked after the database named `name` is deleted.
+    #[allow(unused_variables)]
+    fn database_deleted(&self, name: &str) -> Result<(), bonsaidb_core::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum LifecycleEvent {
+    Opened { name: String, schema: SchemaName },
+    Closed(String),
+    Deleted(String),
+}
+
+impl LifecycleEvent {
+    fn deliver(&self, lifecycle: &dyn DatabaseLifecycle) {
+        let (name, result) = match self {
+            Self::Opened { name, schema } => (name, lifecycle.database_opened(name, schema)),
+            Self::Closed(name) => (name, lifecycle.database_closed(name)),
+            Self::Deleted(name) => (name, lifecycle.database_deleted(name)),
+        };
+        if let Err(err) = result {
+            log::error!("database lifecycle callback for '{name}' failed: {err}");
+        }
+    }
+}
+
+/// Delivers [`LifecycleEvent`]s to the configured [`DatabaseLifecycle`].
+#[derive(Debug)]
+pub(crate) struct LifecycleNotifier {
+    events: Option<flume::Sender<LifecycleEvent>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LifecycleNotifier {
+    pub fn new(lifecycle: Option<Arc<dyn DatabaseLifecycle>>) -> std::io::Result<Self> {
+        let Some(lifecycle) = lifecycle else {
+            return Ok(Self {
+                events: None,
+                thread: None,
+            });
+        };
+
+        let (sender, receiver) = flume::unbounded::<LifecycleEvent>();
+        let thread = std::thread::Builder::new()
+            .name(String::from("bonsaidb-lifecycle"))
+            .spawn(move || {
+                while let Ok(event) = receiver.recv() {
+                    event.deliver(&*lifecycle);
+                }
+            })?;
+        Ok(Self {
+            events: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns true if a [`DatabaseLifecycle`] is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.events.is_some()
+    }
+
+    pub fn notify(&self, event: LifecycleEvent) {
+        if let Some(events) = &self.events {
+            drop(events.send(event));
+        }
+    }
+
+    /// Stops accepting events and waits for the ones already sent to be
+    /// delivered.
+    pub fn shutdown(&mut self) {
+        self.events = None;
+        if let Some(thread) = self.thread.take() {
+            // The storage may be dropped by a callback that held the last
+            // reference to it.
+            if thread.thread().id() != std::thread::current().id() {
+                drop(thread.join());
+            }
+        }
+    }
+}
@@ -1,19 +1,253 @@
 use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use bonsaidb_core::connection::SessionId;
-use bonsaidb_core::pubsub::Receiver;
+use bonsaidb_core::admin::{Admin, DatabaseEvent, ADMIN_DATABASE_NAME};
+use bonsaidb_core::connection::{SessionId, StorageConnection};
+use bonsaidb_core::pubsub::{database_topic, PubSub, Receiver, Subscriber as _, Topic};
 
-use crate::storage::SessionSubscriber;
-use crate::{Database, Subscriber};
+use crate::storage::{SessionSubscriber, SessionSubscribers};
+use crate::{Database, Storage, Subscriber};
+
+/// Statistics about the `PubSub` messages published within a [`Storage`].
+///
+/// These statistics are collected from when the [`Storage`] was opened, or
+/// from the last call to [`Storage::reset_pubsub_statistics()`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PubSubStatistics {
+    /// The number of messages published.
+    pub messages_published: u64,
+    /// The number of messages published to topics that had no subscribers.
+    pub messages_without_subscribers: u64,
+    /// The number of times a message was sent to a subscriber. A subscriber
+    /// that is subscribed to more than one of the topics a message is
+    /// published to is only counted once.
+    pub deliveries_attempted: u64,
+    /// The number of subscribers that currently exist.
+    pub subscribers: usize,
+    /// Statistics for each topic that currently has subscribers, keyed by the
+    /// database name and topic.
+    pub topics: BTreeMap<(String, Vec<u8>), TopicStatistics>,
+}
+
+/// Statistics about the `PubSub` messages published to a single topic. See
+/// [`PubSubStatistics::topics`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TopicStatistics {
+    /// The number of messages published to this topic while it had
+    /// subscribers.
+    pub messages_published: u64,
+    /// The number of subscribers currently subscribed to this topic.
+    pub subscribers: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct PubSubCounters {
+    messages_published: AtomicU64,
+    messages_without_subscribers: AtomicU64,
+    deliveries_attempted: AtomicU64,
+}
+
+/// The subscribers of a single topic. Topics are removed once they have no
+/// subscribers.
+#[derive(Debug, Default)]
+pub(crate) struct TopicSubscribers {
+    subscribers: HashSet<u64>,
+    messages_published: AtomicU64,
+}
+
+impl SessionSubscribers {
+    pub(crate) fn subscribe(&mut self, subscriber_id: u64, topic: Vec<u8>) {
+        if let Some(subscriber) = self.subscribers.get_mut(&subscriber_id) {
+            if subscriber.topics.insert(topic.clone()) {
+                self.topics
+                    .entry(topic)
+                    .or_default()
+                    .subscribers
+                    .insert(subscriber_id);
+            }
+        }
+    }
+
+    pub(crate) fn unsubscribe(&mut self, subscriber_id: u64, topic: &[u8]) {
+        if let Some(subscriber) = self.subscribers.get_mut(&subscriber_id) {
+            if subscriber.topics.remove(topic) {
+                self.remove_topic_subscriber(subscriber_id, topic);
+            }
+        }
+    }
+
+    /// Removes `subscriber` from the topics it was subscribed to.
+    pub(crate) fn drop_subscriptions(&mut self, subscriber_id: u64, subscriber: SessionSubscriber) {
+        for topic in subscriber.topics {
+            self.remove_topic_subscriber(subscriber_id, &topic);
+        }
+    }
+
+    fn remove_topic_subscriber(&mut self, subscriber_id: u64, topic: &[u8]) {
+        if let Some(topic_subscribers) = self.topics.get_mut(topic) {
+            topic_subscribers.subscribers.remove(&subscriber_id);
+            if topic_subscribers.subscribers.is_empty() {
+                self.topics.remove(topic);
+            }
+        }
+    }
+}
+
+impl Storage {
+    /// Returns statistics about the `PubSub` messages published in every
+    /// database of this storage.
+    #[must_use]
+    pub fn pubsub_statistics(&self) -> PubSubStatistics {
+        let data = self.instance.data.subscribers.read();
+        let counters = &data.statistics;
+        let topics = data
+            .topics
+            .iter()
+            .filter_map(|(topic, topic_subscribers)| {
+                // Topics are prefixed with the database name, which can't
+                // contain a null byte.
+                let separator = topic.iter().position(|b| *b == 0)?;
+                Some((
+                    (
+                        String::from_utf8_lossy(&topic[..separator]).into_owned(),
+                        topic[separator + 1..].to_vec(),
+                    ),
+                    TopicStatistics {
+                        messages_published: topic_subscribers
+                            .messages_published
+                            .load(Ordering::Relaxed),
+                        subscribers: topic_subscribers.subscribers.len(),
+                    },
+                ))
+            })
+            .collect();
+
+        PubSubStatistics {
+            messages_published: counters.messages_published.load(Ordering::Relaxed),
+            messages_without_subscribers: counters
+                .messages_without_subscribers
+                .load(Ordering::Relaxed),
+            deliveries_attempted: counters.deliveries_attempted.load(Ordering::Relaxed),
+            subscribers: data.subscribers.len(),
+            topics,
+        }
+    }
+
+    /// Returns a subscriber that receives a [`DatabaseEvent`] each time a
+    /// database is created or deleted in this storage.
+    ///
+    /// The events are published to the admin database, so this requires this
+    /// storage's session to have permission to create subscribers on the admin
+    /// database and to subscribe to the [`DatabaseEvent`] topic. Use
+    /// [`TypedReceiver`](bonsaidb_core::pubsub::TypedReceiver) or
+    /// [`TypedMessage::payload()`](bonsaidb_core::pubsub::TypedMessage::payload)
+    /// to read the events.
+    pub fn subscribe_to_database_events(&self) -> Result<Subscriber, bonsaidb_core::Error> {
+        let subscriber = self
+            .database::<Admin>(ADMIN_DATABASE_NAME)?
+            .create_subscriber()?;
+        subscriber.subscribe_to_topic::<DatabaseEvent>()?;
+        Ok(subscriber)
+    }
+
+    /// Resets all counters returned from [`Self::pubsub_statistics()`] to 0.
+    pub fn reset_pubsub_statistics(&self) {
+        let data = self.instance.data.subscribers.read();
+        let counters = &data.statistics;
+        counters.messages_published.store(0, Ordering::Relaxed);
+        counters
+            .messages_without_subscribers
+            .store(0, Ordering::Relaxed);
+        counters.deliveries_attempted.store(0, Ordering::Relaxed);
+        for topic_subscribers in data.topics.values() {
+            topic_subscribers
+                .messages_published
+                .store(0, Ordering::Relaxed);
+        }
+    }
+}
 
 impl crate::storage::StorageInstance {
+    /// Publishes `event` to the admin database's [`DatabaseEvent`] topic.
+    pub(crate) fn publish_database_event(&self, event: &DatabaseEvent) {
+        let message =
+            pot::to_vec(&DatabaseEvent::name()).and_then(|topic| Ok((topic, pot::to_vec(event)?)));
+        match message {
+            Ok((topic, payload)) => {
+                let topic = database_topic(ADMIN_DATABASE_NAME, &topic);
+                self.record_published_message([topic.as_slice()]);
+                self.relay().publish_raw(topic, payload);
+            }
+            Err(err) => log::error!("error serializing database event: {err}"),
+        }
+    }
+
+    /// Updates the `PubSub` statistics for a message being published to
+    /// `topics`.
+    ///
+    /// Only a shared lock is held and every counter is atomic, so publishes
+    /// don't block each other.
+    pub(crate) fn record_published_message<'a>(&self, topics: impl IntoIterator<Item = &'a [u8]>) {
+        let data = self.data.subscribers.read();
+        let mut topics = topics
+            .into_iter()
+            .filter_map(|topic| data.topics.get(topic))
+            .inspect(|topic_subscribers| {
+                topic_subscribers
+                    .messages_published
+                    .fetch_add(1, Ordering::Relaxed);
+            });
+        let deliveries = match (topics.next(), topics.next()) {
+            (None, _) => 0,
+            (Some(topic_subscribers), None) => topic_subscribers.subscribers.len(),
+            (Some(first), Some(second)) => {
+                // A subscriber subscribed to more than one of the topics only
+                // receives the message once.
+                let mut recipients = HashSet::new();
+                for topic_subscribers in [first, second].into_iter().chain(topics) {
+                    recipients.extend(topic_subscribers.subscribers.iter().copied());
+                }
+                recipients.len()
+            }
+        };
+
+        let counters = &data.statistics;
+        counters.messages_published.fetch_add(1, Ordering::Relaxed);
+        if deliveries == 0 {
+            counters
+                .messages_without_subscribers
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        counters
+            .deliveries_attempted
+            .fetch_add(deliveries as u64, Ordering::Relaxed);
+    }
+
     pub(crate) fn register_subscriber(
         &self,
         session_id: Option<SessionId>,
         database: Database,
-    ) -> Subscriber {
-        let subscriber = self.relay().create_subscriber();
+    ) -> Result<Subscriber, bonsaidb_core::Error> {
         let mut data = self.data.subscribers.write();
+        let limits = &self.data.subscriber_limits;
+        let at_total_limit = limits
+            .max_total
+            .is_some_and(|max| data.subscribers.len() >= max);
+        let at_session_limit = match (session_id, limits.max_per_session) {
+            (Some(session_id), Some(max)) => {
+                data.subscribers_by_session
+                    .get(&session_id)
+                    .map_or(0, HashSet::len)
+                    >= max
+            }
+            _ => false,
+        };
+        if at_total_limit || at_session_limit {
+            return Err(bonsaidb_core::Error::TooManySubscribers);
+        }
+
+        let subscriber = self.relay().create_subscriber();
         let receiver = Receiver::new_stripping_prefixes(subscriber.receiver().clone());
         let id = loop {
             data.last_id = data.last_id.wrapping_add(1);
@@ -23,17 +257,34 @@ impl crate::storage::StorageInstance {
                 entry.or_insert(SessionSubscriber {
                     session_id,
                     subscriber: subscriber.clone(),
+                    topics: HashSet::new(),
                 });
                 break id;
             }
         };
+        if let Some(session_id) = session_id {
+            data.subscribers_by_session
+                .entry(session_id)
+                .or_default()
+                .insert(id);
+        }
 
-        Subscriber {
+        Ok(Subscriber {
             id,
             database,
             subscriber,
             receiver,
-        }
+        })
+    }
+
+    pub(crate) fn record_subscribe(&self, subscriber_id: u64, topic: Vec<u8>) {
+        let mut data = self.data.subscribers.write();
+        data.subscribe(subscriber_id, topic);
+    }
+
+    pub(crate) fn record_unsubscribe(&self, subscriber_id: u64, topic: &[u8]) {
+        let mut data = self.data.subscribers.write();
+        data.unsubscribe(subscriber_id, topic);
     }
 
     pub(crate) fn unregister_subscriber(&self, subscriber: &Subscriber) {
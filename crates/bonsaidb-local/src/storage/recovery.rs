@@ -0,0 +1,555 @@
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use bonsaidb_core::admin::ADMIN_DATABASE_NAME;
+use bonsaidb_core::connection::{HasSession, StorageConnection};
+use bonsaidb_core::document::{DocumentId, Header};
+use bonsaidb_core::permissions::bonsai::{
+    collection_resource_name, document_resource_name, BonsaiAction, DatabaseAction, DocumentAction,
+};
+use bonsaidb_core::schema::{CollectionName, ViewName};
+use bonsaidb_core::transaction::{OperationResult, Transaction};
+use nebari::io::any::AnyFile;
+use nebari::tree::{AnyTreeRoot, Operation, Root, ScanEvaluation, Unversioned, Versioned};
+use nebari::{ArcBytes, Tree};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::RepairMode;
+use crate::database::{deserialize_document, document_tree_name};
+#[cfg(any(feature = "encryption", feature = "compression"))]
+use crate::storage::TreeVault;
+use crate::views::integrity_scanner::ViewVersion;
+use crate::views::{auxiliary_data, view_invalidated_docs_tree_name, view_versions_tree_name};
+use crate::{Database, Error, Storage};
+
+/// The results of the checks performed by [`Storage::open()`] when
+/// [`StorageConfiguration::repair_mode`](crate::config::StorageConfiguration#structfield.repair_mode)
+/// is set.
+#[derive(Debug, Clone)]
+pub struct RecoveryReport {
+    /// The mode the checks were performed with.
+    pub mode: RepairMode,
+    /// The results for each database, keyed by database name. Every database
+    /// that was checked is present, even if no problems were found.
+    pub databases: BTreeMap<String, DatabaseRecovery>,
+}
+
+impl RecoveryReport {
+    /// Returns true if no problems were found in any database. Views that need
+    /// to be mapped again are not considered problems.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.databases.values().all(|database| {
+            database.open_error.is_none()
+                && database.unreadable_collections.is_empty()
+                && database.unreadable_documents.is_empty()
+        })
+    }
+
+    /// Returns an iterator over each document that was quarantined and the
+    /// name of the database it was found in.
+    pub fn quarantined_documents(&self) -> impl Iterator<Item = (&str, &UnreadableDocument)> {
+        self.databases.iter().flat_map(|(database, recovery)| {
+            recovery
+                .unreadable_documents
+                .iter()
+                .filter(|document| document.quarantined)
+                .map(move |document| (database.as_str(), document))
+        })
+    }
+}
+
+/// The results of checking a single database while opening [`Storage`].
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseRecovery {
+    /// The error encountered while opening the database. When present, none
+    /// of the database's documents were checked, and the database is left
+    /// untouched regardless of the [`RepairMode`].
+    pub open_error: Option<String>,
+    /// Collections whose documents could not be listed. Their documents are
+    /// never modified, regardless of the [`RepairMode`].
+    pub unreadable_collections: Vec<UnreadableCollection>,
+    /// Documents that could not be read.
+    pub unreadable_documents: Vec<UnreadableDocument>,
+    /// Views that will be mapped again before they are next queried.
+    pub outdated_views: Vec<ViewName>,
+}
+
+/// A collection whose documents could not be listed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnreadableCollection {
+    /// The name of the collection.
+    pub collection: CollectionName,
+    /// The error encountered while listing the documents.
+    pub error: String,
+}
+
+/// A document that could not be read.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnreadableDocument {
+    /// The collection containing the document.
+    pub collection: CollectionName,
+    /// The id of the document.
+    pub id: DocumentId,
+    /// The error encountered while reading the document.
+    pub error: String,
+    /// True if the document was moved into the collection's quarantine. A
+    /// document is only quarantined when using
+    /// [`RepairMode::RepairAggressively`] and its stored bytes could be read,
+    /// ensuring the quarantine always contains the document's data.
+    pub quarantined: bool,
+}
+
+/// A document that was moved into its collection's quarantine.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct QuarantinedDocument {
+    /// The id of the document.
+    pub id: DocumentId,
+    /// The error that caused the document to be quarantined.
+    pub error: String,
+    /// The bytes the document was stored as.
+    pub contents: Vec<u8>,
+    /// True if `contents` are still encrypted or compressed, because they
+    /// could not be decoded when the document was quarantined.
+    pub encoded: bool,
+}
+
+/// Returns the name of the tree containing the quarantined documents of
+/// `collection`.
+pub fn quarantine_tree_name(collection: &CollectionName) -> String {
+    format!("{}.quarantine", document_tree_name(collection))
+}
+
+impl Storage {
+    /// Returns the report of the checks performed when this storage was
+    /// opened, or `None` if
+    /// [`StorageConfiguration::repair_mode`](crate::config::StorageConfiguration#structfield.repair_mode)
+    /// was not set.
+    #[must_use]
+    pub fn last_recovery_report(&self) -> Option<Arc<RecoveryReport>> {
+        self.instance.data.recovery_report.read().clone()
+    }
+
+    pub(crate) fn recover(&self, mode: RepairMode) -> Result<(), Error> {
+        let mut report = RecoveryReport {
+            mode,
+            databases: BTreeMap::new(),
+        };
+        report
+            .databases
+            .insert(ADMIN_DATABASE_NAME.to_string(), self.admin().recover(mode)?);
+
+        let names = self
+            .instance
+            .data
+            .available_databases
+            .read()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in names {
+            // A database that can't be opened is reported rather than
+            // preventing the rest of the storage from being opened.
+            let recovery = match self
+                .instance
+                .database_without_schema(&name, Some(self), None)
+            {
+                Ok(database) => database.recover(mode)?,
+                Err(err) => DatabaseRecovery {
+                    open_error: Some(err.to_string()),
+                    ..DatabaseRecovery::default()
+                },
+            };
+            report.databases.insert(name, recovery);
+        }
+
+        *self.instance.data.recovery_report.write() = Some(Arc::new(report));
+        Ok(())
+    }
+}
+
+/// The stored bytes of the last value the document tree's vault failed to
+/// decode.
+type FailedPayload = Arc<Mutex<Option<Vec<u8>>>>;
+
+/// Wraps a collection's vault, remembering the payloads it fails to decode so
+/// that they can be quarantined.
+#[cfg(any(feature = "encryption", feature = "compression"))]
+#[derive(Debug)]
+struct CapturingVault {
+    vault: TreeVault,
+    failed: FailedPayload,
+}
+
+#[cfg(any(feature = "encryption", feature = "compression"))]
+impl nebari::Vault for CapturingVault {
+    type Error = Error;
+
+    fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        nebari::Vault::encrypt(&self.vault, payload)
+    }
+
+    fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        nebari::Vault::decrypt(&self.vault, payload).map_err(|err| {
+            *self.failed.lock() = Some(payload.to_vec());
+            err
+        })
+    }
+}
+
+impl Database {
+    /// Returns the documents in `collection`'s quarantine.
+    pub fn quarantined_documents(
+        &self,
+        collection: &CollectionName,
+    ) -> Result<Vec<QuarantinedDocument>, Error> {
+        self.check_permission(
+            collection_resource_name(self.name(), collection),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::List)),
+        )?;
+        let Some(quarantine) = self.quarantine_tree(collection)? else {
+            return Ok(Vec::new());
+        };
+        let mut documents = Vec::new();
+        quarantine.scan::<Infallible, _, _, _, _>(
+            &(..),
+            true,
+            |_, _, _| ScanEvaluation::ReadData,
+            |_, _| ScanEvaluation::ReadData,
+            |_, _, value| {
+                documents.push(value);
+                Ok(())
+            },
+        )?;
+        documents
+            .into_iter()
+            .map(|document| bincode::deserialize(&document).map_err(Error::from))
+            .collect()
+    }
+
+    /// Restores the document with `id` from `collection`'s quarantine,
+    /// returning the header of the restored document. The restored document
+    /// replaces any document with the same id that was created after the
+    /// document was quarantined.
+    ///
+    /// If the document still can't be read, an error is returned and the
+    /// document remains quarantined. The document is removed from the
+    /// quarantine in the same transaction that restores it.
+    pub fn restore_quarantined_document(
+        &self,
+        collection: &CollectionName,
+        id: &DocumentId,
+    ) -> Result<Header, bonsaidb_core::Error> {
+        self.check_permission(
+            document_resource_name(self.name(), collection, id),
+            &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Get)),
+        )?;
+        self.check_documents_writable()?;
+        let not_found =
+            || bonsaidb_core::Error::DocumentNotFound(collection.clone(), Box::new(id.clone()));
+        let quarantine = self.quarantine_tree(collection)?.ok_or_else(not_found)?;
+        let quarantined = quarantine
+            .get(id.as_ref())
+            .map_err(Error::from)?
+            .ok_or_else(not_found)?;
+        let quarantined =
+            bincode::deserialize::<QuarantinedDocument>(&quarantined).map_err(Error::from)?;
+
+        let contents = if quarantined.encoded {
+            self.decode_quarantined(collection, &quarantined.contents)?
+        } else {
+            quarantined.contents
+        };
+        let document = deserialize_document(&contents)?;
+
+        // Overwriting the document also checks the permission to overwrite it.
+        let (_, results) = self.apply_transaction_returning_id(
+            Transaction::overwrite(collection.clone(), id.clone(), document.contents.to_vec()),
+            Some((collection, id)),
+        )?;
+
+        match results.into_iter().next() {
+            Some(OperationResult::DocumentUpdated { header, .. }) => Ok(header),
+            _ => unreachable!("overwrite always updates a document"),
+        }
+    }
+
+    /// Opens `collection`'s quarantine, if it exists. Opening a tree creates
+    /// it, so the quarantine is only opened if documents have been
+    /// quarantined.
+    fn quarantine_tree(
+        &self,
+        collection: &CollectionName,
+    ) -> Result<Option<Tree<Unversioned, AnyFile>>, Error> {
+        let name = quarantine_tree_name(collection);
+        if self.roots().tree_names()?.contains(&name) {
+            Ok(Some(self.roots().tree(
+                self.collection_tree::<Unversioned, _>(collection, name)?,
+            )?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "encryption", feature = "compression")),
+        allow(clippy::unnecessary_wraps, clippy::unused_self, unused_variables)
+    )]
+    fn decode_quarantined(
+        &self,
+        collection: &CollectionName,
+        contents: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        #[cfg(any(feature = "encryption", feature = "compression"))]
+        if let Some(vault) = self.transaction_tree_vault(collection)? {
+            return nebari::Vault::decrypt(&vault, contents);
+        }
+
+        Ok(contents.to_vec())
+    }
+
+    fn recover(&self, mode: RepairMode) -> Result<DatabaseRecovery, Error> {
+        let mut recovery = DatabaseRecovery::default();
+        // Opening a tree creates it if it doesn't exist, so only trees that
+        // already exist are opened.
+        let tree_names = self
+            .roots()
+            .tree_names()?
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        for collection in self.data.schema.collections() {
+            if tree_names.contains(&document_tree_name(collection)) {
+                self.recover_documents(collection, mode, &mut recovery)?;
+            }
+
+            for view in self.data.schema.views_in_collection(collection) {
+                let view_name = view.view_name();
+                if self.view_is_outdated(collection, &view_name, view.version(), &tree_names)? {
+                    recovery.outdated_views.push(view_name);
+                }
+            }
+        }
+
+        Ok(recovery)
+    }
+
+    fn recover_documents(
+        &self,
+        collection: &CollectionName,
+        mode: RepairMode,
+        recovery: &mut DatabaseRecovery,
+    ) -> Result<(), Error> {
+        let failed = FailedPayload::default();
+        let documents = self.recovery_document_tree(collection, &failed)?;
+
+        let mut keys = Vec::new();
+        if let Err(err) = documents.scan::<Infallible, _, _, _, _>(
+            &(..),
+            true,
+            |_, _, _| ScanEvaluation::ReadData,
+            |key, _| {
+                keys.push(key.clone());
+                ScanEvaluation::Skip
+            },
+            |_, _, _| unreachable!(),
+        ) {
+            recovery.unreadable_collections.push(UnreadableCollection {
+                collection: collection.clone(),
+                error: Error::from(err).to_string(),
+            });
+            return Ok(());
+        }
+
+        let mut quarantined = Vec::new();
+        for key in keys {
+            let id = DocumentId::try_from(key.as_slice())?;
+            *failed.lock() = None;
+            let (error, stored) = match documents.get(&key) {
+                Ok(Some(document)) => match deserialize_document(&document) {
+                    Ok(_) => continue,
+                    Err(err) => (err.to_string(), Some((document.to_vec(), false))),
+                },
+                Ok(None) => continue,
+                // The document tree's vault records the payload it couldn't
+                // decode. Other errors mean the stored bytes can't be read.
+                Err(err) => (
+                    err.to_string(),
+                    failed.lock().take().map(|payload| (payload, true)),
+                ),
+            };
+
+            let quarantine = mode == RepairMode::RepairAggressively && stored.is_some();
+            recovery.unreadable_documents.push(UnreadableDocument {
+                collection: collection.clone(),
+                id: id.clone(),
+                error: error.clone(),
+                quarantined: quarantine,
+            });
+            if let (true, Some((contents, encoded))) = (quarantine, stored) {
+                quarantined.push((
+                    key,
+                    QuarantinedDocument {
+                        id,
+                        error,
+                        contents,
+                        encoded,
+                    },
+                ));
+            }
+        }
+
+        if !quarantined.is_empty() {
+            self.quarantine(collection, quarantined)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg_attr(
+        not(any(feature = "encryption", feature = "compression")),
+        allow(unused_mut, unused_variables)
+    )]
+    fn recovery_document_tree(
+        &self,
+        collection: &CollectionName,
+        failed: &FailedPayload,
+    ) -> Result<Tree<Versioned, AnyFile>, Error> {
+        let mut tree = Versioned::tree(document_tree_name(collection));
+
+        #[cfg(any(feature = "encryption", feature = "compression"))]
+        if let Some(vault) = self.transaction_tree_vault(collection)? {
+            tree = tree.with_vault(CapturingVault {
+                vault,
+                failed: failed.clone(),
+            });
+        }
+
+        Ok(self.roots().tree(tree)?)
+    }
+
+    /// Moves `documents` from `collection` into its quarantine, invalidating
+    /// the documents in each of the collection's views.
+    fn quarantine(
+        &self,
+        collection: &CollectionName,
+        documents: Vec<(ArcBytes<'static>, QuarantinedDocument)>,
+    ) -> Result<(), Error> {
+        let views = self
+            .data
+            .schema
+            .views_in_collection(collection)
+            .map(|view| view.view_name())
+            .collect::<Vec<_>>();
+        let mut trees: Vec<Box<dyn AnyTreeRoot<AnyFile>>> = vec![
+            Box::new(
+                self.collection_tree::<Versioned, _>(collection, document_tree_name(collection))?,
+            ),
+            Box::new(
+                self.collection_tree::<Unversioned, _>(
+                    collection,
+                    quarantine_tree_name(collection),
+                )?,
+            ),
+        ];
+        for view in &views {
+            trees.push(Box::new(self.collection_tree::<Unversioned, _>(
+                collection,
+                view_invalidated_docs_tree_name(view),
+            )?));
+        }
+
+        let mut keys = documents
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        keys.sort();
+
+        let mut transaction = self
+            .roots()
+            .transaction::<_, dyn AnyTreeRoot<AnyFile>>(&trees)?;
+        // Removing the documents doesn't need to read their contents.
+        transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .modify(keys.clone(), Operation::Remove)?;
+        {
+            let mut quarantine = transaction.tree::<Unversioned>(1).unwrap();
+            for (key, document) in &documents {
+                quarantine.set(key.clone(), bincode::serialize(document)?)?;
+            }
+        }
+        for index in 2..trees.len() {
+            transaction
+                .tree::<Unversioned>(index)
+                .unwrap()
+                .modify(keys.clone(), Operation::Set(ArcBytes::default()))?;
+        }
+        transaction.commit()?;
+
+        for view in views {
+            self.storage.instance.tasks().mark_view_outdated(
+                self.data.name.clone(),
+                collection.clone(),
+                view,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if `view` will be mapped again before it is next queried.
+    fn view_is_outdated(
+        &self,
+        collection: &CollectionName,
+        view: &ViewName,
+        view_version: u64,
+        tree_names: &HashSet<String>,
+    ) -> Result<bool, Error> {
+        let versions_tree_name = view_versions_tree_name(collection);
+        if !tree_names.contains(&versions_tree_name) {
+            return Ok(true);
+        }
+        let auxiliary_version =
+            auxiliary_data(self, self.data.schema.view_by_name(view)?)?.map(|data| data.version());
+        let is_current = self
+            .roots()
+            .tree(self.collection_tree::<Unversioned, _>(collection, versions_tree_name)?)?
+            .get(view.to_string().as_bytes())?
+            .and_then(|version| ViewVersion::from_bytes(&version).ok())
+            .map_or(false, |version| {
+                version.is_current(view_version, auxiliary_version)
+            });
+        if !is_current {
+            return Ok(true);
+        }
+
+        let invalidated_tree_name = view_invalidated_docs_tree_name(view);
+        if !tree_names.contains(&invalidated_tree_name) {
+            return Ok(false);
+        }
+        has_entries(
+            &self
+                .roots()
+                .tree(self.collection_tree::<Unversioned, _>(collection, invalidated_tree_name)?)?,
+        )
+    }
+}
+
+/// Returns true if `tree` contains at least one entry.
+fn has_entries<R: Root>(tree: &Tree<R, AnyFile>) -> Result<bool, Error> {
+    let mut found = false;
+    tree.scan::<Infallible, _, _, _, _>(
+        &(..),
+        true,
+        |_, _, _| ScanEvaluation::ReadData,
+        |_, _| {
+            found = true;
+            ScanEvaluation::Stop
+        },
+        |_, _, _| unreachable!(),
+    )?;
+    Ok(found)
+}
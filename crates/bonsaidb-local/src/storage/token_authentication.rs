@@ -56,6 +56,7 @@ impl super::StorageInstance {
                 server_timestamp: TimestampAsNanoseconds::now(),
             },
             permissions: Permissions::default(), /* This session will have no permissions until it finishes token authentication */
+            permission_limits: Vec::new(),
         };
         let authentication = Arc::new(AuthenticatedSession {
             storage: Arc::downgrade(&self.data),
@@ -101,12 +102,12 @@ impl super::StorageInstance {
                     IdentityId::User(id) => {
                         let user = User::get(&id, admin)?
                             .ok_or(bonsaidb_core::Error::InvalidCredentials)?;
-                        self.assume_user(user, admin)
+                        self.assume_user(user, admin, Vec::new())
                     }
                     IdentityId::Role(id) => {
                         let role = Role::get(&id, admin)?
                             .ok_or(bonsaidb_core::Error::InvalidCredentials)?;
-                        self.assume_role(role, admin)
+                        self.assume_role(role, admin, Vec::new())
                     }
                     _ => Err(bonsaidb_core::Error::InvalidCredentials),
                 }
@@ -1,17 +1,20 @@
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use bonsaidb_core::connection::Connection;
-use bonsaidb_core::keyvalue::Timestamp;
+use bonsaidb_core::connection::{Connection, ScrubReport};
 use bonsaidb_core::schema::{view, CollectionName, ViewName};
 use parking_lot::RwLock;
 
-use crate::database::keyvalue::ExpirationLoader;
+use crate::database::keyvalue::{ExpirationLoadStatus, ExpirationLoader};
+use crate::database::quota::QuotaReconciler;
+use crate::database::scrub::Scrubber;
 use crate::database::Database;
+pub(crate) use crate::tasks::compactor::compaction_order;
 use crate::tasks::compactor::Compactor;
 use crate::tasks::handle::Handle;
-use crate::tasks::manager::Manager;
+use crate::tasks::manager::{Manager, Priority};
 use crate::views::integrity_scanner::{IntegrityScan, IntegrityScanner, OptionalViewMapHandle};
 use crate::views::mapper::{Map, Mapper};
 use crate::Error;
@@ -24,31 +27,67 @@ mod traits;
 
 pub use self::traits::{Job, Keyed};
 
+// Waiting on a job with a cancellation is only done by the async wrappers.
+#[cfg_attr(not(feature = "async"), allow(dead_code))]
+mod cancellation;
 mod compactor;
 mod task;
 
+pub use cancellation::{CancelOnDrop, Cancellation};
 pub use task::Task;
 
+/// The status of the view integrity scans of a [`Storage`](crate::Storage).
+/// Returned from
+/// [`Storage::integrity_scan_status()`](crate::Storage::integrity_scan_status).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct IntegrityScanStatus {
+    /// The number of integrity scans that are executing or waiting for a
+    /// worker.
+    pub running: usize,
+    /// The number of integrity scans waiting for another scan to complete.
+    pub queued: usize,
+    /// The maximum number of integrity scans that can run at once. See
+    /// [`Tasks::max_concurrent_integrity_scans`](crate::config::Tasks::max_concurrent_integrity_scans).
+    pub max_concurrent: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct TaskManager {
     pub jobs: Manager<Task>,
     statuses: Arc<RwLock<Statuses>>,
+    expiration_load_batch_size: usize,
 }
 
 type ViewKey = (Arc<Cow<'static, str>>, CollectionName, ViewName);
 
+/// How long [`TaskManager::update_view_through()`] waits for the view to be
+/// updated.
+#[derive(Debug, Clone, Copy)]
+pub enum ViewUpdateWait {
+    /// The update is queued without waiting for it.
+    None,
+    /// Waits until the view is updated.
+    Forever,
+    /// Returns [`bonsaidb_core::Error::ViewUpdateTimeout`] if the view isn't
+    /// updated by the deadline.
+    Until(Instant),
+}
+
 #[derive(Default, Debug)]
 pub struct Statuses {
     completed_integrity_checks: HashSet<ViewKey>,
-    key_value_expiration_loads: HashSet<Arc<Cow<'static, str>>>,
+    key_value_expiration_loads: HashMap<Arc<Cow<'static, str>>, ExpirationLoadStatus>,
     view_update_last_status: HashMap<ViewKey, u64>,
+    /// Databases being deleted, whose running jobs should exit early.
+    cancelled_databases: HashSet<String>,
 }
 
 impl TaskManager {
-    pub fn new(jobs: Manager<Task>) -> Self {
+    pub fn new(jobs: Manager<Task>, expiration_load_batch_size: usize) -> Self {
         Self {
             jobs,
             statuses: Arc::default(),
+            expiration_load_batch_size,
         }
     }
 
@@ -58,13 +97,37 @@ impl TaskManager {
         database: &Database,
         block_until_updated: bool,
     ) -> Result<(), crate::Error> {
+        let wait = if block_until_updated {
+            ViewUpdateWait::Forever
+        } else {
+            ViewUpdateWait::None
+        };
+        self.update_view_through(view, database, None, wait)
+    }
+
+    /// Updates `view` until it has mapped `transaction_id`, or the database's
+    /// last transaction if `transaction_id` is `None` or newer than the last
+    /// transaction.
+    pub fn update_view_through(
+        &self,
+        view: &dyn view::Serialized,
+        database: &Database,
+        transaction_id: Option<u64>,
+        wait: ViewUpdateWait,
+    ) -> Result<(), crate::Error> {
+        // A caller that is no longer waiting for the view shouldn't cause more
+        // work to be queued.
+        database.check_cancelled()?;
         let view_name = view.view_name();
         if let Some(job) = self.spawn_integrity_check(view, database) {
-            job.receive()??;
+            database.receive_job(job)??;
         }
 
         // If there is no transaction id, there is no data, so the view is "up-to-date"
-        if let Some(current_transaction_id) = database.last_transaction_id()? {
+        if let Some(current_transaction_id) = database
+            .last_transaction_id()?
+            .map(|current| transaction_id.map_or(current, |through| through.min(current)))
+        {
             let needs_reindex = {
                 // When views finish updating, they store the last transaction_id
                 // they mapped. If that value is current, we don't need to go
@@ -85,7 +148,7 @@ impl TaskManager {
                 let wait_for_transaction = current_transaction_id;
                 loop {
                     let job = self.jobs.lookup_or_enqueue(Mapper {
-                        database: database.clone(),
+                        database: database.without_cancellation(),
                         map: Map {
                             database: database.data.name.clone(),
                             collection: view.collection(),
@@ -93,11 +156,23 @@ impl TaskManager {
                         },
                     });
 
-                    if !block_until_updated {
-                        break;
-                    }
-
-                    let id = job.receive()??;
+                    let id = match wait {
+                        ViewUpdateWait::None => break,
+                        ViewUpdateWait::Forever => database.receive_job(job)??,
+                        ViewUpdateWait::Until(deadline) => {
+                            match database.receive_job_until(job, deadline)? {
+                                Some(result) => result?,
+                                None => {
+                                    return Err(Error::Core(
+                                        bonsaidb_core::Error::ViewUpdateTimeout {
+                                            view: view_name,
+                                            transaction_id: wait_for_transaction,
+                                        },
+                                    ))
+                                }
+                            }
+                        }
+                    };
                     if wait_for_transaction <= id {
                         break;
                     }
@@ -109,8 +184,19 @@ impl TaskManager {
     }
 
     pub fn key_value_expiration_loaded(&self, database: &Arc<Cow<'static, str>>) -> bool {
+        self.key_value_expiration_status(database).complete
+    }
+
+    pub fn key_value_expiration_status(
+        &self,
+        database: &Arc<Cow<'static, str>>,
+    ) -> ExpirationLoadStatus {
         let statuses = self.statuses.read();
-        statuses.key_value_expiration_loads.contains(database)
+        statuses
+            .key_value_expiration_loads
+            .get(database)
+            .copied()
+            .unwrap_or_default()
     }
 
     pub fn view_integrity_checked(
@@ -125,32 +211,66 @@ impl TaskManager {
             .contains(&(database, collection, view_name))
     }
 
+    /// Returns a handle to an integrity check of `view`, or None if the view
+    /// has already been checked. The check is queued ahead of checks that were
+    /// scheduled using [`Self::schedule_integrity_check()`].
     pub fn spawn_integrity_check(
         &self,
         view: &dyn view::Serialized,
         database: &Database,
+    ) -> Option<Handle<OptionalViewMapHandle, Error>> {
+        self.integrity_check(view, database, Priority::High)
+    }
+
+    /// Queues an integrity check of `view` if it hasn't been checked yet,
+    /// behind the checks that something is waiting for.
+    pub fn schedule_integrity_check(&self, view: &dyn view::Serialized, database: &Database) {
+        drop(self.integrity_check(view, database, Priority::Normal));
+    }
+
+    fn integrity_check(
+        &self,
+        view: &dyn view::Serialized,
+        database: &Database,
+        priority: Priority,
     ) -> Option<Handle<OptionalViewMapHandle, Error>> {
         let view_name = view.view_name();
-        if self.view_integrity_checked(
-            database.data.name.clone(),
-            view.collection(),
-            view_name.clone(),
-        ) {
+        // The auxiliary inputs of a view can change without a transaction, so
+        // they are checked each time the view is used.
+        if view.auxiliary_inputs().is_empty()
+            && self.view_integrity_checked(
+                database.data.name.clone(),
+                view.collection(),
+                view_name.clone(),
+            )
+        {
             None
         } else {
-            let job = self.jobs.lookup_or_enqueue(IntegrityScanner {
-                database: database.clone(),
-                scan: IntegrityScan {
-                    database: database.data.name.clone(),
-                    view_version: view.version(),
-                    collection: view.collection(),
-                    view_name,
+            let job = self.jobs.lookup_or_enqueue_throttled(
+                IntegrityScanner {
+                    database: database.without_cancellation(),
+                    scan: IntegrityScan {
+                        database: database.data.name.clone(),
+                        view_version: view.version(),
+                        collection: view.collection(),
+                        view_name,
+                    },
                 },
-            });
+                priority,
+            );
             Some(job)
         }
     }
 
+    pub fn integrity_scan_status(&self) -> IntegrityScanStatus {
+        let status = self.jobs.throttle_status();
+        IntegrityScanStatus {
+            running: status.running,
+            queued: status.pending,
+            max_concurrent: status.limit,
+        }
+    }
+
     pub fn mark_integrity_check_complete(
         &self,
         database: Arc<Cow<'static, str>>,
@@ -163,9 +283,25 @@ impl TaskManager {
             .insert((database, collection, view_name));
     }
 
-    pub fn mark_key_value_expiration_loaded(&self, database: Arc<Cow<'static, str>>) {
+    pub fn mark_view_outdated(
+        &self,
+        database: Arc<Cow<'static, str>>,
+        collection: CollectionName,
+        view_name: ViewName,
+    ) {
         let mut statuses = self.statuses.write();
-        statuses.key_value_expiration_loads.insert(database);
+        statuses
+            .view_update_last_status
+            .remove(&(database, collection, view_name));
+    }
+
+    pub fn update_key_value_expiration_status(
+        &self,
+        database: Arc<Cow<'static, str>>,
+        status: ExpirationLoadStatus,
+    ) {
+        let mut statuses = self.statuses.write();
+        statuses.key_value_expiration_loads.insert(database, status);
     }
 
     pub fn mark_view_updated(
@@ -181,6 +317,64 @@ impl TaskManager {
             .insert((database, collection, view_name), transaction_id);
     }
 
+    /// Discards the queued jobs for `database` and blocks until its running
+    /// jobs have completed. Running jobs observe the cancellation through
+    /// [`Self::check_cancelled()`] until [`Self::forget_database()`] is
+    /// called.
+    pub fn cancel_database_jobs(&self, database: &str) {
+        self.statuses
+            .write()
+            .cancelled_databases
+            .insert(database.to_owned());
+        self.jobs
+            .cancel_keyed_jobs(|task| task.database() == database);
+    }
+
+    /// Blocks until no jobs are queued or running. Jobs can enqueue other
+    /// jobs, so this repeats until a check finds nothing to wait for.
+    pub fn wait_for_idle(&self) {
+        while self.jobs.wait_for_keyed_jobs(|_| true) {}
+    }
+
+    /// Stops accepting jobs and stops the workers. See [`Manager::stop()`].
+    pub fn stop(&self, wait: bool) {
+        self.jobs.stop(wait);
+    }
+
+    /// Returns [`Error::InternalCommunication`] if the workers have been told
+    /// to stop without finishing their jobs.
+    pub fn check_stopping(&self) -> Result<(), Error> {
+        self.jobs.stopping().check()
+    }
+
+    /// Returns [`Error::InternalCommunication`] if the workers have been told
+    /// to stop without finishing their jobs, or if `database`'s jobs have
+    /// been cancelled because it is being deleted.
+    pub fn check_cancelled(&self, database: &str) -> Result<(), Error> {
+        self.check_stopping()?;
+        if self.statuses.read().cancelled_databases.contains(database) {
+            Err(Error::InternalCommunication)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Forgets all statuses tracked for `database`, ensuring a database
+    /// created later with the same name doesn't reuse them.
+    pub fn forget_database(&self, database: &str) {
+        let mut statuses = self.statuses.write();
+        statuses
+            .completed_integrity_checks
+            .retain(|(name, _, _)| **name != database);
+        statuses
+            .key_value_expiration_loads
+            .retain(|name, _| **name != database);
+        statuses
+            .view_update_last_status
+            .retain(|(name, _, _), _| **name != database);
+        statuses.cancelled_databases.remove(database);
+    }
+
     pub fn spawn_key_value_expiration_loader(
         &self,
         database: &Database,
@@ -190,11 +384,28 @@ impl TaskManager {
         } else {
             Some(self.jobs.lookup_or_enqueue(ExpirationLoader {
                 database: database.clone(),
-                launched_at: Timestamp::now(),
+                batch_size: self.expiration_load_batch_size,
             }))
         }
     }
 
+    pub fn spawn_quota_reconciliation(&self, database: &Database) -> Handle<(), Error> {
+        self.jobs.lookup_or_enqueue(QuotaReconciler {
+            database: database.clone(),
+        })
+    }
+
+    pub fn scrub(
+        &self,
+        database: Database,
+        duration: Option<Duration>,
+    ) -> Result<ScrubReport, Error> {
+        Ok(self
+            .jobs
+            .lookup_or_enqueue(Scrubber { database, duration })
+            .receive()??)
+    }
+
     pub fn spawn_compact_target(
         &self,
         database: Database,
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::tasks::handle::Handle;
+use crate::Error;
+
+/// Allows blocking work to detect that the caller waiting for its result is no
+/// longer interested in it, such as when an async query's future is dropped.
+#[derive(Debug, Clone)]
+pub struct Cancellation(flume::Receiver<()>);
+
+/// Cancels the paired [`Cancellation`] when dropped.
+#[derive(Debug)]
+#[must_use = "the cancellation is signaled when this guard is dropped"]
+pub struct CancelOnDrop {
+    // The sender is never used to send. Dropping it disconnects the channel,
+    // which wakes up any callers waiting in `Cancellation::receive()`.
+    _sender: flume::Sender<()>,
+}
+
+impl Cancellation {
+    /// Returns a new cancellation and the guard that signals it.
+    pub fn new() -> (Self, CancelOnDrop) {
+        let (sender, receiver) = flume::bounded(1);
+        (Self(receiver), CancelOnDrop { _sender: sender })
+    }
+
+    /// Returns true if the paired [`CancelOnDrop`] has been dropped.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_disconnected()
+    }
+
+    /// Returns [`Error::InternalCommunication`] if this has been cancelled,
+    /// since there is no longer anyone to communicate a result to.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::InternalCommunication)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Receives the next value from `receiver`, returning `None` once this is
+    /// cancelled or `receiver` is disconnected.
+    pub fn recv<T>(&self, receiver: &flume::Receiver<T>) -> Option<T> {
+        flume::Selector::new()
+            .recv(receiver, Result::ok)
+            .recv(&self.0, |_| None)
+            .wait()
+    }
+
+    /// Waits for `job` to complete, returning early with
+    /// [`Error::InternalCommunication`] if this is cancelled first. The job
+    /// itself continues running, as it may be shared with other callers.
+    pub fn receive<T, E>(&self, job: Handle<T, E>) -> Result<Result<T, Arc<E>>, Error>
+    where
+        T: Send + Sync + 'static,
+        E: Send + Sync + 'static,
+    {
+        flume::Selector::new()
+            .recv(&job.receiver, |result| result.map_err(Error::from))
+            .recv(&self.0, |_| Err(Error::InternalCommunication))
+            .wait()
+    }
+
+    /// Behaves like [`Self::receive()`], returning `None` if `job` hasn't
+    /// completed by `deadline`.
+    pub fn receive_until<T, E>(
+        &self,
+        job: Handle<T, E>,
+        deadline: Instant,
+    ) -> Result<Option<Result<T, Arc<E>>>, Error>
+    where
+        T: Send + Sync + 'static,
+        E: Send + Sync + 'static,
+    {
+        flume::Selector::new()
+            .recv(&job.receiver, |result| {
+                result.map(Some).map_err(Error::from)
+            })
+            .recv(&self.0, |_| Err(Error::InternalCommunication))
+            .wait_deadline(deadline)
+            .unwrap_or(Ok(None))
+    }
+}
@@ -8,8 +8,8 @@ use crate::database::keyvalue::KEY_TREE;
 use crate::database::{document_tree_name, DatabaseNonBlocking};
 use crate::tasks::{Job, Keyed, Task};
 use crate::views::{
-    view_document_map_tree_name, view_entries_tree_name, view_invalidated_docs_tree_name,
-    view_versions_tree_name,
+    view_dependents_tree_name, view_document_map_tree_name, view_entries_tree_name,
+    view_invalidated_docs_tree_name, view_prefix_reductions_tree_name, view_versions_tree_name,
 };
 use crate::{Database, Error};
 
@@ -49,6 +49,12 @@ pub struct Compaction {
     target: Target,
 }
 
+impl Compaction {
+    pub fn database_name(&self) -> &str {
+        &self.database_name
+    }
+}
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum Target {
     VersionedTree(String),
@@ -71,8 +77,8 @@ impl Target {
             Target::KeyValue => compact_tree::<Unversioned, _>(database, KEY_TREE),
             Target::Database => {
                 let mut trees = Vec::new();
-                for collection in database.schematic().collections() {
-                    gather_collection_trees(database, collection, &mut trees);
+                for collection in compaction_order(database)? {
+                    gather_collection_trees(database, &collection, &mut trees);
                 }
                 trees.push(Target::KeyValue);
                 compact_trees(database, trees)
@@ -97,6 +103,59 @@ impl Keyed<Task> for Compactor {
     }
 }
 
+/// Returns the collections of `database` in the order they are compacted.
+/// Collections are ordered by their
+/// [`CompactionPriority::weight()`](crate::config::CompactionPriority::weight)
+/// multiplied by their estimated fragmentation. Ties are ordered by priority,
+/// and then by name.
+pub(crate) fn compaction_order(database: &Database) -> Result<Vec<CollectionName>, Error> {
+    let mut collections = database
+        .schematic()
+        .collections()
+        .map(|collection| {
+            let priority = database.storage().compaction_priority(collection);
+            let score =
+                f64::from(priority.weight()) * estimated_fragmentation(database, collection)?;
+            Ok((score, priority, collection.clone()))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    collections.sort_by(
+        |(a_score, a_priority, a_name), (b_score, b_priority, b_name)| {
+            b_score
+                .total_cmp(a_score)
+                .then_with(|| b_priority.cmp(a_priority))
+                .then_with(|| a_name.cmp(b_name))
+        },
+    );
+    Ok(collections
+        .into_iter()
+        .map(|(_, _, collection)| collection)
+        .collect())
+}
+
+/// Estimates the fraction of the collection's document file that isn't used
+/// by the current versions of its documents.
+fn estimated_fragmentation(database: &Database, collection: &CollectionName) -> Result<f64, Error> {
+    let tree_name = document_tree_name(collection);
+    let file_size = match database
+        .storage
+        .instance
+        .tree_file_length(database.name(), &tree_name)?
+    {
+        Some(file_size) if file_size > 0 => file_size,
+        // Collections that haven't been written to have no file to compact.
+        _ => return Ok(0.),
+    };
+
+    let stats = database
+        .roots()
+        .tree(database.collection_tree::<Versioned, _>(collection, tree_name)?)?
+        .reduce(&(..))?;
+    #[allow(clippy::cast_precision_loss)]
+    let in_use = stats.total_indexed_bytes as f64 / file_size as f64;
+    Ok((1. - in_use).clamp(0., 1.))
+}
+
 fn gather_collection_trees(
     database: &Database,
     collection: &CollectionName,
@@ -112,6 +171,14 @@ fn gather_collection_trees(
         trees.push(Target::UnversionedTree(view_invalidated_docs_tree_name(
             &name,
         )));
+        if view.caches_reduce_prefixes() {
+            trees.push(Target::UnversionedTree(view_prefix_reductions_tree_name(
+                &name,
+            )));
+        }
+        if !view.dependencies().is_empty() {
+            trees.push(Target::UnversionedTree(view_dependents_tree_name(&name)));
+        }
     }
 }
 
@@ -6,12 +6,14 @@ use parking_lot::RwLock;
 
 use crate::tasks::handle::{Handle, Id};
 use crate::tasks::traits::Executable;
-use crate::tasks::{Job, Keyed};
+use crate::tasks::{Cancellation, Job, Keyed};
 
 pub(crate) mod jobs;
 mod managed_job;
 pub(crate) use managed_job::ManagedJob;
 
+pub use self::jobs::ThrottleStatus;
+
 #[cfg(test)]
 mod tests;
 
@@ -32,6 +34,9 @@ where
     #[cfg(test)]
     pub fn enqueue<J: Job + 'static>(&self, job: J) -> Handle<J::Output, J::Error> {
         let mut jobs = self.jobs.write();
+        if jobs.stopped {
+            return jobs.stopped_task_handle();
+        }
         jobs.enqueue(job, None, self.clone())
     }
 
@@ -39,14 +44,136 @@ where
     /// currently running. If another job is already running that matches, a
     /// clone of that [`Handle`] will be returned. When the job finishes, all
     /// [`Handle`] clones will be notified with a copy of the result.
+    ///
+    /// Once [`stop()`](Self::stop) has been called, `job` is dropped and the
+    /// returned handle is disconnected without a result.
     pub fn lookup_or_enqueue<J: Keyed<Key>>(
         &self,
         job: J,
     ) -> Handle<<J as Job>::Output, <J as Job>::Error> {
         let mut jobs = self.jobs.write();
+        if jobs.stopped {
+            let handle = jobs.stopped_task_handle();
+            // The job may hold the last reference to something whose drop
+            // uses this manager.
+            drop(jobs);
+            drop(job);
+            return handle;
+        }
         jobs.lookup_or_enqueue(job, self.clone())
     }
 
+    /// Behaves like [`lookup_or_enqueue()`](Self::lookup_or_enqueue), except
+    /// that the number of throttled jobs running at once is limited. See
+    /// [`set_throttle_limit()`](Self::set_throttle_limit).
+    pub fn lookup_or_enqueue_throttled<J: Keyed<Key>>(
+        &self,
+        job: J,
+        priority: Priority,
+    ) -> Handle<<J as Job>::Output, <J as Job>::Error> {
+        let mut jobs = self.jobs.write();
+        if jobs.stopped {
+            let handle = jobs.stopped_task_handle();
+            drop(jobs);
+            drop(job);
+            return handle;
+        }
+        jobs.lookup_or_enqueue_throttled(job, priority, self.clone())
+    }
+
+    /// Sets the maximum number of jobs enqueued using
+    /// [`lookup_or_enqueue_throttled()`](Self::lookup_or_enqueue_throttled)
+    /// that can run at once. By default, throttled jobs aren't limited.
+    pub fn set_throttle_limit(&self, limit: usize) {
+        self.jobs.write().set_throttle_limit(limit);
+    }
+
+    /// Returns the status of the jobs enqueued using
+    /// [`lookup_or_enqueue_throttled()`](Self::lookup_or_enqueue_throttled).
+    pub fn throttle_status(&self) -> ThrottleStatus {
+        self.jobs.read().throttle_status()
+    }
+
+    /// Returns a cancellation that is signaled once this manager begins
+    /// stopping without waiting for queued jobs. Long-running jobs check it
+    /// to exit early.
+    pub fn stopping(&self) -> Cancellation {
+        self.jobs.read().stopping.clone()
+    }
+
+    /// Prevents new jobs from being enqueued and stops every worker, blocking
+    /// until the workers have exited.
+    ///
+    /// If `wait` is true, jobs that have already been queued are executed
+    /// first. Otherwise, queued jobs are discarded, disconnecting their
+    /// handles, and running jobs are signaled through
+    /// [`stopping()`](Self::stopping).
+    pub fn stop(&self, wait: bool) {
+        let discarded = {
+            let mut jobs = self.jobs.write();
+            jobs.stopped = true;
+            if wait {
+                Vec::new()
+            } else {
+                drop(jobs.stop_workers.take());
+                jobs.drain_queue()
+            }
+        };
+        for mut job in discarded {
+            job.cancel();
+        }
+
+        while self.wait_for_keyed_jobs(|_| true) {}
+
+        let workers = {
+            let mut jobs = self.jobs.write();
+            drop(jobs.stop_workers.take());
+            std::mem::take(&mut jobs.workers)
+        };
+        // Stopping can be triggered by a worker dropping the last reference
+        // to the storage, and a thread can't join itself.
+        let current_thread = std::thread::current().id();
+        for worker in workers {
+            if worker.thread().id() != current_thread && worker.join().is_err() {
+                log::error!("a background task worker panicked");
+                self.jobs.write().panicked_workers += 1;
+            }
+        }
+    }
+
+    /// Returns the number of workers found to have panicked while stopping.
+    #[cfg(test)]
+    pub fn panicked_workers(&self) -> usize {
+        self.jobs.read().panicked_workers
+    }
+
+    /// Blocks until every queued or running keyed job whose key matches
+    /// `predicate` has completed. Returns false if there were no matching
+    /// jobs to wait for.
+    pub fn wait_for_keyed_jobs<F: Fn(&Key) -> bool>(&self, predicate: F) -> bool {
+        let listeners = {
+            let mut jobs = self.jobs.write();
+            jobs.listen_for_completion(predicate)
+        };
+        let waited = !listeners.is_empty();
+        for listener in listeners {
+            // The sender is dropped without sending when the job completes.
+            drop(listener.recv());
+        }
+        waited
+    }
+
+    /// Discards every queued keyed job whose key matches `predicate`,
+    /// disconnecting their handles, and then blocks until the matching jobs
+    /// that were already executing have completed.
+    pub fn cancel_keyed_jobs<F: Fn(&Key) -> bool>(&self, predicate: F) {
+        let discarded = self.jobs.write().drain_keyed_jobs(&predicate);
+        for mut job in discarded {
+            job.cancel();
+        }
+        self.wait_for_keyed_jobs(predicate);
+    }
+
     fn job_completed<T: Clone + Send + Sync + 'static, E: Send + Sync + 'static>(
         &self,
         id: Id,
@@ -60,19 +187,32 @@ where
     /// Spawns a worker. In general, you shouldn't need to call this function
     /// directly.
     pub fn spawn_worker(&self) {
-        let receiver = {
-            let jobs = self.jobs.read();
-            jobs.queue()
-        };
-        std::thread::Builder::new()
+        let mut jobs = self.jobs.write();
+        if jobs.stopped {
+            return;
+        }
+        let receiver = jobs.queue();
+        let stopping = jobs.stopping.clone();
+        let worker = std::thread::Builder::new()
             .name(String::from("bonsaidb-tasks"))
-            .spawn(move || worker_thread(&receiver))
+            .spawn(move || worker_thread(&receiver, &stopping))
             .unwrap();
+        jobs.workers.push(worker);
     }
 }
 
-fn worker_thread(receiver: &flume::Receiver<Box<dyn Executable>>) {
-    while let Ok(mut job) = receiver.recv() {
+/// The order in which throttled jobs are queued.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Priority {
+    /// The job is queued after the other pending jobs.
+    Normal,
+    /// The job is queued before the other pending jobs, because something is
+    /// waiting for its result.
+    High,
+}
+
+fn worker_thread(receiver: &flume::Receiver<Box<dyn Executable>>, stopping: &Cancellation) {
+    while let Some(mut job) = stopping.recv(receiver) {
         job.execute();
     }
 }
@@ -1,21 +1,29 @@
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::thread::JoinHandle;
 
 use flume::{Receiver, Sender};
 
 use crate::tasks::handle::{Handle, Id};
-use crate::tasks::manager::{ManagedJob, Manager};
+use crate::tasks::manager::{ManagedJob, Manager, Priority};
 use crate::tasks::traits::Executable;
-use crate::tasks::{Job, Keyed};
+use crate::tasks::{CancelOnDrop, Cancellation, Job, Keyed};
 
 pub struct Jobs<Key> {
     last_task_id: u64,
     result_senders: HashMap<Id, Vec<Box<dyn AnySender>>>,
     keyed_jobs: HashMap<Key, Id>,
+    completion_listeners: HashMap<Id, Vec<Sender<()>>>,
     queuer: Sender<Box<dyn Executable>>,
     queue: Receiver<Box<dyn Executable>>,
+    throttle: Throttle,
+    pub(crate) stopped: bool,
+    pub(crate) workers: Vec<JoinHandle<()>>,
+    pub(crate) stop_workers: Option<CancelOnDrop>,
+    pub(crate) stopping: Cancellation,
+    pub(crate) panicked_workers: usize,
 }
 
 impl<Key> Debug for Jobs<Key>
@@ -27,8 +35,12 @@ where
             .field("last_task_id", &self.last_task_id)
             .field("result_senders", &self.result_senders.len())
             .field("keyed_jobs", &self.keyed_jobs)
+            .field("completion_listeners", &self.completion_listeners.len())
             .field("queuer", &self.queuer)
             .field("queue", &self.queue)
+            .field("throttle", &self.throttle)
+            .field("stopped", &self.stopped)
+            .field("workers", &self.workers.len())
             .finish()
     }
 }
@@ -36,13 +48,21 @@ where
 impl<Key> Default for Jobs<Key> {
     fn default() -> Self {
         let (queuer, queue) = flume::unbounded();
+        let (stopping, stop_workers) = Cancellation::new();
 
         Self {
             last_task_id: 0,
             result_senders: HashMap::new(),
             keyed_jobs: HashMap::new(),
+            completion_listeners: HashMap::new(),
             queuer,
             queue,
+            throttle: Throttle::default(),
+            stopped: false,
+            workers: Vec::new(),
+            stop_workers: Some(stop_workers),
+            stopping,
+            panicked_workers: 0,
         }
     }
 }
@@ -61,8 +81,7 @@ where
         key: Option<Key>,
         manager: Manager<Key>,
     ) -> Handle<J::Output, J::Error> {
-        self.last_task_id = self.last_task_id.wrapping_add(1);
-        let id = Id(self.last_task_id);
+        let id = self.next_id();
         self.queuer
             .send(Box::new(ManagedJob {
                 id,
@@ -75,6 +94,68 @@ where
         self.create_new_task_handle(id)
     }
 
+    fn next_id(&mut self) -> Id {
+        self.last_task_id = self.last_task_id.wrapping_add(1);
+        Id(self.last_task_id)
+    }
+
+    /// Returns a handle that is disconnected without a result, which is
+    /// returned in place of enqueueing a job once the manager has stopped.
+    pub fn stopped_task_handle<T: Send + Sync + 'static, E: Send + Sync + 'static>(
+        &mut self,
+    ) -> Handle<T, E> {
+        let (_, receiver) = flume::bounded(1);
+        Handle {
+            id: self.next_id(),
+            receiver,
+        }
+    }
+
+    /// Removes every job from the queue without executing it, including
+    /// throttled jobs that are waiting to be queued.
+    pub fn drain_queue(&mut self) -> Vec<Box<dyn Executable>> {
+        let mut drained = self.queue.drain().collect::<Vec<_>>();
+        drained.extend(self.throttle.pending.drain(..).map(|(_, job)| job));
+        drained
+    }
+
+    /// Removes every queued keyed job whose key matches `predicate` without
+    /// executing it, including throttled jobs that are waiting to be queued.
+    /// Jobs that are already executing are unaffected.
+    pub fn drain_keyed_jobs<F: Fn(&Key) -> bool>(
+        &mut self,
+        predicate: F,
+    ) -> Vec<Box<dyn Executable>> {
+        let ids = self
+            .keyed_jobs
+            .iter()
+            .filter(|(key, _)| predicate(key))
+            .map(|(_, id)| *id)
+            .collect::<HashSet<_>>();
+        if ids.is_empty() {
+            return Vec::new();
+        }
+
+        let mut drained = Vec::new();
+        for job in self.queue.drain().collect::<Vec<_>>() {
+            if ids.contains(&job.id()) {
+                drained.push(job);
+            } else {
+                self.queuer.send(job).unwrap();
+            }
+        }
+        let mut index = 0;
+        while index < self.throttle.pending.len() {
+            if ids.contains(&self.throttle.pending[index].0) {
+                let (_, job) = self.throttle.pending.remove(index).expect("index checked");
+                drained.push(job);
+            } else {
+                index += 1;
+            }
+        }
+        drained
+    }
+
     pub fn create_new_task_handle<T: Send + Sync + 'static, E: Send + Sync + 'static>(
         &mut self,
         id: Id,
@@ -101,17 +182,97 @@ where
         }
     }
 
+    /// Behaves like [`Self::lookup_or_enqueue()`], except that the job is only
+    /// queued once fewer than the throttle's limit of throttled jobs are
+    /// queued or running.
+    ///
+    /// Pending jobs are queued in order, except that [`Priority::High`] jobs
+    /// are queued before [`Priority::Normal`] jobs. Requesting a pending job
+    /// again with [`Priority::High`] moves it ahead of the other pending jobs.
+    pub fn lookup_or_enqueue_throttled<J: Keyed<Key>>(
+        &mut self,
+        job: J,
+        priority: Priority,
+        manager: Manager<Key>,
+    ) -> Handle<<J as Job>::Output, <J as Job>::Error> {
+        let key = job.key();
+        if let Some(&id) = self.keyed_jobs.get(&key) {
+            if priority == Priority::High {
+                self.throttle.promote(id);
+            }
+            return self.create_new_task_handle(id);
+        }
+
+        let id = self.next_id();
+        let job = Box::new(ManagedJob {
+            id,
+            job,
+            manager,
+            key: Some(key.clone()),
+        });
+        match priority {
+            Priority::High => self.throttle.pending.push_front((id, job)),
+            Priority::Normal => self.throttle.pending.push_back((id, job)),
+        }
+        self.keyed_jobs.insert(key, id);
+        let handle = self.create_new_task_handle(id);
+        self.queue_throttled_jobs();
+        handle
+    }
+
+    /// Sets the maximum number of throttled jobs that can be queued or
+    /// running at once. A limit of 0 is treated as 1.
+    pub fn set_throttle_limit(&mut self, limit: usize) {
+        self.throttle.limit = limit.max(1);
+        self.queue_throttled_jobs();
+    }
+
+    /// Returns the status of the jobs enqueued using
+    /// [`Self::lookup_or_enqueue_throttled()`].
+    pub fn throttle_status(&self) -> ThrottleStatus {
+        ThrottleStatus {
+            running: self.throttle.running.len(),
+            pending: self.throttle.pending.len(),
+            limit: self.throttle.limit,
+        }
+    }
+
+    fn queue_throttled_jobs(&mut self) {
+        while self.throttle.running.len() < self.throttle.limit {
+            let Some((id, job)) = self.throttle.pending.pop_front() else {
+                break;
+            };
+            self.throttle.running.insert(id);
+            self.queuer.send(job).unwrap();
+        }
+    }
+
+    /// Returns a receiver for each queued or running keyed job whose key
+    /// matches `predicate`. Each receiver is disconnected once its job has
+    /// completed.
+    pub fn listen_for_completion<F: Fn(&Key) -> bool>(
+        &mut self,
+        predicate: F,
+    ) -> Vec<Receiver<()>> {
+        let listeners = &mut self.completion_listeners;
+        self.keyed_jobs
+            .iter()
+            .filter(|(key, _)| predicate(key))
+            .map(|(_, id)| {
+                let (sender, receiver) = flume::bounded(1);
+                listeners.entry(*id).or_default().push(sender);
+                receiver
+            })
+            .collect()
+    }
+
     pub fn job_completed<T: Clone + Send + Sync + 'static, E: Send + Sync + 'static>(
         &mut self,
         id: Id,
         key: Option<&Key>,
         result: Result<T, E>,
     ) {
-        if let Some(key) = key {
-            self.keyed_jobs.remove(key);
-        }
-
-        if let Some(senders) = self.result_senders.remove(&id) {
+        if let Some(senders) = self.job_finished(id, key) {
             let result = result.map_err(Arc::new);
             for sender_handle in senders {
                 let sender = sender_handle
@@ -122,6 +283,74 @@ where
             }
         }
     }
+
+    /// Forgets a job that will never execute. Its handles are disconnected
+    /// without receiving a result.
+    pub fn job_cancelled(&mut self, id: Id, key: Option<&Key>) {
+        self.job_finished(id, key);
+    }
+
+    fn job_finished(&mut self, id: Id, key: Option<&Key>) -> Option<Vec<Box<dyn AnySender>>> {
+        if let Some(key) = key {
+            self.keyed_jobs.remove(key);
+        }
+        if self.throttle.running.remove(&id) {
+            self.queue_throttled_jobs();
+        }
+        self.completion_listeners.remove(&id);
+        self.result_senders.remove(&id)
+    }
+}
+
+/// Throttled jobs that have been queued or are waiting to be queued.
+struct Throttle {
+    limit: usize,
+    /// Jobs that have been queued, including jobs that are executing.
+    running: HashSet<Id>,
+    pending: VecDeque<(Id, Box<dyn Executable>)>,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self {
+            limit: usize::MAX,
+            running: HashSet::new(),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl Debug for Throttle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Throttle")
+            .field("limit", &self.limit)
+            .field("running", &self.running.len())
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+impl Throttle {
+    /// Moves the pending job `id` to the front of the pending jobs.
+    fn promote(&mut self, id: Id) {
+        if let Some(index) = self.pending.iter().position(|(pending, _)| *pending == id) {
+            let job = self.pending.remove(index).expect("index just found");
+            self.pending.push_front(job);
+        }
+    }
+}
+
+/// The status of a [`Manager`]'s throttled jobs.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ThrottleStatus {
+    /// The number of throttled jobs that have been queued or are executing.
+    pub running: usize,
+    /// The number of throttled jobs waiting for another throttled job to
+    /// complete before being queued.
+    pub pending: usize,
+    /// The maximum number of throttled jobs that are queued or executing at
+    /// once.
+    pub limit: usize,
 }
 
 pub trait AnySender: Any + Send + Sync {
@@ -18,10 +18,19 @@ where
     J: Job,
     Key: Clone + std::hash::Hash + Eq + Send + Sync + Debug + 'static,
 {
+    fn id(&self) -> Id {
+        self.id
+    }
+
     fn execute(&mut self) {
         let result = self.job.execute();
 
         self.manager
             .job_completed(self.id, self.key.as_ref(), result);
     }
+
+    fn cancel(&mut self) {
+        let mut jobs = self.manager.jobs.write();
+        jobs.job_cancelled(self.id, self.key.as_ref());
+    }
 }
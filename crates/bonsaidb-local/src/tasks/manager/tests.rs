@@ -1,8 +1,12 @@
 use std::convert::Infallible;
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
 
-use super::Manager;
+use parking_lot::Mutex;
+
+use super::{Manager, Priority, ThrottleStatus};
 use crate::tasks::{Job, Keyed};
 
 #[derive(Debug)]
@@ -65,3 +69,199 @@ fn keyed_simple() {
         assert_eq!(result.unwrap(), 1);
     }
 }
+
+/// Blocks until its manager begins stopping without waiting for jobs.
+#[derive(Debug)]
+struct WaitForStop {
+    key: usize,
+    started: flume::Sender<()>,
+    stopping: crate::tasks::Cancellation,
+}
+
+impl Job for WaitForStop {
+    type Error = Infallible;
+    type Output = ();
+
+    fn execute(&mut self) -> Result<Self::Output, Self::Error> {
+        drop(self.started.send(()));
+        let (_sender, never) = flume::bounded::<()>(1);
+        assert!(self.stopping.recv(&never).is_none());
+        Ok(())
+    }
+}
+
+impl Keyed<usize> for WaitForStop {
+    fn key(&self) -> usize {
+        self.key
+    }
+}
+
+#[test]
+fn stop_waits_for_queued_jobs() {
+    let manager = Manager::<usize>::default();
+    let handles = (0..10)
+        .map(|value| manager.lookup_or_enqueue(Echo(value)))
+        .collect::<Vec<_>>();
+    manager.spawn_worker();
+    manager.spawn_worker();
+    manager.stop(true);
+
+    for (value, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.receive().unwrap().unwrap(), value);
+    }
+    // Jobs can't be enqueued once stopped.
+    assert!(manager.lookup_or_enqueue(Echo(10)).receive().is_err());
+    assert!(manager.jobs.read().workers.is_empty());
+    assert_eq!(manager.panicked_workers(), 0);
+}
+
+#[test]
+fn stop_discards_queued_jobs() {
+    let manager = Manager::<usize>::default();
+    manager.spawn_worker();
+    let (started, started_receiver) = flume::bounded(1);
+    let running = manager.lookup_or_enqueue(WaitForStop {
+        key: 0,
+        started,
+        stopping: manager.stopping(),
+    });
+    started_receiver.recv().unwrap();
+    // The only worker is busy, so this job remains queued.
+    let queued = manager.lookup_or_enqueue(Echo(1));
+
+    manager.stop(false);
+
+    assert!(running.receive().unwrap().is_ok());
+    assert!(queued.receive().is_err());
+    assert!(manager.jobs.read().workers.is_empty());
+    assert_eq!(manager.panicked_workers(), 0);
+}
+
+#[test]
+fn cancel_keyed_jobs_discards_matching_jobs() {
+    let manager = Manager::<usize>::default();
+    manager.set_throttle_limit(1);
+    let cancelled = manager.lookup_or_enqueue(Echo(1));
+    let kept = manager.lookup_or_enqueue(Echo(2));
+    let throttled = manager.lookup_or_enqueue_throttled(Echo(3), Priority::Normal);
+    let pending = manager.lookup_or_enqueue_throttled(Echo(5), Priority::Normal);
+
+    // No workers are running, so every job is still queued.
+    manager.cancel_keyed_jobs(|key| key % 2 == 1);
+    manager.spawn_worker();
+
+    assert!(cancelled.receive().is_err());
+    assert_eq!(kept.receive().unwrap().unwrap(), 2);
+    assert!(throttled.receive().is_err());
+    assert!(pending.receive().is_err());
+    assert_eq!(manager.throttle_status().running, 0);
+}
+
+/// Records the order jobs execute in and the most jobs executing at once.
+#[derive(Debug)]
+struct Record {
+    key: usize,
+    log: Arc<Mutex<RecordLog>>,
+    duration: Duration,
+}
+
+#[derive(Debug, Default)]
+struct RecordLog {
+    executed: Vec<usize>,
+    running: usize,
+    max_running: usize,
+}
+
+impl Job for Record {
+    type Error = Infallible;
+    type Output = ();
+
+    fn execute(&mut self) -> Result<Self::Output, Self::Error> {
+        {
+            let mut log = self.log.lock();
+            log.executed.push(self.key);
+            log.running += 1;
+            log.max_running = log.max_running.max(log.running);
+        }
+        std::thread::sleep(self.duration);
+        self.log.lock().running -= 1;
+        Ok(())
+    }
+}
+
+impl Keyed<usize> for Record {
+    fn key(&self) -> usize {
+        self.key
+    }
+}
+
+#[test]
+fn throttled_jobs_respect_limit() {
+    let manager = Manager::<usize>::default();
+    manager.set_throttle_limit(2);
+    for _ in 0..6 {
+        manager.spawn_worker();
+    }
+
+    let log = Arc::new(Mutex::new(RecordLog::default()));
+    let handles = (0..8)
+        .map(|key| {
+            manager.lookup_or_enqueue_throttled(
+                Record {
+                    key,
+                    log: log.clone(),
+                    duration: Duration::from_millis(20),
+                },
+                Priority::Normal,
+            )
+        })
+        .collect::<Vec<_>>();
+    let status = manager.throttle_status();
+    assert!(status.running <= 2);
+    assert_eq!(status.running + status.pending, 8);
+
+    for handle in handles {
+        handle.receive().unwrap().unwrap();
+    }
+    let log = log.lock();
+    assert_eq!(log.executed.len(), 8);
+    assert!(log.max_running <= 2);
+    assert_eq!(
+        manager.throttle_status(),
+        ThrottleStatus {
+            running: 0,
+            pending: 0,
+            limit: 2
+        }
+    );
+}
+
+#[test]
+fn throttled_jobs_prioritize_and_coalesce() {
+    let manager = Manager::<usize>::default();
+    manager.set_throttle_limit(1);
+    manager.spawn_worker();
+
+    let log = Arc::new(Mutex::new(RecordLog::default()));
+    let record = |key| Record {
+        key,
+        log: log.clone(),
+        duration: Duration::from_millis(if key == 0 { 100 } else { 0 }),
+    };
+    // Occupy the only throttled slot, then fill the queue behind it.
+    let first = manager.lookup_or_enqueue_throttled(record(0), Priority::Normal);
+    let normal = (1..=3)
+        .map(|key| manager.lookup_or_enqueue_throttled(record(key), Priority::Normal))
+        .collect::<Vec<_>>();
+    // Requesting a pending job again returns the same job, and a high
+    // priority request moves it to the front of the queue.
+    let promoted = manager.lookup_or_enqueue_throttled(record(3), Priority::High);
+    assert_eq!(promoted.id, normal[2].id);
+    let urgent = manager.lookup_or_enqueue_throttled(record(4), Priority::High);
+    assert_eq!(manager.throttle_status().pending, 4);
+
+    for handle in [first, promoted, urgent].into_iter().chain(normal) {
+        handle.receive().unwrap().unwrap();
+    }
+    assert_eq!(log.lock().executed, vec![0, 4, 3, 1, 2]);
+}
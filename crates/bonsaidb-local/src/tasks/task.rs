@@ -1,5 +1,6 @@
 use std::borrow::Cow;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::tasks::compactor::Compaction;
 use crate::views::integrity_scanner::IntegrityScan;
@@ -11,4 +12,23 @@ pub enum Task {
     ViewMap(Map),
     Compaction(Compaction),
     ExpirationLoader(Arc<Cow<'static, str>>),
+    QuotaReconciliation(Arc<Cow<'static, str>>),
+    /// Scrubs a database, stopping after the duration if one is provided.
+    /// Scrubs with different durations produce different reports, so they
+    /// don't share results.
+    Scrub(Arc<Cow<'static, str>>, Option<Duration>),
+}
+
+impl Task {
+    /// Returns the name of the database this task operates on.
+    pub fn database(&self) -> &str {
+        match self {
+            Self::IntegrityScan(scan) => &scan.database,
+            Self::ViewMap(map) => &map.database,
+            Self::Compaction(compaction) => compaction.database_name(),
+            Self::ExpirationLoader(database)
+            | Self::QuotaReconciliation(database)
+            | Self::Scrub(database, _) => database,
+        }
+    }
 }
@@ -1,5 +1,7 @@
 use std::fmt::Debug;
 
+use crate::tasks::handle::Id;
+
 /// Defines a background job that can be queued and executed.
 pub trait Job: Debug + Send + Sync + 'static {
     /// The output type of the job.
@@ -21,5 +23,12 @@ where
 }
 
 pub trait Executable: Send + Sync + Debug {
+    /// Returns the id of this job.
+    fn id(&self) -> Id;
+
     fn execute(&mut self);
+
+    /// Notifies the manager that this job was removed from the queue without
+    /// being executed.
+    fn cancel(&mut self);
 }
@@ -0,0 +1,203 @@
+//! Utilities for testing code that uses local storage.
+//!
+//! This module is available when the `test-util` feature is enabled.
+
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bonsaidb_core::keyvalue::Timestamp;
+use bonsaidb_core::schema::View;
+pub use bonsaidb_core::test_util::TestDirectory;
+use parking_lot::Mutex;
+
+use crate::config::{Clock, KeyValueClock, StorageConfiguration};
+use crate::{Database, Error, Storage};
+
+/// A [`Storage`] stored in a unique temporary directory that is removed when
+/// dropped, including while unwinding from a panic.
+///
+/// ```rust
+/// use bonsaidb_core::connection::StorageConnection;
+/// use bonsaidb_local::config::Builder;
+/// use bonsaidb_local::test_util::TestStorage;
+///
+/// # fn test() -> Result<(), bonsaidb_local::Error> {
+/// let storage = TestStorage::open(TestStorage::configuration().with_schema::<()>()?)?;
+/// storage.create_database::<()>("test", false)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TestStorage {
+    // Fields are dropped in order, so the storage must be closed before its
+    // directory is removed.
+    storage: Storage,
+    configuration: StorageConfiguration,
+    directory: TestDirectory,
+}
+
+impl TestStorage {
+    /// Opens a memory-only storage using [`Self::configuration()`].
+    pub fn new() -> Result<Self, Error> {
+        Self::open(Self::configuration())
+    }
+
+    /// Opens a storage that writes to the disk using
+    /// [`Self::configuration()`]. Unlike a memory-only storage, its contents
+    /// survive [`Self::reopen()`].
+    pub fn on_disk() -> Result<Self, Error> {
+        let mut configuration = Self::configuration();
+        configuration.memory_only = false;
+        Self::open(configuration)
+    }
+
+    /// Returns a memory-only configuration with settings that keep tests
+    /// fast. Password hashing uses the smallest parameters Argon2 allows,
+    /// which must never be used outside of tests.
+    #[must_use]
+    pub fn configuration() -> StorageConfiguration {
+        let mut configuration = StorageConfiguration {
+            memory_only: true,
+            ..StorageConfiguration::default()
+        };
+        #[cfg(feature = "password-hashing")]
+        {
+            let mut params = argon2::ParamsBuilder::new();
+            params
+                .m_cost(argon2::Params::MIN_M_COST)
+                .t_cost(argon2::Params::MIN_T_COST)
+                .p_cost(1);
+            configuration.argon.hashers = 1;
+            configuration.argon.params = crate::config::ArgonParams::Params(params);
+        }
+        configuration
+    }
+
+    /// Opens a storage using `configuration`. The path of `configuration` is
+    /// replaced with a new, unique temporary directory.
+    pub fn open(mut configuration: StorageConfiguration) -> Result<Self, Error> {
+        static OPENED: AtomicU64 = AtomicU64::new(0);
+        let directory = TestDirectory::new(format!(
+            "bonsaidb-test-{}-{}",
+            std::process::id(),
+            OPENED.fetch_add(1, Ordering::Relaxed)
+        ));
+        configuration.path = Some(directory.0.clone());
+        let storage = Storage::open(configuration.clone())?;
+        Ok(Self {
+            storage,
+            configuration,
+            directory,
+        })
+    }
+
+    /// Closes and opens this storage again using the same configuration and
+    /// directory. Memory-only storage does not retain any data when reopened.
+    pub fn reopen(self) -> Result<Self, Error> {
+        let Self {
+            storage,
+            configuration,
+            directory,
+        } = self;
+        drop(storage);
+        let storage = Storage::open(configuration.clone())?;
+        Ok(Self {
+            storage,
+            configuration,
+            directory,
+        })
+    }
+
+    /// Returns the directory this storage is stored in.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.directory
+    }
+}
+
+impl Deref for TestStorage {
+    type Target = Storage;
+
+    fn deref(&self) -> &Self::Target {
+        &self.storage
+    }
+}
+
+/// A [`Clock`] that only changes when it is modified, allowing key-value
+/// expiration to be tested without waiting.
+///
+/// The key-value store removes expired keys before it performs each
+/// operation, so after advancing the clock, the next operation observes the
+/// expirations. Clones share the same time.
+///
+/// ```rust
+/// use bonsaidb_local::config::Builder;
+/// use bonsaidb_local::test_util::{TestClock, TestStorage};
+///
+/// let clock = TestClock::default();
+/// let configuration = TestStorage::configuration().key_value_clock(clock.clone().into());
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestClock(Arc<Mutex<Timestamp>>);
+
+impl TestClock {
+    /// Returns a clock whose current time is `now`.
+    #[must_use]
+    pub fn new(now: Timestamp) -> Self {
+        Self(Arc::new(Mutex::new(now)))
+    }
+
+    /// Moves this clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.0.lock();
+        *now = *now + duration;
+    }
+
+    /// Sets the current time of this clock to `now`.
+    pub fn set(&self, now: Timestamp) {
+        *self.0.lock() = now;
+    }
+}
+
+impl Default for TestClock {
+    /// Returns a clock whose current time is the system time.
+    fn default() -> Self {
+        Self::new(Timestamp::now())
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> Timestamp {
+        *self.0.lock()
+    }
+}
+
+impl From<TestClock> for KeyValueClock {
+    fn from(clock: TestClock) -> Self {
+        Self::Custom(Arc::new(clock))
+    }
+}
+
+/// Blocks until the view `V` has indexed every transaction committed to
+/// `database`, including any integrity check that is pending.
+pub fn wait_for_view_current<V: View + 'static>(
+    database: &Database,
+) -> Result<(), bonsaidb_core::Error> {
+    let view = database.schematic().view::<V>()?;
+    database
+        .storage
+        .instance
+        .tasks()
+        .update_view_if_needed(view, database, true)?;
+    Ok(())
+}
+
+/// Blocks until `storage` has no background tasks queued or running, such as
+/// view updates, integrity checks, compaction, or loading key-value
+/// expirations.
+pub fn wait_for_tasks(storage: &Storage) {
+    storage.instance.tasks().wait_for_idle();
+}
@@ -7,7 +7,7 @@ use bonsaidb_core::permissions::{Permissions, Statement};
 #[cfg(feature = "encryption")]
 use bonsaidb_core::test_util::EncryptedBasic;
 use bonsaidb_core::test_util::{
-    Basic, BasicByBrokenParentId, BasicByParentId, BasicCollectionWithNoViews,
+    Basic, BasicByBrokenParentId, BasicByCategory, BasicByParentId, BasicCollectionWithNoViews,
     BasicCollectionWithOnlyBrokenParentId, BasicSchema, HarnessTest, TestDirectory,
 };
 
@@ -189,8 +189,8 @@ fn integrity_checks() -> anyhow::Result<()> {
     // Connect with a new view and see the automatic update with a query
     {
         let db = Database::open::<BasicCollectionWithOnlyBrokenParentId>(config.clone())?;
-        // Give the integrity scanner time to run if it were to run (it shouldn't in this configuration).
-        std::thread::sleep(Duration::from_millis(100));
+        // Let the integrity scanner finish if it were to run (it shouldn't in this configuration).
+        crate::test_util::wait_for_tasks(&db.storage);
 
         // NoUpdate should return data without the validation checker having run.
         assert_eq!(
@@ -207,21 +207,58 @@ fn integrity_checks() -> anyhow::Result<()> {
 
     // Connect with a fixed view, and wait for the integrity scanner to work
     let db = Database::open::<Basic>(config.check_view_integrity_on_open(true))?;
-    for _ in 0_u8..100 {
-        std::thread::sleep(Duration::from_millis(1000));
-        if db
-            .view::<BasicByParentId>()
+    crate::test_util::wait_for_tasks(&db.storage);
+    assert_eq!(
+        db.view::<BasicByParentId>()
             .with_access_policy(AccessPolicy::NoUpdate)
             .with_key(&Some(1))
             .query()?
-            .len()
-            == 1
-        {
-            return Ok(());
-        }
+            .len(),
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn per_view_integrity_checks() -> anyhow::Result<()> {
+    let path = TestDirectory::new("per-view-integrity-checks");
+    let config = StorageConfiguration::new(&path);
+
+    // Add a doc with no views installed
+    {
+        let db = Database::open::<BasicCollectionWithNoViews>(config.clone())?;
+        let collection = db.collection::<BasicCollectionWithNoViews>();
+        collection.push(&Basic::default().with_parent_id(1).with_category("a"))?;
     }
 
-    unreachable!("Integrity checker didn't run in the allocated time")
+    // Opt all of Basic's views into the integrity check except for
+    // BasicByCategory, which has its own override.
+    let db = Database::open::<Basic>(
+        config
+            .check_view_integrity_on_open_for_collection::<Basic>(true)
+            .check_view_integrity_on_open_for_view(&BasicByCategory, false),
+    )?;
+    crate::test_util::wait_for_tasks(&db.storage);
+    assert_eq!(
+        db.view::<BasicByParentId>()
+            .with_access_policy(AccessPolicy::NoUpdate)
+            .with_key(&Some(1))
+            .query()?
+            .len(),
+        1
+    );
+    // The view that opted out should not have been checked.
+    assert_eq!(
+        db.view::<BasicByCategory>()
+            .with_access_policy(AccessPolicy::NoUpdate)
+            .query()?
+            .len(),
+        0
+    );
+    assert_eq!(db.view::<BasicByCategory>().query()?.len(), 1);
+
+    Ok(())
 }
 
 #[test]
@@ -248,9 +285,11 @@ fn encryption() -> anyhow::Result<()> {
 
     // By resetting the encryption key, we should be able to force an error in
     // decryption, which proves that the document was encrypted. To ensure the
-    // server starts up and generates a new key, we must delete the sealing key.
+    // server starts up and generates a new key, we must delete both the sealed
+    // master keys and the vault key.
 
     std::fs::remove_file(path.join("master-keys"))?;
+    std::fs::remove_dir_all(path.join("vault-keys"))?;
 
     let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
 
@@ -266,55 +305,3729 @@ fn encryption() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg(feature = "encryption")]
+fn missing_vault_keys() -> anyhow::Result<()> {
+    use crate::vault;
+
+    // The first time storage is opened, the keys are generated.
+    let path = TestDirectory::new("missing-vault-key");
+    drop(Storage::open(StorageConfiguration::new(&path))?);
+    assert!(path.join("master-keys").exists());
+    assert!(path.join("vault-keys").read_dir()?.next().is_some());
+
+    // Once encrypted data may exist, a missing vault key is an error rather
+    // than a reason to generate new keys.
+    std::fs::remove_dir_all(path.join("vault-keys"))?;
+    assert!(matches!(
+        Storage::open(StorageConfiguration::new(&path)),
+        Err(crate::Error::Vault(vault::Error::VaultKeyNotFound))
+    ));
+
+    // The same is true when the sealed master keys are missing.
+    let path = TestDirectory::new("missing-master-keys");
+    drop(Storage::open(StorageConfiguration::new(&path))?);
+    std::fs::remove_file(path.join("master-keys"))?;
+    assert!(matches!(
+        Storage::open(StorageConfiguration::new(&path)),
+        Err(crate::Error::Vault(vault::Error::MasterKeysNotFound))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn contains_document() -> anyhow::Result<()> {
+    let path = TestDirectory::new("contains-document");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+
+    let header = db.collection::<Basic>().push(&Basic::new("test"))?;
+    assert!(db.contains_document::<Basic, _>(&header.id)?);
+    assert!(!db.contains_document::<Basic, _>(&(header.id + 1))?);
+
+    db.collection::<Basic>().delete(&header)?;
+    assert!(!db.contains_document::<Basic, _>(&header.id)?);
+
+    Ok(())
+}
+
+#[test]
+fn estimated_row_count() -> anyhow::Result<()> {
+    use bonsaidb_core::schema::SerializedCollection;
+
+    let path = TestDirectory::new("estimated-row-count");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+    assert_eq!(db.estimated_row_count::<Basic>()?, 0);
+
+    let mut documents = Vec::new();
+    for index in 0..500 {
+        documents.push(Basic::new(index.to_string()).push_into(&db)?);
+    }
+    for document in documents.iter().step_by(5) {
+        document.delete(&db)?;
+    }
+
+    let exact = Basic::all(&db).count()?;
+    assert_eq!(exact, 400);
+    let estimate = db.estimated_row_count::<Basic>()?;
+    assert!(
+        exact.abs_diff(estimate) <= exact / 10,
+        "estimate {estimate} is not within 10% of {exact}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn document_history() -> anyhow::Result<()> {
+    use bonsaidb_core::schema::SerializedCollection;
+
+    let path = TestDirectory::new("document-history");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+
+    let first =
+        db.append_document_version::<Basic, _>(&1_u64, None, Basic::serialize(&Basic::new("a"))?)?;
+    // Versions of other documents written in between aren't included in the
+    // history.
+    db.append_document_version::<Basic, _>(&2_u64, None, Basic::serialize(&Basic::new("other"))?)?;
+    let second = db.append_document_version::<Basic, _>(
+        &1_u64,
+        Some(&first.revision),
+        Basic::serialize(&Basic::new("b"))?,
+    )?;
+    let third = db.append_document_version::<Basic, _>(
+        &1_u64,
+        Some(&second.revision),
+        Basic::serialize(&Basic::new("c"))?,
+    )?;
+
+    // Appending against a stale revision is a conflict.
+    match db.append_document_version::<Basic, _>(
+        &1_u64,
+        Some(&first.revision),
+        Basic::serialize(&Basic::new("stale"))?,
+    ) {
+        Err(bonsaidb_core::Error::DocumentConflict(_, header)) => assert_eq!(*header, third),
+        other => unreachable!("unexpected result: {other:?}"),
+    }
+    // As is expecting the document to not exist yet.
+    match db.append_document_version::<Basic, _>(
+        &1_u64,
+        None,
+        Basic::serialize(&Basic::new("stale"))?,
+    ) {
+        Err(bonsaidb_core::Error::DocumentConflict(_, header)) => assert_eq!(*header, third),
+        other => unreachable!("unexpected result: {other:?}"),
+    }
+
+    let history = db.document_history::<Basic, _>(&1_u64)?;
+    let headers = history
+        .iter()
+        .map(|doc| doc.header.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(headers, vec![first, second, third]);
+    let values = history
+        .iter()
+        .map(|doc| Basic::deserialize(&doc.contents).map(|basic| basic.value))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(values, vec!["a", "b", "c"]);
+
+    Ok(())
+}
+
+#[test]
+fn purge_document_history() -> anyhow::Result<()> {
+    use bonsaidb_core::schema::SerializedCollection;
+
+    /// Returns true if any file in `path` contains `needle`.
+    fn files_contain(path: &std::path::Path, needle: &[u8]) -> std::io::Result<bool> {
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let found = if entry.metadata()?.is_dir() {
+                files_contain(&entry.path(), needle)?
+            } else {
+                std::fs::read(entry.path())?
+                    .windows(needle.len())
+                    .any(|window| window == needle)
+            };
+            if found {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    let path = TestDirectory::new("purge-document-history");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+
+    Basic::new("erase-me-first")
+        .with_parent_id(1)
+        .insert_into(&1_u64, &db)?;
+    Basic::new("erase-me-second")
+        .with_parent_id(1)
+        .overwrite_into(&1_u64, &db)?;
+    Basic::new("kept")
+        .with_parent_id(1)
+        .insert_into(&2_u64, &db)?;
+    Basic::new("kept-updated")
+        .with_parent_id(1)
+        .overwrite_into(&2_u64, &db)?;
+    assert_eq!(db.view::<BasicByParentId>().query()?.len(), 2);
+    assert!(files_contain(&path, b"erase-me-first")?);
+
+    db.purge_document_history::<Basic, _>(&1_u64)?;
+
+    // The current version was deleted, and no history remains.
+    assert!(Basic::get(&1_u64, &db)?.is_none());
+    assert!(db.document_history::<Basic, _>(&1_u64)?.is_empty());
+    assert_eq!(db.view::<BasicByParentId>().query()?.len(), 1);
+    // Other documents retain their history.
+    let history = db.document_history::<Basic, _>(&2_u64)?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(
+        Basic::deserialize(&history[1].contents)?.value,
+        "kept-updated"
+    );
+    // The replacement and marker trees are removed once the purge finishes.
+    assert!(!db
+        .roots()
+        .tree_names()?
+        .iter()
+        .any(|name| name.starts_with("purge")));
+
+    // After compaction, the purged contents no longer exist on disk.
+    db.compact_collection::<Basic>()?;
+    assert!(!files_contain(&path, b"erase-me-first")?);
+    assert!(!files_contain(&path, b"erase-me-second")?);
+
+    // The collection remains writable.
+    Basic::new("new").insert_into(&3_u64, &db)?;
+    assert_eq!(Basic::all(&db).count()?, 2);
+
+    Ok(())
+}
+
+#[test]
+fn map_reduce() -> anyhow::Result<()> {
+    use std::collections::BTreeMap;
+
+    let path = TestDirectory::new("map-reduce");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+    for (category, parent_id) in [("a", 1), ("a", 2), ("b", 4)] {
+        db.collection::<Basic>().push(
+            &Basic::new("test")
+                .with_category(category)
+                .with_parent_id(parent_id),
+        )?;
+    }
+    db.collection::<Basic>()
+        .push(&Basic::new("uncategorized"))?;
+
+    let sums = db.map_reduce::<Basic, _, _, _, _>(
+        |basic| {
+            basic
+                .category
+                .clone()
+                .map(|category| (category, basic.parent_id.unwrap_or_default()))
+                .into_iter()
+                .collect()
+        },
+        |values| values.iter().sum(),
+    )?;
+    assert_eq!(
+        sums,
+        BTreeMap::from([(String::from("a"), 3), (String::from("b"), 4)])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn permission_group_inheritance() -> anyhow::Result<()> {
+    use bonsaidb_core::admin::{PermissionGroup, MAX_PERMISSION_GROUP_INHERITANCE_DEPTH};
+    use bonsaidb_core::connection::{HasSession, IdentityReference, StorageConnection};
+    use bonsaidb_core::permissions::bonsai::{bonsaidb_resource_name, BonsaiAction, ServerAction};
+    use bonsaidb_core::schema::SerializedCollection;
+
+    fn allowing(action: ServerAction) -> Statement {
+        Statement::for_any().allowing(&BonsaiAction::Server(action))
+    }
+
+    let path = TestDirectory::new("permission-group-inheritance");
+    let storage = Storage::open(StorageConfiguration::new(&path))?;
+    let admin = storage.admin();
+
+    // A diamond: "lead" inherits from "employees" through both "engineering"
+    // and "operations".
+    let employees = PermissionGroup::named("employees")
+        .with_group_ids([allowing(ServerAction::ListDatabases)])
+        .push_into(&admin)?;
+    let engineering = PermissionGroup::named("engineering")
+        .with_group_ids([allowing(ServerAction::CreateDatabase)])
+        .inheriting([employees.header.id])
+        .push_into(&admin)?;
+    let operations = PermissionGroup::named("operations")
+        .with_group_ids([allowing(ServerAction::DeleteDatabase)])
+        .inheriting([employees.header.id])
+        .push_into(&admin)?;
+    let lead = PermissionGroup::named("lead").push_into(&admin)?;
+    assert!(PermissionGroup::add_inherited_group(
+        "lead",
+        "engineering",
+        &admin
+    )?);
+    assert!(PermissionGroup::add_inherited_group(
+        lead.header.id,
+        operations.header.id,
+        &admin
+    )?);
+    assert!(!PermissionGroup::add_inherited_group(
+        "lead",
+        "operations",
+        &admin
+    )?);
+
+    let mut resolved = PermissionGroup::resolve([lead.header.id], &admin)?
+        .into_iter()
+        .map(|group| group.contents.name)
+        .collect::<Vec<_>>();
+    resolved.sort();
+    assert_eq!(resolved, ["employees", "engineering", "lead", "operations"]);
+
+    let permissions = PermissionGroup::resolved_permissions("lead", &admin)?;
+    for action in [
+        ServerAction::ListDatabases,
+        ServerAction::CreateDatabase,
+        ServerAction::DeleteDatabase,
+    ] {
+        assert!(permissions.allowed_to(bonsaidb_resource_name(), &BonsaiAction::Server(action)));
+    }
+
+    // Users are granted the permissions of inherited groups.
+    storage.create_user("user")?;
+    storage.add_permission_group_to_user("user", engineering.header.id)?;
+    let user = storage.assume_identity(IdentityReference::user("user")?)?;
+    assert!(user.allowed_to(
+        bonsaidb_resource_name(),
+        &BonsaiAction::Server(ServerAction::ListDatabases)
+    ));
+    assert!(!user.allowed_to(
+        bonsaidb_resource_name(),
+        &BonsaiAction::Server(ServerAction::DeleteDatabase)
+    ));
+
+    // Cycles can't be created through `add_inherited_group`.
+    assert!(matches!(
+        PermissionGroup::add_inherited_group("employees", "lead", &admin),
+        Err(bonsaidb_core::Error::PermissionGroupInheritanceCycle)
+    ));
+    assert!(matches!(
+        PermissionGroup::add_inherited_group("employees", "employees", &admin),
+        Err(bonsaidb_core::Error::PermissionGroupInheritanceCycle)
+    ));
+
+    // A cycle stored directly is still resolved.
+    let mut employees = PermissionGroup::get(&employees.header.id, &admin)?.unwrap();
+    employees.contents.inherits.push(lead.header.id);
+    employees.update(&admin)?;
+    assert_eq!(
+        PermissionGroup::resolve([employees.header.id], &admin)?.len(),
+        4
+    );
+    assert!(PermissionGroup::remove_inherited_group(
+        "employees",
+        "lead",
+        &admin
+    )?);
+    assert!(!PermissionGroup::remove_inherited_group(
+        "employees",
+        "lead",
+        &admin
+    )?);
+
+    // Inheritance that is too deep is an error.
+    let mut parent = employees.header.id;
+    for depth in 0..MAX_PERMISSION_GROUP_INHERITANCE_DEPTH {
+        parent = PermissionGroup::named(format!("depth-{depth}"))
+            .inheriting([parent])
+            .push_into(&admin)?
+            .header
+            .id;
+    }
+    assert_eq!(
+        PermissionGroup::resolve([parent], &admin)?.len(),
+        MAX_PERMISSION_GROUP_INHERITANCE_DEPTH + 1
+    );
+    let too_deep = PermissionGroup::named("too-deep")
+        .inheriting([parent])
+        .push_into(&admin)?;
+    assert!(matches!(
+        PermissionGroup::resolve([too_deep.header.id], &admin),
+        Err(bonsaidb_core::Error::PermissionGroupInheritanceTooDeep)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn subscriber_limits() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::{IdentityReference, StorageConnection};
+    use bonsaidb_core::pubsub::PubSub;
+
+    let path = TestDirectory::new("subscriber-limits");
+    let storage = Storage::open(
+        StorageConfiguration::new(&path)
+            .with_schema::<()>()?
+            .authenticated_permissions(Permissions::allow_all())
+            .max_subscribers_per_session(2)
+            .max_subscribers(3),
+    )?;
+    storage.create_database::<()>("pubsub", false)?;
+    storage.create_user("user")?;
+
+    let first_session = storage
+        .assume_identity(IdentityReference::user("user")?)?
+        .database::<()>("pubsub")?;
+    let first = first_session.create_subscriber()?;
+    let _second = first_session.create_subscriber()?;
+    assert!(matches!(
+        first_session.create_subscriber(),
+        Err(bonsaidb_core::Error::TooManySubscribers)
+    ));
+
+    // Dropping a subscriber allows the session to create another.
+    drop(first);
+    let _third = first_session.create_subscriber()?;
+
+    // Other sessions have their own limit, but all subscribers count towards
+    // the overall limit.
+    let second_session = storage
+        .assume_identity(IdentityReference::user("user")?)?
+        .database::<()>("pubsub")?;
+    let _fourth = second_session.create_subscriber()?;
+    assert!(matches!(
+        second_session.create_subscriber(),
+        Err(bonsaidb_core::Error::TooManySubscribers)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn composed_schema() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::schema::{Schema, SchemaName, Schematic, SerializedCollection};
+
+    #[derive(Debug)]
+    struct ComposedSchema;
+
+    impl Schema for ComposedSchema {
+        fn schema_name() -> SchemaName {
+            SchemaName::private("composed")
+        }
+
+        fn define_collections(schema: &mut Schematic) -> Result<(), bonsaidb_core::Error> {
+            schema.extend_with::<BasicSchema>()
+        }
+    }
+
+    let path = TestDirectory::new("composed-schema");
+    let db = Database::open::<ComposedSchema>(StorageConfiguration::new(&path))?;
+    Basic::new("child").with_parent_id(1).push_into(&db)?;
+    drop(db);
+
+    // Only the composed schema needs to be known to open the database.
+    let storage = Storage::open(StorageConfiguration::new(&path).with_schema::<ComposedSchema>()?)?;
+    let db = storage.database::<ComposedSchema>("default")?;
+    let mappings = db.view::<BasicByParentId>().with_key(&Some(1)).query()?;
+    assert_eq!(mappings.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn subscriber_drain() -> anyhow::Result<()> {
+    use bonsaidb_core::pubsub::{PubSub, Subscriber, TryReceiveError};
+
+    let path = TestDirectory::new("subscriber-drain");
+    let db = Database::open::<()>(StorageConfiguration::new(&path))?;
+    let subscriber = db.create_subscriber()?;
+    subscriber.subscribe_to(&"topic")?;
+    assert!(subscriber.drain().is_empty());
+
+    for payload in ["a", "b", "c"] {
+        db.publish(&"topic", &payload)?;
+    }
+    let payloads = subscriber
+        .drain()
+        .into_iter()
+        .map(|message| message.payload::<String>())
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(payloads, vec!["a", "b", "c"]);
+    assert!(matches!(
+        subscriber.try_receive(),
+        Err(TryReceiveError::Empty)
+    ));
+
+    db.publish(&"topic", &"d")?;
+    assert_eq!(subscriber.try_receive()?.payload::<String>()?, "d");
+
+    Ok(())
+}
+
+#[test]
+fn pubsub_statistics() -> anyhow::Result<()> {
+    use bonsaidb_core::pubsub::{PubSub, Subscriber};
+
+    let path = TestDirectory::new("pubsub-statistics");
+    let db = Database::open::<()>(StorageConfiguration::new(&path))?;
+    let storage = db.storage();
+
+    let dropped = db.create_subscriber()?;
+    dropped.subscribe_to(&"topic")?;
+    let subscriber = db.create_subscriber()?;
+    subscriber.subscribe_to(&"topic")?;
+    subscriber.subscribe_to(&"other")?;
+    drop(dropped);
+
+    // Dropped subscribers no longer receive messages.
+    db.publish(&"topic", &1_u32)?;
+    db.publish(&"topic", &2_u32)?;
+    db.publish_to_all([&"topic", &"other"], &3_u32)?;
+    db.publish(&"unsubscribed", &4_u32)?;
+    assert_eq!(subscriber.drain().len(), 3);
+
+    let statistics = storage.pubsub_statistics();
+    assert_eq!(statistics.messages_published, 4);
+    assert_eq!(statistics.messages_without_subscribers, 1);
+    assert_eq!(statistics.deliveries_attempted, 3);
+    assert_eq!(statistics.subscribers, 1);
+    let topic = &statistics.topics[&(String::from("default"), pot::to_vec(&"topic")?)];
+    assert_eq!(topic.messages_published, 3);
+    assert_eq!(topic.subscribers, 1);
+    assert_eq!(statistics.topics.len(), 2);
+
+    storage.reset_pubsub_statistics();
+    let statistics = storage.pubsub_statistics();
+    assert_eq!(statistics.messages_published, 0);
+    assert_eq!(statistics.deliveries_attempted, 0);
+    assert_eq!(statistics.subscribers, 1);
+
+    subscriber.unsubscribe_from(&"topic")?;
+    subscriber.unsubscribe_from(&"other")?;
+    assert!(storage.pubsub_statistics().topics.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn pubsub_is_isolated_by_database() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::pubsub::{database_topic, PubSub, Subscriber, TryReceiveError};
+
+    let path = TestDirectory::new("pubsub-isolation");
+    let storage = Storage::open(StorageConfiguration::new(&path).with_schema::<()>()?)?;
+    storage.create_database::<()>("a", false)?;
+    storage.create_database::<()>("b", false)?;
+    let a = storage.database::<()>("a")?;
+    let b = storage.database::<()>("b")?;
+
+    let a_subscriber = a.create_subscriber()?;
+    a_subscriber.subscribe_to(&"events")?;
+    let b_subscriber = b.create_subscriber()?;
+    b_subscriber.subscribe_to(&"events")?;
+
+    a.publish(&"events", &"from a")?;
+    let message = a_subscriber.try_receive()?;
+    assert_eq!(message.payload::<String>()?, "from a");
+    assert_eq!(message.topic::<String>()?, "events");
+    assert!(matches!(
+        b_subscriber.try_receive(),
+        Err(TryReceiveError::Empty)
+    ));
+
+    b.publish(&"events", &"from b")?;
+    assert_eq!(b_subscriber.try_receive()?.payload::<String>()?, "from b");
+    assert!(matches!(
+        a_subscriber.try_receive(),
+        Err(TryReceiveError::Empty)
+    ));
+
+    // Topics naming another database are still scoped to the subscriber's
+    // database.
+    let crafted = database_topic("b", &pot::to_vec(&"events")?);
+    a_subscriber.subscribe_to_bytes(crafted.clone())?;
+    b.publish(&"events", &"from b")?;
+    assert_eq!(b_subscriber.try_receive()?.payload::<String>()?, "from b");
+    assert!(matches!(
+        a_subscriber.try_receive(),
+        Err(TryReceiveError::Empty)
+    ));
+    a.publish_bytes(crafted, pot::to_vec(&"crafted")?)?;
+    assert_eq!(a_subscriber.try_receive()?.payload::<String>()?, "crafted");
+    assert!(matches!(
+        b_subscriber.try_receive(),
+        Err(TryReceiveError::Empty)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn databases_using_schema() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::schema::Schema;
+
+    let path = TestDirectory::new("databases-using-schema");
+    let storage = Storage::open(
+        StorageConfiguration::new(&path)
+            .with_schema::<BasicSchema>()?
+            .with_schema::<()>()?,
+    )?;
+    storage.create_database::<BasicSchema>("b", false)?;
+    storage.create_database::<BasicSchema>("a", false)?;
+    storage.create_database::<()>("unrelated", false)?;
+
+    assert_eq!(
+        storage.databases_using_schema(&BasicSchema::schema_name())?,
+        vec![String::from("a"), String::from("b")]
+    );
+
+    storage.delete_database("b")?;
+    assert_eq!(
+        storage.databases_using_schema(&BasicSchema::schema_name())?,
+        vec![String::from("a")]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn federated_query() -> anyhow::Result<()> {
+    use std::collections::BTreeMap;
+
+    use bonsaidb_core::connection::{Sort, StorageConnection};
+    use bonsaidb_core::schema::SerializedCollection;
+    use bonsaidb_core::test_util::BasicCount;
+
+    let path = TestDirectory::new("federated-query");
+    let storage = Storage::open(
+        StorageConfiguration::new(&path)
+            .with_schema::<BasicSchema>()?
+            .with_schema::<()>()?
+            .tasks_parallelization(2),
+    )?;
+    for (name, documents) in [("tenant-a", 1), ("tenant-b", 3), ("tenant-c", 0)] {
+        let db = storage.create_database::<BasicSchema>(name, false)?;
+        for _ in 0..documents {
+            Basic::new("test").push_into(&db)?;
+        }
+    }
+    storage.create_database::<()>("unrelated", false)?;
+
+    let mappings = storage.federated_query::<BasicSchema, BasicCount, ()>(
+        None,
+        Sort::Ascending,
+        None,
+        AccessPolicy::UpdateBefore,
+    )?;
+    let mut counts = BTreeMap::new();
+    for mapping in &mappings {
+        *counts.entry(mapping.database.as_str()).or_insert(0) += mapping.mapping.value;
+    }
+    assert_eq!(counts, BTreeMap::from([("tenant-a", 1), ("tenant-b", 3)]));
+    // Results are ordered by database name.
+    assert_eq!(mappings[0].database, "tenant-a");
+    assert!(mappings[1..]
+        .iter()
+        .all(|mapping| mapping.database == "tenant-b"));
+
+    Ok(())
+}
+
 #[test]
 fn expiration_after_close() -> anyhow::Result<()> {
+    use std::time::SystemTime;
+
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::keyvalue::{KeyValue, Timestamp};
+
+    use crate::test_util::{wait_for_tasks, TestClock, TestStorage};
+
+    let start = SystemTime::now();
+    let clock = TestClock::new(Timestamp::from(start));
+    let mut config = TestStorage::configuration()
+        .with_schema::<()>()?
+        .key_value_clock(clock.clone().into());
+    config.memory_only = false;
+    let storage = TestStorage::open(config)?;
+
+    // Set a key with an expiration, then close the storage. The key should
+    // exist after reopening, and then expire at the correct time.
+    let db = storage.create_database::<()>("expiration", false)?;
+    db.set_key("a", &0_u32)
+        .expire_at(start + Duration::from_secs(3))
+        .execute()?;
+    db.persist()?;
+    wait_for_tasks(&storage);
+    drop(db);
+
+    let storage = storage.reopen()?;
+    let db = storage.database::<()>("expiration")?;
+    // The expiration is loaded by a background task after opening.
+    wait_for_tasks(&storage);
+    assert!(db.get_key("a").query()?.is_some());
+
+    clock.advance(Duration::from_secs(4));
+    assert!(db.get_key("a").query()?.is_none());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "compression")]
+fn compression_dictionary() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::schema::{Collection, SerializedCollection};
+    use nebari::Vault;
+
+    use crate::config::{Compression, CompressionDictionary};
+
+    let samples = (0..20)
+        .map(|index| {
+            format!(
+                r#"{{"id":{index},"name":"customer {index}","email":"customer{index}@example.com","address":{{"street":"{index} Main Street","city":"Springfield"}},"active":true}}"#
+            )
+        })
+        .collect::<Vec<_>>();
+    let dictionary =
+        CompressionDictionary::train(samples.iter().map(|sample| sample.as_bytes()), 4096);
+
+    let plain_path = TestDirectory::new("compression-dictionary-plain");
+    let without_dictionary = Storage::open(
+        StorageConfiguration::new(&plain_path).default_compression(Compression::Lz4),
+    )?;
+    let plain = without_dictionary
+        .collection_tree_vault("tests", &Basic::collection_name())
+        .expect("compression enabled");
+    let path = TestDirectory::new("compression-dictionary");
+    let with_dictionary = Storage::open(
+        StorageConfiguration::new(&path)
+            .default_compression(Compression::Lz4)
+            .compression_dictionary::<Basic>(dictionary.clone())
+            .with_schema::<BasicSchema>()?,
+    )?;
+    let vault = with_dictionary
+        .collection_tree_vault("tests", &Basic::collection_name())
+        .expect("compression enabled");
+
+    let mut plain_total = 0;
+    let mut dictionary_total = 0;
+    for sample in &samples {
+        let compressed = plain.encrypt(sample.as_bytes())?;
+        assert_eq!(plain.decrypt(&compressed)?, sample.as_bytes());
+        plain_total += compressed.len();
+
+        let compressed = vault.encrypt(sample.as_bytes())?;
+        assert_eq!(vault.decrypt(&compressed)?, sample.as_bytes());
+        dictionary_total += compressed.len();
+    }
+    assert!(dictionary_total < plain_total);
+
+    // Reading the stored documents requires the dictionary to be configured.
+    let value = samples.concat();
+    let db = with_dictionary.create_database::<BasicSchema>("tests", false)?;
+    let header = Basic::new(value.clone()).push_into(&db)?.header;
+    drop((db, with_dictionary));
+
+    let storage = Storage::open(
+        StorageConfiguration::new(&path)
+            .compression_dictionary::<Basic>(dictionary)
+            .with_schema::<BasicSchema>()?,
+    )?;
+    let db = storage.database::<BasicSchema>("tests")?;
+    let doc = Basic::get(&header.id, &db)?.expect("document missing");
+    assert_eq!(doc.contents.value, value);
+
+    Ok(())
+}
+
+#[test]
+fn verify_integrity() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::document::DocumentId;
+    use bonsaidb_core::schema::{Collection, SerializedCollection};
+    use nebari::tree::Unversioned;
+
+    use crate::views::view_document_map_tree_name;
+    use crate::IntegrityProblem;
+
+    let path = TestDirectory::new("verify-integrity");
+    let storage = Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+    let db = storage.create_database::<BasicSchema>("tests", false)?;
+    let id = DocumentId::from_u64(
+        Basic::new("child")
+            .with_parent_id(1)
+            .push_into(&db)?
+            .header
+            .id,
+    );
+    Basic::new("other").with_parent_id(2).push_into(&db)?;
+    // Ensure the view has been mapped.
+    assert_eq!(db.view::<BasicByParentId>().query()?.len(), 2);
+
+    let report = storage.verify_integrity()?;
+    assert!(report.is_ok(), "{report:?}");
+    assert!(report.databases.contains_key("tests"));
+
+    // Remove the record of the keys the document emitted, leaving the view
+    // entry's mapping in place.
+    let view_name = db.schematic().view::<BasicByParentId>()?.view_name();
+    db.roots()
+        .tree(db.collection_tree::<Unversioned, _>(
+            &Basic::collection_name(),
+            view_document_map_tree_name(&view_name),
+        )?)?
+        .set(
+            id.to_vec(),
+            bincode::serialize(&std::collections::HashSet::<
+                bonsaidb_core::arc_bytes::OwnedBytes,
+            >::new())?,
+        )?;
+    db.roots()
+        .tree(Unversioned::tree("orphaned"))?
+        .set(b"key".to_vec(), b"value".to_vec())?;
+
+    let report = storage.verify_integrity()?;
+    assert!(!report.is_ok());
+    let problems = &report.databases["tests"];
+    assert_eq!(problems.len(), 2, "{problems:?}");
+    assert!(matches!(
+        &problems[0],
+        IntegrityProblem::UnexpectedViewMapping { view, document, .. }
+            if view == &view_name && document == &id
+    ));
+    assert_eq!(
+        problems[1],
+        IntegrityProblem::OrphanedTree {
+            name: String::from("orphaned")
+        }
+    );
+    // Only the modified database has problems.
+    assert_eq!(report.problems().count(), 2);
+
+    // A key that isn't a valid document id prevents the documents from being
+    // verified, but the remaining checks still run.
+    let documents_tree_name = crate::database::document_tree_name(&Basic::collection_name());
+    db.roots()
+        .tree(db.collection_tree::<nebari::tree::Versioned, _>(
+            &Basic::collection_name(),
+            documents_tree_name.clone(),
+        )?)?
+        .set(vec![0; 128], b"garbage".to_vec())?;
+    let report = storage.verify_integrity()?;
+    let problems = &report.databases["tests"];
+    assert_eq!(problems.len(), 2, "{problems:?}");
+    assert!(matches!(
+        &problems[0],
+        IntegrityProblem::CheckFailed { tree: Some(tree), .. } if tree == &documents_tree_name
+    ));
+    assert_eq!(
+        problems[1],
+        IntegrityProblem::OrphanedTree {
+            name: String::from("orphaned")
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn database_quotas() -> anyhow::Result<()> {
+    use std::sync::Arc;
+
+    use bonsaidb_core::admin::{DatabaseQuota, QuotaKind, QuotaLimit};
+    use bonsaidb_core::connection::StorageConnection;
     use bonsaidb_core::keyvalue::KeyValue;
-    use bonsaidb_core::test_util::TimingTest;
-    loop {
-        let path = TestDirectory::new("expiration-after-close");
-        // To ensure full cleanup between each block, each runs in its own runtime;
-        let timing = TimingTest::new(Duration::from_millis(100));
-        // Set a key with an expiration, then close it. Then try to validate it
-        // exists after opening, and then expires at the correct time.
-        {
-            let db = Database::open::<()>(StorageConfiguration::new(&path))?;
-
-            // TODO This is a workaroun for the key-value expiration task
-            // taking ownership of an instance of Database. If this async
-            // task runs too quickly, sometimes things don't get cleaned up
-            // if that task hasn't completed. This pause ensures the startup
-            // tasks complete before we continue with the test. This should
-            // be replaced with a proper shutdown call for the local
-            // storage/database.
-            std::thread::sleep(Duration::from_millis(100));
-
-            db.set_key("a", &0_u32)
-                .expire_in(Duration::from_secs(3))
-                .execute()?;
+    use bonsaidb_core::schema::{Collection, NamedCollection, SerializedCollection};
+    use parking_lot::Mutex;
+
+    use crate::QuotaMonitor;
+
+    #[derive(Debug, Default, Clone)]
+    struct RecordingMonitor(Arc<Mutex<Vec<(String, QuotaKind, u64, u64)>>>);
+
+    impl QuotaMonitor for RecordingMonitor {
+        fn soft_limit_exceeded(&self, database: &str, kind: &QuotaKind, limit: u64, usage: u64) {
+            self.0
+                .lock()
+                .push((database.to_string(), kind.clone(), limit, usage));
         }
+    }
 
-        {
-            let db = Database::open::<()>(StorageConfiguration::new(&path))?;
-
-            let key = db.get_key("a").query()?;
-            // Due to not having a reliable way to shut down the database,
-            // we can't make many guarantees about what happened after
-            // setting the key in the above block. If we get None back,
-            // we'll consider the test needing to retry. Once we have a
-            // shutdown operation that guarantees that the key-value store
-            // persists, the key.is_none() check shoud be removed, instead
-            // asserting `key.is_some()`.
-            if timing.elapsed() > Duration::from_secs(1) || key.is_none() {
-                println!("Retrying  expiration_after_close because it was too slow");
-                continue;
-            }
+    let path = TestDirectory::new("database-quotas");
+    let monitor = RecordingMonitor::default();
+    let configuration = || {
+        StorageConfiguration::new(&path)
+            .with_schema::<BasicSchema>()
+            .map(|config| config.quota_monitor(monitor.clone()))
+    };
+    let storage = Storage::open(configuration()?)?;
+    let db = storage.create_database::<BasicSchema>("quotas", false)?;
+    storage.set_database_quota(
+        "quotas",
+        DatabaseQuota::default()
+            .with_documents_per_collection(QuotaLimit::hard(2).with_soft(1))
+            .with_keys(QuotaLimit::hard(1)),
+    )?;
 
-            timing.wait_until(Duration::from_secs(4));
+    let first = Basic::new("a").push_into(&db)?;
+    Basic::new("b").push_into(&db)?;
+    let documents = QuotaKind::Documents(Basic::collection_name());
+    assert_eq!(
+        monitor.0.lock().as_slice(),
+        &[(String::from("quotas"), documents.clone(), 1, 2)]
+    );
+    assert!(matches!(
+        Basic::new("c").push_into(&db).unwrap_err().error,
+        bonsaidb_core::Error::QuotaExceeded { kind, limit: 2, usage: 2 } if kind == documents
+    ));
+    // Writes that don't increase usage are always allowed.
+    first.delete(&db)?;
+    Basic::new("c").push_into(&db)?;
+    assert_eq!(storage.database_quota_usage("quotas")?.usage(&documents), 2);
 
-            assert!(db.get_key("a").query()?.is_none());
+    db.set_key("a", &1_u32).execute()?;
+    assert!(matches!(
+        db.set_key("b", &1_u32).execute(),
+        Err(bonsaidb_core::Error::QuotaExceeded {
+            kind: QuotaKind::Keys,
+            limit: 1,
+            usage: 1
+        })
+    ));
+    db.set_key("a", &2_u32).execute()?;
+    db.delete_key("a")?;
+    db.set_key("b", &1_u32).execute()?;
+
+    let quota = DatabaseQuota::default().with_bytes(QuotaLimit::hard(1));
+    storage.set_database_quota("quotas", quota)?;
+    assert!(matches!(
+        Basic::new("d").push_into(&db).unwrap_err().error,
+        bonsaidb_core::Error::QuotaExceeded {
+            kind: QuotaKind::Bytes,
+            limit: 1,
+            ..
+        }
+    ));
+    drop(db);
+    drop(storage);
+
+    // Quotas are persisted.
+    let storage = Storage::open(configuration()?)?;
+    let admin = storage.admin();
+    let record = bonsaidb_core::admin::Database::load("quotas", &admin)?.expect("record missing");
+    assert_eq!(record.contents.quota, quota);
+    let db = storage.database::<BasicSchema>("quotas")?;
+    assert!(matches!(
+        Basic::new("d").push_into(&db).unwrap_err().error,
+        bonsaidb_core::Error::QuotaExceeded {
+            kind: QuotaKind::Bytes,
+            ..
         }
+    ));
+    let existing = Basic::all(&db).query()?;
+    existing[0].delete(&db)?;
+
+    // Removing the quota allows writes again.
+    storage.set_database_quota("quotas", DatabaseQuota::default())?;
+    Basic::new("d").push_into(&db)?;
+    assert_eq!(monitor.0.lock().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn database_activity() -> anyhow::Result<()> {
+    use bonsaidb_core::schema::SerializedCollection;
+
+    let path = TestDirectory::new("database-activity");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+
+    let initial = db.activity()?;
+    assert_eq!(initial.transactions_committed, 0);
+
+    Basic::new("a").push_into(&db)?;
+    Basic::new("b").push_into(&db)?;
+
+    let activity = db.activity()?;
+    assert_eq!(activity.transactions_committed, 2);
+    assert!(activity.last_transaction_id > initial.last_transaction_id);
+    assert_eq!(activity.last_transaction_id, db.last_transaction_id()?);
+    assert!(activity.elapsed >= initial.elapsed);
+
+    Ok(())
+}
+
+#[test]
+fn permission_group_and_role_management() -> anyhow::Result<()> {
+    use bonsaidb_core::admin::{PermissionGroup, Role};
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::permissions::bonsai::{BonsaiAction, ServerAction};
+    use bonsaidb_core::schema::NamedCollection;
 
-        break;
+    fn allowing(action: ServerAction) -> Statement {
+        Statement::for_any().allowing(&BonsaiAction::Server(action))
     }
+
+    let path = TestDirectory::new("permission-group-and-role-management");
+    let storage = Storage::open(StorageConfiguration::new(&path))?;
+    let admin = storage.admin();
+
+    let group_id =
+        storage.create_permission_group("writers", vec![allowing(ServerAction::CreateDatabase)])?;
+    let mut no_resources = allowing(ServerAction::CreateDatabase);
+    no_resources.resources.clear();
+    assert!(matches!(
+        storage.create_permission_group(
+            "invalid",
+            vec![allowing(ServerAction::CreateDatabase), no_resources.clone()]
+        ),
+        Err(bonsaidb_core::Error::InvalidPermissionStatement { index: 1, .. })
+    ));
+    assert!(PermissionGroup::load("invalid", &admin)?.is_none());
+
+    storage.update_permission_group_statements(
+        "writers",
+        vec![
+            allowing(ServerAction::CreateDatabase),
+            allowing(ServerAction::DeleteDatabase),
+        ],
+    )?;
+    let group = PermissionGroup::load(group_id, &admin)?.expect("group missing");
+    assert_eq!(group.contents.statements.len(), 2);
+    assert!(matches!(
+        storage.update_permission_group_statements("writers", vec![no_resources]),
+        Err(bonsaidb_core::Error::InvalidPermissionStatement { index: 0, .. })
+    ));
+    assert!(matches!(
+        storage.update_permission_group_statements("missing", Vec::new()),
+        Err(bonsaidb_core::Error::PermissionGroupNotFound)
+    ));
+
+    let role_id = storage.create_role("writer", vec![group_id])?;
+    let role = Role::load(role_id, &admin)?.expect("role missing");
+    assert_eq!(role.contents.groups, vec![group_id]);
+    assert!(matches!(
+        storage.create_role("invalid", vec![group_id, group_id + 1]),
+        Err(bonsaidb_core::Error::PermissionGroupNotFound)
+    ));
+    assert!(Role::load("invalid", &admin)?.is_none());
+
+    // Each operation requires its own permission.
+    let restricted = storage
+        .with_effective_permissions(Permissions::from(vec![allowing(ServerAction::CreateRole)]))
+        .unwrap();
+    assert!(matches!(
+        restricted.create_permission_group("denied", Vec::new()),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+    assert!(matches!(
+        restricted.delete_role("writer"),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+    restricted.create_role("restricted", vec![group_id])?;
+
+    storage.delete_role("writer")?;
+    assert!(Role::load(role_id, &admin)?.is_none());
+    assert!(matches!(
+        storage.delete_role("writer"),
+        Err(bonsaidb_core::Error::RoleNotFound)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn concurrent_upsert_by_name() -> anyhow::Result<()> {
+    use std::sync::{Arc, Barrier};
+
+    use bonsaidb_core::schema::SerializedCollection;
+    use bonsaidb_core::test_util::Unique;
+
+    const WORKERS: usize = 8;
+
+    let path = TestDirectory::new("concurrent-upsert-by-name");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+
+    for round in 0..10 {
+        let name = format!("contested-{round}");
+        let barrier = Arc::new(Barrier::new(WORKERS));
+        let workers = (0..WORKERS)
+            .map(|_| {
+                let db = db.clone();
+                let name = name.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    db.upsert_by_name::<Unique>(&name, Unique::new(&name))
+                        .map(|doc| doc.header.id)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut ids = workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect::<Result<Vec<_>, _>>()?;
+        ids.dedup();
+        assert_eq!(ids.len(), 1, "duplicate documents created for {name}");
+    }
+
+    assert_eq!(Unique::all(&db).count()?, 10);
+
+    Ok(())
+}
+
+#[test]
+fn view_queries_require_collection_permissions() -> anyhow::Result<()> {
+    use bonsaidb_core::permissions::bonsai::{
+        BonsaiAction, DatabaseAction, DocumentAction, ViewAction,
+    };
+    use bonsaidb_core::schema::SerializedCollection;
+
+    fn allowing(action: DatabaseAction) -> Statement {
+        Statement::for_any().allowing(&BonsaiAction::Database(action))
+    }
+
+    let path = TestDirectory::new("view-queries-require-collection-permissions");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+    Basic::new("a").with_category("a").push_into(&db)?;
+
+    let query_only = db
+        .with_effective_permissions(Permissions::from(vec![allowing(DatabaseAction::View(
+            ViewAction::Query,
+        ))]))
+        .unwrap();
+    assert!(matches!(
+        query_only.view::<BasicByCategory>().query(),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+
+    let query_and_list = db
+        .with_effective_permissions(Permissions::from(vec![
+            allowing(DatabaseAction::View(ViewAction::Query)),
+            allowing(DatabaseAction::Document(DocumentAction::List)),
+        ]))
+        .unwrap();
+    assert_eq!(query_and_list.view::<BasicByCategory>().query()?.len(), 1);
+    // Retrieving the mapped documents requires permission to get them.
+    assert!(matches!(
+        query_and_list.view::<BasicByCategory>().query_with_docs(),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+
+    let query_list_and_get = db
+        .with_effective_permissions(Permissions::from(vec![
+            allowing(DatabaseAction::View(ViewAction::Query)),
+            allowing(DatabaseAction::Document(DocumentAction::List)),
+            allowing(DatabaseAction::Document(DocumentAction::Get)),
+        ]))
+        .unwrap();
+    assert_eq!(
+        query_list_and_get
+            .view::<BasicByCategory>()
+            .query_with_docs()?
+            .len(),
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn views_remap_when_dependencies_change() -> anyhow::Result<()> {
+    use bonsaidb_core::document::{CollectionDocument, Emit};
+    use bonsaidb_core::schema::view::map::Mappings;
+    use bonsaidb_core::schema::{
+        Collection, CollectionMapReduce, Schema, SerializedCollection, View, ViewLookup,
+        ViewMapResult, ViewSchema,
+    };
+    use nebari::tree::Unversioned;
+    use serde::{Deserialize, Serialize};
+
+    use crate::views::view_invalidated_docs_tree_name;
+
+    #[derive(Schema, Debug)]
+    #[schema(name = "blog", collections = [Author, Post], core = bonsaidb_core)]
+    struct Blog;
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "authors", core = bonsaidb_core)]
+    struct Author {
+        name: String,
+    }
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "posts", views = [PostsByAuthorName], core = bonsaidb_core)]
+    struct Post {
+        title: String,
+        author_id: u64,
+    }
+
+    #[derive(View, ViewSchema, Debug, Clone)]
+    #[view(collection = Post, key = String, core = bonsaidb_core)]
+    #[view_schema(dependencies = [Author], core = bonsaidb_core)]
+    struct PostsByAuthorName;
+
+    impl CollectionMapReduce for PostsByAuthorName {
+        fn map<'doc>(&self, _document: CollectionDocument<Post>) -> ViewMapResult<'doc, Self> {
+            unreachable!("posts are mapped using map_with_lookup")
+        }
+
+        fn map_with_lookup<'doc>(
+            &self,
+            document: CollectionDocument<Post>,
+            lookup: &ViewLookup<'_>,
+        ) -> ViewMapResult<'doc, Self> {
+            match lookup.get::<Author, _>(&document.contents.author_id)? {
+                Some(author) => document.header.emit_key(author.contents.name),
+                None => Ok(Mappings::none()),
+            }
+        }
+    }
+
+    let path = TestDirectory::new("views-remap-when-dependencies-change");
+    let db = Database::open::<Blog>(StorageConfiguration::new(&path))?;
+    let mut alice = Author {
+        name: String::from("alice"),
+    }
+    .push_into(&db)?;
+    let bob = Author {
+        name: String::from("bob"),
+    }
+    .push_into(&db)?;
+    for (title, author_id) in [("first", alice.header.id), ("second", alice.header.id)] {
+        Post {
+            title: String::from(title),
+            author_id,
+        }
+        .push_into(&db)?;
+    }
+    Post {
+        title: String::from("third"),
+        author_id: bob.header.id,
+    }
+    .push_into(&db)?;
+    // This post's author doesn't exist yet.
+    Post {
+        title: String::from("fourth"),
+        author_id: bob.header.id + 1,
+    }
+    .push_into(&db)?;
+
+    let by_alice = db
+        .view::<PostsByAuthorName>()
+        .with_key("alice")
+        .query_with_collection_docs()?;
+    assert_eq!(by_alice.len(), 2);
+    assert_eq!(by_alice.get(0).unwrap().document.contents.title, "first");
+
+    // Renaming the author remaps their posts, even though no post changed.
+    // Only the posts that looked up the author are invalidated.
+    alice.contents.name = String::from("alicia");
+    alice.update(&db)?;
+    let view_name = db.schematic().view::<PostsByAuthorName>()?.view_name();
+    let invalidated = db
+        .roots()
+        .tree(db.collection_tree::<Unversioned, _>(
+            &Post::collection_name(),
+            view_invalidated_docs_tree_name(&view_name),
+        )?)?
+        .get_range(&(..))?;
+    assert_eq!(invalidated.len(), 2);
+    assert!(db
+        .view::<PostsByAuthorName>()
+        .with_key("alice")
+        .query()?
+        .is_empty());
+    assert_eq!(
+        db.view::<PostsByAuthorName>()
+            .with_key("alicia")
+            .query()?
+            .len(),
+        2
+    );
+    assert_eq!(
+        db.view::<PostsByAuthorName>()
+            .with_key("bob")
+            .query()?
+            .len(),
+        1
+    );
+
+    // Posts whose author is deleted no longer emit a key.
+    bob.delete(&db)?;
+    assert!(db
+        .view::<PostsByAuthorName>()
+        .with_key("bob")
+        .query()?
+        .is_empty());
+
+    // Creating a missing author remaps the posts that looked it up.
+    let carol = Author {
+        name: String::from("carol"),
+    }
+    .push_into(&db)?;
+    assert_eq!(carol.header.id, bob.header.id + 1);
+    assert_eq!(
+        db.view::<PostsByAuthorName>()
+            .with_key("carol")
+            .query()?
+            .len(),
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn snapshots_ignore_later_transactions() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::{LowLevelConnection, Sort};
+    use bonsaidb_core::schema::SerializedCollection;
+    use bonsaidb_core::transaction::Transaction;
+
+    let path = TestDirectory::new("snapshots-ignore-later-transactions");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+    let mut updated = Basic::new("a").with_category("a").push_into(&db)?;
+    let deleted = Basic::new("b").with_category("b").push_into(&db)?;
+    Basic::new("c").with_category("a").push_into(&db)?;
+
+    let snapshot = db.read_snapshot()?;
+    assert_eq!(snapshot.transaction_id(), db.last_transaction_id()?);
+
+    updated.contents.value = String::from("updated");
+    updated.contents.category = Some(String::from("b"));
+    updated.update(&db)?;
+    deleted.delete(&db)?;
+    let inserted = Basic::new("d").with_category("a").push_into(&db)?;
+
+    // The database reflects every change.
+    assert_eq!(Basic::all(&db).count()?, 3);
+    assert_eq!(db.view::<BasicByCategory>().with_key("a").reduce()?, 2);
+    assert_eq!(db.view::<BasicByCategory>().with_key("b").reduce()?, 1);
+
+    // The snapshot only reflects the transactions committed before it was
+    // created.
+    let original = snapshot
+        .get::<Basic, _>(&updated.header.id)?
+        .expect("document missing from snapshot");
+    assert_eq!(Basic::document_contents(&original)?.value, "a");
+    assert!(snapshot.get::<Basic, _>(&deleted.header.id)?.is_some());
+    assert!(snapshot.get::<Basic, _>(&inserted.header.id)?.is_none());
+    assert_eq!(snapshot.count::<Basic, _, u64>(..)?, 3);
+    let listed = snapshot
+        .list::<Basic, _, u64>(.., Sort::Descending, Some(2))?
+        .into_iter()
+        .map(|document| Ok(Basic::document_contents(&document)?.value))
+        .collect::<Result<Vec<_>, bonsaidb_core::Error>>()?;
+    assert_eq!(listed, vec![String::from("c"), String::from("b")]);
+    let listed = snapshot
+        .list::<Basic, _, u64>(.., Sort::Ascending, Some(2))?
+        .into_iter()
+        .map(|document| Ok(Basic::document_contents(&document)?.value))
+        .collect::<Result<Vec<_>, bonsaidb_core::Error>>()?;
+    assert_eq!(listed, vec![String::from("a"), String::from("b")]);
+    assert_eq!(snapshot.count::<Basic, _, u64>(deleted.header.id..)?, 2);
+
+    let by_category = snapshot.query::<BasicByCategory, String>(
+        None,
+        Sort::Ascending,
+        None,
+        AccessPolicy::UpdateBefore,
+    )?;
+    assert_eq!(
+        by_category
+            .iter()
+            .map(|mapping| mapping.key.as_str())
+            .collect::<Vec<_>>(),
+        vec!["a", "a", "b"]
+    );
+    assert_eq!(
+        snapshot.reduce::<BasicByCategory, String>(None, AccessPolicy::UpdateBefore)?,
+        3
+    );
+    let grouped =
+        snapshot.reduce_grouped::<BasicByCategory, String>(None, AccessPolicy::UpdateBefore)?;
+    assert_eq!(grouped.len(), 2);
+    assert_eq!(grouped[0].value, 2);
+    assert_eq!(grouped[1].value, 1);
+
+    // Writes can't be made through a snapshot, and collections can't be
+    // compacted until every snapshot is dropped.
+    assert!(matches!(
+        snapshot.apply_transaction(Transaction::new()),
+        Err(bonsaidb_core::Error::ReadOnly)
+    ));
+    assert!(matches!(
+        db.compact_collection::<Basic>(),
+        Err(bonsaidb_core::Error::Conflict(_))
+    ));
+    drop(snapshot);
+    db.compact_collection::<Basic>()?;
+
+    Ok(())
+}
+
+#[test]
+fn open_database_without_schema_type() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::{
+        HasSchema, LowLevelConnection, Range, Sort, StorageConnection,
+    };
+    use bonsaidb_core::document::DocumentId;
+    use bonsaidb_core::keyvalue::KeyValue;
+    use bonsaidb_core::schema::{Collection, Schema, SerializedCollection};
+    use bonsaidb_core::transaction::{Operation, Transaction};
+
+    let path = TestDirectory::new("open-database-without-schema-type");
+    let id = {
+        let storage =
+            Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+        let db = storage.create_database::<BasicSchema>("tests", false)?;
+        Basic::new("a").push_into(&db)?;
+        db.set_key("key", &1_u32).execute()?;
+        Basic::new("b").push_into(&db)?.header.id
+    };
+
+    // The schema isn't registered when reopening the storage.
+    let storage = Storage::open(StorageConfiguration::new(&path))?;
+    let db = storage.with_schema_from_database("tests")?;
+    assert_eq!(db.schematic().name, BasicSchema::schema_name());
+    assert!(db.schematic().views().next().is_none());
+
+    let collection = Basic::collection_name();
+    let document = db
+        .get_from_collection(DocumentId::new(&id)?, &collection)?
+        .expect("document not found");
+    assert_eq!(Basic::document_contents(&document)?.value, "b");
+    assert_eq!(db.count_from_collection(Range::from(..), &collection)?, 2);
+    assert_eq!(
+        db.list_headers_from_collection(Range::from(..), Sort::Ascending, None, &collection)?
+            .len(),
+        2
+    );
+    assert_eq!(db.get_key("key").into_u64()?, Some(1));
+
+    // Writing documents would bypass the unknown views and encryption.
+    assert!(matches!(
+        db.apply_transaction(Transaction::from(Operation::delete(
+            collection,
+            document.header
+        ))),
+        Err(bonsaidb_core::Error::ReadOnly)
+    ));
+    db.set_key("key", &2_u32).execute()?;
+
+    Ok(())
+}
+
+#[test]
+fn users_can_only_access_their_own_scope() -> anyhow::Result<()> {
+    use bonsaidb_core::admin::PermissionGroup;
+    use bonsaidb_core::connection::{IdentityReference, StorageConnection, UserScope};
+    use bonsaidb_core::keyvalue::KeyValue;
+    use bonsaidb_core::schema::SerializedCollection;
+
+    use crate::DatabaseNonBlocking;
+
+    let path = TestDirectory::new("users-can-only-access-their-own-scope");
+    let storage = Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+    storage.create_database::<BasicSchema>("tests", false)?;
+    let alice_id = storage.create_user("alice")?;
+    let bob_id = storage.create_user("bob")?;
+    assert!(storage.user_scope().is_none());
+
+    // Users can create and use the database they own.
+    let alice = storage.assume_identity(IdentityReference::user("alice")?)?;
+    let scope = alice.user_scope().expect("alice has a scope");
+    assert_eq!(scope.user_id(), alice_id);
+    let alice_db = alice.create_database::<BasicSchema>(&scope.database_name(), false)?;
+    assert_eq!(UserScope::owner_of_database(alice_db.name()), Some(scope));
+    assert_eq!(UserScope::owner_of_database("tests"), None);
+    alice_db.set_key("theme", &String::from("dark")).execute()?;
+    Basic::new("a").push_into(&alice_db)?;
+    assert_eq!(Basic::all(&alice_db).count()?, 1);
+
+    // Other databases, including other users' databases and the admin
+    // database, are inaccessible.
+    assert!(matches!(
+        alice.create_database::<BasicSchema>(&UserScope::for_user(bob_id).database_name(), false),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+    let shared = alice.database::<BasicSchema>("tests")?;
+    assert!(matches!(
+        scope
+            .key_value(&shared)
+            .set_key("theme", &String::from("light"))
+            .execute(),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+    assert!(matches!(
+        scope
+            .key_value(&alice.admin())
+            .set_key("theme", &String::from("light"))
+            .execute(),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+
+    // Access to the user's namespace in a shared database is granted per
+    // database.
+    PermissionGroup::named("alice-tests")
+        .with_group_ids([scope.key_value_statement("tests")])
+        .push_into(&storage.admin())?;
+    storage.add_permission_group_to_user("alice", "alice-tests")?;
+    let alice = storage.assume_identity(IdentityReference::user("alice")?)?;
+    let shared = alice.database::<BasicSchema>("tests")?;
+    scope
+        .key_value(&shared)
+        .set_key("theme", &String::from("light"))
+        .execute()?;
+    assert!(matches!(
+        shared.set_key("theme", &String::from("light")).execute(),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+    assert!(matches!(
+        UserScope::for_user(bob_id)
+            .key_value(&shared)
+            .get_key("theme")
+            .query(),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+
+    // Connections with broader permissions can access any user's data.
+    let db = storage.database::<BasicSchema>("tests")?;
+    assert_eq!(
+        UserScope::for_user(alice_id)
+            .key_value(&db)
+            .get_key("theme")
+            .into::<String>()?
+            .as_deref(),
+        Some("light")
+    );
+    let db = storage.database::<BasicSchema>(&scope.database_name())?;
+    assert_eq!(
+        db.get_key("theme").into::<String>()?.as_deref(),
+        Some("dark")
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "compression")]
+fn block_checksums_detect_corruption() -> anyhow::Result<()> {
+    use nebari::Vault;
+
+    use crate::config::Compression;
+    use crate::Error;
+
+    let path = TestDirectory::new("block-checksums");
+    let storage = Storage::open(
+        StorageConfiguration::new(&path)
+            .default_compression(Compression::Lz4)
+            .block_checksums(true),
+    )?;
+    let vault = storage.tree_vault().cloned().expect("compression enabled");
+    let unchecked_path = TestDirectory::new("block-checksums-unchecked");
+    let unchecked = Storage::open(
+        StorageConfiguration::new(&unchecked_path).default_compression(Compression::Lz4),
+    )?;
+    let unchecked = unchecked
+        .tree_vault()
+        .cloned()
+        .expect("compression enabled");
+
+    // Both compressed blocks and blocks too small to compress are checksummed.
+    for payload in [vec![42; 1024], vec![42; 16]] {
+        let mut block = vault.encrypt(&payload)?;
+        assert_eq!(vault.decrypt(&block)?, payload);
+        // Checksums are verified even when they are disabled for new writes.
+        assert_eq!(unchecked.decrypt(&block)?, payload);
+
+        let last = block.len() - 1;
+        block[last] ^= 0xFF;
+        assert!(matches!(vault.decrypt(&block), Err(Error::BlockCorrupted)));
+        assert!(matches!(
+            unchecked.decrypt(&block),
+            Err(Error::BlockCorrupted)
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn views_remap_when_auxiliary_inputs_change() -> anyhow::Result<()> {
+    use bonsaidb_core::document::{CollectionDocument, Emit};
+    use bonsaidb_core::keyvalue::KeyValue;
+    use bonsaidb_core::schema::view::map::Mappings;
+    use bonsaidb_core::schema::view::AuxiliaryInput;
+    use bonsaidb_core::schema::{
+        Collection, CollectionMapReduce, Schema, SerializedCollection, View, ViewLookup,
+        ViewMapResult, ViewSchema,
+    };
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Schema, Debug)]
+    #[schema(name = "store", collections = [Product], core = bonsaidb_core)]
+    struct Store;
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "products", views = [ProductsByLabel], core = bonsaidb_core)]
+    struct Product {
+        name: String,
+        category: String,
+    }
+
+    #[derive(View, Debug, Clone)]
+    #[view(collection = Product, key = String, core = bonsaidb_core)]
+    struct ProductsByLabel;
+
+    impl ViewSchema for ProductsByLabel {
+        type MappedKey<'doc> = String;
+        type View = Self;
+
+        fn auxiliary_inputs(&self) -> Vec<AuxiliaryInput> {
+            vec![AuxiliaryInput::key_value("labels", Some("labels"))]
+        }
+    }
+
+    impl CollectionMapReduce for ProductsByLabel {
+        fn map<'doc>(&self, _document: CollectionDocument<Product>) -> ViewMapResult<'doc, Self> {
+            unreachable!("products are mapped using map_with_lookup")
+        }
+
+        fn map_with_lookup<'doc>(
+            &self,
+            document: CollectionDocument<Product>,
+            lookup: &ViewLookup<'_>,
+        ) -> ViewMapResult<'doc, Self> {
+            match lookup
+                .auxiliary("labels")?
+                .value(&document.contents.category)
+            {
+                Some(label) => document.header.emit_key(label.deserialize::<String>()?),
+                None => Ok(Mappings::none()),
+            }
+        }
+    }
+
+    let path = TestDirectory::new("views-remap-when-auxiliary-inputs-change");
+    let db = Database::open::<Store>(StorageConfiguration::new(&path))?;
+    let labels = db.with_key_namespace("labels");
+    labels.set_key("fruit", &String::from("Fruit")).execute()?;
+    for (name, category) in [
+        ("apple", "fruit"),
+        ("pear", "fruit"),
+        ("carrot", "vegetable"),
+    ] {
+        Product {
+            name: String::from(name),
+            category: String::from(category),
+        }
+        .push_into(&db)?;
+    }
+
+    assert_eq!(
+        db.view::<ProductsByLabel>()
+            .with_key("Fruit")
+            .query()?
+            .len(),
+        2
+    );
+    assert_eq!(db.view::<ProductsByLabel>().query()?.len(), 2);
+
+    // Changing the input remaps every product, even though no product changed.
+    labels.set_key("fruit", &String::from("Fruits")).execute()?;
+    labels
+        .set_key("vegetable", &String::from("Vegetables"))
+        .execute()?;
+    assert!(db
+        .view::<ProductsByLabel>()
+        .with_key("Fruit")
+        .query()?
+        .is_empty());
+    assert_eq!(
+        db.view::<ProductsByLabel>()
+            .with_key("Fruits")
+            .query()?
+            .len(),
+        2
+    );
+    let vegetables = db
+        .view::<ProductsByLabel>()
+        .with_key("Vegetables")
+        .query_with_collection_docs()?;
+    assert_eq!(vegetables.len(), 1);
+    assert_eq!(vegetables.get(0).unwrap().document.contents.name, "carrot");
+
+    Ok(())
+}
+
+#[test]
+fn list_databases_reports_database_state() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::schema::SerializedCollection;
+
+    let path = TestDirectory::new("list-databases-reports-database-state");
+    let storage = Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+    storage.create_database::<BasicSchema>("tests", false)?;
+
+    let summary = |storage: &Storage| -> anyhow::Result<bonsaidb_core::connection::Database> {
+        Ok(storage
+            .list_databases()?
+            .into_iter()
+            .find(|database| database.name == "tests")
+            .unwrap())
+    };
+    let created = summary(&storage)?;
+    assert!(created.created_at.is_some());
+    assert_eq!(created.open, Some(false));
+
+    let db = storage.database::<BasicSchema>("tests")?;
+    Basic::new("a").push_into(&db)?;
+    let written = summary(&storage)?;
+    assert_eq!(written.created_at, created.created_at);
+    assert_eq!(written.open, Some(true));
+    assert!(written.last_transaction_at >= written.created_at);
+    drop(db);
+    drop(storage);
+
+    // The creation time is loaded from the admin database when reopened.
+    let storage = Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+    let reopened = summary(&storage)?;
+    assert_eq!(reopened.created_at, created.created_at);
+    assert!(reopened.last_transaction_at.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn capped_identities_cannot_escalate_privileges() -> anyhow::Result<()> {
+    use bonsaidb_core::admin::PermissionGroup;
+    use bonsaidb_core::connection::{HasSession, IdentityReference, StorageConnection};
+    use bonsaidb_core::permissions::bonsai::{bonsaidb_resource_name, BonsaiAction, ServerAction};
+    use bonsaidb_core::schema::SerializedCollection;
+
+    let path = TestDirectory::new("capped-identities-cannot-escalate-privileges");
+    let storage = Storage::open(StorageConfiguration::new(&path))?;
+    let admin = storage.admin();
+
+    // The service may impersonate users and list databases.
+    let service = PermissionGroup::named("service")
+        .with_group_ids([Statement::for_any()
+            .allowing(&BonsaiAction::Server(ServerAction::AssumeIdentity))
+            .allowing(&BonsaiAction::Server(ServerAction::ListDatabases))])
+        .push_into(&admin)?;
+    // The operator may list and delete databases.
+    let operator = PermissionGroup::named("operator")
+        .with_group_ids([Statement::for_any()
+            .allowing(&BonsaiAction::Server(ServerAction::ListDatabases))
+            .allowing(&BonsaiAction::Server(ServerAction::DeleteDatabase))])
+        .push_into(&admin)?;
+    storage.create_user("service")?;
+    storage.add_permission_group_to_user("service", service.header.id)?;
+    storage.create_user("operator")?;
+    storage.add_permission_group_to_user("operator", operator.header.id)?;
+
+    let service = storage.assume_identity(IdentityReference::user("service")?)?;
+    let delete_database = BonsaiAction::Server(ServerAction::DeleteDatabase);
+    let list_databases = BonsaiAction::Server(ServerAction::ListDatabases);
+
+    // Fully assuming the identity grants all of its permissions.
+    let full = service.assume_identity(IdentityReference::user("operator")?)?;
+    assert!(full.allowed_to(bonsaidb_resource_name(), &delete_database));
+
+    // A capped identity only has the permissions both identities have.
+    let capped = service.assume_identity_capped(IdentityReference::user("operator")?)?;
+    assert!(matches!(
+        capped.session().and_then(|session| session.identity()),
+        Some(bonsaidb_core::connection::Identity::User { username, .. }) if username == "operator"
+    ));
+    assert!(capped.allowed_to(bonsaidb_resource_name(), &list_databases));
+    assert!(!capped.allowed_to(bonsaidb_resource_name(), &delete_database));
+    assert!(matches!(
+        capped.delete_database("admin"),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+    // The capped identity doesn't have the service's permissions either.
+    assert!(!capped.allowed_to(
+        bonsaidb_resource_name(),
+        &BonsaiAction::Server(ServerAction::AssumeIdentity)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn database_configurations_override_storage() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::keyvalue::{DurabilityMode, KeyValue};
+    use nebari::tree::Unversioned;
+
+    use crate::config::{DatabaseConfiguration, KeyValuePersistence, PersistenceThreshold};
+    use crate::database::keyvalue::KEY_TREE;
+
+    let path = TestDirectory::new("database-configurations-override-storage");
+    // Keys are only persisted after 1,000 changes, except in the ledger, which
+    // persists each change before returning, and the journal, which persists
+    // each change in the background.
+    let storage = Storage::open(
+        StorageConfiguration::new(&path)
+            .with_schema::<BasicSchema>()?
+            .key_value_persistence(KeyValuePersistence::lazy([
+                PersistenceThreshold::after_changes(1_000),
+            ]))
+            .database_configuration(
+                "ledger",
+                DatabaseConfiguration::default().key_value_durability(DurabilityMode::Immediate),
+            )
+            .database_configuration(
+                "journal",
+                DatabaseConfiguration::default()
+                    .key_value_persistence(KeyValuePersistence::immediate()),
+            ),
+    )?;
+    let ledger = storage.create_database::<BasicSchema>("ledger", false)?;
+    let journal = storage.create_database::<BasicSchema>("journal", false)?;
+    let cache = storage.create_database::<BasicSchema>("cache", false)?;
+    ledger.set_key("key", &1_u32).execute()?;
+    ledger.increment_key_by("counter", 1_u32).execute()?;
+    journal.set_key("key", &1_u32).execute()?;
+    cache.set_key("key", &1_u32).execute()?;
+
+    let persisted = |db: &Database, key: &[u8]| -> anyhow::Result<bool> {
+        Ok(db
+            .roots()
+            .tree(Unversioned::tree(KEY_TREE))?
+            .get(key)?
+            .is_some())
+    };
+    assert!(persisted(&ledger, b"\0key")?);
+    assert!(persisted(&ledger, b"\0counter")?);
+    let started = std::time::Instant::now();
+    while !persisted(&journal, b"\0key")? {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!persisted(&cache, b"\0key")?);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "compression")]
+fn database_configurations_override_compression() -> anyhow::Result<()> {
+    use bonsaidb_core::schema::Collection;
+    use nebari::Vault;
+
+    use crate::config::{Compression, DatabaseConfiguration};
+
+    let path = TestDirectory::new("database-configurations-override-compression");
+    let storage = Storage::open(
+        StorageConfiguration::new(&path)
+            .default_compression(Compression::Lz4)
+            .database_configuration("archive", DatabaseConfiguration::default())
+            .database_configuration(
+                "incompressible",
+                DatabaseConfiguration::default().compression(None),
+            ),
+    )?;
+    let vault = |database: &str| storage.collection_tree_vault(database, &Basic::collection_name());
+    let payload = vec![42; 1024];
+    for database in ["archive", "other"] {
+        let vault = vault(database).expect("compression enabled");
+        assert!(vault.encrypt(&payload)?.len() < payload.len());
+    }
+    assert!(vault("incompressible").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn key_value_scans_filter_values() -> anyhow::Result<()> {
+    use bonsaidb_core::keyvalue::{KeyValue, NumericComparison, ValueFilter};
+
+    let path = TestDirectory::new("key-value-scans-filter-values");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+    let metrics = db.with_key_namespace("metrics");
+    metrics.set_numeric_key("a", 5_u64).execute()?;
+    metrics.set_numeric_key("b", -3_i64).execute()?;
+    metrics.set_key("c", &String::from("json")).execute()?;
+    // Persist some keys so that the scan merges entries on disk with pending
+    // entries.
+    db.persist()?;
+    metrics.set_numeric_key("d", 2.5_f64).execute()?;
+    metrics
+        .set_binary_key("e", b"{\"count\":1}")
+        .expire_in(Duration::from_secs(3_600))
+        .execute()?;
+    metrics.set_numeric_key("f", u128::MAX).execute()?;
+    metrics.set_binary_key("g", b"[1,2]").execute()?;
+    // Keys outside of the namespace are never returned.
+    db.set_numeric_key("a", 100_u64).execute()?;
+    metrics
+        .with_key_namespace("nested")
+        .set_numeric_key("a", 100_u64)
+        .execute()?;
+
+    let keys = |entries: Vec<bonsaidb_core::keyvalue::KeyEntry>| {
+        entries
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        keys(metrics.scan_keys("").query()?),
+        ["a", "b", "c", "d", "e", "f", "g"]
+    );
+    assert_eq!(
+        keys(metrics.scan_keys("").filter(ValueFilter::IsBytes).query()?),
+        ["c", "e", "g"]
+    );
+    // Numbers are compared by value, regardless of their types.
+    assert_eq!(
+        keys(
+            metrics
+                .scan_keys("")
+                .filter(ValueFilter::greater_than(2_i64))
+                .query()?
+        ),
+        ["a", "d", "f"]
+    );
+    assert_eq!(
+        keys(
+            metrics
+                .scan_keys("")
+                .filter(ValueFilter::Numeric {
+                    comparison: NumericComparison::LessThanOrEqual,
+                    value: 2.5_f64.into(),
+                })
+                .query()?
+        ),
+        ["b", "d"]
+    );
+    assert_eq!(
+        keys(
+            metrics
+                .scan_keys("")
+                .filter(ValueFilter::bytes_start_with(b"{".to_vec()))
+                .query()?
+        ),
+        ["e"]
+    );
+    assert_eq!(
+        keys(
+            metrics
+                .scan_keys("")
+                .filter(ValueFilter::All(vec![
+                    ValueFilter::IsBytes,
+                    ValueFilter::HasExpiration(false),
+                ]))
+                .query()?
+        ),
+        ["c", "g"]
+    );
+
+    // Limits only count matching entries, and pages resume after the last
+    // key returned.
+    let numeric = || metrics.scan_keys("").filter(ValueFilter::IsNumeric);
+    let first_page = numeric().limit(2).query()?;
+    assert_eq!(keys(first_page.clone()), ["a", "b"]);
+    let second_page = numeric()
+        .limit(2)
+        .starting_after(&first_page[1].key)
+        .query()?;
+    assert_eq!(keys(second_page.clone()), ["d", "f"]);
+    assert!(numeric()
+        .limit(2)
+        .starting_after(&second_page[1].key)
+        .query()?
+        .is_empty());
+
+    // Pending deletes hide persisted keys.
+    metrics.delete_key("a")?;
+    assert_eq!(
+        keys(
+            metrics
+                .scan_keys("")
+                .filter(ValueFilter::IsNumeric)
+                .query()?
+        ),
+        ["b", "d", "f"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn dropping_storage_stops_tasks() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::keyvalue::KeyValue;
+    use bonsaidb_core::schema::SerializedCollection;
+
+    use crate::test_util::TestStorage;
+
+    for iteration in 0..5 {
+        let mut configuration = TestStorage::configuration()
+            .with_schema::<BasicSchema>()?
+            .tasks_worker_count(4);
+        configuration.memory_only = false;
+        let storage = TestStorage::open(configuration)?;
+        let db = storage.create_database::<BasicSchema>("stress", false)?;
+        for index in 0..100_u64 {
+            Basic::new(format!("{iteration}-{index}"))
+                .with_parent_id(index % 10)
+                .push_into(&db)?;
+            db.set_key(index.to_string(), &index)
+                .expire_in(Duration::from_millis(index))
+                .execute()?;
+            if index % 10 == 0 {
+                // Queue view updates without waiting for them to finish.
+                db.view::<BasicByParentId>()
+                    .with_access_policy(AccessPolicy::UpdateAfter)
+                    .query()?;
+            }
+        }
+
+        // Pending jobs keep the storage alive until they finish, so the
+        // storage may shut down on a worker thread.
+        let tasks = storage.instance.tasks().clone();
+        drop(db);
+        drop(storage);
+        tasks.stop(true);
+
+        assert_eq!(tasks.jobs.panicked_workers(), 0);
+        assert!(tasks.jobs.jobs.read().workers.is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn key_value_scans_with_callbacks() -> anyhow::Result<()> {
+    use std::time::SystemTime;
+
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::keyvalue::{KeyValue, Timestamp};
+    use serde::{Deserialize, Serialize};
+
+    use crate::test_util::{TestClock, TestStorage};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct UserSession {
+        user: String,
+    }
+
+    let start = SystemTime::now();
+    let clock = TestClock::new(Timestamp::from(start));
+    let storage = TestStorage::open(
+        TestStorage::configuration()
+            .with_schema::<()>()?
+            .key_value_clock(clock.clone().into()),
+    )?;
+    let db = storage.create_database::<()>("sessions", false)?;
+    let sessions = db.with_key_namespace("sessions");
+    let session = |user: &str| UserSession {
+        user: user.to_string(),
+    };
+    sessions.set_key("a", &session("ecton")).execute()?;
+    sessions.set_key("b", &session("other")).execute()?;
+    db.persist()?;
+    sessions
+        .set_key("c", &session("ecton"))
+        .expire_at(start + Duration::from_secs(3))
+        .execute()?;
+    sessions.set_key("d", &session("ecton")).execute()?;
+    sessions.set_numeric_key("e", 1_u64).execute()?;
+    // Keys outside of the namespace are never returned.
+    db.set_key("a", &session("ecton")).execute()?;
+
+    let sessions_for = |user: &'static str| {
+        db.scan_key_values(Some("sessions"), move |value| {
+            value
+                .deserialize::<UserSession>()
+                .map_or(false, |session| session.user == user)
+        })
+        .map(|entries| entries.into_iter().map(|(key, _)| key).collect::<Vec<_>>())
+    };
+    assert_eq!(sessions_for("ecton")?, ["a", "c", "d"]);
+    assert_eq!(sessions_for("other")?, ["b"]);
+
+    let (key, value) = db
+        .scan_key_values(Some("sessions"), |value| value.as_u64() == Some(1))?
+        .pop()
+        .expect("no numeric entries");
+    assert_eq!(key, "e");
+    assert_eq!(value.as_u64(), Some(1));
+
+    // Expired entries are skipped.
+    clock.advance(Duration::from_secs(4));
+    assert_eq!(sessions_for("ecton")?, ["a", "d"]);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "async")]
+fn dropped_queries_stop_waiting_for_views() -> anyhow::Result<()> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    use bonsaidb_core::connection::AsyncConnection;
+    use bonsaidb_core::document::{CollectionDocument, Emit};
+    use bonsaidb_core::keyvalue::AsyncKeyValue;
+    use bonsaidb_core::schema::{
+        Collection, CollectionMapReduce, Schema, SerializedCollection, View, ViewMapResult,
+        ViewSchema,
+    };
+    use serde::{Deserialize, Serialize};
+
+    // Mapping blocks until the gate is unlocked.
+    static GATE: Mutex<()> = Mutex::new(());
+    static MAPPING: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Schema, Debug)]
+    #[schema(name = "gates", collections = [Gate], core = bonsaidb_core)]
+    struct Gates;
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "gates", views = [GatedView], core = bonsaidb_core)]
+    struct Gate {
+        value: u64,
+    }
+
+    #[derive(View, ViewSchema, Debug, Clone)]
+    #[view(collection = Gate, key = u64, core = bonsaidb_core)]
+    struct GatedView;
+
+    impl CollectionMapReduce for GatedView {
+        fn map<'doc>(&self, document: CollectionDocument<Gate>) -> ViewMapResult<'doc, Self> {
+            MAPPING.store(true, Ordering::SeqCst);
+            drop(GATE.lock());
+            document.header.emit_key(document.contents.value)
+        }
+    }
+
+    // With a single blocking thread, a query that keeps waiting after being
+    // dropped prevents every other operation from running.
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .max_blocking_threads(1)
+        .enable_all()
+        .build()?;
+    let path = TestDirectory::new("dropped-queries-stop-waiting-for-views");
+    let db = Database::open::<Gates>(StorageConfiguration::new(&path))?;
+    Gate { value: 1 }.push_into(&db)?;
+    let db = db.into_async_with_runtime(runtime.handle().clone());
+
+    let gate = GATE.lock().unwrap();
+    runtime.block_on(async {
+        let query = db.view::<GatedView>().query();
+        assert!(tokio::time::timeout(Duration::from_millis(500), query)
+            .await
+            .is_err());
+        assert!(MAPPING.load(Ordering::SeqCst));
+
+        // The mapper is still blocked, but the abandoned query no longer
+        // occupies the blocking thread.
+        let unrelated = tokio::time::timeout(Duration::from_secs(5), db.get_key("key")).await;
+        assert!(matches!(unrelated, Ok(Ok(None))));
+        anyhow::Ok(())
+    })?;
+
+    // Once the mapper finishes, the view can be queried normally.
+    drop(gate);
+    let mappings = runtime.block_on(db.view::<GatedView>().query())?;
+    assert_eq!(mappings.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn bulk_deletes_report_each_result() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::schema::SerializedCollection;
+
+    let path = TestDirectory::new("bulk-deletes-report-each-result");
+    let storage = Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+    for name in ["tenant-a", "tenant-b"] {
+        storage.create_database::<BasicSchema>(name, false)?;
+        let db = storage.database::<BasicSchema>(name)?;
+        Basic::new(name).push_into(&db)?;
+        db.view::<BasicByParentId>().query()?;
+        storage.create_user(name)?;
+    }
+
+    let results = storage.delete_databases(&["tenant-a", "missing", "tenant-b"]);
+    assert_eq!(results.len(), 3);
+    assert!(results[0].is_ok());
+    assert!(matches!(
+        &results[1],
+        Err(bonsaidb_core::Error::DatabaseNotFound(name)) if name == "missing"
+    ));
+    assert!(results[2].is_ok());
+    assert!(storage
+        .list_databases()?
+        .iter()
+        .all(|database| !database.name.starts_with("tenant-")));
+    assert!(!path.join("tenant-a").exists());
+    assert!(!path.join("tenant-b").exists());
+
+    let results = storage.delete_users(&["tenant-a", "missing", "tenant-b"]);
+    assert_eq!(results.len(), 3);
+    assert!(results[0].is_ok());
+    assert!(matches!(
+        results[1],
+        Err(bonsaidb_core::Error::UserNotFound)
+    ));
+    assert!(results[2].is_ok());
+    assert!(matches!(
+        storage.delete_user("tenant-a"),
+        Err(bonsaidb_core::Error::UserNotFound)
+    ));
+
+    // Recreating a deleted database starts from scratch.
+    storage.create_database::<BasicSchema>("tenant-a", false)?;
+    let db = storage.database::<BasicSchema>("tenant-a")?;
+    assert_eq!(Basic::all(&db).count()?, 0);
+    assert!(db.view::<BasicByParentId>().query()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn repair_mode_quarantines_unreadable_documents() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::document::DocumentId;
+    use bonsaidb_core::schema::{Collection, SerializedCollection};
+    use nebari::tree::{Unversioned, Versioned};
+
+    use crate::config::RepairMode;
+    use crate::database::document_tree_name;
+    use crate::storage::quarantine_tree_name;
+    use crate::QuarantinedDocument;
+
+    let path = TestDirectory::new("repair-mode-quarantines-unreadable-documents");
+    let collection = Basic::collection_name();
+    let (id, original) = {
+        let storage =
+            Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+        assert!(storage.last_recovery_report().is_none());
+        let db = storage.create_database::<BasicSchema>("tests", false)?;
+        let id = DocumentId::from_u64(
+            Basic::new("corrupt")
+                .with_parent_id(1)
+                .push_into(&db)?
+                .header
+                .id,
+        );
+        Basic::new("intact").with_parent_id(1).push_into(&db)?;
+        assert_eq!(db.view::<BasicByParentId>().query()?.len(), 2);
+
+        let documents = db.roots().tree(
+            db.collection_tree::<Versioned, _>(&collection, document_tree_name(&collection))?,
+        )?;
+        let original = documents.get(id.as_ref())?.expect("document missing");
+        documents.set(id.to_vec(), b"not a document".to_vec())?;
+        (id, original)
+    };
+
+    // Reporting doesn't modify anything.
+    {
+        let storage = Storage::open(
+            StorageConfiguration::new(&path)
+                .with_schema::<BasicSchema>()?
+                .repair_mode(RepairMode::ReportOnly),
+        )?;
+        let report = storage.last_recovery_report().expect("report missing");
+        assert!(!report.is_clean());
+        let recovery = &report.databases["tests"];
+        assert_eq!(recovery.unreadable_documents.len(), 1);
+        assert_eq!(recovery.unreadable_documents[0].id, id);
+        assert!(!recovery.unreadable_documents[0].quarantined);
+        let db = storage.database::<BasicSchema>("tests")?;
+        assert!(db.quarantined_documents(&collection)?.is_empty());
+    }
+
+    let storage = Storage::open(
+        StorageConfiguration::new(&path)
+            .with_schema::<BasicSchema>()?
+            .repair_mode(RepairMode::RepairAggressively),
+    )?;
+    let report = storage.last_recovery_report().expect("report missing");
+    let quarantined = report.quarantined_documents().collect::<Vec<_>>();
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(quarantined[0].0, "tests");
+    assert_eq!(quarantined[0].1.id, id);
+    let view_name = storage
+        .database::<BasicSchema>("tests")?
+        .schematic()
+        .view::<BasicByParentId>()?
+        .view_name();
+    assert!(report.databases["tests"]
+        .outdated_views
+        .contains(&view_name));
+
+    // The rest of the collection is usable, and the view no longer includes
+    // the quarantined document.
+    let db = storage.database::<BasicSchema>("tests")?;
+    assert!(db
+        .collection::<Basic>()
+        .get(&id.deserialize::<u64>()?)?
+        .is_none());
+    assert_eq!(Basic::all(&db).count()?, 1);
+    assert_eq!(db.view::<BasicByParentId>().query()?.len(), 1);
+
+    let documents = db.quarantined_documents(&collection)?;
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].contents, b"not a document");
+    assert!(!documents[0].encoded);
+
+    // Restoring fails while the document is still unreadable.
+    assert!(db.restore_quarantined_document(&collection, &id).is_err());
+    assert_eq!(db.quarantined_documents(&collection)?.len(), 1);
+
+    // Once the stored bytes are readable, the document can be restored.
+    db.roots()
+        .tree(
+            db.collection_tree::<Unversioned, _>(&collection, quarantine_tree_name(&collection))?,
+        )?
+        .set(
+            id.to_vec(),
+            bincode::serialize(&QuarantinedDocument {
+                contents: original.to_vec(),
+                ..documents[0].clone()
+            })?,
+        )?;
+    db.restore_quarantined_document(&collection, &id)?;
+    assert!(db.quarantined_documents(&collection)?.is_empty());
+    let restored = Basic::get(&id.deserialize::<u64>()?, &db)?.expect("document not restored");
+    assert_eq!(restored.contents.value, "corrupt");
+    assert_eq!(db.view::<BasicByParentId>().query()?.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn repair_mode_reports_databases_that_cannot_be_opened() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+
+    use crate::config::RepairMode;
+
+    let path = TestDirectory::new("repair-mode-reports-databases-that-cannot-be-opened");
+    {
+        let storage =
+            Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+        storage.create_database::<BasicSchema>("tests", false)?;
+    }
+
+    // Without its schema registered, the database can't be opened, but the
+    // storage still opens.
+    let storage =
+        Storage::open(StorageConfiguration::new(&path).repair_mode(RepairMode::ReportOnly))?;
+    let report = storage.last_recovery_report().expect("report missing");
+    assert!(!report.is_clean());
+    assert!(report.databases["tests"].open_error.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_storage_waits_for_views() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::schema::SerializedCollection;
+
+    use crate::test_util::{wait_for_view_current, TestStorage};
+
+    let storage = TestStorage::open(TestStorage::configuration().with_schema::<Basic>()?)?;
+    let path = storage.path().to_path_buf();
+    let db = storage.create_database::<Basic>("views", false)?;
+    Basic::new("a").with_parent_id(1).push_into(&db)?;
+    Basic::new("b").with_parent_id(1).push_into(&db)?;
+
+    wait_for_view_current::<BasicByParentId>(&db)?;
+    assert_eq!(
+        db.view::<BasicByParentId>()
+            .with_access_policy(AccessPolicy::NoUpdate)
+            .query()?
+            .len(),
+        2
+    );
+
+    drop(db);
+    drop(storage);
+    assert!(!path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn scrub_resumes() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::keyvalue::KeyValue;
+    use bonsaidb_core::schema::SerializedCollection;
+
+    use crate::test_util::{wait_for_view_current, TestStorage};
+
+    let mut configuration = TestStorage::configuration().with_schema::<Basic>()?;
+    configuration.memory_only = false;
+    let storage = TestStorage::open(configuration)?;
+    let db = storage.create_database::<Basic>("scrub", false)?;
+    for i in 0..5 {
+        Basic::new(format!("{i}"))
+            .with_parent_id(i)
+            .push_into(&db)?;
+    }
+    db.set_key("key", &1_u32).execute()?;
+    db.persist()?;
+    wait_for_view_current::<BasicByParentId>(&db)?;
+
+    // Scrubbing doesn't create the trees that don't exist yet.
+    let mut trees = db.roots().tree_names()?;
+    trees.sort();
+    let full = db.scrub()?;
+    let mut trees_after = db.roots().tree_names()?;
+    trees_after.sort();
+    assert_eq!(trees, trees_after);
+    assert!(full.complete);
+    assert!(full.errors.is_empty());
+    assert!(full.chunks_checked > 5);
+    assert!(full.trees_checked > 2);
+
+    // A scrub that runs out of time checks at least one value, and the next
+    // scrub resumes where it stopped.
+    let mut chunks_checked = 0;
+    let mut scrubs = 0;
+    loop {
+        let partial = db.scrub_for(Duration::ZERO)?;
+        assert!(partial.errors.is_empty());
+        chunks_checked += partial.chunks_checked;
+        scrubs += 1;
+        if partial.complete {
+            break;
+        }
+    }
+    assert_eq!(chunks_checked, full.chunks_checked);
+    assert!(scrubs > 1);
+
+    // The cursor survives reopening the storage.
+    assert!(!db.scrub_for(Duration::ZERO)?.complete);
+    drop(db);
+    let storage = storage.reopen()?;
+    let db = storage.database::<Basic>("scrub")?;
+    let rest = db.scrub()?;
+    assert!(rest.complete);
+    assert!(rest.chunks_checked < full.chunks_checked);
+
+    Ok(())
+}
+
+#[test]
+fn database_lifecycle_events() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::schema::{Schema, SchemaName};
+
+    use crate::test_util::TestStorage;
+    use crate::DatabaseLifecycle;
+
+    #[derive(Debug)]
+    struct Events(flume::Sender<String>);
+
+    impl DatabaseLifecycle for Events {
+        fn database_opened(
+            &self,
+            name: &str,
+            schema: &SchemaName,
+        ) -> Result<(), bonsaidb_core::Error> {
+            drop(self.0.send(format!("opened {name} {schema}")));
+            Ok(())
+        }
+
+        fn database_closed(&self, name: &str) -> Result<(), bonsaidb_core::Error> {
+            drop(self.0.send(format!("closed {name}")));
+            Ok(())
+        }
+
+        fn database_deleted(&self, name: &str) -> Result<(), bonsaidb_core::Error> {
+            drop(self.0.send(format!("deleted {name}")));
+            // Errors are logged rather than returned to the caller.
+            Err(bonsaidb_core::Error::other("test", "callback failed"))
+        }
+    }
+
+    let (sender, events) = flume::unbounded();
+    let storage = TestStorage::open(
+        TestStorage::configuration()
+            .with_schema::<Basic>()?
+            .database_lifecycle(Events(sender)),
+    )?;
+    let next_event = || events.recv_timeout(Duration::from_secs(5));
+
+    drop(storage.create_database::<Basic>("lifecycle", false)?);
+    // Opening a database that is already open doesn't notify again.
+    drop(storage.database::<Basic>("lifecycle")?);
+    storage.delete_database("lifecycle")?;
+    assert_eq!(
+        next_event()?,
+        format!("opened lifecycle {}", Basic::schema_name())
+    );
+    assert_eq!(next_event()?, "closed lifecycle");
+    assert_eq!(next_event()?, "deleted lifecycle");
+
+    // Databases still open are closed when the storage is dropped.
+    drop(storage.create_database::<Basic>("open", false)?);
+    drop(storage);
+    assert_eq!(
+        next_event()?,
+        format!("opened open {}", Basic::schema_name())
+    );
+    assert_eq!(next_event()?, "closed open");
+    assert!(events.try_recv().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn compact_key_value_reclaims_space() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::keyvalue::KeyValue;
+
+    use crate::test_util::TestStorage;
+
+    let mut configuration = TestStorage::configuration().with_schema::<Basic>()?;
+    configuration.memory_only = false;
+    let storage = TestStorage::open(configuration)?;
+    let db = storage.create_database::<Basic>("compact-kv", false)?;
+    let tree_path = storage.path().join("compact-kv").join("kv.nebari");
+
+    let value = "a".repeat(256);
+    for round in 0..4 {
+        for key in 0..250 {
+            db.set_key(format!("{round}-{key}"), &value).execute()?;
+        }
+        db.persist()?;
+        for key in 0..250 {
+            db.delete_key(format!("{round}-{key}"))?;
+        }
+        db.persist()?;
+    }
+    db.set_key("kept", &value).execute()?;
+    db.persist()?;
+
+    let before = std::fs::metadata(&tree_path)?.len();
+    let reclaimed = db.compact_key_value()?;
+    let after = std::fs::metadata(&tree_path)?.len();
+    assert!(after < before);
+    assert_eq!(reclaimed, before - after);
+    assert_eq!(db.get_key("kept").into::<String>()?, Some(value));
+    assert!(db.get_key("0-0").query()?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn diff_reports_changed_documents() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::document::DocumentId;
+    use bonsaidb_core::schema::{Collection, SerializedCollection};
+
+    use crate::test_util::TestStorage;
+
+    let storage = TestStorage::open(TestStorage::configuration().with_schema::<Basic>()?)?;
+    let original = storage.create_database::<Basic>("original", false)?;
+    let copy = storage.create_database::<Basic>("copy", false)?;
+    // More documents than are read in a single page.
+    for index in 0..150_u64 {
+        Basic::new(index.to_string()).insert_into(&index, &original)?;
+        Basic::new(index.to_string()).insert_into(&index, &copy)?;
+    }
+    assert!(original.diff(&copy)?.is_empty());
+
+    let mut changed = Basic::get(&120_u64, &copy)?.unwrap();
+    changed.contents.value = String::from("changed");
+    changed.update(&copy)?;
+    Basic::get(&3_u64, &copy)?.unwrap().delete(&copy)?;
+    Basic::new("added").insert_into(&200_u64, &copy)?;
+
+    let diff = original.diff(&copy)?;
+    let collection = &diff.collections[&Basic::collection_name()];
+    assert_eq!(diff.collections.len(), 1);
+    assert_eq!(collection.added, vec![DocumentId::from_u64(200)]);
+    assert_eq!(collection.removed, vec![DocumentId::from_u64(3)]);
+    assert_eq!(collection.changed, vec![DocumentId::from_u64(120)]);
+
+    // Comparing against a snapshot reports the changes made since it was
+    // created.
+    let snapshot = copy.read_snapshot()?;
+    Basic::get(&200_u64, &copy)?.unwrap().delete(&copy)?;
+    let diff = copy.diff(&snapshot)?;
+    assert_eq!(
+        diff.collections[&Basic::collection_name()].added,
+        vec![DocumentId::from_u64(200)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn max_document_size() -> anyhow::Result<()> {
+    use bonsaidb_core::schema::{Collection, SerializedCollection};
+
+    use crate::DatabaseNonBlocking;
+
+    let path = TestDirectory::new("max-document-size");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+    let oversized = Basic::new("a".repeat(200)).push_into(&db)?;
+    drop(db);
+
+    let db =
+        Database::open::<BasicSchema>(StorageConfiguration::new(&path).max_document_size(100))?;
+    assert_eq!(db.max_document_size(&Basic::collection_name()), Some(100));
+    Basic::new("small").push_into(&db)?;
+    assert!(matches!(
+        Basic::new("b".repeat(200))
+            .push_into(&db)
+            .unwrap_err()
+            .error,
+        bonsaidb_core::Error::DocumentTooLarge { limit: 100, .. }
+    ));
+
+    // Documents written before the limit was configured can be read and
+    // shrunk, but not grown.
+    let mut oversized = Basic::get(&oversized.header.id, &db)?.expect("document missing");
+    oversized.contents.value = "a".repeat(300);
+    assert!(matches!(
+        oversized.update(&db),
+        Err(bonsaidb_core::Error::DocumentTooLarge { limit: 100, .. })
+    ));
+    let mut oversized = Basic::get(&oversized.header.id, &db)?.expect("document missing");
+    oversized.contents.value = "a".repeat(150);
+    oversized.update(&db)?;
+    oversized.delete(&db)?;
+
+    Ok(())
+}
+
+#[test]
+fn deferred_key_value_durability() -> anyhow::Result<()> {
+    use bonsaidb_core::keyvalue::{DurabilityMode, KeyValue};
+
+    use crate::config::{KeyValuePersistence, PersistenceThreshold};
+
+    let path = TestDirectory::new("deferred-key-value-durability");
+    let db =
+        Database::open::<BasicSchema>(StorageConfiguration::new(&path).key_value_persistence(
+            KeyValuePersistence::lazy([PersistenceThreshold::after_changes(1_000)]),
+        ))?;
+    assert!(db.durability_receipt().is_durable());
+
+    db.set_key("deferred", &1_u32).execute()?;
+    let receipt = db.durability_receipt();
+    assert!(!receipt.is_durable());
+
+    // An immediate write persists every pending change, including the
+    // deferred one.
+    db.set_key("immediate", &2_u32)
+        .durability(DurabilityMode::Immediate)
+        .execute()?;
+    assert!(receipt.is_durable());
+    receipt.wait()?;
+    assert!(db.durability_receipt().is_durable());
+
+    Ok(())
+}
+
+#[test]
+fn admin_extension() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::{HasSchema, StorageConnection};
+    use bonsaidb_core::document::{CollectionDocument, Emit};
+    use bonsaidb_core::schema::{
+        Collection, CollectionMapReduce, Schema, SerializedCollection, SerializedView, View,
+        ViewMapResult, ViewSchema,
+    };
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Schema, Debug)]
+    #[schema(name = "tenant-admin", collections = [TenantSetting], core = bonsaidb_core)]
+    struct TenantAdmin;
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "tenant-settings", views = [SettingsByTenant], core = bonsaidb_core)]
+    struct TenantSetting {
+        tenant: String,
+        value: u32,
+    }
+
+    #[derive(View, ViewSchema, Debug, Clone)]
+    #[view(collection = TenantSetting, key = String, value = u32, core = bonsaidb_core)]
+    struct SettingsByTenant;
+
+    impl CollectionMapReduce for SettingsByTenant {
+        fn map<'doc>(
+            &self,
+            document: CollectionDocument<TenantSetting>,
+        ) -> ViewMapResult<'doc, Self::View> {
+            document
+                .header
+                .emit_key_and_value(document.contents.tenant, document.contents.value)
+        }
+    }
+
+    #[derive(Schema, Debug)]
+    #[schema(name = "conflicting-admin", collections = [ConflictingUser], core = bonsaidb_core)]
+    struct ConflictingAdmin;
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "user", authority = "khonsulabs", core = bonsaidb_core)]
+    struct ConflictingUser;
+
+    assert!(matches!(
+        StorageConfiguration::default().with_admin_extension::<ConflictingAdmin>(),
+        Err(crate::Error::Core(
+            bonsaidb_core::Error::CollectionDefinedByMultipleSchemas { .. }
+        ))
+    ));
+
+    let path = TestDirectory::new("admin-extension");
+    // Create the admin database before the extension is added.
+    drop(Storage::open(StorageConfiguration::new(&path))?);
+
+    let storage =
+        Storage::open(StorageConfiguration::new(&path).with_admin_extension::<TenantAdmin>()?)?;
+    TenantSetting {
+        tenant: String::from("acme"),
+        value: 42,
+    }
+    .push_into(&storage.admin())?;
+    drop(storage);
+
+    let storage =
+        Storage::open(StorageConfiguration::new(&path).with_admin_extension::<TenantAdmin>()?)?;
+    let admin = storage.admin();
+    assert!(admin.schematic().contains_collection::<TenantSetting>());
+    let mappings = SettingsByTenant::entries(&admin).with_key("acme").query()?;
+    assert_eq!(mappings.len(), 1);
+    assert_eq!(mappings[0].value, 42);
+
+    Ok(())
+}
+
+#[test]
+fn database_events() -> anyhow::Result<()> {
+    use bonsaidb_core::admin::DatabaseEvent;
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::pubsub::{Subscriber as _, TryReceiveError};
+    use bonsaidb_core::schema::Schema;
+
+    let path = TestDirectory::new("database-events");
+    let storage = Storage::open(StorageConfiguration::new(&path).with_schema::<()>()?)?;
+    let subscriber = storage.subscribe_to_database_events()?;
+    let events = subscriber.receiver().typed::<DatabaseEvent>();
+
+    storage.create_database::<()>("events", false)?;
+    assert_eq!(
+        events.receive()?.payload()?,
+        DatabaseEvent::Created {
+            name: String::from("events"),
+            schema: <() as Schema>::schema_name(),
+        }
+    );
+
+    // Creating a database that already exists doesn't publish an event.
+    storage.create_database::<()>("events", true)?;
+    assert!(matches!(events.try_receive(), Err(TryReceiveError::Empty)));
+
+    storage.delete_database("events")?;
+    assert_eq!(
+        events.receive()?.payload()?,
+        DatabaseEvent::Deleted {
+            name: String::from("events"),
+        }
+    );
+
+    // Subscribing uses the storage's session.
+    let restricted = storage
+        .with_effective_permissions(Permissions::default())
+        .unwrap();
+    assert!(matches!(
+        restricted.subscribe_to_database_events(),
+        Err(bonsaidb_core::Error::PermissionDenied(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn offline_inspection() -> anyhow::Result<()> {
+    use bonsaidb_core::admin::ADMIN_DATABASE_NAME;
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::keyvalue::{KeyValue, Numeric, Value};
+    use bonsaidb_core::schema::{Collection, Schema, SerializedCollection, View, ViewSchema};
+
+    use crate::offline::OfflineStorage;
+
+    let path = TestDirectory::new("offline-inspection");
+    let storage = Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+    let db = storage.create_database::<BasicSchema>("inspected", false)?;
+    let header = Basic::new("offline").push_into(&db)?.header;
+    db.view::<BasicByParentId>().query()?;
+    db.with_key_namespace("counters")
+        .set_numeric_key("visits", 3_u64)
+        .execute()?;
+    db.set_numeric_key("global", 1_u64).execute()?;
+    db.persist()?;
+
+    // The storage can't be inspected while it is open.
+    assert!(matches!(
+        OfflineStorage::open(&path),
+        Err(crate::Error::StorageInUse)
+    ));
+    drop(db);
+    drop(storage);
+
+    let offline = OfflineStorage::open(&path)?;
+    let databases = offline.databases()?;
+    assert_eq!(databases.len(), 1);
+    assert_eq!(databases[0].name, "inspected");
+    assert_eq!(databases[0].schema, BasicSchema::schema_name());
+
+    let inspected = offline.database("inspected")?;
+    assert!(inspected.collections().contains(&Basic::collection_name()));
+    let documents = inspected.documents(&Basic::collection_name())?;
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].id, header.id);
+    assert_eq!(
+        Basic::deserialize(&documents[0].contents)?,
+        Basic::new("offline")
+    );
+    assert!(documents[0].value.is_some());
+
+    let views = inspected.views()?;
+    let view = views
+        .iter()
+        .find(|view| view.name == BasicByParentId.view_name().to_string())
+        .expect("view not listed");
+    assert_eq!(view.collection, Basic::collection_name());
+    assert_eq!(view.schema_version, Some(BasicByParentId.version()));
+    assert!(!view.trees.is_empty());
+
+    let counters = inspected.key_value_entries(Some("counters"))?;
+    assert_eq!(counters.len(), 1);
+    assert_eq!(counters[0].key, "visits");
+    assert_eq!(
+        counters[0].value,
+        Value::Numeric(Numeric::UnsignedInteger(3))
+    );
+    assert_eq!(inspected.key_value_entries(None)?.len(), 1);
+
+    let statistics = offline.statistics()?;
+    assert!(statistics.databases.contains_key(ADMIN_DATABASE_NAME));
+    let inspected_statistics = &statistics.databases["inspected"];
+    assert_eq!(
+        inspected_statistics
+            .documents
+            .get(&Basic::collection_name()),
+        Some(&1)
+    );
+    assert_eq!(inspected_statistics.keys, 2);
+    assert!(inspected_statistics.bytes > 0);
+
+    assert!(matches!(
+        offline.database("missing"),
+        Err(crate::Error::Core(bonsaidb_core::Error::DatabaseNotFound(
+            _
+        )))
+    ));
+
+    // Inspecting the storage didn't prevent it from being opened normally
+    // afterwards.
+    drop(inspected);
+    drop(offline);
+    Storage::open(StorageConfiguration::new(&path).with_schema::<BasicSchema>()?)?;
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "compression")]
+fn offline_inspection_rejects_compressed_storage() -> anyhow::Result<()> {
+    use bonsaidb_core::schema::SerializedCollection;
+
+    use crate::config::Compression;
+    use crate::offline::OfflineStorage;
+
+    let path = TestDirectory::new("offline-compressed");
+    let db = Database::open::<BasicSchema>(
+        StorageConfiguration::new(&path).default_compression(Compression::Lz4),
+    )?;
+    Basic::new("compressed").push_into(&db)?;
+    drop(db);
+
+    let inspected = OfflineStorage::open(&path).and_then(|offline| {
+        for database in offline.databases()? {
+            offline.database(&database.name)?;
+        }
+        Ok(())
+    });
+    assert!(matches!(inspected, Err(crate::Error::ProtectedStorage)));
+
+    Ok(())
+}
+
+#[test]
+fn projected_reads() -> anyhow::Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use bonsaidb_core::schema::{Collection, SerializedCollection};
+    use serde::{Deserialize, Deserializer, Serialize};
+
+    static BLOBS_DESERIALIZED: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, Serialize)]
+    #[serde(transparent)]
+    struct Blob(Vec<u8>);
+
+    impl<'de> Deserialize<'de> for Blob {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            BLOBS_DESERIALIZED.fetch_add(1, Ordering::SeqCst);
+            Vec::deserialize(deserializer).map(Self)
+        }
+    }
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "attachments", core = bonsaidb_core)]
+    struct Attachment {
+        name: String,
+        data: Blob,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct AttachmentName {
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct AttachmentLabel {
+        #[serde(default)]
+        label: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct RequiredLabel {
+        #[allow(dead_code)]
+        label: String,
+    }
+
+    let path = TestDirectory::new("projected-reads");
+    let db = Database::open::<Attachment>(StorageConfiguration::new(&path))?;
+    let header = Attachment {
+        name: String::from("photo.png"),
+        data: Blob(vec![0; 1024 * 1024]),
+    }
+    .push_into(&db)?
+    .header;
+
+    let name = Attachment::get_projection::<AttachmentName, _, _>(&header.id, &db)?;
+    assert_eq!(
+        name,
+        Some(AttachmentName {
+            name: String::from("photo.png")
+        })
+    );
+    assert_eq!(BLOBS_DESERIALIZED.load(Ordering::SeqCst), 0);
+
+    // Absent fields use their default value, or fail to deserialize if they
+    // don't have one.
+    assert_eq!(
+        Attachment::get_projection::<AttachmentLabel, _, _>(&header.id, &db)?,
+        Some(AttachmentLabel { label: None })
+    );
+    assert!(Attachment::get_projection::<RequiredLabel, _, _>(&header.id, &db).is_err());
+    assert_eq!(
+        Attachment::get_projection::<AttachmentName, _, _>(&(header.id + 1), &db)?,
+        None
+    );
+
+    // Reading the whole document deserializes the blob.
+    let attachment = Attachment::get(&header.id, &db)?.expect("missing document");
+    assert_eq!(attachment.contents.data.0.len(), 1024 * 1024);
+    assert_eq!(BLOBS_DESERIALIZED.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+#[test]
+fn integrity_scans_are_throttled() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+
+    use crate::IntegrityScanStatus;
+
+    let path = TestDirectory::new("integrity-scans-are-throttled");
+    let configuration = || {
+        StorageConfiguration::new(&path)
+            .with_schema::<BasicSchema>()
+            .unwrap()
+            .check_view_integrity_on_open(true)
+            .tasks_max_concurrent_integrity_scans(1)
+    };
+    let storage = Storage::open(configuration())?;
+    for index in 0..3 {
+        storage.create_database::<BasicSchema>(&format!("db{index}"), false)?;
+    }
+    drop(storage);
+
+    let storage = Storage::open(configuration())?;
+    for index in 0..3 {
+        storage.database::<BasicSchema>(&format!("db{index}"))?;
+    }
+    // Every view of each database has been scheduled to be scanned, but only
+    // one scan runs at a time.
+    let status = storage.integrity_scan_status();
+    assert_eq!(status.max_concurrent, 1);
+    assert!(status.running <= 1);
+
+    // Querying a view whose scan is still queued moves its scan ahead of the
+    // others instead of queueing another.
+    let db = storage.database::<BasicSchema>("db2")?;
+    db.view::<BasicByParentId>().query()?;
+
+    crate::test_util::wait_for_tasks(&storage);
+    assert_eq!(
+        storage.integrity_scan_status(),
+        IntegrityScanStatus {
+            running: 0,
+            queued: 0,
+            max_concurrent: 1,
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn storage_format_upgrades() -> anyhow::Result<()> {
+    use bonsaidb_core::connection::StorageConnection;
+    use nebari::tree::{Root, Unversioned};
+
+    use crate::database::keyvalue::KEY_TREE;
+    use crate::{Error, STORAGE_FORMAT_VERSION};
+
+    let path = TestDirectory::new("storage-format-upgrades");
+    let configuration = || {
+        StorageConfiguration::new(&path)
+            .with_schema::<BasicSchema>()
+            .unwrap()
+    };
+    let format_version = path.join("format-version");
+
+    // New storage records the current format.
+    let storage = Storage::open(configuration())?;
+    let db = storage.create_database::<BasicSchema>("tests", false)?;
+    db.set_key("key", &1_u32).execute()?;
+    db.persist_key_value_store()?;
+    assert_eq!(
+        std::fs::read_to_string(&format_version)?,
+        STORAGE_FORMAT_VERSION.to_string()
+    );
+
+    // Store an entry whose key is missing the namespace separator, as if it
+    // were written by an older version.
+    let tree = db.roots().tree(Unversioned::tree(KEY_TREE))?;
+    let entry = tree.get(b"\0key")?.expect("entry not persisted");
+    tree.set(b"legacy".to_vec(), entry)?;
+    drop((tree, db, storage));
+
+    // Storage without a recorded format is from before the version was
+    // recorded, and can still be opened.
+    std::fs::remove_file(&format_version)?;
+    drop(Storage::open(configuration())?);
+    assert!(!format_version.exists());
+
+    // Simulate an upgrade that was interrupted after migrating the admin
+    // database.
+    std::fs::write(path.join("format-upgrade"), "1\n_admin")?;
+    let upgrade = Storage::upgrade(&path)?;
+    assert_eq!(upgrade.previous_version, 0);
+    assert_eq!(
+        upgrade.migrations,
+        vec!["kv full-key re-encoding", "header-versioning"]
+    );
+    assert!(!path.join("format-upgrade").exists());
+    assert_eq!(
+        std::fs::read_to_string(&format_version)?,
+        STORAGE_FORMAT_VERSION.to_string()
+    );
+    assert!(Storage::upgrade(&path)?.migrations.is_empty());
+
+    let storage = Storage::open(configuration())?;
+    let db = storage.database::<BasicSchema>("tests")?;
+    assert_eq!(db.get_key("legacy").into::<u32>()?, Some(1));
+    assert_eq!(db.get_key("key").into::<u32>()?, Some(1));
+    drop((db, storage));
+
+    // Storage written by a newer version is refused.
+    std::fs::write(&format_version, (STORAGE_FORMAT_VERSION + 1).to_string())?;
+    match Storage::open(configuration()) {
+        Err(Error::NewerStorageFormat { found, supported }) => {
+            assert_eq!(found, STORAGE_FORMAT_VERSION + 1);
+            assert_eq!(supported, STORAGE_FORMAT_VERSION);
+        }
+        other => unreachable!("unexpected result: {other:?}"),
+    }
+    assert!(matches!(
+        Storage::upgrade(&path),
+        Err(Error::NewerStorageFormat { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "async")]
+fn overloaded_writes_are_shed() -> anyhow::Result<()> {
+    use std::time::Instant;
+
+    use bonsaidb_core::connection::AsyncStorageConnection;
+    use bonsaidb_core::schema::SerializedCollection;
+
+    use crate::config::WorkCategory;
+    use crate::AsyncStorage;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .max_blocking_threads(4)
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let path = TestDirectory::new("overloaded-writes-are-shed");
+        let storage = AsyncStorage::open(
+            StorageConfiguration::new(&path)
+                .with_schema::<BasicSchema>()?
+                .max_in_flight(WorkCategory::Documents, 2),
+        )
+        .await?;
+        let db = storage
+            .create_database::<BasicSchema>("tests", false)
+            .await?;
+        let existing = Basic::new("existing").push_into_async(&db).await?;
+
+        // Flood the database with writes while reading a document.
+        let writers = (0..500)
+            .map(|index| {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    Basic::new(index.to_string())
+                        .push_into_async(&db)
+                        .await
+                        .map(drop)
+                        .map_err(|err| err.error)
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut latencies = Vec::new();
+        for _ in 0..100 {
+            let start = Instant::now();
+            assert!(Basic::get_async(&existing.header.id, &db).await?.is_some());
+            latencies.push(start.elapsed());
+        }
+
+        let mut rejected = 0;
+        for writer in writers {
+            match writer.await? {
+                Ok(()) => {}
+                Err(bonsaidb_core::Error::Overloaded { retry_after_hint }) => {
+                    assert_eq!(retry_after_hint, Duration::from_millis(100));
+                    rejected += 1;
+                }
+                Err(other) => return Err(anyhow::Error::from(other)),
+            }
+        }
+        assert!(rejected > 0);
+
+        let statistics = storage.admission_statistics();
+        assert_eq!(statistics.documents.rejected, rejected);
+        assert_eq!(statistics.documents.in_flight, 0);
+        assert_eq!(statistics.documents.limit, Some(2));
+        assert_eq!(statistics.views.rejected, 0);
+
+        // Reads are never rejected, so they don't wait behind the shed writes.
+        latencies.sort();
+        let p99 = latencies[latencies.len() * 99 / 100];
+        assert!(p99 < Duration::from_secs(1), "p99 read latency: {p99:?}");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn cached_prefix_reductions_match_recomputation() -> anyhow::Result<()> {
+    use bonsaidb_core::document::{CollectionDocument, Emit};
+    use bonsaidb_core::key::KeyPrefixes;
+    use bonsaidb_core::schema::view::map::ViewMappedValue;
+    use bonsaidb_core::schema::view::ReducePrefixesFn;
+    use bonsaidb_core::schema::{
+        Collection, CollectionMapReduce, ReduceResult, Schema, SerializedCollection,
+        SerializedView, View, ViewMapResult, ViewSchema,
+    };
+    use nebari::tree::Unversioned;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use serde::{Deserialize, Serialize};
+
+    use crate::views::view_prefix_reductions_tree_name;
+
+    type Date = (u16, u8, u8);
+
+    #[derive(Schema, Debug)]
+    #[schema(name = "ledger", collections = [Sale], core = bonsaidb_core)]
+    struct Ledger;
+
+    #[derive(Collection, Debug, Clone, Serialize, Deserialize)]
+    #[collection(name = "sales", views = [SalesByDate, UncachedSalesByDate], core = bonsaidb_core)]
+    struct Sale {
+        date: Date,
+        amount: u64,
+    }
+
+    #[derive(View, Debug, Clone)]
+    #[view(collection = Sale, key = Date, value = u64, core = bonsaidb_core)]
+    struct SalesByDate;
+
+    impl ViewSchema for SalesByDate {
+        type MappedKey<'doc> = Date;
+        type View = Self;
+
+        fn reduce_prefixes(&self) -> Option<ReducePrefixesFn<Self>> {
+            Some(<Date as KeyPrefixes>::encoded_prefixes)
+        }
+    }
+
+    impl CollectionMapReduce for SalesByDate {
+        fn map<'doc>(&self, document: CollectionDocument<Sale>) -> ViewMapResult<'doc, Self> {
+            document
+                .header
+                .emit_key_and_value(document.contents.date, document.contents.amount)
+        }
+
+        fn reduce(
+            &self,
+            mappings: &[ViewMappedValue<'_, Self>],
+            _rereduce: bool,
+        ) -> ReduceResult<Self::View> {
+            Ok(mappings.iter().map(|mapping| mapping.value).sum())
+        }
+    }
+
+    #[derive(View, Debug, Clone)]
+    #[view(collection = Sale, key = Date, value = u64, core = bonsaidb_core)]
+    struct UncachedSalesByDate;
+
+    impl ViewSchema for UncachedSalesByDate {
+        type MappedKey<'doc> = Date;
+        type View = Self;
+    }
+
+    impl CollectionMapReduce for UncachedSalesByDate {
+        fn map<'doc>(&self, document: CollectionDocument<Sale>) -> ViewMapResult<'doc, Self> {
+            document
+                .header
+                .emit_key_and_value(document.contents.date, document.contents.amount)
+        }
+
+        fn reduce(
+            &self,
+            mappings: &[ViewMappedValue<'_, Self>],
+            _rereduce: bool,
+        ) -> ReduceResult<Self::View> {
+            Ok(mappings.iter().map(|mapping| mapping.value).sum())
+        }
+    }
+
+    fn random_date(rng: &mut StdRng) -> Date {
+        (
+            rng.gen_range(2021..=2023),
+            rng.gen_range(1..=4),
+            rng.gen_range(1..=6),
+        )
+    }
+
+    fn total(sales: &[CollectionDocument<Sale>], start: Date, end: Date) -> u64 {
+        sales
+            .iter()
+            .filter(|sale| (start..end).contains(&sale.contents.date))
+            .map(|sale| sale.contents.amount)
+            .sum()
+    }
+
+    let path = TestDirectory::new("cached-prefix-reductions-match-recomputation");
+    let db = Database::open::<Ledger>(StorageConfiguration::new(&path))?;
+    let mut rng = StdRng::seed_from_u64(0x5a1e5);
+    let mut sales = Vec::<CollectionDocument<Sale>>::new();
+
+    for round in 0..20 {
+        for _ in 0..25 {
+            match rng.gen_range(0..4) {
+                0 if !sales.is_empty() => {
+                    let sale = sales.swap_remove(rng.gen_range(0..sales.len()));
+                    sale.delete(&db)?;
+                }
+                1 if !sales.is_empty() => {
+                    let index = rng.gen_range(0..sales.len());
+                    let sale = &mut sales[index];
+                    sale.contents.amount = rng.gen_range(1..1000);
+                    if rng.gen_bool(0.5) {
+                        sale.contents.date = random_date(&mut rng);
+                    }
+                    sale.update(&db)?;
+                }
+                _ => {
+                    let sale = Sale {
+                        date: random_date(&mut rng),
+                        amount: rng.gen_range(1..1000),
+                    }
+                    .push_into(&db)?;
+                    sales.push(sale);
+                }
+            }
+        }
+
+        let mut ranges = Vec::new();
+        for year in 2021..=2023 {
+            ranges.push(((year, 0, 0), (year + 1, 0, 0)));
+            for month in 1..=4 {
+                ranges.push(((year, month, 0), (year, month + 1, 0)));
+            }
+        }
+        for _ in 0..10 {
+            let (first, second) = (random_date(&mut rng), random_date(&mut rng));
+            ranges.push((first.min(second), first.max(second)));
+        }
+
+        assert_eq!(
+            db.view::<SalesByDate>().reduce()?,
+            db.view::<UncachedSalesByDate>().reduce()?,
+            "round {round}"
+        );
+        for (start, end) in ranges {
+            let cached = db
+                .view::<SalesByDate>()
+                .with_key_range(start..end)
+                .reduce()?;
+            let uncached = db
+                .view::<UncachedSalesByDate>()
+                .with_key_range(start..end)
+                .reduce()?;
+            assert_eq!(cached, uncached, "round {round}: {start:?}..{end:?}");
+            assert_eq!(
+                cached,
+                total(&sales, start, end),
+                "round {round}: {start:?}..{end:?}"
+            );
+        }
+    }
+
+    // Each month's reduction is cached.
+    let month = (2022_u16, 2_u8, 1_u8).encoded_prefixes()?.pop().unwrap();
+    let cached = db
+        .roots()
+        .tree(db.collection_tree::<Unversioned, _>(
+            &Sale::collection_name(),
+            view_prefix_reductions_tree_name(&SalesByDate.view_name()),
+        )?)?
+        .get(&month)?
+        .map(|value| SalesByDate::deserialize(&value))
+        .transpose()?;
+    assert_eq!(
+        cached.unwrap_or_default(),
+        total(&sales, (2022, 2, 0), (2022, 3, 0))
+    );
+
+    // Each year's reduction is derived from its months' reductions.
+    let year = (2022_u16, 2_u8, 1_u8).encoded_prefixes()?.remove(0);
+    let cached = db
+        .roots()
+        .tree(db.collection_tree::<Unversioned, _>(
+            &Sale::collection_name(),
+            view_prefix_reductions_tree_name(&SalesByDate.view_name()),
+        )?)?
+        .get(&year)?
+        .map(|value| SalesByDate::deserialize(&value))
+        .transpose()?;
+    assert_eq!(
+        cached.unwrap_or_default(),
+        total(&sales, (2022, 0, 0), (2023, 0, 0))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn compaction_order_weighs_priority_and_fragmentation() -> anyhow::Result<()> {
+    use bonsaidb_core::schema::{Collection, Schema, SerializedCollection};
+    use serde::{Deserialize, Serialize};
+
+    use crate::config::CompactionPriority;
+    use crate::tasks::compaction_order;
+
+    #[derive(Schema, Debug)]
+    #[schema(name = "tiers", collections = [Hot, Warm, Cold], core = bonsaidb_core)]
+    struct Tiers;
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "hot", core = bonsaidb_core)]
+    struct Hot {
+        value: String,
+    }
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "warm", core = bonsaidb_core)]
+    struct Warm {
+        value: String,
+    }
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "cold", core = bonsaidb_core)]
+    struct Cold {
+        value: String,
+    }
+
+    let path = TestDirectory::new("compaction-order");
+    let db = Database::open::<Tiers>(
+        StorageConfiguration::new(&path)
+            .compaction_priority::<Hot>(CompactionPriority::High)
+            .compaction_priority::<Cold>(CompactionPriority::Low),
+    )?;
+
+    // Large documents leave little of each file unused, so the collections
+    // are ordered by their priorities.
+    let value = "a".repeat(65_536);
+    Hot {
+        value: value.clone(),
+    }
+    .push_into(&db)?;
+    Warm {
+        value: value.clone(),
+    }
+    .push_into(&db)?;
+    let mut cold = Cold { value }.push_into(&db)?;
+    let by_priority = vec![
+        Hot::collection_name(),
+        Warm::collection_name(),
+        Cold::collection_name(),
+    ];
+    assert_eq!(compaction_order(&db)?, by_priority);
+
+    // Rewriting the cold document leaves most of its file unused, which
+    // outweighs its lower priority.
+    for letter in 'b'..='k' {
+        cold.contents.value = letter.to_string().repeat(65_536);
+        cold.update(&db)?;
+    }
+    assert_eq!(
+        compaction_order(&db)?,
+        vec![
+            Cold::collection_name(),
+            Hot::collection_name(),
+            Warm::collection_name(),
+        ]
+    );
+
+    // Once compacted, the priorities determine the order again.
+    db.compact()?;
+    assert_eq!(compaction_order(&db)?, by_priority);
+
+    Ok(())
+}
+
+#[test]
+fn storage_cannot_be_opened_twice() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    use bonsaidb_core::schema::SerializedCollection;
+
+    let path = TestDirectory::new("storage-opened-twice");
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+    let header = Basic::new("opened").push_into(&db)?.header;
+
+    assert!(matches!(
+        Storage::open(StorageConfiguration::new(&path).lock_timeout(Duration::from_millis(100))),
+        Err(crate::Error::StorageInUse)
+    ));
+    assert!(matches!(
+        Database::open::<BasicSchema>(
+            StorageConfiguration::new(&path).lock_timeout(Duration::from_millis(100))
+        ),
+        Err(crate::Error::StorageInUse)
+    ));
+
+    // Without a timeout, opening waits until the other handle is closed.
+    let closer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        drop(db);
+    });
+    let db = Database::open::<BasicSchema>(StorageConfiguration::new(&path))?;
+    closer.join().unwrap();
+    assert!(Basic::get(&header.id, &db)?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn view_query_consistency() -> anyhow::Result<()> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use bonsaidb_core::connection::Consistency;
+    use bonsaidb_core::document::{CollectionDocument, Emit};
+    use bonsaidb_core::schema::{
+        Collection, CollectionMapReduce, Schema, SerializedCollection, View, ViewMapResult,
+        ViewSchema,
+    };
+    use serde::{Deserialize, Serialize};
+
+    static BLOCK_MAPPING: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Schema, Debug)]
+    #[schema(name = "gated", collections = [Event], core = bonsaidb_core)]
+    struct Gated;
+
+    #[derive(Collection, Debug, Serialize, Deserialize)]
+    #[collection(name = "events", views = [EventsByValue], core = bonsaidb_core)]
+    struct Event {
+        value: u32,
+    }
+
+    #[derive(View, ViewSchema, Debug, Clone)]
+    #[view(collection = Event, key = u32, core = bonsaidb_core)]
+    #[view_schema(core = bonsaidb_core)]
+    struct EventsByValue;
+
+    impl CollectionMapReduce for EventsByValue {
+        fn map<'doc>(&self, document: CollectionDocument<Event>) -> ViewMapResult<'doc, Self> {
+            while BLOCK_MAPPING.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            document.header.emit_key(document.contents.value)
+        }
+    }
+
+    let path = TestDirectory::new("view-query-consistency");
+    let db = Database::open::<Gated>(StorageConfiguration::new(&path))?;
+    assert_eq!(db.last_seen_transaction_id(), None);
+
+    Event { value: 1 }.push_into(&db)?;
+    let seen = db
+        .last_seen_transaction_id()
+        .expect("transaction not tracked");
+    assert_eq!(db.last_transaction_id()?, Some(seen));
+    let strong = Consistency::Strong {
+        timeout: Duration::from_secs(30),
+    };
+    assert_eq!(
+        db.view::<EventsByValue>()
+            .with_consistency(strong)
+            .query()?
+            .len(),
+        1
+    );
+
+    // While the mapper is stuck, strong queries fail once their timeout
+    // elapses, and eventual queries return the outdated entries.
+    BLOCK_MAPPING.store(true, Ordering::SeqCst);
+    Event { value: 2 }.push_into(&db)?;
+    let seen = db
+        .last_seen_transaction_id()
+        .expect("transaction not tracked");
+    let err = db
+        .view::<EventsByValue>()
+        .with_consistency(Consistency::Strong {
+            timeout: Duration::from_millis(100),
+        })
+        .query()
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            bonsaidb_core::Error::ViewUpdateTimeout { transaction_id, .. } if transaction_id == seen
+        ),
+        "unexpected error: {err}"
+    );
+    assert_eq!(
+        db.view::<EventsByValue>()
+            .with_consistency(Consistency::Eventual)
+            .query()?
+            .len(),
+        1
+    );
+
+    BLOCK_MAPPING.store(false, Ordering::SeqCst);
+    assert_eq!(
+        db.view::<EventsByValue>()
+            .with_consistency(strong)
+            .query()?
+            .len(),
+        2
+    );
+
+    Ok(())
+}
+
+#[test]
+fn expiration_loading_is_batched_and_resumable() -> anyhow::Result<()> {
+    use std::time::{Duration, Instant};
+
+    use bonsaidb_core::arc_bytes::serde::Bytes;
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::keyvalue::{KeyValue, Numeric, Timestamp, Value};
+    use nebari::tree::Unversioned;
+
+    use crate::database::keyvalue::{Entry, ExpirationCursors, EXPIRATION_CURSORS_FILE, KEY_TREE};
+    use crate::ExpirationLoadStatus;
+
+    fn loaded(db: &Database) -> ExpirationLoadStatus {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let status = db.key_value_expiration_status();
+            if status.complete {
+                return status;
+            }
+            assert!(Instant::now() < deadline, "expirations not loaded");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    let path = TestDirectory::new("expiration-loading-is-batched-and-resumable");
+    let configuration = || {
+        StorageConfiguration::new(&path)
+            .tasks_expiration_load_batch_size(100)
+            .with_schema::<BasicSchema>()
+            .unwrap()
+    };
+    let cursors = || ExpirationCursors::new(path.join(EXPIRATION_CURSORS_FILE));
+
+    // Store 1,000 entries directly, as if they had been written before the
+    // storage was last closed. Every even entry has expired.
+    let storage = Storage::open(configuration())?;
+    let db = storage.create_database::<BasicSchema>("tests", false)?;
+    loaded(&db);
+    let tree = db.roots().tree(Unversioned::tree(KEY_TREE))?;
+    let later = Timestamp::now() + Duration::from_secs(60 * 60);
+    for index in 0..1_000_u64 {
+        let entry = Entry {
+            value: Value::Numeric(Numeric::UnsignedInteger(index)),
+            expiration: Some(if index % 2 == 0 {
+                Timestamp::MIN
+            } else {
+                later
+            }),
+            last_updated: Timestamp::MIN,
+        };
+        tree.set(
+            format!("\0key{index:04}").into_bytes(),
+            bincode::serialize(&entry)?,
+        )?;
+    }
+    drop((tree, db, storage));
+
+    let storage = Storage::open(configuration())?;
+    let db = storage.database::<BasicSchema>("tests")?;
+    assert_eq!(
+        loaded(&db),
+        ExpirationLoadStatus {
+            keys_scanned: 1_000,
+            expired_keys_removed: 500,
+            expirations_loaded: 500,
+            batches: 10,
+            complete: true,
+        }
+    );
+    assert!(db.get_key("key0000").query()?.is_none());
+    assert_eq!(db.get_key("key0001").into_u64()?, Some(1));
+    assert!(cursors().cursor("tests")?.is_none());
+    db.persist_key_value_store()?;
+    drop((db, storage));
+
+    // Simulate a load that was interrupted after examining key0499. The
+    // remaining 250 entries are examined first, followed by the 250 entries
+    // before the cursor.
+    cursors().save("tests", Some(Bytes::from(b"\0key0499".to_vec())))?;
+    let storage = Storage::open(configuration())?;
+    let db = storage.database::<BasicSchema>("tests")?;
+    assert_eq!(
+        loaded(&db),
+        ExpirationLoadStatus {
+            keys_scanned: 500,
+            expired_keys_removed: 0,
+            expirations_loaded: 500,
+            batches: 6,
+            complete: true,
+        }
+    );
+    assert!(cursors().cursor("tests")?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn expired_keys_are_hidden_and_release_quota() -> anyhow::Result<()> {
+    use std::time::SystemTime;
+
+    use bonsaidb_core::admin::{DatabaseQuota, QuotaLimit};
+    use bonsaidb_core::connection::StorageConnection;
+    use bonsaidb_core::keyvalue::{KeyValue, Numeric, Timestamp, Value};
+    use nebari::tree::Unversioned;
+
+    use crate::database::keyvalue::{Entry, KEY_TREE};
+    use crate::test_util::{wait_for_tasks, TestClock};
+
+    let path = TestDirectory::new("expired-keys-are-hidden-and-release-quota");
+    let start = SystemTime::now();
+    let clock = TestClock::new(Timestamp::from(start));
+    let storage = Storage::open(
+        StorageConfiguration::new(&path)
+            .key_value_clock(clock.clone().into())
+            .with_schema::<BasicSchema>()?,
+    )?;
+    let db = storage.create_database::<BasicSchema>("tests", false)?;
+    wait_for_tasks(&storage);
+
+    // An expired entry that the expiration loader never examined, such as
+    // one it hasn't reached yet, isn't returned.
+    let entry = Entry {
+        value: Value::Numeric(Numeric::UnsignedInteger(1)),
+        expiration: Some(Timestamp::MIN),
+        last_updated: Timestamp::MIN,
+    };
+    let tree = db.roots().tree(Unversioned::tree(KEY_TREE))?;
+    tree.set(b"\0stale".to_vec(), bincode::serialize(&entry)?)?;
+    tree.set(b"\0stale-deleted".to_vec(), bincode::serialize(&entry)?)?;
+    assert!(db.get_key("stale").query()?.is_none());
+    assert!(db.get_key("stale-deleted").and_delete().query()?.is_none());
+
+    // Keys that expire no longer count against the database's quota.
+    storage.set_database_quota(
+        "tests",
+        DatabaseQuota::default().with_keys(QuotaLimit::hard(1)),
+    )?;
+    db.set_key("a", &1_u32)
+        .expire_at(start + Duration::from_secs(3))
+        .execute()?;
+    assert!(matches!(
+        db.set_key("b", &1_u32).execute(),
+        Err(bonsaidb_core::Error::QuotaExceeded { .. })
+    ));
+    clock.advance(Duration::from_secs(4));
+    assert!(db.get_key("a").query()?.is_none());
+    db.set_key("b", &1_u32).execute()?;
+    assert_eq!(storage.database_quota_usage("tests")?.keys, 1);
+
     Ok(())
 }
@@ -264,6 +264,10 @@ fn test_compatibility(dir: &str) {
     .unwrap();
 
     test_databases(&test_dir);
+
+    let upgrade = Storage::upgrade(&test_dir).unwrap();
+    assert_eq!(upgrade.previous_version, 0);
+    test_databases(&test_dir);
 }
 
 #[test]
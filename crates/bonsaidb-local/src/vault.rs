@@ -52,7 +52,7 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::{Debug, Display};
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
@@ -123,6 +123,7 @@ impl<'a> From<&'a KeyPair> for PublicKey {
     }
 }
 
+use crate::files::{atomic_write, FileDurability};
 use crate::storage::StorageId;
 
 pub(crate) struct Vault {
@@ -158,6 +159,13 @@ pub enum Error {
     /// doesn't contain the key.
     #[error("vault key not found")]
     VaultKeyNotFound,
+    /// The vault key storage contains a key for this storage, but the sealed
+    /// master keys are missing. Generating new master keys would replace the
+    /// vault key, making any previously encrypted data permanently unreadable.
+    /// If no data was ever encrypted using this storage, removing its key from
+    /// the vault key storage allows new keys to be generated.
+    #[error("vault key found, but the sealed master keys are missing")]
+    MasterKeysNotFound,
 }
 
 impl From<chacha20poly1305::aead::Error> for Error {
@@ -187,6 +195,12 @@ impl Vault {
         let master_keys_path = server_directory.join("master-keys");
         if master_keys_path.exists() {
             Self::unseal(&master_keys_path, server_id, master_key_storage)
+        } else if master_key_storage
+            .vault_key_for(server_id)
+            .map_err(|err| Error::VaultKeyStorage(err.to_string()))?
+            .is_some()
+        {
+            Err(Error::MasterKeysNotFound)
         } else {
             Self::initialize_vault_key_storage(&master_keys_path, server_id, master_key_storage)
         }
@@ -246,9 +260,12 @@ impl Vault {
                 tag,
             })?;
 
-            File::create(master_keys_path)
-                .and_then(move |mut file| file.write_all(&encrypted_master_keys_payload))
-                .map_err(|err| Error::Initializing(format!("error saving vault key: {err:?}")))?;
+            atomic_write(
+                master_keys_path,
+                &encrypted_master_keys_payload,
+                FileDurability::Durable,
+            )
+            .map_err(|err| Error::Initializing(format!("error saving vault key: {err:?}")))?;
 
             Ok(Self {
                 _vault_public_key: PublicKey::P256(public),
@@ -342,6 +359,11 @@ impl Vault {
         Ok(payload.to_vec())
     }
 
+    /// Returns true if `payload` was produced by [`Self::encrypt_payload()`].
+    pub(crate) fn is_encrypted_payload(payload: &[u8]) -> bool {
+        VaultPayload::from_slice(payload).is_ok()
+    }
+
     pub fn decrypt_payload(
         &self,
         payload: &[u8],
@@ -586,7 +608,7 @@ impl VaultKeyStorage for LocalVaultKeyStorage {
     fn set_vault_key_for(&self, server_id: StorageId, key: KeyPair) -> Result<(), Self::Error> {
         let server_file = self.directory.join(server_id.to_string());
         let bytes = bincode::serialize(&key)?;
-        File::create(server_file).and_then(|mut file| file.write_all(&bytes))?;
+        atomic_write(&server_file, &bytes, FileDurability::Durable)?;
         Ok(())
     }
 }
@@ -1,10 +1,17 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
 use bonsaidb_core::arc_bytes::serde::Bytes;
-use bonsaidb_core::document::Header;
+use bonsaidb_core::arc_bytes::ArcBytes;
+use bonsaidb_core::document::{DocumentId, Header};
+use bonsaidb_core::schema::view::{self, AuxiliaryData, AuxiliaryEntries, AuxiliarySource};
 use bonsaidb_core::schema::CollectionName;
+use nebari::tree::Versioned;
 use serde::{Deserialize, Serialize};
 
+use crate::database::{deserialize_document, document_tree_name};
+use crate::{Database, Error};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ViewEntry {
     pub view_version: u64,
@@ -22,6 +29,7 @@ pub struct EntryMapping {
 
 pub mod integrity_scanner;
 pub mod mapper;
+pub mod prefix_reductions;
 
 pub fn view_entries_tree_name(view_name: &impl Display) -> String {
     format!("view.{view_name:#}")
@@ -36,6 +44,79 @@ pub fn view_invalidated_docs_tree_name(view_name: &impl Display) -> String {
     format!("view.{view_name:#}.invalidated")
 }
 
+/// Used to store the reduced value of each cached key prefix, for views that
+/// implement `ViewSchema::reduce_prefixes()`.
+pub fn view_prefix_reductions_tree_name(view_name: &impl Display) -> String {
+    format!("view.{view_name:#}.prefix-reductions")
+}
+
+/// Used to store the ids of the documents that looked up each document of a
+/// view's dependencies while being mapped, so that changing a dependency only
+/// invalidates the documents that read it.
+pub fn view_dependents_tree_name(view_name: &impl Display) -> String {
+    format!("view.{view_name:#}.dependents")
+}
+
+/// Returns the key that the documents that looked up `id` from `collection`
+/// are stored under in a view's dependents tree.
+pub fn dependency_key(collection: &CollectionName, id: &DocumentId) -> ArcBytes<'static> {
+    let mut key = format!("{collection:#}").into_bytes();
+    key.push(0);
+    key.extend_from_slice(id.as_ref());
+    ArcBytes::from(key)
+}
+
 pub fn view_versions_tree_name(collection: &CollectionName) -> String {
     format!("view-versions.{collection:#}")
 }
+
+/// Captures a snapshot of `view`'s auxiliary inputs. Returns `None` if the view
+/// has no auxiliary inputs.
+pub fn auxiliary_data(
+    database: &Database,
+    view: &dyn view::Serialized,
+) -> Result<Option<AuxiliaryData>, Error> {
+    let inputs = view.auxiliary_inputs();
+    if inputs.is_empty() {
+        return Ok(None);
+    }
+
+    let mut key_value_entries = None;
+    let mut data = BTreeMap::new();
+    for input in inputs {
+        let entries = match &input.source {
+            AuxiliarySource::Collection(collection) => {
+                let _reads = database.begin_document_read();
+                let documents =
+                    database
+                        .roots()
+                        .tree(database.collection_tree::<Versioned, _>(
+                            collection,
+                            document_tree_name(collection),
+                        )?)?;
+                let mut entries = BTreeMap::new();
+                for (_, document) in documents.get_range(&(..))? {
+                    let document = deserialize_document(&document)?.into_owned();
+                    entries.insert(document.header.id.clone(), document);
+                }
+                AuxiliaryEntries::Documents(entries)
+            }
+            AuxiliarySource::KeyValue { namespace } => {
+                if key_value_entries.is_none() {
+                    key_value_entries = Some(database.all_key_value_entries()?);
+                }
+                AuxiliaryEntries::KeyValue(
+                    key_value_entries
+                        .iter()
+                        .flatten()
+                        .filter(|((entry_namespace, _), _)| entry_namespace == namespace)
+                        .map(|((_, key), entry)| (key.clone(), entry.value.clone()))
+                        .collect(),
+                )
+            }
+        };
+        data.insert(input.name.clone(), entries);
+    }
+
+    Ok(Some(AuxiliaryData::new(data)))
+}
@@ -13,11 +13,14 @@ use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
 use super::mapper::{Map, Mapper};
-use super::{view_invalidated_docs_tree_name, view_versions_tree_name};
+use super::{auxiliary_data, view_invalidated_docs_tree_name, view_versions_tree_name};
 use crate::database::{document_tree_name, Database};
 use crate::tasks::handle::Handle;
 use crate::tasks::{Job, Keyed, Task};
-use crate::views::{view_document_map_tree_name, view_entries_tree_name};
+use crate::views::{
+    view_dependents_tree_name, view_document_map_tree_name, view_entries_tree_name,
+    view_prefix_reductions_tree_name,
+};
 use crate::Error;
 
 #[derive(Debug)]
@@ -43,6 +46,8 @@ impl Job for IntegrityScanner {
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     #[allow(clippy::too_many_lines)]
     fn execute(&mut self) -> Result<Self::Output, Self::Error> {
+        let database = self.database.clone();
+        let _reads = database.begin_document_read();
         let documents =
             self.database
                 .roots()
@@ -59,6 +64,13 @@ impl Job for IntegrityScanner {
 
         let view_name = self.scan.view_name.clone();
         let view_version = self.scan.view_version;
+        // Views with auxiliary inputs must be mapped again whenever the inputs
+        // change, so the inputs' version is part of the view's version.
+        let auxiliary_version = auxiliary_data(
+            &self.database,
+            self.database.data.schema.view_by_name(&view_name)?,
+        )?
+        .map(|data| data.version());
         let roots = self.database.roots().clone();
         let version = view_versions
             .get(view_name.to_string().as_bytes())?
@@ -68,7 +80,7 @@ impl Job for IntegrityScanner {
         // Remove any old files that are no longer used.
         version.cleanup(&roots, &view_name)?;
 
-        let task = if version.is_current(view_version) {
+        let task = if version.is_current(view_version, auxiliary_version) {
             None
         } else {
             // The view isn't the current version, queue up all documents.
@@ -79,6 +91,8 @@ impl Job for IntegrityScanner {
             roots.delete_tree(view_invalidated_docs_tree_name(&self.scan.view_name))?;
             roots.delete_tree(view_entries_tree_name(&self.scan.view_name))?;
             roots.delete_tree(view_document_map_tree_name(&self.scan.view_name))?;
+            roots.delete_tree(view_prefix_reductions_tree_name(&self.scan.view_name))?;
+            roots.delete_tree(view_dependents_tree_name(&self.scan.view_name))?;
             // Add all missing entries to the invalidated list. The view
             // mapping job will update them on the next pass.
             let invalidated_entries_tree = self.database.collection_tree::<Unversioned, _>(
@@ -91,7 +105,7 @@ impl Job for IntegrityScanner {
                 let mut view_versions = transaction.tree::<Unversioned>(1).unwrap();
                 view_versions.set(
                     view_name.to_string().as_bytes().to_vec(),
-                    ViewVersion::current_for(view_version).to_vec()?,
+                    ViewVersion::current_for(view_version, auxiliary_version).to_vec()?,
                 )?;
                 let mut invalidated_entries = transaction.tree::<Unversioned>(0).unwrap();
                 let mut missing_entries = missing_entries
@@ -102,6 +116,13 @@ impl Job for IntegrityScanner {
                 invalidated_entries.modify(missing_entries, Operation::Set(ArcBytes::default()))?;
             }
             transaction.commit()?;
+            // Any previously recorded update no longer reflects the view's
+            // contents.
+            self.database.storage.instance.tasks().mark_view_outdated(
+                self.database.data.name.clone(),
+                self.scan.collection.clone(),
+                self.scan.view_name.clone(),
+            );
 
             Some(Arc::new(Mutex::new(Some(
                 self.database
@@ -138,6 +159,8 @@ impl Job for IntegrityScanner {
 pub struct ViewVersion {
     internal_version: u8,
     schema_version: u64,
+    #[serde(default)]
+    auxiliary_version: Option<u64>,
 }
 
 impl ViewVersion {
@@ -153,6 +176,7 @@ impl ViewVersion {
                 Ok(Self {
                     internal_version: 0,
                     schema_version,
+                    auxiliary_version: None,
                 })
             }
             Err(err) => Err(crate::Error::from(err)),
@@ -163,15 +187,26 @@ impl ViewVersion {
         pot::to_vec(self).map_err(crate::Error::from)
     }
 
-    pub fn current_for(schema_version: u64) -> Self {
+    pub fn current_for(schema_version: u64, auxiliary_version: Option<u64>) -> Self {
         Self {
             internal_version: Self::CURRENT_VERSION,
             schema_version,
+            auxiliary_version,
         }
     }
 
-    pub fn is_current(&self, schema_version: u64) -> bool {
-        self.internal_version == Self::CURRENT_VERSION && self.schema_version == schema_version
+    pub const fn schema_version(&self) -> u64 {
+        self.schema_version
+    }
+
+    pub const fn auxiliary_version(&self) -> Option<u64> {
+        self.auxiliary_version
+    }
+
+    pub fn is_current(&self, schema_version: u64, auxiliary_version: Option<u64>) -> bool {
+        self.internal_version == Self::CURRENT_VERSION
+            && self.schema_version == schema_version
+            && self.auxiliary_version == auxiliary_version
     }
 
     pub fn cleanup(&self, roots: &Roots<AnyFile>, view: &ViewName) -> Result<(), crate::Error> {
@@ -6,18 +6,23 @@ use std::sync::Arc;
 use bonsaidb_core::arc_bytes::serde::Bytes;
 use bonsaidb_core::arc_bytes::{ArcBytes, OwnedBytes};
 use bonsaidb_core::connection::Connection;
-use bonsaidb_core::schema::view::{self, map, Serialized, ViewUpdatePolicy};
+use bonsaidb_core::document::{DocumentId, OwnedDocument};
+use bonsaidb_core::schema::view::{
+    self, map, AuxiliaryData, DocumentLookup, Serialized, ViewUpdatePolicy,
+};
 use bonsaidb_core::schema::{CollectionName, ViewName};
 use easy_parallel::Parallel;
 use nebari::io::any::AnyFile;
 use nebari::tree::{AnyTreeRoot, CompareSwap, KeyOperation, Operation, Unversioned, Versioned};
 use nebari::{LockedTransactionTree, Tree, UnlockedTransactionTree};
+use parking_lot::Mutex;
 
 use crate::database::{deserialize_document, document_tree_name, Database};
 use crate::tasks::{Job, Keyed, Task};
 use crate::views::{
+    auxiliary_data, dependency_key, prefix_reductions, view_dependents_tree_name,
     view_document_map_tree_name, view_entries_tree_name, view_invalidated_docs_tree_name,
-    EntryMapping, ViewEntry,
+    view_prefix_reductions_tree_name, EntryMapping, ViewEntry,
 };
 use crate::Error;
 
@@ -41,6 +46,10 @@ impl Job for Mapper {
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     #[allow(clippy::too_many_lines)]
     fn execute(&mut self) -> Result<Self::Output, Error> {
+        // Purges wait for the view to be updated, ensuring documents are never
+        // read from a partially rewritten document tree.
+        let database = self.database.clone();
+        let _reads = database.begin_document_read();
         let documents =
             self.database
                 .roots()
@@ -73,6 +82,34 @@ impl Job for Mapper {
                     view_invalidated_docs_tree_name(&self.map.view_name),
                 )?)?;
 
+        let view = self
+            .database
+            .data
+            .schema
+            .view_by_name(&self.map.view_name)?;
+        let prefix_reductions =
+            if view.caches_reduce_prefixes() {
+                Some(self.database.roots().tree(
+                    self.database.collection_tree::<Unversioned, _>(
+                        &self.map.collection,
+                        view_prefix_reductions_tree_name(&self.map.view_name),
+                    )?,
+                )?)
+            } else {
+                None
+            };
+        let dependents =
+            if view.dependencies().is_empty() {
+                None
+            } else {
+                Some(self.database.roots().tree(
+                    self.database.collection_tree::<Unversioned, _>(
+                        &self.map.collection,
+                        view_dependents_tree_name(&self.map.view_name),
+                    )?,
+                )?)
+            };
+
         let transaction_id = self
             .database
             .last_transaction_id()?
@@ -86,6 +123,8 @@ impl Job for Mapper {
             &document_map,
             &documents,
             &view_entries,
+            prefix_reductions.as_ref(),
+            dependents.as_ref(),
             &storage,
             &map_request,
         )?;
@@ -106,6 +145,8 @@ fn map_view(
     document_map: &Tree<Unversioned, AnyFile>,
     documents: &Tree<Versioned, AnyFile>,
     view_entries: &Tree<Unversioned, AnyFile>,
+    prefix_reductions: Option<&Tree<Unversioned, AnyFile>>,
+    dependents: Option<&Tree<Unversioned, AnyFile>>,
     database: &Database,
     map_request: &Map,
 ) -> Result<(), Error> {
@@ -117,14 +158,28 @@ fn map_view(
         .map(|(key, _)| key)
         .collect::<Vec<_>>();
     while !invalidated_ids.is_empty() {
+        database
+            .storage
+            .instance
+            .tasks()
+            .check_cancelled(&database.data.name)?;
+        let mut trees = vec![
+            Box::new(invalidated_entries.clone()) as Box<dyn AnyTreeRoot<AnyFile>>,
+            Box::new(document_map.clone()),
+            Box::new(documents.clone()),
+            Box::new(view_entries.clone()),
+        ];
+        let prefix_reductions_index = prefix_reductions.map(|prefix_reductions| {
+            trees.push(Box::new(prefix_reductions.clone()));
+            trees.len() - 1
+        });
+        let dependents_index = dependents.map(|dependents| {
+            trees.push(Box::new(dependents.clone()));
+            trees.len() - 1
+        });
         let transaction = database
             .roots()
-            .transaction::<_, dyn AnyTreeRoot<AnyFile>>(&[
-                Box::new(invalidated_entries.clone()) as Box<dyn AnyTreeRoot<AnyFile>>,
-                Box::new(document_map.clone()),
-                Box::new(documents.clone()),
-                Box::new(view_entries.clone()),
-            ])?;
+            .transaction::<_, dyn AnyTreeRoot<AnyFile>>(&trees)?;
         {
             let view = database
                 .data
@@ -138,6 +193,9 @@ fn map_view(
             let document_map = transaction.unlocked_tree(1).unwrap();
             let documents = transaction.unlocked_tree(2).unwrap();
             let view_entries = transaction.unlocked_tree(3).unwrap();
+            let prefix_reductions =
+                prefix_reductions_index.and_then(|index| transaction.unlocked_tree(index));
+            let dependents = dependents_index.and_then(|index| transaction.unlocked_tree(index));
             DocumentRequest {
                 document_ids: document_ids.clone(),
                 map_request,
@@ -145,6 +203,8 @@ fn map_view(
                 document_map,
                 documents,
                 view_entries,
+                prefix_reductions,
+                dependents,
                 view,
             }
             .map()?;
@@ -166,6 +226,8 @@ pub struct DocumentRequest<'a> {
     pub document_map: &'a UnlockedTransactionTree<AnyFile>,
     pub documents: &'a UnlockedTransactionTree<AnyFile>,
     pub view_entries: &'a UnlockedTransactionTree<AnyFile>,
+    pub prefix_reductions: Option<&'a UnlockedTransactionTree<AnyFile>>,
+    pub dependents: Option<&'a UnlockedTransactionTree<AnyFile>>,
     pub view: &'a dyn Serialized,
 }
 
@@ -182,9 +244,14 @@ impl<'a> DocumentRequest<'a> {
         let mut documents = documents.lock::<Versioned>();
         for chunk in document_ids.chunks(1024) {
             let (document_id_sender, document_id_receiver) = flume::bounded(chunk.len());
-            batch_sender
+            // If a later stage stops receiving, it has failed and will report
+            // its error.
+            if batch_sender
                 .send((chunk.to_vec(), document_id_receiver))
-                .unwrap();
+                .is_err()
+            {
+                break;
+            }
             let mut documents = documents.get_multiple(chunk.iter().map(ArcBytes::as_slice))?;
             documents.sort_by(|a, b| a.0.cmp(&b.0));
 
@@ -194,9 +261,12 @@ impl<'a> DocumentRequest<'a> {
                     .map_or(false, |(key, _)| (key == document_id))
                     .then(|| documents.pop().unwrap().1);
 
-                document_id_sender
+                if document_id_sender
                     .send((document_id.clone(), document))
-                    .unwrap();
+                    .is_err()
+                {
+                    break;
+                }
             }
 
             drop(document_id_sender);
@@ -209,6 +279,7 @@ impl<'a> DocumentRequest<'a> {
         batch_receiver: &flume::Receiver<BatchPayload>,
         mapped_sender: flume::Sender<Batch>,
         view: &dyn Serialized,
+        lookup: &dyn DocumentLookup,
         parallelization: usize,
     ) -> Result<(), Error> {
         // Process batches
@@ -221,32 +292,45 @@ impl<'a> DocumentRequest<'a> {
                 .each(1..=parallelization, |_| -> Result<_, Error> {
                     let mut results = Vec::new();
                     while let Ok((document_id, document)) = document_id_receiver.recv() {
+                        let lookup = RecordingLookup {
+                            lookup,
+                            looked_up: Mutex::default(),
+                        };
                         let map_result = if let Some(document) = document {
                             let document = deserialize_document(&document)?;
 
                             // Call the schema map function
-                            view.map(&document).map_err(bonsaidb_core::Error::from)?
+                            view.map(&document, &lookup)
+                                .map_err(bonsaidb_core::Error::from)?
                         } else {
                             // Get multiple didn't return this document ID.
                             Vec::new()
                         };
+                        let looked_up = lookup.looked_up.into_inner();
                         let keys: HashSet<OwnedBytes> = map_result
                             .iter()
                             .map(|map| OwnedBytes::from(map.key.as_slice()))
                             .collect();
                         let new_keys = ArcBytes::from(bincode::serialize(&keys)?);
 
-                        results.push((document_id, new_keys, keys, map_result));
+                        results.push((document_id, new_keys, keys, map_result, looked_up));
                     }
 
                     Ok(results)
                 })
                 .run()
             {
-                for (document_id, new_keys, keys, map_result) in result? {
+                for (document_id, new_keys, keys, map_result, looked_up) in result? {
                     for key in &keys {
                         batch.all_keys.insert(key.0.clone());
                     }
+                    for dependency in looked_up {
+                        batch
+                            .dependents
+                            .entry(dependency)
+                            .or_default()
+                            .insert(OwnedBytes::from(document_id.as_slice()));
+                    }
                     batch.document_maps.insert(document_id.clone(), new_keys);
                     batch.document_keys.insert(document_id.clone(), keys);
                     for mapping in map_result {
@@ -258,7 +342,9 @@ impl<'a> DocumentRequest<'a> {
                     }
                 }
             }
-            mapped_sender.send(batch).unwrap();
+            if mapped_sender.send(batch).is_err() {
+                break;
+            }
         }
         drop(mapped_sender);
         Ok(())
@@ -331,6 +417,8 @@ impl<'a> DocumentRequest<'a> {
         map_request: &Map,
         document_map: &mut LockedTransactionTree<'_, Unversioned, AnyFile>,
         view_entries: &mut LockedTransactionTree<'_, Unversioned, AnyFile>,
+        mut prefix_reductions: Option<LockedTransactionTree<'_, Unversioned, AnyFile>>,
+        mut dependents: Option<LockedTransactionTree<'_, Unversioned, AnyFile>>,
     ) -> Result<(), Error> {
         while let Ok(Batch {
             document_ids,
@@ -338,8 +426,13 @@ impl<'a> DocumentRequest<'a> {
             document_keys,
             new_mappings,
             mut all_keys,
+            dependents: new_dependents,
         }) = mapped_receiver.recv()
         {
+            if let Some(dependents) = &mut dependents {
+                Self::update_dependents(dependents, new_dependents)?;
+            }
+
             let view_entries_to_clean = Self::update_document_map(
                 document_ids,
                 document_map,
@@ -348,6 +441,7 @@ impl<'a> DocumentRequest<'a> {
                 &mut all_keys,
             )?;
 
+            let changed_keys = prefix_reductions.is_some().then(|| all_keys.clone());
             Self::update_view_entries(
                 view,
                 map_request,
@@ -356,11 +450,54 @@ impl<'a> DocumentRequest<'a> {
                 view_entries_to_clean,
                 new_mappings,
             )?;
+
+            if let (Some(prefix_reductions), Some(changed_keys)) =
+                (&mut prefix_reductions, changed_keys)
+            {
+                prefix_reductions::update(view, view_entries, prefix_reductions, &changed_keys)?;
+            }
         }
         Ok(())
     }
 
+    /// Records the documents that looked up each dependency, so that
+    /// changing a dependency invalidates them.
+    fn update_dependents(
+        dependents: &mut LockedTransactionTree<'_, Unversioned, AnyFile>,
+        mut new_dependents: BTreeMap<ArcBytes<'static>, HashSet<OwnedBytes>>,
+    ) -> Result<(), Error> {
+        if new_dependents.is_empty() {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        dependents.modify(
+            new_dependents.keys().cloned().collect(),
+            Operation::CompareSwap(CompareSwap::new(&mut |key, existing| {
+                let mut document_ids = existing
+                    .and_then(|existing| {
+                        bincode::deserialize::<HashSet<OwnedBytes>>(&existing).ok()
+                    })
+                    .unwrap_or_default();
+                document_ids.extend(new_dependents.remove(key).unwrap_or_default());
+                match bincode::serialize(&document_ids) {
+                    Ok(document_ids) => KeyOperation::Set(ArcBytes::from(document_ids)),
+                    Err(err) => {
+                        result = Err(Error::from(err));
+                        KeyOperation::Skip
+                    }
+                }
+            })),
+        )?;
+        result
+    }
+
     pub fn map(&mut self) -> Result<(), Error> {
+        // The auxiliary data is captured once so that every document in this
+        // batch is mapped using the same data.
+        let lookup = DependencyLookup {
+            database: self.database,
+            auxiliary: auxiliary_data(self.database, self.view)?,
+        };
         let (batch_sender, batch_receiver) = flume::bounded(1);
         let (mapped_sender, mapped_receiver) = flume::bounded(1);
 
@@ -371,18 +508,23 @@ impl<'a> DocumentRequest<'a> {
                     &batch_receiver,
                     mapped_sender,
                     self.view,
+                    &lookup,
                     self.database.storage().parallelization(),
                 )
             })
             .add(|| {
                 let mut document_map = self.document_map.lock();
                 let mut view_entries = self.view_entries.lock();
+                let prefix_reductions = self.prefix_reductions.map(|tree| tree.lock());
+                let dependents = self.dependents.map(|tree| tree.lock());
                 Self::save_mappings(
                     &mapped_receiver,
                     self.view,
                     self.map_request,
                     &mut document_map,
                     &mut view_entries,
+                    prefix_reductions,
+                    dependents,
                 )
             })
             .run()
@@ -394,6 +536,61 @@ impl<'a> DocumentRequest<'a> {
     }
 }
 
+/// Reads the documents that views look up from their dependencies. Lookups
+/// read the most recently committed version of each document.
+struct DependencyLookup<'a> {
+    database: &'a Database,
+    auxiliary: Option<AuxiliaryData>,
+}
+
+impl<'a> DocumentLookup for DependencyLookup<'a> {
+    fn get(
+        &self,
+        collection: &CollectionName,
+        id: &DocumentId,
+    ) -> Result<Option<OwnedDocument>, bonsaidb_core::Error> {
+        let documents = self
+            .database
+            .roots()
+            .tree(
+                self.database
+                    .collection_tree::<Versioned, _>(collection, document_tree_name(collection))?,
+            )
+            .map_err(Error::from)?;
+        match documents.get(id.as_ref()).map_err(Error::from)? {
+            Some(document) => Ok(Some(deserialize_document(&document)?.into_owned())),
+            None => Ok(None),
+        }
+    }
+
+    fn auxiliary_data(&self) -> Option<&AuxiliaryData> {
+        self.auxiliary.as_ref()
+    }
+}
+
+/// Records the dependencies a single document looks up while it is mapped.
+struct RecordingLookup<'a> {
+    lookup: &'a dyn DocumentLookup,
+    looked_up: Mutex<Vec<ArcBytes<'static>>>,
+}
+
+impl<'a> DocumentLookup for RecordingLookup<'a> {
+    fn get(
+        &self,
+        collection: &CollectionName,
+        id: &DocumentId,
+    ) -> Result<Option<OwnedDocument>, bonsaidb_core::Error> {
+        // Missing documents are recorded too, so that creating them
+        // invalidates this document.
+        self.looked_up.lock().push(dependency_key(collection, id));
+        self.lookup.get(collection, id)
+    }
+
+    fn auxiliary_data(&self) -> Option<&AuxiliaryData> {
+        self.lookup.auxiliary_data()
+    }
+}
+
 #[derive(Default)]
 struct Batch {
     document_ids: Vec<ArcBytes<'static>>,
@@ -401,6 +598,7 @@ struct Batch {
     document_keys: BTreeMap<ArcBytes<'static>, HashSet<OwnedBytes>>,
     new_mappings: BTreeMap<ArcBytes<'static>, Vec<map::Serialized>>,
     all_keys: BTreeSet<ArcBytes<'static>>,
+    dependents: BTreeMap<ArcBytes<'static>, HashSet<OwnedBytes>>,
 }
 
 impl Keyed<Task> for Mapper {
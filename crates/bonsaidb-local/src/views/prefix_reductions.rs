@@ -0,0 +1,292 @@
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::ops::{Bound, RangeBounds};
+
+use bonsaidb_core::arc_bytes::serde::Bytes;
+use bonsaidb_core::arc_bytes::ArcBytes;
+use bonsaidb_core::connection::Range;
+use bonsaidb_core::schema::view::map::MappedSerializedValue;
+use bonsaidb_core::schema::view::{self, Serialized};
+use nebari::io::any::AnyFile;
+use nebari::tree::{ScanEvaluation, Unversioned};
+use nebari::{LockedTransactionTree, Tree};
+
+use crate::views::ViewEntry;
+use crate::Error;
+
+/// Recomputes the cached reduction of each prefix of `changed_keys`. Prefixes
+/// without any entries are removed from the cache.
+///
+/// Longer prefixes are updated first, which allows each prefix to be reduced
+/// from the cached reductions of the longer prefixes it contains. Only the
+/// entries not contained by a longer cached prefix are read.
+pub fn update(
+    view: &dyn Serialized,
+    view_entries: &mut LockedTransactionTree<'_, Unversioned, AnyFile>,
+    prefix_reductions: &mut LockedTransactionTree<'_, Unversioned, AnyFile>,
+    changed_keys: &BTreeSet<ArcBytes<'static>>,
+) -> Result<(), Error> {
+    let mut prefixes = BTreeSet::new();
+    for key in changed_keys {
+        prefixes.extend(key_prefixes(view, key)?);
+    }
+    let mut prefixes = prefixes.into_iter().collect::<Vec<_>>();
+    prefixes.sort_by_key(|prefix| Reverse(prefix.len()));
+
+    for prefix in prefixes {
+        let mut mappings = prefix_contents(view, view_entries, prefix_reductions, &prefix)?;
+        let reduced = match mappings.len() {
+            0 => {
+                prefix_reductions.remove(&prefix)?;
+                continue;
+            }
+            1 => mappings.pop().unwrap().1,
+            _ => {
+                let mappings = mappings
+                    .iter()
+                    .map(|(key, value)| (key.as_slice(), value.as_slice()))
+                    .collect::<Vec<_>>();
+                match view.reduce(&mappings, true) {
+                    Ok(reduced) => reduced,
+                    // There is nothing to cache for views without a reduce
+                    // function.
+                    Err(view::Error::Core(bonsaidb_core::Error::ReduceUnimplemented)) => {
+                        return Ok(())
+                    }
+                    Err(other) => return Err(Error::from(other)),
+                }
+            }
+        };
+        prefix_reductions.set(prefix, reduced)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the key and reduced value of each mapping needed to reduce
+/// `prefix`: the cached reduction of each longer prefix it contains, paired
+/// with the first key sharing that prefix, and the reduced value of each
+/// remaining key.
+fn prefix_contents(
+    view: &dyn Serialized,
+    view_entries: &mut LockedTransactionTree<'_, Unversioned, AnyFile>,
+    prefix_reductions: &mut LockedTransactionTree<'_, Unversioned, AnyFile>,
+    prefix: &[u8],
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Error> {
+    let end = prefix_end(prefix);
+    let end = end.as_deref().map_or(Bound::Unbounded, Bound::Excluded);
+    let mut mappings = Vec::new();
+    let mut cursor = Bound::Included(prefix.to_vec());
+    loop {
+        let mut cached = None;
+        let mut error = None;
+        view_entries.scan::<Error, _, _, _, _>(
+            &(borrow_bound(cursor.as_ref()), end),
+            true,
+            |_, _, _| ScanEvaluation::ReadData,
+            |key, _| match cached_child(view, prefix_reductions, prefix, key) {
+                Ok(None) => ScanEvaluation::ReadData,
+                Ok(Some(child)) => {
+                    cached = Some((key.to_vec(), child));
+                    ScanEvaluation::Stop
+                }
+                Err(err) => {
+                    error = Some(err);
+                    ScanEvaluation::Stop
+                }
+            },
+            |key, _, entry| {
+                let entry = bincode::deserialize::<ViewEntry>(&entry)
+                    .map_err(|err| nebari::AbortError::Other(Error::from(err)))?;
+                mappings.push((key.to_vec(), entry.reduced_value.into_vec()));
+                Ok(())
+            },
+        )?;
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        let Some((key, (child_end, reduced))) = cached else {
+            break;
+        };
+        mappings.push((key, reduced.to_vec()));
+        match child_end {
+            // Continue after the last key that could share the child prefix.
+            Some(child_end) => cursor = Bound::Included(child_end),
+            None => break,
+        }
+    }
+
+    Ok(mappings)
+}
+
+/// Returns the end and cached reduction of the shortest prefix of `key` that
+/// is longer than `parent` and has a cached reduction.
+fn cached_child(
+    view: &dyn Serialized,
+    prefix_reductions: &mut LockedTransactionTree<'_, Unversioned, AnyFile>,
+    parent: &[u8],
+    key: &[u8],
+) -> Result<Option<(Option<Vec<u8>>, ArcBytes<'static>)>, Error> {
+    for prefix in key_prefixes(view, key)? {
+        if prefix.len() > parent.len() {
+            if let Some(reduced) = prefix_reductions.get(&prefix)? {
+                return Ok(Some((prefix_end(&prefix), reduced)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the reduced values needed to reduce `range`: the cached reduction
+/// of each prefix entirely contained in the range, and the reduced value of
+/// each remaining key. Each cached prefix is paired with the first key sharing
+/// the prefix.
+pub fn reduce_range(
+    view: &dyn Serialized,
+    view_entries: &Tree<Unversioned, AnyFile>,
+    prefix_reductions: &Tree<Unversioned, AnyFile>,
+    range: &Range<Bytes>,
+) -> Result<Vec<MappedSerializedValue>, Error> {
+    let range = range.map_ref(|bytes| &bytes[..]);
+    let start = borrow_bound(range.start_bound());
+    let end = borrow_bound(range.end_bound());
+    let mut mappings = Vec::new();
+    let mut cursor = match start {
+        Bound::Unbounded => Bound::Unbounded,
+        Bound::Included(start) => Bound::Included(start.to_vec()),
+        Bound::Excluded(start) => Bound::Excluded(start.to_vec()),
+    };
+    loop {
+        let mut cached = None;
+        let mut error = None;
+        view_entries.scan::<Error, _, _, _, _>(
+            &(borrow_bound(cursor.as_ref()), end),
+            true,
+            |_, _, _| ScanEvaluation::ReadData,
+            |key, _| match cached_prefix(view, prefix_reductions, key, start, end) {
+                Ok(None) => ScanEvaluation::ReadData,
+                Ok(Some(prefix)) => {
+                    cached = Some((key.to_vec(), prefix));
+                    ScanEvaluation::Stop
+                }
+                Err(err) => {
+                    error = Some(err);
+                    ScanEvaluation::Stop
+                }
+            },
+            |key, _, entry| {
+                let entry = bincode::deserialize::<ViewEntry>(&entry)
+                    .map_err(|err| nebari::AbortError::Other(Error::from(err)))?;
+                mappings.push(MappedSerializedValue {
+                    key: Bytes::from(key.to_vec()),
+                    value: entry.reduced_value,
+                });
+                Ok(())
+            },
+        )?;
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        let Some((key, (prefix_end, reduced))) = cached else {
+            break;
+        };
+        mappings.push(MappedSerializedValue {
+            key: Bytes::from(key),
+            value: Bytes::from(reduced.to_vec()),
+        });
+        match prefix_end {
+            // Continue after the last key that could share the prefix.
+            Some(prefix_end) => cursor = Bound::Included(prefix_end),
+            None => break,
+        }
+    }
+
+    Ok(mappings)
+}
+
+/// Returns the end and cached reduction of the shortest prefix of `key` that
+/// is contained within the range and has a cached reduction.
+fn cached_prefix(
+    view: &dyn Serialized,
+    prefix_reductions: &Tree<Unversioned, AnyFile>,
+    key: &[u8],
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+) -> Result<Option<(Option<Vec<u8>>, ArcBytes<'static>)>, Error> {
+    for prefix in key_prefixes(view, key)? {
+        let prefix_end = prefix_end(&prefix);
+        if range_contains_prefix(start, end, &prefix, prefix_end.as_deref()) {
+            if let Some(reduced) = prefix_reductions.get(&prefix)? {
+                return Ok(Some((prefix_end, reduced)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns true if every key beginning with `prefix` is within the range.
+fn range_contains_prefix(
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+    prefix: &[u8],
+    prefix_end: Option<&[u8]>,
+) -> bool {
+    // Every key sharing the prefix is longer than the prefix, making the
+    // prefix followed by a 0 the smallest key that can share it.
+    let starts_before = match start {
+        Bound::Unbounded => true,
+        Bound::Included(start) => start <= [prefix, &[0]].concat().as_slice(),
+        Bound::Excluded(start) => start <= prefix,
+    };
+    let ends_after = match (end, prefix_end) {
+        (Bound::Unbounded, _) => true,
+        (Bound::Included(end) | Bound::Excluded(end), Some(prefix_end)) => prefix_end <= end,
+        (_, None) => false,
+    };
+    starts_before && ends_after
+}
+
+/// Returns the prefixes of `key`, verifying that each is a prefix of the key.
+fn key_prefixes(view: &dyn Serialized, key: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let prefixes = view.reduce_prefixes(key)?;
+    if prefixes
+        .iter()
+        .any(|prefix| prefix.len() >= key.len() || !key.starts_with(prefix))
+    {
+        return Err(Error::Core(bonsaidb_core::Error::other(
+            "view",
+            format!(
+                "{} returned a reduce prefix that isn't a prefix of its key",
+                view.view_name()
+            ),
+        )));
+    }
+
+    Ok(prefixes)
+}
+
+/// Returns the smallest byte sequence that is greater than every sequence
+/// beginning with `prefix`, or `None` if no such sequence exists.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last_byte) = end.pop() {
+        if let Some(next) = last_byte.checked_add(1) {
+            end.push(next);
+            return Some(end);
+        }
+    }
+
+    None
+}
+
+fn borrow_bound<B: AsRef<[u8]>>(bound: Bound<&B>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Unbounded => Bound::Unbounded,
+        Bound::Included(value) => Bound::Included(value.as_ref()),
+        Bound::Excluded(value) => Bound::Excluded(value.as_ref()),
+    }
+}
@@ -70,10 +70,14 @@ struct CollectionAttribute {
     views: Vec<Type>,
     #[attribute(example = "Format or None")]
     serialization: Option<Path>,
+    #[attribute(example = "\"json\"")]
+    format: Option<String>,
     #[attribute(example = "Some(KeyId::Master)")]
     encryption_key: Option<Expr>,
     encryption_required: bool,
     encryption_optional: bool,
+    #[attribute(example = "1_048_576")]
+    max_document_size: Option<Expr>,
     #[attribute(example = "u64")]
     primary_key: Option<Type>,
     #[attribute(example = "self.0 or something(self)")]
@@ -100,12 +104,14 @@ pub fn collection_derive(input: proc_macro::TokenStream) -> Result {
         name,
         views,
         serialization,
+        format,
         mut primary_key,
         mut natural_id,
         core,
         encryption_key,
         encryption_required,
         encryption_optional,
+        max_document_size,
     } = CollectionAttribute::from_attributes(&attrs)?;
 
     if let Data::Struct(DataStruct { fields, .. }) = data {
@@ -158,6 +164,25 @@ pub fn collection_derive(input: proc_macro::TokenStream) -> Result {
 
     let primary_key = primary_key.unwrap_or_else(|| parse_quote!(u64));
 
+    let format = if let Some(format) = format {
+        if serialization.is_some() {
+            bail!("`format` and `serialization` can't both be specified");
+        }
+        let format = match format.as_str() {
+            "pot" => quote!(Pot),
+            "json" => quote!(Json),
+            "bincode" => quote!(Bincode),
+            "cbor" => quote!(Cbor),
+            _ => bail!(
+                "unknown format `{format}`";
+                help = "supported formats are `pot`, `json`, `bincode` and `cbor`"
+            ),
+        };
+        Some(format)
+    } else {
+        None
+    };
+
     let serialization = if matches!(&serialization, Some(serialization) if serialization.is_ident("None"))
     {
         if let Some(natural_id) = natural_id {
@@ -178,7 +203,20 @@ pub fn collection_derive(input: proc_macro::TokenStream) -> Result {
             )
         });
 
-        if let Some(serialization) = serialization {
+        if let Some(format) = &format {
+            quote! {
+                impl #impl_generics #core::schema::SerializedCollection for #ident #ty_generics #where_clause {
+                    type Contents = #ident #ty_generics;
+                    type Format = #core::schema::DocumentFormat;
+
+                    fn format() -> Self::Format {
+                        #core::schema::DocumentFormat::new(#core::schema::SerializationFormat::#format)
+                    }
+
+                    #natural_id
+                }
+            }
+        } else if let Some(serialization) = serialization {
             let serialization = if serialization.is_ident("Key") {
                 quote!(#core::key::KeyFormat)
             } else {
@@ -229,6 +267,22 @@ pub fn collection_derive(input: proc_macro::TokenStream) -> Result {
         }
     });
 
+    let max_document_size = max_document_size.map(|max_document_size| {
+        quote! {
+            fn max_document_size() -> Option<u64> {
+                Some(#max_document_size)
+            }
+        }
+    });
+
+    let document_format = format.map(|format| {
+        quote! {
+            fn document_format() -> Option<#core::schema::DocumentFormat> {
+                Some(#core::schema::DocumentFormat::new(#core::schema::SerializationFormat::#format))
+            }
+        }
+    });
+
     Ok(quote! {
         impl #impl_generics #core::schema::Collection for #ident #ty_generics #where_clause {
             type PrimaryKey = #primary_key;
@@ -241,6 +295,8 @@ pub fn collection_derive(input: proc_macro::TokenStream) -> Result {
                 Ok(())
             }
             #encryption
+            #max_document_size
+            #document_format
         }
         #serialization
     })
@@ -256,7 +312,7 @@ pub fn view_derive(input: proc_macro::TokenStream) -> Result {
 }
 /// Derives the `bonsaidb::core::schema::ViewSchema` trait.
 #[manyhow]
-/// `#[view_schema(version = 1, policy = Unique, view=ViewType, mapped_key=KeyType<'doc>)]`
+/// `#[view_schema(version = 1, policy = Unique, view=ViewType, mapped_key=KeyType<'doc>, dependencies = [OtherCollection])]`
 ///
 /// All attributes are optional.
 #[proc_macro_derive(ViewSchema, attributes(view_schema))]
@@ -320,7 +376,7 @@ pub fn schema_derive(input: proc_macro::TokenStream) -> Result {
             ) -> Result<(), #core::Error> {
                 #( schema.define_collection::<#collections>()?; )*
 
-                #( <#include as #core::schema::Schema>::define_collections(schema)?; )*
+                #( schema.extend_with::<#include>()?; )*
 
                 Ok(())
             }
@@ -910,6 +966,63 @@ pub fn api_derive(input: proc_macro::TokenStream) -> Result {
     })
 }
 
+#[derive(Attribute)]
+#[attribute(ident = topic)]
+struct TopicAttribute {
+    #[attribute(example = "\"name\"")]
+    name: String,
+    #[attribute(example = "\"authority\"")]
+    authority: Option<Expr>,
+    #[attribute(example = "PayloadType")]
+    payload: Option<Type>,
+    #[attribute(example = "bosaidb::core")]
+    core: Option<Path>,
+}
+
+/// Derives the `bonsaidb::core::pubsub::Topic` trait.
+///
+/// `#[topic(name = "Name", authority = "Authority", payload = PayloadType, core = bonsaidb::core)]`
+/// `authority`, `payload` and `core` are optional
+#[manyhow]
+#[proc_macro_derive(Topic, attributes(topic))]
+pub fn topic_derive(input: proc_macro::TokenStream) -> Result {
+    let DeriveInput {
+        attrs,
+        ident,
+        generics,
+        ..
+    } = parse(input)?;
+
+    let TopicAttribute {
+        name,
+        authority,
+        payload,
+        core,
+    } = TopicAttribute::from_attributes(&attrs)?;
+
+    let core = core.unwrap_or_else(core_path);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let name = authority.map_or_else(
+        || quote!(#core::schema::Qualified::private(#name)),
+        |authority| quote!(#core::schema::Qualified::new(#authority, #name)),
+    );
+
+    let payload = payload.unwrap_or_else(|| parse_quote!(Self));
+
+    Ok(quote! {
+        # use #core::pubsub::{Topic, TopicName};
+
+        impl #impl_generics Topic for #ident #ty_generics #where_clause {
+            type Payload = #payload;
+
+            fn name() -> TopicName {
+                #name
+            }
+        }
+    })
+}
+
 // -----------------------------------------------------------------------------
 //     - File Macros -
 // -----------------------------------------------------------------------------
@@ -98,6 +98,8 @@ struct ViewSchemaAttribute {
     version: Option<u64>,
     #[attribute(example = "Lazy")]
     policy: Option<Ident>,
+    #[attribute(optional, example = "[SomeCollection, AnotherCollection]")]
+    dependencies: Vec<Type>,
     #[attribute(example = "bosaidb::core")]
     core: Option<Path>,
 }
@@ -115,6 +117,7 @@ pub fn derive_schema(
         mapped_key,
         version,
         policy,
+        dependencies,
         core,
     } = ViewSchemaAttribute::from_attributes(&attrs)?;
 
@@ -139,6 +142,12 @@ pub fn derive_schema(
         })
     });
 
+    let dependencies = (!dependencies.is_empty()).then(|| {
+        quote!(fn dependencies(&self) -> Vec<#core::schema::CollectionName> {
+            vec![#(<#dependencies as #core::schema::Collection>::collection_name()),*]
+        })
+    });
+
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     Ok(quote! {
@@ -148,6 +157,7 @@ pub fn derive_schema(
 
             #version
             #policy
+            #dependencies
         }
     })
 }
@@ -94,6 +94,18 @@ fn serialization() {
     let _: transmog_bincode::Bincode = Test::format();
 }
 
+#[test]
+fn format() {
+    use bonsaidb::core::schema::{DocumentFormat, SerializationFormat};
+
+    #[derive(Collection, Clone, Debug, Deserialize, Serialize)]
+    #[collection(name = "Name", authority = "Authority", format = "pot")]
+    struct Test;
+
+    let format: DocumentFormat = Test::format();
+    assert_eq!(format.format(), SerializationFormat::Pot);
+}
+
 #[test]
 fn serialization_none() {
     #[derive(Collection, Debug, Deserialize, Serialize)]
@@ -141,6 +153,15 @@ fn encryption_key() {
     struct Test;
 }
 
+#[test]
+fn max_document_size() {
+    #[derive(Collection, Debug, Deserialize, Serialize)]
+    #[collection(name = "Name", max_document_size = 1024)]
+    struct Test;
+
+    assert_eq!(Test::max_document_size(), Some(1024));
+}
+
 #[test]
 fn primary_key() {
     #[derive(Collection, Debug, Deserialize, Serialize)]
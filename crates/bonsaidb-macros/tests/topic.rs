@@ -0,0 +1,41 @@
+use bonsaidb::core::pubsub::{Topic, TopicName};
+use bonsaidb::core::schema::Qualified;
+use serde::{Deserialize, Serialize};
+
+#[test]
+fn core() {
+    #[derive(Topic, Debug, Serialize, Deserialize)]
+    #[topic(name = "name", core = ::bonsaidb::core)]
+    struct Test;
+
+    assert_eq!(Test::name(), TopicName::private("name"));
+}
+
+#[test]
+fn name_and_authority() {
+    #[derive(Topic, Debug, Serialize, Deserialize)]
+    #[topic(name = "name", authority = "authority")]
+    struct Test;
+
+    assert_eq!(Test::name(), TopicName::new("authority", "name"));
+}
+
+#[test]
+fn payload() {
+    #[derive(Topic, Debug)]
+    #[topic(name = "name", payload = String)]
+    struct Test;
+
+    assert_eq!(<Test as Topic>::Payload::new(), String::new());
+}
+
+#[test]
+fn payload_defaults_to_self() {
+    #[derive(Topic, Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[topic(name = "name")]
+    struct Test {
+        value: u32,
+    }
+
+    assert_eq!(<Test as Topic>::Payload::default(), Test { value: 0 });
+}
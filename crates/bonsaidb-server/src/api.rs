@@ -7,7 +7,7 @@ use bonsaidb_core::arc_bytes::serde::Bytes;
 use bonsaidb_core::permissions::PermissionDenied;
 use bonsaidb_core::schema::{InsertError, InvalidNameError};
 
-use crate::{Backend, ConnectedClient, CustomServer, Error, NoBackend};
+use crate::{Backend, ConnectedClient, CustomServer, Error, NoBackend, ServerDatabase};
 
 /// A trait that can dispatch requests for a [`Api`].
 #[async_trait]
@@ -30,6 +30,10 @@ pub struct HandlerSession<'a, B: Backend = NoBackend> {
     pub as_client: CustomServer<B>,
     /// The connected client making the API request.
     pub client: &'a ConnectedClient<B>,
+    /// The database the request was sent to, if the request was sent using
+    /// `send_api_request_to()`. This database is opened using the connected
+    /// client's permissions.
+    pub database: Option<ServerDatabase<B>>,
 }
 
 #[async_trait]
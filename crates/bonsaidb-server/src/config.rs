@@ -3,18 +3,24 @@ use std::marker::PhantomData;
 use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bonsaidb_core::api;
 use bonsaidb_core::api::ApiName;
 #[cfg(feature = "encryption")]
 use bonsaidb_core::document::KeyId;
+use bonsaidb_core::networking::{RequestSigningKey, SUPPORTED_PROTOCOL_VERSIONS};
 use bonsaidb_core::permissions::{Permissions, Statement};
-use bonsaidb_core::schema::Schema;
+use bonsaidb_core::schema::{Collection, Schema, View};
+use bonsaidb_local::config::{
+    Builder, CompactionPriority, DatabaseConfiguration, KeyValueClock, KeyValuePersistence,
+    RepairMode, StorageConfiguration, WorkCategory,
+};
 #[cfg(feature = "compression")]
-use bonsaidb_local::config::Compression;
-use bonsaidb_local::config::{Builder, KeyValuePersistence, StorageConfiguration};
+use bonsaidb_local::config::{Compression, CompressionDictionary};
 #[cfg(feature = "encryption")]
 use bonsaidb_local::vault::AnyVaultKeyStorage;
+use bonsaidb_local::{DatabaseLifecycle, ExternalIdentityResolver, QuotaMonitor, ScrubMonitor};
 
 use crate::api::{AnyHandler, AnyWrapper, Handler};
 use crate::{Backend, Error, NoBackend};
@@ -42,7 +48,32 @@ pub struct ServerConfiguration<B: Backend = NoBackend> {
     /// The ACME settings for automatic TLS certificate management.
     #[cfg(feature = "acme")]
     pub acme: AcmeConfiguration,
-
+    /// Controls whether requests must be signed, and which key is used to
+    /// verify them. Default value is [`RequestSigning::Disabled`].
+    pub request_signing: RequestSigning,
+    /// The amount of time the results of idempotent requests are remembered.
+    /// Clients retrying an operation within this window receive the original
+    /// response rather than the operation being executed again. Default value
+    /// is 24 hours.
+    pub idempotency_window: Duration,
+    /// The maximum number of idempotent request results remembered at once.
+    /// When this limit is reached, the oldest results are forgotten before
+    /// [`Self::idempotency_window`](Self#structfield.idempotency_window)
+    /// has elapsed. Default value is 100,000.
+    pub max_idempotent_operations: usize,
+    /// The maximum size, in bytes, of a request's payload. Requests with
+    /// larger payloads are rejected with
+    /// [`Error::PayloadTooLarge`](bonsaidb_core::Error::PayloadTooLarge)
+    /// before they are dispatched. `WebSocket` connections are closed as soon
+    /// as they begin receiving a message that exceeds the limit, without
+    /// buffering the rest of it. Bodies of requests to the HTTP bridge are
+    /// limited to 64 MiB when this isn't set. When limiting document sizes using
+    /// [`StorageConfiguration::max_document_size`](bonsaidb_local::config::StorageConfiguration::max_document_size),
+    /// this should be set high enough to include the transactions clients
+    /// are expected to send. Default value is `None`.
+    pub max_payload_size: Option<usize>,
+
+    pub(crate) protocol_versions: &'static [&'static str],
     pub(crate) custom_apis: HashMap<ApiName, Arc<dyn AnyHandler<B>>>,
 }
 
@@ -61,6 +92,11 @@ impl<B: Backend> ServerConfiguration<B> {
             custom_apis: HashMap::default(),
             #[cfg(feature = "acme")]
             acme: AcmeConfiguration::default(),
+            request_signing: RequestSigning::Disabled,
+            idempotency_window: Duration::from_secs(24 * 60 * 60),
+            max_idempotent_operations: 100_000,
+            max_payload_size: None,
+            protocol_versions: SUPPORTED_PROTOCOL_VERSIONS,
         }
     }
 
@@ -96,6 +132,46 @@ impl<B: Backend> ServerConfiguration<B> {
         self
     }
 
+    /// Sets [`Self::request_signing`](Self#structfield.request_signing) to
+    /// [`RequestSigning::Key`] with `key` and returns self.
+    pub fn request_signing_key(mut self, key: RequestSigningKey) -> Self {
+        self.request_signing = RequestSigning::Key(key);
+        self
+    }
+
+    /// Sets [`Self::request_signing`](Self#structfield.request_signing) to
+    /// [`RequestSigning::PerStorage`] and returns self.
+    pub fn generate_request_signing_key(mut self) -> Self {
+        self.request_signing = RequestSigning::PerStorage;
+        self
+    }
+
+    /// Sets [`Self::idempotency_window`](Self#structfield.idempotency_window) to `window` and returns self.
+    pub const fn idempotency_window(mut self, window: Duration) -> Self {
+        self.idempotency_window = window;
+        self
+    }
+
+    /// Sets [`Self::max_idempotent_operations`](Self#structfield.max_idempotent_operations) to `operations` and returns self.
+    pub const fn max_idempotent_operations(mut self, operations: usize) -> Self {
+        self.max_idempotent_operations = operations;
+        self
+    }
+
+    /// Sets [`Self::max_payload_size`](Self#structfield.max_payload_size) to `bytes` and returns self.
+    pub const fn max_payload_size(mut self, bytes: usize) -> Self {
+        self.max_payload_size = Some(bytes);
+        self
+    }
+
+    /// Overrides the protocol versions accepted from clients, ordered from
+    /// newest to oldest. Only for testing purposes.
+    #[cfg(feature = "test-util")]
+    pub const fn with_protocol_versions(mut self, versions: &'static [&'static str]) -> Self {
+        self.protocol_versions = versions;
+        self
+    }
+
     /// Sets [`AcmeConfiguration::contact_email`] to `contact_email` and returns self.
     #[cfg(feature = "acme")]
     pub fn acme_contact_email(mut self, contact_email: impl Into<String>) -> Self {
@@ -168,6 +244,22 @@ mod acme {
 #[cfg(feature = "acme")]
 pub use acme::*;
 
+/// Controls how a server verifies signed requests. See [`RequestSigningKey`].
+#[derive(Debug, Clone)]
+pub enum RequestSigning {
+    /// Requests do not need to be signed.
+    Disabled,
+    /// All requests must be signed using this key, and requests that are
+    /// unsigned or have an invalid signature are rejected.
+    Key(RequestSigningKey),
+    /// All requests must be signed using a key that is randomly generated the
+    /// first time the server's storage is opened. The key is stored in the
+    /// storage directory as `request-signing-key`, and can be retrieved using
+    /// [`CustomServer::request_signing_key()`](crate::CustomServer::request_signing_key)
+    /// to configure clients. Anyone able to read this file can sign requests.
+    PerStorage,
+}
+
 /// The default permissions to use for all connections to the server.
 #[derive(Debug, Clone)]
 pub enum DefaultPermissions {
@@ -210,6 +302,11 @@ impl<B: Backend> Builder for ServerConfiguration<B> {
         Ok(self)
     }
 
+    fn with_admin_extension<S: Schema>(mut self) -> Result<Self, bonsaidb_local::Error> {
+        self.storage.register_admin_extension::<S>()?;
+        Ok(self)
+    }
+
     fn memory_only(mut self) -> Self {
         self.storage.memory_only = true;
         self
@@ -250,22 +347,132 @@ impl<B: Backend> Builder for ServerConfiguration<B> {
         self
     }
 
+    fn tasks_max_concurrent_integrity_scans(mut self, max_scans: usize) -> Self {
+        self.storage.workers.max_concurrent_integrity_scans = max_scans;
+        self
+    }
+
+    fn tasks_expiration_load_batch_size(mut self, batch_size: usize) -> Self {
+        self.storage.workers.expiration_load_batch_size = batch_size;
+        self
+    }
+
     fn check_view_integrity_on_open(mut self, check: bool) -> Self {
         self.storage.views.check_integrity_on_open = check;
         self
     }
 
+    fn check_view_integrity_on_open_for_collection<C: Collection>(mut self, check: bool) -> Self {
+        self.storage = self
+            .storage
+            .check_view_integrity_on_open_for_collection::<C>(check);
+        self
+    }
+
+    fn check_view_integrity_on_open_for_view<V: View>(mut self, view: &V, check: bool) -> Self {
+        self.storage = self
+            .storage
+            .check_view_integrity_on_open_for_view(view, check);
+        self
+    }
+
     #[cfg(feature = "compression")]
     fn default_compression(mut self, compression: Compression) -> Self {
         self.storage.default_compression = Some(compression);
         self
     }
 
+    #[cfg(feature = "compression")]
+    fn compression_dictionary<C: Collection>(mut self, dictionary: CompressionDictionary) -> Self {
+        self.storage = self.storage.compression_dictionary::<C>(dictionary);
+        self
+    }
+
+    #[cfg(feature = "compression")]
+    fn block_checksums(mut self, enabled: bool) -> Self {
+        self.storage.block_checksums = enabled;
+        self
+    }
+
     fn key_value_persistence(mut self, persistence: KeyValuePersistence) -> Self {
         self.storage.key_value_persistence = persistence;
         self
     }
 
+    fn database_configuration(
+        mut self,
+        name: impl Into<String>,
+        configuration: DatabaseConfiguration,
+    ) -> Self {
+        self.storage = self.storage.database_configuration(name, configuration);
+        self
+    }
+
+    fn key_value_clock(mut self, clock: KeyValueClock) -> Self {
+        self.storage.key_value_clock = clock;
+        self
+    }
+
+    fn quota_monitor<M: QuotaMonitor>(mut self, monitor: M) -> Self {
+        self.storage.quota_monitor = Some(Arc::new(monitor));
+        self
+    }
+
+    fn scrub_monitor<M: ScrubMonitor>(mut self, monitor: M) -> Self {
+        self.storage.scrub_monitor = Some(Arc::new(monitor));
+        self
+    }
+
+    fn database_lifecycle<L: DatabaseLifecycle>(mut self, lifecycle: L) -> Self {
+        self.storage.database_lifecycle = Some(Arc::new(lifecycle));
+        self
+    }
+
+    fn max_subscribers_per_session(mut self, max: usize) -> Self {
+        self.storage.subscribers.max_per_session = Some(max);
+        self
+    }
+
+    fn repair_mode(mut self, mode: RepairMode) -> Self {
+        self.storage.repair_mode = Some(mode);
+        self
+    }
+
+    fn max_document_size(mut self, bytes: u64) -> Self {
+        self.storage.max_document_size = Some(bytes);
+        self
+    }
+
+    fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.storage.lock_timeout = Some(timeout);
+        self
+    }
+
+    fn replication_log(mut self, enabled: bool) -> Self {
+        self.storage.replication_log = enabled;
+        self
+    }
+
+    fn compaction_priority<C: Collection>(mut self, priority: CompactionPriority) -> Self {
+        self.storage = self.storage.compaction_priority::<C>(priority);
+        self
+    }
+
+    fn max_subscribers(mut self, max: usize) -> Self {
+        self.storage.subscribers.max_total = Some(max);
+        self
+    }
+
+    fn max_in_flight(mut self, category: WorkCategory, max: usize) -> Self {
+        self.storage.admission.set_limit(category, Some(max));
+        self
+    }
+
+    fn overload_retry_after_hint(mut self, hint: Duration) -> Self {
+        self.storage.admission.retry_after_hint = hint;
+        self
+    }
+
     fn authenticated_permissions<P: Into<Permissions>>(
         mut self,
         authenticated_permissions: P,
@@ -274,6 +481,11 @@ impl<B: Backend> Builder for ServerConfiguration<B> {
         self
     }
 
+    fn external_identities<R: ExternalIdentityResolver>(mut self, resolver: R) -> Self {
+        self.storage.external_identities = Some(Arc::new(resolver));
+        self
+    }
+
     #[cfg(feature = "password-hashing")]
     fn argon(mut self, argon: bonsaidb_local::config::ArgonConfiguration) -> Self {
         self.storage.argon = argon;
@@ -1,26 +1,42 @@
+use std::time::{Duration, Instant};
+
 use bonsaidb_core::api::ApiName;
 use bonsaidb_core::arc_bytes::serde::Bytes;
 use bonsaidb_core::async_trait::async_trait;
+#[cfg(feature = "token-authentication")]
+use bonsaidb_core::connection::SessionAuthentication;
 use bonsaidb_core::connection::{
     AsyncConnection, AsyncLowLevelConnection, AsyncStorageConnection, HasSession,
 };
 use bonsaidb_core::keyvalue::AsyncKeyValue;
+#[cfg(feature = "token-authentication")]
+use bonsaidb_core::networking::AuthenticateConnection;
 use bonsaidb_core::networking::{
-    AlterUserPermissionGroupMembership, AlterUserRoleMembership, ApplyTransaction, AssumeIdentity,
-    Compact, CompactCollection, CompactKeyValueStore, Count, CreateDatabase, CreateSubscriber,
-    CreateUser, DeleteDatabase, DeleteDocs, DeleteUser, ExecuteKeyOperation, Get, GetMultiple,
-    LastTransactionId, List, ListAvailableSchemas, ListDatabases, ListExecutedTransactions,
-    ListHeaders, LogOutSession, Publish, PublishToAll, Query, QueryWithDocs, Reduce, ReduceGrouped,
-    SubscribeTo, UnregisterSubscriber, UnsubscribeFrom,
+    AlterUserPermissionGroupMembership, AlterUserRoleMembership, AppliedTransaction,
+    ApplyTransaction, ApplyTransactionReturningId, AssumeCappedIdentity, AssumeIdentity,
+    AwaitKeyValueDurability, Compact, CompactCollection, CompactKeyValueStore, Count,
+    CreateDatabase, CreatePermissionGroup, CreateRole, CreateSubscriber, CreateUser,
+    DeleteDatabase, DeleteDocs, DeleteDocsReturningHeaders, DeleteRole, DeleteUser, DescribeSchema,
+    ExecuteKeyOperation, Get, GetMultiple, LastTransactionId, List, ListAvailableSchemas,
+    ListCapabilities, ListDatabases, ListExecutedTransactions, ListHeaders, LogOutSession,
+    PersistKeyValueStore, Publish, PublishToAll, Query, QueryWithDocs, Reduce, ReduceGrouped,
+    Scrub, SetDatabaseQuota, SubscribeTo, UnregisterSubscriber, UnsubscribeFrom,
+    UpdatePermissionGroupStatements,
 };
 #[cfg(feature = "password-hashing")]
 use bonsaidb_core::networking::{Authenticate, SetUserPassword};
+use bonsaidb_core::permissions::bonsai::{
+    kv_resource_name, BonsaiAction, DatabaseAction, KeyValueAction,
+};
 use bonsaidb_core::pubsub::AsyncPubSub;
 
 use crate::api::{Handler, HandlerError, HandlerResult, HandlerSession};
 use crate::{Backend, Error, ServerConfiguration};
 
-#[cfg_attr(not(feature = "password-hashing"), allow(unused_mut))]
+#[cfg_attr(
+    not(any(feature = "password-hashing", feature = "token-authentication")),
+    allow(unused_mut)
+)]
 pub fn register_api_handlers<B: Backend>(
     config: ServerConfiguration<B>,
 ) -> Result<ServerConfiguration<B>, Error> {
@@ -28,17 +44,24 @@ pub fn register_api_handlers<B: Backend>(
         .with_api::<ServerDispatcher, AlterUserPermissionGroupMembership>()?
         .with_api::<ServerDispatcher, AlterUserRoleMembership>()?
         .with_api::<ServerDispatcher, ApplyTransaction>()?
+        .with_api::<ServerDispatcher, ApplyTransactionReturningId>()?
         .with_api::<ServerDispatcher, AssumeIdentity>()?
+        .with_api::<ServerDispatcher, AssumeCappedIdentity>()?
         .with_api::<ServerDispatcher, Compact>()?
         .with_api::<ServerDispatcher, CompactCollection>()?
         .with_api::<ServerDispatcher, CompactKeyValueStore>()?
         .with_api::<ServerDispatcher, Count>()?
         .with_api::<ServerDispatcher, CreateDatabase>()?
+        .with_api::<ServerDispatcher, CreatePermissionGroup>()?
+        .with_api::<ServerDispatcher, CreateRole>()?
         .with_api::<ServerDispatcher, CreateSubscriber>()?
         .with_api::<ServerDispatcher, CreateUser>()?
         .with_api::<ServerDispatcher, DeleteDatabase>()?
         .with_api::<ServerDispatcher, DeleteDocs>()?
+        .with_api::<ServerDispatcher, DeleteDocsReturningHeaders>()?
+        .with_api::<ServerDispatcher, DeleteRole>()?
         .with_api::<ServerDispatcher, DeleteUser>()?
+        .with_api::<ServerDispatcher, DescribeSchema>()?
         .with_api::<ServerDispatcher, ExecuteKeyOperation>()?
         .with_api::<ServerDispatcher, Get>()?
         .with_api::<ServerDispatcher, GetMultiple>()?
@@ -46,18 +69,24 @@ pub fn register_api_handlers<B: Backend>(
         .with_api::<ServerDispatcher, List>()?
         .with_api::<ServerDispatcher, ListHeaders>()?
         .with_api::<ServerDispatcher, ListAvailableSchemas>()?
+        .with_api::<ServerDispatcher, ListCapabilities>()?
         .with_api::<ServerDispatcher, ListDatabases>()?
         .with_api::<ServerDispatcher, ListExecutedTransactions>()?
         .with_api::<ServerDispatcher, LogOutSession>()?
+        .with_api::<ServerDispatcher, PersistKeyValueStore>()?
+        .with_api::<ServerDispatcher, AwaitKeyValueDurability>()?
         .with_api::<ServerDispatcher, Publish>()?
         .with_api::<ServerDispatcher, PublishToAll>()?
         .with_api::<ServerDispatcher, Query>()?
         .with_api::<ServerDispatcher, QueryWithDocs>()?
         .with_api::<ServerDispatcher, Reduce>()?
         .with_api::<ServerDispatcher, ReduceGrouped>()?
+        .with_api::<ServerDispatcher, Scrub>()?
+        .with_api::<ServerDispatcher, SetDatabaseQuota>()?
         .with_api::<ServerDispatcher, SubscribeTo>()?
         .with_api::<ServerDispatcher, UnregisterSubscriber>()?
-        .with_api::<ServerDispatcher, UnsubscribeFrom>()?;
+        .with_api::<ServerDispatcher, UnsubscribeFrom>()?
+        .with_api::<ServerDispatcher, UpdatePermissionGroupStatements>()?;
 
     #[cfg(feature = "password-hashing")]
     {
@@ -66,6 +95,11 @@ pub fn register_api_handlers<B: Backend>(
             .with_api::<ServerDispatcher, SetUserPassword>()?;
     }
 
+    #[cfg(feature = "token-authentication")]
+    {
+        config = config.with_api::<ServerDispatcher, AuthenticateConnection>()?;
+    }
+
     Ok(config)
 }
 
@@ -77,8 +111,12 @@ impl ServerDispatcher {
         name: &ApiName,
         request: Bytes,
     ) -> Result<Bytes, Error> {
-        if let Some(dispatcher) = session.server.custom_api_dispatcher(name) {
-            dispatcher.handle(session, &request).await
+        let server = session.server;
+        if let Some(dispatcher) = server.custom_api_dispatcher(name) {
+            let started = Instant::now();
+            let result = dispatcher.handle(session, &request).await;
+            server.record_request_timing(name, started.elapsed());
+            result
         } else {
             Err(Error::from(bonsaidb_core::Error::ApiNotFound(name.clone())))
         }
@@ -114,6 +152,20 @@ impl<B: Backend> Handler<DeleteDatabase, B> for ServerDispatcher {
     }
 }
 
+#[async_trait]
+impl<B: Backend> Handler<SetDatabaseQuota, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: SetDatabaseQuota,
+    ) -> HandlerResult<SetDatabaseQuota> {
+        session
+            .as_client
+            .set_database_quota(&command.name, command.quota)
+            .await?;
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl<B: Backend> Handler<ListDatabases, B> for ServerDispatcher {
     async fn handle(
@@ -142,6 +194,30 @@ impl<B: Backend> Handler<ListAvailableSchemas, B> for ServerDispatcher {
     }
 }
 
+#[async_trait]
+impl<B: Backend> Handler<ListCapabilities, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        _command: ListCapabilities,
+    ) -> HandlerResult<ListCapabilities> {
+        Ok(session.server.capabilities())
+    }
+}
+
+#[async_trait]
+impl<B: Backend> Handler<DescribeSchema, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: DescribeSchema,
+    ) -> HandlerResult<DescribeSchema> {
+        session
+            .as_client
+            .describe_schema(command.schema)
+            .await
+            .map_err(HandlerError::from)
+    }
+}
+
 #[async_trait]
 impl<B: Backend> Handler<CreateUser, B> for ServerDispatcher {
     async fn handle(
@@ -204,6 +280,37 @@ impl<B: Backend> Handler<Authenticate, B> for ServerDispatcher {
     }
 }
 
+#[cfg(feature = "token-authentication")]
+#[async_trait]
+impl<B: Backend> Handler<AuthenticateConnection, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: AuthenticateConnection,
+    ) -> HandlerResult<AuthenticateConnection> {
+        let authenticated = session
+            .as_client
+            .authenticate(command.authentication)
+            .await?;
+        let new_session = authenticated.session().cloned().unwrap();
+
+        // A token challenge is answered from the pending session, which must
+        // be known to the client until the challenge is answered.
+        if matches!(
+            new_session.authentication,
+            SessionAuthentication::TokenChallenge { .. }
+        ) {
+            session.client.logged_in_as(new_session.clone());
+        } else {
+            if let Some(challenge) = session.as_client.session().and_then(|pending| pending.id) {
+                session.client.log_out(challenge);
+            }
+            session.client.set_connection_session(new_session.clone());
+        }
+
+        Ok(new_session)
+    }
+}
+
 #[async_trait]
 impl<B: Backend> Handler<AssumeIdentity, B> for ServerDispatcher {
     async fn handle(
@@ -219,6 +326,21 @@ impl<B: Backend> Handler<AssumeIdentity, B> for ServerDispatcher {
     }
 }
 
+#[async_trait]
+impl<B: Backend> Handler<AssumeCappedIdentity, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: AssumeCappedIdentity,
+    ) -> HandlerResult<AssumeCappedIdentity> {
+        let authenticated = session.as_client.assume_identity_capped(command.0).await?;
+        let new_session = authenticated.session().cloned().unwrap();
+
+        session.client.logged_in_as(new_session.clone());
+
+        Ok(new_session)
+    }
+}
+
 #[async_trait]
 impl<B: Backend> Handler<LogOutSession, B> for ServerDispatcher {
     async fn handle(
@@ -284,6 +406,62 @@ impl<B: Backend> Handler<AlterUserRoleMembership, B> for ServerDispatcher {
     }
 }
 
+#[async_trait]
+impl<B: Backend> Handler<CreatePermissionGroup, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: CreatePermissionGroup,
+    ) -> HandlerResult<CreatePermissionGroup> {
+        session
+            .as_client
+            .create_permission_group(&command.name, command.statements)
+            .await
+            .map_err(HandlerError::from)
+    }
+}
+
+#[async_trait]
+impl<B: Backend> Handler<UpdatePermissionGroupStatements, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: UpdatePermissionGroupStatements,
+    ) -> HandlerResult<UpdatePermissionGroupStatements> {
+        session
+            .as_client
+            .update_permission_group_statements(command.group, command.statements)
+            .await
+            .map_err(HandlerError::from)
+    }
+}
+
+#[async_trait]
+impl<B: Backend> Handler<CreateRole, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: CreateRole,
+    ) -> HandlerResult<CreateRole> {
+        session
+            .as_client
+            .create_role(&command.name, command.groups)
+            .await
+            .map_err(HandlerError::from)
+    }
+}
+
+#[async_trait]
+impl<B: Backend> Handler<DeleteRole, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: DeleteRole,
+    ) -> HandlerResult<DeleteRole> {
+        session
+            .as_client
+            .delete_role(command.role)
+            .await
+            .map_err(HandlerError::from)
+    }
+}
+
 #[async_trait]
 impl<B: Backend> Handler<Get, B> for ServerDispatcher {
     async fn handle(session: HandlerSession<'_, B>, command: Get) -> HandlerResult<Get> {
@@ -462,6 +640,26 @@ impl<B: Backend> Handler<ApplyTransaction, B> for ServerDispatcher {
     }
 }
 
+#[async_trait]
+impl<B: Backend> Handler<ApplyTransactionReturningId, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: ApplyTransactionReturningId,
+    ) -> HandlerResult<ApplyTransactionReturningId> {
+        let database = session
+            .as_client
+            .database_without_schema(&command.0.database)
+            .await?;
+        let (transaction_id, results) = database
+            .apply_transaction_with_id(command.0.transaction)
+            .await?;
+        Ok(AppliedTransaction {
+            transaction_id,
+            results,
+        })
+    }
+}
+
 #[async_trait]
 impl<B: Backend> Handler<DeleteDocs, B> for ServerDispatcher {
     async fn handle(
@@ -473,7 +671,30 @@ impl<B: Backend> Handler<DeleteDocs, B> for ServerDispatcher {
             .database_without_schema(&command.database)
             .await?;
         database
-            .delete_docs_by_name(&command.view, command.key, command.access_policy)
+            .delete_docs_by_name(&command.view, command.key, command.access_policy, false)
+            .await
+            .map(|deleted| deleted.count)
+            .map_err(HandlerError::from)
+    }
+}
+
+#[async_trait]
+impl<B: Backend> Handler<DeleteDocsReturningHeaders, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: DeleteDocsReturningHeaders,
+    ) -> HandlerResult<DeleteDocsReturningHeaders> {
+        let database = session
+            .as_client
+            .database_without_schema(&command.0.database)
+            .await?;
+        database
+            .delete_docs_by_name(
+                &command.0.view,
+                command.0.key,
+                command.0.access_policy,
+                true,
+            )
             .await
             .map_err(HandlerError::from)
     }
@@ -636,6 +857,52 @@ impl<B: Backend> Handler<ExecuteKeyOperation, B> for ServerDispatcher {
     }
 }
 
+#[async_trait]
+impl<B: Backend> Handler<PersistKeyValueStore, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: PersistKeyValueStore,
+    ) -> HandlerResult<PersistKeyValueStore> {
+        let database = session
+            .as_client
+            .database_without_schema(&command.database)
+            .await?;
+        database.persist().await.map_err(HandlerError::from)
+    }
+}
+
+/// The longest an [`AwaitKeyValueDurability`] request waits for the key-value
+/// store to be persisted before responding with a timeout.
+const DURABILITY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[async_trait]
+impl<B: Backend> Handler<AwaitKeyValueDurability, B> for ServerDispatcher {
+    async fn handle(
+        session: HandlerSession<'_, B>,
+        command: AwaitKeyValueDurability,
+    ) -> HandlerResult<AwaitKeyValueDurability> {
+        let database = session
+            .as_client
+            .database_without_schema(&command.database)
+            .await?;
+        database.check_permission(
+            kv_resource_name(&command.database),
+            &BonsaiAction::Database(DatabaseAction::KeyValue(KeyValueAction::Persist)),
+        )?;
+        match tokio::time::timeout(
+            DURABILITY_TIMEOUT,
+            database.durability_receipt().wait_async(),
+        )
+        .await
+        {
+            Ok(result) => result.map_err(HandlerError::from),
+            Err(_) => Err(HandlerError::from(bonsaidb_core::Error::Networking(
+                bonsaidb_core::networking::Error::RequestTimeout,
+            ))),
+        }
+    }
+}
+
 #[async_trait]
 impl<B: Backend> Handler<CompactCollection, B> for ServerDispatcher {
     async fn handle(
@@ -670,6 +937,23 @@ impl<B: Backend> Handler<CompactKeyValueStore, B> for ServerDispatcher {
     }
 }
 
+#[async_trait]
+impl<B: Backend> Handler<Scrub, B> for ServerDispatcher {
+    async fn handle(session: HandlerSession<'_, B>, command: Scrub) -> HandlerResult<Scrub> {
+        let database = session.database.as_ref().ok_or_else(|| {
+            bonsaidb_core::Error::other(
+                "bonsaidb-server",
+                "Scrub must be sent using send_api_request_to()",
+            )
+        })?;
+        let report = match command.duration {
+            Some(duration) => database.db.scrub_for(duration).await?,
+            None => database.db.scrub().await?,
+        };
+        Ok(report)
+    }
+}
+
 #[async_trait]
 impl<B: Backend> Handler<Compact, B> for ServerDispatcher {
     async fn handle(client: HandlerSession<'_, B>, command: Compact) -> HandlerResult<Compact> {
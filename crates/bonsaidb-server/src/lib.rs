@@ -34,11 +34,16 @@ pub use config::{
 };
 
 pub use self::backend::{Backend, BackendError, ConnectionHandling, NoBackend};
-pub use self::config::{BonsaiListenConfig, DefaultPermissions, ServerConfiguration};
+pub use self::config::{
+    BonsaiListenConfig, DefaultPermissions, RequestSigning, ServerConfiguration,
+};
 pub use self::error::Error;
+#[cfg(feature = "http")]
+pub use self::server::HttpBridge;
 pub use self::server::{
-    ApplicationProtocols, ConnectedClient, CustomServer, HttpService, LockedClientDataGuard, Peer,
-    Server, ServerDatabase, StandardTcpProtocols, TcpService, Transport,
+    ApplicationProtocols, ConnectedClient, CustomServer, HttpService, LocalApiClient,
+    LockedClientDataGuard, Peer, RequestTimings, Server, ServerDatabase, StandardTcpProtocols,
+    TcpService, Transport,
 };
 
 #[cfg(test)]
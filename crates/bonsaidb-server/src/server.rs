@@ -2,13 +2,13 @@ use std::collections::{hash_map, HashMap};
 use std::fmt::Debug;
 use std::net::SocketAddr;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
-use bonsaidb_core::admin::{Admin, ADMIN_DATABASE_NAME};
+use bonsaidb_core::admin::{Admin, DatabaseQuota, ADMIN_DATABASE_NAME};
 use bonsaidb_core::api;
 use bonsaidb_core::api::ApiName;
 use bonsaidb_core::arc_bytes::serde::Bytes;
@@ -16,12 +16,15 @@ use bonsaidb_core::connection::{
     self, AsyncConnection, AsyncStorageConnection, HasSession, IdentityReference, Session,
     SessionId,
 };
-use bonsaidb_core::networking::{self, Payload, CURRENT_PROTOCOL_VERSION};
+use bonsaidb_core::networking::{
+    self, Capability, DatabaseRequest, IdempotentRequest, ListCapabilities, Payload,
+    RequestSigningKey,
+};
 use bonsaidb_core::permissions::bonsai::{bonsaidb_resource_name, BonsaiAction, ServerAction};
-use bonsaidb_core::permissions::Permissions;
+use bonsaidb_core::permissions::{Permissions, Statement};
 use bonsaidb_core::schema::{self, Nameable, NamedCollection, Schema, SchemaSummary};
 use bonsaidb_local::config::Builder;
-use bonsaidb_local::{AsyncStorage, Storage, StorageNonBlocking};
+use bonsaidb_local::{atomic_write, AsyncStorage, FileDurability, Storage, StorageNonBlocking};
 use bonsaidb_utils::fast_async_lock;
 use derive_where::derive_where;
 use fabruic::{self, CertificateChain, Endpoint, KeyPair, PrivateKey};
@@ -43,22 +46,35 @@ use crate::dispatch::{register_api_handlers, ServerDispatcher};
 use crate::error::Error;
 use crate::hosted::{Hosted, SerializablePrivateKey, TlsCertificate, TlsCertificatesByDomain};
 use crate::server::shutdown::{Shutdown, ShutdownState, ShutdownStateWatcher};
-use crate::{Backend, BackendError, BonsaiListenConfig, NoBackend, ServerConfiguration};
+use crate::{
+    Backend, BackendError, BonsaiListenConfig, NoBackend, RequestSigning, ServerConfiguration,
+};
 
 #[cfg(feature = "acme")]
 pub mod acme;
 mod connected_client;
 mod database;
+#[cfg(feature = "http")]
+mod http;
+mod local;
+mod operations;
 
 mod shutdown;
 mod tcp;
+mod timings;
 #[cfg(feature = "websockets")]
 mod websockets;
 
 use self::connected_client::OwnedClient;
 pub use self::connected_client::{ConnectedClient, LockedClientDataGuard, Transport};
 pub use self::database::ServerDatabase;
+#[cfg(feature = "http")]
+pub use self::http::HttpBridge;
+pub use self::local::LocalApiClient;
+use self::operations::{IdempotentOperation, OperationKey, Operations};
 pub use self::tcp::{ApplicationProtocols, HttpService, Peer, StandardTcpProtocols, TcpService};
+use self::timings::RequestTimingRegistry;
+pub use self::timings::RequestTimings;
 
 static CONNECTED_CLIENT_ID_COUNTER: AtomicU32 = AtomicU32::new(0);
 
@@ -92,9 +108,14 @@ struct Data<B: Backend = NoBackend> {
     request_processor: flume::Sender<ClientRequest<B>>,
     default_session: Session,
     client_simultaneous_request_limit: usize,
+    max_payload_size: Option<usize>,
+    protocol_versions: &'static [&'static str],
     primary_tls_key: CachedCertifiedKey,
     primary_domain: String,
     custom_apis: RwLock<HashMap<ApiName, Arc<dyn AnyHandler<B>>>>,
+    request_signing_key: Option<RequestSigningKey>,
+    operations: Operations,
+    request_timings: RequestTimingRegistry,
     #[cfg(feature = "acme")]
     acme: AcmeConfiguration,
     #[cfg(feature = "acme")]
@@ -131,6 +152,17 @@ impl<B: Backend> CustomServer<B> {
             tokio::task::spawn(async move {
                 while let Ok(mut client_request) = request_receiver.recv_async().await {
                     let request = client_request.request.take().unwrap();
+                    if let Err(err) = client_request.server.check_payload_size(&request) {
+                        drop(client_request.result_sender.send((request.name, Err(err))));
+                        continue;
+                    }
+                    let request = match client_request.server.verify_request_signature(request) {
+                        Ok(request) => request,
+                        Err((name, err)) => {
+                            drop(client_request.result_sender.send((name, Err(err))));
+                            continue;
+                        }
+                    };
                     let session = client_request.session.clone();
                     // TODO we should be able to upgrade a session-less Storage to one with a Session.
                     // The Session needs to be looked up from the client based on the request's session id.
@@ -143,14 +175,10 @@ impl<B: Backend> CustomServer<B> {
                                     data: client_request.server.data.clone(),
                                     storage,
                                 },
+                                database: None,
                             };
-                            ServerDispatcher::dispatch_api_request(
-                                client,
-                                &request.name,
-                                request.value.unwrap(),
-                            )
-                            .await
-                            .map_err(bonsaidb_core::Error::from)
+                            Self::dispatch_request(client, &request.name, request.value.unwrap())
+                                .await
                         }
                         Err(err) => Err(err),
                     };
@@ -165,6 +193,21 @@ impl<B: Backend> CustomServer<B> {
 
         let default_permissions = Permissions::from(configuration.default_permissions);
 
+        let request_signing_key = match configuration.request_signing {
+            RequestSigning::Disabled => None,
+            RequestSigning::Key(key) => Some(key),
+            RequestSigning::PerStorage => {
+                let storage_path = storage.path().to_path_buf();
+                Some(
+                    tokio::task::spawn_blocking(move || {
+                        load_or_generate_request_signing_key(&storage_path)
+                    })
+                    .await
+                    .map_err(|err| bonsaidb_core::Error::other("bonsaidb-server", err))??,
+                )
+            }
+        };
+
         let server = Self {
             storage,
             data: Arc::new(Data {
@@ -176,9 +219,17 @@ impl<B: Backend> CustomServer<B> {
                     ..Session::default()
                 },
                 client_simultaneous_request_limit: configuration.client_simultaneous_request_limit,
+                max_payload_size: configuration.max_payload_size,
+                protocol_versions: configuration.protocol_versions,
                 primary_tls_key: CachedCertifiedKey::default(),
                 primary_domain: configuration.server_name,
+                request_timings: RequestTimingRegistry::new(configuration.custom_apis.keys()),
                 custom_apis: parking_lot::RwLock::new(configuration.custom_apis),
+                request_signing_key,
+                operations: Operations::new(
+                    configuration.idempotency_window,
+                    configuration.max_idempotent_operations,
+                ),
                 #[cfg(feature = "acme")]
                 acme: configuration.acme,
                 #[cfg(feature = "acme")]
@@ -199,6 +250,13 @@ impl<B: Backend> CustomServer<B> {
         self.path().join("pinned-certificate.der")
     }
 
+    /// Returns the key clients must sign requests with, if request signing is
+    /// enabled. See [`RequestSigning`].
+    #[must_use]
+    pub fn request_signing_key(&self) -> Option<&RequestSigningKey> {
+        self.data.request_signing_key.as_ref()
+    }
+
     /// Returns the primary domain configured for this server.
     #[must_use]
     pub fn primary_domain(&self) -> &str {
@@ -228,6 +286,165 @@ impl<B: Backend> CustomServer<B> {
         }
     }
 
+    /// Returns the capabilities this server reports to clients.
+    #[must_use]
+    pub fn capabilities(&self) -> Vec<Capability> {
+        [
+            (Capability::COMPRESSION, cfg!(feature = "compression")),
+            (Capability::ENCRYPTION, cfg!(feature = "encryption")),
+            (
+                Capability::PASSWORD_AUTHENTICATION,
+                cfg!(feature = "password-hashing"),
+            ),
+            (
+                Capability::REQUEST_SIGNING,
+                self.data.request_signing_key.is_some(),
+            ),
+            (
+                Capability::TOKEN_AUTHENTICATION,
+                cfg!(feature = "token-authentication"),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(capability, supported)| supported.then_some(capability))
+        .collect()
+    }
+
+    /// Verifies the signature of `request` if request signing is enabled,
+    /// returning the request that was signed.
+    ///
+    /// [`ListCapabilities`] may be sent unsigned, because clients use its
+    /// response to learn whether this server requires signed requests.
+    fn verify_request_signature(
+        &self,
+        request: Payload,
+    ) -> Result<Payload, (ApiName, bonsaidb_core::Error)> {
+        match &self.data.request_signing_key {
+            Some(_) if request.name == <ListCapabilities as api::Api>::name() => Ok(request),
+            Some(key) => {
+                let name = request.name.clone();
+                key.verify(request).map_err(|err| {
+                    log::warn!("[server] Rejected request with an invalid signature: {name}");
+                    (name, err)
+                })
+            }
+            None => Ok(request),
+        }
+    }
+
+    fn check_payload_size(&self, request: &Payload) -> Result<(), bonsaidb_core::Error> {
+        let size = request.value.as_ref().map_or(0, |value| value.len());
+        match self.data.max_payload_size {
+            Some(limit) if size > limit => Err(bonsaidb_core::Error::PayloadTooLarge {
+                size: size as u64,
+                limit: limit as u64,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(any(feature = "websockets", feature = "http"))]
+    pub(crate) fn max_payload_size(&self) -> Option<usize> {
+        self.data.max_payload_size
+    }
+
+    pub(crate) fn protocol_versions(&self) -> &'static [&'static str] {
+        self.data.protocol_versions
+    }
+
+    /// Dispatches a request. If the request is an [`IdempotentRequest`] whose
+    /// operation has already succeeded, the original response is returned
+    /// instead of executing the request again. The session's permissions are
+    /// checked again before returning a previous response.
+    async fn dispatch_request(
+        session: HandlerSession<'_, B>,
+        name: &ApiName,
+        request: Bytes,
+    ) -> Result<Bytes, bonsaidb_core::Error> {
+        if *name != IdempotentRequest::name() {
+            return Self::dispatch_database_request(session, name, request).await;
+        }
+
+        let request = pot::from_slice::<IdempotentRequest>(&request)?;
+        let idempotent = IdempotentOperation::parse(&request.name, &request.value)?;
+        let client_session = session.as_client.session().cloned();
+        let key = OperationKey::new(
+            client_session.as_ref(),
+            idempotent.database(),
+            request.operation_id,
+        );
+        let server = session.server;
+        let operations = &server.data.operations;
+        let operation = operations.operation(&key);
+        let mut executed = false;
+        let result = {
+            let executed = &mut executed;
+            operation
+                .get_or_init(move || async move {
+                    *executed = true;
+                    Self::dispatch_database_request(session, &request.name, request.value).await
+                })
+                .await
+                .clone()
+        };
+        if !executed {
+            if let Some(client_session) = &client_session {
+                idempotent.check_permission(client_session)?;
+            }
+        }
+        operations.completed(&key, &operation, &result);
+        result
+    }
+
+    /// Dispatches a request. If the request is a [`DatabaseRequest`], the
+    /// wrapped request is dispatched with its database available through
+    /// [`HandlerSession::database`].
+    async fn dispatch_database_request(
+        mut session: HandlerSession<'_, B>,
+        name: &ApiName,
+        request: Bytes,
+    ) -> Result<Bytes, bonsaidb_core::Error> {
+        if *name != DatabaseRequest::name() {
+            return ServerDispatcher::dispatch_api_request(session, name, request)
+                .await
+                .map_err(bonsaidb_core::Error::from);
+        }
+
+        let request = pot::from_slice::<DatabaseRequest>(&request)?;
+        let db = session
+            .as_client
+            .database_without_schema(&request.database)
+            .await?;
+        session.database = Some(ServerDatabase {
+            server: session.as_client.clone(),
+            db,
+        });
+        ServerDispatcher::dispatch_api_request(session, &request.name, request.value)
+            .await
+            .map_err(bonsaidb_core::Error::from)
+    }
+
+    pub(crate) fn record_request_timing(&self, name: &ApiName, duration: Duration) {
+        self.data.request_timings.record(name, duration);
+    }
+
+    /// Returns the time spent handling requests for each registered
+    /// [`Api`](api::Api), including the built-in ones. Requests are timed
+    /// from when their handler is invoked until it returns, regardless of
+    /// whether the request was sent over the network or using
+    /// [`custom_api()`](Self::custom_api).
+    #[must_use]
+    pub fn request_statistics(&self) -> HashMap<ApiName, RequestTimings> {
+        self.data.request_timings.snapshot()
+    }
+
+    /// Returns the time spent handling requests for `Api`, or None if `Api`
+    /// is not registered with this server.
+    #[must_use]
+    pub fn request_timings<Api: api::Api>(&self) -> Option<RequestTimings> {
+        self.data.request_timings.timings(&Api::name())
+    }
+
     pub(crate) fn custom_api_dispatcher(&self, name: &ApiName) -> Option<Arc<dyn AnyHandler<B>>> {
         let dispatchers = self.data.custom_apis.read();
         dispatchers.get(name).cloned()
@@ -244,11 +461,17 @@ impl<B: Backend> CustomServer<B> {
         self.install_certificate(keypair.certificate_chain(), keypair.private_key())
             .await?;
 
-        tokio::fs::write(
-            self.pinned_certificate_path(),
-            keypair.end_entity_certificate().as_ref(),
-        )
-        .await?;
+        let pinned_certificate_path = self.pinned_certificate_path();
+        let certificate = keypair.end_entity_certificate().as_ref().to_vec();
+        tokio::task::spawn_blocking(move || {
+            atomic_write(
+                &pinned_certificate_path,
+                &certificate,
+                FileDurability::Durable,
+            )
+        })
+        .await
+        .map_err(|err| bonsaidb_core::Error::other("bonsaidb-server", err))??;
 
         Ok(())
     }
@@ -383,7 +606,14 @@ impl<B: Backend> CustomServer<B> {
         let keypair =
             KeyPair::from_parts(certificate.certificate_chain, certificate.private_key.0)?;
         let mut builder = Endpoint::builder();
-        builder.set_protocols([CURRENT_PROTOCOL_VERSION.as_bytes().to_vec()]);
+        // The server's order of preference is used when negotiating, which
+        // selects the newest version offered by the client.
+        builder.set_protocols(
+            self.protocol_versions()
+                .iter()
+                .map(|version| version.as_bytes().to_vec())
+                .collect::<Vec<_>>(),
+        );
         builder.set_address(config.address);
         builder.set_max_idle_timeout(None)?;
         builder.set_server_key_pair(Some(keypair));
@@ -849,6 +1079,28 @@ impl<B: Backend> CustomServer<B> {
     }
 }
 
+/// Loads the request signing key stored in `storage_path`, generating and
+/// storing a new key if one doesn't exist yet.
+fn load_or_generate_request_signing_key(storage_path: &Path) -> std::io::Result<RequestSigningKey> {
+    let path = storage_path.join("request-signing-key");
+    match std::fs::read(&path) {
+        Ok(bytes) => <[u8; 32]>::try_from(bytes.as_slice())
+            .map(RequestSigningKey::from_bytes)
+            .map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "request-signing-key does not contain a 32-byte key",
+                )
+            }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let key = RequestSigningKey::from_bytes(rand::random());
+            atomic_write(&path, &key.to_bytes(), FileDurability::Durable)?;
+            Ok(key)
+        }
+        Err(err) => Err(err),
+    }
+}
+
 impl<B: Backend> Deref for CustomServer<B> {
     type Target = AsyncStorage;
 
@@ -925,6 +1177,18 @@ impl<B: Backend> AsyncStorageConnection for CustomServer<B> {
         self.storage.delete_database(name).await
     }
 
+    async fn delete_databases(&self, names: &[&str]) -> Vec<Result<(), bonsaidb_core::Error>> {
+        self.storage.delete_databases(names).await
+    }
+
+    async fn set_database_quota(
+        &self,
+        name: &str,
+        quota: DatabaseQuota,
+    ) -> Result<(), bonsaidb_core::Error> {
+        self.storage.set_database_quota(name, quota).await
+    }
+
     async fn list_databases(&self) -> Result<Vec<connection::Database>, bonsaidb_core::Error> {
         self.storage.list_databases().await
     }
@@ -944,6 +1208,13 @@ impl<B: Backend> AsyncStorageConnection for CustomServer<B> {
         self.storage.delete_user(user).await
     }
 
+    async fn delete_users<'user, U: Nameable<'user, u64> + Clone + Send + Sync>(
+        &self,
+        users: &[U],
+    ) -> Vec<Result<(), bonsaidb_core::Error>> {
+        self.storage.delete_users(users).await
+    }
+
     #[cfg(feature = "password-hashing")]
     async fn set_user_password<'user, U: Nameable<'user, u64> + Send + Sync>(
         &self,
@@ -976,6 +1247,17 @@ impl<B: Backend> AsyncStorageConnection for CustomServer<B> {
         })
     }
 
+    async fn assume_identity_capped(
+        &self,
+        identity: IdentityReference<'_>,
+    ) -> Result<Self::Authenticated, bonsaidb_core::Error> {
+        let storage = self.storage.assume_identity_capped(identity).await?;
+        Ok(Self {
+            data: self.data.clone(),
+            storage,
+        })
+    }
+
     async fn add_permission_group_to_user<
         'user,
         'group,
@@ -1031,6 +1313,35 @@ impl<B: Backend> AsyncStorageConnection for CustomServer<B> {
     ) -> Result<(), bonsaidb_core::Error> {
         self.storage.remove_role_from_user(user, role).await
     }
+
+    async fn create_permission_group(
+        &self,
+        name: &str,
+        statements: Vec<Statement>,
+    ) -> Result<u64, bonsaidb_core::Error> {
+        self.storage.create_permission_group(name, statements).await
+    }
+
+    async fn update_permission_group_statements<'group, G: Nameable<'group, u64> + Send + Sync>(
+        &self,
+        permission_group: G,
+        statements: Vec<Statement>,
+    ) -> Result<(), bonsaidb_core::Error> {
+        self.storage
+            .update_permission_group_statements(permission_group, statements)
+            .await
+    }
+
+    async fn create_role(&self, name: &str, groups: Vec<u64>) -> Result<u64, bonsaidb_core::Error> {
+        self.storage.create_role(name, groups).await
+    }
+
+    async fn delete_role<'role, R: Nameable<'role, u64> + Send + Sync>(
+        &self,
+        role: R,
+    ) -> Result<(), bonsaidb_core::Error> {
+        self.storage.delete_role(role).await
+    }
 }
 
 #[derive(Default)]
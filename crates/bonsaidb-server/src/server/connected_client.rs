@@ -27,6 +27,9 @@ pub enum Transport {
     /// A connection over WebSockets.
     #[cfg(feature = "websockets")]
     WebSocket,
+    /// A client within the same process, created using
+    /// [`CustomServer::custom_api()`]. Its address is always `127.0.0.1:0`.
+    Local,
 }
 
 /// A connected database client.
@@ -87,6 +90,15 @@ impl<B: Backend> ConnectedClient<B> {
         );
     }
 
+    /// Replaces the session used for requests that don't specify a session.
+    #[cfg(feature = "token-authentication")]
+    pub(crate) fn set_connection_session(&self, session: Session) {
+        let mut sessions = self.data.sessions.write();
+        if let Some(client_session) = sessions.get_mut(&None) {
+            client_session.session = session;
+        }
+    }
+
     pub(crate) fn log_out(&self, session: SessionId) -> Option<Session> {
         let mut sessions = self.data.sessions.write();
         sessions.remove(&Some(session)).map(|cs| cs.session)
@@ -2,7 +2,8 @@ use std::ops::Deref;
 
 use async_trait::async_trait;
 use bonsaidb_core::connection::{
-    AccessPolicy, AsyncLowLevelConnection, HasSchema, HasSession, Range, SerializedQueryKey, Sort,
+    AccessPolicy, AsyncLowLevelConnection, DeletedDocuments, HasSchema, HasSession, Range,
+    SerializedQueryKey, Sort,
 };
 use bonsaidb_core::document::{DocumentId, Header, OwnedDocument};
 use bonsaidb_core::keyvalue::AsyncKeyValue;
@@ -113,6 +114,10 @@ impl<B: Backend> bonsaidb_core::connection::AsyncConnection for ServerDatabase<B
         self.db.last_transaction_id().await
     }
 
+    fn last_seen_transaction_id(&self) -> Option<u64> {
+        self.db.last_seen_transaction_id()
+    }
+
     async fn compact_collection<C: schema::Collection>(&self) -> Result<(), bonsaidb_core::Error> {
         self.db.compact_collection::<C>().await
     }
@@ -135,6 +140,10 @@ impl<B: Backend> AsyncKeyValue for ServerDatabase<B> {
     ) -> Result<bonsaidb_core::keyvalue::Output, bonsaidb_core::Error> {
         self.db.execute_key_operation(op).await
     }
+
+    async fn persist(&self) -> Result<(), bonsaidb_core::Error> {
+        self.db.persist().await
+    }
 }
 
 #[async_trait]
@@ -245,8 +254,11 @@ impl<B: Backend> AsyncLowLevelConnection for ServerDatabase<B> {
         view: &ViewName,
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, bonsaidb_core::Error> {
-        self.db.delete_docs_by_name(view, key, access_policy).await
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, bonsaidb_core::Error> {
+        self.db
+            .delete_docs_by_name(view, key, access_policy, include_headers)
+            .await
     }
 
     async fn apply_transaction(
@@ -0,0 +1,746 @@
+use std::collections::HashSet;
+use std::convert::Infallible;
+
+use async_trait::async_trait;
+use bonsaidb_core::arc_bytes::serde::Bytes;
+use bonsaidb_core::connection::{
+    AccessPolicy, AsyncLowLevelConnection, Bound, HasSchema, HasSession, Range, SerializedQueryKey,
+    Sort,
+};
+use bonsaidb_core::document::DocumentId;
+use bonsaidb_core::keyvalue::{AsyncKeyValue, KeyStatus, Numeric, Value};
+use bonsaidb_core::schema::view::map;
+use bonsaidb_core::schema::{CollectionName, DocumentFormat, ViewName};
+use bonsaidb_core::transaction::{OperationResult, Transaction};
+use bonsaidb_core::transmog::{Format, OwnedDeserializer};
+use bonsaidb_local::{AsyncDatabase, StorageNonBlocking};
+use derive_where::derive_where;
+use hyper::body::{HttpBody, Sender};
+use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use hyper::server::conn::Http;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::json;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{Backend, CustomServer, HttpService, NoBackend, Peer};
+
+/// The maximum number of view entries queried at once while streaming a view.
+const VIEW_PAGE_SIZE: u32 = 100;
+
+/// The maximum size of a request body when
+/// [`ServerConfiguration::max_payload_size`](crate::ServerConfiguration::max_payload_size)
+/// isn't set. This matches the default message size limit of `WebSocket`
+/// connections.
+const DEFAULT_MAX_BODY_SIZE: usize = 64 << 20;
+
+/// The header containing the hex-encoded signature of a request when request
+/// signing is enabled.
+const SIGNATURE_HEADER: &str = "x-bonsaidb-signature";
+
+impl<B: Backend> CustomServer<B> {
+    /// Handles `request` using the HTTP bridge, which exposes a subset of
+    /// this server's operations as JSON endpoints for clients that can't use
+    /// the BonsaiDb protocol. Requires feature `http`.
+    ///
+    /// | Endpoint | Description |
+    /// |----------|-------------|
+    /// | `GET /databases/{database}/collections/{collection}/documents/{id}` | Returns the document as `{"id", "revision", "contents"}`. |
+    /// | `PUT /databases/{database}/collections/{collection}/documents/{id}` | Stores the JSON body as the document's contents, overwriting the document if it exists. Returns `{"id", "revision"}`. |
+    /// | `GET /databases/{database}/views/{view}` | Streams the view's entries as newline-delimited JSON. |
+    /// | `GET /databases/{database}/kv/{key}` | Returns the key's value. |
+    /// | `PUT /databases/{database}/kv/{key}` | Sets the key to the JSON body. |
+    ///
+    /// Collections and views are identified by their encoded names, such as
+    /// `khonsulabs.basic.by-category`, and document ids use the string form
+    /// of [`DocumentId`]. Stored contents and values are converted to JSON
+    /// using [`DocumentFormat`]. Documents can only be written to collections
+    /// that declare a self-describing
+    /// [`document_format()`](bonsaidb_core::schema::Collection::document_format),
+    /// such as collections deriving `Collection` with `format = "json"`, and
+    /// are stored using that format. Other collections can't be written to,
+    /// because their documents can't be encoded without their Rust types.
+    /// Key-value endpoints accept a `namespace` query parameter, and JSON
+    /// numbers are stored as numeric values.
+    ///
+    /// Each line streamed from a view is an entry formatted as `{"key",
+    /// "value", "source"}`, where `key` is the hex-encoded key and `source` is
+    /// the id of the document that emitted the entry. By default, every entry
+    /// is streamed. When the `limit` query parameter is provided, a final
+    /// `{"cursor"}` line is streamed if more entries may remain. Passing the
+    /// cursor as the `after` query parameter continues with the entries
+    /// following it. Entries are paginated by key, which means all entries
+    /// sharing the last key are returned, even if this exceeds `limit`.
+    ///
+    /// Request bodies are limited to
+    /// [`ServerConfiguration::max_payload_size`](crate::ServerConfiguration::max_payload_size),
+    /// or 64 MiB if no limit is configured. If
+    /// [request signing](crate::RequestSigning) is enabled, each request must
+    /// include an `x-bonsaidb-signature` header containing the hex-encoded
+    /// result of
+    /// [`RequestSigningKey::sign_http_request()`](bonsaidb_core::networking::RequestSigningKey::sign_http_request),
+    /// and requests that don't are rejected with `401 Unauthorized`.
+    ///
+    /// Requests with an `Authorization: Basic` header are authenticated as
+    /// the user and password it contains, which requires feature
+    /// `password-hashing`. Each request is authenticated separately, and
+    /// other authorization schemes are rejected. Other requests are checked
+    /// against this instance's session if it has one, such as an instance
+    /// returned from
+    /// [`authenticate()`](bonsaidb_core::connection::AsyncStorageConnection::authenticate),
+    /// and against the server's default permissions otherwise. Errors are
+    /// returned as `{"error"}` with a matching status code.
+    pub async fn handle_http_request(&self, request: Request<Body>) -> Response<Body> {
+        match self.route_http_request(request).await {
+            Ok(response) => response,
+            Err(err) => err.into_response(),
+        }
+    }
+
+    async fn route_http_request(
+        &self,
+        request: Request<Body>,
+    ) -> Result<Response<Body>, HttpError> {
+        let (parts, body) = request.into_parts();
+        // The body is read before anything else so that the signature, which
+        // covers it, can be verified before the request is dispatched.
+        let body = self.read_http_body(body).await?;
+        if let Some(key) = self.request_signing_key() {
+            let signature = parts
+                .headers
+                .get(SIGNATURE_HEADER)
+                .and_then(|signature| signature.to_str().ok())
+                .and_then(|signature| decode_hex(signature).ok())
+                .ok_or(bonsaidb_core::Error::SignatureInvalid)?;
+            let path_and_query = parts
+                .uri
+                .path_and_query()
+                .map_or("/", |path_and_query| path_and_query.as_str());
+            key.verify_http_request(parts.method.as_str(), path_and_query, &body, &signature)?;
+        }
+
+        match self.authenticate_http_request(&parts.headers).await? {
+            Some(authenticated) => authenticated.dispatch_http_request(&parts, body).await,
+            None => self.dispatch_http_request(&parts, body).await,
+        }
+    }
+
+    async fn authenticate_http_request(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<Option<Self>, HttpError> {
+        let Some(authorization) = headers.get(AUTHORIZATION) else {
+            return Ok(None);
+        };
+
+        #[cfg(feature = "password-hashing")]
+        if let Some(credentials) = authorization
+            .to_str()
+            .ok()
+            .and_then(|authorization| authorization.strip_prefix("Basic "))
+        {
+            use base64::engine::general_purpose::STANDARD as BASE64;
+            use base64::Engine;
+            use bonsaidb_core::connection::{
+                AsyncStorageConnection, Authentication, SensitiveBytes, SensitiveString,
+            };
+
+            let invalid = || HttpError::unauthorized("invalid basic credentials");
+            // The decoded credentials contain the password, and are zeroed
+            // when dropped.
+            let credentials =
+                SensitiveBytes::from(BASE64.decode(credentials.trim()).map_err(|_| invalid())?);
+            let credentials: &[u8] = &credentials;
+            let separator = credentials
+                .iter()
+                .position(|&byte| byte == b':')
+                .ok_or_else(invalid)?;
+            let user = std::str::from_utf8(&credentials[..separator]).map_err(|_| invalid())?;
+            let password =
+                std::str::from_utf8(&credentials[separator + 1..]).map_err(|_| invalid())?;
+            let authentication = Authentication::password(user, SensitiveString::from(password))?;
+            return Ok(Some(self.authenticate(authentication).await?));
+        }
+
+        #[cfg(not(feature = "password-hashing"))]
+        let _ = authorization;
+        Err(HttpError::unauthorized("unsupported authorization scheme"))
+    }
+
+    async fn dispatch_http_request(
+        &self,
+        parts: &hyper::http::request::Parts,
+        body: Vec<u8>,
+    ) -> Result<Response<Body>, HttpError> {
+        let method = parts.method.clone();
+        let segments = parts
+            .uri
+            .path()
+            .trim_matches('/')
+            .split('/')
+            .map(percent_decode)
+            .collect::<Result<Vec<_>, _>>()?;
+        let query = parse_query(parts.uri.query())?;
+        let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+        let ["databases", database, endpoint @ ..] = segments.as_slice() else {
+            return Err(HttpError::not_found("unknown endpoint"));
+        };
+
+        match endpoint {
+            ["collections", collection, "documents", id] => {
+                let collection = collection
+                    .parse::<CollectionName>()
+                    .map_err(bonsaidb_core::Error::from)?;
+                let id = id.parse::<DocumentId>()?;
+                match method {
+                    Method::GET => {
+                        get_document(&self.http_database(database).await?, collection, id).await
+                    }
+                    Method::PUT => {
+                        put_document(&self.http_database(database).await?, collection, id, &body)
+                            .await
+                    }
+                    _ => Err(HttpError::method_not_allowed()),
+                }
+            }
+            ["views", view] => {
+                let view = view
+                    .parse::<ViewName>()
+                    .map_err(bonsaidb_core::Error::from)?;
+                if method != Method::GET {
+                    return Err(HttpError::method_not_allowed());
+                }
+                let limit = match parameter(&query, "limit") {
+                    Some(limit) => Some(
+                        limit
+                            .parse::<u32>()
+                            .map_err(|_| HttpError::bad_request("invalid limit"))?,
+                    ),
+                    None => None,
+                };
+                let after = parameter(&query, "after").map(decode_hex).transpose()?;
+                stream_view(self.http_database(database).await?, view, limit, after).await
+            }
+            ["kv", key] => {
+                let database = self.http_database(database).await?;
+                let namespace = parameter(&query, "namespace");
+                match method {
+                    Method::GET => {
+                        let value = match namespace {
+                            Some(namespace) => {
+                                database.with_key_namespace(namespace).get_key(*key).await?
+                            }
+                            None => database.get_key(*key).await?,
+                        };
+                        let value = value.ok_or_else(|| HttpError::not_found("key not found"))?;
+                        Ok(json_response(StatusCode::OK, &value_to_json(value)?))
+                    }
+                    Method::PUT => {
+                        let value = serde_json::from_slice(&body)?;
+                        let status = match namespace {
+                            Some(namespace) => {
+                                set_key(&database.with_key_namespace(namespace), key, &value)
+                                    .await?
+                            }
+                            None => set_key(&database, key, &value).await?,
+                        };
+                        Ok(empty_response(if status == KeyStatus::Inserted {
+                            StatusCode::CREATED
+                        } else {
+                            StatusCode::NO_CONTENT
+                        }))
+                    }
+                    _ => Err(HttpError::method_not_allowed()),
+                }
+            }
+            _ => Err(HttpError::not_found("unknown endpoint")),
+        }
+    }
+
+    /// Opens `name` using this instance's session, or the server's default
+    /// permissions if it has none.
+    async fn http_database(&self, name: &str) -> Result<AsyncDatabase, HttpError> {
+        let session = self
+            .session()
+            .cloned()
+            .unwrap_or_else(|| self.data.default_session.clone());
+        let storage = self.storage.assume_session(session)?;
+        Ok(storage.database_without_schema(name).await?)
+    }
+
+    async fn read_http_body(&self, mut body: Body) -> Result<Vec<u8>, HttpError> {
+        let max = self.max_payload_size().unwrap_or(DEFAULT_MAX_BODY_SIZE);
+        let mut contents = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(|err| HttpError::bad_request(err.to_string()))?;
+            if contents.len() + chunk.len() > max {
+                return Err(HttpError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "the request body is too large",
+                ));
+            }
+            contents.extend_from_slice(&chunk);
+        }
+        Ok(contents)
+    }
+}
+
+/// An [`HttpService`] that serves [`CustomServer::handle_http_request()`].
+/// Requires feature `http`.
+///
+/// If feature `websockets` is enabled, requests to upgrade to the `WebSocket`
+/// protocol are passed to [`CustomServer::upgrade_websocket()`], allowing
+/// BonsaiDb clients to connect to the same port.
+///
+/// ```rust,no_run
+/// # async fn example(server: bonsaidb_server::Server) -> Result<(), bonsaidb_server::Error> {
+/// use bonsaidb_server::HttpBridge;
+///
+/// server
+///     .listen_for_tcp_on("localhost:8080", HttpBridge::new(server.clone()))
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive_where(Debug, Clone)]
+pub struct HttpBridge<B: Backend = NoBackend> {
+    server: CustomServer<B>,
+}
+
+impl<B: Backend> HttpBridge<B> {
+    /// Returns a service that handles HTTP requests using `server`.
+    #[must_use]
+    pub const fn new(server: CustomServer<B>) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl<B: Backend> HttpService for HttpBridge<B> {
+    async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+        &self,
+        connection: S,
+        peer: &Peer,
+    ) -> Result<(), S> {
+        let server = self.server.clone();
+        #[cfg_attr(not(feature = "websockets"), allow(unused_variables))]
+        let address = peer.address;
+        let service = hyper::service::service_fn(move |request: Request<Body>| {
+            let server = server.clone();
+            async move {
+                #[cfg(feature = "websockets")]
+                if request.headers().contains_key(hyper::header::UPGRADE) {
+                    return Ok::<_, Infallible>(server.upgrade_websocket(address, request));
+                }
+
+                Ok::<_, Infallible>(server.handle_http_request(request).await)
+            }
+        });
+
+        if let Err(err) = Http::new()
+            .serve_connection(connection, service)
+            .with_upgrades()
+            .await
+        {
+            log::error!("[http] error serving {}: {:?}", peer.address, err);
+        }
+
+        Ok(())
+    }
+}
+
+async fn get_document(
+    database: &AsyncDatabase,
+    collection: CollectionName,
+    id: DocumentId,
+) -> Result<Response<Body>, HttpError> {
+    let document = database
+        .get_from_collection(id.clone(), &collection)
+        .await?
+        .ok_or_else(|| bonsaidb_core::Error::DocumentNotFound(collection, Box::new(id)))?;
+    Ok(json_response(
+        StatusCode::OK,
+        &json!({
+            "id": document.header.id.to_string(),
+            "revision": document.header.revision.to_string(),
+            "contents": decode_json(&document.contents)?,
+        }),
+    ))
+}
+
+async fn put_document(
+    database: &AsyncDatabase,
+    collection: CollectionName,
+    id: DocumentId,
+    contents: &[u8],
+) -> Result<Response<Body>, HttpError> {
+    let schematic = database.schematic();
+    if schematic
+        .collection_primary_key_description(&collection)
+        .is_none()
+    {
+        return Err(bonsaidb_core::Error::CollectionNotFound.into());
+    }
+    let format = schematic
+        .document_format_for_collection(&collection)
+        .filter(|format| format.format().is_self_describing())
+        .ok_or_else(|| {
+            HttpError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "this collection's documents can't be written from JSON",
+            )
+        })?;
+    let contents = serde_json::from_slice::<serde_json::Value>(contents)?;
+    let contents = format
+        .serialize(&contents)
+        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+    let results = database
+        .apply_transaction(Transaction::overwrite(collection, id, contents))
+        .await?;
+    let Some(OperationResult::DocumentUpdated { header, .. }) = results.into_iter().next() else {
+        return Err(HttpError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "the document was not updated",
+        ));
+    };
+    Ok(json_response(
+        StatusCode::OK,
+        &json!({
+            "id": header.id.to_string(),
+            "revision": header.revision.to_string(),
+        }),
+    ))
+}
+
+/// A page of view entries.
+struct ViewPage {
+    entries: Vec<map::Serialized>,
+    /// True if the view may contain entries after this page.
+    more: bool,
+}
+
+/// Queries up to `limit` entries of `view` with keys after `after`. If the
+/// limit is reached, the remaining entries sharing the last key are included.
+async fn view_page(
+    database: &AsyncDatabase,
+    view: &ViewName,
+    after: Option<&[u8]>,
+    limit: u32,
+) -> Result<ViewPage, bonsaidb_core::Error> {
+    let key = after.map(|after| {
+        SerializedQueryKey::Range(Range {
+            start: Bound::Excluded(Bytes::from(after.to_vec())),
+            end: Bound::Unbounded,
+        })
+    });
+    let mut entries = database
+        .query_by_name(
+            view,
+            key,
+            Sort::Ascending,
+            Some(limit),
+            AccessPolicy::UpdateBefore,
+        )
+        .await?;
+    let more = entries.len() >= limit as usize;
+    if let Some(last_key) = entries
+        .last()
+        .filter(|_| more)
+        .map(|entry| entry.key.clone())
+    {
+        let included = entries
+            .iter()
+            .rev()
+            .take_while(|entry| entry.key == last_key)
+            .map(|entry| entry.source.id.clone())
+            .collect::<HashSet<_>>();
+        let sharing_last_key = database
+            .query_by_name(
+                view,
+                Some(SerializedQueryKey::Matches(last_key)),
+                Sort::Ascending,
+                None,
+                AccessPolicy::NoUpdate,
+            )
+            .await?;
+        entries.extend(
+            sharing_last_key
+                .into_iter()
+                .filter(|entry| !included.contains(&entry.source.id)),
+        );
+    }
+
+    Ok(ViewPage { entries, more })
+}
+
+async fn stream_view(
+    database: AsyncDatabase,
+    view: ViewName,
+    limit: Option<u32>,
+    after: Option<Vec<u8>>,
+) -> Result<Response<Body>, HttpError> {
+    let page_size =
+        |remaining: Option<u32>| remaining.map_or(VIEW_PAGE_SIZE, |r| r.min(VIEW_PAGE_SIZE));
+    // The first page is queried before responding so that errors, such as
+    // the view not existing, are reported using the response's status.
+    let first_page = view_page(&database, &view, after.as_deref(), page_size(limit)).await?;
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut remaining = limit;
+        let mut page = first_page;
+        loop {
+            for entry in &page.entries {
+                let line = match decode_json(&entry.value) {
+                    Ok(value) => json!({
+                        "key": encode_hex(&entry.key),
+                        "value": value,
+                        "source": entry.source.id.to_string(),
+                    }),
+                    Err(err) => json!({ "error": err.message }),
+                };
+                if send_line(&mut sender, &line).await.is_err() {
+                    return;
+                }
+            }
+
+            let Some(cursor) = page
+                .entries
+                .last()
+                .filter(|_| page.more)
+                .map(|entry| entry.key.to_vec())
+            else {
+                break;
+            };
+            remaining = remaining.map(|remaining| {
+                remaining.saturating_sub(u32::try_from(page.entries.len()).unwrap_or(u32::MAX))
+            });
+            if remaining == Some(0) {
+                drop(send_line(&mut sender, &json!({ "cursor": encode_hex(&cursor) })).await);
+                break;
+            }
+
+            page = match view_page(&database, &view, Some(&cursor), page_size(remaining)).await {
+                Ok(page) => page,
+                Err(err) => {
+                    drop(send_line(&mut sender, &json!({ "error": err.to_string() })).await);
+                    break;
+                }
+            };
+        }
+    });
+
+    let mut response = Response::new(body);
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/x-ndjson"),
+    );
+    Ok(response)
+}
+
+async fn send_line(sender: &mut Sender, line: &serde_json::Value) -> Result<(), hyper::Error> {
+    let mut line = line.to_string().into_bytes();
+    line.push(b'\n');
+    sender.send_data(hyper::body::Bytes::from(line)).await
+}
+
+async fn set_key<K: AsyncKeyValue>(
+    kv: &K,
+    key: &str,
+    value: &serde_json::Value,
+) -> Result<KeyStatus, bonsaidb_core::Error> {
+    if let serde_json::Value::Number(number) = value {
+        let numeric = if let Some(value) = number.as_u64() {
+            Numeric::UnsignedInteger(value)
+        } else if let Some(value) = number.as_i64() {
+            Numeric::Integer(value)
+        } else {
+            Numeric::Float(number.as_f64().unwrap_or_default())
+        };
+        kv.set_numeric_key(key, numeric).await
+    } else {
+        kv.set_key(key, value).await
+    }
+}
+
+fn value_to_json(value: Value) -> Result<serde_json::Value, HttpError> {
+    Ok(match value {
+        Value::Bytes(bytes) => decode_json(&bytes)?,
+        Value::Numeric(Numeric::Integer(value)) => value.into(),
+        Value::Numeric(Numeric::UnsignedInteger(value)) => value.into(),
+        Value::Numeric(Numeric::Float(value)) => value.into(),
+        // JSON numbers are commonly parsed as 64-bit values, so larger values
+        // are returned as strings.
+        Value::Numeric(Numeric::Integer128(value)) => {
+            i64::try_from(value).map_or_else(|_| value.to_string().into(), Into::into)
+        }
+        Value::Numeric(Numeric::UnsignedInteger128(value)) => {
+            u64::try_from(value).map_or_else(|_| value.to_string().into(), Into::into)
+        }
+    })
+}
+
+fn decode_json(bytes: &[u8]) -> Result<serde_json::Value, HttpError> {
+    DocumentFormat::default()
+        .deserialize_owned(bytes)
+        .map_err(|err| {
+            HttpError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("the stored data can't be represented as JSON: {err}"),
+            )
+        })
+}
+
+fn json_response(status: StatusCode, value: &serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(value.to_string()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn parse_query(query: Option<&str>) -> Result<Vec<(String, String)>, HttpError> {
+    query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((percent_decode(name)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+fn parameter<'a>(query: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    query
+        .iter()
+        .find(|(parameter, _)| parameter == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn percent_decode(encoded: &str) -> Result<String, HttpError> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut bytes = encoded.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            match (
+                bytes.next().and_then(hex_digit),
+                bytes.next().and_then(hex_digit),
+            ) {
+                (Some(high), Some(low)) => decoded.push((high << 4) | low),
+                _ => return Err(HttpError::bad_request("invalid percent-encoding")),
+            }
+        } else {
+            decoded.push(byte);
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| HttpError::bad_request("invalid UTF-8 in request"))
+}
+
+fn decode_hex(encoded: &str) -> Result<Vec<u8>, HttpError> {
+    let encoded = encoded.as_bytes();
+    if encoded.len() % 2 != 0 {
+        return Err(HttpError::bad_request("invalid hex"));
+    }
+    encoded
+        .chunks(2)
+        .map(|pair| match (hex_digit(pair[0]), hex_digit(pair[1])) {
+            (Some(high), Some(low)) => Ok((high << 4) | low),
+            _ => Err(HttpError::bad_request("invalid hex")),
+        })
+        .collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut encoded = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        encoded.push(char::from(DIGITS[usize::from(byte >> 4)]));
+        encoded.push(char::from(DIGITS[usize::from(byte & 0xf)]));
+    }
+    encoded
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    char::from(digit)
+        .to_digit(16)
+        .and_then(|digit| u8::try_from(digit).ok())
+}
+
+/// An error returned from the HTTP bridge.
+struct HttpError {
+    status: StatusCode,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, message)
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    fn method_not_allowed() -> Self {
+        Self::new(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+    }
+
+    fn into_response(self) -> Response<Body> {
+        json_response(self.status, &json!({ "error": self.message }))
+    }
+}
+
+impl From<bonsaidb_core::Error> for HttpError {
+    fn from(err: bonsaidb_core::Error) -> Self {
+        let status = match &err {
+            bonsaidb_core::Error::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            bonsaidb_core::Error::SignatureInvalid | bonsaidb_core::Error::InvalidCredentials => {
+                StatusCode::UNAUTHORIZED
+            }
+            bonsaidb_core::Error::DatabaseNotFound(_)
+            | bonsaidb_core::Error::CollectionNotFound
+            | bonsaidb_core::Error::ViewNotFound
+            | bonsaidb_core::Error::DocumentNotFound(..) => StatusCode::NOT_FOUND,
+            bonsaidb_core::Error::InvalidDatabaseName(_)
+            | bonsaidb_core::Error::InvalidName(_)
+            | bonsaidb_core::Error::DocumentIdTooLong => StatusCode::BAD_REQUEST,
+            bonsaidb_core::Error::DocumentConflict(..)
+            | bonsaidb_core::Error::UniqueKeyViolation { .. } => StatusCode::CONFLICT,
+            bonsaidb_core::Error::TransactionTooLarge
+            | bonsaidb_core::Error::DocumentTooLarge { .. }
+            | bonsaidb_core::Error::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            bonsaidb_core::Error::Overloaded { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, err.to_string())
+    }
+}
+
+impl From<bonsaidb_local::Error> for HttpError {
+    fn from(err: bonsaidb_local::Error) -> Self {
+        Self::from(bonsaidb_core::Error::from(err))
+    }
+}
+
+impl From<serde_json::Error> for HttpError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::bad_request(format!("invalid JSON: {err}"))
+    }
+}
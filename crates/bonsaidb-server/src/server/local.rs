@@ -0,0 +1,97 @@
+use std::marker::PhantomData;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use bonsaidb_core::api;
+use bonsaidb_core::arc_bytes::serde::Bytes;
+use bonsaidb_core::connection::{HasSession, Session};
+use bonsaidb_local::StorageNonBlocking;
+use derive_where::derive_where;
+
+use crate::api::{HandlerError, HandlerSession};
+use crate::server::connected_client::OwnedClient;
+use crate::{Backend, CustomServer, Error, Transport};
+
+impl<B: Backend> CustomServer<B> {
+    /// Returns a client that sends `Api` requests to this server's handlers
+    /// without connecting over the network. Requests are dispatched exactly
+    /// as if they were received from a connected client, allowing the same
+    /// [`Handler`](crate::api::Handler) implementations to be used whether
+    /// the server is embedded or accessed remotely.
+    ///
+    /// Requests are checked against this instance's session if it has one,
+    /// such as an instance returned from
+    /// [`authenticate()`](bonsaidb_core::connection::AsyncStorageConnection::authenticate),
+    /// and against the server's default permissions otherwise.
+    ///
+    /// The client is connected using [`Transport::Local`], and it is
+    /// disconnected once it and all of its clones are dropped. Responses sent
+    /// to the client outside of a request, such as by
+    /// [`broadcast()`](Self::broadcast), are discarded.
+    pub async fn custom_api<Api: api::Api>(&self) -> Result<LocalApiClient<B, Api>, Error> {
+        // Responses sent outside of a request can't be delivered, so the
+        // receiver is dropped immediately.
+        let (sender, _) = flume::unbounded();
+        let client = self
+            .initialize_client(
+                Transport::Local,
+                SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+                sender,
+            )
+            .await
+            .ok_or_else(|| {
+                Error::Core(bonsaidb_core::Error::other(
+                    "bonsaidb-server",
+                    "the local client was not allowed to connect",
+                ))
+            })?;
+        let session = self
+            .session()
+            .cloned()
+            .unwrap_or_else(|| self.data.default_session.clone());
+        Ok(LocalApiClient {
+            server: self.clone(),
+            client: Arc::new(client),
+            session,
+            _api: PhantomData,
+        })
+    }
+}
+
+/// A client that invokes an [`Api`](api::Api) on a [`CustomServer`] within
+/// the same process. Returned from [`CustomServer::custom_api()`].
+#[derive_where(Debug, Clone)]
+pub struct LocalApiClient<B: Backend, Api> {
+    server: CustomServer<B>,
+    client: Arc<OwnedClient<B>>,
+    session: Session,
+    _api: PhantomData<Api>,
+}
+
+impl<B: Backend, Api: api::Api> LocalApiClient<B, Api> {
+    /// Sends `request` to the server's handler for `Api`, returning its
+    /// response.
+    ///
+    /// Unlike requests received over the network, requests sent using this
+    /// function are not required to be signed, even if the server is
+    /// configured with a
+    /// [`RequestSigningKey`](bonsaidb_core::networking::RequestSigningKey).
+    pub async fn send(&self, request: &Api) -> Result<Api::Response, HandlerError<Api::Error>> {
+        let request = Bytes::from(pot::to_vec(request)?);
+        let storage = self.server.storage.assume_session(self.session.clone())?;
+        let session = HandlerSession {
+            server: &self.server,
+            client: &self.client,
+            as_client: CustomServer {
+                data: self.server.data.clone(),
+                storage,
+            },
+            database: None,
+        };
+        let response = CustomServer::dispatch_request(session, &Api::name(), request).await?;
+        match pot::from_slice::<Result<Api::Response, Api::Error>>(&response)? {
+            Ok(response) => Ok(response),
+            Err(err) => Err(HandlerError::Api(err)),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::{hash_map, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bonsaidb_core::api::{Api, ApiName};
+use bonsaidb_core::arc_bytes::serde::Bytes;
+use bonsaidb_core::connection::{Identity, Session, SessionAuthentication, SessionId};
+use bonsaidb_core::keyvalue::Command;
+use bonsaidb_core::networking::{
+    ApplyTransaction, ApplyTransactionReturningId, DeleteDocs, DeleteDocsReturningHeaders,
+    ExecuteKeyOperation,
+};
+use bonsaidb_core::permissions::bonsai::{
+    collection_resource_name, document_resource_name, keyvalue_key_resource_name,
+    keyvalue_namespace_resource_name, view_resource_name, BonsaiAction, DatabaseAction,
+    DocumentAction, KeyValueAction, ViewAction,
+};
+use bonsaidb_core::transaction;
+use parking_lot::Mutex;
+use tokio::sync::OnceCell;
+
+pub type OperationResult = Result<Bytes, bonsaidb_core::Error>;
+pub type OperationCell = Arc<OnceCell<OperationResult>>;
+
+/// Tracks the results of
+/// [`IdempotentRequest`](bonsaidb_core::networking::IdempotentRequest)s so
+/// that retried operations are only executed once.
+#[derive(Debug)]
+pub struct Operations {
+    window: Duration,
+    limit: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    results: HashMap<OperationKey, (Instant, OperationCell)>,
+    started: VecDeque<(Instant, OperationKey)>,
+}
+
+/// Identifies an operation. Operation ids are chosen by clients, so they are
+/// only unique within the identity and database that executed them.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct OperationKey {
+    owner: OperationOwner,
+    database: String,
+    operation_id: u64,
+}
+
+impl OperationKey {
+    pub fn new(session: Option<&Session>, database: &str, operation_id: u64) -> Self {
+        let owner = match session.map(|session| (&session.authentication, session.id)) {
+            Some((SessionAuthentication::Identity(identity), _)) => {
+                OperationOwner::Identity(identity.clone())
+            }
+            Some((_, id)) => OperationOwner::Session(id),
+            None => OperationOwner::Session(None),
+        };
+        Self {
+            owner,
+            database: database.to_string(),
+            operation_id,
+        }
+    }
+}
+
+/// The owner of an operation. Operations executed by an authenticated
+/// identity can be retried from any of its sessions, which allows retrying
+/// after reconnecting. Unauthenticated operations can only be retried from the
+/// same session.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+enum OperationOwner {
+    Identity(Arc<Identity>),
+    Session(Option<SessionId>),
+}
+
+/// A request that can be executed as an
+/// [`IdempotentRequest`](bonsaidb_core::networking::IdempotentRequest).
+///
+/// Only requests whose permissions can be checked without executing them are
+/// supported, because the permissions are checked again each time a result is
+/// returned for a retried operation.
+pub enum IdempotentOperation {
+    Transaction(ApplyTransaction),
+    DeleteDocs(DeleteDocs),
+    KeyValue(ExecuteKeyOperation),
+}
+
+impl IdempotentOperation {
+    pub fn parse(name: &ApiName, request: &[u8]) -> Result<Self, bonsaidb_core::Error> {
+        if *name == ApplyTransaction::name() {
+            Ok(Self::Transaction(pot::from_slice(request)?))
+        } else if *name == ApplyTransactionReturningId::name() {
+            Ok(Self::Transaction(
+                pot::from_slice::<ApplyTransactionReturningId>(request)?.0,
+            ))
+        } else if *name == DeleteDocs::name() {
+            Ok(Self::DeleteDocs(pot::from_slice(request)?))
+        } else if *name == DeleteDocsReturningHeaders::name() {
+            Ok(Self::DeleteDocs(
+                pot::from_slice::<DeleteDocsReturningHeaders>(request)?.0,
+            ))
+        } else if *name == ExecuteKeyOperation::name() {
+            Ok(Self::KeyValue(pot::from_slice(request)?))
+        } else {
+            Err(bonsaidb_core::Error::other(
+                "bonsaidb-server",
+                format!("{name} can't be executed idempotently"),
+            ))
+        }
+    }
+
+    pub fn database(&self) -> &str {
+        match self {
+            Self::Transaction(request) => &request.database,
+            Self::DeleteDocs(request) => &request.database,
+            Self::KeyValue(request) => &request.database,
+        }
+    }
+
+    /// Checks that `session` is allowed to execute this operation. A
+    /// [`DeleteDocs`] or [`DeleteDocsReturningHeaders`] request requires permission to delete every document in
+    /// the view's collection, because the documents it deleted aren't known.
+    pub fn check_permission(&self, session: &Session) -> Result<(), bonsaidb_core::Error> {
+        match self {
+            Self::Transaction(request) => {
+                for op in &request.transaction.operations {
+                    let (resource, action) = match &op.command {
+                        transaction::Command::Insert { .. } => (
+                            collection_resource_name(&request.database, &op.collection),
+                            DocumentAction::Insert,
+                        ),
+                        transaction::Command::Update { header, .. } => (
+                            document_resource_name(&request.database, &op.collection, &header.id),
+                            DocumentAction::Update,
+                        ),
+                        transaction::Command::Overwrite { id, .. } => (
+                            document_resource_name(&request.database, &op.collection, id),
+                            DocumentAction::Overwrite,
+                        ),
+                        transaction::Command::Delete { header } => (
+                            document_resource_name(&request.database, &op.collection, &header.id),
+                            DocumentAction::Delete,
+                        ),
+                        transaction::Command::Check { id, .. } => (
+                            document_resource_name(&request.database, &op.collection, id),
+                            DocumentAction::Get,
+                        ),
+                    };
+                    session.check_permission(
+                        resource,
+                        &BonsaiAction::Database(DatabaseAction::Document(action)),
+                    )?;
+                }
+                Ok(())
+            }
+            Self::DeleteDocs(request) => {
+                session.check_permission(
+                    view_resource_name(&request.database, &request.view),
+                    &BonsaiAction::Database(DatabaseAction::View(ViewAction::Query)),
+                )?;
+                session.check_permission(
+                    collection_resource_name(&request.database, &request.view.collection),
+                    &BonsaiAction::Database(DatabaseAction::Document(DocumentAction::Delete)),
+                )
+            }
+            Self::KeyValue(request) => {
+                let op = &request.op;
+                let resource = if matches!(op.command, Command::Scan(_)) {
+                    keyvalue_namespace_resource_name(&request.database, op.namespace.as_deref())
+                } else {
+                    keyvalue_key_resource_name(&request.database, op.namespace.as_deref(), &op.key)
+                };
+                let action = BonsaiAction::Database(DatabaseAction::KeyValue(
+                    KeyValueAction::ExecuteOperation,
+                ));
+                session.check_permission(resource, &action)?;
+                if let Command::Swap {
+                    other_namespace,
+                    other_key,
+                } = &op.command
+                {
+                    session.check_permission(
+                        keyvalue_key_resource_name(
+                            &request.database,
+                            other_namespace.as_deref(),
+                            other_key,
+                        ),
+                        &action,
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Operations {
+    pub fn new(window: Duration, limit: usize) -> Self {
+        Self {
+            window,
+            limit,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns the cell containing the result of the operation identified by
+    /// `key`. If the operation is still executing, initializing the returned
+    /// cell waits for the original execution to complete.
+    ///
+    /// Results older than the window are forgotten, as are the oldest results
+    /// once `limit` results are being remembered.
+    pub fn operation(&self, key: &OperationKey) -> OperationCell {
+        let mut state = self.state.lock();
+        let State { results, started } = &mut *state;
+
+        let now = Instant::now();
+        while let Some((started_at, _)) = started.front() {
+            if now.duration_since(*started_at) < self.window && results.len() < self.limit {
+                break;
+            }
+            let (started_at, key) = started.pop_front().expect("just checked");
+            if let hash_map::Entry::Occupied(entry) = results.entry(key) {
+                if entry.get().0 == started_at {
+                    entry.remove();
+                }
+            }
+        }
+
+        match results.entry(key.clone()) {
+            hash_map::Entry::Occupied(entry) => entry.get().1.clone(),
+            hash_map::Entry::Vacant(entry) => {
+                started.push_back((now, key.clone()));
+                entry.insert((now, OperationCell::default())).1.clone()
+            }
+        }
+    }
+
+    /// Forgets the operation identified by `key` if `result` indicates that it
+    /// failed. Failed operations don't modify any data, which allows them to be
+    /// executed again when retried.
+    pub fn completed(&self, key: &OperationKey, cell: &OperationCell, result: &OperationResult) {
+        let succeeded = match result {
+            Ok(response) => pot::from_slice::<Result<pot::Value<'_>, pot::Value<'_>>>(response)
+                .map_or(false, |response| response.is_ok()),
+            Err(_) => false,
+        };
+        if !succeeded {
+            let mut state = self.state.lock();
+            if let hash_map::Entry::Occupied(entry) = state.results.entry(key.clone()) {
+                if Arc::ptr_eq(&entry.get().1, cell) {
+                    entry.remove();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use bonsaidb_core::api::ApiName;
+
+/// The number of histogram buckets tracked for each [`Api`](bonsaidb_core::api::Api).
+const BUCKETS: usize = 24;
+
+/// A snapshot of the time spent handling requests for a single
+/// [`Api`](bonsaidb_core::api::Api). Returned from
+/// [`CustomServer::request_statistics()`](crate::CustomServer::request_statistics).
+///
+/// Durations are grouped into buckets whose upper bounds double, starting at
+/// 1 microsecond. The final bucket contains all requests that took longer than
+/// the previous bucket's upper bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestTimings {
+    /// The number of requests that have been handled.
+    pub count: u64,
+    /// The total time spent handling requests.
+    pub total: Duration,
+    /// The longest time spent handling a single request.
+    pub max: Duration,
+    buckets: [u64; BUCKETS],
+}
+
+impl RequestTimings {
+    /// Returns the average time spent handling a request, or None if no
+    /// requests have been handled.
+    #[must_use]
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count).unwrap_or(u32::MAX);
+        (self.count > 0).then(|| self.total / count)
+    }
+
+    /// Returns an iterator over the histogram's buckets. Each entry contains
+    /// the bucket's upper bound and the number of requests that completed
+    /// within it. The last bucket's upper bound is [`Duration::MAX`].
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(index, count)| (bucket_upper_bound(index), *count))
+    }
+
+    /// Returns the upper bound of the bucket containing the `percentile`th
+    /// fastest request, or None if no requests have been handled.
+    /// `percentile` is clamped to `0.0..=1.0`.
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let target = ((self.count as f64 * percentile.clamp(0., 1.)).ceil() as u64).max(1);
+        let mut seen = 0;
+        self.buckets()
+            .find(|(_, count)| {
+                seen += count;
+                seen >= target
+            })
+            .map(|(upper_bound, _)| upper_bound)
+    }
+}
+
+fn bucket_upper_bound(index: usize) -> Duration {
+    if index + 1 == BUCKETS {
+        Duration::MAX
+    } else {
+        Duration::from_micros(1 << index)
+    }
+}
+
+fn bucket_for(duration: Duration) -> usize {
+    let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+    let micros = nanos.saturating_add(999) / 1000;
+    // The first bucket covers everything up to 1us, and each following bucket
+    // covers up to double the previous bucket's bound.
+    let index = (u64::BITS - micros.saturating_sub(1).leading_zeros()) as usize;
+    index.min(BUCKETS - 1)
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl Histogram {
+    fn record(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+        self.buckets[bucket_for(duration)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> RequestTimings {
+        RequestTimings {
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed)),
+            buckets: std::array::from_fn(|index| self.buckets[index].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Tracks how long each registered [`Api`](bonsaidb_core::api::Api) takes to
+/// handle requests.
+///
+/// A histogram is allocated for every registered `Api` when the server is
+/// opened, which allows recording a request to be done using only atomic
+/// operations.
+#[derive(Debug)]
+pub struct RequestTimingRegistry {
+    histograms: HashMap<ApiName, Histogram>,
+}
+
+impl RequestTimingRegistry {
+    pub fn new<'a>(apis: impl IntoIterator<Item = &'a ApiName>) -> Self {
+        Self {
+            histograms: apis
+                .into_iter()
+                .map(|name| (name.clone(), Histogram::default()))
+                .collect(),
+        }
+    }
+
+    pub fn record(&self, name: &ApiName, duration: Duration) {
+        if let Some(histogram) = self.histograms.get(name) {
+            histogram.record(duration);
+        }
+        #[cfg(feature = "instrument")]
+        tracing::trace!(
+            api = %name,
+            duration_us = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX),
+            "request handled"
+        );
+    }
+
+    pub fn timings(&self, name: &ApiName) -> Option<RequestTimings> {
+        self.histograms.get(name).map(Histogram::snapshot)
+    }
+
+    pub fn snapshot(&self) -> HashMap<ApiName, RequestTimings> {
+        self.histograms
+            .iter()
+            .map(|(name, histogram)| (name.clone(), histogram.snapshot()))
+            .collect()
+    }
+}
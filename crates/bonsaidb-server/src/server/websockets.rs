@@ -1,6 +1,7 @@
-use bonsaidb_core::networking::{Payload, CURRENT_PROTOCOL_VERSION};
+use bonsaidb_core::networking::{negotiate_protocol_version, Payload};
 use futures::{SinkExt, StreamExt};
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use tokio_tungstenite::tungstenite::Message;
 
 use crate::server::connected_client::OwnedClient;
@@ -28,7 +29,12 @@ impl<B: Backend> CustomServer<B> {
         connection: S,
         peer_address: std::net::SocketAddr,
     ) -> Result<(), Error> {
-        let stream = tokio_tungstenite::accept_hdr_async(connection, VersionChecker).await?;
+        let stream = tokio_tungstenite::accept_hdr_async_with_config(
+            connection,
+            VersionChecker(self.protocol_versions()),
+            Some(self.websocket_config()),
+        )
+        .await?;
         self.handle_websocket(stream, peer_address).await;
         Ok(())
     }
@@ -65,7 +71,12 @@ impl<B: Backend> CustomServer<B> {
         tokio::spawn(async move {
             match hyper::upgrade::on(&mut request).await {
                 Ok(upgraded) => {
-                    let ws = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                    let ws = WebSocketStream::from_raw_socket(
+                        upgraded,
+                        Role::Server,
+                        Some(task_self.websocket_config()),
+                    )
+                    .await;
                     task_self.handle_websocket(ws, peer_address).await;
                 }
                 Err(err) => {
@@ -89,6 +100,16 @@ impl<B: Backend> CustomServer<B> {
         response
     }
 
+    fn websocket_config(&self) -> WebSocketConfig {
+        let mut config = WebSocketConfig::default();
+        // Without a configured limit, tungstenite's default limits still apply.
+        if let Some(limit) = self.max_payload_size() {
+            config.max_message_size = Some(limit);
+            config.max_frame_size = Some(limit);
+        }
+        config
+    }
+
     /// Handles an established `tokio-tungstenite` `WebSocket` stream.
     pub async fn handle_websocket<
         S: futures::Stream<Item = Result<tokio_tungstenite::tungstenite::Message, E>>
@@ -236,7 +257,7 @@ fn compute_websocket_accept_header(key: &[u8]) -> hyper::header::HeaderValue {
     hyper::header::HeaderValue::from_str(&encoded).expect("base64 is a valid value")
 }
 
-struct VersionChecker;
+struct VersionChecker(&'static [&'static str]);
 
 impl tokio_tungstenite::tungstenite::handshake::server::Callback for VersionChecker {
     fn on_request(
@@ -249,14 +270,13 @@ impl tokio_tungstenite::tungstenite::handshake::server::Callback for VersionChec
     > {
         if let Some(protocols) = request.headers().get("Sec-WebSocket-Protocol") {
             if let Ok(protocols) = protocols.to_str() {
-                for protocol in protocols.split(',').map(str::trim) {
-                    if protocol == CURRENT_PROTOCOL_VERSION {
-                        response.headers_mut().insert(
-                            "Sec-WebSocket-Protocol",
-                            CURRENT_PROTOCOL_VERSION.try_into().unwrap(),
-                        );
-                        return Ok(response);
-                    }
+                if let Some(protocol) =
+                    negotiate_protocol_version(self.0, protocols.split(',').map(str::trim))
+                {
+                    response
+                        .headers_mut()
+                        .insert("Sec-WebSocket-Protocol", protocol.try_into().unwrap());
+                    return Ok(response);
                 }
             }
         }
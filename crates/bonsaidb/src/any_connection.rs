@@ -1,10 +1,13 @@
 use bonsaidb_client::{AsyncClient, AsyncRemoteDatabase};
+use bonsaidb_core::admin::DatabaseQuota;
 use bonsaidb_core::async_trait::async_trait;
 use bonsaidb_core::connection::{
     self, AccessPolicy, AsyncConnection, AsyncLowLevelConnection, AsyncStorageConnection,
-    HasSchema, HasSession, IdentityReference, Range, SerializedQueryKey, Session, Sort,
+    DeletedDocuments, HasSchema, HasSession, IdentityReference, Range, SerializedQueryKey, Session,
+    Sort,
 };
 use bonsaidb_core::document::{DocumentId, Header, OwnedDocument};
+use bonsaidb_core::permissions::Statement;
 use bonsaidb_core::schema::view::map::MappedSerializedValue;
 use bonsaidb_core::schema::{
     self, Collection, CollectionName, Nameable, Schema, SchemaName, SchemaSummary, Schematic,
@@ -84,6 +87,24 @@ impl<B: Backend> AsyncStorageConnection for AnyServerConnection<B> {
         }
     }
 
+    async fn delete_databases(&self, names: &[&str]) -> Vec<Result<(), bonsaidb_core::Error>> {
+        match self {
+            Self::Local(server) => server.delete_databases(names).await,
+            Self::Networked(client) => client.delete_databases(names).await,
+        }
+    }
+
+    async fn set_database_quota(
+        &self,
+        name: &str,
+        quota: DatabaseQuota,
+    ) -> Result<(), bonsaidb_core::Error> {
+        match self {
+            Self::Local(server) => server.set_database_quota(name, quota).await,
+            Self::Networked(client) => client.set_database_quota(name, quota).await,
+        }
+    }
+
     async fn list_databases(&self) -> Result<Vec<connection::Database>, bonsaidb_core::Error> {
         match self {
             Self::Local(server) => server.list_databases().await,
@@ -115,6 +136,16 @@ impl<B: Backend> AsyncStorageConnection for AnyServerConnection<B> {
         }
     }
 
+    async fn delete_users<'user, U: Nameable<'user, u64> + Clone + Send + Sync>(
+        &self,
+        users: &[U],
+    ) -> Vec<Result<(), bonsaidb_core::Error>> {
+        match self {
+            Self::Local(server) => server.delete_users(users).await,
+            Self::Networked(client) => client.delete_users(users).await,
+        }
+    }
+
     #[cfg(feature = "password-hashing")]
     async fn set_user_password<'user, U: Nameable<'user, u64> + Send + Sync>(
         &self,
@@ -151,6 +182,22 @@ impl<B: Backend> AsyncStorageConnection for AnyServerConnection<B> {
         }
     }
 
+    async fn assume_identity_capped(
+        &self,
+        identity: IdentityReference<'_>,
+    ) -> Result<Self::Authenticated, bonsaidb_core::Error> {
+        match self {
+            Self::Local(server) => server
+                .assume_identity_capped(identity)
+                .await
+                .map(Self::Local),
+            Self::Networked(client) => client
+                .assume_identity_capped(identity)
+                .await
+                .map(Self::Networked),
+        }
+    }
+
     async fn add_permission_group_to_user<
         'user,
         'group,
@@ -230,6 +277,53 @@ impl<B: Backend> AsyncStorageConnection for AnyServerConnection<B> {
             Self::Networked(client) => client.remove_role_from_user(user, role).await,
         }
     }
+
+    async fn create_permission_group(
+        &self,
+        name: &str,
+        statements: Vec<Statement>,
+    ) -> Result<u64, bonsaidb_core::Error> {
+        match self {
+            Self::Local(server) => server.create_permission_group(name, statements).await,
+            Self::Networked(client) => client.create_permission_group(name, statements).await,
+        }
+    }
+
+    async fn update_permission_group_statements<'group, G: Nameable<'group, u64> + Send + Sync>(
+        &self,
+        permission_group: G,
+        statements: Vec<Statement>,
+    ) -> Result<(), bonsaidb_core::Error> {
+        match self {
+            Self::Local(server) => {
+                server
+                    .update_permission_group_statements(permission_group, statements)
+                    .await
+            }
+            Self::Networked(client) => {
+                client
+                    .update_permission_group_statements(permission_group, statements)
+                    .await
+            }
+        }
+    }
+
+    async fn create_role(&self, name: &str, groups: Vec<u64>) -> Result<u64, bonsaidb_core::Error> {
+        match self {
+            Self::Local(server) => server.create_role(name, groups).await,
+            Self::Networked(client) => client.create_role(name, groups).await,
+        }
+    }
+
+    async fn delete_role<'role, R: Nameable<'role, u64> + Send + Sync>(
+        &self,
+        role: R,
+    ) -> Result<(), bonsaidb_core::Error> {
+        match self {
+            Self::Local(server) => server.delete_role(role).await,
+            Self::Networked(client) => client.delete_role(role).await,
+        }
+    }
 }
 
 /// A database connection that can be either from a local server or a server
@@ -288,6 +382,13 @@ impl<B: Backend> AsyncConnection for AnyDatabase<B> {
         }
     }
 
+    fn last_seen_transaction_id(&self) -> Option<u64> {
+        match self {
+            Self::Local(server) => server.last_seen_transaction_id(),
+            Self::Networked(client) => client.last_seen_transaction_id(),
+        }
+    }
+
     async fn compact_collection<C: Collection>(&self) -> Result<(), bonsaidb_core::Error> {
         match self {
             Self::Local(server) => server.compact_collection::<C>().await,
@@ -488,10 +589,19 @@ impl<B: Backend> AsyncLowLevelConnection for AnyDatabase<B> {
         view: &ViewName,
         key: Option<SerializedQueryKey>,
         access_policy: AccessPolicy,
-    ) -> Result<u64, bonsaidb_core::Error> {
+        include_headers: bool,
+    ) -> Result<DeletedDocuments, bonsaidb_core::Error> {
         match self {
-            Self::Local(server) => server.delete_docs_by_name(view, key, access_policy).await,
-            Self::Networked(client) => client.delete_docs_by_name(view, key, access_policy).await,
+            Self::Local(server) => {
+                server
+                    .delete_docs_by_name(view, key, access_policy, include_headers)
+                    .await
+            }
+            Self::Networked(client) => {
+                client
+                    .delete_docs_by_name(view, key, access_policy, include_headers)
+                    .await
+            }
         }
     }
 }
@@ -1,15 +1,21 @@
 //! Tests invoking an API defined in a custom backend.
 
+use std::collections::BTreeMap;
+
 use bonsaidb::client::url::Url;
-use bonsaidb::client::AsyncClient;
+use bonsaidb::client::{ApiError, AsyncClient};
 use bonsaidb::core::api::{Api, Infallible};
 use bonsaidb::core::async_trait::async_trait;
+use bonsaidb::core::connection::{AsyncStorageConnection, HasSession, IdentityReference, Session};
+use bonsaidb::core::networking::{Capability, RequestSigningKey};
+use bonsaidb::core::permissions::Permissions;
 use bonsaidb::core::test_util::{Basic, TestDirectory};
 use bonsaidb::local::config::Builder;
+use bonsaidb::local::{ExternalIdentityResolver, ResolvedIdentity};
 use bonsaidb::server::api::Handler;
 use bonsaidb::server::{Backend, CustomServer, DefaultPermissions, ServerConfiguration};
 use bonsaidb_core::api::ApiName;
-use bonsaidb_core::schema::Qualified;
+use bonsaidb_core::schema::{Qualified, SerializedCollection};
 use bonsaidb_server::api::{HandlerResult, HandlerSession};
 use serde::{Deserialize, Serialize};
 
@@ -51,6 +57,261 @@ async fn custom_api() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn local_custom_api() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("local_custom_api.bonsaidb");
+    let server = CustomServer::<CustomBackend>::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_api::<SetValueHandler, _>()?
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+
+    let client = server.custom_api::<SetValue>().await?;
+    assert_eq!(client.send(&SetValue { new_value: 1 }).await?, None);
+    assert_eq!(client.send(&SetValue { new_value: 2 }).await?, Some(1));
+
+    // Each local client is a separate connected client.
+    let other_client = server.custom_api::<SetValue>().await?;
+    assert_eq!(other_client.send(&SetValue { new_value: 3 }).await?, None);
+    assert_eq!(client.send(&SetValue { new_value: 4 }).await?, Some(2));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn signed_custom_api() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("signed_custom_api.bonsaidb");
+    let server = CustomServer::<CustomBackend>::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .request_signing_key(RequestSigningKey::new(b"shared secret"))
+            .with_api::<SetValueHandler, _>()?
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    tokio::spawn(async move { server.listen_on(12347).await });
+
+    let url = Url::parse("bonsaidb://localhost:12347")?;
+    let client = AsyncClient::build(url.clone())
+        .with_api::<SetValue>()
+        .with_certificate(certificate.clone())
+        .with_request_signing_key(RequestSigningKey::new(b"shared secret"))
+        .build()?;
+    let old_value = client.send_api_request(&SetValue { new_value: 1 }).await?;
+    assert_eq!(old_value, None);
+
+    // Requests signed using a different key are indistinguishable from
+    // requests that were tampered with after being signed.
+    let tampering_client = AsyncClient::build(url.clone())
+        .with_api::<SetValue>()
+        .with_certificate(certificate.clone())
+        .with_request_signing_key(RequestSigningKey::new(b"other secret"))
+        .build()?;
+    assert!(matches!(
+        tampering_client
+            .send_api_request(&SetValue { new_value: 2 })
+            .await,
+        Err(ApiError::Client(bonsaidb::client::Error::Core(
+            bonsaidb::core::Error::SignatureInvalid
+        )))
+    ));
+
+    // The server reports that it requires signed requests when connecting.
+    let unsigned_client = AsyncClient::build(url)
+        .with_api::<SetValue>()
+        .with_certificate(certificate)
+        .build()?;
+    assert!(matches!(
+        unsigned_client
+            .send_api_request(&SetValue { new_value: 2 })
+            .await,
+        Err(ApiError::Client(bonsaidb::client::Error::RequestSigningRequired))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn signing_client_with_unsigned_server() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("signing_client_with_unsigned_server.bonsaidb");
+    let server = CustomServer::<CustomBackend>::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_api::<SetValueHandler, _>()?
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    tokio::spawn(async move { server.listen_on(12368).await });
+
+    // The server doesn't verify signatures, so the connection is rejected
+    // before any signed request is sent.
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:12368")?)
+        .with_api::<SetValue>()
+        .with_certificate(certificate)
+        .with_request_signing_key(RequestSigningKey::new(b"shared secret"))
+        .build()?;
+    match client.send_api_request(&SetValue { new_value: 1 }).await {
+        Err(ApiError::Client(bonsaidb::client::Error::MissingCapabilities(missing))) => {
+            assert_eq!(missing, vec![Capability::REQUEST_SIGNING]);
+        }
+        other => unreachable!("expected missing capabilities, got {other:?}"),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn generated_request_signing_key() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("generated_request_signing_key.bonsaidb");
+    let configuration = || -> anyhow::Result<ServerConfiguration<CustomBackend>> {
+        Ok(ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .generate_request_signing_key()
+            .with_api::<SetValueHandler, _>()?
+            .with_schema::<Basic>()?)
+    };
+    let server = CustomServer::<CustomBackend>::open(configuration()?).await?;
+    let key = server.request_signing_key().unwrap().to_bytes();
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    server.shutdown(None).await?;
+    drop(server);
+
+    // The generated key is reused when the storage is opened again.
+    let server = CustomServer::<CustomBackend>::open(configuration()?).await?;
+    assert_eq!(server.request_signing_key().unwrap().to_bytes(), key);
+    tokio::spawn(async move { server.listen_on(12361).await });
+
+    let url = Url::parse("bonsaidb://localhost:12361")?;
+    let client = AsyncClient::build(url.clone())
+        .with_api::<SetValue>()
+        .with_certificate(certificate.clone())
+        .with_request_signing_key(RequestSigningKey::from_bytes(key))
+        .build()?;
+    let old_value = client.send_api_request(&SetValue { new_value: 1 }).await?;
+    assert_eq!(old_value, None);
+
+    // The server reports that it requires signed requests when connecting.
+    let unsigned_client = AsyncClient::build(url)
+        .with_api::<SetValue>()
+        .with_certificate(certificate)
+        .build()?;
+    assert!(matches!(
+        unsigned_client
+            .send_api_request(&SetValue { new_value: 2 })
+            .await,
+        Err(ApiError::Client(bonsaidb::client::Error::RequestSigningRequired))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn database_scoped_custom_api() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("database_scoped_custom_api.bonsaidb");
+    let server = CustomServer::<CustomBackend>::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_api::<CountBasicHandler, _>()?
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    server.create_database::<Basic>("first", false).await?;
+    server.create_database::<Basic>("second", false).await?;
+    let first = server.database::<Basic>("first").await?;
+    Basic::new("a").push_into_async(&first).await?;
+    Basic::new("b").push_into_async(&first).await?;
+    tokio::spawn(async move { server.listen_on(12350).await });
+
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:12350")?)
+        .with_api::<CountBasic>()
+        .with_certificate(certificate)
+        .build()?;
+
+    assert_eq!(client.send_api_request(&CountBasic).await?, None);
+    assert_eq!(
+        client.send_api_request_to("first", &CountBasic).await?,
+        Some(2)
+    );
+    assert_eq!(
+        client.send_api_request_to("second", &CountBasic).await?,
+        Some(0)
+    );
+    assert!(matches!(
+        client.send_api_request_to("missing", &CountBasic).await,
+        Err(ApiError::Client(bonsaidb::client::Error::Core(
+            bonsaidb::core::Error::DatabaseNotFound(_)
+        )))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn external_identity_claims() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("external_identity_claims.bonsaidb");
+    let server = CustomServer::<CustomBackend>::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .external_identities(OidcResolver)
+            .with_api::<ClaimsHandler, _>()?
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    tokio::spawn(async move { server.listen_on(12360).await });
+
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:12360")?)
+        .with_api::<Claims>()
+        .with_certificate(certificate)
+        .build()?;
+    assert_eq!(client.send_api_request(&Claims).await?, None);
+
+    let assumed = client
+        .assume_identity(IdentityReference::external("oidc", OIDC_TOKEN.as_bytes()))
+        .await?;
+    let claims = OidcResolver::claims();
+    assert_eq!(assumed.session().and_then(Session::claims), Some(&claims));
+    assert_eq!(assumed.send_api_request(&Claims).await?, Some(claims));
+
+    // Credentials the resolver can't verify, and providers it doesn't
+    // recognize, can't be assumed.
+    for (provider, credential) in [("oidc", "forged token"), ("other", OIDC_TOKEN)] {
+        assert!(matches!(
+            client
+                .assume_identity(IdentityReference::external(provider, credential.as_bytes()))
+                .await,
+            Err(bonsaidb::core::Error::InvalidCredentials)
+        ));
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct SetValue {
     new_value: u64,
@@ -79,3 +340,97 @@ impl Handler<SetValue, CustomBackend> for SetValueHandler {
         Ok(existing_value)
     }
 }
+
+/// The only token [`OidcResolver`] accepts, standing in for a signed ID token.
+const OIDC_TOKEN: &str = "issued-to-ecton";
+
+/// Verifies tokens issued by `oidc`, granting no additional permissions.
+#[derive(Debug)]
+struct OidcResolver;
+
+impl OidcResolver {
+    fn claims() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            (String::from("email"), String::from("ecton@example.com")),
+            (String::from("org"), String::from("khonsulabs")),
+        ])
+    }
+}
+
+impl ExternalIdentityResolver for OidcResolver {
+    fn resolve(
+        &self,
+        provider: &str,
+        credential: &[u8],
+    ) -> Result<ResolvedIdentity, bonsaidb::core::Error> {
+        if provider == "oidc" && credential == OIDC_TOKEN.as_bytes() {
+            Ok(ResolvedIdentity {
+                subject: String::from("ecton"),
+                claims: Self::claims(),
+                permissions: Permissions::default(),
+            })
+        } else {
+            Err(bonsaidb::core::Error::InvalidCredentials)
+        }
+    }
+}
+
+/// Returns the claims of the external identity the client is authenticated
+/// as.
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims;
+
+impl Api for Claims {
+    type Error = Infallible;
+    type Response = Option<BTreeMap<String, String>>;
+
+    fn name() -> ApiName {
+        ApiName::private("claims")
+    }
+}
+
+#[derive(Debug)]
+struct ClaimsHandler;
+
+#[async_trait]
+impl Handler<Claims, CustomBackend> for ClaimsHandler {
+    async fn handle(
+        session: HandlerSession<'_, CustomBackend>,
+        _request: Claims,
+    ) -> HandlerResult<Claims> {
+        Ok(session
+            .as_client
+            .session()
+            .and_then(Session::claims)
+            .cloned())
+    }
+}
+
+/// Counts the [`Basic`] documents in the database the request is sent to.
+#[derive(Debug, Serialize, Deserialize)]
+struct CountBasic;
+
+impl Api for CountBasic {
+    type Error = Infallible;
+    type Response = Option<u64>;
+
+    fn name() -> ApiName {
+        ApiName::private("count-basic")
+    }
+}
+
+#[derive(Debug)]
+struct CountBasicHandler;
+
+#[async_trait]
+impl Handler<CountBasic, CustomBackend> for CountBasicHandler {
+    async fn handle(
+        session: HandlerSession<'_, CustomBackend>,
+        _request: CountBasic,
+    ) -> HandlerResult<CountBasic> {
+        match &session.database {
+            Some(database) => Ok(Some(Basic::all_async(database).count().await?)),
+            None => Ok(None),
+        }
+    }
+}
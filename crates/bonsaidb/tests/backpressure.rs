@@ -0,0 +1,83 @@
+//! Tests limiting the number of requests a client has in flight.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::AsyncClient;
+use bonsaidb::core::api::Api;
+use bonsaidb::core::async_trait::async_trait;
+use bonsaidb::core::test_util::{Basic, TestDirectory};
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::api::{Handler, HandlerResult, HandlerSession};
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+use serde::{Deserialize, Serialize};
+
+const SLOW_CALL_DURATION: Duration = Duration::from_millis(200);
+
+#[derive(Api, Debug, Serialize, Deserialize, Clone)]
+#[api(name = "slow-call")]
+struct SlowCall;
+
+#[async_trait]
+impl Handler<SlowCall> for SlowCall {
+    async fn handle(_session: HandlerSession<'_>, _request: SlowCall) -> HandlerResult<SlowCall> {
+        tokio::time::sleep(SLOW_CALL_DURATION).await;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn requests_wait_for_in_flight_limit() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("backpressure.bonsaidb");
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Basic>()?
+            .with_api::<SlowCall, SlowCall>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    tokio::spawn(async move { server.listen_on(12355).await });
+
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:12355")?)
+        .with_certificate(certificate)
+        .with_max_in_flight_requests(2)
+        .build()?;
+    // Establish the connection before flooding it.
+    client.send_api_request(&SlowCall).await?;
+    assert_eq!(client.in_flight_requests(), 0);
+
+    let most_in_flight = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+    let requests = (0..10)
+        .map(|_| {
+            let client = client.clone();
+            let most_in_flight = most_in_flight.clone();
+            tokio::spawn(async move {
+                let result = client.send_api_request(&SlowCall).await;
+                most_in_flight.fetch_max(client.in_flight_requests(), Ordering::SeqCst);
+                result
+            })
+        })
+        .collect::<Vec<_>>();
+    while start.elapsed() < SLOW_CALL_DURATION * 2 {
+        most_in_flight.fetch_max(client.in_flight_requests(), Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    for request in requests {
+        request.await??;
+    }
+
+    // Only two of the ten requests could be waiting on the server at a time.
+    assert!(start.elapsed() >= SLOW_CALL_DURATION * 5);
+    assert_eq!(most_in_flight.load(Ordering::SeqCst), 2);
+    assert_eq!(client.in_flight_requests(), 0);
+
+    Ok(())
+}
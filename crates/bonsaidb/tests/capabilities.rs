@@ -0,0 +1,51 @@
+//! Tests requiring server capabilities when connecting.
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::{ApiError, AsyncClient, Error};
+use bonsaidb::core::connection::AsyncStorageConnection;
+use bonsaidb::core::networking::{Capability, ListDatabases};
+use bonsaidb::core::test_util::{Basic, TestDirectory};
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+
+#[tokio::test]
+async fn required_capabilities() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("capabilities.bonsaidb");
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    let expected_capabilities = server.capabilities();
+    tokio::spawn(async move { server.listen_on(12351).await });
+
+    let url = Url::parse("bonsaidb://localhost:12351")?;
+    let client = AsyncClient::build(url.clone())
+        .with_certificate(certificate.clone())
+        .build()?;
+    assert_eq!(client.capabilities(), None);
+    client.list_databases().await?;
+    assert_eq!(client.capabilities(), Some(expected_capabilities));
+
+    // The server has no request signing key configured.
+    let unsupported = Capability::new("unsupported");
+    let client = AsyncClient::build(url)
+        .with_certificate(certificate)
+        .with_required_capability(Capability::REQUEST_SIGNING)
+        .with_required_capability(unsupported.clone())
+        .build()?;
+    match client.send_api_request(&ListDatabases).await {
+        Err(ApiError::Client(Error::MissingCapabilities(missing))) => {
+            assert_eq!(missing, vec![Capability::REQUEST_SIGNING, unsupported]);
+        }
+        other => unreachable!("expected missing capabilities, got {other:?}"),
+    }
+
+    Ok(())
+}
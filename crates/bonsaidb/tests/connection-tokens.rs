@@ -0,0 +1,92 @@
+//! Tests authenticating connections with a token while they are established.
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::AsyncClient;
+use bonsaidb::core::admin::AuthenticationToken;
+use bonsaidb::core::connection::{
+    AsyncStorageConnection, AuthenticationMethod, IdentityReference, SensitiveString,
+};
+use bonsaidb::core::permissions::bonsai::{BonsaiAction, ServerAction};
+use bonsaidb::core::permissions::{Permissions, Statement};
+use bonsaidb::core::test_util::{Basic, TestDirectory};
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+
+#[tokio::test]
+async fn connection_tokens() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("connection-tokens.bonsaidb");
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(Permissions::from(
+                Statement::for_any()
+                    .allowing(&BonsaiAction::Server(ServerAction::Connect))
+                    .allowing(&BonsaiAction::Server(ServerAction::Authenticate(
+                        AuthenticationMethod::Token,
+                    ))),
+            ))
+            .authenticated_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    server.create_user("token-user").await?;
+    let token = AuthenticationToken::create_async(
+        IdentityReference::user("token-user")?,
+        &server.admin().await,
+    )
+    .await?;
+    tokio::spawn(async move { server.listen_on(12352).await });
+
+    let url = Url::parse("bonsaidb://localhost:12352")?;
+
+    // Without a token, requests use the default permissions.
+    let client = AsyncClient::build(url.clone())
+        .with_certificate(certificate.clone())
+        .build()?;
+    assert!(matches!(
+        client.list_databases().await,
+        Err(bonsaidb::core::Error::PermissionDenied(_))
+    ));
+    assert!(client.connection_session().is_none());
+
+    let client = AsyncClient::build(url.clone())
+        .with_certificate(certificate.clone())
+        .with_token(token.header.id, token.contents.token.clone())
+        .build()?;
+    client.list_databases().await?;
+    let session = client
+        .connection_session()
+        .expect("connection not authenticated");
+    assert!(session.identity().is_some());
+
+    // The token can be provided in the url instead.
+    let mut with_credentials = url.clone();
+    with_credentials
+        .set_username(&token.header.id.to_string())
+        .unwrap();
+    with_credentials
+        .set_password(Some(&token.contents.token))
+        .unwrap();
+    let client = AsyncClient::build(with_credentials)
+        .with_certificate(certificate.clone())
+        .build()?;
+    client.list_databases().await?;
+    assert!(client.connection_session().is_some());
+
+    // An invalid token prevents connecting.
+    let client = AsyncClient::build(url)
+        .with_certificate(certificate)
+        .with_token(token.header.id, SensitiveString::from("invalid"))
+        .build()?;
+    assert!(matches!(
+        client.list_databases().await,
+        Err(bonsaidb::core::Error::InvalidCredentials)
+    ));
+    assert!(client.connection_session().is_none());
+
+    Ok(())
+}
@@ -21,6 +21,13 @@ use rand::Rng;
 use tokio::sync::Mutex;
 
 const INCOMPATIBLE_PROTOCOL_VERSION: &str = "otherprotocol";
+/// Simulates a server that is newer than its clients: it prefers a protocol
+/// version that older clients don't know about, but it still supports the
+/// current one.
+const UPGRADED_SERVER_PROTOCOL_VERSIONS: &[&str] = &[
+    "bonsai-future",
+    bonsaidb::core::networking::CURRENT_PROTOCOL_VERSION,
+];
 
 async fn initialize_shared_server() -> Certificate {
     static CERTIFICATE: Lazy<Mutex<Option<Certificate>>> = Lazy::new(|| Mutex::new(None));
@@ -187,7 +194,7 @@ mod websockets {
         let url = Url::parse("ws://localhost:6001")?;
         let client = AsyncClient::build(url.clone())
             .with_certificate(certificate.clone())
-            .with_protocol_version(INCOMPATIBLE_PROTOCOL_VERSION)
+            .with_protocol_versions(&[INCOMPATIBLE_PROTOCOL_VERSION])
             .build()?;
 
         check_incompatible_client(client).await
@@ -272,7 +279,7 @@ mod bonsai {
         ))?;
         let client = AsyncClient::build(url.clone())
             .with_certificate(certificate.clone())
-            .with_protocol_version(INCOMPATIBLE_PROTOCOL_VERSION)
+            .with_protocol_versions(&[INCOMPATIBLE_PROTOCOL_VERSION])
             .build()?;
 
         check_incompatible_client(client).await
@@ -306,6 +313,15 @@ async fn check_incompatible_client(client: AsyncClient) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn check_negotiated_client(client: AsyncClient) -> anyhow::Result<()> {
+    use bonsaidb_core::connection::AsyncStorageConnection;
+    // Any response from the server proves the connection was established
+    // using a mutually supported protocol version.
+    client.list_available_schemas().await?;
+
+    Ok(())
+}
+
 #[allow(dead_code)] // We will want this in the future but it's currently unused
 async fn assume_permissions(
     connection: AsyncClient,
@@ -334,6 +350,7 @@ async fn assume_permissions(
             let administrator_group_id = match (PermissionGroup {
                 name: String::from(label),
                 statements,
+                inherits: Vec::new(),
             }
             .push_into_async(&admin)
             .await)
@@ -426,6 +443,59 @@ async fn authenticated_permissions_test() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn older_client_negotiates_version() -> anyhow::Result<()> {
+    let database_path = TestDirectory::new("older-client-negotiates-version");
+    let server = Server::open(
+        ServerConfiguration::new(&database_path)
+            .default_permissions(Permissions::allow_all())
+            .with_protocol_versions(UPGRADED_SERVER_PROTOCOL_VERSIONS),
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+
+    #[cfg(feature = "websockets")]
+    tokio::spawn({
+        let server = server.clone();
+        async move {
+            server
+                .listen_for_websockets_on("localhost:6005", false)
+                .await?;
+            Result::<(), anyhow::Error>::Ok(())
+        }
+    });
+    tokio::spawn(async move {
+        server.listen_on(6004).await?;
+        Result::<(), anyhow::Error>::Ok(())
+    });
+    // Give the server time to listen
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // The client only knows about the protocol version the server falls back
+    // to.
+    let older_versions = &[bonsaidb::core::networking::CURRENT_PROTOCOL_VERSION];
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:6004")?)
+        .with_certificate(certificate.clone())
+        .with_protocol_versions(older_versions)
+        .build()?;
+    check_negotiated_client(client).await?;
+
+    #[cfg(feature = "websockets")]
+    {
+        let client = AsyncClient::build(Url::parse("ws://localhost:6005")?)
+            .with_certificate(certificate)
+            .with_protocol_versions(older_versions)
+            .build()?;
+        check_negotiated_client(client).await?;
+    }
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn client_disconnection() -> anyhow::Result<()> {
     use bonsaidb_core::connection::AsyncStorageConnection;
@@ -0,0 +1,343 @@
+//! Tests the server's HTTP bridge.
+
+use std::time::Duration;
+
+use bonsaidb::core::connection::AsyncStorageConnection;
+use bonsaidb::core::document::DocumentId;
+use bonsaidb::core::keyvalue::AsyncKeyValue;
+use bonsaidb::core::networking::RequestSigningKey;
+use bonsaidb::core::schema::{Collection, Qualified, SerializedCollection, View};
+use bonsaidb::core::test_util::{Basic, BasicByCategory, TestDirectory};
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::{DefaultPermissions, HttpBridge, Server, ServerConfiguration};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Collection)]
+#[collection(name = "json-documents", format = "json")]
+struct JsonDocument {
+    value: String,
+}
+
+/// Sends a `GET` request for `path`, returning the response's status code and
+/// body.
+async fn get(port: u16, path: &str) -> anyhow::Result<(u16, String)> {
+    request(port, "GET", path, &[], "").await
+}
+
+/// Sends a request, returning the response's status code and body.
+async fn request(
+    port: u16,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> anyhow::Result<(u16, String)> {
+    let mut attempts = 0;
+    let mut stream = loop {
+        match TcpStream::connect(("localhost", port)).await {
+            Ok(stream) => break stream,
+            // The server may not be listening yet.
+            Err(_) if attempts < 50 => {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+    let mut head = format!(
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n",
+        body.len()
+    );
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .expect("invalid HTTP response");
+    let status = head
+        .split(' ')
+        .nth(1)
+        .expect("missing status code")
+        .parse()?;
+    Ok((status, body.to_string()))
+}
+
+#[tokio::test]
+async fn get_document_as_json() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("http.bonsaidb");
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.create_database::<Basic>("tests", false).await?;
+    let db = server.database::<Basic>("tests").await?;
+    let doc = Basic::new("over http")
+        .with_category("json")
+        .push_into_async(&db)
+        .await?;
+
+    let listening = server.clone();
+    tokio::spawn(async move {
+        listening
+            .listen_for_tcp_on("localhost:12359", HttpBridge::new(listening.clone()))
+            .await
+    });
+
+    let documents = format!(
+        "/databases/tests/collections/{}/documents",
+        Basic::collection_name().encoded()
+    );
+    let id = DocumentId::new(&doc.header.id)?;
+    let (status, body) = get(12359, &format!("{documents}/{id}")).await?;
+    assert_eq!(status, 200);
+    let body = serde_json::from_str::<serde_json::Value>(&body)?;
+    assert_eq!(body["id"], id.to_string());
+    assert_eq!(body["contents"]["value"], "over http");
+    assert_eq!(body["contents"]["category"], "json");
+
+    let missing = DocumentId::new(&(doc.header.id + 1))?;
+    let (status, body) = get(12359, &format!("{documents}/{missing}")).await?;
+    assert_eq!(status, 404);
+    assert!(serde_json::from_str::<serde_json::Value>(&body)?["error"].is_string());
+
+    let (status, _) = get(12359, "/databases/missing/kv/key").await?;
+    assert_eq!(status, 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn write_and_stream_over_http() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("http-writes.bonsaidb");
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Basic>()?
+            .with_schema::<JsonDocument>()?,
+    )
+    .await?;
+    server.create_database::<Basic>("tests", false).await?;
+    server
+        .create_database::<JsonDocument>("json", false)
+        .await?;
+    let db = server.database::<Basic>("tests").await?;
+    for (value, category) in [
+        ("a", "first"),
+        ("b", "second"),
+        ("c", "second"),
+        ("d", "third"),
+    ] {
+        Basic::new(value)
+            .with_category(category)
+            .push_into_async(&db)
+            .await?;
+    }
+
+    let listening = server.clone();
+    tokio::spawn(async move {
+        listening
+            .listen_for_tcp_on("localhost:12366", HttpBridge::new(listening.clone()))
+            .await
+    });
+
+    // Documents are written using the collection's format, and can be read
+    // back using its Rust type.
+    let path = format!(
+        "/databases/json/collections/{}/documents/{}",
+        JsonDocument::collection_name().encoded(),
+        DocumentId::new(&1_u64)?
+    );
+    let (status, _) = request(12366, "PUT", &path, &[], r#"{"value":"over http"}"#).await?;
+    assert_eq!(status, 200);
+    let (status, body) = get(12366, &path).await?;
+    assert_eq!(status, 200);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body)?["contents"]["value"],
+        "over http"
+    );
+    let json = server.database::<JsonDocument>("json").await?;
+    let doc = JsonDocument::get_async(&1_u64, &json).await?.unwrap();
+    assert_eq!(doc.contents.value, "over http");
+
+    // Collections without a declared format can't be written to.
+    let path = format!(
+        "/databases/tests/collections/{}/documents/{}",
+        Basic::collection_name().encoded(),
+        DocumentId::new(&100_u64)?
+    );
+    let (status, _) = request(12366, "PUT", &path, &[], r#"{"value":"rejected"}"#).await?;
+    assert_eq!(status, 415);
+
+    // Key-value entries can be written and read.
+    let (status, _) = request(12366, "PUT", "/databases/tests/kv/key", &[], r#""value""#).await?;
+    assert_eq!(status, 201);
+    let (status, _) = request(
+        12366,
+        "PUT",
+        "/databases/tests/kv/count?namespace=n",
+        &[],
+        "1",
+    )
+    .await?;
+    assert_eq!(status, 201);
+    let (status, body) = get(12366, "/databases/tests/kv/key").await?;
+    assert_eq!(status, 200);
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&body)?, "value");
+    let (_, body) = get(12366, "/databases/tests/kv/count?namespace=n").await?;
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&body)?, 1);
+    assert_eq!(
+        db.with_key_namespace("n")
+            .get_key("count")
+            .into_u64()
+            .await?,
+        Some(1)
+    );
+
+    // Views are streamed as newline-delimited JSON, and can be paginated.
+    let view = format!("/databases/tests/views/{:#}", BasicByCategory.view_name());
+    let (status, body) = get(12366, &view).await?;
+    assert_eq!(status, 200);
+    let lines = parse_lines(&body)?;
+    assert_eq!(lines.len(), 4);
+    assert!(lines.iter().all(|line| line.get("cursor").is_none()));
+
+    let (_, body) = get(12366, &format!("{view}?limit=2")).await?;
+    let lines = parse_lines(&body)?;
+    // Both entries sharing the last key are returned, followed by the cursor.
+    assert_eq!(lines.len(), 4);
+    let cursor = lines[3]["cursor"].as_str().unwrap().to_string();
+    let (_, body) = get(12366, &format!("{view}?limit=2&after={cursor}")).await?;
+    let lines = parse_lines(&body)?;
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["source"], "4");
+
+    Ok(())
+}
+
+fn parse_lines(body: &str) -> anyhow::Result<Vec<serde_json::Value>> {
+    body.lines()
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+#[tokio::test]
+async fn signed_and_authenticated_requests() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("http-auth.bonsaidb");
+    let key = RequestSigningKey::new(b"shared secret");
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .authenticated_permissions(DefaultPermissions::AllowAll)
+            .request_signing_key(key.clone())
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.create_database::<Basic>("tests", false).await?;
+
+    let listening = server.clone();
+    tokio::spawn(async move {
+        listening
+            .listen_for_tcp_on("localhost:12367", HttpBridge::new(listening.clone()))
+            .await
+    });
+
+    let sign = |method: &str, path: &str, body: &str| {
+        key.sign_http_request(method, path, body.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    };
+
+    // Unsigned requests and requests whose signature doesn't match are
+    // rejected before they are dispatched.
+    let (status, _) = request(12367, "PUT", "/databases/tests/kv/key", &[], "1").await?;
+    assert_eq!(status, 401);
+    let signature = sign("PUT", "/databases/tests/kv/key", "1");
+    let (status, _) = request(
+        12367,
+        "PUT",
+        "/databases/tests/kv/key",
+        &[("x-bonsaidb-signature", &signature)],
+        "2",
+    )
+    .await?;
+    assert_eq!(status, 401);
+    let db = server.database::<Basic>("tests").await?;
+    assert!(db.get_key("key").await?.is_none());
+
+    let (status, _) = request(
+        12367,
+        "PUT",
+        "/databases/tests/kv/key",
+        &[("x-bonsaidb-signature", &signature)],
+        "1",
+    )
+    .await?;
+    assert_eq!(status, 201);
+
+    // Unsupported authorization schemes are rejected.
+    let signature = sign("GET", "/databases/tests/kv/key", "");
+    let (status, _) = request(
+        12367,
+        "GET",
+        "/databases/tests/kv/key",
+        &[
+            ("x-bonsaidb-signature", &signature),
+            ("authorization", "Bearer token"),
+        ],
+        "",
+    )
+    .await?;
+    assert_eq!(status, 401);
+
+    #[cfg(feature = "password-hashing")]
+    {
+        use base64::Engine;
+        let basic = |credentials: &str| {
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(credentials)
+            )
+        };
+        let user_id = server.create_user("http-user").await?;
+        server.set_user_password(user_id, "password".into()).await?;
+
+        let (status, _) = request(
+            12367,
+            "GET",
+            "/databases/tests/kv/key",
+            &[
+                ("x-bonsaidb-signature", &signature),
+                ("authorization", &basic("http-user:wrong")),
+            ],
+            "",
+        )
+        .await?;
+        assert_eq!(status, 401);
+        let (status, body) = request(
+            12367,
+            "GET",
+            "/databases/tests/kv/key",
+            &[
+                ("x-bonsaidb-signature", &signature),
+                ("authorization", &basic("http-user:password")),
+            ],
+            "",
+        )
+        .await?;
+        assert_eq!(status, 200);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&body)?, 1);
+    }
+
+    Ok(())
+}
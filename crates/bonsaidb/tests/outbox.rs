@@ -0,0 +1,165 @@
+//! Tests journaling writes to a client's outbox and replaying them.
+
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::{AsyncClient, OUTBOX_NAMESPACE};
+use bonsaidb::core::connection::{AsyncConnection, AsyncStorageConnection};
+use bonsaidb::core::keyvalue::KeyValue;
+use bonsaidb::core::schema::SerializedCollection;
+use bonsaidb::core::test_util::{Basic, TestDirectory};
+use bonsaidb::local::config::{Builder, StorageConfiguration};
+use bonsaidb::local::Database;
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+
+/// Waits until `replayed` returns true, panicking if it takes too long.
+async fn wait_for_replay(mut replayed: impl FnMut() -> bool) {
+    for _ in 0..100 {
+        if replayed() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("outbox was not replayed");
+}
+
+/// Returns true once the journal at `path` no longer contains any pending
+/// operations.
+fn journal_is_empty(path: &std::path::Path) -> bool {
+    std::fs::metadata(path).map_or(false, |metadata| metadata.len() == 0)
+}
+
+#[tokio::test]
+async fn outbox_replays_after_restart() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("outbox-server.bonsaidb");
+    let client_dir = TestDirectory::new("outbox-client");
+    std::fs::create_dir_all(&client_dir)?;
+    let outbox = client_dir.as_ref().join("outbox");
+
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    server.create_database::<Basic>("tests", false).await?;
+    tokio::spawn(async move { server.listen_on(12348).await });
+
+    let online = AsyncClient::build(Url::parse("bonsaidb://localhost:12348")?)
+        .with_certificate(certificate.clone())
+        .build()?;
+    let online_db = online.database::<Basic>("tests").await?;
+
+    // Writes made while the server can't be reached stay in the outbox.
+    let udp = UdpSocket::bind("0.0.0.0:0")?;
+    let offline_port = udp.local_addr()?.port();
+    let offline = AsyncClient::build(Url::parse(&format!("bonsaidb://localhost:{offline_port}"))?)
+        .with_connect_timeout(Duration::from_secs(1))
+        .with_outbox(&outbox)
+        .build()?;
+    let offline_db = offline.database::<Basic>("tests").await?;
+    assert!(Basic::new("queued")
+        .push_into_async(&offline_db)
+        .await
+        .is_err());
+    assert!(Basic::new("conflicting")
+        .insert_into_async(&42_u64, &offline_db)
+        .await
+        .is_err());
+    // Reads are never journaled.
+    assert!(Basic::get_async(&1_u64, &offline_db).await.is_err());
+    drop(offline_db);
+    drop(offline);
+
+    // Another client creates the document the queued insert will conflict
+    // with.
+    Basic::new("existing")
+        .insert_into_async(&42_u64, &online_db)
+        .await?;
+    let journal = std::fs::read(&outbox)?;
+
+    // The outbox is replayed as soon as the client is created.
+    let conflicts = Arc::new(Mutex::new(Vec::new()));
+    let replaying = AsyncClient::build(Url::parse("bonsaidb://localhost:12348")?)
+        .with_certificate(certificate.clone())
+        .with_outbox(&outbox)
+        .with_outbox_conflict_callback({
+            let conflicts = conflicts.clone();
+            move |conflict| conflicts.lock().unwrap().push(conflict)
+        })
+        .build()?;
+    wait_for_replay(|| journal_is_empty(&outbox)).await;
+    // Replaying again does nothing once the server has acknowledged the
+    // operations.
+    assert_eq!(replaying.flush_outbox().await?, 0);
+    drop(replaying);
+
+    let conflicts = std::mem::take(&mut *conflicts.lock().unwrap());
+    assert_eq!(conflicts.len(), 1);
+    assert!(matches!(
+        conflicts[0].error,
+        bonsaidb::core::Error::DocumentConflict(..)
+    ));
+    let documents = Basic::all_async(&online_db).await?;
+    assert_eq!(documents.len(), 2);
+    assert!(documents
+        .iter()
+        .any(|document| document.contents.value == "queued"));
+
+    // If the acknowledgement is lost, replaying the operations again doesn't
+    // execute the write twice.
+    std::fs::write(&outbox, journal)?;
+    let replaying = AsyncClient::build(Url::parse("bonsaidb://localhost:12348")?)
+        .with_certificate(certificate.clone())
+        .with_outbox(&outbox)
+        .with_outbox_conflict_callback(|_| {})
+        .build()?;
+    wait_for_replay(|| journal_is_empty(&outbox)).await;
+    drop(replaying);
+    assert_eq!(Basic::all_async(&online_db).await?.len(), 2);
+
+    // An outbox can also be stored in a local database.
+    let local = Database::open::<Basic>(StorageConfiguration::new(
+        client_dir.as_ref().join("outbox.bonsaidb"),
+    ))?;
+    let offline = AsyncClient::build(Url::parse(&format!("bonsaidb://localhost:{offline_port}"))?)
+        .with_connect_timeout(Duration::from_secs(1))
+        .with_outbox_store(local.clone())
+        .build()?;
+    let offline_db = offline.database::<Basic>("tests").await?;
+    assert!(Basic::new("stored locally")
+        .push_into_async(&offline_db)
+        .await
+        .is_err());
+    drop(offline_db);
+    drop(offline);
+    let queued = || {
+        local
+            .with_key_namespace(OUTBOX_NAMESPACE)
+            .scan_keys("")
+            .query()
+            .unwrap()
+            .len()
+    };
+    assert_eq!(queued(), 1);
+
+    let replaying = AsyncClient::build(Url::parse("bonsaidb://localhost:12348")?)
+        .with_certificate(certificate)
+        .with_outbox_store(local.clone())
+        .build()?;
+    wait_for_replay(|| queued() == 0).await;
+    drop(replaying);
+    assert!(Basic::all_async(&online_db)
+        .await?
+        .iter()
+        .any(|document| document.contents.value == "stored locally"));
+
+    Ok(())
+}
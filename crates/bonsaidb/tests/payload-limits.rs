@@ -0,0 +1,65 @@
+//! Tests rejecting requests whose payloads exceed the server's limit.
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::AsyncClient;
+use bonsaidb::core::connection::AsyncStorageConnection;
+use bonsaidb::core::keyvalue::AsyncKeyValue;
+use bonsaidb::core::test_util::{Basic, TestDirectory};
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+
+const LIMIT: usize = 1024;
+
+#[tokio::test]
+async fn payload_too_large() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("payload-limits.bonsaidb");
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .max_payload_size(LIMIT)
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    server.create_database::<Basic>("tests", false).await?;
+    #[cfg(feature = "websockets")]
+    tokio::spawn({
+        let server = server.clone();
+        async move {
+            server
+                .listen_for_websockets_on("localhost:12363", false)
+                .await
+        }
+    });
+    tokio::spawn(async move { server.listen_on(12362).await });
+
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:12362")?)
+        .with_certificate(certificate)
+        .build()?;
+    let db = client.database::<Basic>("tests").await?;
+    db.set_key("small", &"a".repeat(16)).await?;
+    match db.set_key("large", &"a".repeat(LIMIT * 4)).await {
+        Err(bonsaidb::core::Error::PayloadTooLarge { size, limit }) => {
+            assert!(size > LIMIT as u64);
+            assert_eq!(limit, LIMIT as u64);
+        }
+        other => unreachable!("expected payload too large, got {other:?}"),
+    }
+    // The connection remains usable.
+    db.set_key("small", &"b".repeat(16)).await?;
+
+    // WebSocket connections reject the oversized message while receiving it.
+    #[cfg(feature = "websockets")]
+    {
+        let client = AsyncClient::build(Url::parse("ws://localhost:12363")?).build()?;
+        let db = client.database::<Basic>("tests").await?;
+        db.set_key("small", &"c".repeat(16)).await?;
+        assert!(db.set_key("large", &"a".repeat(LIMIT * 4)).await.is_err());
+    }
+
+    Ok(())
+}
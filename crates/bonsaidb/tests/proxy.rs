@@ -0,0 +1,160 @@
+//! Tests connecting WebSocket clients through proxies.
+
+#![cfg(feature = "websockets")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::{AsyncClient, ProxyConfig};
+use bonsaidb::core::connection::{AsyncStorageConnection, SensitiveString};
+use bonsaidb::core::schema::SerializedCollection;
+use bonsaidb::core::test_util::{Basic, TestDirectory};
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Opens a server listening for WebSocket connections on `port`.
+async fn open_server(dir: &TestDirectory, port: u16) -> anyhow::Result<Server> {
+    let server = Server::open(
+        ServerConfiguration::new(dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.create_database::<Basic>("tests", false).await?;
+    let task_server = server.clone();
+    tokio::spawn(async move {
+        task_server
+            .listen_for_websockets_on(("0.0.0.0", port), false)
+            .await
+            .unwrap();
+    });
+    Ok(server)
+}
+
+/// Accepts connections on `listener`, passing each one to `handshake` before
+/// relaying traffic to the address it returns. Returns a counter of the
+/// tunnels established.
+fn spawn_proxy<F, Fut>(listener: TcpListener, handshake: F) -> Arc<AtomicUsize>
+where
+    F: Fn(TcpStream) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = anyhow::Result<(TcpStream, String)>> + Send + 'static,
+{
+    let tunnels = Arc::new(AtomicUsize::new(0));
+    let task_tunnels = tunnels.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let negotiation = handshake(stream);
+            let tunnels = task_tunnels.clone();
+            tokio::spawn(async move {
+                let Ok((mut client, target)) = negotiation.await else {
+                    return;
+                };
+                let mut server = TcpStream::connect(target).await.unwrap();
+                tunnels.fetch_add(1, Ordering::SeqCst);
+                drop(tokio::io::copy_bidirectional(&mut client, &mut server).await);
+            });
+        }
+    });
+    tunnels
+}
+
+async fn http_connect(mut stream: TcpStream) -> anyhow::Result<(TcpStream, String)> {
+    let mut request = Vec::new();
+    while !request.ends_with(b"\r\n\r\n") {
+        request.push(stream.read_u8().await?);
+    }
+    let request = String::from_utf8(request)?;
+    let mut lines = request.lines();
+    let target = lines
+        .next()
+        .and_then(|line| line.strip_prefix("CONNECT "))
+        .and_then(|line| line.strip_suffix(" HTTP/1.1"))
+        .expect("invalid CONNECT request")
+        .to_string();
+    // "user:secret"
+    if lines.any(|line| line == "Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=") {
+        stream
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await?;
+    } else {
+        stream
+            .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+            .await?;
+        anyhow::bail!("missing credentials");
+    }
+    Ok((stream, target))
+}
+
+async fn socks5_connect(mut stream: TcpStream) -> anyhow::Result<(TcpStream, String)> {
+    let mut greeting = [0; 2];
+    stream.read_exact(&mut greeting).await?;
+    assert_eq!(greeting[0], 5);
+    let mut methods = vec![0; usize::from(greeting[1])];
+    stream.read_exact(&mut methods).await?;
+    assert!(methods.contains(&0));
+    stream.write_all(&[5, 0]).await?;
+
+    let mut request = [0; 4];
+    stream.read_exact(&mut request).await?;
+    assert_eq!(request[..3], [5, 1, 0]);
+    // The client sends host names to the proxy rather than resolving them.
+    assert_eq!(request[3], 3);
+    let mut host = vec![0; usize::from(stream.read_u8().await?)];
+    stream.read_exact(&mut host).await?;
+    let port = stream.read_u16().await?;
+    stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+
+    Ok((stream, format!("{}:{port}", String::from_utf8(host)?)))
+}
+
+#[tokio::test]
+async fn http_proxy() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("proxy-http.bonsaidb");
+    let _server = open_server(&dir, 12356).await?;
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let proxy_address = listener.local_addr()?;
+    let tunnels = spawn_proxy(listener, http_connect);
+
+    let client = AsyncClient::build(Url::parse("ws://localhost:12356")?)
+        .with_proxy(
+            ProxyConfig::http(proxy_address.to_string())
+                .with_credentials("user", SensitiveString(String::from("secret"))),
+        )
+        .build()?;
+    let db = client.database::<Basic>("tests").await?;
+    let doc = Basic::new("through a proxy").push_into_async(&db).await?;
+    assert!(Basic::get_async(&doc.header.id, &db).await?.is_some());
+    assert_eq!(tunnels.load(Ordering::SeqCst), 1);
+
+    // The proxy rejects connections without credentials.
+    let client = AsyncClient::build(Url::parse("ws://localhost:12356")?)
+        .with_proxy(ProxyConfig::http(proxy_address.to_string()))
+        .build()?;
+    let err = client.list_databases().await.unwrap_err();
+    assert!(err.to_string().contains("407"), "unexpected error: {err}");
+    assert_eq!(tunnels.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn socks5_proxy() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("proxy-socks5.bonsaidb");
+    let _server = open_server(&dir, 12357).await?;
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let proxy_address = listener.local_addr()?;
+    let tunnels = spawn_proxy(listener, socks5_connect);
+
+    let client = AsyncClient::build(Url::parse("ws://localhost:12357")?)
+        .with_proxy(ProxyConfig::socks5(proxy_address.to_string()))
+        .build()?;
+    let db = client.database::<Basic>("tests").await?;
+    let doc = Basic::new("through a proxy").push_into_async(&db).await?;
+    assert!(Basic::get_async(&doc.header.id, &db).await?.is_some());
+    assert_eq!(tunnels.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Tests that `PubSub` messages published through a server never reach
+//! subscribers of another database.
+
+use std::time::Duration;
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::AsyncClient;
+use bonsaidb::core::connection::AsyncStorageConnection;
+use bonsaidb::core::pubsub::{database_topic, AsyncPubSub, AsyncSubscriber, Message};
+use bonsaidb::core::test_util::{Basic, TestDirectory};
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+
+const TOPIC: &[u8] = b"events";
+
+async fn next_payload<S: AsyncSubscriber>(subscriber: &S) -> anyhow::Result<Vec<u8>> {
+    let message: Message = tokio::time::timeout(
+        Duration::from_secs(5),
+        subscriber.receiver().receive_async(),
+    )
+    .await??;
+    assert_eq!(&message.topic[..], TOPIC);
+    Ok(message.payload.to_vec())
+}
+
+#[tokio::test]
+async fn topics_are_isolated_by_database() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("pubsub-isolation.bonsaidb");
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    server.create_database::<Basic>("a", false).await?;
+    server.create_database::<Basic>("b", false).await?;
+
+    let listening = server.clone();
+    tokio::spawn(async move { listening.listen_on(12358).await });
+
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:12358")?)
+        .with_certificate(certificate)
+        .build()?;
+    let a = client.database::<Basic>("a").await?;
+    let b = client.database::<Basic>("b").await?;
+    let a_subscriber = a.create_subscriber().await?;
+    a_subscriber.subscribe_to_bytes(TOPIC.to_vec()).await?;
+    let b_subscriber = b.create_subscriber().await?;
+    b_subscriber.subscribe_to_bytes(TOPIC.to_vec()).await?;
+    // A topic naming the other database is scoped to the subscriber's
+    // database like any other topic.
+    a_subscriber
+        .subscribe_to_bytes(database_topic("b", TOPIC))
+        .await?;
+
+    // Each subscriber receives its messages in order, so a leaked message
+    // would be received before the subscriber's next expected message.
+    a.publish_bytes(TOPIC.to_vec(), b"from a".to_vec()).await?;
+    b.publish_bytes(TOPIC.to_vec(), b"from b".to_vec()).await?;
+    assert_eq!(next_payload(&a_subscriber).await?, b"from a");
+    assert_eq!(next_payload(&b_subscriber).await?, b"from b");
+    a.publish_bytes(TOPIC.to_vec(), b"again from a".to_vec())
+        .await?;
+    assert_eq!(next_payload(&a_subscriber).await?, b"again from a");
+
+    // Messages published on the server are isolated the same way.
+    let server_b = server.database::<Basic>("b").await?;
+    server_b
+        .publish_bytes(TOPIC.to_vec(), b"from the server".to_vec())
+        .await?;
+    assert_eq!(next_payload(&b_subscriber).await?, b"from the server");
+    a.publish_bytes(TOPIC.to_vec(), b"last from a".to_vec())
+        .await?;
+    assert_eq!(next_payload(&a_subscriber).await?, b"last from a");
+    assert!(a_subscriber.receiver().try_receive().is_err());
+    assert!(b_subscriber.receiver().try_receive().is_err());
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Tests replicating databases from a primary server to a follower storage.
+
+use std::time::{Duration, Instant};
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::AsyncClient;
+use bonsaidb::core::connection::{AsyncConnection, AsyncStorageConnection};
+use bonsaidb::core::keyvalue::AsyncKeyValue;
+use bonsaidb::core::schema::SerializedCollection;
+use bonsaidb::core::test_util::{Basic, TestDirectory};
+use bonsaidb::local::config::{Builder, StorageConfiguration};
+use bonsaidb::local::{AsyncStorage, Follower};
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+
+#[tokio::test]
+async fn follower_replicates_until_promoted() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("replication-primary.bonsaidb");
+    let follower_dir = TestDirectory::new("replication-follower.bonsaidb");
+
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .replication_log(true)
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    server.create_database::<Basic>("tests", false).await?;
+    let primary = server.database::<Basic>("tests").await?;
+    let seeded = Basic::new("seeded").push_into_async(&primary).await?;
+    primary.set_key("seeded", &String::from("key")).await?;
+    tokio::spawn(async move { server.listen_on(12349).await });
+
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:12349")?)
+        .with_certificate(certificate)
+        .build()?;
+    let storage =
+        AsyncStorage::open(StorageConfiguration::new(&follower_dir).with_schema::<Basic>()?)
+            .await?;
+    let follower = Follower::new(storage, client)?.with_poll_interval(Duration::from_millis(10));
+    follower.replicate::<Basic>("tests").await?;
+
+    // The initial copy is complete once replication has started.
+    let replica = follower.storage().database::<Basic>("tests").await?;
+    assert_eq!(Basic::all_async(&replica).await?.len(), 1);
+    assert_eq!(
+        replica.get_key("seeded").into::<String>().await?.as_deref(),
+        Some("key")
+    );
+
+    // The follower can't be modified while replicating.
+    let error = Basic::new("local")
+        .push_into_async(&replica)
+        .await
+        .unwrap_err()
+        .error;
+    assert!(matches!(error, bonsaidb::core::Error::ReadOnly));
+    assert!(matches!(
+        replica.set_key("local", &1_u32).await,
+        Err(bonsaidb::core::Error::ReadOnly)
+    ));
+
+    // Documents and keys changed on the primary are applied to the follower.
+    Basic::new("replicated").push_into_async(&primary).await?;
+    seeded.delete_async(&primary).await?;
+    primary.set_key("replicated", &2_u32).await?;
+    primary.delete_key("seeded").await?;
+    let expires_at = Instant::now() + Duration::from_secs(2);
+    primary
+        .set_key("expiring", &3_u32)
+        .expire_in(Duration::from_secs(2))
+        .await?;
+    let last_transaction_id = primary.last_transaction_id().await?;
+
+    let started = Instant::now();
+    loop {
+        let status = follower.status();
+        assert_eq!(status.len(), 1);
+        if status[0].applied_transaction_id >= last_transaction_id {
+            assert_eq!(status[0].lag(), 0);
+            break;
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "follower never caught up: {status:?}"
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let documents = Basic::all_async(&replica).await?;
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].contents.value, "replicated");
+    assert_eq!(replica.get_key("replicated").into_u64().await?, Some(2));
+    assert!(replica.get_key("seeded").await?.is_none());
+
+    // Expirations are replicated along with the values.
+    assert_eq!(replica.get_key("expiring").into_u64().await?, Some(3));
+    tokio::time::sleep_until((expires_at + Duration::from_millis(100)).into()).await;
+    assert!(replica.get_key("expiring").await?.is_none());
+
+    // Once promoted, the follower accepts writes.
+    let promoted = follower.promote().await?;
+    let promoted = promoted.database::<Basic>("tests").await?;
+    Basic::new("promoted").push_into_async(&promoted).await?;
+    assert_eq!(Basic::all_async(&promoted).await?.len(), 2);
+
+    Ok(())
+}
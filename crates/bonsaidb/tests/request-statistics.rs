@@ -0,0 +1,59 @@
+//! Tests the per-request timing statistics tracked by the server.
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::AsyncClient;
+use bonsaidb::core::api::Api;
+use bonsaidb::core::connection::{AsyncConnection, AsyncStorageConnection};
+use bonsaidb::core::networking::{CreateDatabase, LastTransactionId, ListDatabases};
+use bonsaidb::core::test_util::{Basic, TestDirectory};
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+
+#[tokio::test]
+async fn request_counts_increase() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("request-statistics.bonsaidb");
+    let server = Server::open(
+        ServerConfiguration::new(&dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Basic>()?,
+    )
+    .await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+
+    let statistics = server.request_statistics();
+    assert!(statistics.contains_key(&ListDatabases::name()));
+    assert!(statistics.values().all(|timings| timings.count == 0));
+    assert!(statistics[&ListDatabases::name()].mean().is_none());
+
+    let listening = server.clone();
+    tokio::spawn(async move { listening.listen_on(12353).await });
+
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:12353")?)
+        .with_certificate(certificate)
+        .build()?;
+    client.list_databases().await?;
+    client.list_databases().await?;
+    client.create_database::<Basic>("tests", false).await?;
+    let db = client.database::<Basic>("tests").await?;
+    db.last_transaction_id().await?;
+
+    let list = server.request_timings::<ListDatabases>().unwrap();
+    assert_eq!(list.count, 2);
+    assert!(list.max <= list.total);
+    assert!(list.mean().is_some());
+    assert_eq!(list.buckets().map(|(_, count)| count).sum::<u64>(), 2);
+    assert!(list.percentile(1.) >= Some(list.max));
+
+    assert_eq!(server.request_timings::<CreateDatabase>().unwrap().count, 1);
+    // Requests for a specific database are recorded under the wrapped request.
+    assert_eq!(
+        server.request_timings::<LastTransactionId>().unwrap().count,
+        1
+    );
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Checks that sensitive values don't remain on the heap after being dropped.
+//!
+//! This is a best-effort check: the global allocator inspects each block as
+//! it is freed, which catches values that aren't zeroized before their memory
+//! is returned. It cannot detect copies made outside of these types, such as
+//! serialized buffers.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bonsaidb::core::connection::{SensitiveBytes, SensitiveString};
+
+const SECRET: &str = "correct horse battery staple";
+
+static INSPECTING: AtomicBool = AtomicBool::new(false);
+static SECRET_FREED: AtomicBool = AtomicBool::new(false);
+
+struct InspectingAllocator;
+
+unsafe impl GlobalAlloc for InspectingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if INSPECTING.load(Ordering::SeqCst) {
+            let freed = std::slice::from_raw_parts(ptr, layout.size());
+            if freed
+                .windows(SECRET.len())
+                .any(|window| window == SECRET.as_bytes())
+            {
+                SECRET_FREED.store(true, Ordering::SeqCst);
+            }
+        }
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: InspectingAllocator = InspectingAllocator;
+
+/// Runs `allocate_and_drop` and returns whether the secret was still present
+/// in any block freed while it ran.
+fn secret_freed(allocate_and_drop: impl FnOnce()) -> bool {
+    SECRET_FREED.store(false, Ordering::SeqCst);
+    INSPECTING.store(true, Ordering::SeqCst);
+    allocate_and_drop();
+    INSPECTING.store(false, Ordering::SeqCst);
+    SECRET_FREED.load(Ordering::SeqCst)
+}
+
+#[test]
+fn dropped_sensitive_values_are_zeroed() {
+    // Ensure the allocator detects a plain string being freed, otherwise the
+    // checks below would prove nothing.
+    assert!(secret_freed(|| drop(String::from(SECRET))));
+
+    assert!(!secret_freed(|| drop(SensitiveString::from(SECRET))));
+    assert!(!secret_freed(|| {
+        let password = SensitiveString::from(SECRET);
+        let copy = password.clone();
+        drop(password);
+        drop(copy);
+    }));
+    assert!(!secret_freed(|| drop(SensitiveBytes::from(
+        SECRET.as_bytes()
+    ))));
+}
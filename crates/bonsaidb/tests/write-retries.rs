@@ -0,0 +1,158 @@
+//! Tests retrying writes whose responses were lost.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bonsaidb::client::url::Url;
+use bonsaidb::client::AsyncClient;
+use bonsaidb::core::connection::AsyncStorageConnection;
+use bonsaidb::core::document::{CollectionDocument, Emit};
+use bonsaidb::core::schema::{
+    Collection, CollectionMapReduce, SerializedCollection, View, ViewMapResult, ViewSchema,
+};
+use bonsaidb::core::test_util::TestDirectory;
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::{DefaultPermissions, Server, ServerConfiguration};
+use serde::{Deserialize, Serialize};
+
+/// The values of documents whose transactions are delayed from completing
+/// long enough for the client to lose the response. Each value is only
+/// delayed once.
+static DELAYED_VALUES: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+fn delay_write_of(value: u32) {
+    DELAYED_VALUES.lock().unwrap().push(value);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Collection)]
+#[collection(name = "writes", views = [WritesByValue])]
+struct Write {
+    value: u32,
+}
+
+#[derive(Debug, Clone, View, ViewSchema)]
+#[view(collection = Write, key = u32, value = (), name = "by-value")]
+#[view_schema(policy = Eager)]
+struct WritesByValue;
+
+impl CollectionMapReduce for WritesByValue {
+    fn map<'doc>(&self, document: CollectionDocument<Write>) -> ViewMapResult<'doc, Self::View> {
+        let delayed = {
+            let mut delayed = DELAYED_VALUES.lock().unwrap();
+            let index = delayed
+                .iter()
+                .position(|value| *value == document.contents.value);
+            index.map(|index| delayed.remove(index)).is_some()
+        };
+        if delayed {
+            std::thread::sleep(Duration::from_millis(1500));
+        }
+        document.header.emit_key(document.contents.value)
+    }
+}
+
+async fn open_server(dir: &TestDirectory) -> anyhow::Result<Server> {
+    let server = Server::open(
+        ServerConfiguration::new(dir)
+            .default_permissions(DefaultPermissions::AllowAll)
+            .with_schema::<Write>()?,
+    )
+    .await?;
+    server.create_database::<Write>("tests", false).await?;
+    Ok(server)
+}
+
+#[tokio::test]
+async fn retried_writes_are_not_duplicated() -> anyhow::Result<()> {
+    let dir = TestDirectory::new("write-retries.bonsaidb");
+    let server = open_server(&dir).await?;
+    server.install_self_signed_certificate(false).await?;
+    let certificate = server
+        .certificate_chain()
+        .await?
+        .into_end_entity_certificate();
+    tokio::spawn(async move { server.listen_on(12354).await });
+
+    let client = AsyncClient::build(Url::parse("bonsaidb://localhost:12354")?)
+        .with_certificate(certificate)
+        .with_request_timeout(Duration::from_millis(250))
+        .with_write_retries(10)
+        .build()?;
+    let db = client.database::<Write>("tests").await?;
+
+    // The first attempt commits after the client has given up waiting for its
+    // response. Each retry is answered with the original result once it is
+    // available, instead of inserting the document again.
+    delay_write_of(1);
+    let inserted = Write { value: 1 }.push_into_async(&db).await?;
+    let documents = Write::all_async(&db).await?;
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].header, inserted.header);
+
+    Ok(())
+}
+
+/// Forwards each connection accepted by `listener` to `target`, until
+/// `disconnect` is notified. Connections accepted afterwards are forwarded
+/// normally.
+#[cfg(feature = "websockets")]
+async fn proxy(
+    listener: tokio::net::TcpListener,
+    target: &'static str,
+    disconnect: std::sync::Arc<tokio::sync::Notify>,
+) -> anyhow::Result<()> {
+    loop {
+        let (mut incoming, _) = listener.accept().await?;
+        let disconnect = disconnect.clone();
+        tokio::spawn(async move {
+            let mut outgoing = tokio::net::TcpStream::connect(target).await?;
+            tokio::select! {
+                _ = tokio::io::copy_bidirectional(&mut incoming, &mut outgoing) => {}
+                () = disconnect.notified() => {}
+            }
+            anyhow::Ok(())
+        });
+    }
+}
+
+#[cfg(feature = "websockets")]
+#[tokio::test]
+async fn writes_are_retried_after_disconnecting() -> anyhow::Result<()> {
+    use std::sync::Arc;
+
+    use tokio::net::TcpListener;
+    use tokio::sync::Notify;
+
+    let dir = TestDirectory::new("write-retries-disconnect.bonsaidb");
+    let server = open_server(&dir).await?;
+    tokio::spawn(async move {
+        server
+            .listen_for_websockets_on("localhost:12364", false)
+            .await
+    });
+    let disconnect = Arc::new(Notify::new());
+    let listener = TcpListener::bind("localhost:12365").await?;
+    tokio::spawn(proxy(listener, "localhost:12364", disconnect.clone()));
+
+    let client = AsyncClient::build(Url::parse("ws://localhost:12365")?)
+        .with_write_retries(10)
+        .build()?;
+    let db = client.database::<Write>("tests").await?;
+
+    // Drop the connection while the write is executing, before its response
+    // is sent. The retries are sent over a new connection, and receive the
+    // original result once it is available.
+    delay_write_of(2);
+    let insert = tokio::spawn({
+        let db = db.clone();
+        async move { Write { value: 2 }.push_into_async(&db).await }
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    disconnect.notify_waiters();
+    let inserted = insert.await??;
+    let documents = Write::all_async(&db).await?;
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].header, inserted.header);
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! Shows how to invoke a custom api on a server embedded in the same process,
+//! without connecting to it over the network.
+//!
+//! The handlers are identical to the ones in the `custom-api` example, which
+//! demonstrates calling them from a networked client.
+
+use bonsaidb::core::actionable::Permissions;
+use bonsaidb::core::api::Api;
+use bonsaidb::core::async_trait::async_trait;
+use bonsaidb::core::connection::{
+    AsyncStorageConnection, Authentication, AuthenticationMethod, HasSession, SensitiveString,
+};
+use bonsaidb::core::keyvalue::AsyncKeyValue;
+use bonsaidb::core::permissions::bonsai::{BonsaiAction, ServerAction};
+use bonsaidb::core::permissions::{Action, Identifier, Statement};
+use bonsaidb::local::config::Builder;
+use bonsaidb::server::api::{Handler, HandlerError, HandlerResult, HandlerSession};
+use bonsaidb::server::{Server, ServerConfiguration};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Api)]
+#[api(name = "ping", response = Pong)]
+pub struct Ping;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pong;
+
+#[derive(Serialize, Deserialize, Debug, Api)]
+#[api(name = "increment", response = Counter)]
+pub struct IncrementCounter {
+    amount: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Counter(pub u64);
+
+#[derive(Debug)]
+pub struct ExampleHandler;
+
+#[async_trait]
+impl Handler<Ping> for ExampleHandler {
+    async fn handle(_session: HandlerSession<'_>, _request: Ping) -> HandlerResult<Ping> {
+        Ok(Pong)
+    }
+}
+
+#[derive(Debug, Action)]
+#[action(actionable = bonsaidb::core::actionable)]
+pub enum ExampleActions {
+    Increment,
+}
+
+#[async_trait]
+impl Handler<IncrementCounter> for ExampleHandler {
+    async fn handle(
+        session: HandlerSession<'_>,
+        request: IncrementCounter,
+    ) -> HandlerResult<IncrementCounter> {
+        session
+            .as_client
+            .check_permission([Identifier::from("increment")], &ExampleActions::Increment)?;
+        let database = session.server.database::<()>("counter").await?;
+        Ok(Counter(
+            database.increment_key_by("counter", request.amount).await?,
+        ))
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    // The server is configured exactly as it would be if it were listening
+    // for network connections.
+    let server = Server::open(
+        ServerConfiguration::new("local-custom-api.bonsaidb")
+            .default_permissions(Permissions::from(
+                Statement::for_any()
+                    .allowing(&BonsaiAction::Server(ServerAction::Connect))
+                    .allowing(&BonsaiAction::Server(ServerAction::Authenticate(
+                        AuthenticationMethod::PasswordHash,
+                    ))),
+            ))
+            .authenticated_permissions(Permissions::from(vec![
+                Statement::for_any().allowing(&ExampleActions::Increment)
+            ]))
+            .with_api::<ExampleHandler, Ping>()?
+            .with_api::<ExampleHandler, IncrementCounter>()?
+            .with_schema::<()>()?,
+    )
+    .await?;
+
+    server.create_database::<()>("counter", true).await?;
+    match server.create_user("test-user").await {
+        Ok(_) | Err(bonsaidb::core::Error::UniqueKeyViolation { .. }) => {}
+        Err(other) => anyhow::bail!(other),
+    }
+    server
+        .set_user_password("test-user", SensitiveString::from("hunter2"))
+        .await?;
+
+    // Requests sent through `custom_api()` are checked against the server's
+    // default permissions, just like an unauthenticated network client.
+    let ping = server.custom_api::<Ping>().await?;
+    let Pong = ping.send(&Ping).await?;
+    println!("Received Pong from the local server");
+
+    let increment = server.custom_api::<IncrementCounter>().await?;
+    assert!(matches!(
+        increment.send(&IncrementCounter { amount: 1 }).await,
+        Err(HandlerError::Server(bonsaidb::server::Error::Core(
+            bonsaidb::core::Error::PermissionDenied(_)
+        )))
+    ));
+
+    // An authenticated server instance sends requests using its session.
+    let authenticated = server
+        .authenticate(Authentication::password(
+            "test-user",
+            SensitiveString(String::from("hunter2")),
+        )?)
+        .await?;
+    let increment = authenticated.custom_api::<IncrementCounter>().await?;
+    let Counter(count) = increment.send(&IncrementCounter { amount: 1 }).await?;
+    println!("Incremented the counter to {count}");
+
+    Ok(())
+}
+
+#[test]
+fn runs() {
+    main().unwrap()
+}